@@ -1,23 +1,28 @@
-use std::{io, sync::Arc};
+use std::{future, io, net::SocketAddr, sync::Arc, time::Duration};
 
 use dashmap::DashMap;
+use futures::{StreamExt, stream::FuturesUnordered};
 use handy::UdpSocketController;
 use qbase::{
     net::address::{AddrKind, BindAddr, IpFamily},
-    param::RememberedParameters,
+    param::VersionInformation,
 };
 use qconnection::builder::*;
 use qevent::telemetry::{Log, handy::NoopLogger};
 use rustls::{
     ClientConfig as TlsClientConfig, ConfigBuilder, WantsVerifier,
-    client::{ResolvesClientCert, WantsClientCert},
+    client::{ClientSessionStore, ResolvesClientCert, Resumption, WantsClientCert},
 };
 use tokio::sync::mpsc;
 
-use crate::*;
+use crate::{resolver::interleave_families, *};
 
 type TlsClientConfigBuilder<T> = ConfigBuilder<TlsClientConfig, T>;
 
+/// The delay between the starts of two connection attempts of the Happy Eyeballs algorithm,
+/// see [`QuicClientBuilder::enable_happy_eyeballs`].
+pub const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
 /// A QUIC client for initiating connections to servers.
 ///
 /// ## Creating Clients
@@ -36,19 +41,24 @@ type TlsClientConfigBuilder<T> = ConfigBuilder<TlsClientConfig, T>;
 /// Call [`QuicClient::connect`] to establish connections. The client supports:
 /// - **Connection reuse**: Enable with [`QuicClientBuilder::reuse_connection`] to reuse existing connections
 /// - **Automatic interface selection**: Matches interface with server endpoint address
+///
+/// Call [`QuicClient::connect_by_name`] to resolve the server name and connect to the resolved
+/// addresses, enable [`QuicClientBuilder::enable_happy_eyeballs`] to race the address families.
 pub struct QuicClient {
+    endpoint: Arc<Endpoint>,
     bind_interfaces: Option<DashMap<BindAddr, Arc<dyn QuicInterface>>>,
     defer_idle_timeout: HeartbeatConfig,
-    // TODO: 好像得创建2个quic连接，一个用ipv4，一个用ipv6
-    //       然后看谁先收到服务器的响应比较好
-    _enable_happy_eyepballs: bool,
+    congestion_controller: Arc<dyn ProductCongestionController>,
+    key_update: KeyUpdatePolicy,
+    recv_window_limits: RecvWindowLimits,
+    path_scheduler: Arc<dyn ProductPathScheduler>,
+    enable_happy_eyeballs: bool,
+    resolver: Arc<dyn Resolve>,
     parameters: ClientParameters,
-    _prefer_versions: Vec<u32>,
     quic_iface_factory: Box<dyn ProductQuicInterface>,
-    // TODO: 要改成一个加载上次连接的parameters的函数，根据server name
-    _remembered: Option<RememberedParameters>,
     reuse_connection: bool,
     reuse_address: bool,
+    migrate_to_preferred_address: bool,
     stream_strategy_factory: Box<dyn ProductStreamsConcurrencyController>,
     logger: Arc<dyn Log + Send + Sync>,
     tls_config: Arc<TlsClientConfig>,
@@ -56,16 +66,9 @@ pub struct QuicClient {
 }
 
 impl QuicClient {
-    fn reuseable_connections() -> &'static DashMap<String, Arc<Connection>> {
-        static REUSEABLE_CONNECTIONS: OnceLock<DashMap<String, Arc<Connection>>> = OnceLock::new();
-        REUSEABLE_CONNECTIONS.get_or_init(Default::default)
-    }
-
-    /// Create a new [`QuicClient`] builder.
+    /// Create a new [`QuicClient`] builder on the [`Endpoint::global`].
     pub fn builder() -> QuicClientBuilder<TlsClientConfigBuilder<WantsVerifier>> {
-        Self::builder_with_tls(TlsClientConfig::builder_with_protocol_versions(&[
-            &rustls::version::TLS13,
-        ]))
+        Endpoint::global().client_builder()
     }
 
     /// Create a [`QuicClient`] builder with custom crypto provider.
@@ -83,20 +86,7 @@ impl QuicClient {
     ///
     /// This is useful when you want to customize the TLS configuration, or integrate qm-quic with other crates.
     pub fn builder_with_tls<T>(tls_config: T) -> QuicClientBuilder<T> {
-        QuicClientBuilder {
-            bind_interfaces: DashMap::new(),
-            reuse_address: false,
-            reuse_connection: false,
-            enable_happy_eyepballs: false,
-            prefer_versions: vec![1],
-            defer_idle_timeout: HeartbeatConfig::default(),
-            quic_iface_factory: Box::new(UdpSocketController::bind),
-            parameters: ClientParameters::default(),
-            tls_config,
-            stream_strategy_factory: Box::new(ConsistentConcurrency::new),
-            logger: None,
-            token_sink: None,
-        }
+        Endpoint::global().client_builder_with_tls(tls_config)
     }
 
     fn new_connection(
@@ -116,7 +106,7 @@ impl QuicClient {
                     }
                 };
                 let quic_iface = self.quic_iface_factory.bind(bind_addr)?;
-                crate::proto().add_interface(quic_iface.clone());
+                self.endpoint.proto().add_interface(quic_iface.clone());
                 quic_iface
             }
             Some(bind_interfaces) => bind_interfaces
@@ -125,9 +115,10 @@ impl QuicClient {
                 .filter(|bind_addr| bind_addr.kind() == server_ep.kind())
                 .find_map(|bind_addr| {
                     if self.reuse_address {
-                        crate::proto().get_interface(bind_addr)
+                        self.endpoint.proto().get_interface(bind_addr)
                     } else {
-                        crate::proto()
+                        self.endpoint
+                            .proto()
                             .get_interface_if(bind_addr, |iface, _| Arc::strong_count(iface) == 1)
                     }
                 })
@@ -152,11 +143,16 @@ impl QuicClient {
         let origin_dcid = ConnectionId::random_gen(8);
         let connection = Arc::new(
             Connection::with_token_sink(server_name.clone(), token_sink)
-                .with_parameters(self.parameters.clone(), None)
+                .with_parameters(self.parameters.clone())
                 .with_tls_config(self.tls_config.clone())
                 .with_streams_concurrency_strategy(self.stream_strategy_factory.as_ref())
-                .with_proto(crate::proto().clone())
+                .with_proto(self.endpoint.proto().clone())
+                .with_stateless_reset_key(self.endpoint.stateless_reset_key())
                 .defer_idle_timeout(self.defer_idle_timeout)
+                .with_congestion_controller(self.congestion_controller.clone())
+                .with_key_update_policy(self.key_update)
+                .with_recv_window_limits(self.recv_window_limits)
+                .with_path_scheduler(self.path_scheduler.clone())
                 .with_cids(origin_dcid)
                 .with_qlog(self.logger.as_ref())
                 .run_with(event_broker),
@@ -164,32 +160,67 @@ impl QuicClient {
 
         tokio::spawn({
             let connection = connection.clone();
+            let endpoint = self.endpoint.clone();
+            let migrate_to_preferred_address = self.migrate_to_preferred_address;
             async move {
                 while let Some(event) = events.recv().await {
                     match event {
+                        Event::Handshaked if migrate_to_preferred_address => {
+                            let connection = connection.clone();
+                            tokio::spawn(async move {
+                                if let Err(error) = connection.migrate_to_preferred_address().await
+                                {
+                                    tracing::warn!(
+                                        ?error,
+                                        "failed to migrate to the preferred address"
+                                    );
+                                }
+                            });
+                        }
                         Event::Handshaked => {}
                         Event::ProbedNewPath(_, _) => {}
                         Event::PathInactivated(bind_addr, ..) => {
-                            crate::proto().try_free_interface(bind_addr);
+                            endpoint.proto().try_free_interface(bind_addr);
                         }
                         Event::ApplicationClose => {
-                            Self::reuseable_connections().remove_if(&server_name, |_, exist| {
-                                Arc::ptr_eq(&connection, exist)
-                            });
+                            endpoint
+                                .reuseable_connections()
+                                .remove_if(&server_name, |_, exist| {
+                                    Arc::ptr_eq(&connection, exist)
+                                });
                         }
                         Event::Failed(error) => {
-                            Self::reuseable_connections().remove_if(&server_name, |_, exist| {
-                                Arc::ptr_eq(&connection, exist)
-                            });
+                            endpoint
+                                .reuseable_connections()
+                                .remove_if(&server_name, |_, exist| {
+                                    Arc::ptr_eq(&connection, exist)
+                                });
                             connection.enter_closing(qbase::error::Error::from(error).into())
                         }
                         Event::Closed(ccf) => {
-                            Self::reuseable_connections().remove_if(&server_name, |_, exist| {
-                                Arc::ptr_eq(&connection, exist)
-                            });
+                            endpoint
+                                .reuseable_connections()
+                                .remove_if(&server_name, |_, exist| {
+                                    Arc::ptr_eq(&connection, exist)
+                                });
                             connection.enter_draining(ccf)
                         }
-                        Event::StatelessReset => { /* TOOD: stateless reset */ }
+                        // The connection has been terminated by itself
+                        Event::StatelessReset => {
+                            endpoint
+                                .reuseable_connections()
+                                .remove_if(&server_name, |_, exist| {
+                                    Arc::ptr_eq(&connection, exist)
+                                });
+                        }
+                        Event::VersionMismatch(versions) => {
+                            endpoint
+                                .reuseable_connections()
+                                .remove_if(&server_name, |_, exist| {
+                                    Arc::ptr_eq(&connection, exist)
+                                });
+                            connection.on_version_mismatch(versions)
+                        }
                         Event::Terminated => return,
                     }
                 }
@@ -238,7 +269,8 @@ impl QuicClient {
         let server_name = server_name.into();
         let server_ep = server_ep.to_endpoint_addr();
         if self.reuse_connection {
-            Self::reuseable_connections()
+            self.endpoint
+                .reuseable_connections()
                 .entry(server_name.clone())
                 .or_try_insert_with(|| self.new_connection(server_name, server_ep))
                 .map(|entry| entry.clone())
@@ -246,15 +278,101 @@ impl QuicClient {
             self.new_connection(server_name, server_ep)
         }
     }
+
+    /// Resolve `server_name` with the resolver of the client, and connect to the server at the
+    /// resolved addresses on `port`.
+    ///
+    /// `server_name` is also included in the `ClientHello` message.
+    ///
+    /// Unlike [`QuicClient::connect`], the returned connection has completed the handshake, the
+    /// address of the server it connected to is returned along with it. The addresses are
+    /// attempted as described in [`QuicClientBuilder::enable_happy_eyeballs`], the connections
+    /// to the other addresses are closed. An error is returned if all the attempts failed.
+    ///
+    /// A new connection is always initiated, regardless of [`QuicClientBuilder::reuse_connection`].
+    pub async fn connect_by_name(
+        &self,
+        server_name: impl Into<String>,
+        port: u16,
+    ) -> io::Result<(Arc<Connection>, SocketAddr)> {
+        let server_name = server_name.into();
+        let addresses = self.resolver.lookup(&server_name, port).await?;
+        let (addresses, attempt_delay) = if self.enable_happy_eyeballs {
+            (
+                interleave_families(addresses),
+                Some(CONNECTION_ATTEMPT_DELAY),
+            )
+        } else {
+            (addresses, None)
+        };
+
+        let mut error = io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no address resolved for {server_name}"),
+        );
+        let mut addresses = addresses.into_iter();
+        let mut connections = Vec::new();
+        let mut attempts = FuturesUnordered::new();
+        let winner = loop {
+            if let Some(server_addr) = addresses.next() {
+                match self.new_connection(server_name.clone(), EndpointAddr::direct(server_addr)) {
+                    Ok(connection) => {
+                        connections.push(connection.clone());
+                        attempts.push(async move {
+                            let handshaked = connection.handshaked().await;
+                            (handshaked, connection, server_addr)
+                        });
+                    }
+                    Err(e) => {
+                        error = e;
+                        continue;
+                    }
+                }
+            } else if attempts.is_empty() {
+                break None;
+            }
+
+            // Start the next attempt once the delay elapsed, or an attempt failed
+            let next_attempt = async {
+                match attempt_delay {
+                    Some(delay) if addresses.len() > 0 => tokio::time::sleep(delay).await,
+                    _ => future::pending().await,
+                }
+            };
+            tokio::select! {
+                Some((handshaked, connection, server_addr)) = attempts.next() => {
+                    if handshaked {
+                        break Some((connection, server_addr));
+                    }
+                    error = io::Error::new(
+                        io::ErrorKind::ConnectionRefused,
+                        format!("failed to connect to {server_addr}"),
+                    );
+                }
+                _ = next_attempt => {}
+            }
+        };
+
+        for connection in &connections {
+            if !matches!(&winner, Some((winner, _)) if Arc::ptr_eq(winner, connection)) {
+                connection.close("lost the connection race", 0);
+            }
+        }
+        let (connection, server_addr) = winner.ok_or(error)?;
+        tracing::debug!(%server_name, %server_addr, "connected to the server");
+        Ok((connection, server_addr))
+    }
 }
 
 impl Drop for QuicClient {
     fn drop(&mut self) {
         if let Some(bind_interfaces) = self.bind_interfaces.take() {
             for (bind_addr, bind_iface) in bind_interfaces.into_read_only().iter() {
-                crate::proto().del_interface_if(bind_addr.clone(), |iface, _| {
-                    Arc::ptr_eq(bind_iface, iface) && Arc::strong_count(iface) == 2
-                });
+                self.endpoint
+                    .proto()
+                    .del_interface_if(bind_addr.clone(), |iface, _| {
+                        Arc::ptr_eq(bind_iface, iface) && Arc::strong_count(iface) == 2
+                    });
             }
         }
     }
@@ -262,13 +380,20 @@ impl Drop for QuicClient {
 
 /// A builder for [`QuicClient`].
 pub struct QuicClientBuilder<T> {
+    endpoint: Arc<Endpoint>,
     bind_interfaces: DashMap<BindAddr, Arc<dyn QuicInterface>>,
     reuse_address: bool,
     reuse_connection: bool,
-    enable_happy_eyepballs: bool,
+    migrate_to_preferred_address: bool,
+    enable_happy_eyeballs: bool,
+    resolver: Arc<dyn Resolve>,
     prefer_versions: Vec<u32>,
     quic_iface_factory: Box<dyn ProductQuicInterface>,
     defer_idle_timeout: HeartbeatConfig,
+    congestion_controller: Arc<dyn ProductCongestionController>,
+    key_update: KeyUpdatePolicy,
+    recv_window_limits: RecvWindowLimits,
+    path_scheduler: Arc<dyn ProductPathScheduler>,
     parameters: ClientParameters,
     tls_config: T,
     stream_strategy_factory: Box<dyn ProductStreamsConcurrencyController>,
@@ -277,6 +402,30 @@ pub struct QuicClientBuilder<T> {
 }
 
 impl<T> QuicClientBuilder<T> {
+    pub(crate) fn new(endpoint: Arc<Endpoint>, tls_config: T) -> Self {
+        Self {
+            endpoint,
+            bind_interfaces: DashMap::new(),
+            reuse_address: false,
+            reuse_connection: false,
+            migrate_to_preferred_address: true,
+            enable_happy_eyeballs: false,
+            resolver: Arc::new(SystemResolver),
+            prefer_versions: vec![1],
+            defer_idle_timeout: HeartbeatConfig::default(),
+            congestion_controller: Arc::new(Algorithm::default()),
+            key_update: KeyUpdatePolicy::default(),
+            recv_window_limits: RecvWindowLimits::default(),
+            path_scheduler: Arc::new(PathScheduling::default()),
+            quic_iface_factory: Box::new(UdpSocketController::bind),
+            parameters: ClientParameters::default(),
+            tls_config,
+            stream_strategy_factory: Box::new(ConsistentConcurrency::new),
+            logger: None,
+            token_sink: None,
+        }
+    }
+
     /// Specify how client bind interfaces.
     ///
     /// The given factory will be used by [`Self::bind`],
@@ -313,7 +462,9 @@ impl<T> QuicClientBuilder<T> {
     /// If all interfaces are closed, clients will no longer be able to initiate new connections.
     pub fn bind(self, addrs: impl IntoIterator<Item = impl Into<BindAddr>>) -> io::Result<Self> {
         for entry in self.bind_interfaces.iter() {
-            crate::proto().try_free_interface(entry.key().clone());
+            self.endpoint
+                .proto()
+                .try_free_interface(entry.key().clone());
         }
         self.bind_interfaces.clear();
 
@@ -349,9 +500,61 @@ impl<T> QuicClientBuilder<T> {
         self
     }
 
-    /// (WIP)Specify the quic versions that the client prefers.
+    /// Whether to migrate the connections to the [preferred address] of the servers.
+    ///
+    /// Once the handshake is completed, the client validates a path to the preferred address
+    /// provided by the server, of the same IP family as the server address it connected to, and
+    /// migrates the connection to it. The connection keeps using the original path if the
+    /// validation failed.
+    ///
+    /// Default: true.
+    ///
+    /// [preferred address]: https://www.rfc-editor.org/rfc/rfc9000.html#name-server-s-preferred-address
+    pub fn migrate_to_preferred_address(mut self, enable: bool) -> Self {
+        self.migrate_to_preferred_address = enable;
+        self
+    }
+
+    /// Enable the Happy Eyeballs algorithm for [`QuicClient::connect_by_name`].
+    ///
+    /// The connection attempts to the resolved addresses are raced, IPv6 first and alternating
+    /// between the address families, the next attempt starts if the previous ones are not
+    /// completed in [`CONNECTION_ATTEMPT_DELAY`], or failed. The first connection completing the
+    /// handshake wins, the others are closed.
+    ///
+    /// By default, the addresses are attempted one after another in the order of the resolver,
+    /// the next attempt starts only if the previous one failed.
+    ///
+    /// See [RFC8305](https://www.rfc-editor.org/rfc/rfc8305.html) for more information.
+    pub fn enable_happy_eyeballs(mut self) -> Self {
+        self.enable_happy_eyeballs = true;
+        self
+    }
+
+    /// Specify how the client resolves the names of the servers in [`QuicClient::connect_by_name`].
+    ///
+    /// Default: [`SystemResolver`].
+    pub fn with_resolver(mut self, resolver: Arc<dyn Resolve>) -> Self {
+        self.resolver = resolver;
+        self
+    }
+
+    /// Specify the quic versions that the client prefers, in the order of preference.
+    ///
+    /// The first version is used to initiate connections, and all the versions are sent in the
+    /// `version_information` transport parameter to prevent the version downgrade attacks, see
+    /// [RFC9368](https://www.rfc-editor.org/rfc/rfc9368.html).
+    ///
+    /// The versions not implemented by gm-quic are ignored, [`VERSION_1`] and [`VERSION_2`] are
+    /// implemented now. If the server responds with a [Version Negotiation packet], the client starts
+    /// a new connection attempt with the most preferred version supported by the server, or abandons
+    /// the connection attempt if there is no such version.
+    ///
+    /// Default: [`VERSION_1`] only.
     ///
     /// If you call this multiple times, only the last call will take effect.
+    ///
+    /// [Version Negotiation packet]: https://www.rfc-editor.org/rfc/rfc9000.html#name-version-negotiation
     pub fn prefer_versions(mut self, versions: impl IntoIterator<Item = u32>) -> Self {
         self.prefer_versions.clear();
         self.prefer_versions.extend(versions);
@@ -372,6 +575,90 @@ impl<T> QuicClientBuilder<T> {
         self
     }
 
+    /// Specify the congestion control algorithm for the connections.
+    ///
+    /// The algorithm is used by every path of every connection.
+    ///
+    /// If you call this multiple times, only the last `algorithm` will be used.
+    ///
+    /// Default: [`Algorithm::Cubic`]
+    pub fn with_congestion_algorithm(mut self, algorithm: Algorithm) -> Self {
+        self.congestion_controller = Arc::new(algorithm);
+        self
+    }
+
+    /// Specify a custom congestion controller for the connections.
+    ///
+    /// `controller_factory` is called to create a [`Control`] for every path of every connection,
+    /// with the read-only RTT estimation and PMTU of the path. It can be a closure that accepts
+    /// ([`ArcRtt`], [`Pmtu`]) and returns a [`Control`] object.
+    ///
+    /// This replaces the algorithm specified by [`Self::with_congestion_algorithm`], and vice versa.
+    ///
+    /// [`Control`]: crate::congestion::Control
+    /// [`ArcRtt`]: crate::congestion::ArcRtt
+    /// [`Pmtu`]: crate::congestion::Pmtu
+    pub fn with_congestion_controller(
+        mut self,
+        controller_factory: impl ProductCongestionController + 'static,
+    ) -> Self {
+        self.congestion_controller = Arc::new(controller_factory);
+        self
+    }
+
+    /// Specify when to initiate the 1-RTT key updates for the connections proactively.
+    ///
+    /// Updating the keys regularly limits the amount of data protected with the same keys, the
+    /// keys are always updated before reaching the confidentiality limit of the AEAD algorithm,
+    /// whatever the policy is. See [`KeyUpdatePolicy`] for more information.
+    ///
+    /// If you call this multiple times, only the last `policy` will be used.
+    ///
+    /// Default: only update the keys before reaching the confidentiality limit.
+    pub fn with_key_update_policy(mut self, policy: KeyUpdatePolicy) -> Self {
+        self.key_update = policy;
+        self
+    }
+
+    /// Specify how large the receive windows of the connections can grow to.
+    ///
+    /// The receive windows start from the `initial_max_data` and `initial_max_stream_data_*`
+    /// transport parameters, and are doubled when the application reads the data faster than the
+    /// windows are updated once per RTT, up to the `limits`. The data received but not read by the
+    /// application never exceeds the windows, so the `limits` also cap the memory used to buffer it.
+    ///
+    /// If you call this multiple times, only the last `limits` will be used.
+    ///
+    /// Default: 24 MiB for the connection, and 16 MiB for each stream.
+    pub fn with_recv_window_limits(mut self, limits: RecvWindowLimits) -> Self {
+        self.recv_window_limits = limits;
+        self
+    }
+
+    /// Specify which paths carry the data of the connections when multipath is enabled.
+    ///
+    /// Multipath is enabled if both endpoints send the `initial_max_path_id` transport
+    /// parameter, the built-in schedulers are listed in [`PathScheduling`].
+    ///
+    /// If you call this multiple times, only the last `scheduling` will be used.
+    ///
+    /// Default: [`PathScheduling::MinRtt`].
+    pub fn with_path_scheduling(mut self, scheduling: PathScheduling) -> Self {
+        self.path_scheduler = Arc::new(scheduling);
+        self
+    }
+
+    /// Specify a custom path scheduler for the connections, see [`Self::with_path_scheduling`].
+    ///
+    /// The `scheduler_factory` creates a [`SchedulePaths`] for each connection.
+    pub fn with_path_scheduler(
+        mut self,
+        scheduler_factory: impl ProductPathScheduler + 'static,
+    ) -> Self {
+        self.path_scheduler = Arc::new(scheduler_factory);
+        self
+    }
+
     /// Specify the [transport parameters] for the client.
     ///
     /// If you call this multiple times, only the last `parameters` will be used.
@@ -428,12 +715,19 @@ impl QuicClientBuilder<TlsClientConfigBuilder<WantsVerifier>> {
         root_store: impl Into<Arc<rustls::RootCertStore>>,
     ) -> QuicClientBuilder<TlsClientConfigBuilder<WantsClientCert>> {
         QuicClientBuilder {
+            endpoint: self.endpoint,
             bind_interfaces: self.bind_interfaces,
             reuse_address: self.reuse_address,
             reuse_connection: self.reuse_connection,
-            enable_happy_eyepballs: self.enable_happy_eyepballs,
+            migrate_to_preferred_address: self.migrate_to_preferred_address,
+            enable_happy_eyeballs: self.enable_happy_eyeballs,
+            resolver: self.resolver,
             prefer_versions: self.prefer_versions,
             defer_idle_timeout: self.defer_idle_timeout,
+            congestion_controller: self.congestion_controller,
+            key_update: self.key_update,
+            recv_window_limits: self.recv_window_limits,
+            path_scheduler: self.path_scheduler,
             quic_iface_factory: self.quic_iface_factory,
             parameters: self.parameters,
             tls_config: self.tls_config.with_root_certificates(root_store),
@@ -451,12 +745,19 @@ impl QuicClientBuilder<TlsClientConfigBuilder<WantsVerifier>> {
         verifier: Arc<rustls::client::WebPkiServerVerifier>,
     ) -> QuicClientBuilder<TlsClientConfigBuilder<WantsClientCert>> {
         QuicClientBuilder {
+            endpoint: self.endpoint,
             bind_interfaces: self.bind_interfaces,
             reuse_address: self.reuse_address,
             reuse_connection: self.reuse_connection,
-            enable_happy_eyepballs: self.enable_happy_eyepballs,
+            migrate_to_preferred_address: self.migrate_to_preferred_address,
+            enable_happy_eyeballs: self.enable_happy_eyeballs,
+            resolver: self.resolver,
             prefer_versions: self.prefer_versions,
             defer_idle_timeout: self.defer_idle_timeout,
+            congestion_controller: self.congestion_controller,
+            key_update: self.key_update,
+            recv_window_limits: self.recv_window_limits,
+            path_scheduler: self.path_scheduler,
             quic_iface_factory: self.quic_iface_factory,
             parameters: self.parameters,
             tls_config: self.tls_config.with_webpki_verifier(verifier),
@@ -522,12 +823,19 @@ impl QuicClientBuilder<TlsClientConfigBuilder<WantsVerifier>> {
             }
         }
         QuicClientBuilder {
+            endpoint: self.endpoint,
             bind_interfaces: self.bind_interfaces,
             reuse_address: self.reuse_address,
             reuse_connection: self.reuse_connection,
-            enable_happy_eyepballs: self.enable_happy_eyepballs,
+            migrate_to_preferred_address: self.migrate_to_preferred_address,
+            enable_happy_eyeballs: self.enable_happy_eyeballs,
+            resolver: self.resolver,
             prefer_versions: self.prefer_versions,
             defer_idle_timeout: self.defer_idle_timeout,
+            congestion_controller: self.congestion_controller,
+            key_update: self.key_update,
+            recv_window_limits: self.recv_window_limits,
+            path_scheduler: self.path_scheduler,
             quic_iface_factory: self.quic_iface_factory,
             parameters: self.parameters,
             tls_config: self
@@ -552,12 +860,19 @@ impl QuicClientBuilder<TlsClientConfigBuilder<WantsClientCert>> {
         key_der: impl ToPrivateKey,
     ) -> QuicClientBuilder<TlsClientConfig> {
         QuicClientBuilder {
+            endpoint: self.endpoint,
             bind_interfaces: self.bind_interfaces,
             reuse_address: self.reuse_address,
             reuse_connection: self.reuse_connection,
-            enable_happy_eyepballs: self.enable_happy_eyepballs,
+            migrate_to_preferred_address: self.migrate_to_preferred_address,
+            enable_happy_eyeballs: self.enable_happy_eyeballs,
+            resolver: self.resolver,
             prefer_versions: self.prefer_versions,
             defer_idle_timeout: self.defer_idle_timeout,
+            congestion_controller: self.congestion_controller,
+            key_update: self.key_update,
+            recv_window_limits: self.recv_window_limits,
+            path_scheduler: self.path_scheduler,
             quic_iface_factory: self.quic_iface_factory,
             parameters: self.parameters,
             tls_config: self
@@ -573,12 +888,19 @@ impl QuicClientBuilder<TlsClientConfigBuilder<WantsClientCert>> {
     /// Do not support client auth.
     pub fn without_cert(self) -> QuicClientBuilder<TlsClientConfig> {
         QuicClientBuilder {
+            endpoint: self.endpoint,
             bind_interfaces: self.bind_interfaces,
             reuse_address: self.reuse_address,
             reuse_connection: self.reuse_connection,
-            enable_happy_eyepballs: self.enable_happy_eyepballs,
+            migrate_to_preferred_address: self.migrate_to_preferred_address,
+            enable_happy_eyeballs: self.enable_happy_eyeballs,
+            resolver: self.resolver,
             prefer_versions: self.prefer_versions,
             defer_idle_timeout: self.defer_idle_timeout,
+            congestion_controller: self.congestion_controller,
+            key_update: self.key_update,
+            recv_window_limits: self.recv_window_limits,
+            path_scheduler: self.path_scheduler,
             quic_iface_factory: self.quic_iface_factory,
             parameters: self.parameters,
             tls_config: self.tls_config.with_no_client_auth(),
@@ -594,13 +916,20 @@ impl QuicClientBuilder<TlsClientConfigBuilder<WantsClientCert>> {
         cert_resolver: Arc<dyn ResolvesClientCert>,
     ) -> QuicClientBuilder<TlsClientConfig> {
         QuicClientBuilder {
+            endpoint: self.endpoint,
             bind_interfaces: self.bind_interfaces,
             reuse_address: self.reuse_address,
             reuse_connection: self.reuse_connection,
-            enable_happy_eyepballs: self.enable_happy_eyepballs,
+            migrate_to_preferred_address: self.migrate_to_preferred_address,
+            enable_happy_eyeballs: self.enable_happy_eyeballs,
+            resolver: self.resolver,
             prefer_versions: self.prefer_versions,
             quic_iface_factory: self.quic_iface_factory,
             defer_idle_timeout: self.defer_idle_timeout,
+            congestion_controller: self.congestion_controller,
+            key_update: self.key_update,
+            recv_window_limits: self.recv_window_limits,
+            path_scheduler: self.path_scheduler,
             parameters: self.parameters,
             tls_config: self.tls_config.with_client_cert_resolver(cert_resolver),
             stream_strategy_factory: self.stream_strategy_factory,
@@ -625,6 +954,27 @@ impl QuicClientBuilder<TlsClientConfig> {
         self
     }
 
+    /// Enable sending early data in 0-RTT packets when resuming sessions with the servers.
+    ///
+    /// The session tickets issued by the servers are kept in the `store` by server name, together
+    /// with the server transport parameters of the connections where they were issued. When the
+    /// client connects to a server again, the streams and datagrams can be sent before the
+    /// handshake completes, within the limits of the remembered transport parameters.
+    ///
+    /// Use [`rustls::client::ClientSessionMemoryCache`] to keep the sessions in memory, or
+    /// implement [`ClientSessionStore`] to persist them.
+    ///
+    /// Early data can be replayed by attackers, so the application should only send idempotent
+    /// requests in it. The server may reject the early data, check it with
+    /// [`Connection::early_data_accepted`].
+    ///
+    /// Default: disabled, and sessions are not resumed.
+    pub fn enable_0rtt(mut self, store: Arc<dyn ClientSessionStore>) -> Self {
+        self.tls_config.resumption = Resumption::store(store);
+        self.tls_config.enable_early_data = true;
+        self
+    }
+
     /// Enable the `keylog` feature.
     ///
     /// This is useful when you want to debug the TLS connection.
@@ -639,23 +989,32 @@ impl QuicClientBuilder<TlsClientConfig> {
 
     /// Build the QuicClient, ready to initiates connect to the servers.
     pub fn build(mut self) -> QuicClient {
-        self.tls_config.resumption = rustls::client::Resumption::disabled();
+        if !self.tls_config.enable_early_data {
+            self.tls_config.resumption = Resumption::disabled();
+        }
+        let prefer_versions = crate::implemented_versions(self.prefer_versions);
+        self.parameters
+            .set_version_information(VersionInformation::new(prefer_versions[0], prefer_versions));
         let bind_interfaces = if self.bind_interfaces.is_empty() {
             None
         } else {
             Some(self.bind_interfaces)
         };
         QuicClient {
+            endpoint: self.endpoint,
             bind_interfaces,
             reuse_address: self.reuse_address,
             reuse_connection: self.reuse_connection,
-            _enable_happy_eyepballs: self.enable_happy_eyepballs,
-            _prefer_versions: self.prefer_versions,
+            migrate_to_preferred_address: self.migrate_to_preferred_address,
+            enable_happy_eyeballs: self.enable_happy_eyeballs,
+            resolver: self.resolver,
             quic_iface_factory: self.quic_iface_factory,
             defer_idle_timeout: self.defer_idle_timeout,
+            congestion_controller: self.congestion_controller,
+            key_update: self.key_update,
+            recv_window_limits: self.recv_window_limits,
+            path_scheduler: self.path_scheduler,
             parameters: self.parameters,
-            // TODO: 要能加载上次连接的parameters
-            _remembered: None,
             tls_config: Arc::new(self.tls_config),
             stream_strategy_factory: self.stream_strategy_factory,
             logger: self.logger.unwrap_or_else(|| Arc::new(NoopLogger)),
@@ -0,0 +1,244 @@
+use std::{
+    io,
+    sync::{
+        Arc, OnceLock, RwLock, Weak,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use dashmap::DashMap;
+use qbase::{
+    packet::{DataHeader, Packet},
+    token::StatelessResetKey,
+};
+use rustls::{
+    ClientConfig as TlsClientConfig, ConfigBuilder, ServerConfig as TlsServerConfig, WantsVerifier,
+};
+use tokio::task::AbortHandle;
+
+use crate::*;
+
+/// An isolated QUIC endpoint, owning the [`QuicProto`] that routes the packets of its interfaces
+/// and connections, its [`QuicListeners`], and the connections reused by its [`QuicClient`]s.
+///
+/// The endpoints share nothing with each other, the servers and clients of different endpoints
+/// can run with different configurations in the same process, and be torn down independently.
+/// An interface should only be bound by the servers and clients of one endpoint.
+///
+/// [`QuicListeners::builder`] and [`QuicClient::builder`] build on the [`Endpoint::global`],
+/// use [`Endpoint::listeners_builder`] and [`Endpoint::client_builder`] to build on the others.
+///
+/// The endpoint is torn down after all its listeners, clients and connections are dropped.
+pub struct Endpoint {
+    proto: Arc<QuicProto>,
+    // Derives the stateless reset tokens of the connections of this endpoint, set by its listeners
+    reset_key: RwLock<Option<StatelessResetKey>>,
+    listeners: RwLock<Weak<QuicListeners>>,
+    // Whether a QuicListenersBuilder of this endpoint exists
+    building_listeners: AtomicBool,
+    reuseable_connections: DashMap<String, Arc<Connection>>,
+    task: AbortHandle,
+}
+
+impl Endpoint {
+    /// Create a new endpoint.
+    ///
+    /// Must be called in the context of a tokio runtime, the task handling the packets that are
+    /// not routed to any connection is spawned on it.
+    pub fn new() -> Arc<Self> {
+        Arc::new_cyclic(|endpoint: &Weak<Self>| {
+            let proto = Arc::new(QuicProto::new());
+            let handle_unrouted_packets = {
+                let (proto, endpoint) = (proto.clone(), endpoint.clone());
+                async move {
+                    while let Some((bind_addr, packet, pathway, link)) =
+                        proto.recv_unrouted_packet().await
+                    {
+                        match packet {
+                            // Short header packets can never establish a new connection, the
+                            // state of their connection may have been lost
+                            Packet::Data(packet)
+                                if matches!(packet.header, DataHeader::Short(..)) =>
+                            {
+                                let Some(reset_key) =
+                                    endpoint.upgrade().and_then(|ep| ep.stateless_reset_key())
+                                else {
+                                    continue;
+                                };
+                                proto
+                                    .send_stateless_reset(
+                                        bind_addr, &packet, pathway, link, &reset_key,
+                                    )
+                                    .await;
+                            }
+                            packet => {
+                                let Some(listeners) =
+                                    endpoint.upgrade().and_then(|ep| ep.running_listeners())
+                                else {
+                                    continue;
+                                };
+                                listeners
+                                    .try_accept_connection(bind_addr, packet, pathway, link)
+                                    .await;
+                            }
+                        }
+                    }
+                }
+            };
+            let handle_broken_interfaces = {
+                let (proto, endpoint) = (proto.clone(), endpoint.clone());
+                async move {
+                    while let Some((bind_addr, iface, error)) = proto.get_broken_interface().await {
+                        let Some(listeners) =
+                            endpoint.upgrade().and_then(|ep| ep.running_listeners())
+                        else {
+                            continue;
+                        };
+                        listeners.on_interface_broken(bind_addr, iface, error);
+                    }
+                }
+            };
+            let task = tokio::spawn(async move {
+                tokio::join!(handle_unrouted_packets, handle_broken_interfaces);
+            });
+            Self {
+                proto,
+                reset_key: RwLock::default(),
+                listeners: RwLock::default(),
+                building_listeners: AtomicBool::new(false),
+                reuseable_connections: DashMap::new(),
+                task: task.abort_handle(),
+            }
+        })
+    }
+
+    /// The endpoint used by [`QuicListeners::builder`] and [`QuicClient::builder`], which lives
+    /// until the process exits.
+    ///
+    /// It is created on the first call, in the context of the tokio runtime of the caller.
+    pub fn global() -> &'static Arc<Self> {
+        static GLOBAL: OnceLock<Arc<Endpoint>> = OnceLock::new();
+        GLOBAL.get_or_init(Self::new)
+    }
+
+    /// The [`QuicProto`] routing the packets of this endpoint.
+    pub fn proto(&self) -> &Arc<QuicProto> {
+        &self.proto
+    }
+
+    /// The running [`QuicListeners`] of this endpoint, if any.
+    pub fn listeners(&self) -> Option<Arc<QuicListeners>> {
+        self.listeners
+            .read()
+            .unwrap()
+            .upgrade()
+            .filter(|listeners| !listeners.is_shutdown())
+    }
+
+    /// Start to build the [`QuicListeners`] of this endpoint.
+    ///
+    /// Only one [`QuicListeners`] can run on an endpoint at a time, an error is returned if
+    /// the listeners of this endpoint are running or being built.
+    pub fn listeners_builder(
+        self: &Arc<Self>,
+    ) -> io::Result<QuicListenersBuilder<ConfigBuilder<TlsServerConfig, WantsVerifier>>> {
+        self.listeners_builder_with_tls(TlsServerConfig::builder_with_protocol_versions(&[
+            &rustls::version::TLS13,
+        ]))
+    }
+
+    /// Start to build the [`QuicListeners`] of this endpoint with the given TLS configuration.
+    ///
+    /// See [`Endpoint::listeners_builder`] for more information.
+    pub fn listeners_builder_with_tls<T>(
+        self: &Arc<Self>,
+        tls_config: T,
+    ) -> io::Result<QuicListenersBuilder<T>> {
+        let reservation = ListenersReservation::reserve(self)?;
+        Ok(QuicListenersBuilder::new(reservation, tls_config))
+    }
+
+    /// Start to build a [`QuicClient`] on this endpoint.
+    pub fn client_builder(
+        self: &Arc<Self>,
+    ) -> QuicClientBuilder<ConfigBuilder<TlsClientConfig, WantsVerifier>> {
+        self.client_builder_with_tls(TlsClientConfig::builder_with_protocol_versions(&[
+            &rustls::version::TLS13,
+        ]))
+    }
+
+    /// Start to build a [`QuicClient`] on this endpoint with the given TLS configuration.
+    pub fn client_builder_with_tls<T>(self: &Arc<Self>, tls_config: T) -> QuicClientBuilder<T> {
+        QuicClientBuilder::new(self.clone(), tls_config)
+    }
+
+    // The listeners are still responsible for the connections accepted after shutdown
+    fn running_listeners(&self) -> Option<Arc<QuicListeners>> {
+        self.listeners.read().unwrap().upgrade()
+    }
+
+    pub(crate) fn reuseable_connections(&self) -> &DashMap<String, Arc<Connection>> {
+        &self.reuseable_connections
+    }
+
+    /// The key to derive the stateless reset tokens of the connections of this endpoint, if any.
+    pub(crate) fn stateless_reset_key(&self) -> Option<StatelessResetKey> {
+        self.reset_key.read().unwrap().clone()
+    }
+
+    /// Set the key to derive the stateless reset tokens of the connections of this endpoint.
+    ///
+    /// Once the key is set, a stateless reset will be sent for each unrouted short header packet.
+    /// A static key allows sending stateless resets for connections established before restart.
+    ///
+    /// Only connections created after this call will use the key to issue reset tokens.
+    pub(crate) fn set_stateless_reset_key(&self, key: StatelessResetKey) {
+        *self.reset_key.write().unwrap() = Some(key);
+    }
+}
+
+impl Drop for Endpoint {
+    fn drop(&mut self) {
+        self.task.abort();
+        self.proto.dismiss_unrouted_packets();
+        self.proto.dismiss_broken_interfaces();
+    }
+}
+
+/// Reserves the [`QuicListeners`] of an [`Endpoint`] for a [`QuicListenersBuilder`], until the
+/// listeners start or the builder is dropped.
+pub(crate) struct ListenersReservation(Arc<Endpoint>);
+
+impl ListenersReservation {
+    fn reserve(endpoint: &Arc<Endpoint>) -> io::Result<Self> {
+        if endpoint.building_listeners.swap(true, Ordering::AcqRel) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                "A QuicServer is being built, please build it first.",
+            ));
+        }
+        let reservation = Self(endpoint.clone());
+        if endpoint.listeners().is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                "A QuicServer is already running, please shutdown it first.",
+            ));
+        }
+        Ok(reservation)
+    }
+
+    pub(crate) fn endpoint(&self) -> &Arc<Endpoint> {
+        &self.0
+    }
+
+    /// Start the `listeners` on the endpoint, the reservation is released.
+    pub(crate) fn fulfill(self, listeners: &Arc<QuicListeners>) {
+        *self.0.listeners.write().unwrap() = Arc::downgrade(listeners);
+    }
+}
+
+impl Drop for ListenersReservation {
+    fn drop(&mut self) {
+        self.0.building_listeners.store(false, Ordering::Release);
+    }
+}
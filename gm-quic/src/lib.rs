@@ -1,4 +1,4 @@
-use std::sync::{Arc, OnceLock};
+use std::sync::Arc;
 
 pub use qconnection::{
     builder::{
@@ -11,41 +11,49 @@ pub use qinterface::factory::ProductQuicInterface;
 
 pub use crate::{
     cert::{ToCertificate, ToPrivateKey},
-    client::{QuicClient, QuicClientBuilder},
-    server::{QuicListeners, QuicListenersBuilder},
+    client::{CONNECTION_ATTEMPT_DELAY, QuicClient, QuicClientBuilder},
+    endpoint::Endpoint,
+    resolver::{Resolve, StaticResolver, SystemResolver},
+    server::{
+        AcceptEarlyData, NotifyShutdown, QuicListeners, QuicListenersBuilder, RetryPolicy,
+        ShutdownPolicy, ShutdownReport,
+    },
 };
 
 mod cert;
 mod client;
+mod endpoint;
+mod resolver;
 mod server;
 #[cfg(test)]
 mod tests;
 
-pub fn proto() -> &'static Arc<QuicProto> {
-    static PROTO: OnceLock<Arc<QuicProto>> = OnceLock::new();
-    PROTO.get_or_init(|| {
-        let proto = Arc::new(QuicProto::new());
-        let handle_unrouted_packets = {
-            let proto = proto.clone();
-            async move {
-                while let Some((bind_addr, packet, pathway, ink)) =
-                    proto.recv_unrouted_packet().await
-                {
-                    QuicListeners::try_accept_connection(bind_addr, packet, pathway, ink).await;
-                }
-            }
-        };
-        let handle_broken_interfaces = {
-            let proto = proto.clone();
-            async move {
-                while let Some((local_addr, iface, error)) = proto.get_broken_interface().await {
-                    QuicListeners::on_interface_broken(local_addr, iface, error);
-                }
+/// Keep the versions implemented by gm-quic in the `versions`, in their order.
+///
+/// All the implemented versions are returned if none of the `versions` is implemented.
+fn implemented_versions(versions: impl IntoIterator<Item = u32>) -> Vec<u32> {
+    use qbase::packet::r#type::long::SUPPORTED_VERSIONS;
+
+    let versions = versions
+        .into_iter()
+        .filter(|version| SUPPORTED_VERSIONS.contains(version))
+        .fold(Vec::new(), |mut versions, version| {
+            if !versions.contains(&version) {
+                versions.push(version);
             }
-        };
-        tokio::spawn(async move {
-            tokio::join!(handle_unrouted_packets, handle_broken_interfaces);
+            versions
         });
-        proto
-    })
+    if versions.is_empty() {
+        SUPPORTED_VERSIONS.to_vec()
+    } else {
+        versions
+    }
+}
+
+/// The [`QuicProto`] of the [`Endpoint::global`].
+///
+/// The connections of the other endpoints are not routed by it, see [`Endpoint::proto`].
+#[deprecated(note = "use `Endpoint::proto` of the endpoint of the connections instead")]
+pub fn proto() -> &'static Arc<QuicProto> {
+    Endpoint::global().proto()
 }
@@ -0,0 +1,145 @@
+use std::{
+    collections::HashMap,
+    io,
+    net::{IpAddr, SocketAddr},
+};
+
+use futures::future::BoxFuture;
+
+/// Resolve the names of the servers to their addresses, for [`QuicClient::connect_by_name`].
+///
+/// [`QuicClient::connect_by_name`]: crate::QuicClient::connect_by_name
+pub trait Resolve: Send + Sync {
+    /// Look up the addresses of `name`, with the given `port`.
+    ///
+    /// The addresses are returned in the order of preference of the resolver.
+    fn lookup<'a>(&'a self, name: &'a str, port: u16)
+    -> BoxFuture<'a, io::Result<Vec<SocketAddr>>>;
+}
+
+/// The resolver of the operating system, see [`tokio::net::lookup_host`].
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemResolver;
+
+impl Resolve for SystemResolver {
+    fn lookup<'a>(
+        &'a self,
+        name: &'a str,
+        port: u16,
+    ) -> BoxFuture<'a, io::Result<Vec<SocketAddr>>> {
+        Box::pin(async move { Ok(tokio::net::lookup_host((name, port)).await?.collect()) })
+    }
+}
+
+/// A resolver looking up the names in a static map, mostly useful for testing.
+///
+/// ``` rust
+/// use gm_quic::StaticResolver;
+///
+/// let resolver = StaticResolver::default()
+///     .with("localhost", ["::1".parse().unwrap(), "127.0.0.1".parse().unwrap()]);
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct StaticResolver {
+    hosts: HashMap<String, Vec<IpAddr>>,
+}
+
+impl StaticResolver {
+    /// Resolve `name` to the `addresses`, in their order.
+    ///
+    /// If you call this multiple times with the same name, only the last call will take effect.
+    pub fn with(
+        mut self,
+        name: impl Into<String>,
+        addresses: impl IntoIterator<Item = IpAddr>,
+    ) -> Self {
+        self.hosts
+            .insert(name.into(), addresses.into_iter().collect());
+        self
+    }
+}
+
+impl Resolve for StaticResolver {
+    fn lookup<'a>(
+        &'a self,
+        name: &'a str,
+        port: u16,
+    ) -> BoxFuture<'a, io::Result<Vec<SocketAddr>>> {
+        let addresses = self
+            .hosts
+            .get(name)
+            .map(|ips| ips.iter().map(|ip| SocketAddr::new(*ip, port)).collect())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("unknown host {name}")));
+        Box::pin(async move { addresses })
+    }
+}
+
+/// Sort the addresses to race the connection attempts to them.
+///
+/// The IPv6 addresses are preferred, and the address families are interleaved, so that a broken
+/// family delays the connection by a single attempt only. The order of the addresses of the same
+/// family is kept.
+///
+/// See [section 4](https://www.rfc-editor.org/rfc/rfc8305.html#name-sorting-addresses) of
+/// [RFC8305](https://www.rfc-editor.org/rfc/rfc8305.html).
+pub(crate) fn interleave_families(addresses: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let (v6, v4): (Vec<_>, Vec<_>) = addresses.into_iter().partition(SocketAddr::is_ipv6);
+    let mut sorted = Vec::with_capacity(v6.len() + v4.len());
+    let (mut v6, mut v4) = (v6.into_iter(), v4.into_iter());
+    loop {
+        match (v6.next(), v4.next()) {
+            (None, None) => return sorted,
+            (v6, v4) => sorted.extend(v6.into_iter().chain(v4)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interleave_families() {
+        let addresses = [
+            "1.1.1.1:443",
+            "2.2.2.2:443",
+            "[::1]:443",
+            "3.3.3.3:443",
+            "[::2]:443",
+        ]
+        .into_iter()
+        .map(|addr| addr.parse().unwrap())
+        .collect();
+        let sorted = [
+            "[::1]:443",
+            "1.1.1.1:443",
+            "[::2]:443",
+            "2.2.2.2:443",
+            "3.3.3.3:443",
+        ]
+        .into_iter()
+        .map(|addr| addr.parse().unwrap())
+        .collect::<Vec<SocketAddr>>();
+        assert_eq!(interleave_families(addresses), sorted);
+        assert_eq!(interleave_families(vec![]), vec![]);
+    }
+
+    #[tokio::test]
+    async fn test_static_resolver() {
+        let resolver = StaticResolver::default()
+            .with("localhost", ["127.0.0.1".parse().unwrap()])
+            .with(
+                "localhost",
+                ["::1".parse().unwrap(), "127.0.0.1".parse().unwrap()],
+            );
+        assert_eq!(
+            resolver.lookup("localhost", 4433).await.unwrap(),
+            vec![
+                "[::1]:4433".parse::<SocketAddr>().unwrap(),
+                "127.0.0.1:4433".parse().unwrap()
+            ]
+        );
+        let error = resolver.lookup("example.com", 4433).await.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+    }
+}
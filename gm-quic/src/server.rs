@@ -1,12 +1,33 @@
 use std::{
+    borrow::Cow,
     collections::HashMap,
     fmt::Debug,
     io,
-    sync::{Arc, RwLock, RwLockWriteGuard, Weak},
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6},
+    sync::{
+        Arc, Weak,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+    time::Duration,
 };
 
+use bytes::BufMut;
 use dashmap::{DashMap, DashSet};
 use handy::UdpSocketController;
+use qbase::{
+    error::ErrorKind,
+    frame::{ConnectionCloseFrame, FrameType},
+    net::route::PacketHeader,
+    packet::{
+        MarshalFrame, PacketWriter,
+        header::long::io::LongHeaderBuilder,
+        number::PacketNumber,
+        retry::RetryIntegrity,
+        r#type::long::{GetVersion, rustls_version},
+    },
+    param::VersionInformation,
+    token::{RetryTokenKey, StatelessResetKey},
+};
 use qconnection::builder::*;
 use qevent::{
     quic::connectivity::ServerListening,
@@ -15,11 +36,11 @@ use qevent::{
 use qinterface::util::Channel;
 use rustls::{
     ConfigBuilder, ServerConfig as TlsServerConfig, WantsVerifier,
-    server::{NoClientAuth, ResolvesServerCert, danger::ClientCertVerifier},
+    server::{NoClientAuth, ResolvesServerCert, StoresServerSessions, danger::ClientCertVerifier},
 };
 use tokio::sync::{OwnedSemaphorePermit, Semaphore, mpsc};
 
-use crate::*;
+use crate::{endpoint::ListenersReservation, *};
 
 type TlsServerConfigBuilder<T> = ConfigBuilder<TlsServerConfig, T>;
 
@@ -65,8 +86,137 @@ impl Debug for Server {
     }
 }
 
+/// When the server validates the client's address with a [Retry packet] before creating
+/// the connection.
+///
+/// A Retry costs the client an extra round trip, but the server keeps no state for a client
+/// until the client proves that it can receive packets at its claimed address. This is the
+/// main defense against the floods of Initial packets with spoofed source addresses.
+///
+/// [Retry packet]: https://www.rfc-editor.org/rfc/rfc9000.html#name-address-validation-using-r
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RetryPolicy {
+    /// Never send Retry packets, this is the default.
+    #[default]
+    Never,
+    /// Always send Retry packets to the clients whose addresses have not been validated.
+    Always,
+    /// Send Retry packets when the number of pending connections, which are handshaking
+    /// or waiting to be accepted, reaches the threshold.
+    Threshold(usize),
+}
+
+/// The policy deciding whether to accept the early data of the clients resuming sessions, which
+/// is sent in 0-RTT packets before the handshake completes.
+///
+/// It is consulted for every incoming connection before its ClientHello is processed, with the
+/// link the connection is accepted from, and whether the client's address has been validated by
+/// a Retry packet or a token from a NEW_TOKEN frame. The early data of the connection is rejected
+/// if it returns false, and the client will resend it after the handshake.
+///
+/// Early data can be replayed by attackers. Each session ticket is single-use in the session
+/// storage of [`QuicListenersBuilder::enable_0rtt`], which prevents the replays of the whole
+/// ClientHello, this policy is the hook for further defenses, such as rejecting early data from
+/// the unvalidated addresses, or while the server is under heavy load.
+///
+/// It can be a closure that accepts (&[`Link`], `bool`) and returns `bool`.
+pub trait AcceptEarlyData: Send + Sync {
+    fn accept_early_data(&self, link: &Link, address_validated: bool) -> bool;
+}
+
+impl<F> AcceptEarlyData for F
+where
+    F: Fn(&Link, bool) -> bool + Send + Sync,
+{
+    fn accept_early_data(&self, link: &Link, address_validated: bool) -> bool {
+        self(link, address_validated)
+    }
+}
+
+/// How the [`QuicListeners`] shut down, see [`QuicListeners::shutdown_gracefully`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShutdownPolicy {
+    /// Whether the Initial packets of the new connections are answered with CONNECTION_CLOSE
+    /// frames, which tell the clients that the connections are refused at once. They are
+    /// ignored otherwise, the clients give up after their handshakes time out.
+    ///
+    /// The CONNECTION_CLOSE frames are sent statelessly, no connection is created for them.
+    /// The clients are still asked to prove their addresses with Retry packets first, as the
+    /// [`RetryPolicy`] says. The connections are refused silently if
+    /// [`QuicListenersBuilder::enable_silent_rejection`] is enabled.
+    pub refuse_new: bool,
+    /// Redirect the new connections to the given addresses instead of refusing them.
+    ///
+    /// The new connections are still accepted until the deadline, with the [preferred address]
+    /// set to these addresses, so the clients move to them right after their handshakes, and
+    /// the addresses being shut down carry no more packets of them. The addresses must be
+    /// served by the interfaces of these listeners, just like the
+    /// [`QuicListenersBuilder::with_preferred_address`]. The unspecified address of an IP family
+    /// leaves the clients of that family where they are.
+    ///
+    /// The endpoint is not free to run new listeners until the deadline in this case.
+    ///
+    /// [preferred address]: https://www.rfc-editor.org/rfc/rfc9000.html#name-server-s-preferred-address
+    pub redirect: Option<(SocketAddrV4, SocketAddrV6)>,
+    /// The application error code to close the connections which have not been accepted,
+    /// or are still open at the deadline.
+    pub error_code: u64,
+    /// The reason to close the connections with.
+    pub reason: Cow<'static, str>,
+}
+
+impl Default for ShutdownPolicy {
+    fn default() -> Self {
+        Self {
+            refuse_new: false,
+            redirect: None,
+            error_code: 0,
+            reason: Cow::Borrowed("server shutdown"),
+        }
+    }
+}
+
+/// Notified of each accepted connection when the [`QuicListeners`] start to shut down gracefully,
+/// before the connections are closed.
+///
+/// This is the chance for the application protocols to ask the peers to wind down the
+/// connections, such as sending the GOAWAY frame of HTTP/3.
+///
+/// It can be a closure that accepts (&[`Arc<Connection>`], `&str`), with the server name of the
+/// connection.
+pub trait NotifyShutdown: Send + Sync {
+    fn notify_shutdown(&self, connection: &Arc<Connection>, server_name: &str);
+}
+
+impl<F> NotifyShutdown for F
+where
+    F: Fn(&Arc<Connection>, &str) + Send + Sync,
+{
+    fn notify_shutdown(&self, connection: &Arc<Connection>, server_name: &str) {
+        self(connection, server_name)
+    }
+}
+
+/// What happened in a graceful shutdown, returned by [`QuicListeners::shutdown_gracefully`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ShutdownReport {
+    /// The accepted connections closed by the application or the peers before the deadline.
+    pub drained: usize,
+    /// The accepted connections still open at the deadline, closed by the listeners.
+    pub closed: usize,
+    /// The connections waiting to be accepted when the shutdown began, closed by the listeners.
+    pub unaccepted: usize,
+    /// The Initial packets of the new connections refused or ignored since the listeners were
+    /// shut down.
+    pub refused_initials: usize,
+    /// The new connections redirected by the [`ShutdownPolicy::redirect`], which are closed at
+    /// the deadline like the other accepted connections if still open.
+    pub redirected: usize,
+}
+
 /// An interface that has been bound to servers in the [`QuicListeners`].
 struct BoundInterface {
+    proto: Arc<QuicProto>,
     iface: Arc<dyn QuicInterface>,
     servers: DashSet<String>,
 }
@@ -82,9 +232,10 @@ impl Debug for BoundInterface {
 
 impl Drop for BoundInterface {
     fn drop(&mut self) {
-        crate::proto().del_interface_if(self.iface.bind_addr(), |iface, _| {
-            Arc::ptr_eq(iface, &self.iface) && Arc::strong_count(iface) == 2
-        });
+        self.proto
+            .del_interface_if(self.iface.bind_addr(), |iface, _| {
+                Arc::ptr_eq(iface, &self.iface) && Arc::strong_count(iface) == 2
+            });
     }
 }
 
@@ -95,8 +246,9 @@ impl Drop for BoundInterface {
 /// Use [`QuicListenersBuilder`] to configure the listener, then call [`QuicListenersBuilder::listen`]
 /// to start accepting connections.
 ///
-/// **Note**: Only one [`QuicListeners`] instance can run at a time globally.
-/// To stop the listeners, call [`QuicListeners::shutdown`] or drop all references to the [`Arc<QuicListeners>`].
+/// **Note**: Only one [`QuicListeners`] instance can run on an [`Endpoint`] at a time.
+/// To stop the listeners, call [`QuicListeners::shutdown`] or drop all references to the [`Arc<QuicListeners>`],
+/// or call [`QuicListeners::shutdown_gracefully`] to wait for the accepted connections to close.
 ///
 /// ## Managing Servers
 ///
@@ -115,10 +267,12 @@ impl Drop for BoundInterface {
 /// - Rejects connections if the target server isn't listening on the receiving interface
 /// - Returns connections that may still be completing their QUIC handshake
 pub struct QuicListeners {
+    endpoint: Arc<Endpoint>,
     quic_iface_factory: Box<dyn ProductQuicInterface>,
     ifaces: Arc<DashMap<BindAddr, BoundInterface>>,
     servers: Arc<DashMap<String, Server>>,
     backlog: Arc<Semaphore>,
+    backlog_size: usize,
     #[allow(clippy::type_complexity)]
     incomings: Arc<
         Channel<(
@@ -126,24 +280,39 @@ pub struct QuicListeners {
             OwnedSemaphorePermit,
         )>,
     >,
+    // The connections accepted by the application, until they are terminated
+    accepted: Arc<DashMap<ConnectionId, (Arc<Connection>, String)>>,
+    refused_initials: AtomicUsize,
+    // Whether the new connections are redirected during the graceful shutdown
+    redirecting: AtomicBool,
+    redirected: AtomicUsize,
+    shutdown_policy: ShutdownPolicy,
+    shutdown_notifier: Option<Arc<dyn NotifyShutdown>>,
 
     token_provider: Arc<dyn TokenProvider>,
     parameters: ServerParameters,
+    preferred_address: Option<(SocketAddrV4, SocketAddrV6)>,
     silent_rejection: bool,
+    retry_policy: RetryPolicy,
+    retry_token_key: RetryTokenKey,
+    early_data_policy: Option<Arc<dyn AcceptEarlyData>>,
+    initial_suite: rustls::quic::Suite,
+    supported_versions: Vec<u32>,
     client_authers: Vec<Arc<dyn AuthClient>>,
     tls_config: Arc<TlsServerConfig>,
     stream_strategy_factory: Box<dyn ProductStreamsConcurrencyController>,
     defer_idle_timeout: HeartbeatConfig,
+    congestion_controller: Arc<dyn ProductCongestionController>,
+    key_update: KeyUpdatePolicy,
+    recv_window_limits: RecvWindowLimits,
+    path_scheduler: Arc<dyn ProductPathScheduler>,
     logger: Arc<dyn Log + Send + Sync>,
-    _supported_versions: Vec<u32>,
 }
 
 impl QuicListeners {
-    /// Start to build a [`QuicListeners`].
+    /// Start to build a [`QuicListeners`] on the [`Endpoint::global`].
     pub fn builder() -> io::Result<QuicListenersBuilder<TlsServerConfigBuilder<WantsVerifier>>> {
-        Self::builder_with_tls(TlsServerConfig::builder_with_protocol_versions(&[
-            &rustls::version::TLS13,
-        ]))
+        Endpoint::global().listeners_builder()
     }
 
     /// Start to build a QuicServer with the given tls crypto provider.
@@ -161,32 +330,7 @@ impl QuicListeners {
     ///
     /// This is useful when you want to customize the TLS configuration, or integrate qm-quic with other crates.
     pub fn builder_with_tls<T>(tls_config: T) -> io::Result<QuicListenersBuilder<T>> {
-        let global_guard = QuicListeners::global()
-            .write()
-            .expect("QuicListeners global lock");
-        if let Some(server) = global_guard.upgrade() {
-            if !server.incomings.is_closed() {
-                return Err(io::Error::new(
-                    io::ErrorKind::AlreadyExists,
-                    "A QuicServer is already running, please shutdown it first.",
-                ));
-            }
-        }
-
-        Ok(QuicListenersBuilder {
-            global_guard,
-            quic_iface_factory: Box::new(UdpSocketController::bind),
-            servers: Arc::default(),
-            token_provider: None,
-            parameters: ServerParameters::default(),
-            silent_rejection: false,
-            client_authers: vec![],
-            tls_config,
-            stream_strategy_factory: Box::new(ConsistentConcurrency::new),
-            defer_idle_timeout: HeartbeatConfig::default(),
-            logger: None,
-            _supported_versions: vec![],
-        })
+        Endpoint::global().listeners_builder_with_tls(tls_config)
     }
 
     /// Let the server bind to an interface.
@@ -215,8 +359,9 @@ impl QuicListeners {
             dashmap::Entry::Occupied(mut exist_iface) => {
                 // if the interface on the same address is already exist, update if the interface is different
                 if !Arc::ptr_eq(&iface, &exist_iface.get().iface) {
-                    crate::proto().add_interface(iface.clone());
+                    self.endpoint.proto().add_interface(iface.clone());
                     exist_iface.insert(BoundInterface {
+                        proto: self.endpoint.proto().clone(),
                         iface: iface.clone(),
                         servers: exist_iface.get().servers.clone(),
                     });
@@ -224,8 +369,9 @@ impl QuicListeners {
                 exist_iface.into_ref()
             }
             dashmap::Entry::Vacant(vacant_entry) => {
-                crate::proto().add_interface(iface.clone());
+                self.endpoint.proto().add_interface(iface.clone());
                 vacant_entry.insert(BoundInterface {
+                    proto: self.endpoint.proto().clone(),
                     iface: iface.clone(),
                     servers: DashSet::default(),
                 })
@@ -353,10 +499,11 @@ impl QuicListeners {
                     } else {
                         let iface = self.quic_iface_factory.bind(bind_addr.clone())?;
                         let bind_addr = iface.bind_addr();
-                        crate::proto().add_interface(iface.clone());
+                        self.endpoint.proto().add_interface(iface.clone());
                         let previous = self.ifaces.insert(
                             bind_addr.clone(),
                             BoundInterface {
+                                proto: self.endpoint.proto().clone(),
                                 iface,
                                 servers: [server_name.clone()].into_iter().collect(),
                             },
@@ -413,23 +560,111 @@ impl QuicListeners {
     /// The connection queue size is limited by the `backlog` parameter in [`QuicListenersBuilder::listen`].
     /// When the queue is full, new incoming packets may be dropped at the network level.
     pub async fn accept(&self) -> io::Result<(Arc<Connection>, String, Pathway, Link)> {
-        self.incomings
+        let (connection, server_name, pathway, link) = self
+            .incomings
             .recv()
             .await
             .ok_or_else(|| io::Error::other("Listeners shutdown"))
-            .map(|(i, ..)| i)
+            .map(|(i, ..)| i)?;
+        // The terminated connections are removed by the task handling their events
+        if let Ok(origin_dcid) = connection.origin_dcid() {
+            self.accepted
+                .insert(origin_dcid, (connection.clone(), server_name.clone()));
+            if !connection.is_active() {
+                self.accepted.remove(&origin_dcid);
+            }
+        }
+        Ok((connection, server_name, pathway, link))
     }
 
     /// Close the QuicListeners, stops accepting new connections.
     ///
-    /// Unaccepted connections will be closed
+    /// Unaccepted connections will be closed with the error code of the [`ShutdownPolicy`],
+    /// the accepted connections are left to the application.
     pub fn shutdown(&self) {
-        if self.incomings.close().is_none() {
-            // already closed
-            return;
+        self.close_incomings();
+    }
+
+    /// Shut down the QuicListeners gracefully, the accepted connections are given the time
+    /// until the `deadline` to close.
+    ///
+    /// The new connections are refused, ignored or redirected as the [`ShutdownPolicy`] says
+    /// from now on, and the unaccepted connections are closed. The application is notified of
+    /// each accepted connection by the [`NotifyShutdown`], then the connections still open at
+    /// the `deadline` are closed with the error code of the [`ShutdownPolicy`].
+    ///
+    /// The endpoint is free to run new listeners once this is called, for the zero-downtime
+    /// deploys, the accepted connections are still served by this QuicListeners until closed.
+    /// If the new connections are redirected, the endpoint is free after the `deadline`.
+    pub async fn shutdown_gracefully(&self, deadline: Duration) -> ShutdownReport {
+        let redirect = self.shutdown_policy.redirect.is_some();
+        let mut unaccepted = 0;
+        match redirect {
+            true => self.redirecting.store(true, Ordering::Release),
+            false => unaccepted = self.close_incomings(),
+        }
+        let accepted = self
+            .accepted
+            .iter()
+            .map(|entry| entry.value().clone())
+            .collect::<Vec<_>>();
+        if let Some(notifier) = &self.shutdown_notifier {
+            for (connection, server_name) in &accepted {
+                notifier.notify_shutdown(connection, server_name);
+            }
         }
 
-        self.incomings.close();
+        let all_terminated = async {
+            for (connection, _) in &accepted {
+                connection.terminated().await;
+            }
+        };
+        match redirect {
+            // The new connections are redirected until the deadline
+            true => tokio::time::sleep(deadline).await,
+            false => _ = tokio::time::timeout(deadline, all_terminated).await,
+        }
+        let drained = accepted
+            .iter()
+            .filter(|(connection, _)| !connection.is_active())
+            .count();
+        if redirect {
+            unaccepted = self.close_incomings();
+        }
+
+        let ShutdownPolicy {
+            error_code, reason, ..
+        } = &self.shutdown_policy;
+        // The redirected connections have been accepted since the shutdown began
+        let closed = self
+            .accepted
+            .iter()
+            .map(|entry| entry.value().0.clone())
+            .filter(|connection| connection.is_active())
+            .inspect(|connection| connection.close(reason.clone(), *error_code))
+            .count();
+        ShutdownReport {
+            drained,
+            closed,
+            unaccepted,
+            refused_initials: self.refused_initials.load(Ordering::Relaxed),
+            redirected: self.redirected.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Stop accepting new connections, returns the number of the unaccepted connections closed.
+    fn close_incomings(&self) -> usize {
+        let Some(unaccepted) = self.incomings.close() else {
+            // already closed
+            return 0;
+        };
+        let ShutdownPolicy {
+            error_code, reason, ..
+        } = &self.shutdown_policy;
+        unaccepted
+            .into_iter()
+            .map(|((connection, ..), _premit)| connection.close(reason.clone(), *error_code))
+            .count()
     }
 }
 
@@ -458,40 +693,125 @@ impl AuthClient for ServerAuther {
 
 // internal methods
 impl QuicListeners {
-    fn global() -> &'static RwLock<Weak<QuicListeners>> {
-        static LISTENERS: OnceLock<RwLock<Weak<QuicListeners>>> = OnceLock::new();
-        LISTENERS.get_or_init(Default::default)
+    pub(crate) fn is_shutdown(&self) -> bool {
+        self.incomings.is_closed()
     }
 
     pub(crate) async fn try_accept_connection(
+        self: Arc<Self>,
         bind_addr: BindAddr,
         packet: Packet,
         pathway: Pathway,
         link: Link,
     ) {
-        let Some(listeners) = Self::global().read().unwrap().upgrade() else {
+        let listeners = self;
+        let proto = listeners.endpoint.proto().clone();
+
+        // The packet may be routed to a connection accepted after it was received, such as the
+        // 0-RTT packet coalesced with the Initial packet in the same datagram.
+        let Err((bind_addr, packet, pathway, link)) =
+            proto.try_deliver(bind_addr, packet, pathway, link).await
+        else {
             return;
         };
 
-        // Acquire a permit from the backlog semaphore to limit the number of concurrent connections.
-        let Ok(premit) = listeners.backlog.clone().acquire_owned().await else {
-            return;
-        };
-
-        let (client_scid, origin_dcid) = match &packet {
+        let (version, client_scid, dcid, token) = match &packet {
             Packet::Data(data_packet) => match &data_packet.header {
-                DataHeader::Long(LongHeader::Initial(hdr)) => (*hdr.scid(), *hdr.dcid()),
-                DataHeader::Long(LongHeader::ZeroRtt(hdr)) => (*hdr.scid(), *hdr.dcid()),
+                DataHeader::Long(LongHeader::Initial(hdr)) => (
+                    hdr.get_version(),
+                    *hdr.scid(),
+                    *hdr.dcid(),
+                    Some(hdr.token()),
+                ),
+                DataHeader::Long(LongHeader::ZeroRtt(hdr)) => {
+                    (hdr.get_version(), *hdr.scid(), *hdr.dcid(), None)
+                }
                 _ => return,
             },
             _ => return,
         };
 
-        if origin_dcid.is_empty() {
+        if dcid.is_empty() {
             tracing::warn!("Received a packet with empty destination CID, ignoring it");
             return;
         }
 
+        // The version is implemented by gm-quic, but not supported by the listeners.
+        if !listeners.supported_versions.contains(&version) {
+            if token.is_some() {
+                proto
+                    .send_version_negotiation(bind_addr, (dcid, client_scid), pathway, link)
+                    .await;
+            }
+            return;
+        }
+
+        // The listeners shut down accept no more connections, the Initial packets are either
+        // ignored, or refused statelessly once the clients have proved their addresses.
+        let refusing = listeners.is_shutdown();
+        if refusing {
+            if token.is_none() {
+                return;
+            }
+            if !listeners.shutdown_policy.refuse_new || listeners.silent_rejection {
+                listeners.refused_initials.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+        }
+
+        let (origin_dcid, retry_scid, address_validated) = match token {
+            // The client which has been retried sends the Initial packets to the source connection
+            // ID of the Retry packet, and the original destination connection ID is recorded in
+            // the token.
+            Some(token) if RetryTokenKey::is_retry_token(token) => {
+                match listeners.retry_token_key.validate(&link.dst(), token) {
+                    Some(origin_dcid) => (origin_dcid, Some(dcid), true),
+                    // rfc9000 8.1.2
+                    // A server MUST NOT send more than one Retry packet in response to a single
+                    // UDP datagram, and a client that is retried will not accept another Retry.
+                    // The invalid Retry token may be replayed or expired, the packet is dropped.
+                    None => {
+                        tracing::debug!(
+                            %pathway,
+                            "drop Initial packet with an invalid Retry token"
+                        );
+                        return;
+                    }
+                }
+            }
+            // rfc9000 8.1.3
+            // A valid token from a NEW_TOKEN frame validates the address of the client, so the
+            // connection is accepted without a Retry.
+            Some(token) if !token.is_empty() && listeners.verify_new_token(token) => {
+                (dcid, None, true)
+            }
+            // Only Initial packets can be responded with Retry packets, the 0-RTT packets will
+            // be resent by the client after the Retry. An invalid token from a NEW_TOKEN frame
+            // is treated as if there were no token.
+            _ if listeners.should_retry() => {
+                if token.is_some() {
+                    listeners
+                        .send_retry(bind_addr, pathway, link, version, client_scid, dcid)
+                        .await;
+                }
+                return;
+            }
+            _ => (dcid, None, false),
+        };
+
+        if refusing {
+            listeners.refused_initials.fetch_add(1, Ordering::Relaxed);
+            listeners
+                .send_connection_refused(bind_addr, pathway, link, version, client_scid, dcid)
+                .await;
+            return;
+        }
+
+        // Acquire a permit from the backlog semaphore to limit the number of concurrent connections.
+        let Ok(premit) = listeners.backlog.clone().acquire_owned().await else {
+            return;
+        };
+
         let server_auther: Arc<dyn AuthClient> = Arc::new(ServerAuther {
             iface: bind_addr.clone(),
             servers: listeners.servers.clone(),
@@ -503,42 +823,80 @@ impl QuicListeners {
 
         let (event_broker, mut events) = mpsc::unbounded_channel();
 
+        let foundation = Connection::with_token_provider(listeners.token_provider.clone())
+            .with_parameters(listeners.parameters.clone())
+            .with_silent_rejection(listeners.silent_rejection)
+            .with_client_authers(client_authers)
+            .with_version(version)
+            .with_early_data(
+                listeners
+                    .early_data_policy
+                    .as_ref()
+                    .is_none_or(|policy| policy.accept_early_data(&link, address_validated)),
+            );
+        let foundation = match retry_scid {
+            Some(retry_scid) => foundation.with_retry(retry_scid, link),
+            None => foundation,
+        };
+        let redirect = match listeners.redirecting.load(Ordering::Acquire) {
+            true => listeners.shutdown_policy.redirect,
+            false => None,
+        };
+        if redirect.is_some() {
+            listeners.redirected.fetch_add(1, Ordering::Relaxed);
+        }
+        let foundation = match redirect.or(listeners.preferred_address) {
+            Some((address_v4, address_v6)) => {
+                foundation.with_preferred_address(address_v4, address_v6)
+            }
+            None => foundation,
+        };
+
         let connection = Arc::new(
-            Connection::with_token_provider(listeners.token_provider.clone())
-                .with_parameters(listeners.parameters.clone())
-                .with_silent_rejection(listeners.silent_rejection)
-                .with_client_authers(client_authers)
+            foundation
                 .with_tls_config(listeners.tls_config.clone())
                 .with_streams_concurrency_strategy(listeners.stream_strategy_factory.as_ref())
-                .with_proto(crate::proto().clone())
+                .with_proto(proto.clone())
+                .with_stateless_reset_key(listeners.endpoint.stateless_reset_key())
                 .defer_idle_timeout(listeners.defer_idle_timeout)
+                .with_congestion_controller(listeners.congestion_controller.clone())
+                .with_key_update_policy(listeners.key_update)
+                .with_recv_window_limits(listeners.recv_window_limits)
+                .with_path_scheduler(listeners.path_scheduler.clone())
                 .with_cids(origin_dcid, client_scid)
                 .with_qlog(listeners.logger.as_ref())
                 .run_with(event_broker),
         );
 
         tokio::spawn(async move {
-            crate::proto()
+            proto
                 .deliver(bind_addr.clone(), packet, pathway, link)
                 .await;
 
             tokio::spawn({
                 let connection = connection.clone();
+                let accepted = listeners.accepted.clone();
                 async move {
                     while let Some(event) = events.recv().await {
                         match event {
                             Event::Handshaked => {}
                             Event::ProbedNewPath(..) => {}
                             Event::PathInactivated(bind_addr, ..) => {
-                                crate::proto().try_free_interface(bind_addr);
+                                proto.try_free_interface(bind_addr);
                             }
                             Event::ApplicationClose => {}
                             Event::Failed(error) => {
                                 connection.enter_closing(qbase::error::Error::from(error).into())
                             }
                             Event::Closed(ccf) => connection.enter_draining(ccf),
-                            Event::StatelessReset => { /* TOOD: stateless reset */ }
-                            Event::Terminated => return,
+                            // The connection has been terminated by itself
+                            Event::StatelessReset => {}
+                            // Only the client receives Version Negotiation packets.
+                            Event::VersionMismatch(..) => {}
+                            Event::Terminated => {
+                                accepted.remove(&origin_dcid);
+                                return;
+                            }
                         }
                     }
                 }
@@ -548,7 +906,8 @@ impl QuicListeners {
                 Ok(server_name) => {
                     let incoming = (connection.clone(), server_name, pathway, link);
                     if listeners.incomings.send((incoming, premit)).await.is_err() {
-                        connection.close("", 1);
+                        let policy = &listeners.shutdown_policy;
+                        connection.close(policy.reason.clone(), policy.error_code);
                     }
                 }
                 Err(error) => {
@@ -563,16 +922,129 @@ impl QuicListeners {
         });
     }
 
-    pub(crate) fn on_interface_broken(
+    /// Whether the `token` was issued in a NEW_TOKEN frame by any of the servers.
+    ///
+    /// The server name indicated by the client is unknown until its ClientHello is parsed.
+    fn verify_new_token(&self, token: &[u8]) -> bool {
+        self.servers.iter().any(|server| {
+            self.token_provider
+                .verify_token(server.key().clone(), token)
+        })
+    }
+
+    fn should_retry(&self) -> bool {
+        match self.retry_policy {
+            RetryPolicy::Never => false,
+            RetryPolicy::Always => true,
+            RetryPolicy::Threshold(threshold) => {
+                let pending = self.backlog_size - self.backlog.available_permits();
+                pending >= threshold
+            }
+        }
+    }
+
+    /// Respond the Initial packet of the `version` sent to `origin_dcid` with a Retry packet.
+    ///
+    /// See [section 8.1.2](https://www.rfc-editor.org/rfc/rfc9000.html#name-address-validation-using-r)
+    /// of [RFC9000](https://www.rfc-editor.org/rfc/rfc9000.html).
+    async fn send_retry(
+        &self,
         bind_addr: BindAddr,
-        broken_iface: Weak<dyn QuicInterface>,
-        error: io::Error,
+        pathway: Pathway,
+        link: Link,
+        version: u32,
+        client_scid: ConnectionId,
+        origin_dcid: ConnectionId,
     ) {
-        let Some(listeners) = Self::global().read().unwrap().upgrade() else {
+        let Some(iface) = self.endpoint.proto().get_interface(bind_addr) else {
             return;
         };
 
-        if let Some(listened_interface) = listeners.ifaces.get(&bind_addr) {
+        // rfc9000 17.2.5.1
+        // The server populates the Destination Connection ID with the connection ID that the client included
+        // in the Source Connection ID of the Initial packet. The server includes a connection ID of its choice
+        // in the Source Connection ID field.
+        let retry_scid = ConnectionId::random_gen(8);
+        let token = self.retry_token_key.gen_token(&link.dst(), &origin_dcid);
+        let header = LongHeaderBuilder::with_cid(client_scid, retry_scid)
+            .version(version)
+            .retry(token, [0; 16]);
+        let retry_integrity = RetryIntegrity::new(&self.initial_suite, rustls_version(version));
+        let retry_packet = retry_integrity.seal(&origin_dcid, &header);
+
+        tracing::debug!(
+            %pathway,
+            odcid = format!("{origin_dcid:x}"),
+            retry_scid = format!("{retry_scid:x}"),
+            "send Retry packet"
+        );
+        let hdr = PacketHeader::new(pathway, link, 64, None, retry_packet.len() as _);
+        _ = core::future::poll_fn(|cx| {
+            iface.poll_send(cx, &[io::IoSlice::new(&retry_packet)], hdr)
+        })
+        .await;
+    }
+
+    /// Refuse the connection of the Initial packet of the `version` sent to `dcid`, with an
+    /// Initial packet carrying a CONNECTION_CLOSE frame, no connection is created for it.
+    ///
+    /// See [section 10.3.1](https://www.rfc-editor.org/rfc/rfc9000.html#name-immediate-close-during-the-)
+    /// of [RFC9000](https://www.rfc-editor.org/rfc/rfc9000.html).
+    async fn send_connection_refused(
+        &self,
+        bind_addr: BindAddr,
+        pathway: Pathway,
+        link: Link,
+        version: u32,
+        client_scid: ConnectionId,
+        dcid: ConnectionId,
+    ) {
+        let Some(iface) = self.endpoint.proto().get_interface(bind_addr) else {
+            return;
+        };
+
+        let keys = self
+            .initial_suite
+            .keys(&dcid, rustls::Side::Server, rustls_version(version));
+        let header = LongHeaderBuilder::with_cid(client_scid, ConnectionId::random_gen(8))
+            .version(version)
+            .initial(vec![]);
+        let ccf = ConnectionCloseFrame::new_quic(
+            ErrorKind::ConnectionRefused,
+            FrameType::Crypto.into(),
+            self.shutdown_policy.reason.clone(),
+        );
+        let mut buf = [0; 1200];
+        let Ok(mut packet_writer) = PacketWriter::new_long(
+            &header,
+            &mut buf,
+            (0, PacketNumber::encode(0, 0)),
+            Arc::new(keys),
+        ) else {
+            return;
+        };
+        packet_writer.dump_frame(ccf);
+        // rfc9000 14.1: the client discards the Initial packets in the datagrams smaller than
+        // 1200 bytes
+        let padding_len = packet_writer.remaining_mut();
+        packet_writer.pad(padding_len);
+        let packet_len = packet_writer.encrypt_and_protect().sent_bytes();
+
+        tracing::debug!(%pathway, dcid = format!("{dcid:x}"), "refuse the connection");
+        let hdr = PacketHeader::new(pathway, link, 64, None, packet_len as _);
+        _ = core::future::poll_fn(|cx| {
+            iface.poll_send(cx, &[io::IoSlice::new(&buf[..packet_len])], hdr)
+        })
+        .await;
+    }
+
+    pub(crate) fn on_interface_broken(
+        &self,
+        bind_addr: BindAddr,
+        broken_iface: Weak<dyn QuicInterface>,
+        error: io::Error,
+    ) {
+        if let Some(listened_interface) = self.ifaces.get(&bind_addr) {
             if Weak::ptr_eq(&Arc::downgrade(&listened_interface.iface), &broken_iface) {
                 for server_name in listened_interface.servers.iter() {
                     let server_name = &*server_name;
@@ -587,28 +1059,76 @@ impl QuicListeners {
 
 /// The builder for the quic listeners.
 pub struct QuicListenersBuilder<T> {
-    global_guard: RwLockWriteGuard<'static, Weak<QuicListeners>>,
+    reservation: ListenersReservation,
     quic_iface_factory: Box<dyn ProductQuicInterface>,
     servers: Arc<DashMap<String, Server>>, // must be empty while building
 
     token_provider: Option<Arc<dyn TokenProvider>>,
     parameters: ServerParameters,
+    preferred_address: Option<(SocketAddrV4, SocketAddrV6)>,
     silent_rejection: bool,
+    retry_policy: RetryPolicy,
+    early_data_policy: Option<Arc<dyn AcceptEarlyData>>,
+    shutdown_policy: ShutdownPolicy,
+    shutdown_notifier: Option<Arc<dyn NotifyShutdown>>,
     client_authers: Vec<Arc<dyn AuthClient>>,
     tls_config: T,
     stream_strategy_factory: Box<dyn ProductStreamsConcurrencyController>,
     defer_idle_timeout: HeartbeatConfig,
+    congestion_controller: Arc<dyn ProductCongestionController>,
+    key_update: KeyUpdatePolicy,
+    recv_window_limits: RecvWindowLimits,
+    path_scheduler: Arc<dyn ProductPathScheduler>,
+    stateless_reset_secret: Option<Vec<u8>>,
     logger: Option<Arc<dyn Log + Send + Sync>>,
-    _supported_versions: Vec<u32>,
+    supported_versions: Vec<u32>,
 }
 
 impl<T> QuicListenersBuilder<T> {
-    /// (WIP)Specify the supported quic versions.
+    pub(crate) fn new(reservation: ListenersReservation, tls_config: T) -> Self {
+        Self {
+            reservation,
+            quic_iface_factory: Box::new(UdpSocketController::bind),
+            servers: Arc::default(),
+            token_provider: None,
+            parameters: ServerParameters::default(),
+            preferred_address: None,
+            silent_rejection: false,
+            retry_policy: RetryPolicy::default(),
+            early_data_policy: None,
+            shutdown_policy: ShutdownPolicy::default(),
+            shutdown_notifier: None,
+            client_authers: vec![],
+            tls_config,
+            stream_strategy_factory: Box::new(ConsistentConcurrency::new),
+            defer_idle_timeout: HeartbeatConfig::default(),
+            congestion_controller: Arc::new(Algorithm::default()),
+            key_update: KeyUpdatePolicy::default(),
+            recv_window_limits: RecvWindowLimits::default(),
+            path_scheduler: Arc::new(PathScheduling::default()),
+            stateless_reset_secret: None,
+            logger: None,
+            supported_versions: vec![],
+        }
+    }
+
+    /// Specify the supported quic versions, in the order of preference.
+    ///
+    /// The versions are listed in the [Version Negotiation packets] sent in response to the packets
+    /// of unsupported versions, and in the `version_information` transport parameter to prevent the
+    /// version downgrade attacks, see [RFC9368](https://www.rfc-editor.org/rfc/rfc9368.html).
+    ///
+    /// The versions not implemented by gm-quic are ignored, [`VERSION_1`] and [`VERSION_2`] are
+    /// implemented now. The connection uses the version of the client's first Initial packet.
     ///
     /// If you call this multiple times, only the last call will take effect.
+    ///
+    /// Default: all the versions implemented by gm-quic.
+    ///
+    /// [Version Negotiation packets]: https://www.rfc-editor.org/rfc/rfc9000.html#name-version-negotiation
     pub fn with_supported_versions(mut self, versions: impl IntoIterator<Item = u32>) -> Self {
-        self._supported_versions.clear();
-        self._supported_versions.extend(versions);
+        self.supported_versions.clear();
+        self.supported_versions.extend(versions);
         self
     }
 
@@ -650,6 +1170,109 @@ impl<T> QuicListenersBuilder<T> {
         self
     }
 
+    /// Specify the congestion control algorithm for the server connections.
+    ///
+    /// The algorithm is used by every path of every connection.
+    ///
+    /// If you call this multiple times, only the last `algorithm` will be used.
+    ///
+    /// Default: [`Algorithm::Cubic`]
+    pub fn with_congestion_algorithm(mut self, algorithm: Algorithm) -> Self {
+        self.congestion_controller = Arc::new(algorithm);
+        self
+    }
+
+    /// Specify a custom congestion controller for the server connections.
+    ///
+    /// `controller_factory` is called to create a [`Control`] for every path of every connection,
+    /// with the read-only RTT estimation and PMTU of the path. It can be a closure that accepts
+    /// ([`ArcRtt`], [`Pmtu`]) and returns a [`Control`] object.
+    ///
+    /// This replaces the algorithm specified by [`Self::with_congestion_algorithm`], and vice versa.
+    ///
+    /// [`Control`]: crate::congestion::Control
+    /// [`ArcRtt`]: crate::congestion::ArcRtt
+    /// [`Pmtu`]: crate::congestion::Pmtu
+    pub fn with_congestion_controller(
+        mut self,
+        controller_factory: impl ProductCongestionController + 'static,
+    ) -> Self {
+        self.congestion_controller = Arc::new(controller_factory);
+        self
+    }
+
+    /// Specify when to initiate the 1-RTT key updates for the server connections proactively.
+    ///
+    /// Updating the keys regularly limits the amount of data protected with the same keys, the
+    /// keys are always updated before reaching the confidentiality limit of the AEAD algorithm,
+    /// whatever the policy is. See [`KeyUpdatePolicy`] for more information.
+    ///
+    /// If you call this multiple times, only the last `policy` will be used.
+    ///
+    /// Default: only update the keys before reaching the confidentiality limit.
+    pub fn with_key_update_policy(mut self, policy: KeyUpdatePolicy) -> Self {
+        self.key_update = policy;
+        self
+    }
+
+    /// Specify how large the receive windows of the server connections can grow to.
+    ///
+    /// The receive windows start from the `initial_max_data` and `initial_max_stream_data_*`
+    /// transport parameters, and are doubled when the application reads the data faster than the
+    /// windows are updated once per RTT, up to the `limits`. The data received but not read by the
+    /// application never exceeds the windows, so the `limits` also cap the memory used to buffer it.
+    ///
+    /// If you call this multiple times, only the last `limits` will be used.
+    ///
+    /// Default: 24 MiB for the connection, and 16 MiB for each stream.
+    pub fn with_recv_window_limits(mut self, limits: RecvWindowLimits) -> Self {
+        self.recv_window_limits = limits;
+        self
+    }
+
+    /// Specify which paths carry the data of the connections when multipath is enabled.
+    ///
+    /// Multipath is enabled if both endpoints send the `initial_max_path_id` transport
+    /// parameter, the built-in schedulers are listed in [`PathScheduling`].
+    ///
+    /// If you call this multiple times, only the last `scheduling` will be used.
+    ///
+    /// Default: [`PathScheduling::MinRtt`].
+    pub fn with_path_scheduling(mut self, scheduling: PathScheduling) -> Self {
+        self.path_scheduler = Arc::new(scheduling);
+        self
+    }
+
+    /// Specify a custom path scheduler for the connections, see [`Self::with_path_scheduling`].
+    ///
+    /// The `scheduler_factory` creates a [`SchedulePaths`] for each connection.
+    pub fn with_path_scheduler(
+        mut self,
+        scheduler_factory: impl ProductPathScheduler + 'static,
+    ) -> Self {
+        self.path_scheduler = Arc::new(scheduler_factory);
+        self
+    }
+
+    /// Specify the static secret to derive the [stateless reset] tokens.
+    ///
+    /// When the server lost the state of a connection, such as after a restart, it sends a
+    /// stateless reset in response to the packets of that connection, so that the client can
+    /// close the connection immediately instead of waiting for the idle timeout.
+    ///
+    /// To make stateless reset work across restarts, the same secret must be specified each
+    /// time the server starts, and it must be kept secret from others.
+    ///
+    /// If you call this multiple times, only the last `secret` will be used.
+    ///
+    /// Default: a random secret, stateless resets only work until the process exits.
+    ///
+    /// [stateless reset](https://www.rfc-editor.org/rfc/rfc9000.html#name-stateless-reset)
+    pub fn with_stateless_reset_secret(mut self, secret: impl Into<Vec<u8>>) -> Self {
+        self.stateless_reset_secret = Some(secret.into());
+        self
+    }
+
     /// Specify the [transport parameters] for the server connections.
     ///
     /// If you call this multiple times, only the last `parameters` will be used.
@@ -662,6 +1285,31 @@ impl<T> QuicListenersBuilder<T> {
         self
     }
 
+    /// Provide a [preferred address] to the clients, which they migrate the connections to after
+    /// the handshake, such as the unicast address of a server reached through an anycast address.
+    ///
+    /// Call it once for each IP family, the clients migrate to the address of the IP family they
+    /// use. The server must listen on the preferred address, it should be bound by one of the
+    /// interfaces added by [`QuicListeners::add_interface`].
+    ///
+    /// If you call this multiple times for the same IP family, only the last `address` will be
+    /// used.
+    ///
+    /// Default: no preferred address.
+    ///
+    /// [preferred address]: https://www.rfc-editor.org/rfc/rfc9000.html#name-server-s-preferred-address
+    pub fn with_preferred_address(mut self, address: SocketAddr) -> Self {
+        let (address_v4, address_v6) = self.preferred_address.get_or_insert((
+            SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0),
+            SocketAddrV6::new(Ipv6Addr::UNSPECIFIED, 0, 0, 0),
+        ));
+        match address {
+            SocketAddr::V4(address) => *address_v4 = address,
+            SocketAddr::V6(address) => *address_v6 = address,
+        }
+        self
+    }
+
     /// Specify how hosts bind to the interface.
     ///
     /// If you call this multiple times, only the last `factory` will be used.
@@ -720,6 +1368,54 @@ impl<T> QuicListenersBuilder<T> {
         self
     }
 
+    /// Specify when the server validates the client's address with a [Retry packet].
+    ///
+    /// See [`RetryPolicy`] for more information.
+    ///
+    /// If you call this multiple times, only the last `policy` will be used.
+    ///
+    /// Default: [`RetryPolicy::Never`]
+    ///
+    /// [Retry packet]: https://www.rfc-editor.org/rfc/rfc9000.html#name-address-validation-using-r
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
+    }
+
+    /// Specify the policy deciding whether to accept the early data of the clients.
+    ///
+    /// It takes effect only if 0-RTT is enabled by [`QuicListenersBuilder::enable_0rtt`], see
+    /// [`AcceptEarlyData`] for more information.
+    ///
+    /// If you call this multiple times, only the last `policy` will be used.
+    ///
+    /// Default: accept the early data of all clients.
+    pub fn with_early_data_policy(mut self, policy: impl AcceptEarlyData + 'static) -> Self {
+        self.early_data_policy = Some(Arc::new(policy));
+        self
+    }
+
+    /// Specify how the listeners shut down, see [`ShutdownPolicy`].
+    ///
+    /// If you call this multiple times, only the last `policy` will be used.
+    ///
+    /// Default: ignore the new connections, and close the connections with the error code 0.
+    pub fn with_shutdown_policy(mut self, policy: ShutdownPolicy) -> Self {
+        self.shutdown_policy = policy;
+        self
+    }
+
+    /// Specify the notifier of the accepted connections when the listeners start to shut down
+    /// gracefully, see [`NotifyShutdown`] and [`QuicListeners::shutdown_gracefully`].
+    ///
+    /// If you call this multiple times, only the last `notifier` will be used.
+    ///
+    /// Default: no notification.
+    pub fn with_shutdown_notifier(mut self, notifier: impl NotifyShutdown + 'static) -> Self {
+        self.shutdown_notifier = Some(Arc::new(notifier));
+        self
+    }
+
     /// Specify custom client authentication handlers for the server.
     ///
     /// Client authers are used to perform additional validation beyond standard TLS
@@ -770,12 +1466,17 @@ impl QuicListenersBuilder<TlsServerConfigBuilder<WantsVerifier>> {
         client_cert_verifier: Arc<dyn ClientCertVerifier>,
     ) -> QuicListenersBuilder<TlsServerConfig> {
         QuicListenersBuilder {
-            global_guard: self.global_guard,
+            reservation: self.reservation,
             quic_iface_factory: self.quic_iface_factory,
             servers: self.servers.clone(),
             token_provider: self.token_provider,
             parameters: self.parameters,
+            preferred_address: self.preferred_address,
             silent_rejection: self.silent_rejection,
+            retry_policy: self.retry_policy,
+            early_data_policy: self.early_data_policy,
+            shutdown_policy: self.shutdown_policy,
+            shutdown_notifier: self.shutdown_notifier,
             client_authers: self.client_authers,
             tls_config: self
                 .tls_config
@@ -783,20 +1484,30 @@ impl QuicListenersBuilder<TlsServerConfigBuilder<WantsVerifier>> {
                 .with_cert_resolver(Arc::new(VirtualHosts(self.servers))),
             stream_strategy_factory: self.stream_strategy_factory,
             defer_idle_timeout: self.defer_idle_timeout,
+            congestion_controller: self.congestion_controller,
+            key_update: self.key_update,
+            recv_window_limits: self.recv_window_limits,
+            path_scheduler: self.path_scheduler,
+            stateless_reset_secret: self.stateless_reset_secret,
             logger: self.logger,
-            _supported_versions: self._supported_versions,
+            supported_versions: self.supported_versions,
         }
     }
 
     /// Disable client authentication.
     pub fn without_client_cert_verifier(self) -> QuicListenersBuilder<TlsServerConfig> {
         QuicListenersBuilder {
-            global_guard: self.global_guard,
+            reservation: self.reservation,
             quic_iface_factory: self.quic_iface_factory,
             servers: self.servers.clone(),
             token_provider: self.token_provider,
             parameters: self.parameters,
+            preferred_address: self.preferred_address,
             silent_rejection: self.silent_rejection,
+            retry_policy: self.retry_policy,
+            early_data_policy: self.early_data_policy,
+            shutdown_policy: self.shutdown_policy,
+            shutdown_notifier: self.shutdown_notifier,
             client_authers: self.client_authers,
             tls_config: self
                 .tls_config
@@ -804,8 +1515,13 @@ impl QuicListenersBuilder<TlsServerConfigBuilder<WantsVerifier>> {
                 .with_cert_resolver(Arc::new(VirtualHosts(self.servers))),
             stream_strategy_factory: self.stream_strategy_factory,
             defer_idle_timeout: self.defer_idle_timeout,
+            congestion_controller: self.congestion_controller,
+            key_update: self.key_update,
+            recv_window_limits: self.recv_window_limits,
+            path_scheduler: self.path_scheduler,
+            stateless_reset_secret: self.stateless_reset_secret,
             logger: self.logger,
-            _supported_versions: self._supported_versions,
+            supported_versions: self.supported_versions,
         }
     }
 }
@@ -825,6 +1541,30 @@ impl QuicListenersBuilder<TlsServerConfig> {
         self
     }
 
+    /// Enable accepting early data in 0-RTT packets from the clients resuming sessions.
+    ///
+    /// The sessions issued to the clients are kept in the `storage`, each of them is taken out
+    /// when a client resumes it, so a replayed ClientHello cannot resume the session again, and
+    /// its early data is rejected. Share the `storage` between the servers behind the same
+    /// address to prevent the replays across them.
+    ///
+    /// Use [`rustls::server::ServerSessionMemoryCache`] to keep the sessions in memory, or
+    /// implement [`StoresServerSessions`] to store them elsewhere.
+    ///
+    /// Note that the server transport parameters must not be reduced while 0-RTT is enabled,
+    /// the clients send early data within the limits of the parameters remembered with the
+    /// sessions.
+    ///
+    /// Default: disabled.
+    pub fn enable_0rtt(mut self, storage: Arc<dyn StoresServerSessions>) -> Self {
+        self.tls_config.session_storage = storage;
+        // rfc9001 4.6.1
+        // Servers MUST NOT send the early_data extension with a max_early_data_size field set
+        // to any value other than 0xffffffff.
+        self.tls_config.max_early_data_size = u32::MAX;
+        self
+    }
+
     /// Start listening for incoming connections.
     ///
     /// The `backlog` parameter has the same meaning as the backlog parameter of the UNIX listen function,
@@ -835,27 +1575,75 @@ impl QuicListenersBuilder<TlsServerConfig> {
     pub fn listen(mut self, backlog: usize) -> Arc<QuicListeners> {
         assert!(backlog > 0, "backlog must be greater than 0");
         debug_assert!(self.servers.is_empty());
+        let proto = self.reservation.endpoint().proto();
+
+        let hkdf = self
+            .tls_config
+            .crypto_provider()
+            .cipher_suites
+            .iter()
+            .find_map(|suite| suite.tls13())
+            .map(|suite| suite.hkdf_provider);
+        if let Some(hkdf) = hkdf {
+            let reset_key = match &self.stateless_reset_secret {
+                Some(secret) => StatelessResetKey::new(hkdf, secret),
+                None => StatelessResetKey::random(hkdf),
+            };
+            self.reservation
+                .endpoint()
+                .set_stateless_reset_key(reset_key);
+        }
+
+        let supported_versions = crate::implemented_versions(self.supported_versions);
+        self.parameters
+            .set_version_information(VersionInformation::new(
+                supported_versions[0],
+                supported_versions.clone(),
+            ));
+        proto.set_supported_versions(supported_versions.clone());
+
+        let initial_suite = initial_suite(self.tls_config.crypto_provider());
+        // Retry tokens are only valid for a few seconds, there is no need to keep the key
+        // across restarts.
+        let retry_token_key = RetryTokenKey::random(initial_suite.suite.hkdf_provider);
 
         let quic_listeners = Arc::new(QuicListeners {
+            endpoint: self.reservation.endpoint().clone(),
             quic_iface_factory: self.quic_iface_factory,
             ifaces: Arc::default(),
             servers: self.servers,
             backlog: Arc::new(Semaphore::new(backlog)),
+            backlog_size: backlog,
             incomings: Arc::new(Channel::new(8)), // any number greater than 0
+            accepted: Arc::default(),
+            refused_initials: AtomicUsize::new(0),
+            redirecting: AtomicBool::new(false),
+            redirected: AtomicUsize::new(0),
+            shutdown_policy: self.shutdown_policy,
+            shutdown_notifier: self.shutdown_notifier,
             token_provider: self
                 .token_provider
                 .unwrap_or_else(|| Arc::new(NoopTokenRegistry)),
             parameters: self.parameters,
+            preferred_address: self.preferred_address,
             silent_rejection: self.silent_rejection,
+            retry_policy: self.retry_policy,
+            retry_token_key,
+            early_data_policy: self.early_data_policy,
+            initial_suite,
+            supported_versions,
             client_authers: self.client_authers,
             tls_config: Arc::new(self.tls_config),
             stream_strategy_factory: self.stream_strategy_factory,
             defer_idle_timeout: self.defer_idle_timeout,
+            congestion_controller: self.congestion_controller,
+            key_update: self.key_update,
+            recv_window_limits: self.recv_window_limits,
+            path_scheduler: self.path_scheduler,
             logger: self.logger.unwrap_or_else(|| Arc::new(NoopLogger)),
-            _supported_versions: self._supported_versions,
         });
 
-        *self.global_guard = Arc::downgrade(&quic_listeners);
+        self.reservation.fulfill(&quic_listeners);
         quic_listeners
     }
 }
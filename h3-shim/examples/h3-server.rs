@@ -116,6 +116,7 @@ async fn run(options: Options) -> Result<(), Box<dyn std::error::Error + Send +
 
     // handle incoming connections and requests
     while let Ok((new_conn, _server, _pathway, _link)) = listeners.accept().await {
+        let quic_conn = new_conn.clone();
         let h3_conn =
             match h3::server::Connection::new(h3_shim::QuicConnection::new(new_conn)).await {
                 Ok(h3_conn) => {
@@ -128,7 +129,7 @@ async fn run(options: Options) -> Result<(), Box<dyn std::error::Error + Send +
                 }
             };
         let root = root.clone();
-        tokio::spawn(handle_connection(root, h3_conn));
+        tokio::spawn(handle_connection(root, quic_conn, h3_conn));
     }
 
     Ok(())
@@ -136,6 +137,7 @@ async fn run(options: Options) -> Result<(), Box<dyn std::error::Error + Send +
 
 async fn handle_connection<T>(
     serve_root: Arc<PathBuf>,
+    quic_conn: Arc<gm_quic::Connection>,
     mut connection: h3::server::Connection<T, Bytes>,
 ) where
     T: h3::quic::Connection<Bytes> + 'static,
@@ -145,8 +147,12 @@ async fn handle_connection<T>(
         match connection.accept().await {
             Ok(Some(request_resolver)) => {
                 let serve_root = serve_root.clone();
+                let quic_conn = quic_conn.clone();
                 let handle_request = async move {
                     let (request, stream) = request_resolver.resolve_request().await?;
+                    if let Some(priority) = request.headers().get("priority") {
+                        h3_shim::priority::apply(&quic_conn, stream.id(), priority.as_bytes())?;
+                    }
                     handle_request(request, stream, serve_root).await
                 };
                 tokio::spawn(async move {
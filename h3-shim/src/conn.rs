@@ -6,7 +6,8 @@ use std::{
     task::{Context, Poll},
 };
 
-use futures::Stream;
+use bytes::Bytes;
+use futures::{Stream, TryStreamExt};
 use gm_quic::{StreamId, StreamReader, StreamWriter};
 use h3::quic::{ConnectionErrorIncoming, StreamErrorIncoming};
 
@@ -21,6 +22,10 @@ pub struct QuicConnection {
     accept_uni: AcceptUniStreams,
     open_bi: OpenBiStreams,
     open_uni: OpenUniStreams,
+    #[cfg(feature = "unreliable")]
+    datagrams: crate::ext::H3Datagrams,
+    #[cfg(feature = "webtransport")]
+    webtransport: crate::webtransport::WebTransport,
 }
 
 impl Deref for QuicConnection {
@@ -33,14 +38,42 @@ impl Deref for QuicConnection {
 
 impl QuicConnection {
     pub fn new(conn: Arc<gm_quic::Connection>) -> Self {
+        #[cfg(feature = "unreliable")]
+        let datagrams = crate::ext::H3Datagrams::new(conn.clone());
+        // The WebTransport streams are picked out before the streams are accepted by h3
+        #[cfg(feature = "webtransport")]
+        let (webtransport, accept_bi, accept_uni) =
+            crate::webtransport::WebTransport::intercept(conn.clone(), datagrams.clone());
+        #[cfg(not(feature = "webtransport"))]
+        let (accept_bi, accept_uni) = (
+            AcceptBiStreams::new(conn.clone()),
+            AcceptUniStreams::new(conn.clone()),
+        );
         Self {
-            accept_bi: AcceptBiStreams::new(conn.clone()),
-            accept_uni: AcceptUniStreams::new(conn.clone()),
+            accept_bi,
+            accept_uni,
             open_bi: OpenBiStreams::new(conn.clone()),
             open_uni: OpenUniStreams::new(conn.clone()),
+            #[cfg(feature = "unreliable")]
+            datagrams,
+            #[cfg(feature = "webtransport")]
+            webtransport,
             connection: conn,
         }
     }
+
+    /// The [HTTP Datagrams](https://www.rfc-editor.org/rfc/rfc9297.html) of the connection.
+    #[cfg(feature = "unreliable")]
+    pub fn datagrams(&self) -> &crate::ext::H3Datagrams {
+        &self.datagrams
+    }
+
+    /// The [WebTransport](https://datatracker.ietf.org/doc/draft-ietf-webtrans-http3/) sessions
+    /// of the connection.
+    #[cfg(feature = "webtransport")]
+    pub fn webtransport(&self) -> &crate::webtransport::WebTransport {
+        &self.webtransport
+    }
 }
 
 /// 首先，QuicConnection需能主动创建双向流和发送流，以及关闭连接.
@@ -99,6 +132,13 @@ impl<B: bytes::Buf> h3::quic::Connection<B> for QuicConnection {
         &mut self,
         cx: &mut Context<'_>,
     ) -> Poll<Result<Self::RecvStream, ConnectionErrorIncoming>> {
+        // The SETTINGS_H3_DATAGRAM of the peer is learnt from its control stream
+        #[cfg(feature = "unreliable")]
+        return self
+            .accept_uni
+            .poll_accept(cx)
+            .map_ok(|stream| stream.with_settings_sniffer(self.datagrams.sniff_settings()));
+        #[cfg(not(feature = "unreliable"))]
         self.accept_uni.poll_accept(cx)
     }
 
@@ -177,7 +217,7 @@ impl<B: bytes::Buf> h3::quic::OpenStreams<B> for OpenStreams {
 
 type BoxStream<T> = Pin<Box<dyn Stream<Item = T> + Send + Sync>>;
 
-fn sid_exceed_limit_error() -> io::Error {
+pub(crate) fn sid_exceed_limit_error() -> io::Error {
     io::Error::other(
         "the stream IDs in the `dir` direction exceed 2^60, this is very very hard to happen.",
     )
@@ -249,12 +289,16 @@ impl OpenUniStreams {
     }
 }
 
-#[allow(clippy::type_complexity)]
-struct AcceptBiStreams(
-    BoxStream<Result<(StreamId, (StreamReader, StreamWriter)), ConnectionErrorIncoming>>,
-);
+/// A bidirectional stream accepted, with the data read ahead from it.
+pub(crate) type AcceptedBiStream = (StreamId, (StreamReader, StreamWriter), Bytes);
+
+/// A unidirectional stream accepted, with the data read ahead from it.
+pub(crate) type AcceptedUniStream = (StreamId, StreamReader, Bytes);
+
+pub(crate) struct AcceptBiStreams(BoxStream<Result<AcceptedBiStream, ConnectionErrorIncoming>>);
 
 impl AcceptBiStreams {
+    #[cfg_attr(feature = "webtransport", allow(dead_code))]
     fn new(conn: Arc<gm_quic::Connection>) -> Self {
         let stream = futures::stream::unfold(conn, |conn| async {
             Some((
@@ -265,6 +309,15 @@ impl AcceptBiStreams {
                 conn,
             ))
         });
+        Self::from_stream(stream.map_ok(|(sid, stream)| (sid, stream, Bytes::new())))
+    }
+
+    pub(crate) fn from_stream(
+        stream: impl Stream<Item = Result<AcceptedBiStream, ConnectionErrorIncoming>>
+        + Send
+        + Sync
+        + 'static,
+    ) -> Self {
         Self(Box::pin(stream))
     }
 
@@ -276,13 +329,16 @@ impl AcceptBiStreams {
             .as_mut()
             .poll_next(cx)
             .map(Option::unwrap)
-            .map_ok(|(sid, stream)| BidiStream::new(sid, stream))
+            .map_ok(|(sid, stream, read_ahead)| {
+                BidiStream::new(sid, stream).with_read_ahead(read_ahead)
+            })
     }
 }
 
-struct AcceptUniStreams(BoxStream<Result<(StreamId, StreamReader), ConnectionErrorIncoming>>);
+pub(crate) struct AcceptUniStreams(BoxStream<Result<AcceptedUniStream, ConnectionErrorIncoming>>);
 
 impl AcceptUniStreams {
+    #[cfg_attr(feature = "webtransport", allow(dead_code))]
     fn new(conn: Arc<gm_quic::Connection>) -> Self {
         let stream = futures::stream::unfold(conn, |conn| async {
             let uni = conn
@@ -292,6 +348,15 @@ impl AcceptUniStreams {
                 .map_err(error::convert_connection_io_error);
             Some((uni, conn))
         });
+        Self::from_stream(stream.map_ok(|(sid, reader)| (sid, reader, Bytes::new())))
+    }
+
+    pub(crate) fn from_stream(
+        stream: impl Stream<Item = Result<AcceptedUniStream, ConnectionErrorIncoming>>
+        + Send
+        + Sync
+        + 'static,
+    ) -> Self {
         Self(Box::pin(stream))
     }
 
@@ -303,6 +368,8 @@ impl AcceptUniStreams {
             .as_mut()
             .poll_next(cx)
             .map(Option::unwrap)
-            .map_ok(|(sid, reader)| RecvStream::new(sid, reader))
+            .map_ok(|(sid, reader, read_ahead)| {
+                RecvStream::new(sid, reader).with_read_ahead(read_ahead)
+            })
     }
 }
@@ -1,75 +1,507 @@
-// See https://github.com/hyperium/h3/issues/307"
-
-// use std::{
-//     io,
-//     ops::Deref,
-//     task::{Context, Poll},
-// };
-
-// use bytes::{Buf, Bytes};
-// use futures::future::BoxFuture;
-// use gm_quic::{DatagramReader, DatagramWriter};
-// use h3_datagram::{
-//     ConnectionErrorIncoming,
-//     datagram::EncodedDatagram,
-//     quic_traits::{DatagramConnectionExt, RecvDatagram, SendDatagram, SendDatagramErrorIncoming},
-// };
-
-// use crate::{conn::QuicConnection, error::convert_connection_io_error};
-
-// impl<B: bytes::Buf> DatagramConnectionExt<B> for QuicConnection {
-//     type SendDatagramHandler = DatagramSender;
-
-//     type RecvDatagramHandler = DatagramReceiver;
-
-//     fn send_datagram_handler(&self) -> Self::SendDatagramHandler {
-//         let conn = self.deref().clone();
-//         DatagramSender::Pending(Box::pin(async move { conn.unreliable_writer().await }))
-//     }
-
-//     fn recv_datagram_handler(&self) -> Self::RecvDatagramHandler {
-//         let conn = self.deref().clone();
-//         DatagramReceiver::Pending(Box::pin(async move { conn.unreliable_reader() }))
-//     }
-// }
-
-// pub enum DatagramSender {
-//     Pending(BoxFuture<'static, io::Result<DatagramWriter>>),
-//     Ready(Result<DatagramWriter, SendDatagramErrorIncoming>),
-// }
-
-// impl<B: bytes::Buf> SendDatagram<B> for DatagramSender {
-//     fn send_datagram<T: Into<EncodedDatagram<B>>>(
-//         &mut self,
-//         data: T,
-//     ) -> Result<(), SendDatagramErrorIncoming> {
-//         // let mut buf = bytes::BytesMut::new();
-//         // buf
-//         // data.encode(&mut buf);
-//         let mut datagram = <T as Into<EncodedDatagram<B>>>::into(data);
-//         self.0
-//             .send_bytes(datagram.copy_to_bytes(datagram.remaining()))
-//             .map_err(|e| match e {
-//                 e if e.kind() == io::ErrorKind::InvalidInput => SendDatagramErrorIncoming::TooLarge,
-//                 e => SendDatagramErrorIncoming::ConnectionError(convert_connection_io_error(e)),
-//             })
-//     }
-// }
-
-// pub enum DatagramReceiver {
-//     Pending(BoxFuture<'static, io::Result<DatagramReader>>),
-//     Ready(Result<DatagramReader, ConnectionErrorIncoming>),
-// }
-
-// impl RecvDatagram for DatagramReceiver {
-//     /// The buffer type
-//     type Buffer = Bytes;
-
-//     /// Poll the connection for incoming datagrams.
-//     fn poll_incoming_datagram(
-//         &mut self,
-//         cx: &mut Context<'_>,
-//     ) -> Poll<Result<Self::Buffer, ConnectionErrorIncoming>> {
-//         self.0.poll_recv(cx).map_err(convert_connection_io_error)
-//     }
-// }
+//! [HTTP Datagrams](https://www.rfc-editor.org/rfc/rfc9297.html) over the QUIC DATAGRAM frames.
+//!
+//! To use the HTTP Datagrams, both the server and the client advertise SETTINGS_H3_DATAGRAM by
+//! calling `enable_datagram(true)` on their `h3` builders, and allow the QUIC DATAGRAM frames by
+//! setting a non-zero `max_datagram_frame_size` in their transport parameters.
+//!
+//! The datagrams are not sent until the SETTINGS frame of the peer enabling them is received, see
+//! [`H3Datagrams::peer_enabled`]. It is learnt when `h3` reads the control stream of the peer.
+//!
+//! The received datagrams are routed to the request streams by their Quarter Stream IDs. A request
+//! stream sends and receives its datagrams with the [`StreamDatagramSender`] and the
+//! [`StreamDatagramReceiver`] bound by [`H3Datagrams::bind`], which is how CONNECT-UDP and other
+//! relays are built. The datagrams of the unbound streams are left to the reader of
+//! `h3_datagram::datagram_handler::HandleDatagramsExt`.
+//!
+//! Note that `h3-datagram` 0.0.2 always encodes the Quarter Stream ID as 0 when sending, send the
+//! datagrams of other streams with [`StreamDatagramSender`].
+use std::{
+    collections::HashMap,
+    io,
+    sync::{Arc, Mutex, OnceLock, Weak},
+    task::{Context, Poll},
+};
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use futures::FutureExt;
+use gm_quic::{DatagramReader, DatagramWriter};
+use h3::quic::ConnectionErrorIncoming;
+use h3_datagram::{
+    datagram::EncodedDatagram,
+    quic_traits::{DatagramConnectionExt, RecvDatagram, SendDatagram, SendDatagramErrorIncoming},
+};
+use qbase::varint::{VARINT_MAX, VarInt, WriteVarInt, be_varint};
+use tokio::sync::{mpsc, watch};
+
+use crate::{conn::QuicConnection, error::convert_connection_io_error};
+
+/// The number of datagrams buffered for a request stream, or for the unbound streams.
+///
+/// The datagrams arrived when the buffer is full are dropped, just like lost in the network.
+pub const DATAGRAM_BUFFER_SIZE: usize = 64;
+
+/// The HTTP/3 error code `H3_DATAGRAM_ERROR`.
+const H3_DATAGRAM_ERROR: u64 = 0x33;
+
+/// The stream type of the HTTP/3 control streams.
+const CONTROL_STREAM: u64 = 0x00;
+
+/// The HTTP/3 frame type of SETTINGS.
+const SETTINGS_FRAME: u64 = 0x04;
+
+/// The identifier of the setting `SETTINGS_H3_DATAGRAM`.
+const SETTINGS_H3_DATAGRAM: u64 = 0x33;
+
+/// The data of a control stream peeked at most, to find SETTINGS_H3_DATAGRAM in its SETTINGS frame.
+const MAX_SETTINGS_SIZE: usize = 4096;
+
+impl<B: Buf> DatagramConnectionExt<B> for QuicConnection {
+    type SendDatagramHandler = DatagramSender;
+
+    type RecvDatagramHandler = DatagramReceiver;
+
+    fn send_datagram_handler(&self) -> Self::SendDatagramHandler {
+        DatagramSender(self.datagrams().clone())
+    }
+
+    fn recv_datagram_handler(&self) -> Self::RecvDatagramHandler {
+        DatagramReceiver(self.datagrams().clone())
+    }
+}
+
+/// The HTTP Datagrams of a QUIC connection, shared by the request streams.
+///
+/// Get it from [`QuicConnection::datagrams`] before the [`QuicConnection`] is moved into the `h3`
+/// connection, and clone it for the tasks handling the requests.
+#[derive(Clone)]
+pub struct H3Datagrams(Arc<Router>);
+
+struct Router {
+    connection: Arc<gm_quic::Connection>,
+    writer: OnceLock<DatagramWriter>,
+    // Whether the peer enables the HTTP Datagrams, None until its SETTINGS frame is received
+    peer_enabled: watch::Sender<Option<bool>>,
+    // Started by the first receiver, the error is returned to all the receivers
+    routing: OnceLock<Result<(), Arc<io::Error>>>,
+    // Keyed by the Quarter Stream ID, None after the routing ends
+    routes: Mutex<Option<HashMap<u64, mpsc::Sender<Bytes>>>>,
+    unbound: (
+        Mutex<Option<mpsc::Sender<Bytes>>>,
+        Mutex<mpsc::Receiver<Bytes>>,
+    ),
+}
+
+impl H3Datagrams {
+    pub(crate) fn new(connection: Arc<gm_quic::Connection>) -> Self {
+        let (unbound_tx, unbound_rx) = mpsc::channel(DATAGRAM_BUFFER_SIZE);
+        Self(Arc::new(Router {
+            connection,
+            writer: OnceLock::new(),
+            peer_enabled: watch::Sender::new(None),
+            routing: OnceLock::new(),
+            routes: Mutex::new(Some(HashMap::new())),
+            unbound: (Mutex::new(Some(unbound_tx)), Mutex::new(unbound_rx)),
+        }))
+    }
+
+    /// Wait for the SETTINGS frame of the peer, returns whether the peer enables the HTTP
+    /// Datagrams with SETTINGS_H3_DATAGRAM.
+    ///
+    /// The datagrams are not sent until the peer enables them, see
+    /// [section 2.1.1](https://www.rfc-editor.org/rfc/rfc9297.html#section-2.1.1) of
+    /// [RFC9297](https://www.rfc-editor.org/rfc/rfc9297.html). The SETTINGS frame is learnt when
+    /// `h3` reads the control stream of the peer, returns an error if the connection is closed
+    /// before that.
+    pub async fn peer_enabled(&self) -> io::Result<bool> {
+        let mut peer_enabled = self.0.peer_enabled.subscribe();
+        tokio::select! {
+            Ok(enabled) = peer_enabled.wait_for(Option::is_some) => Ok(*enabled == Some(true)),
+            _ = self.0.connection.terminated() => Err(self.0.closed_error()),
+        }
+    }
+
+    /// Peek the unidirectional stream of the peer read by `h3`, to learn whether the peer enables
+    /// the HTTP Datagrams if it is the control stream.
+    pub(crate) fn sniff_settings(&self) -> SettingsSniffer {
+        SettingsSniffer {
+            router: self.0.clone(),
+            buf: BytesMut::new(),
+        }
+    }
+
+    /// Bind the request stream `stream_id` to send and receive its datagrams.
+    ///
+    /// Returns an error if the stream is not a client-initiated bidirectional stream, if the
+    /// stream is bound already and its receiver is alive, or if the local transport parameters
+    /// disable the QUIC DATAGRAM frames.
+    ///
+    /// The datagrams received before the stream is bound are left to the unbound streams.
+    pub fn bind(
+        &self,
+        stream_id: h3::quic::StreamId,
+    ) -> io::Result<(StreamDatagramSender, StreamDatagramReceiver)> {
+        let stream_id = stream_id.into_inner();
+        if stream_id & 0b11 != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("stream {stream_id} is not a client-initiated bidirectional stream"),
+            ));
+        }
+        self.0.start_routing()?;
+
+        let quarter_stream_id = stream_id / 4;
+        let (tx, rx) = mpsc::channel(DATAGRAM_BUFFER_SIZE);
+        let mut routes = self.0.routes.lock().unwrap();
+        let Some(routes) = routes.as_mut() else {
+            return Err(self.0.closed_error());
+        };
+        if routes
+            .get(&quarter_stream_id)
+            .is_some_and(|route| !route.is_closed())
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("the datagrams of stream {stream_id} are bound already"),
+            ));
+        }
+        routes.insert(quarter_stream_id, tx);
+
+        let sender = StreamDatagramSender {
+            router: self.0.clone(),
+            quarter_stream_id: VarInt::from_u64(quarter_stream_id)
+                .expect("quarter stream id of h3 stream id is a varint"),
+        };
+        let receiver = StreamDatagramReceiver {
+            router: self.0.clone(),
+            datagrams: rx,
+        };
+        Ok((sender, receiver))
+    }
+}
+
+impl Router {
+    fn start_routing(self: &Arc<Self>) -> io::Result<()> {
+        let routing = self.routing.get_or_init(|| {
+            let reader = self.connection.unreliable_reader().map_err(Arc::new)?;
+            let unbound = self.unbound.0.lock().unwrap().take();
+            let unbound = unbound.expect("routing starts only once");
+            tokio::spawn(Self::route(Arc::downgrade(self), reader, unbound));
+            Ok(())
+        });
+        routing
+            .clone()
+            .map_err(|error| io::Error::new(error.kind(), error.to_string()))
+    }
+
+    async fn route(router: Weak<Self>, mut reader: DatagramReader, unbound: mpsc::Sender<Bytes>) {
+        while let Ok(datagram) = reader.recv().await {
+            let Some(router) = router.upgrade() else {
+                return;
+            };
+            router.dispatch(datagram, &unbound);
+        }
+        // The receivers return the error once their buffered datagrams are received
+        if let Some(router) = router.upgrade() {
+            *router.routes.lock().unwrap() = None;
+        }
+    }
+
+    fn dispatch(&self, mut datagram: Bytes, unbound: &mpsc::Sender<Bytes>) {
+        let quarter_stream_id = match be_varint(&datagram) {
+            Ok((remain, quarter_stream_id)) if quarter_stream_id <= VARINT_MAX >> 2 => {
+                datagram.advance(datagram.len() - remain.len());
+                quarter_stream_id
+            }
+            // See [section 2.1](https://www.rfc-editor.org/rfc/rfc9297.html#section-2.1)
+            // of [RFC9297](https://www.rfc-editor.org/rfc/rfc9297.html).
+            _ => {
+                self.connection
+                    .close("invalid quarter stream id", H3_DATAGRAM_ERROR);
+                return;
+            }
+        };
+
+        let mut routes = self.routes.lock().unwrap();
+        let Some(routes) = routes.as_mut() else {
+            return;
+        };
+        match routes.get(&quarter_stream_id.into_inner()) {
+            Some(route) => {
+                if let Err(mpsc::error::TrySendError::Closed(_)) = route.try_send(datagram) {
+                    routes.remove(&quarter_stream_id.into_inner());
+                }
+            }
+            None => {
+                // The unbound datagrams are left to h3-datagram, which decodes them again
+                let mut raw =
+                    BytesMut::with_capacity(quarter_stream_id.encoding_size() + datagram.len());
+                raw.put_varint(&quarter_stream_id);
+                raw.put(datagram);
+                _ = unbound.try_send(raw.freeze());
+            }
+        }
+    }
+
+    fn writer(&self) -> io::Result<Option<&DatagramWriter>> {
+        if let Some(writer) = self.writer.get() {
+            return Ok(Some(writer));
+        }
+        // The writer is ready once the transport parameters of the peer are received
+        let Some(writer) = self.connection.unreliable_writer().now_or_never() else {
+            return Ok(None);
+        };
+        let writer = writer?;
+        Ok(Some(self.writer.get_or_init(|| writer)))
+    }
+
+    fn send(&self, datagram: Bytes) -> Result<(), SendDatagramErrorIncoming> {
+        // See [section 2.1.1](https://www.rfc-editor.org/rfc/rfc9297.html#section-2.1.1)
+        // of [RFC9297](https://www.rfc-editor.org/rfc/rfc9297.html).
+        if *self.peer_enabled.borrow() != Some(true) {
+            return Err(SendDatagramErrorIncoming::NotAvailable);
+        }
+        let writer = match self.writer() {
+            Ok(Some(writer)) => writer,
+            Ok(None) => return Err(SendDatagramErrorIncoming::NotAvailable),
+            Err(error) => return Err(convert_send_error(error)),
+        };
+        match writer.max_datagram_frame_size() {
+            Ok(0) => Err(SendDatagramErrorIncoming::NotAvailable),
+            Ok(_) => writer.send_bytes(datagram).map_err(convert_send_error),
+            Err(error) => Err(convert_send_error(error)),
+        }
+    }
+
+    fn closed_error(&self) -> io::Error {
+        match (self.routing.get(), self.connection.unreliable_reader()) {
+            (Some(Err(error)), _) => io::Error::new(error.kind(), error.to_string()),
+            (_, Err(error)) => error,
+            (_, Ok(_)) => io::Error::new(io::ErrorKind::BrokenPipe, "datagrams are not routed"),
+        }
+    }
+}
+
+/// Peeks the data of a unidirectional stream of the peer, created by
+/// [`H3Datagrams::sniff_settings`].
+pub(crate) struct SettingsSniffer {
+    router: Arc<Router>,
+    buf: BytesMut,
+}
+
+impl SettingsSniffer {
+    /// Peek the `data` read from the stream, returns false if nothing more is learnt from it.
+    pub(crate) fn sniff(&mut self, data: &[u8]) -> bool {
+        self.buf.extend_from_slice(data);
+        match decode_h3_datagram_setting(&self.buf) {
+            Ok(Some(enabled)) => {
+                // Only the first SETTINGS frame counts, h3 rejects the others
+                self.router.peer_enabled.send_if_modified(|peer_enabled| {
+                    if peer_enabled.is_some() {
+                        return false;
+                    }
+                    *peer_enabled = Some(enabled);
+                    true
+                });
+                false
+            }
+            Ok(None) => self.buf.len() <= MAX_SETTINGS_SIZE,
+            Err(()) => false,
+        }
+    }
+}
+
+/// Decode the SETTINGS_H3_DATAGRAM of the peer from the front of a unidirectional stream.
+///
+/// Returns `Ok(None)` if the SETTINGS frame is not complete yet, or an error if the stream is not
+/// a control stream starting with a SETTINGS frame. The absent setting means disabled.
+fn decode_h3_datagram_setting(buf: &[u8]) -> Result<Option<bool>, ()> {
+    let Ok((remain, stream_type)) = be_varint(buf) else {
+        return Ok(None);
+    };
+    if stream_type.into_inner() != CONTROL_STREAM {
+        return Err(());
+    }
+    let Ok((remain, frame_type)) = be_varint(remain) else {
+        return Ok(None);
+    };
+    // The first frame of the control stream must be SETTINGS, h3 closes the connection otherwise
+    if frame_type.into_inner() != SETTINGS_FRAME {
+        return Err(());
+    }
+    let Ok((remain, length)) = be_varint(remain) else {
+        return Ok(None);
+    };
+    let Some(mut settings) = remain.get(..length.into_inner().min(usize::MAX as u64) as usize)
+    else {
+        return Ok(None);
+    };
+
+    let mut enabled = false;
+    while !settings.is_empty() {
+        let Ok((remain, id)) = be_varint(settings) else {
+            return Err(());
+        };
+        let Ok((remain, value)) = be_varint(remain) else {
+            return Err(());
+        };
+        if id.into_inner() == SETTINGS_H3_DATAGRAM {
+            enabled = value.into_inner() == 1;
+        }
+        settings = remain;
+    }
+    Ok(Some(enabled))
+}
+
+fn convert_send_error(error: io::Error) -> SendDatagramErrorIncoming {
+    match error.kind() {
+        io::ErrorKind::InvalidInput => SendDatagramErrorIncoming::TooLarge,
+        _ => SendDatagramErrorIncoming::ConnectionError(convert_connection_io_error(error)),
+    }
+}
+
+/// Sends the datagrams of a request stream, returned by [`H3Datagrams::bind`].
+///
+/// The sender can be cloned to send the datagrams in many tasks.
+#[derive(Clone)]
+pub struct StreamDatagramSender {
+    router: Arc<Router>,
+    quarter_stream_id: VarInt,
+}
+
+impl StreamDatagramSender {
+    /// Send the `payload` as a datagram of the request stream.
+    ///
+    /// Like the QUIC DATAGRAM frames, the datagram is not guaranteed to be delivered. Returns an
+    /// error if the peer has not enabled the HTTP Datagrams (see [`H3Datagrams::peer_enabled`]),
+    /// if the peer does not accept the QUIC DATAGRAM frames, if the datagram exceeds the
+    /// `max_datagram_frame_size` of the peer, or if the connection is closed.
+    pub fn send(&self, payload: impl Buf) -> io::Result<()> {
+        let mut datagram =
+            BytesMut::with_capacity(self.quarter_stream_id.encoding_size() + payload.remaining());
+        datagram.put_varint(&self.quarter_stream_id);
+        datagram.put(payload);
+        self.router
+            .send(datagram.freeze())
+            .map_err(|error| match error {
+                SendDatagramErrorIncoming::NotAvailable => io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "the peer does not accept HTTP datagrams",
+                ),
+                SendDatagramErrorIncoming::TooLarge => {
+                    io::Error::new(io::ErrorKind::InvalidInput, "datagram is too large")
+                }
+                SendDatagramErrorIncoming::ConnectionError(error) => io::Error::other(error),
+            })
+    }
+
+    /// The id of the request stream.
+    pub fn stream_id(&self) -> h3::quic::StreamId {
+        h3::quic::StreamId::try_from(self.quarter_stream_id.into_inner() * 4)
+            .expect("the stream id is bound from a h3 stream id")
+    }
+}
+
+/// Receives the datagrams of a request stream, returned by [`H3Datagrams::bind`].
+///
+/// Dropping the receiver unbinds the request stream.
+pub struct StreamDatagramReceiver {
+    router: Arc<Router>,
+    datagrams: mpsc::Receiver<Bytes>,
+}
+
+impl StreamDatagramReceiver {
+    /// Poll to receive the payload of a datagram of the request stream.
+    ///
+    /// Returns an error if the connection is closed.
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<Bytes>> {
+        self.datagrams
+            .poll_recv(cx)
+            .map(|datagram| datagram.ok_or_else(|| self.router.closed_error()))
+    }
+
+    /// Receive the payload of a datagram of the request stream.
+    ///
+    /// Returns an error if the connection is closed.
+    pub async fn recv(&mut self) -> io::Result<Bytes> {
+        futures::future::poll_fn(|cx| self.poll_recv(cx)).await
+    }
+}
+
+/// The [`SendDatagram`] handler of `h3-datagram`.
+pub struct DatagramSender(H3Datagrams);
+
+impl<B: Buf> SendDatagram<B> for DatagramSender {
+    fn send_datagram<T: Into<EncodedDatagram<B>>>(
+        &mut self,
+        data: T,
+    ) -> Result<(), SendDatagramErrorIncoming> {
+        let mut datagram = data.into();
+        (self.0)
+            .0
+            .send(datagram.copy_to_bytes(datagram.remaining()))
+    }
+}
+
+/// The [`RecvDatagram`] handler of `h3-datagram`, receiving the datagrams of the unbound streams.
+pub struct DatagramReceiver(H3Datagrams);
+
+impl RecvDatagram for DatagramReceiver {
+    type Buffer = Bytes;
+
+    fn poll_incoming_datagram(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Self::Buffer, ConnectionErrorIncoming>> {
+        let router = &(self.0).0;
+        if let Err(error) = router.start_routing() {
+            return Poll::Ready(Err(convert_connection_io_error(error)));
+        }
+        let mut unbound = router.unbound.1.lock().unwrap();
+        unbound.poll_recv(cx).map(|datagram| {
+            datagram.ok_or_else(|| convert_connection_io_error(router.closed_error()))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn control_stream(settings: &[(u32, u32)]) -> BytesMut {
+        let mut payload = BytesMut::new();
+        for (id, value) in settings {
+            payload.put_varint(&VarInt::from_u32(*id));
+            payload.put_varint(&VarInt::from_u32(*value));
+        }
+        let mut buf = BytesMut::new();
+        buf.put_varint(&VarInt::from_u32(CONTROL_STREAM as u32));
+        buf.put_varint(&VarInt::from_u32(SETTINGS_FRAME as u32));
+        buf.put_varint(&VarInt::from_u32(payload.len() as u32));
+        buf.extend_from_slice(&payload);
+        buf
+    }
+
+    #[test]
+    fn decode_settings() {
+        let buf = control_stream(&[(0x06, 16384), (SETTINGS_H3_DATAGRAM as u32, 1)]);
+        // the incomplete SETTINGS frame is waited for
+        for len in 0..buf.len() {
+            assert_eq!(decode_h3_datagram_setting(&buf[..len]), Ok(None));
+        }
+        assert_eq!(decode_h3_datagram_setting(&buf), Ok(Some(true)));
+
+        // the absent setting means disabled
+        let buf = control_stream(&[(0x06, 16384)]);
+        assert_eq!(decode_h3_datagram_setting(&buf), Ok(Some(false)));
+        let buf = control_stream(&[(SETTINGS_H3_DATAGRAM as u32, 0)]);
+        assert_eq!(decode_h3_datagram_setting(&buf), Ok(Some(false)));
+    }
+
+    #[test]
+    fn ignore_other_streams() {
+        // a QPACK encoder stream
+        assert!(decode_h3_datagram_setting(&[0x02, 0x3f]).is_err());
+        // a control stream not starting with SETTINGS
+        assert!(decode_h3_datagram_setting(&[0x00, 0x07, 0x01, 0x00]).is_err());
+    }
+}
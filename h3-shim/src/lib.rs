@@ -1,6 +1,7 @@
 pub mod conn;
 mod error;
 pub mod pool;
+pub mod priority;
 pub use conn::{OpenStreams, QuicConnection};
 #[cfg(feature = "unreliable")]
 pub mod ext;
@@ -10,6 +11,8 @@ pub use ext::*;
 pub mod streams;
 pub use gm_quic;
 pub use streams::{BidiStream, RecvStream, SendStream};
+#[cfg(feature = "webtransport")]
+pub mod webtransport;
 
 #[cfg(test)]
 mod tests;
@@ -0,0 +1,78 @@
+//! Map the [Extensible Priorities](https://www.rfc-editor.org/rfc/rfc9218.html) of HTTP onto the
+//! priorities of the QUIC streams.
+//!
+//! The server applies the Priority header field of a request with [`apply`], by the id of the
+//! request stream. The PRIORITY_UPDATE frames are not surfaced by `h3` yet, an application that
+//! receives them in other ways can apply the Priority Field Value carried with the same [`apply`].
+use std::io;
+
+use gm_quic::{Connection, Priority, StreamId};
+use qbase::varint::VarInt;
+
+/// The default priority of HTTP requests, urgency 3 and not incremental.
+pub const DEFAULT_PRIORITY: (u8, bool) = (Priority::DEFAULT_URGENCY, false);
+
+/// Parse the Priority Field Value, either the value of the Priority header field or the one in the
+/// PRIORITY_UPDATE frame.
+///
+/// The parameters missing or cannot be understood take the default value of HTTP, urgency 3 and
+/// not incremental.
+///
+/// See [section 4](https://www.rfc-editor.org/rfc/rfc9218.html#name-priority-parameters) of
+/// [RFC9218](https://www.rfc-editor.org/rfc/rfc9218.html).
+pub fn parse(field_value: &[u8]) -> Priority {
+    let (mut urgency, mut incremental) = DEFAULT_PRIORITY;
+    let Ok(field_value) = std::str::from_utf8(field_value) else {
+        return Priority::new(urgency, incremental);
+    };
+    for member in field_value.split(',') {
+        // the parameters of the members are ignored
+        let member = member.split(';').next().unwrap_or_default().trim();
+        let (key, value) = member.split_once('=').unwrap_or((member, "?1"));
+        match (key, value) {
+            ("u", value) => match value.parse::<u8>() {
+                Ok(value) if value <= Priority::MAX_URGENCY => urgency = value,
+                _ => {}
+            },
+            ("i", "?1") => incremental = true,
+            ("i", "?0") => incremental = false,
+            _ => {}
+        }
+    }
+    Priority::new(urgency, incremental)
+}
+
+/// Apply the Priority Field Value on the request stream `id` of the `connection`.
+///
+/// Returns an error if the connection is closed.
+pub fn apply(
+    connection: &Connection,
+    id: h3::quic::StreamId,
+    field_value: &[u8],
+) -> io::Result<()> {
+    let sid = VarInt::from_u64(id.into_inner()).expect("h3 stream id is a varint");
+    connection.set_stream_priority(StreamId::from(sid), parse(field_value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let parse = |field_value: &str| {
+            let priority = parse(field_value.as_bytes());
+            (priority.urgency(), priority.is_incremental())
+        };
+        assert_eq!(parse(""), DEFAULT_PRIORITY);
+        assert_eq!(parse("u=5"), (5, false));
+        assert_eq!(parse("i"), (3, true));
+        assert_eq!(parse("u=0, i"), (0, true));
+        assert_eq!(parse("i=?1,u=1"), (1, true));
+        assert_eq!(parse("u=2, i=?0"), (2, false));
+        assert_eq!(parse("u=1;foo=bar, x=7"), (1, false));
+        // out of range or invalid values are ignored
+        assert_eq!(parse("u=8, i=1"), DEFAULT_PRIORITY);
+        assert_eq!(parse("u=-1, i=?2"), DEFAULT_PRIORITY);
+    }
+}
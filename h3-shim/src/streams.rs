@@ -99,7 +99,12 @@ impl<B: bytes::Buf> h3::quic::SendStreamUnframed<B> for SendStream<B> {
 
 pub struct RecvStream {
     reader: StreamReader,
+    // The data read ahead before the stream is handed to h3, returned first
+    read_ahead: bytes::Bytes,
     recv_id: h3::quic::StreamId,
+    // Peeks the data returned to h3, until nothing more is learnt from it
+    #[cfg(feature = "unreliable")]
+    sniffer: Option<crate::ext::SettingsSniffer>,
 }
 
 impl RecvStream {
@@ -107,19 +112,31 @@ impl RecvStream {
         let sid = u64::from(sid);
         Self {
             reader,
+            read_ahead: bytes::Bytes::new(),
             recv_id: h3::quic::StreamId::try_from(sid).expect("unreachable"),
+            #[cfg(feature = "unreliable")]
+            sniffer: None,
         }
     }
-}
 
-impl h3::quic::RecvStream for RecvStream {
-    type Buf = bytes::Bytes;
+    pub(crate) fn with_read_ahead(mut self, read_ahead: bytes::Bytes) -> Self {
+        self.read_ahead = read_ahead;
+        self
+    }
 
-    #[inline]
-    fn poll_data(
+    #[cfg(feature = "unreliable")]
+    pub(crate) fn with_settings_sniffer(mut self, sniffer: crate::ext::SettingsSniffer) -> Self {
+        self.sniffer = Some(sniffer);
+        self
+    }
+
+    fn poll_read_data(
         &mut self,
         cx: &mut Context<'_>,
-    ) -> Poll<Result<Option<Self::Buf>, StreamErrorIncoming>> {
+    ) -> Poll<Result<Option<bytes::Bytes>, StreamErrorIncoming>> {
+        if !self.read_ahead.is_empty() {
+            return Poll::Ready(Ok(Some(std::mem::take(&mut self.read_ahead))));
+        }
         let mut uninit_buf = [MaybeUninit::uninit(); 4096];
         let mut read_buf = ReadBuf::uninit(&mut uninit_buf);
         match ready!(Pin::new(&mut self.reader).poll_read(cx, &mut read_buf)) {
@@ -133,6 +150,25 @@ impl h3::quic::RecvStream for RecvStream {
             Err(e) => Poll::Ready(Err(convert_stream_io_error(e))),
         }
     }
+}
+
+impl h3::quic::RecvStream for RecvStream {
+    type Buf = bytes::Bytes;
+
+    #[inline]
+    fn poll_data(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<Self::Buf>, StreamErrorIncoming>> {
+        let data = ready!(self.poll_read_data(cx));
+        #[cfg(feature = "unreliable")]
+        if let (Ok(Some(bytes)), Some(sniffer)) = (&data, &mut self.sniffer) {
+            if !sniffer.sniff(bytes) {
+                self.sniffer = None;
+            }
+        }
+        Poll::Ready(data)
+    }
 
     #[inline]
     fn stop_sending(&mut self, error_code: u64) {
@@ -160,6 +196,11 @@ impl<B> BidiStream<B> {
             recv: RecvStream::new(sid, reader),
         }
     }
+
+    pub(crate) fn with_read_ahead(mut self, read_ahead: bytes::Bytes) -> Self {
+        self.recv = self.recv.with_read_ahead(read_ahead);
+        self
+    }
 }
 
 impl<B> h3::quic::RecvStream for BidiStream<B> {
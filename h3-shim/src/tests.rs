@@ -59,4 +59,257 @@ where
     })
 }
 
-// TODO
+#[cfg(feature = "unreliable")]
+#[test]
+fn datagrams_routed_by_stream() -> Result<(), Error> {
+    use bytes::Bytes;
+    use gm_quic::{QuicClient, ToCertificate, handy::*};
+
+    const CA_CERT: &[u8] = include_bytes!("../../tests/keychain/localhost/ca.cert");
+    const SERVER_CERT: &[u8] = include_bytes!("../../tests/keychain/localhost/server.cert");
+    const SERVER_KEY: &[u8] = include_bytes!("../../tests/keychain/localhost/server.key");
+
+    async fn serve_datagram_echo(listeners: Arc<QuicListeners>) -> Result<(), Error> {
+        loop {
+            let (connection, ..) = listeners.accept().await?;
+            let connection = crate::QuicConnection::new(connection);
+            let datagrams = connection.datagrams().clone();
+            let mut h3_connection = h3::server::builder()
+                .enable_datagram(true)
+                .build::<_, Bytes>(connection)
+                .await?;
+            tokio::spawn(async move {
+                while let Ok(Some(resolver)) = h3_connection.accept().await {
+                    let datagrams = datagrams.clone();
+                    tokio::spawn(async move {
+                        let (_request, mut stream) = resolver.resolve_request().await?;
+                        assert!(datagrams.peer_enabled().await?);
+                        let (sender, mut receiver) = datagrams.bind(stream.id())?;
+                        let response = http::Response::builder().status(200).body(())?;
+                        stream.send_response(response).await?;
+                        while let Ok(payload) = receiver.recv().await {
+                            sender.send(payload)?;
+                        }
+                        Result::<(), Error>::Ok(())
+                    });
+                }
+            });
+        }
+    }
+
+    let launch_server = || {
+        let mut parameters = server_parameters();
+        parameters.set_max_datagram_frame_size(1200u32);
+        let listeners = QuicListeners::builder()?
+            .without_client_cert_verifier()
+            .with_parameters(parameters)
+            .listen(128);
+        listeners.add_server(
+            "localhost",
+            SERVER_CERT,
+            SERVER_KEY,
+            ["inet://127.0.0.1/alloc"],
+            None,
+        )?;
+        Ok((listeners.clone(), serve_datagram_echo(listeners)))
+    };
+
+    let launch_client = |server_addr: SocketAddr| async move {
+        let mut roots = rustls::RootCertStore::empty();
+        roots.add_parsable_certificates(CA_CERT.to_certificate());
+        let mut parameters = client_parameters();
+        parameters.set_max_datagram_frame_size(1200u32);
+        let client = QuicClient::builder()
+            .with_root_certificates(roots)
+            .with_parameters(parameters)
+            .without_cert()
+            .build();
+
+        let connection = crate::QuicConnection::new(client.connect("localhost", server_addr)?);
+        let datagrams = connection.datagrams().clone();
+        let (mut h3_connection, mut send_request) = h3::client::builder()
+            .enable_datagram(true)
+            .build::<_, _, Bytes>(connection)
+            .await?;
+        tokio::spawn(async move { h3_connection.wait_idle().await });
+        // The datagrams are not sent until the SETTINGS frame of the server is received
+        assert!(datagrams.peer_enabled().await?);
+
+        let mut streams = vec![];
+        for _ in 0..2 {
+            let request = http::Request::builder()
+                .uri("https://localhost/")
+                .body(())?;
+            let mut stream = send_request.send_request(request).await?;
+            let (sender, receiver) = datagrams.bind(stream.id())?;
+            assert!(datagrams.bind(stream.id()).is_err());
+            assert_eq!(stream.recv_response().await?.status(), 200);
+            streams.push((stream, sender, receiver));
+        }
+
+        for (stream, sender, receiver) in &mut streams {
+            let payload = Bytes::from(format!("datagram of stream {}", stream.id()));
+            // The datagrams sent before the server binds the stream are not echoed
+            let echo = loop {
+                sender.send(payload.clone())?;
+                if let Ok(echo) = time::timeout(Duration::from_millis(100), receiver.recv()).await {
+                    break echo?;
+                }
+            };
+            assert_eq!(echo, payload);
+        }
+        Ok(())
+    };
+
+    run_serially(launch_server, launch_client)
+}
+
+#[cfg(feature = "webtransport")]
+#[test]
+fn webtransport_session() -> Result<(), Error> {
+    use bytes::Bytes;
+    use gm_quic::{QuicClient, ToCertificate, handy::*};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use crate::webtransport::{self, SessionClose, WebTransport, WebTransportSession};
+
+    const CA_CERT: &[u8] = include_bytes!("../../tests/keychain/localhost/ca.cert");
+    const SERVER_CERT: &[u8] = include_bytes!("../../tests/keychain/localhost/server.cert");
+    const SERVER_KEY: &[u8] = include_bytes!("../../tests/keychain/localhost/server.key");
+
+    async fn serve_session(session: Arc<WebTransportSession>) -> Result<(), Error> {
+        let (_sid, mut welcome) = session.open_uni().await?;
+        welcome.write_all(b"welcome").await?;
+        welcome.shutdown().await?;
+
+        tokio::spawn({
+            let session = session.clone();
+            async move {
+                while let Ok(datagram) = session.recv_datagram().await {
+                    _ = session.send_datagram(datagram);
+                }
+            }
+        });
+
+        while let Ok((_sid, (mut reader, mut writer))) = session.accept_bi().await {
+            let session = session.clone();
+            tokio::spawn(async move {
+                let mut data = vec![];
+                reader.read_to_end(&mut data).await?;
+                if data == b"close" {
+                    return session.close(42, "done").await;
+                }
+                writer.write_all(&data).await?;
+                writer.shutdown().await
+            });
+        }
+        Ok(())
+    }
+
+    async fn serve_webtransport(listeners: Arc<QuicListeners>) -> Result<(), Error> {
+        loop {
+            let (connection, ..) = listeners.accept().await?;
+            let connection = crate::QuicConnection::new(connection);
+            let webtransport = connection.webtransport().clone();
+            let mut h3_connection = webtransport::server_builder(1)
+                .build::<_, Bytes>(connection)
+                .await?;
+            tokio::spawn(async move {
+                while let Ok(Some(resolver)) = h3_connection.accept().await {
+                    let webtransport = webtransport.clone();
+                    tokio::spawn(async move {
+                        let (request, stream) = resolver.resolve_request().await?;
+                        assert!(WebTransport::is_session_request(&request));
+                        let session = webtransport.accept(&request, stream).await?;
+                        serve_session(Arc::new(session)).await
+                    });
+                }
+            });
+        }
+    }
+
+    let launch_server = || {
+        let mut parameters = server_parameters();
+        parameters.set_max_datagram_frame_size(1200u32);
+        let listeners = QuicListeners::builder()?
+            .without_client_cert_verifier()
+            .with_parameters(parameters)
+            .listen(128);
+        listeners.add_server(
+            "localhost",
+            SERVER_CERT,
+            SERVER_KEY,
+            ["inet://127.0.0.1/alloc"],
+            None,
+        )?;
+        Ok((listeners.clone(), serve_webtransport(listeners)))
+    };
+
+    let launch_client = |server_addr: SocketAddr| async move {
+        let mut roots = rustls::RootCertStore::empty();
+        roots.add_parsable_certificates(CA_CERT.to_certificate());
+        let mut parameters = client_parameters();
+        parameters.set_max_datagram_frame_size(1200u32);
+        let client = QuicClient::builder()
+            .with_root_certificates(roots)
+            .with_parameters(parameters)
+            .without_cert()
+            .build();
+
+        let connection = crate::QuicConnection::new(client.connect("localhost", server_addr)?);
+        let webtransport = connection.webtransport().clone();
+        let (mut h3_connection, mut send_request) = webtransport::client_builder()
+            .build::<_, _, Bytes>(connection)
+            .await?;
+        tokio::spawn(async move { h3_connection.wait_idle().await });
+
+        let session = webtransport
+            .connect(&mut send_request, "https://localhost/chat".parse()?)
+            .await?;
+
+        let (_sid, mut welcome) = session.accept_uni().await?;
+        let mut data = vec![];
+        welcome.read_to_end(&mut data).await?;
+        assert_eq!(data, b"welcome");
+
+        let (_sid, (mut reader, mut writer)) = session.open_bi().await?;
+        writer.write_all(b"echo").await?;
+        writer.shutdown().await?;
+        let mut data = vec![];
+        reader.read_to_end(&mut data).await?;
+        assert_eq!(data, b"echo");
+
+        let payload = Bytes::from_static(b"datagram of the session");
+        // The datagrams sent before the server binds the session are not echoed
+        let echo = loop {
+            session.send_datagram(payload.clone())?;
+            if let Ok(echo) =
+                time::timeout(Duration::from_millis(100), session.recv_datagram()).await
+            {
+                break echo?;
+            }
+        };
+        assert_eq!(echo, payload);
+
+        // the stream left open is reset when the session is closed
+        let (_sid, (mut pending_reader, mut pending_writer)) = session.open_bi().await?;
+        pending_writer.write_all(b"pending").await?;
+
+        let (_sid, (_reader, mut writer)) = session.open_bi().await?;
+        writer.write_all(b"close").await?;
+        // the stream may be reset before it is acknowledged, with the session closed by the server
+        _ = writer.shutdown().await;
+        let close = SessionClose {
+            error_code: 42,
+            reason: "done".to_owned(),
+        };
+        assert_eq!(session.closed().await, close);
+        assert!(session.accept_bi().await.is_err());
+        assert!(session.open_uni().await.is_err());
+        assert!(pending_reader.read_to_end(&mut vec![]).await.is_err());
+        assert!(pending_writer.write_all(b"more").await.is_err());
+        Ok(())
+    };
+
+    run_serially(launch_server, launch_client)
+}
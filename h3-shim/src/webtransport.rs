@@ -0,0 +1,1064 @@
+//! [WebTransport over HTTP/3](https://datatracker.ietf.org/doc/draft-ietf-webtrans-http3/).
+//!
+//! A WebTransport session is established by an extended CONNECT request whose `:protocol` is
+//! `webtransport`, and is identified by the ID of the CONNECT stream. The server advertises the
+//! support of WebTransport with the `h3` builder returned by [`server_builder`], accepts the
+//! sessions with [`WebTransport::accept`], and the client establishes the sessions with
+//! [`WebTransport::connect`] on the `h3` connection built by [`client_builder`]. Both sides also
+//! need a non-zero `max_datagram_frame_size` in their transport parameters for the datagrams.
+//!
+//! The streams of the sessions, which start with the WebTransport stream signal values, are picked
+//! out of the [`QuicConnection`] before they are accepted by `h3`, and the datagrams of the sessions
+//! are routed by the [`H3Datagrams`].
+//!
+//! Note that the client builder of `h3` 0.0.8 cannot advertise SETTINGS_ENABLE_WEBTRANSPORT, this
+//! is fine with the servers built by this crate, but may be refused by other servers.
+//!
+//! [`QuicConnection`]: crate::QuicConnection
+use std::{
+    collections::HashMap,
+    io,
+    pin::pin,
+    sync::{Arc, Mutex, Weak},
+    time::Duration,
+};
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use futures::{
+    future::{self, BoxFuture, Either},
+    stream,
+};
+use gm_quic::{StreamId, StreamReader, StreamWriter};
+use h3::{error::StreamError, ext::Protocol, quic::ConnectionErrorIncoming};
+use http::{Method, Request, Response, StatusCode, Uri};
+use qbase::varint::{VarInt, WriteVarInt, be_varint};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    sync::{mpsc, watch},
+    time::{self, Instant},
+};
+
+use crate::{
+    OpenStreams,
+    conn::{
+        AcceptBiStreams, AcceptUniStreams, AcceptedBiStream, AcceptedUniStream,
+        sid_exceed_limit_error,
+    },
+    error::convert_connection_io_error,
+    ext::{H3Datagrams, StreamDatagramReceiver, StreamDatagramSender},
+    streams::{BidiStream, RecvStream, SendStream},
+};
+
+/// The signal value starting the bidirectional streams of the sessions.
+const WEBTRANSPORT_STREAM: u64 = 0x41;
+/// The stream type of the unidirectional streams of the sessions.
+const WEBTRANSPORT_UNI_STREAM: u64 = 0x54;
+
+/// The capsule type of `CLOSE_WEBTRANSPORT_SESSION`.
+const CLOSE_WEBTRANSPORT_SESSION: u64 = 0x2843;
+/// The capsule type of `DRAIN_WEBTRANSPORT_SESSION`.
+const DRAIN_WEBTRANSPORT_SESSION: u64 = 0x78ae;
+/// The maximum length of the error message in `CLOSE_WEBTRANSPORT_SESSION`.
+const MAX_CLOSE_MESSAGE_LEN: usize = 1024;
+/// The maximum length of the value of the capsules received, the session receiving a longer
+/// capsule is closed with `H3_GENERAL_PROTOCOL_ERROR`.
+pub const MAX_CAPSULE_SIZE: usize = 16 * 1024;
+
+/// The error code resetting the streams of the sessions which are gone.
+pub const H3_WEBTRANSPORT_SESSION_GONE: u64 = 0x170d7b68;
+/// The error code resetting the buffered streams, when too many streams arrive before their
+/// sessions are established.
+pub const H3_WEBTRANSPORT_BUFFERED_STREAM_REJECTED: u64 = 0x3994bd84;
+
+/// The number of streams buffered for the sessions not established yet.
+///
+/// The streams may arrive before the CONNECT requests of their sessions are handled, the streams
+/// arrived when the buffer is full are rejected with [`H3_WEBTRANSPORT_BUFFERED_STREAM_REJECTED`].
+pub const MAX_BUFFERED_STREAMS: usize = 32;
+
+/// The number of sessions not established yet, for which the streams are buffered.
+pub const MAX_PENDING_SESSIONS: usize = 8;
+
+/// How long the streams are buffered for a session not established yet, the streams are rejected
+/// with [`H3_WEBTRANSPORT_BUFFERED_STREAM_REJECTED`] if the session is not established in time.
+pub const BUFFERED_STREAM_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// The HTTP/3 error code `H3_GENERAL_PROTOCOL_ERROR`.
+const H3_GENERAL_PROTOCOL_ERROR: u64 = 0x101;
+/// The HTTP/3 error code `H3_ID_ERROR`.
+const H3_ID_ERROR: u64 = 0x108;
+
+/// The `h3` server builder advertising the support of WebTransport, which accepts at most
+/// `max_sessions` sessions per connection.
+pub fn server_builder(max_sessions: u64) -> h3::server::Builder {
+    let mut builder = h3::server::builder();
+    builder
+        .enable_webtransport(true)
+        .enable_extended_connect(true)
+        .enable_datagram(true)
+        .max_webtransport_sessions(max_sessions);
+    builder
+}
+
+/// The `h3` client builder able to establish WebTransport sessions.
+pub fn client_builder() -> h3::client::Builder {
+    let mut builder = h3::client::builder();
+    builder.enable_extended_connect(true).enable_datagram(true);
+    builder
+}
+
+type IncomingBiStream = (StreamId, (StreamReader, StreamWriter));
+type IncomingUniStream = (StreamId, StreamReader);
+
+enum IncomingStream {
+    Bi(IncomingBiStream),
+    Uni(IncomingUniStream),
+}
+
+impl IncomingStream {
+    fn id(&self) -> StreamId {
+        match self {
+            IncomingStream::Bi((sid, _)) | IncomingStream::Uni((sid, _)) => *sid,
+        }
+    }
+
+    fn reject(self, error_code: u64) {
+        match self {
+            IncomingStream::Bi((_, (mut reader, mut writer))) => {
+                reader.stop(error_code);
+                writer.cancel(error_code);
+            }
+            IncomingStream::Uni((_, mut reader)) => reader.stop(error_code),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct SessionStreams {
+    bi: mpsc::UnboundedSender<IncomingBiStream>,
+    uni: mpsc::UnboundedSender<IncomingUniStream>,
+}
+
+impl SessionStreams {
+    fn deliver(&self, stream: IncomingStream) {
+        let undelivered = match stream {
+            IncomingStream::Bi(bi) => self.bi.send(bi).err().map(|e| IncomingStream::Bi(e.0)),
+            IncomingStream::Uni(uni) => self.uni.send(uni).err().map(|e| IncomingStream::Uni(e.0)),
+        };
+        if let Some(stream) = undelivered {
+            stream.reject(H3_WEBTRANSPORT_SESSION_GONE);
+        }
+    }
+}
+
+enum Route {
+    /// The streams arrived before the session is established, evicted after `expire`.
+    Buffered {
+        streams: Vec<IncomingStream>,
+        expire: Instant,
+    },
+    /// The session is established, the streams of the session are reset when it is terminated.
+    Established {
+        streams: SessionStreams,
+        associated: Vec<StreamId>,
+    },
+    /// The session is terminated, but its CONNECT stream is not closed yet.
+    Gone,
+}
+
+#[derive(Default)]
+struct Routes {
+    routes: HashMap<u64, Route>,
+    /// The number of the streams buffered.
+    buffered: usize,
+    /// The number of the sessions not established yet, for which the streams are buffered.
+    pending: usize,
+}
+
+impl Routes {
+    fn take_buffered(&mut self, session_id: u64) -> Vec<IncomingStream> {
+        match self.routes.remove(&session_id) {
+            Some(Route::Buffered { streams, .. }) => {
+                self.buffered -= streams.len();
+                self.pending -= 1;
+                streams
+            }
+            Some(route) => {
+                self.routes.insert(session_id, route);
+                vec![]
+            }
+            None => vec![],
+        }
+    }
+}
+
+/// The QUIC stream ID of the CONNECT stream of the session.
+fn connect_stream_id(session_id: u64) -> StreamId {
+    StreamId::from(VarInt::from_u64(session_id).expect("session id is a stream id"))
+}
+
+/// The WebTransport sessions of a QUIC connection.
+///
+/// Get it from [`QuicConnection::webtransport`] before the [`QuicConnection`] is moved into the
+/// `h3` connection, and clone it for the tasks handling the requests.
+///
+/// [`QuicConnection`]: crate::QuicConnection
+/// [`QuicConnection::webtransport`]: crate::QuicConnection::webtransport
+#[derive(Clone)]
+pub struct WebTransport {
+    connection: Arc<gm_quic::Connection>,
+    datagrams: H3Datagrams,
+    routes: Arc<Mutex<Routes>>,
+}
+
+impl WebTransport {
+    /// Pick the streams of the sessions out of the connection, the other streams are returned to
+    /// be accepted by `h3`, with the data read when picking.
+    pub(crate) fn intercept(
+        connection: Arc<gm_quic::Connection>,
+        datagrams: H3Datagrams,
+    ) -> (Self, AcceptBiStreams, AcceptUniStreams) {
+        let webtransport = Self {
+            connection,
+            datagrams,
+            routes: Default::default(),
+        };
+
+        let (bi_tx, bi_rx) = mpsc::unbounded_channel();
+        tokio::spawn(webtransport.clone().pick_bi_streams(bi_tx));
+        let (uni_tx, uni_rx) = mpsc::unbounded_channel();
+        tokio::spawn(webtransport.clone().pick_uni_streams(uni_tx));
+
+        let accept_bi = AcceptBiStreams::from_stream(stream::unfold(
+            (bi_rx, None),
+            |(mut rx, error)| async move {
+                let (accepted, error) = next_accepted(&mut rx, error).await;
+                Some((accepted, (rx, error)))
+            },
+        ));
+        let accept_uni = AcceptUniStreams::from_stream(stream::unfold(
+            (uni_rx, None),
+            |(mut rx, error)| async move {
+                let (accepted, error) = next_accepted(&mut rx, error).await;
+                Some((accepted, (rx, error)))
+            },
+        ));
+        (webtransport, accept_bi, accept_uni)
+    }
+
+    async fn pick_bi_streams(
+        self,
+        h3: mpsc::UnboundedSender<Result<AcceptedBiStream, ConnectionErrorIncoming>>,
+    ) {
+        loop {
+            let accept = pin!(self.connection.accept_bi_stream());
+            let accepted = match future::select(accept, pin!(h3.closed())).await {
+                Either::Left((accepted, _)) => accepted,
+                // the QuicConnection is dropped, no stream will be accepted anymore
+                Either::Right(_) => return,
+            };
+            let (sid, (mut reader, writer)) = match accepted {
+                Ok(Some(stream)) => stream,
+                Ok(None) => unreachable!("the stream IDs of the peer are limited"),
+                Err(error) => {
+                    _ = h3.send(Err(convert_connection_io_error(error)));
+                    return;
+                }
+            };
+            let webtransport = self.clone();
+            let h3 = h3.clone();
+            // the streams are picked concurrently, a slow stream should not block the others
+            tokio::spawn(async move {
+                let mut read_ahead = BytesMut::new();
+                match read_session_id(&mut reader, &mut read_ahead, WEBTRANSPORT_STREAM).await {
+                    Some(session_id) => {
+                        webtransport.route(session_id, IncomingStream::Bi((sid, (reader, writer))))
+                    }
+                    None => _ = h3.send(Ok((sid, (reader, writer), read_ahead.freeze()))),
+                }
+            });
+        }
+    }
+
+    async fn pick_uni_streams(
+        self,
+        h3: mpsc::UnboundedSender<Result<AcceptedUniStream, ConnectionErrorIncoming>>,
+    ) {
+        loop {
+            let accept = pin!(self.connection.accept_uni_stream());
+            let accepted = match future::select(accept, pin!(h3.closed())).await {
+                Either::Left((accepted, _)) => accepted,
+                // the QuicConnection is dropped, no stream will be accepted anymore
+                Either::Right(_) => return,
+            };
+            let (sid, mut reader) = match accepted {
+                Ok(Some(stream)) => stream,
+                Ok(None) => unreachable!("the stream IDs of the peer are limited"),
+                Err(error) => {
+                    _ = h3.send(Err(convert_connection_io_error(error)));
+                    return;
+                }
+            };
+            let webtransport = self.clone();
+            let h3 = h3.clone();
+            tokio::spawn(async move {
+                let mut read_ahead = BytesMut::new();
+                match read_session_id(&mut reader, &mut read_ahead, WEBTRANSPORT_UNI_STREAM).await {
+                    Some(session_id) => {
+                        webtransport.route(session_id, IncomingStream::Uni((sid, reader)))
+                    }
+                    None => _ = h3.send(Ok((sid, reader, read_ahead.freeze()))),
+                }
+            });
+        }
+    }
+
+    fn route(&self, session_id: u64, stream: IncomingStream) {
+        // the session ID is the ID of the CONNECT stream, a client-initiated bidirectional stream
+        if session_id & 0b11 != 0 {
+            stream.reject(H3_WEBTRANSPORT_SESSION_GONE);
+            self.connection
+                .close("invalid WebTransport session ID", H3_ID_ERROR);
+            return;
+        }
+
+        let mut routes = self.routes.lock().unwrap();
+        let Routes {
+            routes,
+            buffered,
+            pending,
+        } = &mut *routes;
+        match routes.get_mut(&session_id) {
+            Some(Route::Established {
+                streams,
+                associated,
+            }) => {
+                associated.push(stream.id());
+                streams.deliver(stream);
+            }
+            Some(Route::Gone) => stream.reject(H3_WEBTRANSPORT_SESSION_GONE),
+            Some(Route::Buffered { streams, .. }) if *buffered < MAX_BUFFERED_STREAMS => {
+                streams.push(stream);
+                *buffered += 1;
+            }
+            None if *buffered < MAX_BUFFERED_STREAMS && *pending < MAX_PENDING_SESSIONS => {
+                let expire = Instant::now() + BUFFERED_STREAM_TIMEOUT;
+                let streams = vec![stream];
+                routes.insert(session_id, Route::Buffered { streams, expire });
+                *buffered += 1;
+                *pending += 1;
+                tokio::spawn(evict_buffered(
+                    Arc::downgrade(&self.routes),
+                    session_id,
+                    expire,
+                ));
+            }
+            // no entry is created for the rejected streams
+            _ => stream.reject(H3_WEBTRANSPORT_BUFFERED_STREAM_REJECTED),
+        }
+    }
+
+    fn establish(
+        &self,
+        session_id: u64,
+    ) -> (
+        mpsc::UnboundedReceiver<IncomingBiStream>,
+        mpsc::UnboundedReceiver<IncomingUniStream>,
+    ) {
+        let (bi, bi_rx) = mpsc::unbounded_channel();
+        let (uni, uni_rx) = mpsc::unbounded_channel();
+        let streams = SessionStreams { bi, uni };
+
+        let mut routes = self.routes.lock().unwrap();
+        let buffered = routes.take_buffered(session_id);
+        let associated = buffered.iter().map(IncomingStream::id).collect();
+        buffered
+            .into_iter()
+            .for_each(|stream| streams.deliver(stream));
+        let route = Route::Established {
+            streams,
+            associated,
+        };
+        routes.routes.insert(session_id, route);
+        (bi_rx, uni_rx)
+    }
+
+    /// Associate the stream opened locally with the session, returns `false` if the session is
+    /// terminated.
+    fn associate(&self, session_id: u64, sid: StreamId) -> bool {
+        let mut routes = self.routes.lock().unwrap();
+        match routes.routes.get_mut(&session_id) {
+            Some(Route::Established { associated, .. }) => {
+                associated.push(sid);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Terminate the session, the streams of the session are reset with
+    /// [`H3_WEBTRANSPORT_SESSION_GONE`].
+    fn terminate(&self, session_id: u64) {
+        let mut routes = self.routes.lock().unwrap();
+        let buffered = routes.take_buffered(session_id);
+        let associated = match routes.routes.insert(session_id, Route::Gone) {
+            Some(Route::Established { associated, .. }) => associated,
+            _ => vec![],
+        };
+        drop(routes);
+
+        buffered
+            .into_iter()
+            .for_each(|stream| stream.reject(H3_WEBTRANSPORT_SESSION_GONE));
+        for sid in associated {
+            _ = self
+                .connection
+                .cancel_stream(sid, H3_WEBTRANSPORT_SESSION_GONE);
+        }
+    }
+
+    /// Forget the session whose CONNECT stream is closed.
+    ///
+    /// The streams of the session arriving later are buffered as if for an unknown session, and
+    /// are rejected when they expire.
+    fn forget(&self, session_id: u64) {
+        let mut routes = self.routes.lock().unwrap();
+        if let Some(Route::Gone) = routes.routes.get(&session_id) {
+            routes.routes.remove(&session_id);
+        }
+    }
+
+    /// Returns whether the `request` is an extended CONNECT request establishing a WebTransport
+    /// session.
+    pub fn is_session_request(request: &Request<()>) -> bool {
+        request.method() == Method::CONNECT
+            && request.extensions().get::<Protocol>() == Some(&Protocol::WEB_TRANSPORT)
+    }
+
+    /// Accept the WebTransport session requested by the `request` on the request `stream`.
+    ///
+    /// The request which is not a WebTransport session request is answered with
+    /// `400 Bad Request`, and an error is returned.
+    pub async fn accept(
+        &self,
+        request: &Request<()>,
+        stream: h3::server::RequestStream<BidiStream<Bytes>, Bytes>,
+    ) -> io::Result<WebTransportSession> {
+        let session_id = stream.id();
+        let (mut send, recv) = stream.split();
+        if !Self::is_session_request(request) {
+            let response = Response::builder()
+                .status(StatusCode::BAD_REQUEST)
+                .body(())
+                .expect("a valid response");
+            send.send_response(response)
+                .await
+                .map_err(io::Error::other)?;
+            send.finish().await.map_err(io::Error::other)?;
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the request is not a WebTransport session request",
+            ));
+        }
+
+        let (datagram_sender, datagram_receiver) = self.datagrams.bind(session_id)?;
+        let incoming = self.establish(session_id.into_inner());
+        let response = Response::builder()
+            .status(StatusCode::OK)
+            // required by the browsers implementing the draft-02
+            .header("sec-webtransport-http3-draft", "draft02")
+            .body(())
+            .expect("a valid response");
+        if let Err(error) = send.send_response(response).await {
+            self.terminate(session_id.into_inner());
+            return Err(io::Error::other(error));
+        }
+
+        Ok(WebTransportSession::new(
+            self.clone(),
+            session_id,
+            Box::new(send),
+            Box::new(recv),
+            incoming,
+            (datagram_sender, datagram_receiver),
+        ))
+    }
+
+    /// Establish a WebTransport session to the `uri` with the `h3` connection.
+    ///
+    /// Returns an error if the server responds with a status other than `2xx`.
+    pub async fn connect(
+        &self,
+        send_request: &mut h3::client::SendRequest<OpenStreams, Bytes>,
+        uri: Uri,
+    ) -> io::Result<WebTransportSession> {
+        let mut request = Request::builder()
+            .method(Method::CONNECT)
+            .uri(uri)
+            .body(())
+            .map_err(io::Error::other)?;
+        request.extensions_mut().insert(Protocol::WEB_TRANSPORT);
+        let mut stream = send_request
+            .send_request(request)
+            .await
+            .map_err(io::Error::other)?;
+        let session_id = stream.id();
+
+        let (datagram_sender, datagram_receiver) = self.datagrams.bind(session_id)?;
+        // the streams of the session may arrive before the response
+        let incoming = self.establish(session_id.into_inner());
+        let response = match stream.recv_response().await {
+            Ok(response) if response.status().is_success() => response,
+            Ok(response) => {
+                self.terminate(session_id.into_inner());
+                return Err(io::Error::new(
+                    io::ErrorKind::ConnectionRefused,
+                    format!(
+                        "the WebTransport session is refused with {}",
+                        response.status()
+                    ),
+                ));
+            }
+            Err(error) => {
+                self.terminate(session_id.into_inner());
+                return Err(io::Error::other(error));
+            }
+        };
+        _ = response;
+
+        let (send, recv) = stream.split();
+        Ok(WebTransportSession::new(
+            self.clone(),
+            session_id,
+            Box::new(send),
+            Box::new(recv),
+            incoming,
+            (datagram_sender, datagram_receiver),
+        ))
+    }
+}
+
+type Accepted<T> = Result<T, ConnectionErrorIncoming>;
+
+async fn next_accepted<T>(
+    rx: &mut mpsc::UnboundedReceiver<Accepted<T>>,
+    error: Option<ConnectionErrorIncoming>,
+) -> (Accepted<T>, Option<ConnectionErrorIncoming>) {
+    let accepted = match rx.recv().await {
+        Some(accepted) => accepted,
+        // the connection is closed, keep returning the error like accepting from the connection
+        None => Err(error.clone().unwrap_or_else(|| {
+            ConnectionErrorIncoming::Undefined(Arc::new(io::Error::other(
+                "the streams of the connection are no longer accepted",
+            )))
+        })),
+    };
+    let error = accepted.as_ref().err().cloned().or(error);
+    (accepted, error)
+}
+
+/// Reject the streams buffered for the session, if it is not established before `expire`.
+async fn evict_buffered(routes: Weak<Mutex<Routes>>, session_id: u64, expire: Instant) {
+    time::sleep_until(expire).await;
+    let Some(routes) = routes.upgrade() else {
+        return;
+    };
+    let mut routes = routes.lock().unwrap();
+    // the streams buffered again after the eviction expire later
+    let expired = matches!(
+        routes.routes.get(&session_id),
+        Some(Route::Buffered { expire: buffered_expire, .. }) if *buffered_expire <= expire
+    );
+    if expired {
+        let streams = routes.take_buffered(session_id);
+        drop(routes);
+        streams
+            .into_iter()
+            .for_each(|stream| stream.reject(H3_WEBTRANSPORT_BUFFERED_STREAM_REJECTED));
+    }
+}
+
+/// Read the stream header `signal`, followed by the session ID.
+///
+/// Returns `None` if the stream is not a stream of the sessions, all the data read is left in
+/// `read_ahead`.
+async fn read_session_id(
+    reader: &mut StreamReader,
+    read_ahead: &mut BytesMut,
+    signal: u64,
+) -> Option<u64> {
+    if read_varint(reader, read_ahead).await? != signal {
+        return None;
+    }
+    read_varint(reader, read_ahead).await
+}
+
+/// Read a varint into `buf` exactly, without reading any byte after it.
+///
+/// Returns `None` if the stream is finished or broken before the varint is complete.
+async fn read_varint(reader: &mut StreamReader, buf: &mut BytesMut) -> Option<u64> {
+    let start = buf.len();
+    let mut len = 1;
+    while buf.len() < start + len {
+        let remaining = start + len - buf.len();
+        match (&mut *reader).take(remaining as u64).read_buf(buf).await {
+            Ok(0) | Err(_) => return None,
+            Ok(_) => len = 1 << (buf[start] >> 6),
+        }
+    }
+    let (_, varint) = be_varint(&buf[start..]).expect("a complete varint");
+    Some(varint.into_inner())
+}
+
+/// The reason why a WebTransport session is closed.
+///
+/// A session closed without `CLOSE_WEBTRANSPORT_SESSION`, like the CONNECT stream is finished or
+/// reset, is closed with the error code 0 and an empty message. A session closed for the malformed
+/// capsules received is closed with the error code 0 and a message describing the error.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SessionClose {
+    pub error_code: u32,
+    pub reason: String,
+}
+
+#[derive(Default)]
+struct SessionState {
+    draining: bool,
+    closed: Option<SessionClose>,
+}
+
+/// Sends the capsules on the CONNECT stream of a session.
+trait SendCapsules: Send {
+    fn send_capsule(&mut self, capsule: Bytes) -> BoxFuture<'_, Result<(), StreamError>>;
+
+    fn finish(&mut self) -> BoxFuture<'_, Result<(), StreamError>>;
+}
+
+impl SendCapsules for h3::server::RequestStream<SendStream<Bytes>, Bytes> {
+    fn send_capsule(&mut self, capsule: Bytes) -> BoxFuture<'_, Result<(), StreamError>> {
+        Box::pin(self.send_data(capsule))
+    }
+
+    fn finish(&mut self) -> BoxFuture<'_, Result<(), StreamError>> {
+        Box::pin(h3::server::RequestStream::finish(self))
+    }
+}
+
+impl SendCapsules for h3::client::RequestStream<SendStream<Bytes>, Bytes> {
+    fn send_capsule(&mut self, capsule: Bytes) -> BoxFuture<'_, Result<(), StreamError>> {
+        Box::pin(self.send_data(capsule))
+    }
+
+    fn finish(&mut self) -> BoxFuture<'_, Result<(), StreamError>> {
+        Box::pin(h3::client::RequestStream::finish(self))
+    }
+}
+
+/// Receives the data of the CONNECT stream of a session, which carries the capsules.
+trait RecvCapsules: Send {
+    fn recv_data(&mut self) -> BoxFuture<'_, Result<Option<Bytes>, StreamError>>;
+}
+
+impl RecvCapsules for h3::server::RequestStream<RecvStream, Bytes> {
+    fn recv_data(&mut self) -> BoxFuture<'_, Result<Option<Bytes>, StreamError>> {
+        Box::pin(async move {
+            let data = h3::server::RequestStream::recv_data(self).await?;
+            Ok(data.map(|mut data| data.copy_to_bytes(data.remaining())))
+        })
+    }
+}
+
+impl RecvCapsules for h3::client::RequestStream<RecvStream, Bytes> {
+    fn recv_data(&mut self) -> BoxFuture<'_, Result<Option<Bytes>, StreamError>> {
+        Box::pin(async move {
+            let data = h3::client::RequestStream::recv_data(self).await?;
+            Ok(data.map(|mut data| data.copy_to_bytes(data.remaining())))
+        })
+    }
+}
+
+/// Encode a capsule into a DATA frame payload.
+fn encode_capsule(capsule_type: u64, value: &[u8]) -> Bytes {
+    let capsule_type = VarInt::from_u64(capsule_type).expect("capsule type is a varint");
+    let length = VarInt::try_from(value.len()).expect("capsule length is a varint");
+    let mut capsule = BytesMut::with_capacity(16 + value.len());
+    capsule.put_varint(&capsule_type);
+    capsule.put_varint(&length);
+    capsule.put_slice(value);
+    capsule.freeze()
+}
+
+/// Decode a complete capsule from the front of `buf`.
+///
+/// Returns `Ok(None)` if the capsule is not complete yet, or an error if the capsule is longer
+/// than [`MAX_CAPSULE_SIZE`].
+fn decode_capsule(buf: &mut BytesMut) -> Result<Option<(u64, Bytes)>, &'static str> {
+    let Ok((remain, capsule_type)) = be_varint(buf) else {
+        return Ok(None);
+    };
+    let Ok((remain, length)) = be_varint(remain) else {
+        return Ok(None);
+    };
+    if length.into_inner() > MAX_CAPSULE_SIZE as u64 {
+        return Err("the capsule is too large");
+    }
+    let length = length.into_inner() as usize;
+    if remain.len() < length {
+        return Ok(None);
+    }
+    let header_len = buf.len() - remain.len();
+    buf.advance(header_len);
+    Ok(Some((
+        capsule_type.into_inner(),
+        buf.split_to(length).freeze(),
+    )))
+}
+
+/// Receive the capsules of a session until it is closed, then terminate the session.
+///
+/// The session whose CONNECT stream carries malformed capsules is closed by resetting the CONNECT
+/// stream with `H3_GENERAL_PROTOCOL_ERROR`.
+async fn recv_capsules(
+    webtransport: WebTransport,
+    session_id: u64,
+    mut stream: Box<dyn RecvCapsules>,
+    capsules: Arc<tokio::sync::Mutex<Box<dyn SendCapsules>>>,
+    state: Arc<watch::Sender<SessionState>>,
+) {
+    let mut buf = BytesMut::new();
+    let close = 'recv: loop {
+        match stream.recv_data().await {
+            Ok(Some(data)) => buf.extend_from_slice(&data),
+            // the CONNECT stream is finished or reset without CLOSE_WEBTRANSPORT_SESSION
+            Ok(None) | Err(_) => break Ok(SessionClose::default()),
+        }
+        loop {
+            let (capsule_type, mut value) = match decode_capsule(&mut buf) {
+                Ok(Some(capsule)) => capsule,
+                Ok(None) => break,
+                Err(error) => break 'recv Err(error),
+            };
+            match capsule_type {
+                DRAIN_WEBTRANSPORT_SESSION => state.send_modify(|state| state.draining = true),
+                CLOSE_WEBTRANSPORT_SESSION => {
+                    if !(4..=4 + MAX_CLOSE_MESSAGE_LEN).contains(&value.len()) {
+                        break 'recv Err("malformed CLOSE_WEBTRANSPORT_SESSION capsule");
+                    }
+                    let error_code = value.get_u32();
+                    let reason = String::from_utf8_lossy(&value).into_owned();
+                    break 'recv Ok(SessionClose { error_code, reason });
+                }
+                // unknown capsules are ignored
+                _ => {}
+            }
+        }
+    };
+
+    let protocol_error = close.is_err();
+    let close = close.unwrap_or_else(|error| SessionClose {
+        error_code: 0,
+        reason: error.to_owned(),
+    });
+    let closed_by_peer = state.send_if_modified(|state| match state.closed {
+        Some(_) => false,
+        None => {
+            state.closed = Some(close);
+            true
+        }
+    });
+    if protocol_error {
+        webtransport.terminate(session_id);
+        _ = webtransport
+            .connection
+            .cancel_stream(connect_stream_id(session_id), H3_GENERAL_PROTOCOL_ERROR);
+        // the peer violating the protocol is not waited to close the CONNECT stream
+        webtransport.forget(session_id);
+        return;
+    }
+    if closed_by_peer {
+        webtransport.terminate(session_id);
+        // the CONNECT stream is finished in response
+        _ = capsules.lock().await.finish().await;
+    }
+    // the CONNECT stream is closed in both directions
+    webtransport.forget(session_id);
+}
+
+/// A WebTransport session, established by [`WebTransport::accept`] or [`WebTransport::connect`].
+///
+/// Dropping the session without [`WebTransportSession::close`] closes the session with the error
+/// code 0, by finishing the CONNECT stream.
+pub struct WebTransportSession {
+    webtransport: WebTransport,
+    session_id: h3::quic::StreamId,
+    capsules: Arc<tokio::sync::Mutex<Box<dyn SendCapsules>>>,
+    state: Arc<watch::Sender<SessionState>>,
+    incoming_bi: tokio::sync::Mutex<mpsc::UnboundedReceiver<IncomingBiStream>>,
+    incoming_uni: tokio::sync::Mutex<mpsc::UnboundedReceiver<IncomingUniStream>>,
+    datagram_sender: StreamDatagramSender,
+    datagram_receiver: tokio::sync::Mutex<StreamDatagramReceiver>,
+}
+
+impl WebTransportSession {
+    fn new(
+        webtransport: WebTransport,
+        session_id: h3::quic::StreamId,
+        send: Box<dyn SendCapsules>,
+        recv: Box<dyn RecvCapsules>,
+        (incoming_bi, incoming_uni): (
+            mpsc::UnboundedReceiver<IncomingBiStream>,
+            mpsc::UnboundedReceiver<IncomingUniStream>,
+        ),
+        (datagram_sender, datagram_receiver): (StreamDatagramSender, StreamDatagramReceiver),
+    ) -> Self {
+        let state = Arc::new(watch::Sender::new(SessionState::default()));
+        let capsules = Arc::new(tokio::sync::Mutex::new(send));
+        tokio::spawn(recv_capsules(
+            webtransport.clone(),
+            session_id.into_inner(),
+            recv,
+            capsules.clone(),
+            state.clone(),
+        ));
+        Self {
+            webtransport,
+            session_id,
+            capsules,
+            state,
+            incoming_bi: tokio::sync::Mutex::new(incoming_bi),
+            incoming_uni: tokio::sync::Mutex::new(incoming_uni),
+            datagram_sender,
+            datagram_receiver: tokio::sync::Mutex::new(datagram_receiver),
+        }
+    }
+
+    /// The session ID, which is the ID of the CONNECT stream.
+    pub fn session_id(&self) -> h3::quic::StreamId {
+        self.session_id
+    }
+
+    fn gone_error(&self) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::ConnectionAborted,
+            format!("WebTransport session {} is closed", self.session_id),
+        )
+    }
+
+    fn check_open(&self) -> io::Result<()> {
+        match self.state.borrow().closed {
+            Some(_) => Err(self.gone_error()),
+            None => Ok(()),
+        }
+    }
+
+    /// Mark the session closed locally and stop routing its streams, returns `false` if the session
+    /// is closed already.
+    fn mark_closed(&self, close: SessionClose) -> bool {
+        let closed = self.state.send_if_modified(|state| match state.closed {
+            Some(_) => false,
+            None => {
+                state.closed = Some(close);
+                true
+            }
+        });
+        if closed {
+            self.webtransport.terminate(self.session_id.into_inner());
+        }
+        closed
+    }
+
+    /// The header of the streams opened in the session.
+    fn stream_header(&self, signal: u64) -> Bytes {
+        let mut header = BytesMut::with_capacity(16);
+        header.put_varint(&VarInt::from_u64(signal).expect("signal is a varint"));
+        header.put_varint(
+            &VarInt::from_u64(self.session_id.into_inner()).expect("stream id is a varint"),
+        );
+        header.freeze()
+    }
+
+    /// Associate the stream opened locally with the session, so that it is reset when the session
+    /// is terminated. The stream is reset at once if the session is terminated already.
+    fn associate(&self, sid: StreamId) -> io::Result<()> {
+        if self
+            .webtransport
+            .associate(self.session_id.into_inner(), sid)
+        {
+            return Ok(());
+        }
+        _ = self
+            .webtransport
+            .connection
+            .cancel_stream(sid, H3_WEBTRANSPORT_SESSION_GONE);
+        Err(self.gone_error())
+    }
+
+    /// Open a bidirectional stream in the session.
+    pub async fn open_bi(&self) -> io::Result<(StreamId, (StreamReader, StreamWriter))> {
+        self.check_open()?;
+        let (sid, (reader, mut writer)) = self
+            .webtransport
+            .connection
+            .open_bi_stream()
+            .await?
+            .ok_or_else(sid_exceed_limit_error)?;
+        self.associate(sid)?;
+        writer
+            .write_all(&self.stream_header(WEBTRANSPORT_STREAM))
+            .await?;
+        Ok((sid, (reader, writer)))
+    }
+
+    /// Open a unidirectional stream in the session.
+    pub async fn open_uni(&self) -> io::Result<(StreamId, StreamWriter)> {
+        self.check_open()?;
+        let (sid, mut writer) = self
+            .webtransport
+            .connection
+            .open_uni_stream()
+            .await?
+            .ok_or_else(sid_exceed_limit_error)?;
+        self.associate(sid)?;
+        writer
+            .write_all(&self.stream_header(WEBTRANSPORT_UNI_STREAM))
+            .await?;
+        Ok((sid, writer))
+    }
+
+    /// Accept a bidirectional stream opened by the peer in the session.
+    ///
+    /// Returns an error if the session is closed.
+    pub async fn accept_bi(&self) -> io::Result<(StreamId, (StreamReader, StreamWriter))> {
+        let mut incoming = self.incoming_bi.lock().await;
+        incoming.recv().await.ok_or_else(|| self.gone_error())
+    }
+
+    /// Accept a unidirectional stream opened by the peer in the session.
+    ///
+    /// Returns an error if the session is closed.
+    pub async fn accept_uni(&self) -> io::Result<(StreamId, StreamReader)> {
+        let mut incoming = self.incoming_uni.lock().await;
+        incoming.recv().await.ok_or_else(|| self.gone_error())
+    }
+
+    /// Send a datagram in the session.
+    pub fn send_datagram(&self, payload: impl Buf) -> io::Result<()> {
+        self.check_open()?;
+        self.datagram_sender.send(payload)
+    }
+
+    /// Receive a datagram of the session.
+    ///
+    /// Returns an error if the connection is closed.
+    pub async fn recv_datagram(&self) -> io::Result<Bytes> {
+        self.datagram_receiver.lock().await.recv().await
+    }
+
+    /// Ask the peer to close the session gracefully, by sending `DRAIN_WEBTRANSPORT_SESSION`.
+    pub async fn drain(&self) -> io::Result<()> {
+        self.check_open()?;
+        let capsule = encode_capsule(DRAIN_WEBTRANSPORT_SESSION, &[]);
+        let mut capsules = self.capsules.lock().await;
+        capsules
+            .send_capsule(capsule)
+            .await
+            .map_err(io::Error::other)
+    }
+
+    /// Close the session with the `error_code` and the `reason`, by sending
+    /// `CLOSE_WEBTRANSPORT_SESSION` and finishing the CONNECT stream.
+    ///
+    /// The `reason` is truncated to 1024 bytes.
+    pub async fn close(&self, error_code: u32, reason: &str) -> io::Result<()> {
+        let mut reason_len = reason.len().min(MAX_CLOSE_MESSAGE_LEN);
+        while !reason.is_char_boundary(reason_len) {
+            reason_len -= 1;
+        }
+        let reason = &reason[..reason_len];
+
+        if !self.mark_closed(SessionClose {
+            error_code,
+            reason: reason.to_owned(),
+        }) {
+            return Err(self.gone_error());
+        }
+
+        let mut value = Vec::with_capacity(4 + reason.len());
+        value.put_u32(error_code);
+        value.put_slice(reason.as_bytes());
+        let capsule = encode_capsule(CLOSE_WEBTRANSPORT_SESSION, &value);
+        let mut capsules = self.capsules.lock().await;
+        capsules
+            .send_capsule(capsule)
+            .await
+            .map_err(io::Error::other)?;
+        capsules.finish().await.map_err(io::Error::other)
+    }
+
+    /// Returns whether the peer asked to close the session, by sending
+    /// `DRAIN_WEBTRANSPORT_SESSION`.
+    pub fn is_draining(&self) -> bool {
+        self.state.borrow().draining
+    }
+
+    /// Wait for the peer to ask to close the session, or for the session to be closed.
+    pub async fn draining(&self) {
+        let mut state = self.state.subscribe();
+        _ = state
+            .wait_for(|state| state.draining || state.closed.is_some())
+            .await;
+    }
+
+    /// Wait for the session to be closed, by the peer or by [`WebTransportSession::close`].
+    pub async fn closed(&self) -> SessionClose {
+        let mut state = self.state.subscribe();
+        let state = state
+            .wait_for(|state| state.closed.is_some())
+            .await
+            .expect("the sender is owned by the session");
+        state.closed.clone().expect("the session is closed")
+    }
+}
+
+impl Drop for WebTransportSession {
+    fn drop(&mut self) {
+        if !self.mark_closed(SessionClose::default()) {
+            return;
+        }
+        let capsules = self.capsules.clone();
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            runtime.spawn(async move { _ = capsules.lock().await.finish().await });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_capsules() {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(&encode_capsule(DRAIN_WEBTRANSPORT_SESSION, &[]));
+        let close = encode_capsule(CLOSE_WEBTRANSPORT_SESSION, b"\0\0\0\x2adone");
+        buf.extend_from_slice(&close[..close.len() - 1]);
+
+        let drain = decode_capsule(&mut buf).unwrap().unwrap();
+        assert_eq!(drain, (DRAIN_WEBTRANSPORT_SESSION, Bytes::new()));
+        // the incomplete capsule is left in the buffer
+        assert_eq!(decode_capsule(&mut buf), Ok(None));
+        buf.extend_from_slice(&close[close.len() - 1..]);
+        let close = decode_capsule(&mut buf).unwrap().unwrap();
+        assert_eq!(close.1, Bytes::from_static(b"\0\0\0\x2adone"));
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn reject_large_capsule() {
+        let mut buf = BytesMut::new();
+        buf.put_varint(&VarInt::from_u64(CLOSE_WEBTRANSPORT_SESSION).unwrap());
+        buf.put_varint(&VarInt::from_u64(1 << 40).unwrap());
+        // the capsule is rejected before its value is received
+        assert!(decode_capsule(&mut buf).is_err());
+    }
+}
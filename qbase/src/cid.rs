@@ -1,3 +1,5 @@
+use crate::token::ResetToken;
+
 mod connection_id;
 pub use connection_id::*;
 
@@ -13,6 +15,16 @@ pub trait GenUniqueCid {
     /// Generate a unique connection ID.
     #[must_use]
     fn gen_unique_cid(&self) -> ConnectionId;
+
+    /// Generate the stateless reset token for a connection ID issued to the peer.
+    ///
+    /// A random token is generated by default. Endpoints that want to send stateless
+    /// resets after losing the connection state should derive the token from a static
+    /// key instead, see [`StatelessResetKey`](crate::token::StatelessResetKey).
+    fn gen_reset_token(&self, cid: &ConnectionId) -> ResetToken {
+        _ = cid;
+        ResetToken::random_gen()
+    }
 }
 
 pub trait RetireCid {
@@ -40,12 +40,16 @@ where
     fn new(scid: ConnectionId, issued_cids: ISSUED) -> Self {
         let mut cid_deque = IndexDeque::default();
         cid_deque
-            .push_back(Some((scid, ResetToken::default())))
+            .push_back(Some((scid, issued_cids.gen_reset_token(&scid))))
             .unwrap();
 
         let new_cid = issued_cids.gen_unique_cid();
-        let new_cid_frame =
-            NewConnectionIdFrame::new(new_cid, VarInt::from_u32(1), VarInt::from_u32(0));
+        let new_cid_frame = NewConnectionIdFrame::new(
+            new_cid,
+            VarInt::from_u32(1),
+            VarInt::from_u32(0),
+            issued_cids.gen_reset_token(&new_cid),
+        );
         issued_cids.send_frame([new_cid_frame]);
         cid_deque
             .push_back(Some((
@@ -60,6 +64,20 @@ where
         }
     }
 
+    /// Create a local connection ID manager for a path other than the path 0 in multipath,
+    /// issuing `active_cid_limit` connection IDs at once, starting from sequence number 0.
+    fn with_limit(issued_cids: ISSUED, active_cid_limit: u64) -> Self {
+        let mut local_cids = Self {
+            cid_deque: IndexDeque::default(),
+            issued_cids,
+            active_cid_limit: Some(active_cid_limit),
+        };
+        for _ in 0..active_cid_limit {
+            local_cids.issue_new_cid();
+        }
+        local_cids
+    }
+
     fn initial_scid(&self) -> Option<ConnectionId> {
         self.cid_deque.get(0)?.map(|(cid, _)| cid)
     }
@@ -92,7 +110,8 @@ where
         let seq = VarInt::from_u64(self.cid_deque.largest()).unwrap();
         let retire_prior_to = VarInt::from_u64(self.cid_deque.offset()).unwrap();
         let new_cid = self.issued_cids.gen_unique_cid();
-        let new_cid_frame = NewConnectionIdFrame::new(new_cid, seq, retire_prior_to);
+        let reset_token = self.issued_cids.gen_reset_token(&new_cid);
+        let new_cid_frame = NewConnectionIdFrame::new(new_cid, seq, retire_prior_to, reset_token);
         self.issued_cids.send_frame([new_cid_frame]);
         self.cid_deque.push_back(Some((*new_cid_frame.connection_id(), *new_cid_frame.reset_token())))
             .expect("it's very very hard to issue a new connection ID whose sequence excceeds VARINT_MAX");
@@ -182,6 +201,28 @@ where
         Self(Arc::new(Mutex::new(raw_local_cids)))
     }
 
+    /// Create a new share local connection ID manager for a path other than the path 0,
+    /// when multipath is enabled.
+    ///
+    /// Each path has its own connection IDs and sequence numbers, `active_cid_limit`
+    /// connection IDs are issued to the peer at once, the peer's active_connection_id_limit
+    /// applies to each path.
+    pub fn with_limit(issued_cids: ISSUED, active_cid_limit: u64) -> Self {
+        let raw_local_cids = LocalCids::with_limit(issued_cids, active_cid_limit);
+        Self(Arc::new(Mutex::new(raw_local_cids)))
+    }
+
+    /// Whether the connection ID is an active one issued by this manager.
+    pub fn contains(&self, cid: &ConnectionId) -> bool {
+        self.0
+            .lock()
+            .unwrap()
+            .cid_deque
+            .iter()
+            .flatten()
+            .any(|(issued, _)| issued == cid)
+    }
+
     /// Get the initial source connection ID.
     ///
     /// 0-RTT packets in the first flight use the same Destination Connection ID
@@ -238,6 +279,20 @@ where
         self.0.lock().unwrap().clear();
     }
 
+    /// Get the connection ID of sequence number 1 and its stateless reset token.
+    ///
+    /// The sequence number of the connection ID supplied in the preferred_address
+    /// transport parameter is 1, see
+    /// [section-5.1.1](https://datatracker.ietf.org/doc/html/rfc9000#section-5.1.1)
+    /// of [RFC9000](https://datatracker.ietf.org/doc/html/rfc9000).
+    /// It is issued in a [`NewConnectionIdFrame`] as well, the peer receiving it twice
+    /// is harmless.
+    ///
+    /// Return None if the connection ID has been retired.
+    pub fn preferred_address_cid(&self) -> Option<(ConnectionId, ResetToken)> {
+        self.0.lock().unwrap().cid_deque.get(1).copied().flatten()
+    }
+
     /// Set the maximum number of active connection IDs.
     ///
     /// After fully obtaining the peer's connection parameters, extract the peer's
@@ -276,11 +331,11 @@ mod tests {
     }
 
     impl IssuedCids {
-        fn frames(&self) -> MutexGuard<Vec<NewConnectionIdFrame>> {
+        fn frames(&self) -> MutexGuard<'_, Vec<NewConnectionIdFrame>> {
             self.frames.lock().unwrap()
         }
 
-        fn active_cids(&self) -> MutexGuard<HashMap<ConnectionId, ResetToken>> {
+        fn active_cids(&self) -> MutexGuard<'_, HashMap<ConnectionId, ResetToken>> {
             self.active_cids.lock().unwrap()
         }
     }
@@ -322,6 +377,40 @@ mod tests {
         assert_eq!(local_cids.cid_deque.len(), 3);
     }
 
+    #[test]
+    fn test_issue_path_cids() {
+        let local_cids = ArcLocalCids::with_limit(IssuedCids::default(), 3);
+        let frames = local_cids.0.lock().unwrap().issued_cids.frames().clone();
+        assert_eq!(frames.len(), 3);
+        assert_eq!(
+            frames.iter().map(|f| f.sequence()).collect::<Vec<_>>(),
+            [0, 1, 2]
+        );
+        assert!(local_cids.contains(frames[0].connection_id()));
+        assert_eq!(local_cids.initial_scid(), Some(*frames[0].connection_id()));
+
+        let retire_frame = RetireConnectionIdFrame::new(VarInt::from_u32(0));
+        local_cids.recv_frame(&retire_frame).unwrap();
+        assert!(!local_cids.contains(frames[0].connection_id()));
+        assert!(local_cids.contains(frames[1].connection_id()));
+    }
+
+    #[test]
+    fn test_preferred_address_cid() {
+        let initial_scid = ConnectionId::random_gen(8);
+        let local_cids = ArcLocalCids::new(initial_scid, IssuedCids::default());
+
+        let frame = local_cids.0.lock().unwrap().issued_cids.frames()[0];
+        assert_eq!(
+            local_cids.preferred_address_cid(),
+            Some((*frame.connection_id(), *frame.reset_token()))
+        );
+
+        let retire_frame = RetireConnectionIdFrame::new(VarInt::from_u32(1));
+        local_cids.recv_frame(&retire_frame).unwrap();
+        assert_eq!(local_cids.preferred_address_cid(), None);
+    }
+
     #[test]
     fn test_recv_retire_cid_frame() {
         let initial_scid = ConnectionId::random_gen(8);
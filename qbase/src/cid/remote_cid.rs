@@ -22,8 +22,10 @@ where
     RETIRED: SendFrame<RetireConnectionIdFrame> + Clone,
 {
     // the cid issued by the peer, the sequence number maybe not continuous
-    // since the disordered [`NewConnectionIdFrame`]
-    cid_deque: IndexDeque<Option<(u64, ConnectionId, ResetToken)>, VARINT_MAX>,
+    // since the disordered [`NewConnectionIdFrame`].
+    // The reset token of the initial dcid is unknown until the server's transport
+    // parameters are received, and the client never provides one.
+    cid_deque: IndexDeque<Option<(u64, ConnectionId, Option<ResetToken>)>, VARINT_MAX>,
     // The cell of the connection ID, which is ready in use
     ready_cells: IndexDeque<ArcCidCell<RETIRED>, VARINT_MAX>,
     // The cell of the connection ID, which needs to be assigned or reassigned
@@ -52,10 +54,7 @@ where
     fn new(initial_dcid: ConnectionId, active_cid_limit: u64, retired_cids: RETIRED) -> Self {
         let mut cid_deque = IndexDeque::default();
 
-        let reset_token = ResetToken::default();
-        cid_deque
-            .push_back(Some((0, initial_dcid, reset_token)))
-            .unwrap();
+        cid_deque.push_back(Some((0, initial_dcid, None))).unwrap();
 
         Self {
             active_cid_limit,
@@ -67,12 +66,26 @@ where
         }
     }
 
+    /// Create a RemoteCids for a path other than the path 0 in multipath, all the
+    /// connection IDs of which are issued in PATH_NEW_CONNECTION_ID frames.
+    fn with_limit(active_cid_limit: u64, retired_cids: RETIRED) -> Self {
+        Self {
+            active_cid_limit,
+            cid_deque: Default::default(),
+            ready_cells: Default::default(),
+            pending_cells: Default::default(),
+            cursor: 0,
+            retired_cids,
+        }
+    }
+
     /// Revise the initial dcid, which is used when the client received the
     /// response packet from the server, and the initial dcid should be updated
     /// based on the scid in the response packet.
     fn revise_initial_dcid(&mut self, responsed_dcid: ConnectionId) {
         let first_dcid = self.cid_deque.get_mut(0).unwrap();
-        *first_dcid = Some((0, responsed_dcid, ResetToken::default()));
+        let reset_token = (*first_dcid).and_then(|(_, _, token)| token);
+        *first_dcid = Some((0, responsed_dcid, reset_token));
 
         if let Some(apply) = self.ready_cells.get_mut(0) {
             apply.revise(responsed_dcid);
@@ -113,7 +126,9 @@ where
 
         let id = *frame.connection_id();
         let token = *frame.reset_token();
-        self.cid_deque.insert(seq, Some((seq, id, token))).unwrap();
+        self.cid_deque
+            .insert(seq, Some((seq, id, Some(token))))
+            .unwrap();
         self.retire_prior_to(retire_prior_to);
         self.arrange_idle_cid();
 
@@ -205,6 +220,27 @@ where
         }
     }
 
+    /// Set the reset token of the initial dcid, which is carried by the
+    /// stateless_reset_token transport parameter of the server.
+    fn set_initial_reset_token(&mut self, reset_token: ResetToken) {
+        if let Some(Some((0, _, token))) = self.cid_deque.get_mut(0) {
+            *token = Some(reset_token);
+        }
+    }
+
+    /// Check whether the `tail` of a datagram is the reset token of any connection ID in use.
+    ///
+    /// An endpoint MUST NOT check for any stateless reset tokens associated with connection IDs
+    /// it has not used or for connection IDs that have been retired.
+    fn is_stateless_reset(&self, tail: &[u8]) -> bool {
+        self.cid_deque
+            .iter()
+            .flatten()
+            .filter(|(seq, ..)| *seq < self.cursor)
+            .filter_map(|(_, _, token)| *token)
+            .fold(false, |matched, token| token.matches(tail) | matched)
+    }
+
     /// Apply for a new connection ID, and return an [`ArcCidCell`], which may be not ready state.
     fn apply_dcid(&mut self) -> ArcCidCell<RETIRED> {
         let cell = ArcCidCell::new(self.retired_cids.clone());
@@ -245,6 +281,15 @@ where
         ))))
     }
 
+    /// Create a new RemoteCids for a path other than the path 0 when multipath is enabled,
+    /// there is no initial dcid, the connection IDs are issued by the peer later.
+    pub fn with_limit(active_cid_limit: u64, retired_cids: RETIRED) -> Self {
+        Self(Arc::new(Mutex::new(RemoteCids::with_limit(
+            active_cid_limit,
+            retired_cids,
+        ))))
+    }
+
     /// Revise the initial dcid, which is used if and only if the client
     /// received the response packet from the server, and the initial dcid
     /// should be updated based on the scid in the response packet.
@@ -252,6 +297,21 @@ where
         self.0.lock().unwrap().revise_initial_dcid(initial_dcid);
     }
 
+    /// Set the reset token of the initial dcid, which is used if and only if the client
+    /// received the stateless_reset_token transport parameter from the server.
+    pub fn set_initial_reset_token(&self, reset_token: ResetToken) {
+        self.0.lock().unwrap().set_initial_reset_token(reset_token);
+    }
+
+    /// Check whether a datagram that cannot be processed is a stateless reset,
+    /// by comparing its trailing 16 bytes with the reset tokens of the connection IDs in use.
+    ///
+    /// See [section 10.3.1](https://www.rfc-editor.org/rfc/rfc9000.html#name-detecting-a-stateless-reset)
+    /// of [RFC9000](https://www.rfc-editor.org/rfc/rfc9000.html).
+    pub fn is_stateless_reset(&self, tail: &[u8]) -> bool {
+        self.0.lock().unwrap().is_stateless_reset(tail)
+    }
+
     /// Apply for a new connection ID, which is used when the Path is created.
     ///
     /// Return an [`ArcCidCell`], which may be not ready state.
@@ -413,7 +473,7 @@ where
     pub fn borrow_cid(
         &self,
         tx_waker: ArcSendWaker,
-    ) -> Result<Option<BorrowedCid<RETIRED>>, Signals> {
+    ) -> Result<Option<BorrowedCid<'_, RETIRED>>, Signals> {
         self.0.lock().unwrap().borrow_cid(tx_waker).map(|cid| {
             cid.map(|cid| BorrowedCid {
                 cid_cell: &self.0,
@@ -494,7 +554,12 @@ mod tests {
         assert!(cid_apply1.borrow_cid(waker.clone()).is_err());
 
         let cid = ConnectionId::random_gen(8);
-        let frame = NewConnectionIdFrame::new(cid, VarInt::from_u32(1), VarInt::from_u32(0));
+        let frame = NewConnectionIdFrame::new(
+            cid,
+            VarInt::from_u32(1),
+            VarInt::from_u32(0),
+            ResetToken::random_gen(),
+        );
         assert!(remote_cids.recv_new_cid_frame(&frame).is_ok());
         assert_eq!(remote_cids.cid_deque.len(), 2);
 
@@ -509,6 +574,28 @@ mod tests {
         assert!(cid_apply2.borrow_cid(waker.clone()).is_err());
     }
 
+    #[test]
+    fn test_path_remote_cids() {
+        let remote_cids = ArcRemoteCids::with_limit(2, RetiredCids::default());
+        let waker = ArcSendWaker::new();
+        let cell = remote_cids.apply_dcid();
+        assert!(cell.borrow_cid(waker.clone()).is_err());
+        assert_eq!(remote_cids.latest_dcid(), None);
+
+        let cid = ConnectionId::random_gen(8);
+        let frame = NewConnectionIdFrame::new(
+            cid,
+            VarInt::from_u32(0),
+            VarInt::from_u32(0),
+            ResetToken::random_gen(),
+        );
+        assert!(remote_cids.recv_frame(&frame).unwrap().is_some());
+        assert!(matches!(
+            cell.borrow_cid(waker.clone()),
+            Ok(Some(borrowed)) if *borrowed == cid
+        ));
+    }
+
     #[test]
     fn test_retire_in_remote_cids() {
         let initial_dcid = ConnectionId::random_gen(8);
@@ -520,7 +607,12 @@ mod tests {
         for seq in 1..8 {
             let cid = ConnectionId::random_gen(8);
             cids.push(cid);
-            let frame = NewConnectionIdFrame::new(cid, VarInt::from_u32(seq), VarInt::from_u32(0));
+            let frame = NewConnectionIdFrame::new(
+                cid,
+                VarInt::from_u32(seq),
+                VarInt::from_u32(0),
+                ResetToken::random_gen(),
+            );
             _ = guard.recv_new_cid_frame(&frame);
         }
 
@@ -597,7 +689,12 @@ mod tests {
         for seq in 1..8 {
             let cid = ConnectionId::random_gen(8);
             cids.push(cid);
-            let frame = NewConnectionIdFrame::new(cid, VarInt::from_u32(seq), VarInt::from_u32(0));
+            let frame = NewConnectionIdFrame::new(
+                cid,
+                VarInt::from_u32(seq),
+                VarInt::from_u32(0),
+                ResetToken::random_gen(),
+            );
             _ = guard.recv_new_cid_frame(&frame);
         }
 
@@ -620,4 +717,32 @@ mod tests {
             Ok(Some(entry)) if *entry == cids[5]
         ));
     }
+
+    #[test]
+    fn test_stateless_reset_detection() {
+        let initial_dcid = ConnectionId::random_gen(8);
+        let remote_cids = ArcRemoteCids::new(initial_dcid, 8, RetiredCids::default());
+
+        let initial_token = ResetToken::random_gen();
+        remote_cids.set_initial_reset_token(initial_token);
+        // the initial dcid has not been used yet
+        assert!(!remote_cids.is_stateless_reset(&*initial_token));
+
+        let cid_apply0 = remote_cids.apply_dcid();
+        assert!(remote_cids.is_stateless_reset(&*initial_token));
+        assert!(!remote_cids.is_stateless_reset(&[0; 16]));
+
+        let token = ResetToken::random_gen();
+        let frame = NewConnectionIdFrame::new(
+            ConnectionId::random_gen(8),
+            VarInt::from_u32(1),
+            VarInt::from_u32(1),
+            token,
+        );
+        assert_eq!(remote_cids.recv_frame(&frame).unwrap(), Some(token));
+        // the initial dcid is retired, and the new one is assigned to the cell
+        assert!(!remote_cids.is_stateless_reset(&*initial_token));
+        assert!(remote_cids.is_stateless_reset(&*token));
+        drop(cid_apply0);
+    }
 }
@@ -1,15 +1,35 @@
 use std::{
+    fmt,
     ops::{Deref, DerefMut},
-    sync::{Arc, Mutex},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
 };
 
 use crate::{
     error::{Error, ErrorFrameType, ErrorKind, QuicError},
     frame::{DataBlockedFrame, FrameType, MaxDataFrame, ReceiveFrame, SendFrame},
     net::tx::{ArcSendWakers, Signals},
-    varint::VarInt,
+    varint::{VARINT_MAX, VarInt},
 };
 
+/// A snapshot of a connection-level flow controller, the amount of the new stream data sent or
+/// received, and the limit of it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FlowWindow {
+    pub data: u64,
+    pub max_data: u64,
+}
+
+impl FlowWindow {
+    /// The amount of the new stream data that can still be sent or received.
+    pub fn credit(&self) -> u64 {
+        self.max_data.saturating_sub(self.data)
+    }
+}
+
 /// Connection-level global Stream Flow Control in the sending direction,
 /// regulated by the peer's `initial_max_data` transport parameter
 /// and updated by the [`MaxDataFrame`] sent by the peer.
@@ -128,6 +148,20 @@ impl<TX> ArcSendControler<TX> {
         }
     }
 
+    /// Returns the amount of the new stream data sent, and the limit of it set by peer.
+    ///
+    /// The credit being used by the sending task is counted as sent. A default window is
+    /// returned once the flow control is terminated.
+    pub fn window(&self) -> FlowWindow {
+        match self.0.lock().unwrap().as_ref() {
+            Ok(inner) => FlowWindow {
+                data: inner.sent_data,
+                max_data: inner.max_data,
+            },
+            Err(_) => FlowWindow::default(),
+        }
+    }
+
     /// Connection-level Stream Flow Control can only be terminated
     /// if the connection encounters an error
     pub fn on_error(&self, error: &Error) {
@@ -188,31 +222,186 @@ impl<TX> Drop for Credit<'_, TX> {
     }
 }
 
+/// The limits of the receive windows, which cap the memory used to buffer the stream data
+/// received but not read by the application yet.
+///
+/// The receive windows start from the `initial_max_data` and `initial_max_stream_data_*`
+/// transport parameters, and grow up to these limits if the application reads the data fast
+/// enough, see [`RecvWindow`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecvWindowLimits {
+    /// The limit of the connection-level receive window, for all streams.
+    pub connection: u64,
+    /// The limit of the receive window of each stream.
+    pub stream: u64,
+}
+
+impl Default for RecvWindowLimits {
+    fn default() -> Self {
+        Self {
+            connection: 24 << 20,
+            stream: 16 << 20,
+        }
+    }
+}
+
+/// The smoothed RTT of the connection, shared by the receive windows to tune themselves.
+///
+/// It's the initial RTT (333ms) before it's updated by the RTT samples of the paths, see
+/// [section 6.2.2](https://www.rfc-editor.org/rfc/rfc9002.html#name-before-the-first-rtt-sample)
+/// of [RFC9002](https://www.rfc-editor.org/rfc/rfc9002.html).
+#[derive(Debug, Clone)]
+pub struct SmoothedRtt(Arc<AtomicU64>);
+
+impl Default for SmoothedRtt {
+    fn default() -> Self {
+        Self(Arc::new(AtomicU64::new(333_000)))
+    }
+}
+
+impl SmoothedRtt {
+    /// Returns the smoothed RTT.
+    pub fn get(&self) -> Duration {
+        Duration::from_micros(self.0.load(Ordering::Relaxed))
+    }
+
+    /// Updates the smoothed RTT, with the one estimated by the path carrying the data.
+    pub fn set(&self, smoothed_rtt: Duration) {
+        self.0
+            .store(smoothed_rtt.as_micros() as u64, Ordering::Relaxed);
+    }
+}
+
+/// A receive window tuned by the bandwidth-delay product of the connection.
+///
+/// The limit advertised to the peer is the amount of the data consumed by the application, plus
+/// the window. It is updated once less than half of the window is left, so that the amount of the
+/// data buffered never exceeds the window.
+///
+/// If the updates are sent more often than once per RTT, the window is too small to fill the pipe
+/// while waiting for the update, the window is doubled then, up to the limit.
+#[derive(Debug, Clone)]
+pub struct RecvWindow {
+    window: u64,
+    max_window: u64,
+    last_update: Option<Instant>,
+    rtt: SmoothedRtt,
+}
+
+impl RecvWindow {
+    /// Creates a window starting from `initial_window`, and tuned up to `max_window`.
+    pub fn new(initial_window: u64, max_window: u64, rtt: SmoothedRtt) -> Self {
+        Self {
+            window: initial_window,
+            max_window: max_window.max(initial_window),
+            last_update: None,
+            rtt,
+        }
+    }
+
+    /// Returns the current size of the window.
+    pub fn window(&self) -> u64 {
+        self.window
+    }
+
+    /// Returns the new limit to advertise to the peer, if the credit left is less than half of the
+    /// window, tuning the window by the way.
+    ///
+    /// `consumed` is the amount of the data consumed by the application, and `max_data` is the
+    /// limit advertised to peer currently.
+    pub fn update(&mut self, consumed: u64, max_data: u64, now: Instant) -> Option<u64> {
+        if consumed + self.window / 2 < max_data {
+            return None;
+        }
+        if self
+            .last_update
+            .is_some_and(|last_update| now.duration_since(last_update) < self.rtt.get())
+        {
+            self.window = (self.window * 2).min(self.max_window);
+        }
+        self.last_update = Some(now);
+        let max_data = (consumed + self.window).min(VARINT_MAX).max(max_data);
+        Some(max_data)
+    }
+}
+
+/// The data received by the streams is consumed, by the application reading it or discarded.
+///
+/// It's implemented by the connection-level [`ArcRecvController`], for the streams to give the
+/// credit back to the connection.
+pub trait ConsumeData: Send + Sync {
+    /// Called when `amount` bytes of the new stream data are consumed.
+    fn on_data_consumed(&self, amount: u64);
+}
+
+/// The receive flow control shared by all the streams of a connection.
+///
+/// It creates the receive windows of the streams, and returns the credit consumed by the streams
+/// to the connection.
+#[derive(Clone)]
+pub struct StreamsRecvFlow {
+    connection: Arc<dyn ConsumeData>,
+    max_window: u64,
+    rtt: SmoothedRtt,
+}
+
+impl fmt::Debug for StreamsRecvFlow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StreamsRecvFlow")
+            .field("max_window", &self.max_window)
+            .field("rtt", &self.rtt)
+            .finish()
+    }
+}
+
+impl StreamsRecvFlow {
+    /// Creates the receive flow control of the streams, whose windows are tuned up to
+    /// `max_window`.
+    pub fn new(connection: Arc<dyn ConsumeData>, max_window: u64, rtt: SmoothedRtt) -> Self {
+        Self {
+            connection,
+            max_window,
+            rtt,
+        }
+    }
+
+    /// Creates the receive window of a stream, starting from `initial_window`.
+    pub fn window(&self, initial_window: u64) -> RecvWindow {
+        RecvWindow::new(initial_window, self.max_window, self.rtt.clone())
+    }
+
+    /// Called when `amount` bytes of the stream data are consumed.
+    pub fn on_data_consumed(&self, amount: u64) {
+        if amount > 0 {
+            self.connection.on_data_consumed(amount);
+        }
+    }
+}
+
 /// Receiver's flow controller for managing the flow limit of incoming stream data.
-#[derive(Debug, Default)]
+#[derive(Debug)]
 struct RecvController<TX> {
     rcvd_data: u64,
+    consumed_data: u64,
     max_data: u64,
-    step: u64,
+    window: RecvWindow,
+    max_stream_window: u64,
     broker: TX,
 }
 
 impl<TX> RecvController<TX> {
     /// Creates a new [`RecvController`] with the specified `initial_max_data`.
-    fn new(initial_max_data: u64, broker: TX) -> Self {
+    fn new(initial_max_data: u64, limits: RecvWindowLimits, broker: TX) -> Self {
         Self {
             rcvd_data: 0,
+            consumed_data: 0,
             max_data: initial_max_data,
-            step: initial_max_data / 2,
+            window: RecvWindow::new(initial_max_data, limits.connection, SmoothedRtt::default()),
+            max_stream_window: limits.stream,
             broker,
         }
     }
-}
 
-impl<TX> RecvController<TX>
-where
-    TX: SendFrame<MaxDataFrame>,
-{
     /// Handles the event when new data is received.
     ///
     /// The data must be new, old retransmitted data does not count. Whether the data is
@@ -221,13 +410,6 @@ where
     fn on_new_rcvd(&mut self, frame_type: FrameType, amount: usize) -> Result<usize, Error> {
         self.rcvd_data += amount as u64;
         if self.rcvd_data <= self.max_data {
-            if self.rcvd_data + self.step >= self.max_data {
-                self.max_data += self.step;
-                self.broker
-                    .send_frame([MaxDataFrame::new(VarInt::from_u64(self.max_data).expect(
-                        "max_data of flow controller is very very hard to exceed 2^62 - 1",
-                    ))])
-            }
             Ok(amount)
         } else {
             // Err(Overflow((rcvd_data - max_data) as usize))
@@ -241,6 +423,28 @@ where
     }
 }
 
+impl<TX> RecvController<TX>
+where
+    TX: SendFrame<MaxDataFrame>,
+{
+    /// Handles the event when the new data is consumed, the limit is raised only when the
+    /// application consumes the data, so a slow reader holds the peer back.
+    fn on_data_consumed(&mut self, amount: u64) {
+        self.consumed_data += amount;
+        if let Some(max_data) =
+            self.window
+                .update(self.consumed_data, self.max_data, Instant::now())
+        {
+            if max_data > self.max_data {
+                self.max_data = max_data;
+                self.broker.send_frame([MaxDataFrame::new(
+                    VarInt::from_u64(self.max_data).expect("max_data is limited by VARINT_MAX"),
+                )]);
+            }
+        }
+    }
+}
+
 /// Shared receiver's flow controller for managing the incoming stream data flow.
 ///
 /// Flow control on the receiving end,
@@ -257,17 +461,35 @@ where
 /// promptly send a [`MaxDataFrame`] to the sender after the application layer reads the data,
 /// to expand the receive window since more receive buffer space is freed up,
 /// and to inform the sender that more data can be sent.
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Clone)]
 pub struct ArcRecvController<TX>(Arc<Mutex<RecvController<TX>>>);
 
 impl<TX> ArcRecvController<TX> {
-    /// Creates a new [`ArcRecvController`] with local `initial_max_data` transport parameter.
-    pub fn new(initial_max_data: u64, broker: TX) -> Self {
+    /// Creates a new [`ArcRecvController`] with local `initial_max_data` transport parameter, the
+    /// receive window is tuned up to the `limits`.
+    pub fn new(initial_max_data: u64, limits: RecvWindowLimits, broker: TX) -> Self {
         Self(Arc::new(Mutex::new(RecvController::new(
             initial_max_data,
+            limits,
             broker,
         ))))
     }
+
+    /// Returns the smoothed RTT used to tune the receive windows, which should be updated by the
+    /// path carrying the data.
+    pub fn smoothed_rtt(&self) -> SmoothedRtt {
+        self.0.lock().unwrap().window.rtt.clone()
+    }
+
+    /// Returns the amount of the new stream data received, and the limit of it advertised to
+    /// peer.
+    pub fn window(&self) -> FlowWindow {
+        let guard = self.0.lock().unwrap();
+        FlowWindow {
+            data: guard.rcvd_data,
+            max_data: guard.max_data,
+        }
+    }
 }
 
 impl<TX> ArcRecvController<TX>
@@ -284,6 +506,31 @@ where
     }
 }
 
+impl<TX> ArcRecvController<TX>
+where
+    TX: SendFrame<MaxDataFrame> + Send + 'static,
+{
+    /// Returns the receive flow control of the streams, which give the credit consumed back to
+    /// this controller.
+    pub fn streams_flow(&self) -> StreamsRecvFlow {
+        let guard = self.0.lock().unwrap();
+        StreamsRecvFlow::new(
+            Arc::new(ArcRecvController(self.0.clone())),
+            guard.max_stream_window,
+            guard.window.rtt.clone(),
+        )
+    }
+}
+
+impl<TX> ConsumeData for ArcRecvController<TX>
+where
+    TX: SendFrame<MaxDataFrame> + Send,
+{
+    fn on_data_consumed(&self, amount: u64) {
+        self.0.lock().unwrap().on_data_consumed(amount);
+    }
+}
+
 /// [`ArcRecvController`] need to receive [`DataBlockedFrame`] from peer.
 ///
 /// However, the receiver may also not be able to immediately expand the receive window
@@ -312,15 +559,18 @@ impl<TX: Clone> FlowController<TX> {
     /// Unfortunately, at the beginning, the peer's `initial_max_data` is unknown.
     /// Therefore, peer's `initial_max_data` can be set to 0 initially,
     /// and then updated later after obtaining the peer's `initial_max_data` setting.
+    ///
+    /// The receive windows are tuned up to the `limits`, see [`RecvWindowLimits`].
     pub fn new(
         peer_initial_max_data: u64,
         local_initial_max_data: u64,
+        limits: RecvWindowLimits,
         broker: TX,
         tx_wakers: ArcSendWakers,
     ) -> Self {
         Self {
             sender: ArcSendControler::new(peer_initial_max_data, broker.clone(), tx_wakers),
-            recver: ArcRecvController::new(local_initial_max_data, broker),
+            recver: ArcRecvController::new(local_initial_max_data, limits, broker),
         }
     }
 
@@ -357,8 +607,9 @@ where
     TX: SendFrame<MaxDataFrame>,
 {
     /// Updates the total received data size and checks if the flow control limit is exceeded.
-    /// By the way, it will also send a [`MaxDataFrame`] to the sender
-    /// to expand the receive window if necessary.
+    ///
+    /// The [`MaxDataFrame`] is sent when the data is consumed by the streams, see
+    /// [`ArcRecvController::streams_flow`].
     pub fn on_new_rcvd(&self, frame_type: FrameType, amount: usize) -> Result<usize, Error> {
         self.recver.on_new_rcvd(frame_type, amount)
     }
@@ -405,6 +656,18 @@ mod tests {
         assert_eq!(credit.available(), 100);
         credit.post_sent(50);
         assert_eq!(credit.available(), 50);
+        drop(credit);
+        let window = controler.window();
+        assert_eq!(
+            window,
+            FlowWindow {
+                data: 150,
+                max_data: 200
+            }
+        );
+        assert_eq!(window.credit(), 50);
+
+        let mut credit = controler.credit(200).unwrap();
         credit.post_sent(50);
         assert_eq!(credit.available(), 0);
         drop(credit);
@@ -426,20 +689,62 @@ mod tests {
     #[test]
     fn test_recv_controller() {
         let broker = RecvControllerBroker::default();
-        let controler = ArcRecvController::new(100, broker.clone());
+        let controler = ArcRecvController::new(100, RecvWindowLimits::default(), broker.clone());
         let amount = controler.on_new_rcvd(FrameType::Stream(0), 20).unwrap();
         assert_eq!(amount, 20);
         assert_eq!(broker.lock().unwrap().len(), 0);
 
         let amount = controler.on_new_rcvd(FrameType::Stream(3), 30).unwrap();
         assert_eq!(amount, 30);
+        // the credit is not given back until the data is consumed
+        assert_eq!(broker.lock().unwrap().len(), 0);
+
+        let streams_flow = controler.streams_flow();
+        streams_flow.on_data_consumed(40);
+        assert_eq!(broker.lock().unwrap().len(), 0);
+        streams_flow.on_data_consumed(10);
         // broker should have a MaxDataFrame
         assert_eq!(broker.lock().unwrap().len(), 1);
         assert_eq!(broker.lock().unwrap()[0].max_data(), 150);
+        assert_eq!(
+            controler.window(),
+            FlowWindow {
+                data: 50,
+                max_data: 150
+            }
+        );
 
         // test overflow
         let result = controler.on_new_rcvd(FrameType::ResetStream, 101);
         assert!(result.is_err());
         assert_eq!(result.unwrap_err().kind(), ErrorKind::FlowControl);
     }
+
+    #[test]
+    fn test_recv_window() {
+        let rtt = SmoothedRtt::default();
+        rtt.set(Duration::from_millis(100));
+        let mut window = RecvWindow::new(100, 300, rtt);
+        let now = Instant::now();
+
+        // more than half of the window left
+        assert_eq!(window.update(49, 100, now), None);
+        assert_eq!(window.update(50, 100, now), Some(150));
+        assert_eq!(window.window(), 100);
+
+        // updated again within one RTT, the window is doubled
+        let now = now + Duration::from_millis(50);
+        assert_eq!(window.update(100, 150, now), Some(300));
+        assert_eq!(window.window(), 200);
+
+        // but never exceeds the limit
+        let now = now + Duration::from_millis(50);
+        assert_eq!(window.update(200, 300, now), Some(500));
+        assert_eq!(window.window(), 300);
+
+        // the application reads slowly, the window is kept
+        let now = now + Duration::from_millis(500);
+        assert_eq!(window.update(350, 500, now), Some(650));
+        assert_eq!(window.window(), 300);
+    }
 }
@@ -9,19 +9,29 @@ use super::varint::VarInt;
 use crate::packet::r#type::Type;
 
 mod ack;
+mod ack_frequency;
 mod connection_close;
 mod crypto;
 mod data_blocked;
 mod datagram;
 mod handshake_done;
+mod immediate_ack;
 mod max_data;
+mod max_path_id;
 mod max_stream_data;
 mod max_streams;
 mod new_connection_id;
 mod new_token;
 mod padding;
+mod path_abandon;
+mod path_ack;
 pub mod path_challenge;
+mod path_cids_blocked;
+mod path_new_connection_id;
 mod path_response;
+mod path_retire_connection_id;
+mod path_status;
+mod paths_blocked;
 mod ping;
 mod reset_stream;
 mod retire_connection_id;
@@ -36,6 +46,7 @@ pub mod error;
 pub mod io;
 
 pub use ack::{AckFrame, EcnCounts};
+pub use ack_frequency::AckFrequencyFrame;
 pub use connection_close::{AppCloseFrame, ConnectionCloseFrame, QuicCloseFrame};
 pub use crypto::CryptoFrame;
 pub use data_blocked::DataBlockedFrame;
@@ -43,14 +54,23 @@ pub use datagram::DatagramFrame;
 #[doc(hidden)]
 pub use error::Error;
 pub use handshake_done::HandshakeDoneFrame;
+pub use immediate_ack::ImmediateAckFrame;
 pub use max_data::MaxDataFrame;
+pub use max_path_id::MaxPathIdFrame;
 pub use max_stream_data::MaxStreamDataFrame;
 pub use max_streams::MaxStreamsFrame;
 pub use new_connection_id::NewConnectionIdFrame;
 pub use new_token::NewTokenFrame;
 pub use padding::PaddingFrame;
+pub use path_abandon::PathAbandonFrame;
+pub use path_ack::PathAckFrame;
 pub use path_challenge::PathChallengeFrame;
+pub use path_cids_blocked::PathCidsBlockedFrame;
+pub use path_new_connection_id::PathNewConnectionIdFrame;
 pub use path_response::PathResponseFrame;
+pub use path_retire_connection_id::PathRetireConnectionIdFrame;
+pub use path_status::PathStatusFrame;
+pub use paths_blocked::PathsBlockedFrame;
 pub use ping::PingFrame;
 pub use reset_stream::{ResetStreamError, ResetStreamFrame};
 pub use retire_connection_id::RetireConnectionIdFrame;
@@ -180,6 +200,28 @@ pub enum FrameType {
     HandshakeDone,
     /// DATAGRAM frame, see [`DatagramFrame`].
     Datagram(u8),
+    /// ACK_FREQUENCY frame, see [`AckFrequencyFrame`].
+    AckFrequency,
+    /// IMMEDIATE_ACK frame, see [`ImmediateAckFrame`].
+    ImmediateAck,
+    /// PATH_ACK frame, see [`PathAckFrame`].
+    PathAck(u8),
+    /// PATH_ABANDON frame, see [`PathAbandonFrame`].
+    PathAbandon,
+    /// PATH_STATUS_BACKUP frame, see [`PathStatusFrame`].
+    PathStatusBackup,
+    /// PATH_STATUS_AVAILABLE frame, see [`PathStatusFrame`].
+    PathStatusAvailable,
+    /// PATH_NEW_CONNECTION_ID frame, see [`PathNewConnectionIdFrame`].
+    PathNewConnectionId,
+    /// PATH_RETIRE_CONNECTION_ID frame, see [`PathRetireConnectionIdFrame`].
+    PathRetireConnectionId,
+    /// MAX_PATH_ID frame, see [`MaxPathIdFrame`].
+    MaxPathId,
+    /// PATHS_BLOCKED frame, see [`PathsBlockedFrame`].
+    PathsBlocked,
+    /// PATH_CIDS_BLOCKED frame, see [`PathCidsBlockedFrame`].
+    PathCidsBlocked,
 }
 
 #[enum_dispatch]
@@ -203,13 +245,25 @@ impl<T: GetFrameType> FrameFeture for T {
 impl FrameFeture for FrameType {
     fn belongs_to(&self, packet_type: Type) -> bool {
         use crate::packet::r#type::{
-            long::{Type::V1, Ver1},
+            long::{
+                Type::{V1, V2},
+                Ver1, Ver2,
+            },
             short::OneRtt,
         };
         // IH01
-        let i = matches!(packet_type, Type::Long(V1(Ver1::INITIAL)));
-        let h = matches!(packet_type, Type::Long(V1(Ver1::HANDSHAKE)));
-        let o = matches!(packet_type, Type::Long(V1(Ver1::ZERO_RTT)));
+        let i = matches!(
+            packet_type,
+            Type::Long(V1(Ver1::INITIAL) | V2(Ver2::INITIAL))
+        );
+        let h = matches!(
+            packet_type,
+            Type::Long(V1(Ver1::HANDSHAKE) | V2(Ver2::HANDSHAKE))
+        );
+        let o = matches!(
+            packet_type,
+            Type::Long(V1(Ver1::ZERO_RTT) | V2(Ver2::ZERO_RTT))
+        );
         let l = matches!(packet_type, Type::Short(OneRtt(_)));
 
         match self {
@@ -247,6 +301,19 @@ impl FrameFeture for FrameType {
             }
             FrameType::HandshakeDone => l,
             FrameType::Datagram(_) => o | l,
+            FrameType::AckFrequency => o | l,
+            FrameType::ImmediateAck => o | l,
+            // The multipath extension is negotiated in the handshake, 0-RTT packets
+            // can only be sent on the initial path.
+            FrameType::PathAck(_)
+            | FrameType::PathAbandon
+            | FrameType::PathStatusBackup
+            | FrameType::PathStatusAvailable
+            | FrameType::PathNewConnectionId
+            | FrameType::PathRetireConnectionId
+            | FrameType::MaxPathId
+            | FrameType::PathsBlocked
+            | FrameType::PathCidsBlocked => l,
         }
     }
 
@@ -259,9 +326,9 @@ impl FrameFeture for FrameType {
         );
         match self {
             FrameType::Padding => n | p,
-            FrameType::Ack(_) => n | c,
+            FrameType::Ack(_) | FrameType::PathAck(_) => n | c,
             FrameType::Stream(_) => f,
-            FrameType::NewConnectionId => p,
+            FrameType::NewConnectionId | FrameType::PathNewConnectionId => p,
             FrameType::PathChallenge => p,
             FrameType::PathResponse => p,
             // different from [table 3](https://www.rfc-editor.org/rfc/rfc9000.html#table-3),
@@ -305,6 +372,18 @@ impl TryFrom<VarInt> for FrameType {
             // The last bit is the length flag bit, 0 the length field is absent and the Datagram Data
             // field extends to the end of the packet, 1 the length field is present.
             ty @ (0x30 | 0x31) => FrameType::Datagram(ty as u8 & 1),
+            0xaf => FrameType::AckFrequency,
+            0x1f => FrameType::ImmediateAck,
+            // The last bit is the ECN flag.
+            ty @ (0x15228c00 | 0x15228c01) => FrameType::PathAck(ty as u8 & 0b1),
+            0x15228c05 => FrameType::PathAbandon,
+            0x15228c07 => FrameType::PathStatusBackup,
+            0x15228c08 => FrameType::PathStatusAvailable,
+            0x15228c09 => FrameType::PathNewConnectionId,
+            0x15228c0a => FrameType::PathRetireConnectionId,
+            0x15228c0c => FrameType::MaxPathId,
+            0x15228c0d => FrameType::PathsBlocked,
+            0x15228c0e => FrameType::PathCidsBlocked,
             // May be extension frame
             _ => return Err(Self::Error::InvalidType(frame_type)),
         })
@@ -335,6 +414,17 @@ impl From<FrameType> for VarInt {
             FrameType::ConnectionClose(layer) => VarInt::from(0x1c | layer),
             FrameType::HandshakeDone => VarInt::from_u32(0x1e),
             FrameType::Datagram(with_len) => VarInt::from(0x30 | with_len),
+            FrameType::AckFrequency => VarInt::from_u32(0xaf),
+            FrameType::ImmediateAck => VarInt::from_u32(0x1f),
+            FrameType::PathAck(ecn) => VarInt::from_u32(0x15228c00 | ecn as u32),
+            FrameType::PathAbandon => VarInt::from_u32(0x15228c05),
+            FrameType::PathStatusBackup => VarInt::from_u32(0x15228c07),
+            FrameType::PathStatusAvailable => VarInt::from_u32(0x15228c08),
+            FrameType::PathNewConnectionId => VarInt::from_u32(0x15228c09),
+            FrameType::PathRetireConnectionId => VarInt::from_u32(0x15228c0a),
+            FrameType::MaxPathId => VarInt::from_u32(0x15228c0c),
+            FrameType::PathsBlocked => VarInt::from_u32(0x15228c0d),
+            FrameType::PathCidsBlocked => VarInt::from_u32(0x15228c0e),
         }
     }
 }
@@ -385,6 +475,22 @@ pub enum ReliableFrame {
     RetireConnectionId(RetireConnectionIdFrame),
     /// HANDSHAKE_DONE frame, see [`HandshakeDoneFrame`].
     HandshakeDone(HandshakeDoneFrame),
+    /// ACK_FREQUENCY frame, see [`AckFrequencyFrame`].
+    AckFrequency(AckFrequencyFrame),
+    /// PATH_ABANDON frame, see [`PathAbandonFrame`].
+    PathAbandon(PathAbandonFrame),
+    /// PATH_STATUS_BACKUP or PATH_STATUS_AVAILABLE frame, see [`PathStatusFrame`].
+    PathStatus(PathStatusFrame),
+    /// PATH_NEW_CONNECTION_ID frame, see [`PathNewConnectionIdFrame`].
+    PathNewConnectionId(PathNewConnectionIdFrame),
+    /// PATH_RETIRE_CONNECTION_ID frame, see [`PathRetireConnectionIdFrame`].
+    PathRetireConnectionId(PathRetireConnectionIdFrame),
+    /// MAX_PATH_ID frame, see [`MaxPathIdFrame`].
+    MaxPathId(MaxPathIdFrame),
+    /// PATHS_BLOCKED frame, see [`PathsBlockedFrame`].
+    PathsBlocked(PathsBlockedFrame),
+    /// PATH_CIDS_BLOCKED frame, see [`PathCidsBlockedFrame`].
+    PathCidsBlocked(PathCidsBlockedFrame),
     /// STREAM control frame, see [`StreamCtlFrame`].
     Stream(StreamCtlFrame),
 }
@@ -414,6 +520,26 @@ pub enum Frame {
     RetireConnectionId(RetireConnectionIdFrame),
     /// HANDSHAKE_DONE frame, see [`HandshakeDoneFrame`].
     HandshakeDone(HandshakeDoneFrame),
+    /// ACK_FREQUENCY frame, see [`AckFrequencyFrame`].
+    AckFrequency(AckFrequencyFrame),
+    /// IMMEDIATE_ACK frame, see [`ImmediateAckFrame`].
+    ImmediateAck(ImmediateAckFrame),
+    /// PATH_ACK frame, see [`PathAckFrame`].
+    PathAck(PathAckFrame),
+    /// PATH_ABANDON frame, see [`PathAbandonFrame`].
+    PathAbandon(PathAbandonFrame),
+    /// PATH_STATUS_BACKUP or PATH_STATUS_AVAILABLE frame, see [`PathStatusFrame`].
+    PathStatus(PathStatusFrame),
+    /// PATH_NEW_CONNECTION_ID frame, see [`PathNewConnectionIdFrame`].
+    PathNewConnectionId(PathNewConnectionIdFrame),
+    /// PATH_RETIRE_CONNECTION_ID frame, see [`PathRetireConnectionIdFrame`].
+    PathRetireConnectionId(PathRetireConnectionIdFrame),
+    /// MAX_PATH_ID frame, see [`MaxPathIdFrame`].
+    MaxPathId(MaxPathIdFrame),
+    /// PATHS_BLOCKED frame, see [`PathsBlockedFrame`].
+    PathsBlocked(PathsBlockedFrame),
+    /// PATH_CIDS_BLOCKED frame, see [`PathCidsBlockedFrame`].
+    PathCidsBlocked(PathCidsBlockedFrame),
     /// PATH_CHALLENGE frame, see [`PathChallengeFrame`].
     Challenge(PathChallengeFrame),
     /// PATH_RESPONSE frame, see [`PathResponseFrame`].
@@ -445,6 +571,26 @@ impl From<ReliableFrame> for Frame {
             ReliableFrame::HandshakeDone(handshake_done_frame) => {
                 Frame::HandshakeDone(handshake_done_frame)
             }
+            ReliableFrame::AckFrequency(ack_frequency_frame) => {
+                Frame::AckFrequency(ack_frequency_frame)
+            }
+            ReliableFrame::PathAbandon(path_abandon_frame) => {
+                Frame::PathAbandon(path_abandon_frame)
+            }
+            ReliableFrame::PathStatus(path_status_frame) => Frame::PathStatus(path_status_frame),
+            ReliableFrame::PathNewConnectionId(path_new_connection_id_frame) => {
+                Frame::PathNewConnectionId(path_new_connection_id_frame)
+            }
+            ReliableFrame::PathRetireConnectionId(path_retire_connection_id_frame) => {
+                Frame::PathRetireConnectionId(path_retire_connection_id_frame)
+            }
+            ReliableFrame::MaxPathId(max_path_id_frame) => Frame::MaxPathId(max_path_id_frame),
+            ReliableFrame::PathsBlocked(paths_blocked_frame) => {
+                Frame::PathsBlocked(paths_blocked_frame)
+            }
+            ReliableFrame::PathCidsBlocked(path_cids_blocked_frame) => {
+                Frame::PathCidsBlocked(path_cids_blocked_frame)
+            }
             ReliableFrame::Stream(stream_frame) => Frame::StreamCtl(stream_frame),
         }
     }
@@ -531,6 +677,14 @@ impl<T: BufMut> WriteFrame<ReliableFrame> for T {
             ReliableFrame::NewConnectionId(frame) => self.put_frame(frame),
             ReliableFrame::RetireConnectionId(frame) => self.put_frame(frame),
             ReliableFrame::HandshakeDone(frame) => self.put_frame(frame),
+            ReliableFrame::AckFrequency(frame) => self.put_frame(frame),
+            ReliableFrame::PathAbandon(frame) => self.put_frame(frame),
+            ReliableFrame::PathStatus(frame) => self.put_frame(frame),
+            ReliableFrame::PathNewConnectionId(frame) => self.put_frame(frame),
+            ReliableFrame::PathRetireConnectionId(frame) => self.put_frame(frame),
+            ReliableFrame::MaxPathId(frame) => self.put_frame(frame),
+            ReliableFrame::PathsBlocked(frame) => self.put_frame(frame),
+            ReliableFrame::PathCidsBlocked(frame) => self.put_frame(frame),
             ReliableFrame::Stream(frame) => self.put_frame(frame),
         }
     }
@@ -564,6 +718,17 @@ mod tests {
             FrameType::ConnectionClose(0),
             FrameType::HandshakeDone,
             FrameType::Datagram(0),
+            FrameType::AckFrequency,
+            FrameType::ImmediateAck,
+            FrameType::PathAck(1),
+            FrameType::PathAbandon,
+            FrameType::PathStatusBackup,
+            FrameType::PathStatusAvailable,
+            FrameType::PathNewConnectionId,
+            FrameType::PathRetireConnectionId,
+            FrameType::MaxPathId,
+            FrameType::PathsBlocked,
+            FrameType::PathCidsBlocked,
         ];
 
         for frame_type in frame_types {
@@ -2,7 +2,10 @@ use std::ops::RangeInclusive;
 
 use nom::{Parser, combinator::map};
 
-use crate::varint::{VarInt, WriteVarInt, be_varint};
+use crate::{
+    net::route::Ecn,
+    varint::{VarInt, WriteVarInt, be_varint},
+};
 
 /// ACK Frame
 ///
@@ -145,7 +148,7 @@ impl AckFrame {
 /// The counts of Explicit Congestion Notification (ECN) types.
 ///
 /// See [ecn-counts](https://www.rfc-editor.org/rfc/rfc9000.html#name-ecn-counts) of QUIC RFC 9000.
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
 pub struct EcnCounts {
     ect0: VarInt,
     ect1: VarInt,
@@ -173,8 +176,24 @@ impl EcnCounts {
         self.ce.into_inner()
     }
 
+    /// Increase the counter of the given ECN codepoint, Not-ECT is not counted.
+    pub fn increase(&mut self, ecn: Ecn) {
+        let counter = match ecn {
+            Ecn::NotEct => return,
+            Ecn::Ect0 => &mut self.ect0,
+            Ecn::Ect1 => &mut self.ect1,
+            Ecn::Ce => &mut self.ce,
+        };
+        *counter = VarInt::from_u64(counter.into_inner() + 1).expect("ECN count overflow");
+    }
+
+    /// Whether no packet with ECN codepoint has been counted.
+    pub fn is_zero(&self) -> bool {
+        *self == Self::default()
+    }
+
     /// Calculates the encoding size of the [`EcnCounts`] struct.
-    fn encoding_size(&self) -> usize {
+    pub fn encoding_size(&self) -> usize {
         self.ect0.encoding_size() + self.ect1.encoding_size() + self.ce.encoding_size()
     }
 }
@@ -231,21 +250,27 @@ impl<T: bytes::BufMut> super::io::WriteFrame<AckFrame> for T {
             frame_type |= ECN_OPT;
         }
         self.put_u8(frame_type);
-        self.put_varint(&frame.largest);
-        self.put_varint(&frame.delay);
-
-        let ack_range_count = VarInt::try_from(frame.ranges.len()).unwrap();
-        self.put_varint(&ack_range_count);
-        self.put_varint(&frame.first_range);
-        for (gap, ack) in &frame.ranges {
-            self.put_varint(gap);
-            self.put_varint(ack);
-        }
-        if let Some(ecn) = &frame.ecn {
-            self.put_varint(&ecn.ect0);
-            self.put_varint(&ecn.ect1);
-            self.put_varint(&ecn.ce);
-        }
+        put_ack_frame_body(self, frame);
+    }
+}
+
+/// Write the fields of the ACK frame following the frame type,
+/// they are shared by the PATH_ACK frame.
+pub(super) fn put_ack_frame_body(buf: &mut impl bytes::BufMut, frame: &AckFrame) {
+    buf.put_varint(&frame.largest);
+    buf.put_varint(&frame.delay);
+
+    let ack_range_count = VarInt::try_from(frame.ranges.len()).unwrap();
+    buf.put_varint(&ack_range_count);
+    buf.put_varint(&frame.first_range);
+    for (gap, ack) in &frame.ranges {
+        buf.put_varint(gap);
+        buf.put_varint(ack);
+    }
+    if let Some(ecn) = &frame.ecn {
+        buf.put_varint(&ecn.ect0);
+        buf.put_varint(&ecn.ect1);
+        buf.put_varint(&ecn.ce);
     }
 }
 
@@ -0,0 +1,175 @@
+use std::time::Duration;
+
+use crate::varint::{VarInt, WriteVarInt, be_varint};
+
+/// ACK_FREQUENCY frame.
+///
+/// ```text
+/// ACK_FREQUENCY Frame {
+///   Type (i) = 0xaf,
+///   Sequence Number (i),
+///   Ack-Eliciting Threshold (i),
+///   Request Max Ack Delay (i),
+///   Reordering Threshold (i),
+/// }
+/// ```
+///
+/// See [ACK_FREQUENCY Frame](https://datatracker.ietf.org/doc/html/draft-ietf-quic-ack-frequency#name-ack_frequency-frame)
+/// of [QUIC Acknowledgment Frequency](https://datatracker.ietf.org/doc/html/draft-ietf-quic-ack-frequency)
+/// for more details.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AckFrequencyFrame {
+    sequence: VarInt,
+    ack_eliciting_threshold: VarInt,
+    request_max_ack_delay: VarInt,
+    reordering_threshold: VarInt,
+}
+
+const ACK_FREQUENCY_FRAME_TYPE: u32 = 0xaf;
+
+impl super::GetFrameType for AckFrequencyFrame {
+    fn frame_type(&self) -> super::FrameType {
+        super::FrameType::AckFrequency
+    }
+}
+
+impl super::EncodeFrame for AckFrequencyFrame {
+    fn max_encoding_size(&self) -> usize {
+        2 + 8 + 8 + 8 + 8
+    }
+
+    fn encoding_size(&self) -> usize {
+        2 + self.sequence.encoding_size()
+            + self.ack_eliciting_threshold.encoding_size()
+            + self.request_max_ack_delay.encoding_size()
+            + self.reordering_threshold.encoding_size()
+    }
+}
+
+impl AckFrequencyFrame {
+    /// Create a new [`AckFrequencyFrame`].
+    ///
+    /// The `request_max_ack_delay` is in microseconds.
+    pub fn new(
+        sequence: VarInt,
+        ack_eliciting_threshold: VarInt,
+        request_max_ack_delay: VarInt,
+        reordering_threshold: VarInt,
+    ) -> Self {
+        Self {
+            sequence,
+            ack_eliciting_threshold,
+            request_max_ack_delay,
+            reordering_threshold,
+        }
+    }
+
+    /// Return the sequence number of the frame.
+    pub fn sequence(&self) -> u64 {
+        self.sequence.into_inner()
+    }
+
+    /// Return the number of ack-eliciting packets the receiver may get
+    /// without sending an acknowledgment immediately.
+    pub fn ack_eliciting_threshold(&self) -> u64 {
+        self.ack_eliciting_threshold.into_inner()
+    }
+
+    /// Return the requested maximum acknowledgment delay.
+    pub fn request_max_ack_delay(&self) -> Duration {
+        Duration::from_micros(self.request_max_ack_delay.into_inner())
+    }
+
+    /// Return the reordering threshold, 0 means out-of-order packets
+    /// should not trigger an immediate acknowledgment.
+    pub fn reordering_threshold(&self) -> u64 {
+        self.reordering_threshold.into_inner()
+    }
+}
+
+/// Parse an ACK_FREQUENCY frame from the input buffer,
+/// [nom](https://docs.rs/nom/latest/nom/) parser style.
+pub fn be_ack_frequency_frame(input: &[u8]) -> nom::IResult<&[u8], AckFrequencyFrame> {
+    use nom::{Parser, combinator::map};
+    map(
+        (be_varint, be_varint, be_varint, be_varint),
+        |(sequence, ack_eliciting_threshold, request_max_ack_delay, reordering_threshold)| {
+            AckFrequencyFrame {
+                sequence,
+                ack_eliciting_threshold,
+                request_max_ack_delay,
+                reordering_threshold,
+            }
+        },
+    )
+    .parse(input)
+}
+
+impl<T: bytes::BufMut> super::io::WriteFrame<AckFrequencyFrame> for T {
+    fn put_frame(&mut self, frame: &AckFrequencyFrame) {
+        self.put_varint(&VarInt::from_u32(ACK_FREQUENCY_FRAME_TYPE));
+        self.put_varint(&frame.sequence);
+        self.put_varint(&frame.ack_eliciting_threshold);
+        self.put_varint(&frame.request_max_ack_delay);
+        self.put_varint(&frame.reordering_threshold);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{ACK_FREQUENCY_FRAME_TYPE, AckFrequencyFrame};
+    use crate::{
+        frame::{EncodeFrame, FrameType, GetFrameType, io::WriteFrame},
+        varint::VarInt,
+    };
+
+    fn frame() -> AckFrequencyFrame {
+        AckFrequencyFrame::new(
+            VarInt::from_u32(1),
+            VarInt::from_u32(10),
+            VarInt::from_u32(25_000),
+            VarInt::from_u32(3),
+        )
+    }
+
+    #[test]
+    fn test_ack_frequency_frame() {
+        let frame = frame();
+        assert_eq!(frame.frame_type(), FrameType::AckFrequency);
+        assert_eq!(frame.max_encoding_size(), 2 + 8 + 8 + 8 + 8);
+        assert_eq!(frame.encoding_size(), 2 + 1 + 1 + 4 + 1);
+        assert_eq!(frame.request_max_ack_delay(), Duration::from_millis(25));
+    }
+
+    #[test]
+    fn test_read_ack_frequency_frame() {
+        use nom::{Parser, combinator::flat_map};
+
+        use super::be_ack_frequency_frame;
+        use crate::varint::be_varint;
+        let buf = vec![0x40, 0xaf, 0x01, 0x0a, 0x80, 0x00, 0x61, 0xa8, 0x03];
+        let (input, frame) = flat_map(be_varint, |frame_type| {
+            if frame_type.into_inner() == ACK_FREQUENCY_FRAME_TYPE as u64 {
+                be_ack_frequency_frame
+            } else {
+                panic!("wrong frame type: {frame_type}")
+            }
+        })
+        .parse(buf.as_ref())
+        .unwrap();
+        assert!(input.is_empty());
+        assert_eq!(frame, super::tests::frame());
+    }
+
+    #[test]
+    fn test_write_ack_frequency_frame() {
+        let mut buf = Vec::new();
+        buf.put_frame(&frame());
+        assert_eq!(
+            buf,
+            vec![0x40, 0xaf, 0x01, 0x0a, 0x80, 0x00, 0x61, 0xa8, 0x03]
+        );
+    }
+}
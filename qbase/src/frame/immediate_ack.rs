@@ -0,0 +1,77 @@
+use super::EncodeFrame;
+
+/// IMMEDIATE_ACK frame
+///
+/// ```text
+/// IMMEDIATE_ACK Frame {
+///   Type (i) = 0x1f,
+/// }
+/// ```
+///
+/// See [IMMEDIATE_ACK Frame](https://datatracker.ietf.org/doc/html/draft-ietf-quic-ack-frequency#name-immediate_ack-frame)
+/// of [QUIC Acknowledgment Frequency](https://datatracker.ietf.org/doc/html/draft-ietf-quic-ack-frequency)
+/// for more details.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ImmediateAckFrame;
+
+const IMMEDIATE_ACK_FRAME_TYPE: u8 = 0x1f;
+
+impl super::GetFrameType for ImmediateAckFrame {
+    fn frame_type(&self) -> super::FrameType {
+        super::FrameType::ImmediateAck
+    }
+}
+
+impl EncodeFrame for ImmediateAckFrame {}
+
+/// Parse an IMMEDIATE_ACK frame from the input buffer,
+/// [nom](https://docs.rs/nom/latest/nom/) parser style.
+#[allow(unused)]
+pub fn be_immediate_ack_frame(input: &[u8]) -> nom::IResult<&[u8], ImmediateAckFrame> {
+    Ok((input, ImmediateAckFrame))
+}
+
+impl<T: bytes::BufMut> super::io::WriteFrame<ImmediateAckFrame> for T {
+    fn put_frame(&mut self, _: &ImmediateAckFrame) {
+        self.put_u8(IMMEDIATE_ACK_FRAME_TYPE);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::frame::{EncodeFrame, FrameType, GetFrameType, ImmediateAckFrame, io::WriteFrame};
+
+    #[test]
+    fn test_immediate_ack_frame() {
+        assert_eq!(ImmediateAckFrame.frame_type(), FrameType::ImmediateAck);
+        assert_eq!(ImmediateAckFrame.max_encoding_size(), 1);
+        assert_eq!(ImmediateAckFrame.encoding_size(), 1);
+    }
+
+    #[test]
+    fn test_read_immediate_ack_frame() {
+        use nom::{Parser, combinator::flat_map};
+
+        use super::be_immediate_ack_frame;
+        use crate::varint::be_varint;
+        let buf = vec![super::IMMEDIATE_ACK_FRAME_TYPE];
+        let (input, frame) = flat_map(be_varint, |frame_type| {
+            if frame_type.into_inner() == super::IMMEDIATE_ACK_FRAME_TYPE as u64 {
+                be_immediate_ack_frame
+            } else {
+                panic!("wrong frame type: {frame_type}")
+            }
+        })
+        .parse(buf.as_ref())
+        .unwrap();
+        assert!(input.is_empty());
+        assert_eq!(frame, super::ImmediateAckFrame);
+    }
+
+    #[test]
+    fn test_write_immediate_ack_frame() {
+        let mut buf = Vec::new();
+        buf.put_frame(&ImmediateAckFrame);
+        assert_eq!(buf, vec![super::IMMEDIATE_ACK_FRAME_TYPE]);
+    }
+}
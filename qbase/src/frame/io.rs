@@ -1,12 +1,17 @@
 use bytes::Bytes;
 
 use super::{
-    ack::ack_frame_with_flag, connection_close::connection_close_frame_at_layer,
-    crypto::be_crypto_frame, data_blocked::be_data_blocked_frame,
-    datagram::datagram_frame_with_flag, max_data::be_max_data_frame,
+    ack::ack_frame_with_flag, ack_frequency::be_ack_frequency_frame,
+    connection_close::connection_close_frame_at_layer, crypto::be_crypto_frame,
+    data_blocked::be_data_blocked_frame, datagram::datagram_frame_with_flag,
+    max_data::be_max_data_frame, max_path_id::be_max_path_id_frame,
     max_stream_data::be_max_stream_data_frame, max_streams::max_streams_frame_with_dir,
     new_connection_id::be_new_connection_id_frame, new_token::be_new_token_frame,
-    path_challenge::be_path_challenge_frame, path_response::be_path_response_frame,
+    path_abandon::be_path_abandon_frame, path_ack::path_ack_frame_with_flag,
+    path_challenge::be_path_challenge_frame, path_cids_blocked::be_path_cids_blocked_frame,
+    path_new_connection_id::be_path_new_connection_id_frame, path_response::be_path_response_frame,
+    path_retire_connection_id::be_path_retire_connection_id_frame,
+    path_status::path_status_frame_with_backup, paths_blocked::be_paths_blocked_frame,
     reset_stream::be_reset_stream_frame, retire_connection_id::be_retire_connection_id_frame,
     stop_sending::be_stop_sending_frame, stream::stream_frame_with_flag,
     stream_data_blocked::be_stream_data_blocked_frame,
@@ -41,8 +46,31 @@ fn complete_frame(
         FrameType::PathChallenge => map(be_path_challenge_frame, Frame::Challenge).parse(input),
         FrameType::PathResponse => map(be_path_response_frame, Frame::Response).parse(input),
         FrameType::HandshakeDone => Ok((input, Frame::HandshakeDone(HandshakeDoneFrame))),
+        FrameType::AckFrequency => map(be_ack_frequency_frame, Frame::AckFrequency).parse(input),
+        FrameType::ImmediateAck => Ok((input, Frame::ImmediateAck(ImmediateAckFrame))),
         FrameType::NewToken => map(be_new_token_frame, Frame::NewToken).parse(input),
         FrameType::Ack(ecn) => map(ack_frame_with_flag(ecn), Frame::Ack).parse(input),
+        FrameType::PathAck(ecn) => map(path_ack_frame_with_flag(ecn), Frame::PathAck).parse(input),
+        FrameType::PathAbandon => map(be_path_abandon_frame, Frame::PathAbandon).parse(input),
+        FrameType::PathStatusBackup => {
+            map(path_status_frame_with_backup(true), Frame::PathStatus).parse(input)
+        }
+        FrameType::PathStatusAvailable => {
+            map(path_status_frame_with_backup(false), Frame::PathStatus).parse(input)
+        }
+        FrameType::PathNewConnectionId => {
+            map(be_path_new_connection_id_frame, Frame::PathNewConnectionId).parse(input)
+        }
+        FrameType::PathRetireConnectionId => map(
+            be_path_retire_connection_id_frame,
+            Frame::PathRetireConnectionId,
+        )
+        .parse(input),
+        FrameType::MaxPathId => map(be_max_path_id_frame, Frame::MaxPathId).parse(input),
+        FrameType::PathsBlocked => map(be_paths_blocked_frame, Frame::PathsBlocked).parse(input),
+        FrameType::PathCidsBlocked => {
+            map(be_path_cids_blocked_frame, Frame::PathCidsBlocked).parse(input)
+        }
         FrameType::ResetStream => {
             map(be_reset_stream_frame, |f| Frame::StreamCtl(f.into())).parse(input)
         }
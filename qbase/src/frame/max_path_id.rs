@@ -0,0 +1,109 @@
+use crate::varint::{VarInt, WriteVarInt, be_varint};
+
+/// MAX_PATH_ID frame.
+///
+/// ```text
+/// MAX_PATH_ID Frame {
+///   Type (i) = 0x15228c0c,
+///   Maximum Path Identifier (i),
+/// }
+/// ```
+///
+/// Raises the maximum path identifier the peer is allowed to open paths with.
+///
+/// See [MAX_PATH_ID Frame](https://datatracker.ietf.org/doc/html/draft-ietf-quic-multipath#name-max_path_id-frame)
+/// of [Multipath Extension for QUIC](https://datatracker.ietf.org/doc/html/draft-ietf-quic-multipath)
+/// for more details.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MaxPathIdFrame {
+    max_path_id: VarInt,
+}
+
+const MAX_PATH_ID_FRAME_TYPE: u32 = 0x15228c0c;
+
+impl super::GetFrameType for MaxPathIdFrame {
+    fn frame_type(&self) -> super::FrameType {
+        super::FrameType::MaxPathId
+    }
+}
+
+impl super::EncodeFrame for MaxPathIdFrame {
+    fn max_encoding_size(&self) -> usize {
+        4 + 8
+    }
+
+    fn encoding_size(&self) -> usize {
+        4 + self.max_path_id.encoding_size()
+    }
+}
+
+impl MaxPathIdFrame {
+    /// Create a new [`MaxPathIdFrame`].
+    pub fn new(max_path_id: VarInt) -> Self {
+        Self { max_path_id }
+    }
+
+    /// Return the maximum path identifier.
+    pub fn max_path_id(&self) -> u64 {
+        self.max_path_id.into_inner()
+    }
+}
+
+/// Parse a MAX_PATH_ID frame from the input buffer,
+/// [nom](https://docs.rs/nom/latest/nom/) parser style.
+pub fn be_max_path_id_frame(input: &[u8]) -> nom::IResult<&[u8], MaxPathIdFrame> {
+    use nom::{Parser, combinator::map};
+    map(be_varint, MaxPathIdFrame::new).parse(input)
+}
+
+impl<T: bytes::BufMut> super::io::WriteFrame<MaxPathIdFrame> for T {
+    fn put_frame(&mut self, frame: &MaxPathIdFrame) {
+        self.put_varint(&VarInt::from_u32(MAX_PATH_ID_FRAME_TYPE));
+        self.put_varint(&frame.max_path_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MAX_PATH_ID_FRAME_TYPE, MaxPathIdFrame};
+    use crate::{
+        frame::{EncodeFrame, FrameType, GetFrameType, io::WriteFrame},
+        varint::VarInt,
+    };
+
+    #[test]
+    fn test_max_path_id_frame() {
+        let frame = MaxPathIdFrame::new(VarInt::from_u32(0x1234));
+        assert_eq!(frame.frame_type(), FrameType::MaxPathId);
+        assert_eq!(frame.max_encoding_size(), 4 + 8);
+        assert_eq!(frame.encoding_size(), 4 + 2);
+        assert_eq!(frame.max_path_id(), 0x1234);
+    }
+
+    #[test]
+    fn test_read_max_path_id_frame() {
+        use nom::{Parser, combinator::flat_map};
+
+        use super::be_max_path_id_frame;
+        use crate::varint::be_varint;
+        let buf = vec![0x95, 0x22, 0x8c, 0x0c, 0x52, 0x34];
+        let (input, frame) = flat_map(be_varint, |frame_type| {
+            if frame_type.into_inner() == MAX_PATH_ID_FRAME_TYPE as u64 {
+                be_max_path_id_frame
+            } else {
+                panic!("wrong frame type: {frame_type}")
+            }
+        })
+        .parse(buf.as_ref())
+        .unwrap();
+        assert!(input.is_empty());
+        assert_eq!(frame, MaxPathIdFrame::new(VarInt::from_u32(0x1234)));
+    }
+
+    #[test]
+    fn test_write_max_path_id_frame() {
+        let mut buf = Vec::new();
+        buf.put_frame(&MaxPathIdFrame::new(VarInt::from_u32(0x1234)));
+        assert_eq!(buf, vec![0x95, 0x22, 0x8c, 0x0c, 0x52, 0x34]);
+    }
+}
@@ -31,8 +31,12 @@ pub struct NewConnectionIdFrame {
 
 impl NewConnectionIdFrame {
     /// Create a new [`NewConnectionIdFrame`].
-    pub fn new(cid: ConnectionId, sequence: VarInt, retire_prior_to: VarInt) -> Self {
-        let reset_token = ResetToken::random_gen();
+    pub fn new(
+        cid: ConnectionId,
+        sequence: VarInt,
+        retire_prior_to: VarInt,
+        reset_token: ResetToken,
+    ) -> Self {
         Self {
             sequence,
             retire_prior_to,
@@ -124,13 +128,22 @@ pub fn be_new_connection_id_frame(input: &[u8]) -> nom::IResult<&[u8], NewConnec
 impl<T: bytes::BufMut> super::io::WriteFrame<NewConnectionIdFrame> for T {
     fn put_frame(&mut self, frame: &NewConnectionIdFrame) {
         self.put_u8(NEW_CONNECTION_ID_FRAME_TYPE);
-        self.put_varint(&frame.sequence);
-        self.put_varint(&frame.retire_prior_to);
-        self.put_connection_id(&frame.id);
-        self.put_slice(frame.reset_token.as_slice());
+        put_new_connection_id_frame_body(self, frame);
     }
 }
 
+/// Write the fields of the NEW_CONNECTION_ID frame following the frame type,
+/// they are shared by the PATH_NEW_CONNECTION_ID frame.
+pub(super) fn put_new_connection_id_frame_body(
+    buf: &mut impl bytes::BufMut,
+    frame: &NewConnectionIdFrame,
+) {
+    buf.put_varint(&frame.sequence);
+    buf.put_varint(&frame.retire_prior_to);
+    buf.put_connection_id(&frame.id);
+    buf.put_slice(frame.reset_token.as_slice());
+}
+
 #[cfg(test)]
 mod tests {
     use bytes::{BufMut, BytesMut};
@@ -144,6 +157,7 @@ mod tests {
             ConnectionId::from_slice(&[1, 2, 3, 4][..]),
             VarInt::from_u32(1),
             VarInt::from_u32(0),
+            ResetToken::random_gen(),
         );
         assert_eq!(new_cid_frame.sequence(), 1);
         assert_eq!(new_cid_frame.retire_prior_to(), 0);
@@ -164,8 +178,12 @@ mod tests {
     fn test_frame_parsing() {
         let mut buf = BytesMut::new();
         let original_cid = ConnectionId::from_slice(&[1, 2, 3, 4][..]);
-        let original_frame =
-            NewConnectionIdFrame::new(original_cid, VarInt::from_u32(1), VarInt::from_u32(0));
+        let original_frame = NewConnectionIdFrame::new(
+            original_cid,
+            VarInt::from_u32(1),
+            VarInt::from_u32(0),
+            ResetToken::random_gen(),
+        );
 
         // Write frame to buffer
         buf.put_frame(&original_frame);
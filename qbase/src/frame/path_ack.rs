@@ -0,0 +1,161 @@
+use super::ack::{AckFrame, ack_frame_with_flag, put_ack_frame_body};
+use crate::varint::{VarInt, WriteVarInt, be_varint};
+
+/// PATH_ACK frame.
+///
+/// ```text
+/// PATH_ACK Frame {
+///   Type (i) = 0x15228c00..0x15228c01,
+///   Path Identifier (i),
+///   Largest Acknowledged (i),
+///   ACK Delay (i),
+///   ACK Range Count (i),
+///   First ACK Range (i),
+///   ACK Range (..) ...,
+///   [ECN Counts (..)],
+/// }
+/// ```
+///
+/// Acknowledges the packets received in the packet number space of the path,
+/// the ACK frame is used for the path 0 only.
+///
+/// See [PATH_ACK Frame](https://datatracker.ietf.org/doc/html/draft-ietf-quic-multipath#name-path_ack-frame)
+/// of [Multipath Extension for QUIC](https://datatracker.ietf.org/doc/html/draft-ietf-quic-multipath)
+/// for more details.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathAckFrame {
+    path_id: VarInt,
+    ack: AckFrame,
+}
+
+const PATH_ACK_FRAME_TYPE: u32 = 0x15228c00;
+
+const ECN_OPT: u32 = 0x1;
+
+impl super::GetFrameType for PathAckFrame {
+    fn frame_type(&self) -> super::FrameType {
+        super::FrameType::PathAck(if self.ack.ecn().is_some() { 1 } else { 0 })
+    }
+}
+
+impl super::EncodeFrame for PathAckFrame {
+    fn max_encoding_size(&self) -> usize {
+        // the type of the ACK frame takes 1 byte
+        4 + 8 + self.ack.max_encoding_size() - 1
+    }
+
+    fn encoding_size(&self) -> usize {
+        4 + self.path_id.encoding_size() + self.ack.encoding_size() - 1
+    }
+}
+
+impl PathAckFrame {
+    /// Create a new [`PathAckFrame`] acknowledging the packets of the path.
+    pub fn new(path_id: VarInt, ack: AckFrame) -> Self {
+        Self { path_id, ack }
+    }
+
+    /// Return the path identifier of the frame.
+    pub fn path_id(&self) -> u64 {
+        self.path_id.into_inner()
+    }
+
+    /// Return the acknowledgment of the packets received on the path.
+    pub fn ack(&self) -> &AckFrame {
+        &self.ack
+    }
+}
+
+/// Parser for parsing a PATH_ACK frame with the given ECN flag,
+/// [nom](https://docs.rs/nom/latest/nom/) parser style.
+pub fn path_ack_frame_with_flag(
+    ecn_flag: u8,
+) -> impl Fn(&[u8]) -> nom::IResult<&[u8], PathAckFrame> {
+    move |input: &[u8]| {
+        let (remain, path_id) = be_varint(input)?;
+        let (remain, ack) = ack_frame_with_flag(ecn_flag)(remain)?;
+        Ok((remain, PathAckFrame { path_id, ack }))
+    }
+}
+
+impl<T: bytes::BufMut> super::io::WriteFrame<PathAckFrame> for T {
+    fn put_frame(&mut self, frame: &PathAckFrame) {
+        let mut frame_type = PATH_ACK_FRAME_TYPE;
+        if frame.ack.ecn().is_some() {
+            frame_type |= ECN_OPT;
+        }
+        self.put_varint(&VarInt::from_u32(frame_type));
+        self.put_varint(&frame.path_id);
+        put_ack_frame_body(self, &frame.ack);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nom::{Parser, combinator::flat_map};
+
+    use super::{PATH_ACK_FRAME_TYPE, PathAckFrame, path_ack_frame_with_flag};
+    use crate::{
+        frame::{AckFrame, EcnCounts, EncodeFrame, FrameType, GetFrameType, io::WriteFrame},
+        varint::{VarInt, be_varint},
+    };
+
+    fn frame(ecn: Option<EcnCounts>) -> PathAckFrame {
+        PathAckFrame::new(
+            VarInt::from_u32(1),
+            AckFrame::new(
+                VarInt::from_u32(0x1234),
+                VarInt::from_u32(0x1234),
+                VarInt::from_u32(0x1234),
+                vec![(VarInt::from_u32(3), VarInt::from_u32(20))],
+                ecn,
+            ),
+        )
+    }
+
+    #[test]
+    fn test_path_ack_frame() {
+        let frame = frame(None);
+        assert_eq!(frame.frame_type(), FrameType::PathAck(0));
+        assert_eq!(frame.path_id(), 1);
+        assert_eq!(frame.encoding_size(), 4 + 1 + 2 * 3 + 1 + 2);
+        assert_eq!(frame.max_encoding_size(), 4 + 8 + 4 * 8 + 2 * 8);
+    }
+
+    #[test]
+    fn test_read_path_ack_frame() {
+        let buf = vec![
+            0x95, 0x22, 0x8c, 0x00, 0x01, 0x52, 0x34, 0x52, 0x34, 0x01, 0x52, 0x34, 3, 20,
+        ];
+        let (input, frame) = flat_map(be_varint, |frame_type| {
+            if frame_type.into_inner() == PATH_ACK_FRAME_TYPE as u64 {
+                path_ack_frame_with_flag(frame_type.into_inner() as u8 & 0x1)
+            } else {
+                panic!("wrong frame type: {frame_type}")
+            }
+        })
+        .parse(buf.as_ref())
+        .unwrap();
+        assert!(input.is_empty());
+        assert_eq!(frame, super::tests::frame(None));
+    }
+
+    #[test]
+    fn test_write_path_ack_frame() {
+        let ecn = EcnCounts::new(
+            VarInt::from_u32(1),
+            VarInt::from_u32(2),
+            VarInt::from_u32(3),
+        );
+        let mut buf = Vec::new();
+        buf.put_frame(&frame(Some(ecn)));
+        assert_eq!(
+            buf,
+            vec![
+                0x95, 0x22, 0x8c, 0x01, 0x01, 0x52, 0x34, 0x52, 0x34, 0x01, 0x52, 0x34, 3,
+                20, // frame
+                1, 2, 3 // ecn
+            ]
+        );
+    }
+}
@@ -0,0 +1,132 @@
+use crate::varint::{VarInt, WriteVarInt, be_varint};
+
+/// PATH_CIDS_BLOCKED frame.
+///
+/// ```text
+/// PATH_CIDS_BLOCKED Frame {
+///   Type (i) = 0x15228c0e,
+///   Path Identifier (i),
+///   Next Sequence Number (i),
+/// }
+/// ```
+///
+/// Sent when the path cannot be used because no unused connection ID is available for it.
+///
+/// See [PATH_CIDS_BLOCKED Frame](https://datatracker.ietf.org/doc/html/draft-ietf-quic-multipath#name-paths_blocked-and-path_cids)
+/// of [Multipath Extension for QUIC](https://datatracker.ietf.org/doc/html/draft-ietf-quic-multipath)
+/// for more details.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PathCidsBlockedFrame {
+    path_id: VarInt,
+    next_sequence: VarInt,
+}
+
+const PATH_CIDS_BLOCKED_FRAME_TYPE: u32 = 0x15228c0e;
+
+impl super::GetFrameType for PathCidsBlockedFrame {
+    fn frame_type(&self) -> super::FrameType {
+        super::FrameType::PathCidsBlocked
+    }
+}
+
+impl super::EncodeFrame for PathCidsBlockedFrame {
+    fn max_encoding_size(&self) -> usize {
+        4 + 8 + 8
+    }
+
+    fn encoding_size(&self) -> usize {
+        4 + self.path_id.encoding_size() + self.next_sequence.encoding_size()
+    }
+}
+
+impl PathCidsBlockedFrame {
+    /// Create a new [`PathCidsBlockedFrame`].
+    pub fn new(path_id: VarInt, next_sequence: VarInt) -> Self {
+        Self {
+            path_id,
+            next_sequence,
+        }
+    }
+
+    /// Return the identifier of the blocked path.
+    pub fn path_id(&self) -> u64 {
+        self.path_id.into_inner()
+    }
+
+    /// Return the sequence number of the next connection ID expected on the path.
+    pub fn next_sequence(&self) -> u64 {
+        self.next_sequence.into_inner()
+    }
+}
+
+/// Parse a PATH_CIDS_BLOCKED frame from the input buffer,
+/// [nom](https://docs.rs/nom/latest/nom/) parser style.
+pub fn be_path_cids_blocked_frame(input: &[u8]) -> nom::IResult<&[u8], PathCidsBlockedFrame> {
+    use nom::{Parser, combinator::map};
+    map((be_varint, be_varint), |(path_id, next_sequence)| {
+        PathCidsBlockedFrame {
+            path_id,
+            next_sequence,
+        }
+    })
+    .parse(input)
+}
+
+impl<T: bytes::BufMut> super::io::WriteFrame<PathCidsBlockedFrame> for T {
+    fn put_frame(&mut self, frame: &PathCidsBlockedFrame) {
+        self.put_varint(&VarInt::from_u32(PATH_CIDS_BLOCKED_FRAME_TYPE));
+        self.put_varint(&frame.path_id);
+        self.put_varint(&frame.next_sequence);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PATH_CIDS_BLOCKED_FRAME_TYPE, PathCidsBlockedFrame};
+    use crate::{
+        frame::{EncodeFrame, FrameType, GetFrameType, io::WriteFrame},
+        varint::VarInt,
+    };
+
+    #[test]
+    fn test_path_cids_blocked_frame() {
+        let frame = PathCidsBlockedFrame::new(VarInt::from_u32(1), VarInt::from_u32(2));
+        assert_eq!(frame.frame_type(), FrameType::PathCidsBlocked);
+        assert_eq!(frame.max_encoding_size(), 4 + 8 + 8);
+        assert_eq!(frame.encoding_size(), 4 + 1 + 1);
+        assert_eq!(frame.next_sequence(), 2);
+    }
+
+    #[test]
+    fn test_read_path_cids_blocked_frame() {
+        use nom::{Parser, combinator::flat_map};
+
+        use super::be_path_cids_blocked_frame;
+        use crate::varint::be_varint;
+        let buf = vec![0x95, 0x22, 0x8c, 0x0e, 0x01, 0x02];
+        let (input, frame) = flat_map(be_varint, |frame_type| {
+            if frame_type.into_inner() == PATH_CIDS_BLOCKED_FRAME_TYPE as u64 {
+                be_path_cids_blocked_frame
+            } else {
+                panic!("wrong frame type: {frame_type}")
+            }
+        })
+        .parse(buf.as_ref())
+        .unwrap();
+        assert!(input.is_empty());
+        assert_eq!(
+            frame,
+            PathCidsBlockedFrame::new(VarInt::from_u32(1), VarInt::from_u32(2))
+        );
+    }
+
+    #[test]
+    fn test_write_path_cids_blocked_frame() {
+        let mut buf = Vec::new();
+        buf.put_frame(&PathCidsBlockedFrame::new(
+            VarInt::from_u32(1),
+            VarInt::from_u32(2),
+        ));
+        assert_eq!(buf, vec![0x95, 0x22, 0x8c, 0x0e, 0x01, 0x02]);
+    }
+}
@@ -0,0 +1,151 @@
+use super::new_connection_id::{
+    NewConnectionIdFrame, be_new_connection_id_frame, put_new_connection_id_frame_body,
+};
+use crate::varint::{VarInt, WriteVarInt, be_varint};
+
+/// PATH_NEW_CONNECTION_ID frame.
+///
+/// ```text
+/// PATH_NEW_CONNECTION_ID Frame {
+///   Type (i) = 0x15228c09,
+///   Path Identifier (i),
+///   Sequence Number (i),
+///   Retire Prior To (i),
+///   Length (8),
+///   Connection ID (8..160),
+///   Stateless Reset Token (128),
+/// }
+/// ```
+///
+/// Issues a connection ID for the path, the sequence numbers are scoped to the path.
+/// The NEW_CONNECTION_ID frame issues the connection IDs of the path 0.
+///
+/// See [PATH_NEW_CONNECTION_ID Frame](https://datatracker.ietf.org/doc/html/draft-ietf-quic-multipath#name-path_new_connection_id-fram)
+/// of [Multipath Extension for QUIC](https://datatracker.ietf.org/doc/html/draft-ietf-quic-multipath)
+/// for more details.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PathNewConnectionIdFrame {
+    path_id: VarInt,
+    new_cid: NewConnectionIdFrame,
+}
+
+const PATH_NEW_CONNECTION_ID_FRAME_TYPE: u32 = 0x15228c09;
+
+impl super::GetFrameType for PathNewConnectionIdFrame {
+    fn frame_type(&self) -> super::FrameType {
+        super::FrameType::PathNewConnectionId
+    }
+}
+
+impl super::EncodeFrame for PathNewConnectionIdFrame {
+    fn max_encoding_size(&self) -> usize {
+        // the type of the NEW_CONNECTION_ID frame takes 1 byte
+        4 + 8 + self.new_cid.max_encoding_size() - 1
+    }
+
+    fn encoding_size(&self) -> usize {
+        4 + self.path_id.encoding_size() + self.new_cid.encoding_size() - 1
+    }
+}
+
+impl PathNewConnectionIdFrame {
+    /// Create a new [`PathNewConnectionIdFrame`] issuing the connection ID for the path.
+    pub fn new(path_id: VarInt, new_cid: NewConnectionIdFrame) -> Self {
+        Self { path_id, new_cid }
+    }
+
+    /// Return the path identifier of the frame.
+    pub fn path_id(&self) -> u64 {
+        self.path_id.into_inner()
+    }
+
+    /// Return the connection ID issued, with its sequence number on the path.
+    pub fn new_connection_id(&self) -> &NewConnectionIdFrame {
+        &self.new_cid
+    }
+}
+
+/// Parse a PATH_NEW_CONNECTION_ID frame from the input buffer,
+/// [nom](https://docs.rs/nom/latest/nom/) parser style.
+pub fn be_path_new_connection_id_frame(
+    input: &[u8],
+) -> nom::IResult<&[u8], PathNewConnectionIdFrame> {
+    let (remain, path_id) = be_varint(input)?;
+    let (remain, new_cid) = be_new_connection_id_frame(remain)?;
+    Ok((remain, PathNewConnectionIdFrame { path_id, new_cid }))
+}
+
+impl<T: bytes::BufMut> super::io::WriteFrame<PathNewConnectionIdFrame> for T {
+    fn put_frame(&mut self, frame: &PathNewConnectionIdFrame) {
+        self.put_varint(&VarInt::from_u32(PATH_NEW_CONNECTION_ID_FRAME_TYPE));
+        self.put_varint(&frame.path_id);
+        put_new_connection_id_frame_body(self, &frame.new_cid);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        PATH_NEW_CONNECTION_ID_FRAME_TYPE, PathNewConnectionIdFrame,
+        be_path_new_connection_id_frame,
+    };
+    use crate::{
+        cid::ConnectionId,
+        frame::{EncodeFrame, FrameType, GetFrameType, NewConnectionIdFrame, io::WriteFrame},
+        token::{RESET_TOKEN_SIZE, ResetToken},
+        varint::{VarInt, be_varint},
+    };
+
+    fn frame() -> PathNewConnectionIdFrame {
+        PathNewConnectionIdFrame::new(
+            VarInt::from_u32(3),
+            NewConnectionIdFrame::new(
+                ConnectionId::from_slice(&[1, 2, 3, 4][..]),
+                VarInt::from_u32(1),
+                VarInt::from_u32(0),
+                ResetToken::new(&[0xff; RESET_TOKEN_SIZE]),
+            ),
+        )
+    }
+
+    #[test]
+    fn test_path_new_connection_id_frame() {
+        let frame = frame();
+        assert_eq!(frame.frame_type(), FrameType::PathNewConnectionId);
+        assert_eq!(frame.path_id(), 3);
+        assert_eq!(frame.new_connection_id().sequence(), 1);
+        assert_eq!(
+            frame.max_encoding_size(),
+            4 + 8 + 8 + 8 + 21 + RESET_TOKEN_SIZE
+        );
+        assert_eq!(frame.encoding_size(), 4 + 1 + 1 + 1 + 1 + 4 + 16);
+    }
+
+    #[test]
+    fn test_read_path_new_connection_id_frame() {
+        use nom::{Parser, combinator::flat_map};
+
+        let mut buf = vec![0x95, 0x22, 0x8c, 0x09, 0x03, 0x01, 0x00, 0x04, 1, 2, 3, 4];
+        buf.extend_from_slice(&[0xff; RESET_TOKEN_SIZE]);
+        let (input, parsed) = flat_map(be_varint, |frame_type| {
+            if frame_type.into_inner() == PATH_NEW_CONNECTION_ID_FRAME_TYPE as u64 {
+                be_path_new_connection_id_frame
+            } else {
+                panic!("wrong frame type: {frame_type}")
+            }
+        })
+        .parse(buf.as_ref())
+        .unwrap();
+        assert!(input.is_empty());
+        assert_eq!(parsed, frame());
+    }
+
+    #[test]
+    fn test_write_path_new_connection_id_frame() {
+        let mut buf = Vec::new();
+        buf.put_frame(&frame());
+        let mut expected = vec![0x95, 0x22, 0x8c, 0x09, 0x03, 0x01, 0x00, 0x04, 1, 2, 3, 4];
+        expected.extend_from_slice(&[0xff; RESET_TOKEN_SIZE]);
+        assert_eq!(buf, expected);
+    }
+}
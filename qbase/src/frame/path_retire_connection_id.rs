@@ -0,0 +1,135 @@
+use super::RetireConnectionIdFrame;
+use crate::varint::{VarInt, WriteVarInt, be_varint};
+
+/// PATH_RETIRE_CONNECTION_ID frame.
+///
+/// ```text
+/// PATH_RETIRE_CONNECTION_ID Frame {
+///   Type (i) = 0x15228c0a,
+///   Path Identifier (i),
+///   Sequence Number (i),
+/// }
+/// ```
+///
+/// Retires a connection ID of the path, the RETIRE_CONNECTION_ID frame
+/// retires the connection IDs of the path 0.
+///
+/// See [PATH_RETIRE_CONNECTION_ID Frame](https://datatracker.ietf.org/doc/html/draft-ietf-quic-multipath#name-path_retire_connection_id-f)
+/// of [Multipath Extension for QUIC](https://datatracker.ietf.org/doc/html/draft-ietf-quic-multipath)
+/// for more details.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PathRetireConnectionIdFrame {
+    path_id: VarInt,
+    sequence: VarInt,
+}
+
+const PATH_RETIRE_CONNECTION_ID_FRAME_TYPE: u32 = 0x15228c0a;
+
+impl super::GetFrameType for PathRetireConnectionIdFrame {
+    fn frame_type(&self) -> super::FrameType {
+        super::FrameType::PathRetireConnectionId
+    }
+}
+
+impl super::EncodeFrame for PathRetireConnectionIdFrame {
+    fn max_encoding_size(&self) -> usize {
+        4 + 8 + 8
+    }
+
+    fn encoding_size(&self) -> usize {
+        4 + self.path_id.encoding_size() + self.sequence.encoding_size()
+    }
+}
+
+impl PathRetireConnectionIdFrame {
+    /// Create a new [`PathRetireConnectionIdFrame`].
+    pub fn new(path_id: VarInt, sequence: VarInt) -> Self {
+        Self { path_id, sequence }
+    }
+
+    /// Return the path identifier of the frame.
+    pub fn path_id(&self) -> u64 {
+        self.path_id.into_inner()
+    }
+
+    /// Return the sequence number of the retired connection ID on the path.
+    pub fn sequence(&self) -> u64 {
+        self.sequence.into_inner()
+    }
+
+    /// Return the equivalent [`RetireConnectionIdFrame`] in the scope of the path.
+    pub fn retire_connection_id(&self) -> RetireConnectionIdFrame {
+        RetireConnectionIdFrame::new(self.sequence)
+    }
+}
+
+/// Parse a PATH_RETIRE_CONNECTION_ID frame from the input buffer,
+/// [nom](https://docs.rs/nom/latest/nom/) parser style.
+pub fn be_path_retire_connection_id_frame(
+    input: &[u8],
+) -> nom::IResult<&[u8], PathRetireConnectionIdFrame> {
+    use nom::{Parser, combinator::map};
+    map((be_varint, be_varint), |(path_id, sequence)| {
+        PathRetireConnectionIdFrame { path_id, sequence }
+    })
+    .parse(input)
+}
+
+impl<T: bytes::BufMut> super::io::WriteFrame<PathRetireConnectionIdFrame> for T {
+    fn put_frame(&mut self, frame: &PathRetireConnectionIdFrame) {
+        self.put_varint(&VarInt::from_u32(PATH_RETIRE_CONNECTION_ID_FRAME_TYPE));
+        self.put_varint(&frame.path_id);
+        self.put_varint(&frame.sequence);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PATH_RETIRE_CONNECTION_ID_FRAME_TYPE, PathRetireConnectionIdFrame};
+    use crate::{
+        frame::{EncodeFrame, FrameType, GetFrameType, io::WriteFrame},
+        varint::VarInt,
+    };
+
+    #[test]
+    fn test_path_retire_connection_id_frame() {
+        let frame = PathRetireConnectionIdFrame::new(VarInt::from_u32(1), VarInt::from_u32(0x1234));
+        assert_eq!(frame.frame_type(), FrameType::PathRetireConnectionId);
+        assert_eq!(frame.max_encoding_size(), 4 + 8 + 8);
+        assert_eq!(frame.encoding_size(), 4 + 1 + 2);
+        assert_eq!(frame.retire_connection_id().sequence(), 0x1234);
+    }
+
+    #[test]
+    fn test_read_path_retire_connection_id_frame() {
+        use nom::{Parser, combinator::flat_map};
+
+        use super::be_path_retire_connection_id_frame;
+        use crate::varint::be_varint;
+        let buf = vec![0x95, 0x22, 0x8c, 0x0a, 0x01, 0x52, 0x34];
+        let (input, frame) = flat_map(be_varint, |frame_type| {
+            if frame_type.into_inner() == PATH_RETIRE_CONNECTION_ID_FRAME_TYPE as u64 {
+                be_path_retire_connection_id_frame
+            } else {
+                panic!("wrong frame type: {frame_type}")
+            }
+        })
+        .parse(buf.as_ref())
+        .unwrap();
+        assert!(input.is_empty());
+        assert_eq!(
+            frame,
+            PathRetireConnectionIdFrame::new(VarInt::from_u32(1), VarInt::from_u32(0x1234))
+        );
+    }
+
+    #[test]
+    fn test_write_path_retire_connection_id_frame() {
+        let mut buf = Vec::new();
+        buf.put_frame(&PathRetireConnectionIdFrame::new(
+            VarInt::from_u32(1),
+            VarInt::from_u32(0x1234),
+        ));
+        assert_eq!(buf, vec![0x95, 0x22, 0x8c, 0x0a, 0x01, 0x52, 0x34]);
+    }
+}
@@ -0,0 +1,163 @@
+use crate::varint::{VarInt, WriteVarInt, be_varint};
+
+/// PATH_STATUS_BACKUP and PATH_STATUS_AVAILABLE frames.
+///
+/// ```text
+/// PATH_STATUS_BACKUP Frame {
+///   Type (i) = 0x15228c07,
+///   Path Identifier (i),
+///   Path Status Sequence Number (i),
+/// }
+///
+/// PATH_STATUS_AVAILABLE Frame {
+///   Type (i) = 0x15228c08,
+///   Path Identifier (i),
+///   Path Status Sequence Number (i),
+/// }
+/// ```
+///
+/// Tells the peer whether the path should only be used when no other path is available,
+/// the status with a larger sequence number overrides the previous ones.
+///
+/// See [PATH_STATUS_BACKUP and PATH_STATUS_AVAILABLE Frames](https://datatracker.ietf.org/doc/html/draft-ietf-quic-multipath#name-path_status_backup-and-path)
+/// of [Multipath Extension for QUIC](https://datatracker.ietf.org/doc/html/draft-ietf-quic-multipath)
+/// for more details.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PathStatusFrame {
+    path_id: VarInt,
+    sequence: VarInt,
+    is_backup: bool,
+}
+
+const PATH_STATUS_BACKUP_FRAME_TYPE: u32 = 0x15228c07;
+const PATH_STATUS_AVAILABLE_FRAME_TYPE: u32 = 0x15228c08;
+
+impl super::GetFrameType for PathStatusFrame {
+    fn frame_type(&self) -> super::FrameType {
+        if self.is_backup {
+            super::FrameType::PathStatusBackup
+        } else {
+            super::FrameType::PathStatusAvailable
+        }
+    }
+}
+
+impl super::EncodeFrame for PathStatusFrame {
+    fn max_encoding_size(&self) -> usize {
+        4 + 8 + 8
+    }
+
+    fn encoding_size(&self) -> usize {
+        4 + self.path_id.encoding_size() + self.sequence.encoding_size()
+    }
+}
+
+impl PathStatusFrame {
+    /// Create a new PATH_STATUS_BACKUP frame if `is_backup`,
+    /// otherwise a PATH_STATUS_AVAILABLE frame.
+    pub fn new(path_id: VarInt, sequence: VarInt, is_backup: bool) -> Self {
+        Self {
+            path_id,
+            sequence,
+            is_backup,
+        }
+    }
+
+    /// Return the identifier of the path whose status changed.
+    pub fn path_id(&self) -> u64 {
+        self.path_id.into_inner()
+    }
+
+    /// Return the sequence number of the path status.
+    pub fn sequence(&self) -> u64 {
+        self.sequence.into_inner()
+    }
+
+    /// Return whether the path should be used as a backup path.
+    pub fn is_backup(&self) -> bool {
+        self.is_backup
+    }
+}
+
+/// Returns a parser for PATH_STATUS_BACKUP frame if `is_backup`,
+/// otherwise PATH_STATUS_AVAILABLE frame, [nom](https://docs.rs/nom/latest/nom/) parser style.
+pub fn path_status_frame_with_backup(
+    is_backup: bool,
+) -> impl Fn(&[u8]) -> nom::IResult<&[u8], PathStatusFrame> {
+    use nom::{Parser, combinator::map};
+    move |input: &[u8]| {
+        map((be_varint, be_varint), |(path_id, sequence)| {
+            PathStatusFrame::new(path_id, sequence, is_backup)
+        })
+        .parse(input)
+    }
+}
+
+impl<T: bytes::BufMut> super::io::WriteFrame<PathStatusFrame> for T {
+    fn put_frame(&mut self, frame: &PathStatusFrame) {
+        self.put_varint(&VarInt::from_u32(if frame.is_backup {
+            PATH_STATUS_BACKUP_FRAME_TYPE
+        } else {
+            PATH_STATUS_AVAILABLE_FRAME_TYPE
+        }));
+        self.put_varint(&frame.path_id);
+        self.put_varint(&frame.sequence);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nom::{Parser, combinator::flat_map};
+
+    use super::{
+        PATH_STATUS_AVAILABLE_FRAME_TYPE, PATH_STATUS_BACKUP_FRAME_TYPE, PathStatusFrame,
+        path_status_frame_with_backup,
+    };
+    use crate::{
+        frame::{EncodeFrame, FrameType, GetFrameType, io::WriteFrame},
+        varint::{VarInt, be_varint},
+    };
+
+    #[test]
+    fn test_path_status_frame() {
+        let frame = PathStatusFrame::new(VarInt::from_u32(1), VarInt::from_u32(0x1234), true);
+        assert_eq!(frame.frame_type(), FrameType::PathStatusBackup);
+        assert_eq!(frame.max_encoding_size(), 4 + 8 + 8);
+        assert_eq!(frame.encoding_size(), 4 + 1 + 2);
+
+        let frame = PathStatusFrame::new(VarInt::from_u32(1), VarInt::from_u32(2), false);
+        assert_eq!(frame.frame_type(), FrameType::PathStatusAvailable);
+        assert_eq!(frame.encoding_size(), 4 + 1 + 1);
+        assert!(!frame.is_backup());
+    }
+
+    #[test]
+    fn test_read_path_status_frame() {
+        let buf = vec![0x95, 0x22, 0x8c, 0x08, 0x01, 0x02];
+        let (input, frame) = flat_map(be_varint, |frame_type| {
+            match frame_type.into_inner() as u32 {
+                PATH_STATUS_BACKUP_FRAME_TYPE => path_status_frame_with_backup(true),
+                PATH_STATUS_AVAILABLE_FRAME_TYPE => path_status_frame_with_backup(false),
+                _ => panic!("wrong frame type: {frame_type}"),
+            }
+        })
+        .parse(buf.as_ref())
+        .unwrap();
+        assert!(input.is_empty());
+        assert_eq!(
+            frame,
+            PathStatusFrame::new(VarInt::from_u32(1), VarInt::from_u32(2), false)
+        );
+    }
+
+    #[test]
+    fn test_write_path_status_frame() {
+        let mut buf = Vec::new();
+        buf.put_frame(&PathStatusFrame::new(
+            VarInt::from_u32(1),
+            VarInt::from_u32(2),
+            true,
+        ));
+        assert_eq!(buf, vec![0x95, 0x22, 0x8c, 0x07, 0x01, 0x02]);
+    }
+}
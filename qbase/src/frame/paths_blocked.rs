@@ -0,0 +1,110 @@
+use crate::varint::{VarInt, WriteVarInt, be_varint};
+
+/// PATHS_BLOCKED frame.
+///
+/// ```text
+/// PATHS_BLOCKED Frame {
+///   Type (i) = 0x15228c0d,
+///   Maximum Path Identifier (i),
+/// }
+/// ```
+///
+/// Sent when a new path cannot be opened because of the maximum path identifier
+/// the peer allowed.
+///
+/// See [PATHS_BLOCKED Frame](https://datatracker.ietf.org/doc/html/draft-ietf-quic-multipath#name-paths_blocked-and-path_cids)
+/// of [Multipath Extension for QUIC](https://datatracker.ietf.org/doc/html/draft-ietf-quic-multipath)
+/// for more details.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PathsBlockedFrame {
+    max_path_id: VarInt,
+}
+
+const PATHS_BLOCKED_FRAME_TYPE: u32 = 0x15228c0d;
+
+impl super::GetFrameType for PathsBlockedFrame {
+    fn frame_type(&self) -> super::FrameType {
+        super::FrameType::PathsBlocked
+    }
+}
+
+impl super::EncodeFrame for PathsBlockedFrame {
+    fn max_encoding_size(&self) -> usize {
+        4 + 8
+    }
+
+    fn encoding_size(&self) -> usize {
+        4 + self.max_path_id.encoding_size()
+    }
+}
+
+impl PathsBlockedFrame {
+    /// Create a new [`PathsBlockedFrame`].
+    pub fn new(max_path_id: VarInt) -> Self {
+        Self { max_path_id }
+    }
+
+    /// Return the maximum path identifier the sender is blocked at.
+    pub fn max_path_id(&self) -> u64 {
+        self.max_path_id.into_inner()
+    }
+}
+
+/// Parse a PATHS_BLOCKED frame from the input buffer,
+/// [nom](https://docs.rs/nom/latest/nom/) parser style.
+pub fn be_paths_blocked_frame(input: &[u8]) -> nom::IResult<&[u8], PathsBlockedFrame> {
+    use nom::{Parser, combinator::map};
+    map(be_varint, PathsBlockedFrame::new).parse(input)
+}
+
+impl<T: bytes::BufMut> super::io::WriteFrame<PathsBlockedFrame> for T {
+    fn put_frame(&mut self, frame: &PathsBlockedFrame) {
+        self.put_varint(&VarInt::from_u32(PATHS_BLOCKED_FRAME_TYPE));
+        self.put_varint(&frame.max_path_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PATHS_BLOCKED_FRAME_TYPE, PathsBlockedFrame};
+    use crate::{
+        frame::{EncodeFrame, FrameType, GetFrameType, io::WriteFrame},
+        varint::VarInt,
+    };
+
+    #[test]
+    fn test_paths_blocked_frame() {
+        let frame = PathsBlockedFrame::new(VarInt::from_u32(4));
+        assert_eq!(frame.frame_type(), FrameType::PathsBlocked);
+        assert_eq!(frame.max_encoding_size(), 4 + 8);
+        assert_eq!(frame.encoding_size(), 4 + 1);
+        assert_eq!(frame.max_path_id(), 4);
+    }
+
+    #[test]
+    fn test_read_paths_blocked_frame() {
+        use nom::{Parser, combinator::flat_map};
+
+        use super::be_paths_blocked_frame;
+        use crate::varint::be_varint;
+        let buf = vec![0x95, 0x22, 0x8c, 0x0d, 0x04];
+        let (input, frame) = flat_map(be_varint, |frame_type| {
+            if frame_type.into_inner() == PATHS_BLOCKED_FRAME_TYPE as u64 {
+                be_paths_blocked_frame
+            } else {
+                panic!("wrong frame type: {frame_type}")
+            }
+        })
+        .parse(buf.as_ref())
+        .unwrap();
+        assert!(input.is_empty());
+        assert_eq!(frame, PathsBlockedFrame::new(VarInt::from_u32(4)));
+    }
+
+    #[test]
+    fn test_write_paths_blocked_frame() {
+        let mut buf = Vec::new();
+        buf.put_frame(&PathsBlockedFrame::new(VarInt::from_u32(4)));
+        assert_eq!(buf, vec![0x95, 0x22, 0x8c, 0x0d, 0x04]);
+    }
+}
@@ -247,17 +247,38 @@ impl Link {
     }
 }
 
+/// The ECN codepoint in the IP header, see [RFC 3168](https://www.rfc-editor.org/rfc/rfc3168#section-5).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ecn {
+    NotEct = 0b00,
+    Ect1 = 0b01,
+    Ect0 = 0b10,
+    Ce = 0b11,
+}
+
+impl Ecn {
+    /// Get the ECN codepoint from the lower two bits of the ToS or Traffic Class byte.
+    pub fn from_bits(bits: u8) -> Self {
+        match bits & 0b11 {
+            0b00 => Ecn::NotEct,
+            0b01 => Ecn::Ect1,
+            0b10 => Ecn::Ect0,
+            _ => Ecn::Ce,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct PacketHeader {
     pathway: Pathway,
     link: Link,
     ttl: u8,
-    ecn: Option<u8>,
+    ecn: Option<Ecn>,
     seg_size: u16,
 }
 
 impl PacketHeader {
-    pub fn new(pathway: Pathway, link: Link, ttl: u8, ecn: Option<u8>, seg_size: u16) -> Self {
+    pub fn new(pathway: Pathway, link: Link, ttl: u8, ecn: Option<Ecn>, seg_size: u16) -> Self {
         Self {
             pathway,
             link,
@@ -288,7 +309,7 @@ impl PacketHeader {
         self.ttl
     }
 
-    pub fn ecn(&self) -> Option<u8> {
+    pub fn ecn(&self) -> Option<Ecn> {
         self.ecn
     }
 
@@ -13,7 +13,7 @@ use crate::{
         ContainSpec, FrameFeture, FrameType, Spec,
         io::{WriteDataFrame, WriteFrame},
     },
-    net::tx::Signals,
+    net::{route::Ecn, tx::Signals},
     util::{DescribeData, WriteData},
     varint::{EncodeBytes, VarInt, WriteVarInt},
 };
@@ -64,6 +64,9 @@ pub mod encrypt;
 /// Encapsulate the crypto keys's logic for long headers and 1-RTT headers.
 pub mod keys;
 
+/// The integrity protection of the Retry packet.
+pub mod retry;
+
 /// The sum type of all QUIC packet headers.
 #[derive(Debug, Clone)]
 #[enum_dispatch(GetDcid, GetType)]
@@ -94,6 +97,20 @@ pub struct DataPacket {
     pub bytes: BytesMut,
     // payload_offset
     pub offset: usize,
+    // the ECN codepoint of the datagram carrying this packet, if the interface reports it
+    pub ecn: Option<Ecn>,
+}
+
+/// The Retry packet, with its raw bytes.
+///
+/// The raw bytes, rather than the re-encoded header, are needed to verify the
+/// Retry Integrity Tag, because the unused bits in the first byte are arbitrary.
+#[derive(Debug, Clone, Deref, DerefMut)]
+pub struct RetryPacket {
+    #[deref]
+    #[deref_mut]
+    pub header: RetryHeader,
+    pub bytes: BytesMut,
 }
 
 impl GetType for DataPacket {
@@ -128,7 +145,7 @@ impl PacketContains {
 #[derive(Debug, Clone)]
 pub enum Packet {
     VN(VersionNegotiationHeader),
-    Retry(RetryHeader),
+    Retry(RetryPacket),
     // Data(header, bytes, payload_offset)
     Data(DataPacket),
 }
@@ -18,7 +18,9 @@ pub use short::OneRttHeader;
 
 use super::r#type::{
     Type,
-    long::{Type as LongType, Version, v1},
+    long::{
+        GetVersion, SUPPORTED_VERSIONS, Type as LongType, VERSION_1, VERSION_2, Version, v1, v2,
+    },
     short::OneRtt,
 };
 
@@ -100,8 +102,7 @@ pub mod io {
             Type::Long(long_ty) => {
                 let (remain, dcid) = be_connection_id(input)?;
                 let (remain, scid) = be_connection_id(remain)?;
-                let builder = LongHeaderBuilder { dcid, scid };
-                builder.parse(long_ty, remain)
+                LongHeaderBuilder::with_cid(dcid, scid).parse(long_ty, remain)
             }
             Type::Short(OneRtt(spin)) => {
                 let (remain, one_rtt) = be_one_rtt_header(spin, dcid_len, input)?;
@@ -356,4 +357,47 @@ mod tests {
         buf.put_header(&one_rtt_header);
         assert_eq!(buf, [0x40]);
     }
+
+    #[test]
+    fn test_v2_header() {
+        use crate::packet::r#type::{
+            io::be_packet_type,
+            long::{GetVersion, VERSION_2, Ver2},
+        };
+
+        let mut buf = vec![];
+        let initial_header = Header::Initial(
+            LongHeaderBuilder::with_cid(ConnectionId::default(), ConnectionId::default())
+                .version(VERSION_2)
+                .wrap(Initial::with_token(vec![0x01, 0x02, 0x03])),
+        );
+        buf.put_header(&initial_header);
+        assert_eq!(
+            buf,
+            [
+                0xd0, 0x6b, 0x33, 0x43, 0xcf, 0x00, 0x00, 0x03, 0x01, 0x02, 0x03
+            ]
+        );
+
+        let (remain, ty) = be_packet_type(&buf).unwrap();
+        assert_eq!(ty, Type::Long(long::Type::V2(Ver2::INITIAL)));
+        let (remain, header) = be_header(ty, 0, remain).unwrap();
+        assert_eq!(remain.len(), 0);
+        match header {
+            Header::Initial(initial) => {
+                assert_eq!(initial.get_version(), VERSION_2);
+                assert_eq!(initial.token().deref(), [0x01, 0x02, 0x03]);
+            }
+            _ => panic!("unexpected header type"),
+        }
+
+        let mut buf = vec![];
+        let handshake_header = Header::Handshake(
+            LongHeaderBuilder::with_cid(ConnectionId::default(), ConnectionId::default())
+                .version(VERSION_2)
+                .wrap(Handshake),
+        );
+        buf.put_header(&handshake_header);
+        assert_eq!(buf, [0xf0, 0x6b, 0x33, 0x43, 0xcf, 0x00, 0x00]);
+    }
 }
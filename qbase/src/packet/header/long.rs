@@ -25,6 +25,7 @@ use crate::{cid::ConnectionId, varint::VarInt};
 /// of [QUIC](https://www.rfc-editor.org/rfc/rfc9000.html) for more details.
 #[derive(Debug, Default, Clone, Deref, DerefMut)]
 pub struct LongHeader<T> {
+    version: u32,
     dcid: ConnectionId,
     scid: ConnectionId,
     #[deref]
@@ -44,6 +45,12 @@ impl<T> super::GetScid for LongHeader<T> {
     }
 }
 
+impl<T> GetVersion for LongHeader<T> {
+    fn get_version(&self) -> u32 {
+        self.version
+    }
+}
+
 // The following is the header definition, which may exist in all future versions
 // of QUIC, so it is placed in this file without distinguishing versions.
 
@@ -200,12 +207,21 @@ impl<S: EncodeHeader> EncodeHeader for LongHeader<S> {
     }
 }
 
+impl GetType for VersionNegotiationHeader {
+    fn get_type(&self) -> Type {
+        Type::Long(LongType::VersionNegotiation)
+    }
+}
+
 macro_rules! bind_type {
-    ($($type:ty => $value:expr),*) => {
+    ($($type:ty => $variant:ident),*) => {
         $(
             impl GetType for $type {
                 fn get_type(&self) -> Type {
-                    $value
+                    match self.version {
+                        VERSION_2 => Type::Long(LongType::V2(Version(v2::Type::$variant))),
+                        _ => Type::Long(LongType::V1(Version(v1::Type::$variant))),
+                    }
                 }
             }
         )*
@@ -213,11 +229,10 @@ macro_rules! bind_type {
 }
 
 bind_type!(
-    VersionNegotiationHeader => Type::Long(LongType::VersionNegotiation),
-    RetryHeader => Type::Long(LongType::V1(Version::<1, _>(v1::Type::Retry))),
-    InitialHeader => Type::Long(LongType::V1(Version::<1, _>(v1::Type::Initial))),
-    ZeroRttHeader => Type::Long(LongType::V1(Version::<1, _>(v1::Type::ZeroRtt))),
-    HandshakeHeader => Type::Long(LongType::V1(Version::<1, _>(v1::Type::Handshake)))
+    RetryHeader => Retry,
+    InitialHeader => Initial,
+    ZeroRttHeader => ZeroRtt,
+    HandshakeHeader => Handshake
 );
 
 /// The sum type of long packets that carry data,
@@ -232,30 +247,47 @@ pub enum DataHeader {
 
 /// The io module provides functions for parsing and writing long headers.
 pub mod io {
-    use std::ops::Deref;
-
     use bytes::BufMut;
     use nom::{
         Err, Parser,
         bytes::streaming::take,
-        combinator::{eof, map},
+        combinator::{eof, map, verify},
         multi::{length_data, many_till},
-        number::streaming::be_u32,
+        number::streaming::{be_u8, be_u32},
     };
 
     use super::*;
     use crate::{
-        cid::WriteConnectionId,
+        cid::{WriteConnectionId, be_connection_id},
         packet::{
             header::io::WriteHeader,
             r#type::{
                 io::WritePacketType,
-                long::{Type as LongType, v1::Type as LongV1Type},
+                long::{Type as LongType, Ver1, Ver2},
             },
         },
         varint::{WriteVarInt, be_varint},
     };
 
+    /// Parse the version-independent fields of a long header packet of any version, which are
+    /// the version, the destination and source connection IDs,
+    /// [nom](https://docs.rs/nom/latest/nom/) parser style.
+    ///
+    /// It is used to respond the packets of unsupported versions with version negotiation packets.
+    /// Connection IDs longer than 20 bytes are not supported, though they are allowed by other versions.
+    ///
+    /// See [Long Header](https://www.rfc-editor.org/rfc/rfc8999.html#name-long-header)
+    /// of [RFC8999](https://www.rfc-editor.org/rfc/rfc8999.html) for more details.
+    pub fn be_invariant_long_header(
+        input: &[u8],
+    ) -> nom::IResult<&[u8], (u32, ConnectionId, ConnectionId)> {
+        let (remain, _) = verify(be_u8, |first_byte| first_byte & 0x80 != 0).parse(input)?;
+        let (remain, version) = be_u32(remain)?;
+        let (remain, dcid) = be_connection_id(remain)?;
+        let (remain, scid) = be_connection_id(remain)?;
+        Ok((remain, (version, dcid, scid)))
+    }
+
     /// Parse the version negotiation packet,
     /// [nom](https://docs.rs/nom/latest/nom/) parser style.
     pub fn be_version_negotiation(input: &[u8]) -> nom::IResult<&[u8], VersionNegotiation> {
@@ -305,20 +337,32 @@ pub mod io {
     /// let handshake_header = LongHeaderBuilder::with_cid(dcid, scid).handshake();
     /// ```
     pub struct LongHeaderBuilder {
+        pub(crate) version: u32,
         pub(crate) dcid: ConnectionId,
         pub(crate) scid: ConnectionId,
     }
 
     impl LongHeaderBuilder {
         /// Create a new long header builder with the given destination
-        /// and source connection IDs.
+        /// and source connection IDs, the version is QUIC version 1 by default.
         pub fn with_cid(dcid: ConnectionId, scid: ConnectionId) -> Self {
-            Self { dcid, scid }
+            Self {
+                version: VERSION_1,
+                dcid,
+                scid,
+            }
+        }
+
+        /// Specify the QUIC version of the long header, which must be implemented,
+        /// see [`SUPPORTED_VERSIONS`](crate::packet::r#type::long::SUPPORTED_VERSIONS).
+        pub fn version(self, version: u32) -> Self {
+            debug_assert!(SUPPORTED_VERSIONS.contains(&version));
+            Self { version, ..self }
         }
 
-        /// Build into a version negotiation header.
+        /// Build into a version negotiation header, whose version is always 0.
         pub fn vn(self, versions: Vec<u32>) -> LongHeader<VersionNegotiation> {
-            self.wrap(VersionNegotiation::new(versions))
+            Self { version: 0, ..self }.wrap(VersionNegotiation::new(versions))
         }
 
         /// Build into a retry header.
@@ -345,6 +389,7 @@ pub mod io {
         /// Return the specific long header.
         pub fn wrap<T>(self, specific: T) -> LongHeader<T> {
             LongHeader {
+                version: self.version,
                 dcid: self.dcid,
                 scid: self.scid,
                 specific,
@@ -356,29 +401,31 @@ pub mod io {
         ///
         /// The input buffer would be the remaining data of the buffer.
         pub fn parse(self, ty: LongType, input: &[u8]) -> nom::IResult<&[u8], Header> {
+            let builder = Self {
+                version: ty.get_version(),
+                ..self
+            };
             match ty {
                 LongType::VersionNegotiation => {
                     let (remain, versions) = be_version_negotiation(input)?;
-                    Ok((remain, Header::VN(self.wrap(versions))))
+                    Ok((remain, Header::VN(builder.wrap(versions))))
+                }
+                LongType::V1(Ver1::RETRY) | LongType::V2(Ver2::RETRY) => {
+                    let (remain, retry) = be_retry(input)?;
+                    Ok((remain, Header::Retry(builder.wrap(retry))))
+                }
+                LongType::V1(Ver1::INITIAL) | LongType::V2(Ver2::INITIAL) => {
+                    let (remain, initial) = be_initial(input)?;
+                    Ok((remain, Header::Initial(builder.wrap(initial))))
+                }
+                LongType::V1(Ver1::ZERO_RTT) | LongType::V2(Ver2::ZERO_RTT) => {
+                    let (remain, zero_rtt) = be_zero_rtt(input)?;
+                    Ok((remain, Header::ZeroRtt(builder.wrap(zero_rtt))))
+                }
+                LongType::V1(Ver1::HANDSHAKE) | LongType::V2(Ver2::HANDSHAKE) => {
+                    let (remain, handshake) = be_handshake(input)?;
+                    Ok((remain, Header::Handshake(builder.wrap(handshake))))
                 }
-                LongType::V1(ty) => match ty.deref() {
-                    LongV1Type::Retry => {
-                        let (remain, retry) = be_retry(input)?;
-                        Ok((remain, Header::Retry(self.wrap(retry))))
-                    }
-                    LongV1Type::Initial => {
-                        let (remain, initial) = be_initial(input)?;
-                        Ok((remain, Header::Initial(self.wrap(initial))))
-                    }
-                    LongV1Type::ZeroRtt => {
-                        let (remain, zero_rtt) = be_zero_rtt(input)?;
-                        Ok((remain, Header::ZeroRtt(self.wrap(zero_rtt))))
-                    }
-                    LongV1Type::Handshake => {
-                        let (remain, handshake) = be_handshake(input)?;
-                        Ok((remain, Header::Handshake(self.wrap(handshake))))
-                    }
-                },
             }
         }
     }
@@ -448,6 +495,28 @@ mod tests {
         assert_eq!(remain.len(), 0);
     }
 
+    #[test]
+    fn test_be_invariant_long_header() {
+        use super::io::be_invariant_long_header;
+        use crate::cid::ConnectionId;
+
+        let buf = [
+            0xc0, 0x1a, 0x2a, 0x3a, 0x4a, 0x02, 0x01, 0x02, 0x01, 0x03, 0xff,
+        ];
+        let (remain, (version, dcid, scid)) = be_invariant_long_header(&buf).unwrap();
+        assert_eq!(remain, &[0xff]);
+        assert_eq!(version, 0x1a2a3a4a);
+        assert_eq!(dcid, ConnectionId::from_slice(&[0x01, 0x02]));
+        assert_eq!(scid, ConnectionId::from_slice(&[0x03]));
+
+        // short header
+        assert!(be_invariant_long_header(&[0x40, 0x00, 0x00, 0x00, 0x01]).is_err());
+        // connection id longer than 20 bytes
+        let mut buf = vec![0xc0, 0x1a, 0x2a, 0x3a, 0x4a, 21];
+        buf.extend_from_slice(&[0; 22]);
+        assert!(be_invariant_long_header(&buf).is_err());
+    }
+
     #[test]
     fn test_be_retry() {
         use super::io::be_retry;
@@ -54,8 +54,8 @@ pub fn be_packet(datagram: &mut BytesMut, dcid_len: usize) -> Result<Packet, Err
             Ok(Packet::VN(header))
         }
         Header::Retry(header) => {
-            datagram.clear();
-            Ok(Packet::Retry(header))
+            let bytes = datagram.split();
+            Ok(Packet::Retry(RetryPacket { header, bytes }))
         }
         Header::Initial(header) => {
             let (bytes, offset) = be_payload(pkty, datagram, remain.len())?;
@@ -63,6 +63,7 @@ pub fn be_packet(datagram: &mut BytesMut, dcid_len: usize) -> Result<Packet, Err
                 header: DataHeader::Long(long::DataHeader::Initial(header)),
                 bytes,
                 offset,
+                ecn: None,
             }))
         }
         Header::ZeroRtt(header) => {
@@ -71,6 +72,7 @@ pub fn be_packet(datagram: &mut BytesMut, dcid_len: usize) -> Result<Packet, Err
                 header: DataHeader::Long(long::DataHeader::ZeroRtt(header)),
                 bytes,
                 offset,
+                ecn: None,
             }))
         }
         Header::Handshake(header) => {
@@ -79,6 +81,7 @@ pub fn be_packet(datagram: &mut BytesMut, dcid_len: usize) -> Result<Packet, Err
                 header: DataHeader::Long(long::DataHeader::Handshake(header)),
                 bytes,
                 offset,
+                ecn: None,
             }))
         }
         Header::OneRtt(header) => {
@@ -94,6 +97,7 @@ pub fn be_packet(datagram: &mut BytesMut, dcid_len: usize) -> Result<Packet, Err
                 header: DataHeader::Short(header),
                 bytes,
                 offset,
+                ecn: None,
             }))
         }
     }
@@ -131,7 +135,7 @@ pub struct PacketLayout {
 }
 
 impl PacketLayout {
-    pub fn writer(mut self, buffer: &mut [u8]) -> PacketWriter {
+    pub fn writer(mut self, buffer: &mut [u8]) -> PacketWriter<'_> {
         self.end = buffer.len() - self.keys.pk().tag_len();
         assert!(self.end >= self.cursor);
         PacketWriter {
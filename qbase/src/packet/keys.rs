@@ -1,12 +1,16 @@
 use std::{
+    collections::HashMap,
     future::Future,
     ops::DerefMut,
     pin::Pin,
-    sync::{Arc, Mutex, MutexGuard},
+    sync::{
+        Arc, Mutex, MutexGuard,
+        atomic::{AtomicU64, Ordering},
+    },
     task::{Context, Poll, Waker},
 };
 
-use rustls::quic::{HeaderProtectionKey, Keys, PacketKey, Secrets};
+use rustls::quic::{HeaderProtectionKey, Keys, PacketKey, Secrets, Tag};
 
 use super::KeyPhaseBit;
 
@@ -34,7 +38,7 @@ pub struct ArcKeys(Arc<Mutex<KeysState>>);
 
 impl ArcKeys {
     #[inline]
-    fn lock_guard(&self) -> MutexGuard<KeysState> {
+    fn lock_guard(&self) -> MutexGuard<'_, KeysState> {
         self.0.lock().unwrap()
     }
 
@@ -79,7 +83,7 @@ impl ArcKeys {
     ///     // use pk to decrypt packet body...
     /// }
     /// ```
-    pub fn get_remote_keys(&self) -> GetRemoteKeys {
+    pub fn get_remote_keys(&self) -> GetRemoteKeys<'_> {
         GetRemoteKeys(self)
     }
 
@@ -134,12 +138,34 @@ impl ArcKeys {
         }
     }
 
+    /// Replace the keys of the [`ArcKeys`], whether they are ready or not.
+    ///
+    /// This is used by the client to re-derive the Initial keys after receiving a Retry packet,
+    /// which changes the destination connection ID the Initial keys are derived from.
+    /// Do nothing if the keys have been retired.
+    pub fn replace_keys(&self, keys: Keys) {
+        let mut state = self.lock_guard();
+        match &mut *state {
+            KeysState::Pending(waker) => {
+                if let Some(waker) = waker.take() {
+                    waker.wake();
+                }
+                *state = KeysState::Ready(Arc::new(keys));
+            }
+            KeysState::Ready(_) => *state = KeysState::Ready(Arc::new(keys)),
+            KeysState::Invalid => {}
+        }
+    }
+
     /// Retire the keys, which means that the keys are no longer available.
     ///
     /// This is used when the connection enters the closing state or draining state.
     /// Especially in the closing state, the return keys are used to generate the final packet
     /// containing the ConnectionClose frame, and decrypt the data packets received from the
     /// peer for a while.
+    ///
+    /// It is also used to discard the 0-RTT keys, and the keys that have been retired will
+    /// not be returned again.
     pub fn invalid(&self) -> Option<Arc<Keys>> {
         let mut state = self.lock_guard();
         match std::mem::replace(state.deref_mut(), KeysState::Invalid) {
@@ -150,7 +176,7 @@ impl ArcKeys {
                 None
             }
             KeysState::Ready(keys) => Some(keys),
-            KeysState::Invalid => unreachable!(),
+            KeysState::Invalid => None,
         }
     }
 }
@@ -183,41 +209,324 @@ impl Future for GetRemoteKeys<'_> {
     }
 }
 
+/// The policy to initiate 1-RTT key updates proactively.
+///
+/// A key update is initiated once the local packet key has protected the specified number of
+/// packets or bytes, whichever comes first. Besides, the keys are always updated before the
+/// number of packets protected reaches the confidentiality limit of the AEAD algorithm, see
+/// [Section 6.6](https://www.rfc-editor.org/rfc/rfc9001#section-6.6) of RFC 9001.
+///
+/// Default: only update the keys before reaching the confidentiality limit.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct KeyUpdatePolicy {
+    packets: Option<u64>,
+    bytes: Option<u64>,
+}
+
+impl KeyUpdatePolicy {
+    /// Update the keys after protecting `packets` packets with them.
+    pub fn after_packets(self, packets: u64) -> Self {
+        Self {
+            packets: Some(packets),
+            ..self
+        }
+    }
+
+    /// Update the keys after protecting `bytes` bytes of packet payload with them.
+    pub fn after_bytes(self, bytes: u64) -> Self {
+        Self {
+            bytes: Some(bytes),
+            ..self
+        }
+    }
+}
+
+/// The endpoint that initiated a 1-RTT key update.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyUpdateTrigger {
+    Local,
+    Remote,
+}
+
+/// The local 1-RTT packet key, recording how much it has been used,
+/// to decide when to update it.
+struct LocalPacketKey {
+    key: Box<dyn PacketKey>,
+    packets: AtomicU64,
+    bytes: AtomicU64,
+    // The smallest packet number protected with the key on each path,
+    // each path has its own packet number space with multipath.
+    first_pns: Mutex<HashMap<u32, u64>>,
+}
+
+impl LocalPacketKey {
+    fn new(key: Box<dyn PacketKey>) -> Arc<Self> {
+        Arc::new(Self {
+            key,
+            packets: AtomicU64::new(0),
+            bytes: AtomicU64::new(0),
+            first_pns: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn on_encrypt(&self, path_id: u32, packet_number: u64, payload: &[u8]) {
+        self.packets.fetch_add(1, Ordering::Relaxed);
+        self.bytes
+            .fetch_add(payload.len() as u64, Ordering::Relaxed);
+        self.first_pns
+            .lock()
+            .unwrap()
+            .entry(path_id)
+            .and_modify(|first| *first = (*first).min(packet_number))
+            .or_insert(packet_number);
+    }
+
+    fn is_acked(&self, path_id: u32, largest: u64) -> bool {
+        self.first_pns
+            .lock()
+            .unwrap()
+            .get(&path_id)
+            .is_some_and(|first| largest >= *first)
+    }
+
+    fn is_exhausted(&self) -> bool {
+        self.packets.load(Ordering::Relaxed) >= self.key.confidentiality_limit()
+    }
+
+    fn is_used_up(&self, policy: &KeyUpdatePolicy) -> bool {
+        let limit = self.key.confidentiality_limit();
+        let packets = policy.packets.map_or(limit, |packets| packets.min(limit));
+        self.packets.load(Ordering::Relaxed) >= packets
+            || policy
+                .bytes
+                .is_some_and(|bytes| self.bytes.load(Ordering::Relaxed) >= bytes)
+    }
+}
+
+impl PacketKey for LocalPacketKey {
+    fn encrypt_in_place(
+        &self,
+        packet_number: u64,
+        header: &[u8],
+        payload: &mut [u8],
+    ) -> Result<Tag, rustls::Error> {
+        self.on_encrypt(0, packet_number, payload);
+        self.key.encrypt_in_place(packet_number, header, payload)
+    }
+
+    fn encrypt_in_place_for_path(
+        &self,
+        path_id: u32,
+        packet_number: u64,
+        header: &[u8],
+        payload: &mut [u8],
+    ) -> Result<Tag, rustls::Error> {
+        self.on_encrypt(path_id, packet_number, payload);
+        self.key
+            .encrypt_in_place_for_path(path_id, packet_number, header, payload)
+    }
+
+    fn decrypt_in_place<'a>(
+        &self,
+        packet_number: u64,
+        header: &[u8],
+        payload: &'a mut [u8],
+    ) -> Result<&'a [u8], rustls::Error> {
+        self.key.decrypt_in_place(packet_number, header, payload)
+    }
+
+    fn tag_len(&self) -> usize {
+        self.key.tag_len()
+    }
+
+    fn confidentiality_limit(&self) -> u64 {
+        self.key.confidentiality_limit()
+    }
+
+    fn integrity_limit(&self) -> u64 {
+        self.key.integrity_limit()
+    }
+}
+
+/// The 1-RTT packet key for the packets of a path other than the path 0,
+/// whose nonce is derived from both the path ID and the packet number.
+///
+/// See [nonce calculation](https://datatracker.ietf.org/doc/html/draft-ietf-quic-multipath#name-nonce-calculation)
+/// of [Multipath Extension for QUIC](https://datatracker.ietf.org/doc/html/draft-ietf-quic-multipath).
+struct PathPacketKey {
+    path_id: u32,
+    key: Arc<dyn PacketKey>,
+}
+
+impl PathPacketKey {
+    fn bind(path_id: u32, key: Arc<dyn PacketKey>) -> Arc<dyn PacketKey> {
+        // The nonce of the path 0 is the same as the one without multipath.
+        if path_id == 0 {
+            return key;
+        }
+        Arc::new(Self { path_id, key })
+    }
+}
+
+impl PacketKey for PathPacketKey {
+    fn encrypt_in_place(
+        &self,
+        packet_number: u64,
+        header: &[u8],
+        payload: &mut [u8],
+    ) -> Result<Tag, rustls::Error> {
+        self.key
+            .encrypt_in_place_for_path(self.path_id, packet_number, header, payload)
+    }
+
+    fn decrypt_in_place<'a>(
+        &self,
+        packet_number: u64,
+        header: &[u8],
+        payload: &'a mut [u8],
+    ) -> Result<&'a [u8], rustls::Error> {
+        self.key
+            .decrypt_in_place_for_path(self.path_id, packet_number, header, payload)
+    }
+
+    fn tag_len(&self) -> usize {
+        self.key.tag_len()
+    }
+
+    fn confidentiality_limit(&self) -> u64 {
+        self.key.confidentiality_limit()
+    }
+
+    fn integrity_limit(&self) -> u64 {
+        self.key.integrity_limit()
+    }
+}
+
 /// The packet encryption and decryption keys for 1-RTT packets,
 /// which will still change after negotiation between the two endpoints.
 ///
+/// Each key update starts a new generation of keys, and toggles the key phase bit.
+/// The remote keys of the previous generation are retained for a while to decrypt
+/// the delayed packets, until they are phased out, see [`Self::phase_out`].
+///
 /// See [key update](https://www.rfc-editor.org/rfc/rfc9001#name-key-update)
 /// of [RFC 9001](https://www.rfc-editor.org/rfc/rfc9001) for more details.
 pub struct OneRttPacketKeys {
     cur_phase: KeyPhaseBit,
+    generation: u64,
     secrets: Secrets,
     remote: [Option<Arc<dyn PacketKey>>; 2],
-    local: Arc<dyn PacketKey>,
+    local: Arc<LocalPacketKey>,
+    // The keys of the next generation, derived in advance to try to decrypt the packets
+    // in the next key phase, which are adopted only if the decryption succeeds.
+    next: Option<(Arc<dyn PacketKey>, Box<dyn PacketKey>)>,
+    // The smallest packet number received in the current key phase on each path.
+    first_rcvd_pns: HashMap<u32, u64>,
+    // Whether a packet protected with the current local key has been acknowledged.
+    is_acked: bool,
+    decryption_failures: u64,
+    policy: KeyUpdatePolicy,
+    new_phase: Option<(u64, KeyUpdateTrigger)>,
 }
 
 impl OneRttPacketKeys {
     /// Create new [`OneRttPacketKeys`].
     ///
     /// The TLS handshake session must exchange enough information to generate the 1-RTT keys.
-    fn new(remote: Box<dyn PacketKey>, local: Box<dyn PacketKey>, secrets: Secrets) -> Self {
+    fn new(
+        remote: Box<dyn PacketKey>,
+        local: Box<dyn PacketKey>,
+        secrets: Secrets,
+        policy: KeyUpdatePolicy,
+    ) -> Self {
         Self {
             cur_phase: KeyPhaseBit::default(),
+            generation: 0,
             secrets,
             remote: [Some(Arc::from(remote)), None],
-            local: Arc::from(local),
+            local: LocalPacketKey::new(local),
+            next: None,
+            first_rcvd_pns: HashMap::new(),
+            is_acked: false,
+            decryption_failures: 0,
+            policy,
+            new_phase: None,
         }
     }
 
-    /// Proactively update the 1-RTT packet key locally.
-    /// Or be informed by the peer to update the key.
+    fn next_keys(&mut self) -> &(Arc<dyn PacketKey>, Box<dyn PacketKey>) {
+        self.next.get_or_insert_with(|| {
+            let key_set = self.secrets.next_packet_keys();
+            (Arc::from(key_set.remote), key_set.local)
+        })
+    }
+
+    /// Switch to the keys of the next generation, and toggle the key phase bit.
+    fn update(&mut self) {
+        self.next_keys();
+        let (remote, local) = self.next.take().unwrap();
+        self.cur_phase.toggle();
+        self.generation += 1;
+        self.remote[self.cur_phase.as_index()] = Some(remote);
+        self.local = LocalPacketKey::new(local);
+        self.first_rcvd_pns.clear();
+        self.is_acked = false;
+    }
+
+    /// Whether the keys are the old keys for the packet in the `key_phase` with the `pn`
+    /// received on the path `path_id`.
+    ///
+    /// The packets with lower packet numbers than any packet of the same path in the current
+    /// key phase are protected with the old keys, the others in the other key phase are
+    /// protected with the next keys.
+    fn is_old_phase(&self, key_phase: KeyPhaseBit, path_id: u32, pn: u64) -> bool {
+        key_phase != self.cur_phase
+            && self.remote[key_phase.as_index()].is_some()
+            && self
+                .first_rcvd_pns
+                .get(&path_id)
+                .map_or(true, |first| pn < *first)
+    }
+
+    /// Proactively update the 1-RTT packet keys locally, if the local key has protected
+    /// enough packets or bytes according to the [`KeyUpdatePolicy`].
     ///
     /// The key phase bit will be toggled and sent to the peer,
     /// informing the peer to update the key to next 1-RTT packet key too.
-    pub fn update(&mut self) {
-        self.cur_phase.toggle();
-        let key_set = self.secrets.next_packet_keys();
-        self.remote[self.cur_phase.as_index()] = Some(Arc::from(key_set.remote));
-        self.local = Arc::from(key_set.local);
+    ///
+    /// Return true if the keys are updated.
+    ///
+    /// See [Section 6.1](https://www.rfc-editor.org/rfc/rfc9001#section-6.1) of RFC 9001,
+    /// a subsequent key update must not be initiated until a packet protected with the
+    /// current key has been acknowledged, see [`Self::on_ack_rcvd`].
+    pub fn try_update(&mut self) -> bool {
+        let should_update = self.is_acked && self.local.is_used_up(&self.policy);
+        if should_update {
+            self.update();
+        }
+        should_update
+    }
+
+    /// Whether the local key has protected as many packets as the confidentiality limit of the
+    /// AEAD algorithm allows, while the keys cannot be updated since no packet protected with
+    /// it has been acknowledged yet, see [`Self::try_update`].
+    ///
+    /// The key must not be used anymore, the connection should be closed with an
+    /// AEAD_LIMIT_REACHED error, see [Section 6.6](https://www.rfc-editor.org/rfc/rfc9001#section-6.6)
+    /// of RFC 9001.
+    pub fn is_exhausted(&self) -> bool {
+        self.local.is_exhausted()
+    }
+
+    /// Called when an ACK or PATH_ACK frame acknowledging the packets of the path `path_id`
+    /// up to `largest` is received.
+    ///
+    /// The handshake must have been confirmed before initiating any key update,
+    /// the ACK frames received before that should not be passed in.
+    pub fn on_ack_rcvd(&mut self, path_id: u32, largest: u64) {
+        if self.local.is_acked(path_id, largest) {
+            self.is_acked = true;
+        }
     }
 
     /// Old key must be phased out within a certain period of time.
@@ -225,26 +534,99 @@ impl OneRttPacketKeys {
     /// If the old one don't go, the new ones won't come.
     /// If it is not phased out, it will be considered as new keys and
     /// fail to decrypt the packet in future.
-    pub fn phase_out(&mut self) {
-        self.remote[(!self.cur_phase).as_index()].take();
+    ///
+    /// Only the old keys of the `generation` will be phased out, if the keys have been
+    /// updated again in the meantime, the old keys are still needed for a while.
+    ///
+    /// Return true if the old keys are phased out.
+    pub fn phase_out(&mut self, generation: u64) -> bool {
+        generation == self.generation && self.remote[(!self.cur_phase).as_index()].take().is_some()
     }
 
     /// Get the remote key to decrypt the incoming 1-RTT packet.
-    /// If the key phase is not the current key phase, update the key, see [`Self::update`].
     ///
-    /// Return `Arc<PacketKey>` to decrypt the incoming 1-RTT packet.
-    pub fn get_remote(&mut self, key_phase: KeyPhaseBit, _pn: u64) -> Arc<dyn PacketKey> {
-        if key_phase != self.cur_phase && self.remote[key_phase.as_index()].is_none() {
+    /// If the key phase is not the current key phase, it may be a delayed packet protected with
+    /// the old key, or the peer has initiated a key update, and the next key will be returned.
+    /// The keys will not be updated until the packet is decrypted successfully, see
+    /// [`Self::on_packet_decrypted`].
+    ///
+    /// Return `Arc<PacketKey>` to decrypt the incoming 1-RTT packet received on the path
+    /// `path_id`, which is 0 unless multipath is enabled.
+    pub fn get_remote(
+        &mut self,
+        key_phase: KeyPhaseBit,
+        path_id: u32,
+        pn: u64,
+    ) -> Arc<dyn PacketKey> {
+        let key = if key_phase == self.cur_phase || self.is_old_phase(key_phase, path_id, pn) {
+            self.remote[key_phase.as_index()].clone().unwrap()
+        } else {
+            self.next_keys().0.clone()
+        };
+        PathPacketKey::bind(path_id, key)
+    }
+
+    /// Called when a packet in the `key_phase` with the `pn` received on the path `path_id`
+    /// is decrypted successfully with the key from [`Self::get_remote`].
+    ///
+    /// If the packet is protected with the next key, the peer has initiated a key update,
+    /// and the keys will be updated too.
+    pub fn on_packet_decrypted(&mut self, key_phase: KeyPhaseBit, path_id: u32, pn: u64) {
+        if self.is_old_phase(key_phase, path_id, pn) {
+            return;
+        }
+        if key_phase != self.cur_phase {
             self.update();
+            self.new_phase = Some((self.generation, KeyUpdateTrigger::Remote));
+        } else if self.first_rcvd_pns.is_empty() && self.generation > 0 {
+            self.new_phase = Some((self.generation, KeyUpdateTrigger::Local));
         }
-        self.remote[key_phase.as_index()].clone().unwrap()
+        self.first_rcvd_pns
+            .entry(path_id)
+            .and_modify(|first| *first = (*first).min(pn))
+            .or_insert(pn);
     }
 
-    /// Get the local current key to encrypt the outgoing packet.
+    /// Called when a packet fails to be decrypted.
+    ///
+    /// Return true if the number of the packets that failed to be decrypted exceeds
+    /// the integrity limit of the AEAD algorithm, the connection must be closed with
+    /// an AEAD_LIMIT_REACHED error immediately, see
+    /// [Section 6.6](https://www.rfc-editor.org/rfc/rfc9001#section-6.6) of RFC 9001.
+    pub fn on_decryption_failure(&mut self) -> bool {
+        self.decryption_failures += 1;
+        self.decryption_failures > self.local.integrity_limit()
+    }
+
+    /// Take the generation of the keys that the peer has started to use since the last call,
+    /// and the endpoint that initiated the key update.
+    ///
+    /// The old remote keys should be phased out after a while, see [`Self::phase_out`].
+    pub fn take_new_phase(&mut self) -> Option<(u64, KeyUpdateTrigger)> {
+        self.new_phase.take()
+    }
+
+    /// The generation of the current keys, which starts from 0 and increases by 1
+    /// for each key update.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Get the local current key to encrypt the outgoing packet on the path `path_id`,
+    /// which is 0 unless multipath is enabled.
     ///
     /// Return `Arc<PacketKey>` to encrypt the outgoing 1-RTT packet.
-    pub fn get_local(&self) -> (KeyPhaseBit, Arc<dyn PacketKey>) {
-        (self.cur_phase, self.local.clone())
+    pub fn get_local(&self, path_id: u32) -> (KeyPhaseBit, Arc<dyn PacketKey>) {
+        (
+            self.cur_phase,
+            PathPacketKey::bind(path_id, self.local.clone()),
+        )
+    }
+
+    /// Like [`Self::get_local`], but [`None`] if the local key is exhausted, see
+    /// [`Self::is_exhausted`].
+    pub fn try_get_local(&self, path_id: u32) -> Option<(KeyPhaseBit, Arc<dyn PacketKey>)> {
+        (!self.is_exhausted()).then(|| self.get_local(path_id))
     }
 }
 
@@ -261,7 +643,7 @@ impl ArcOneRttPacketKeys {
     /// Obtain exclusive access to the 1-RTT packet keys.
     /// During the exclusive period of encrypting or decrypting packets,
     /// the keys must not be updated elsewhere.
-    pub fn lock_guard(&self) -> MutexGuard<OneRttPacketKeys> {
+    pub fn lock_guard(&self) -> MutexGuard<'_, OneRttPacketKeys> {
         self.0.0.lock().unwrap()
     }
 
@@ -306,17 +688,19 @@ enum OneRttKeysState {
 /// but the PacketKey may still be updated with changes in the KeyPhase bit.
 /// Therefore, the HeaderProtectionKey and PacketKey need to be managed separately.
 #[derive(Clone)]
-pub struct ArcOneRttKeys(Arc<Mutex<OneRttKeysState>>);
+pub struct ArcOneRttKeys(Arc<(Mutex<OneRttKeysState>, KeyUpdatePolicy)>);
 
 impl ArcOneRttKeys {
-    fn lock_guard(&self) -> MutexGuard<OneRttKeysState> {
-        self.0.lock().unwrap()
+    fn lock_guard(&self) -> MutexGuard<'_, OneRttKeysState> {
+        self.0.0.lock().unwrap()
     }
 
     /// Create a Pending state [`ArcOneRttKeys`], waiting for the keys being ready
     /// from TLS handshaking.
-    pub fn new_pending() -> Self {
-        Self(Arc::new(OneRttKeysState::Pending(None).into()))
+    ///
+    /// The packet keys will be updated proactively according to the `policy`.
+    pub fn new_pending(policy: KeyUpdatePolicy) -> Self {
+        Self(Arc::new((OneRttKeysState::Pending(None).into(), policy)))
     }
 
     /// Set the keys to the [`ArcOneRttKeys`].
@@ -338,6 +722,7 @@ impl ArcOneRttKeys {
                         keys.remote.packet,
                         keys.local.packet,
                         secrets,
+                        self.0.1,
                     )),
                     tag_len,
                 )));
@@ -381,7 +766,7 @@ impl ArcOneRttKeys {
     /// Asynchronously obtain the remote keys for removing header protection and packet decryption.
     ///
     /// Rreturn [`GetRemoteKeys`], which implemented the Future trait.
-    pub fn get_remote_keys(&self) -> GetRemoteOneRttKeys {
+    pub fn get_remote_keys(&self) -> GetRemoteOneRttKeys<'_> {
         GetRemoteOneRttKeys(self)
     }
 }
@@ -415,3 +800,87 @@ impl Future for GetRemoteOneRttKeys<'_> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A packet key allowing to protect 4 packets only, without any protection.
+    struct LimitedKey;
+
+    impl PacketKey for LimitedKey {
+        fn encrypt_in_place(&self, _: u64, _: &[u8], _: &mut [u8]) -> Result<Tag, rustls::Error> {
+            Ok(Tag::from(&[0u8; 16][..]))
+        }
+
+        fn encrypt_in_place_for_path(
+            &self,
+            _: u32,
+            packet_number: u64,
+            header: &[u8],
+            payload: &mut [u8],
+        ) -> Result<Tag, rustls::Error> {
+            self.encrypt_in_place(packet_number, header, payload)
+        }
+
+        fn decrypt_in_place<'a>(
+            &self,
+            _: u64,
+            _: &[u8],
+            payload: &'a mut [u8],
+        ) -> Result<&'a [u8], rustls::Error> {
+            Ok(payload)
+        }
+
+        fn tag_len(&self) -> usize {
+            16
+        }
+
+        fn confidentiality_limit(&self) -> u64 {
+            4
+        }
+
+        fn integrity_limit(&self) -> u64 {
+            4
+        }
+    }
+
+    #[test]
+    fn test_local_packet_key_limits() {
+        let policy = KeyUpdatePolicy::default().after_bytes(2048);
+        let key = LocalPacketKey::new(Box::new(LimitedKey));
+        let path1 = PathPacketKey::bind(1, key.clone());
+        let path2 = PathPacketKey::bind(2, key.clone());
+
+        path1.encrypt_in_place(7, &[], &mut [0; 1000]).unwrap();
+        path2.encrypt_in_place(3, &[], &mut [0; 1000]).unwrap();
+        assert!(!key.is_used_up(&policy));
+        assert!(
+            key.is_acked(1, 7) && !key.is_acked(1, 6) && !key.is_acked(0, 7) && key.is_acked(2, 3)
+        );
+
+        // used up by the bytes of the policy
+        path1.encrypt_in_place(8, &[], &mut [0; 100]).unwrap();
+        assert!(key.is_used_up(&policy));
+        assert!(!key.is_exhausted());
+
+        // used up by the packets of the policy, whichever comes first
+        let policy = KeyUpdatePolicy::default()
+            .after_packets(2)
+            .after_bytes(2048);
+        let key = LocalPacketKey::new(Box::new(LimitedKey));
+        key.encrypt_in_place(0, &[], &mut [0; 10]).unwrap();
+        assert!(!key.is_used_up(&policy));
+        key.encrypt_in_place(1, &[], &mut [0; 10]).unwrap();
+        assert!(key.is_used_up(&policy));
+
+        // the confidentiality limit always applies
+        let key = LocalPacketKey::new(Box::new(LimitedKey));
+        for pn in 0..4 {
+            assert!(!key.is_exhausted());
+            key.encrypt_in_place(pn, &[], &mut [0; 10]).unwrap();
+        }
+        assert!(key.is_used_up(&KeyUpdatePolicy::default()));
+        assert!(key.is_exhausted());
+    }
+}
@@ -0,0 +1,229 @@
+use bytes::BufMut;
+use rustls::{
+    crypto::tls13::{HkdfExpander, OkmBlock, OutputLengthError},
+    internal::derive_traffic_key,
+    quic::{PacketKey, Suite, Version},
+};
+
+use super::{RetryHeader, header::io::WriteHeader};
+use crate::cid::ConnectionId;
+
+/// The size of the Retry Integrity Tag at the end of the Retry packet.
+pub const RETRY_INTEGRITY_TAG_SIZE: usize = 16;
+
+/// rustls has no public constructor for an [`AeadKey`] shorter than 32 bytes, the only way to
+/// build one with the key length of the AEAD algorithm is [`derive_traffic_key`], which expands
+/// the key from a [`HkdfExpander`].
+///
+/// The Retry integrity key is a fixed value, so this "expander" just outputs it.
+///
+/// [`AeadKey`]: rustls::crypto::cipher::AeadKey
+struct FixedKey(&'static [u8; 16]);
+
+impl HkdfExpander for FixedKey {
+    fn expand_slice(&self, _: &[&[u8]], output: &mut [u8]) -> Result<(), OutputLengthError> {
+        let (key, rest) = output.split_at_mut(self.0.len());
+        key.copy_from_slice(self.0);
+        rest.fill(0);
+        Ok(())
+    }
+
+    fn expand_block(&self, _: &[&[u8]]) -> OkmBlock {
+        OkmBlock::new(self.0)
+    }
+
+    fn hash_len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+/// The integrity protection of the Retry packet.
+///
+/// The Retry Integrity Tag is the output of AEAD_AES_128_GCM with a fixed key and nonce,
+/// whose plaintext is empty and whose associated data is the Retry Pseudo-Packet:
+///
+/// ```text
+/// +------+------------------+---------------------------------------+
+/// | ODCIL| ODCID (0..160)   | Retry packet without the integrity tag |
+/// +------+------------------+---------------------------------------+
+/// ```
+///
+/// See [Retry Packet Integrity](https://www.rfc-editor.org/rfc/rfc9001.html#name-retry-packet-integrity)
+/// of [RFC9001](https://www.rfc-editor.org/rfc/rfc9001.html) for more details.
+pub struct RetryIntegrity {
+    key: Box<dyn PacketKey>,
+}
+
+impl RetryIntegrity {
+    /// Create the Retry integrity protection of the `version`, with the AEAD_AES_128_GCM
+    /// provided by the `suite`, which must be the `TLS_AES_128_GCM_SHA256` suite, the same
+    /// one that protects the Initial packets.
+    pub fn new(suite: &Suite, version: Version) -> Self {
+        let (key, nonce) = match version {
+            // https://datatracker.ietf.org/doc/html/draft-ietf-quic-tls-29#section-5.8
+            Version::V1Draft => (
+                &[
+                    0xcc, 0xce, 0x18, 0x7e, 0xd0, 0x9a, 0x09, 0xd0, 0x57, 0x28, 0x15, 0x5a, 0x6c,
+                    0xb9, 0x6b, 0xe1,
+                ],
+                [
+                    0xe5, 0x49, 0x30, 0xf9, 0x7f, 0x21, 0x36, 0xf0, 0x53, 0x0a, 0x8c, 0x1c,
+                ],
+            ),
+            // https://www.rfc-editor.org/rfc/rfc9369.html#name-retry-integrity-tag
+            Version::V2 => (
+                &[
+                    0x8f, 0xb4, 0xb0, 0x1b, 0x56, 0xac, 0x48, 0xe2, 0x60, 0xfb, 0xcb, 0xce, 0xad,
+                    0x7c, 0xcc, 0x92,
+                ],
+                [
+                    0xd8, 0x69, 0x69, 0xbc, 0x2d, 0x7c, 0x6d, 0x99, 0x90, 0xef, 0xb0, 0x4a,
+                ],
+            ),
+            // https://www.rfc-editor.org/rfc/rfc9001.html#name-retry-packet-integrity
+            _ => (
+                &[
+                    0xbe, 0x0c, 0x69, 0x0b, 0x9f, 0x66, 0x57, 0x5a, 0x1d, 0x76, 0x6b, 0x54, 0xe3,
+                    0x68, 0xc8, 0x4e,
+                ],
+                [
+                    0x46, 0x15, 0x99, 0xd3, 0x5d, 0x63, 0x2b, 0xf2, 0x23, 0x98, 0x25, 0xbb,
+                ],
+            ),
+        };
+        let key = derive_traffic_key(&FixedKey(key), suite.suite.aead_alg);
+        Self {
+            // the nonce is used as the iv, with the packet number 0
+            key: suite.quic.packet_key(key, nonce.into()),
+        }
+    }
+
+    /// Compute the Retry Integrity Tag of the Retry packet `without_tag`, which responds
+    /// to the Initial packet whose Destination Connection ID is `origin_dcid`.
+    pub fn tag(
+        &self,
+        origin_dcid: &ConnectionId,
+        without_tag: &[u8],
+    ) -> [u8; RETRY_INTEGRITY_TAG_SIZE] {
+        let mut pseudo_packet = Vec::with_capacity(1 + origin_dcid.len() + without_tag.len());
+        pseudo_packet.put_u8(origin_dcid.len() as u8);
+        pseudo_packet.put_slice(origin_dcid);
+        pseudo_packet.put_slice(without_tag);
+
+        let tag = self
+            .key
+            .encrypt_in_place(0, &pseudo_packet, &mut [])
+            .expect("empty plaintext never exceeds the limit of AEAD");
+        let mut integrity = [0; RETRY_INTEGRITY_TAG_SIZE];
+        integrity.copy_from_slice(tag.as_ref());
+        integrity
+    }
+
+    /// Verify the Retry Integrity Tag at the end of the raw `retry_packet`, in constant time.
+    ///
+    /// Clients MUST discard Retry packets that have a Retry Integrity Tag that cannot be validated.
+    pub fn verify(&self, origin_dcid: &ConnectionId, retry_packet: &[u8]) -> bool {
+        let Some(tag_offset) = retry_packet.len().checked_sub(RETRY_INTEGRITY_TAG_SIZE) else {
+            return false;
+        };
+        let (without_tag, integrity) = retry_packet.split_at(tag_offset);
+        self.tag(origin_dcid, without_tag)
+            .iter()
+            .zip(integrity)
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+    }
+
+    /// Encode the Retry packet with the `header`, in response to the Initial packet whose
+    /// Destination Connection ID is `origin_dcid`.
+    ///
+    /// The integrity field in the `header` is ignored, the right Retry Integrity Tag is
+    /// computed and appended.
+    pub fn seal(&self, origin_dcid: &ConnectionId, header: &RetryHeader) -> Vec<u8> {
+        let mut packet = Vec::with_capacity(64 + header.token().len());
+        packet.put_header(header);
+        let tag_offset = packet.len() - RETRY_INTEGRITY_TAG_SIZE;
+        let tag = self.tag(origin_dcid, &packet[..tag_offset]);
+        packet[tag_offset..].copy_from_slice(&tag);
+        packet
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet::header::{GetDcid, GetScid, long::io::LongHeaderBuilder};
+
+    fn suite() -> Suite {
+        rustls::crypto::ring::cipher_suite::TLS13_AES_128_GCM_SHA256
+            .tls13()
+            .and_then(|suite| suite.quic_suite())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_rfc9001_retry_sample() {
+        // https://www.rfc-editor.org/rfc/rfc9001.html#name-retry
+        let origin_dcid =
+            ConnectionId::from_slice(&[0x83, 0x94, 0xc8, 0xf0, 0x3e, 0x51, 0x57, 0x08]);
+        let retry_packet = [
+            0xff, 0x00, 0x00, 0x00, 0x01, 0x00, 0x08, 0xf0, 0x67, 0xa5, 0x50, 0x2a, 0x42, 0x62,
+            0xb5, 0x74, 0x6f, 0x6b, 0x65, 0x6e, 0x04, 0xa2, 0x65, 0xba, 0x2e, 0xff, 0x4d, 0x82,
+            0x90, 0x58, 0xfb, 0x3f, 0x0f, 0x24, 0x96, 0xba,
+        ];
+
+        let integrity = RetryIntegrity::new(&suite(), Version::V1);
+        assert!(integrity.verify(&origin_dcid, &retry_packet));
+
+        let mut tampered = retry_packet;
+        tampered[16] ^= 1;
+        assert!(!integrity.verify(&origin_dcid, &tampered));
+        let other_dcid = ConnectionId::from_slice(&[0x83, 0x94, 0xc8, 0xf0]);
+        assert!(!integrity.verify(&other_dcid, &retry_packet));
+        assert!(!integrity.verify(&origin_dcid, &retry_packet[..15]));
+
+        let v2 = RetryIntegrity::new(&suite(), Version::V2);
+        assert!(!v2.verify(&origin_dcid, &retry_packet));
+    }
+
+    #[test]
+    fn test_rfc9369_retry_sample() {
+        // https://www.rfc-editor.org/rfc/rfc9369.html#name-retry
+        let origin_dcid =
+            ConnectionId::from_slice(&[0x83, 0x94, 0xc8, 0xf0, 0x3e, 0x51, 0x57, 0x08]);
+        let retry_packet = [
+            0xcf, 0x6b, 0x33, 0x43, 0xcf, 0x00, 0x08, 0xf0, 0x67, 0xa5, 0x50, 0x2a, 0x42, 0x62,
+            0xb5, 0x74, 0x6f, 0x6b, 0x65, 0x6e, 0xc8, 0x64, 0x6c, 0xe8, 0xbf, 0xe3, 0x39, 0x52,
+            0xd9, 0x55, 0x54, 0x36, 0x65, 0xdc, 0xc7, 0xb6,
+        ];
+
+        let integrity = RetryIntegrity::new(&suite(), Version::V2);
+        assert!(integrity.verify(&origin_dcid, &retry_packet));
+
+        let v1 = RetryIntegrity::new(&suite(), Version::V1);
+        assert!(!v1.verify(&origin_dcid, &retry_packet));
+    }
+
+    #[test]
+    fn test_seal_retry_packet() {
+        let origin_dcid = ConnectionId::random_gen(8);
+        let header =
+            LongHeaderBuilder::with_cid(ConnectionId::random_gen(8), ConnectionId::random_gen(8))
+                .retry(b"token".to_vec(), [0; 16]);
+
+        let integrity = RetryIntegrity::new(&suite(), Version::V1);
+        let packet = integrity.seal(&origin_dcid, &header);
+        assert!(integrity.verify(&origin_dcid, &packet));
+
+        let mut datagram = bytes::BytesMut::from(packet.as_slice());
+        let Ok(crate::packet::Packet::Retry(retry)) =
+            crate::packet::io::be_packet(&mut datagram, 8)
+        else {
+            panic!("failed to parse the Retry packet");
+        };
+        assert_eq!(retry.dcid(), header.dcid());
+        assert_eq!(retry.scid(), header.scid());
+        assert_eq!(retry.token(), header.token());
+        assert_eq!(&retry.bytes[..], &packet[..]);
+    }
+}
@@ -2,6 +2,8 @@ use derive_more::Deref;
 
 /// Supports IQuic version 1, if other versions are supported in the future, add them here.
 pub mod v1;
+/// Supports QUIC version 2, see [RFC 9369](https://www.rfc-editor.org/rfc/rfc9369.html).
+pub mod v2;
 
 /// The long packet header contains version information, so the 32-bit
 /// version number info is also one part of the versioned packet type.
@@ -39,13 +41,69 @@ impl Version<1, v1::Type> {
 /// Represent the packet types in the IQuic version 1, including Retry/Initial/0-RTT/Handshake.
 pub type Ver1 = Version<1, v1::Type>;
 
+/// Mainly define the long packet types of the QUIC version 2.
+impl Version<0x6b33_43cf, v2::Type> {
+    /// Retry packet type of the QUIC version 2.
+    pub const RETRY: Self = Self(v2::Type::Retry);
+    /// Initial packet type of the QUIC version 2.
+    pub const INITIAL: Self = Self(v2::Type::Initial);
+    /// 0-RTT packet type of the QUIC version 2.
+    pub const ZERO_RTT: Self = Self(v2::Type::ZeroRtt);
+    /// Handshake packet type of the QUIC version 2.
+    pub const HANDSHAKE: Self = Self(v2::Type::Handshake);
+}
+
+/// Represent the packet types in the QUIC version 2, including Retry/Initial/0-RTT/Handshake.
+pub type Ver2 = Version<0x6b33_43cf, v2::Type>;
+
+/// The version number of the IQuic version 1.
+pub const VERSION_1: u32 = 1;
+/// The version number of the QUIC version 2, see [RFC 9369](https://www.rfc-editor.org/rfc/rfc9369.html).
+pub const VERSION_2: u32 = 0x6b33_43cf;
+
+/// The QUIC versions implemented, in the order of preference.
+pub const SUPPORTED_VERSIONS: &[u32] = &[VERSION_1, VERSION_2];
+
+/// Get the [`rustls::quic::Version`] of the QUIC `version`, which determines the initial salt and the
+/// labels to derive the packet protection keys, and the keys to protect the Retry packets.
+///
+/// # Panics
+///
+/// Panics if the `version` is not implemented, see [`SUPPORTED_VERSIONS`].
+pub fn rustls_version(version: u32) -> rustls::quic::Version {
+    match version {
+        VERSION_1 => rustls::quic::Version::V1,
+        VERSION_2 => rustls::quic::Version::V2,
+        version => panic!("QUIC version 0x{version:x} is not implemented"),
+    }
+}
+
+/// Whether the `version` is reserved to exercise the version negotiation, whose pattern
+/// is `0x?a?a?a?a`.
+///
+/// See [section 15](https://www.rfc-editor.org/rfc/rfc9000.html#name-versions)
+/// of [QUIC](https://www.rfc-editor.org/rfc/rfc9000.html) for more details.
+pub fn is_reserved_version(version: u32) -> bool {
+    version & 0x0f0f_0f0f == 0x0a0a_0a0a
+}
+
 /// The sum types of the long packets.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Type {
     VersionNegotiation,
     V1(Version<1, v1::Type>),
+    V2(Version<0x6b33_43cf, v2::Type>),
     // in the future, add other versions here
-    // V2(v2::HeaderType),
+}
+
+impl GetVersion for Type {
+    fn get_version(&self) -> u32 {
+        match self {
+            Type::VersionNegotiation => 0,
+            Type::V1(ty) => ty.get_version(),
+            Type::V2(ty) => ty.get_version(),
+        }
+    }
 }
 
 /// The io module provides the functions to parse and write the long packet type.
@@ -65,12 +123,18 @@ pub mod io {
             let (remain, version) = be_u32(input)?;
             match version {
                 0 => Ok((remain, Type::VersionNegotiation)),
-                1 => Ok((
+                VERSION_1 => Ok((
                     remain,
                     Type::V1(Version::<1, v1::Type>(
                         ty.try_into().map_err(nom::Err::Error)?,
                     )),
                 )),
+                VERSION_2 => Ok((
+                    remain,
+                    Type::V2(Version::<0x6b33_43cf, v2::Type>(
+                        ty.try_into().map_err(nom::Err::Error)?,
+                    )),
+                )),
                 v => Err(nom::Err::Error(Error::UnsupportedVersion(v))),
             }
         }
@@ -92,7 +156,12 @@ pub mod io {
                 Type::V1(Version::<1, _>(ty)) => {
                     let ty: u8 = (*ty).into();
                     self.put_u8(LONG_HEADER_BIT | FIXED_BIT | ty);
-                    self.put_u32(1);
+                    self.put_u32(VERSION_1);
+                }
+                Type::V2(Version::<0x6b33_43cf, _>(ty)) => {
+                    let ty: u8 = (*ty).into();
+                    self.put_u8(LONG_HEADER_BIT | FIXED_BIT | ty);
+                    self.put_u32(VERSION_2);
                 }
             }
         }
@@ -101,7 +170,7 @@ pub mod io {
 
 #[cfg(test)]
 mod tests {
-    use crate::packet::r#type::long::Ver1;
+    use crate::packet::r#type::long::{Ver1, Ver2};
 
     #[test]
     fn test_read_long_type() {
@@ -116,6 +185,23 @@ mod tests {
         let (remain, ty) = parse_long_type(0x80)(&buf).unwrap();
         assert_eq!(remain.len(), 0);
         assert_eq!(ty, Type::VersionNegotiation);
+
+        let buf = vec![0x6b, 0x33, 0x43, 0xcf];
+        let (remain, ty) = parse_long_type(0xd0)(&buf).unwrap();
+        assert_eq!(remain.len(), 0);
+        assert_eq!(ty, Type::V2(Ver2::INITIAL));
+        let (_, ty) = parse_long_type(0xc0)(&buf).unwrap();
+        assert_eq!(ty, Type::V2(Ver2::RETRY));
+    }
+
+    #[test]
+    fn test_reserved_version() {
+        use super::{SUPPORTED_VERSIONS, is_reserved_version};
+
+        assert!(is_reserved_version(0x1a2a3a4a));
+        assert!(is_reserved_version(0xfafafafa));
+        assert!(!is_reserved_version(0x1a2a3a4b));
+        assert!(!SUPPORTED_VERSIONS.iter().any(|v| is_reserved_version(*v)));
     }
 
     #[test]
@@ -138,6 +224,11 @@ mod tests {
         let ty = Type::V1(Ver1::INITIAL);
         buf.put_long_type(&ty);
         assert_eq!(buf, vec![0xc0, 0x00, 0x00, 0x00, 0x01]);
+
+        let mut buf = vec![];
+        let ty = Type::V2(Ver2::HANDSHAKE);
+        buf.put_long_type(&ty);
+        assert_eq!(buf, vec![0xf0, 0x6b, 0x33, 0x43, 0xcf]);
     }
 
     #[test]
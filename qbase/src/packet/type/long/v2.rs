@@ -0,0 +1,69 @@
+use crate::packet::{error::Error, r#type::FIXED_BIT};
+
+/// Long packet types of QUIC version 2. They are the same as the types of QUIC version 1,
+/// but the 3th and 4th bits of the first byte of the long header are encoded differently.
+///
+/// See [long header packet types](https://www.rfc-editor.org/rfc/rfc9369.html#name-long-header-packet-types)
+/// of [RFC 9369](https://www.rfc-editor.org/rfc/rfc9369.html) for more details.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Type {
+    /// Initial packet type, represented by 0b01
+    Initial,
+    /// 0-RTT packet type, represented by 0b10
+    ZeroRtt,
+    /// Handshake packet type, represented by 0b11
+    Handshake,
+    /// Retry packet type, represented by 0b00
+    Retry,
+}
+
+const LONG_PACKET_TYPE_MASK: u8 = 0x30;
+const INITIAL_PACKET_TYPE: u8 = 0x10;
+const ZERO_RTT_PACKET_TYPE: u8 = 0x20;
+const HANDSHAKE_PACKET_TYPE: u8 = 0x30;
+const RETRY_PACKET_TYPE: u8 = 0x00;
+
+impl From<Type> for u8 {
+    fn from(value: Type) -> u8 {
+        match value {
+            Type::Retry => RETRY_PACKET_TYPE,
+            Type::Initial => INITIAL_PACKET_TYPE,
+            Type::ZeroRtt => ZERO_RTT_PACKET_TYPE,
+            Type::Handshake => HANDSHAKE_PACKET_TYPE,
+        }
+    }
+}
+
+impl TryFrom<u8> for Type {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        if value & FIXED_BIT == 0 {
+            tracing::error!("   Cause by: invalid fixed bit in quic packet header");
+            return Err(Error::InvalidFixedBit);
+        }
+        match value & LONG_PACKET_TYPE_MASK {
+            INITIAL_PACKET_TYPE => Ok(Type::Initial),
+            ZERO_RTT_PACKET_TYPE => Ok(Type::ZeroRtt),
+            HANDSHAKE_PACKET_TYPE => Ok(Type::Handshake),
+            RETRY_PACKET_TYPE => Ok(Type::Retry),
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    #[test]
+    fn test_try_from() {
+        use super::Type;
+        use crate::packet::error::Error;
+
+        assert_eq!(Type::try_from(0xd0), Ok(Type::Initial));
+        assert_eq!(Type::try_from(0xe0), Ok(Type::ZeroRtt));
+        assert_eq!(Type::try_from(0xf0), Ok(Type::Handshake));
+        assert_eq!(Type::try_from(0xc0), Ok(Type::Retry));
+        assert_eq!(Type::try_from(0x00), Err(Error::InvalidFixedBit));
+    }
+}
@@ -143,11 +143,6 @@ impl Parameters {
         self.remembered.as_ref()
     }
 
-    // fn set_retry_scid(&mut self, cid: ConnectionId) {
-    //     assert_eq!(self.role(), Role::Server);
-    //     self.server.set_retry_source_connection_id(cid);
-    // }
-
     fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<()> {
         if self.state == Self::CLIENT_READY | Self::SERVER_READY {
             Poll::Ready(())
@@ -196,9 +191,86 @@ impl Parameters {
             Role::Client => self.server = Arc::new(be_server_parameters(input)?),
             Role::Server => self.client = Arc::new(be_client_parameters(input)?),
         }
+        self.authenticate_version()
+    }
+
+    /// Validate the version information from the peer, to prevent the version downgrade attacks.
+    ///
+    /// Both endpoints use the version in the local version information to send packets, so the
+    /// peer must have chosen the same version.
+    /// If any endpoint does not send the version information, the handshake can be completed
+    /// without the validation.
+    ///
+    /// The client initiates the connection with its most preferred version, the first one of its
+    /// available versions. If the client has chosen another version after receiving a Version
+    /// Negotiation packet, the version must still be the most preferred one among the available
+    /// versions of the server, otherwise the Version Negotiation packet may be forged.
+    ///
+    /// See [section 4](https://www.rfc-editor.org/rfc/rfc9368.html#name-version-downgrade-preventio)
+    /// of [RFC9368](https://www.rfc-editor.org/rfc/rfc9368.html) for more details.
+    fn authenticate_version(&self) -> Result<(), QuicError> {
+        let (local, remote) = match self.role() {
+            Role::Client => (
+                self.client.version_information(),
+                self.server.version_information(),
+            ),
+            Role::Server => (
+                self.server.version_information(),
+                self.client.version_information(),
+            ),
+        };
+        let (Some(local), Some(remote)) = (local, remote) else {
+            return Ok(());
+        };
+        if remote.chosen_version() != local.chosen_version() {
+            return Err(QuicError::new(
+                ErrorKind::VersionNegotiation,
+                FrameType::Crypto.into(),
+                format!(
+                    "chosen version 0x{:x} from peer mismatch the version in use 0x{:x}",
+                    remote.chosen_version(),
+                    local.chosen_version()
+                ),
+            ));
+        }
+        // rfc9368 4
+        // If the client has reacted to a Version Negotiation packet, it MUST validate that the
+        // version it chose would have been chosen given the server's Available Versions.
+        if self.role() == Role::Client
+            && local.available_versions().first() != Some(&local.chosen_version())
+        {
+            let expected = local
+                .available_versions()
+                .iter()
+                .find(|version| remote.available_versions().contains(version));
+            if expected != Some(&local.chosen_version()) {
+                return Err(QuicError::new(
+                    ErrorKind::VersionNegotiation,
+                    FrameType::Crypto.into(),
+                    format!(
+                        "version 0x{:x} chosen after version negotiation is not preferred, the server supports {:#x?}",
+                        local.chosen_version(),
+                        remote.available_versions()
+                    ),
+                ));
+            }
+        }
         Ok(())
     }
 
+    /// The client starts a new connection attempt with the `version` after receiving a
+    /// Version Negotiation packet, which will be the chosen version of its version information.
+    fn choose_version(&mut self, version: u32) {
+        assert_eq!(self.role(), Role::Client, "server shuold never call this");
+        let mut client = self.client.as_ref().clone();
+        let available_versions = client
+            .version_information()
+            .map(|version_information| version_information.available_versions().to_vec())
+            .unwrap_or_else(|| vec![version]);
+        client.set_version_information(VersionInformation::new(version, available_versions));
+        self.client = Arc::new(client);
+    }
+
     fn initial_scid_from_peer_need_equal(&mut self, cid: ConnectionId) {
         let initial_scid = match &mut self.requirements {
             Requirements::Client { initial_scid, .. } => initial_scid,
@@ -221,6 +293,20 @@ impl Parameters {
         }
     }
 
+    fn get_initial_dcid(&self) -> ConnectionId {
+        match self.requirements {
+            Requirements::Client {
+                retry_scid,
+                origin_dcid,
+                ..
+            } => retry_scid.unwrap_or(origin_dcid),
+            Requirements::Server { .. } => self
+                .server
+                .retry_source_connection_id()
+                .unwrap_or_else(|| self.server.original_destination_connection_id()),
+        }
+    }
+
     fn authenticate_cids(&self) -> Result<bool, QuicError> {
         fn param_error(reason: &'static str) -> QuicError {
             QuicError::new(
@@ -237,7 +323,7 @@ impl Parameters {
         match self.requirements {
             Requirements::Client {
                 initial_scid,
-                retry_scid: _,
+                retry_scid,
                 origin_dcid,
             } => {
                 // Because TLS and packet parsing are in parallel,
@@ -252,10 +338,11 @@ impl Parameters {
                         "Initial Source Connection ID from server mismatch",
                     ));
                 }
-                // 并不正确，要和intiial_scid一样地去验证
-                // if self.server.retry_source_connection_id() != retry_scid {
-                //     return Err(param_error("Retry Source Connection ID mismatch"));
-                // }
+                // The retry_scid has been set before the Initial packet from the server
+                // is received, it is None if no Retry packet was received.
+                if self.server.retry_source_connection_id() != retry_scid {
+                    return Err(param_error("Retry Source Connection ID mismatch"));
+                }
                 if self.server.original_destination_connection_id() != origin_dcid {
                     return Err(param_error("Original Destination Connection ID mismatch"));
                 }
@@ -423,17 +510,32 @@ impl ArcParameters {
         Ok(params.remembered().and_then(|r| r.get_as(id)))
     }
 
-    // /// Sets the retry source connection ID in the server
-    // /// transport parameters.
-    // ///
-    // /// It is meaningful only for the client, because only
-    // /// server can send the Retry packet.
-    // pub fn set_retry_scid(&self, cid: ConnectionId) {
-    //     let mut guard = self.0.lock().unwrap();
-    //     if let Ok(params) = guard.deref_mut() {
-    //         params.set_retry_scid(cid);
-    //     }
-    // }
+    /// Get the remote transport parameter by id, or the remembered one if the
+    /// remote transport parameters have not been received yet.
+    ///
+    /// The client sending early data with 0Rtt packets uses the remembered
+    /// server transport parameters, without waiting for the handshake.
+    ///
+    /// Returns Err if some connection error occurred, or the parameter not exist
+    pub async fn get_remote_or_remembered_as<V>(&self, id: ParameterId) -> Result<V, Error>
+    where
+        V: TryFrom<ParameterValue>,
+        <V as TryFrom<ParameterValue>>::Error: Debug,
+    {
+        if let Some(value) = std::future::poll_fn(|cx| match self.0.lock().unwrap().as_mut() {
+            Ok(params) if params.is_remote_params_ready() => Poll::Ready(Ok(None)),
+            Ok(params) => match params.remembered().and_then(|r| r.get_as::<V>(id)) {
+                Some(value) => Poll::Ready(Ok(Some(value))),
+                None => params.poll_ready(cx).map(|()| Ok(None)),
+            },
+            Err(e) => Poll::Ready(Err(e.clone())),
+        })
+        .await?
+        {
+            return Ok(value);
+        }
+        self.get_remote_as(id).await
+    }
 
     /// Gets the original destination connection ID of the connection.
     ///
@@ -447,6 +549,19 @@ impl ArcParameters {
         Ok(params.get_origin_dcid())
     }
 
+    /// Gets the destination connection ID of the Initial packets sent by the client,
+    /// from which the Initial keys are derived.
+    ///
+    /// It is the original destination connection ID, or the source connection ID
+    /// of the Retry packet if the server has responded with a Retry packet.
+    ///
+    /// The server uses it to route the Initial packets to the connection.
+    pub fn get_initial_dcid(&self) -> Result<ConnectionId, Error> {
+        let guard = self.0.lock().unwrap();
+        let params = guard.as_ref().map_err(Clone::clone)?;
+        Ok(params.get_initial_dcid())
+    }
+
     /// Load the local transport parameters into the buffer, which
     /// will be send to the peer soon.
     pub fn load_local_params_into(&self, buf: &mut Vec<u8>) {
@@ -493,6 +608,30 @@ impl ArcParameters {
         }
     }
 
+    /// Gets the local version information, whose chosen version is the version in use.
+    ///
+    /// Returns [`None`] if the local transport parameters have no version information.
+    pub fn version_information(&self) -> Result<Option<VersionInformation>, Error> {
+        let guard = self.0.lock().unwrap();
+        let params = guard.as_ref().map_err(Clone::clone)?;
+        Ok(match params.role() {
+            Role::Client => params.client.version_information(),
+            Role::Server => params.server.version_information(),
+        })
+    }
+
+    /// The client starts a new connection attempt with the `version` after receiving a
+    /// Version Negotiation packet, the `version` will be the chosen version in its version
+    /// information sent to the server.
+    ///
+    /// It must be called before the client transport parameters are loaded to be sent.
+    pub fn choose_version(&self, version: u32) {
+        let mut guard = self.0.lock().unwrap();
+        if let Ok(params) = guard.deref_mut() {
+            params.choose_version(version);
+        }
+    }
+
     /// Being called when the remote transport parameters are received.
     /// It will parse and check the remote transport parameters,
     /// and wake all the wakers waiting for the remote transport parameters
@@ -596,6 +735,96 @@ mod tests {
         assert!(dbg!(params.authenticate_cids()).is_ok());
     }
 
+    #[test]
+    fn test_authenticate_version() {
+        let odcid = ConnectionId::from_slice(b"odcid");
+        let mut client_params = create_test_client_params();
+        client_params.set_version_information(VersionInformation::new(1, vec![1]));
+        let mut params = Parameters::new_client(client_params, None, odcid);
+
+        let mut server_params = ServerParameters::default();
+        server_params.set_initial_source_connection_id(ConnectionId::from_slice(b"server"));
+        server_params.set_original_destination_connection_id(odcid);
+        let mut buf = Vec::new();
+        buf.put_parameters(server_params.as_ref());
+        // the version information is optional
+        assert!(params.parse_and_validate_remote_params(&buf).is_ok());
+
+        server_params.set_version_information(VersionInformation::new(1, vec![1, 0x6b3343cf]));
+        let mut buf = Vec::new();
+        buf.put_parameters(server_params.as_ref());
+        assert!(params.parse_and_validate_remote_params(&buf).is_ok());
+
+        server_params.set_version_information(VersionInformation::new(0x6b3343cf, vec![1]));
+        let mut buf = Vec::new();
+        buf.put_parameters(server_params.as_ref());
+        let error = params.parse_and_validate_remote_params(&buf).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::VersionNegotiation);
+    }
+
+    #[test]
+    fn test_authenticate_version_after_negotiation() {
+        let odcid = ConnectionId::from_slice(b"odcid");
+        let mut client_params = create_test_client_params();
+        client_params
+            .set_version_information(VersionInformation::new(0x6b3343cf, vec![0x6b3343cf, 1]));
+        let mut params = Parameters::new_client(client_params, None, odcid);
+        // the server responded with a Version Negotiation packet which does not list version 2
+        params.choose_version(1);
+        let version_information = params.client.version_information().unwrap();
+        assert_eq!(version_information.chosen_version(), 1);
+        assert_eq!(version_information.available_versions(), &[0x6b3343cf, 1]);
+
+        let mut server_params = ServerParameters::default();
+        server_params.set_initial_source_connection_id(ConnectionId::from_slice(b"server"));
+        server_params.set_original_destination_connection_id(odcid);
+        server_params.set_version_information(VersionInformation::new(1, vec![1]));
+        let mut buf = Vec::new();
+        buf.put_parameters(server_params.as_ref());
+        assert!(params.parse_and_validate_remote_params(&buf).is_ok());
+
+        // the server supports version 2 indeed, the Version Negotiation packet was forged
+        server_params.set_version_information(VersionInformation::new(1, vec![1, 0x6b3343cf]));
+        let mut buf = Vec::new();
+        buf.put_parameters(server_params.as_ref());
+        let error = params.parse_and_validate_remote_params(&buf).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::VersionNegotiation);
+    }
+
+    #[test]
+    fn test_authenticate_retry_scid() {
+        let odcid = ConnectionId::from_slice(b"odcid");
+        let retry_scid = ConnectionId::from_slice(b"retry");
+        let server_cid = ConnectionId::from_slice(b"server_test");
+        let server_params = |retry_scid: Option<ConnectionId>| {
+            let mut server_params = ServerParameters::default();
+            server_params.set_initial_source_connection_id(server_cid);
+            server_params.set_original_destination_connection_id(odcid);
+            if let Some(retry_scid) = retry_scid {
+                server_params.set_retry_source_connection_id(retry_scid);
+            }
+            server_params
+        };
+
+        let mut params = Parameters::new_client(create_test_client_params(), None, odcid);
+        params.initial_scid_from_peer_need_equal(server_cid);
+        assert_eq!(params.get_initial_dcid(), odcid);
+        // the server claims a Retry that the client never received
+        params.server = Arc::new(server_params(Some(retry_scid)));
+        assert!(params.authenticate_cids().is_err());
+
+        params.retry_scid_from_server_need_equal(retry_scid);
+        assert_eq!(params.get_initial_dcid(), retry_scid);
+        assert!(params.authenticate_cids().is_ok());
+        // the server omits the retry_source_connection_id after a Retry
+        params.server = Arc::new(server_params(None));
+        assert!(params.authenticate_cids().is_err());
+
+        let server = Parameters::new_server(server_params(Some(retry_scid)));
+        assert_eq!(server.get_initial_dcid(), retry_scid);
+        assert_eq!(server.get_origin_dcid(), odcid);
+    }
+
     #[test]
     fn test_parameters_as_client() {
         let client_params = create_test_client_params();
@@ -612,6 +841,23 @@ mod tests {
             Ok(None)
         );
 
+        // Test remote params fall back to remembered params
+        let remembered = RememberedParameters::from([(
+            ParameterId::InitialMaxData,
+            ParameterValue::from(VarInt::from_u32(1024)),
+        )]);
+        let arc_params = ArcParameters::new_client(
+            create_test_client_params(),
+            Some(remembered),
+            ConnectionId::from_slice(b"odcid"),
+        );
+        let initial_max_data =
+            arc_params.get_remote_or_remembered_as::<VarInt>(ParameterId::InitialMaxData);
+        assert_eq!(
+            futures::executor::block_on(initial_max_data),
+            Ok(VarInt::from_u32(1024))
+        );
+
         // Test loading local params
         let mut buf = Vec::new();
         arc_params.load_local_params_into(&mut buf);
@@ -9,7 +9,7 @@ use crate::{
     frame::FrameType,
     param::{
         ParameterId, ParameterValue, PreferredAddress, StoreParameter, StoreParameterExt,
-        be_parameter,
+        VersionInformation, be_parameter,
     },
     token::ResetToken,
     varint::VarInt,
@@ -233,10 +233,22 @@ parameters! {
             setter = set_initial_source_connection_id,
             getter = initial_source_connection_id or must_be_exist(ParameterId::InitialSourceConnectionId)
         }
+        VersionInformation: VersionInformation => {
+            setter = set_version_information,
+            getter = version_information
+        }
         GreaseQuicBit: bool => {
             setter = set_grease_quic_bit,
             getter = grease_quic_bit or false
         }
+        MinAckDelay: Duration .as_micros() in 0..=16_384_000 => {
+            setter = set_min_ack_delay,
+            getter = min_ack_delay
+        }
+        InitialMaxPathId: VarInt .into_inner() in 0..=u32::MAX as u64 => {
+            setter = set_initial_max_path_id,
+            getter = initial_max_path_id
+        }
     }
 }
 
@@ -282,6 +294,33 @@ pub fn be_client_parameters(mut input: &[u8]) -> Result<ClientParameters, QuicEr
         }
     }
 
+    // rfc9368 4
+    // If a server receives Version Information where the Chosen Version is not included in
+    // Available Versions, it MUST treat it as a parsing failure.
+    if let Some(info) = params.version_information() {
+        if !info.available_versions().contains(&info.chosen_version()) {
+            tracing::error!("   Cause by: validating parameters");
+            return Err(parameter_error(
+                ParameterId::VersionInformation,
+                "chosen version is not included in available versions",
+            ));
+        }
+    }
+
+    // draft-ietf-quic-ack-frequency 3
+    // Receiving a min_ack_delay value that is greater than the max_ack_delay value MUST be
+    // treated as a connection error of type TRANSPORT_PARAMETER_ERROR.
+    if params
+        .min_ack_delay()
+        .is_some_and(|min| min > params.max_ack_delay())
+    {
+        tracing::error!("   Cause by: validating parameters");
+        return Err(parameter_error(
+            ParameterId::MinAckDelay,
+            "min_ack_delay is greater than max_ack_delay",
+        ));
+    }
+
     Ok(params)
 }
 
@@ -356,6 +395,10 @@ parameters! {
             setter = set_retry_source_connection_id,
             getter = retry_source_connection_id
         }
+        VersionInformation: VersionInformation => {
+            setter = set_version_information,
+            getter = version_information
+        }
         MaxDatagramFrameSize: VarInt .into_inner() in 8..=65535 => {
             setter = set_max_datagram_frame_size,
             getter = max_datagram_frame_size or VarInt::from_u32(0)
@@ -364,6 +407,14 @@ parameters! {
             setter = set_grease_quic_bit,
             getter = grease_quic_bit or false
         }
+        MinAckDelay: Duration .as_micros() in 0..=16_384_000 => {
+            setter = set_min_ack_delay,
+            getter = min_ack_delay
+        }
+        InitialMaxPathId: VarInt .into_inner() in 0..=u32::MAX as u64 => {
+            setter = set_initial_max_path_id,
+            getter = initial_max_path_id
+        }
     }
 }
 
@@ -396,11 +447,47 @@ pub fn be_server_parameters(mut input: &[u8]) -> Result<ServerParameters, QuicEr
         }
     }
 
+    // draft-ietf-quic-ack-frequency 3
+    // Receiving a min_ack_delay value that is greater than the max_ack_delay value MUST be
+    // treated as a connection error of type TRANSPORT_PARAMETER_ERROR.
+    if params
+        .min_ack_delay()
+        .is_some_and(|min| min > params.max_ack_delay())
+    {
+        tracing::error!("   Cause by: validate parameters");
+        return Err(parameter_error(
+            ParameterId::MinAckDelay,
+            "min_ack_delay is greater than max_ack_delay",
+        ));
+    }
+
     Ok(params)
 }
 
 pub type RememberedParameters = GeneralParameters;
 
+/// Parses the server transport parameters remembered by the client from a previous connection,
+/// only the parameters that the client uses to send 0-RTT packets are kept.
+///
+/// See [section 7.4.1](https://www.rfc-editor.org/rfc/rfc9000.html#name-values-of-transport-paramet)
+/// of [RFC9000](https://www.rfc-editor.org/rfc/rfc9000.html) for more details.
+pub fn be_remembered_parameters(input: &[u8]) -> Result<RememberedParameters, QuicError> {
+    let server_params = be_server_parameters(input)?;
+    Ok([
+        ParameterId::ActiveConnectionIdLimit,
+        ParameterId::InitialMaxData,
+        ParameterId::InitialMaxStreamDataBidiLocal,
+        ParameterId::InitialMaxStreamDataBidiRemote,
+        ParameterId::InitialMaxStreamDataUni,
+        ParameterId::InitialMaxStreamsBidi,
+        ParameterId::InitialMaxStreamsUni,
+        ParameterId::MaxDatagramFrameSize,
+    ]
+    .into_iter()
+    .filter_map(|id| Some((id, server_params.get(id)?)))
+    .collect())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -496,6 +583,39 @@ mod tests {
         assert_eq!(params.initial_max_streams_uni().into_inner(), 2);
     }
 
+    #[test]
+    fn test_parse_remembered_parameters() {
+        let input = &[
+            1, 1, 0, // max_idle_timeout
+            4, 2, 0x44, 0, // initial_max_data
+            8, 1, 8, // initial_max_streams_bidi
+            15, 0, // initial_source_connection_id
+            0, 0, // original_destination_connection_id
+        ];
+        let remembered = be_remembered_parameters(input).unwrap();
+        assert_eq!(
+            remembered.get_as::<VarInt>(ParameterId::InitialMaxData),
+            Some(VarInt::from_u32(1024))
+        );
+        assert_eq!(
+            remembered.get_as::<VarInt>(ParameterId::InitialMaxStreamsBidi),
+            Some(VarInt::from_u32(8))
+        );
+        // absent parameters are remembered with their default values
+        assert_eq!(
+            remembered.get_as::<VarInt>(ParameterId::InitialMaxStreamsUni),
+            Some(VarInt::from_u32(0))
+        );
+        assert_eq!(
+            remembered.get_as::<Duration>(ParameterId::MaxIdleTimeout),
+            None
+        );
+        assert_eq!(
+            remembered.get_as::<ConnectionId>(ParameterId::InitialSourceConnectionId),
+            None
+        );
+    }
+
     #[test]
     fn test_parse_client_parameters() {
         let empty_input = &[
@@ -517,4 +637,49 @@ mod tests {
         assert_eq!(params.max_ack_delay().as_millis(), 60);
         assert_eq!(params.active_connection_id_limit().into_inner(), 10);
     }
+
+    #[test]
+    fn test_parse_client_version_information() {
+        let input = &[
+            15, 0, // initial_source_connection_id
+            0x11, 8, 0, 0, 0, 1, 0, 0, 0, 1, // version_information
+        ];
+        let params = be_client_parameters(input).unwrap();
+        assert_eq!(
+            params.version_information(),
+            Some(VersionInformation::new(1, vec![1]))
+        );
+
+        let chosen_not_available = &[
+            15, 0, // initial_source_connection_id
+            0x11, 8, 0, 0, 0, 1, 0x6b, 0x33, 0x43, 0xcf, // version_information
+        ];
+        assert!(be_client_parameters(chosen_not_available).is_err());
+    }
+
+    #[test]
+    fn test_parse_min_ack_delay() {
+        let input = &[
+            15, 0, // initial_source_connection_id
+            0xc0, 0, 0, 0, 0xff, 0x04, 0xde, 0x1b, 2, 0x43, 0xe8, // min_ack_delay
+        ];
+        let params = be_client_parameters(input).unwrap();
+        assert_eq!(params.min_ack_delay(), Some(Duration::from_millis(1)));
+
+        let greater_than_max_ack_delay = &[
+            15, 0, // initial_source_connection_id
+            0xc0, 0, 0, 0, 0xff, 0x04, 0xde, 0x1b, 4, 0x80, 0, 0x75, 0x30, // min_ack_delay
+        ];
+        assert!(be_client_parameters(greater_than_max_ack_delay).is_err());
+    }
+
+    #[test]
+    fn test_parse_initial_max_path_id() {
+        let input = &[
+            15, 0, // initial_source_connection_id
+            0xcf, 0x73, 0x9b, 0xbc, 0x1b, 0x66, 0x6d, 0x0c, 1, 4, // initial_max_path_id
+        ];
+        let params = be_client_parameters(input).unwrap();
+        assert_eq!(params.initial_max_path_id(), Some(VarInt::from_u32(4)));
+    }
 }
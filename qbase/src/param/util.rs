@@ -2,7 +2,7 @@ use std::{
     any::Any,
     collections::HashMap,
     fmt::Debug,
-    net::{SocketAddrV4, SocketAddrV6},
+    net::{SocketAddr, SocketAddrV4, SocketAddrV6},
     time::Duration,
 };
 
@@ -14,6 +14,7 @@ use nom::Parser;
 use crate::{
     cid::{ConnectionId, WriteConnectionId, be_connection_id, be_connection_id_with_len},
     error::QuicError,
+    net::address::IpFamily,
     token::{ResetToken, WriteResetToken, be_reset_token},
     varint::{VarInt, WriteVarInt, be_varint},
 };
@@ -38,8 +39,11 @@ pub enum ParameterId {
     ActiveConnectionIdLimit,
     InitialSourceConnectionId,
     RetrySourceConnectionId,
+    VersionInformation,
     MaxDatagramFrameSize,
     GreaseQuicBit,
+    MinAckDelay,
+    InitialMaxPathId,
     Value(VarInt),
 }
 
@@ -69,8 +73,14 @@ impl From<ParameterId> for VarInt {
             ParameterId::ActiveConnectionIdLimit => 0x0e,
             ParameterId::InitialSourceConnectionId => 0x0f,
             ParameterId::RetrySourceConnectionId => 0x10,
+            ParameterId::VersionInformation => 0x11,
             ParameterId::MaxDatagramFrameSize => 0x20,
             ParameterId::GreaseQuicBit => 0x2a_b2,
+            ParameterId::MinAckDelay => 0xff04_de1b,
+            // the only parameter id that does not fit in u32
+            ParameterId::InitialMaxPathId => {
+                return VarInt::from_u64(0x0f73_9bbc_1b66_6d0c).unwrap();
+            }
             ParameterId::Value(id) => return id,
         })
     }
@@ -96,8 +106,11 @@ impl From<VarInt> for ParameterId {
             0x0e => ParameterId::ActiveConnectionIdLimit,
             0x0f => ParameterId::InitialSourceConnectionId,
             0x10 => ParameterId::RetrySourceConnectionId,
+            0x11 => ParameterId::VersionInformation,
             0x20 => ParameterId::MaxDatagramFrameSize,
             0x2a_b2 => ParameterId::GreaseQuicBit,
+            0xff04_de1b => ParameterId::MinAckDelay,
+            0x0f73_9bbc_1b66_6d0c => ParameterId::InitialMaxPathId,
             _ => ParameterId::Value(id),
         }
     }
@@ -160,6 +173,20 @@ impl PreferredAddress {
     pub fn encoding_size(&self) -> usize {
         6 + 18 + self.connection_id.encoding_size() + self.stateless_reset_token.encoding_size()
     }
+
+    /// Returns the preferred address of the IP `family`, or None if the server does not
+    /// provide one.
+    ///
+    /// Servers MAY choose to only send a preferred address of one address family by
+    /// sending an all-zero address and port (0.0.0.0:0 or [::]:0) for the other family,
+    /// see [section-9.6.1](https://datatracker.ietf.org/doc/html/rfc9000#section-9.6.1).
+    pub fn address(&self, family: IpFamily) -> Option<SocketAddr> {
+        let address = match family {
+            IpFamily::V4 => SocketAddr::V4(self.address_v4),
+            IpFamily::V6 => SocketAddr::V6(self.address_v6),
+        };
+        (!address.ip().is_unspecified() && address.port() != 0).then_some(address)
+    }
 }
 
 /// Parse the preferred address from the input buffer,
@@ -217,6 +244,85 @@ impl<T: bytes::BufMut> WirtePreferredAddress for T {
     }
 }
 
+/// The version information of an endpoint, which is used by the compatible version
+/// negotiation to prevent the version downgrade attacks.
+///
+/// The chosen version is the version in use for the connection, and the available
+/// versions are the versions supported by the endpoint, in the order of preference.
+///
+/// See [Version Information](https://www.rfc-editor.org/rfc/rfc9368.html#name-version-information)
+/// of [RFC9368](https://www.rfc-editor.org/rfc/rfc9368.html) for more details.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct VersionInformation {
+    chosen_version: u32,
+    available_versions: Vec<u32>,
+}
+
+impl VersionInformation {
+    /// Create a new version information.
+    pub fn new(chosen_version: u32, available_versions: Vec<u32>) -> Self {
+        Self {
+            chosen_version,
+            available_versions,
+        }
+    }
+
+    /// Returns the version in use for the connection.
+    pub fn chosen_version(&self) -> u32 {
+        self.chosen_version
+    }
+
+    /// Returns the versions supported by the endpoint, in the order of preference.
+    pub fn available_versions(&self) -> &[u32] {
+        &self.available_versions
+    }
+
+    /// Returns the encoding size of the version information.
+    pub fn encoding_size(&self) -> usize {
+        4 + 4 * self.available_versions.len()
+    }
+}
+
+/// Parse the version information from the input buffer, which must be exactly
+/// the value of the parameter, [nom](https://docs.rs/nom/latest/nom/) parser style.
+///
+/// A version information whose length is not divisible by 4, or contains a version
+/// of 0, is treated as a parsing failure.
+pub fn be_version_information(input: &[u8]) -> nom::IResult<&[u8], VersionInformation> {
+    use nom::{
+        combinator::{all_consuming, verify},
+        multi::many0,
+        number::complete::be_u32,
+    };
+
+    let non_zero = |version: &u32| *version != 0;
+    let (remain, (chosen_version, available_versions)) =
+        all_consuming((verify(be_u32, non_zero), many0(verify(be_u32, non_zero)))).parse(input)?;
+    Ok((
+        remain,
+        VersionInformation {
+            chosen_version,
+            available_versions,
+        },
+    ))
+}
+
+/// A [`bytes::BufMut`] extension trait, makes buffer more friendly
+/// to write the version information.
+pub trait WriteVersionInformation: bytes::BufMut {
+    /// Write the version information to the buffer.
+    fn put_version_information(&mut self, info: &VersionInformation);
+}
+
+impl<T: bytes::BufMut> WriteVersionInformation for T {
+    fn put_version_information(&mut self, info: &VersionInformation) {
+        self.put_u32(info.chosen_version);
+        for version in &info.available_versions {
+            self.put_u32(*version);
+        }
+    }
+}
+
 #[derive(Debug, Clone, From, TryInto)]
 pub enum ParameterValue {
     // for custom
@@ -227,6 +333,7 @@ pub enum ParameterValue {
     PreferredAddress(PreferredAddress),
     ResetToken(ResetToken),
     VarInt(VarInt),
+    VersionInformation(VersionInformation),
 }
 
 impl TryFrom<ParameterValue> for u64 {
@@ -266,6 +373,12 @@ pub fn be_parameter(input: &[u8]) -> nom::IResult<&[u8], (ParameterId, Parameter
             Duration::from_millis(millis).into()
         })
         .parse(remain)?,
+        // min_ack_delay is the only duration carried in microseconds
+        ParameterId::MinAckDelay => map(be_varint, |varint| {
+            let micros = varint.into_inner();
+            Duration::from_micros(micros).into()
+        })
+        .parse(remain)?,
         // flag
         ParameterId::DisableActiveMigration | ParameterId::GreaseQuicBit => (remain, true.into()),
         ParameterId::StatelssResetToken => {
@@ -281,13 +394,18 @@ pub fn be_parameter(input: &[u8]) -> nom::IResult<&[u8], (ParameterId, Parameter
         | ParameterId::InitialMaxStreamsUni
         | ParameterId::AckDelayExponent
         | ParameterId::ActiveConnectionIdLimit
-        | ParameterId::MaxDatagramFrameSize => {
-            map(be_varint, ParameterValue::VarInt).parse(remain)?
-        }
+        | ParameterId::MaxDatagramFrameSize
+        | ParameterId::InitialMaxPathId => map(be_varint, ParameterValue::VarInt).parse(remain)?,
         // prefer address
         ParameterId::PreferredAddress => {
             map(be_preferred_address, ParameterValue::PreferredAddress).parse(remain)?
         }
+        // version information
+        ParameterId::VersionInformation => {
+            let (remain, value) = take(len.into_inner() as usize)(remain)?;
+            let (_, info) = be_version_information(value)?;
+            (remain, info.into())
+        }
         // custom bytes
         ParameterId::Value(_) => map(take(len.into_inner() as usize), |bytes| {
             Bytes::copy_from_slice(bytes).into()
@@ -305,7 +423,11 @@ pub trait WriteParameter {
     fn put_cid_parameter(&mut self, id: ParameterId, cid: &ConnectionId);
 
     fn put_duration_parameter(&mut self, id: ParameterId, dur: &Duration) {
-        let value = VarInt::from_u128(dur.as_millis()).expect("Duration too large");
+        let value = match id {
+            ParameterId::MinAckDelay => VarInt::from_u128(dur.as_micros()),
+            _ => VarInt::from_u128(dur.as_millis()),
+        }
+        .expect("Duration too large");
         self.put_varint_parameter(id, &value);
     }
 
@@ -317,6 +439,8 @@ pub trait WriteParameter {
 
     fn put_varint_parameter(&mut self, id: ParameterId, value: &VarInt);
 
+    fn put_version_information_parameter(&mut self, id: ParameterId, info: &VersionInformation);
+
     fn put_parameter(&mut self, id: ParameterId, value: &ParameterValue) {
         match value {
             ParameterValue::Bytes(bytes) => self.put_bytes_parameter(id, bytes),
@@ -328,6 +452,9 @@ pub trait WriteParameter {
             }
             ParameterValue::ResetToken(token) => self.put_reset_token_parameter(id, token),
             ParameterValue::VarInt(varint) => self.put_varint_parameter(id, varint),
+            ParameterValue::VersionInformation(info) => {
+                self.put_version_information_parameter(id, info)
+            }
         }
     }
 }
@@ -370,6 +497,12 @@ impl<T: bytes::BufMut> WriteParameter for T {
         self.put_varint(&VarInt::try_from(value.encoding_size()).expect("param too large"));
         self.put_varint(value);
     }
+
+    fn put_version_information_parameter(&mut self, id: ParameterId, info: &VersionInformation) {
+        self.put_parameter_id(id);
+        self.put_varint(&VarInt::try_from(info.encoding_size()).expect("param too large"));
+        self.put_version_information(info);
+    }
 }
 
 pub trait StoreParameter: Any {
@@ -470,6 +603,19 @@ mod tests {
         assert_eq!(param_id, ParameterId::MaxIdleTimeout);
     }
 
+    #[test]
+    fn test_min_ack_delay_in_micros() {
+        let mut buf = Vec::new();
+        buf.put_duration_parameter(ParameterId::MinAckDelay, &Duration::from_micros(1500));
+        let (remain, (id, value)) = be_parameter(&buf).unwrap();
+        assert!(remain.is_empty());
+        assert_eq!(id, ParameterId::MinAckDelay);
+        assert_eq!(
+            Duration::try_from(value).unwrap(),
+            Duration::from_micros(1500)
+        );
+    }
+
     #[test]
     fn test_preferred_address() {
         let addr = PreferredAddress {
@@ -486,6 +632,21 @@ mod tests {
         assert_eq!(decoded, addr);
     }
 
+    #[test]
+    fn test_preferred_address_of_family() {
+        let addr = PreferredAddress::new(
+            "127.0.0.1:8080".parse().unwrap(),
+            "[::]:0".parse().unwrap(),
+            ConnectionId::from_slice(&[1, 2, 3, 4]),
+            ResetToken::new(&[0; 16]),
+        );
+        assert_eq!(
+            addr.address(IpFamily::V4),
+            Some("127.0.0.1:8080".parse().unwrap())
+        );
+        assert_eq!(addr.address(IpFamily::V6), None);
+    }
+
     #[test]
     fn test_preferred_address_encoding() {
         let prefered_addr = PreferredAddress {
@@ -509,6 +670,33 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_version_information() {
+        let info = VersionInformation::new(1, vec![0x6b3343cf, 1]);
+
+        let mut buf = Vec::new();
+        buf.put_parameter(ParameterId::VersionInformation, &info.clone().into());
+        assert_eq!(
+            buf,
+            vec![
+                0x11, 12, // id and length
+                0, 0, 0, 1, // chosen version
+                0x6b, 0x33, 0x43, 0xcf, 0, 0, 0, 1, // available versions
+            ]
+        );
+        let (remain, (id, value)) = be_parameter(&buf).unwrap();
+        assert!(remain.is_empty());
+        assert_eq!(id, ParameterId::VersionInformation);
+        assert_eq!(VersionInformation::try_from(value).unwrap(), info);
+
+        // length not divisible by 4
+        assert!(be_version_information(&[0, 0, 0, 1, 0, 0]).is_err());
+        // chosen version or available version is 0
+        assert!(be_version_information(&[0, 0, 0, 0]).is_err());
+        assert!(be_version_information(&[0, 0, 0, 1, 0, 0, 0, 0]).is_err());
+        assert!(be_version_information(&[]).is_err());
+    }
+
     #[test]
     fn test_preferred_address_parsing() {
         let input = vec![
@@ -284,6 +284,14 @@ where
     }
 }
 
+/// The number of the streams of a type opened so far, and the maximum number of them allowed
+/// to be opened, which grows along with the [`MaxStreamsFrame`]s.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct StreamsCount {
+    pub opened: u64,
+    pub max: u64,
+}
+
 pub mod handy;
 
 pub mod local_sid;
@@ -7,7 +7,7 @@ use std::{
 use super::{Dir, Role, StreamId};
 use crate::{
     frame::{MaxStreamsFrame, ReceiveFrame, SendFrame, StreamsBlockedFrame},
-    sid::MAX_STREAMS_LIMIT,
+    sid::{MAX_STREAMS_LIMIT, StreamsCount},
     varint::VarInt,
 };
 
@@ -148,6 +148,16 @@ where
     pub fn poll_alloc_sid(&self, cx: &mut Context<'_>, dir: Dir) -> Poll<Option<StreamId>> {
         self.0.lock().unwrap().poll_alloc_sid(cx, dir)
     }
+
+    /// Returns the number of the streams opened locally in the `dir` direction, and the
+    /// maximum number of them allowed by peer.
+    pub fn streams_count(&self, dir: Dir) -> StreamsCount {
+        let guard = self.0.lock().unwrap();
+        StreamsCount {
+            opened: guard.unallocated[dir as usize],
+            max: guard.max[dir as usize],
+        }
+    }
 }
 
 impl<BLOCKED> ReceiveFrame<MaxStreamsFrame> for ArcLocalStreamIds<BLOCKED>
@@ -216,4 +226,17 @@ mod tests {
         assert_eq!(local.poll_alloc_sid(&mut cx, Dir::Uni), Poll::Pending);
         assert!(!local.0.lock().unwrap().wakers[1].is_empty());
     }
+
+    #[test]
+    fn test_streams_count() {
+        let local = ArcLocalStreamIds::new(Role::Server, 0, 3, StreamsBlockedFrameTx::default());
+        let waker = futures::task::noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        assert!(local.poll_alloc_sid(&mut cx, Dir::Uni).is_ready());
+        assert_eq!(
+            local.streams_count(Dir::Uni),
+            StreamsCount { opened: 1, max: 3 }
+        );
+        assert_eq!(local.streams_count(Dir::Bi), StreamsCount::default());
+    }
 }
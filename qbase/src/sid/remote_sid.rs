@@ -2,7 +2,7 @@ use std::sync::{Arc, Mutex};
 
 use thiserror::Error;
 
-use super::{ControlStreamsConcurrency, Dir, Role, StreamId};
+use super::{ControlStreamsConcurrency, Dir, Role, StreamId, StreamsCount};
 use crate::{
     frame::{MaxStreamsFrame, ReceiveFrame, SendFrame, StreamsBlockedFrame},
     varint::VarInt,
@@ -214,6 +214,16 @@ where
         self.0.lock().unwrap().try_accept_sid(sid)
     }
 
+    /// Returns the number of the streams opened by peer in the `dir` direction, and the
+    /// maximum number of them allowed locally.
+    pub fn streams_count(&self, dir: Dir) -> StreamsCount {
+        let guard = self.0.lock().unwrap();
+        StreamsCount {
+            opened: guard.unallocated[dir as usize].id(),
+            max: guard.max[dir as usize],
+        }
+    }
+
     #[inline]
     pub fn on_end_of_stream(&self, sid: StreamId) {
         self.0.lock().unwrap().on_end_of_stream(sid);
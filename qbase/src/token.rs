@@ -1,13 +1,21 @@
-use std::{ops::Deref, sync::Arc};
+use std::{
+    fmt,
+    ops::Deref,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 use bytes::BufMut;
 use derive_more::Deref;
 use nom::{IResult, bytes::complete::take};
 use rand::Rng;
+use rustls::crypto::tls13::{Hkdf, HkdfExpander};
 
 use crate::{
+    cid::{ConnectionId, MAX_CID_SIZE},
     error::{ErrorKind, QuicError},
     frame::{GetFrameType, NewTokenFrame, ReceiveFrame},
+    net::address::RealAddr,
 };
 
 pub const RESET_TOKEN_SIZE: usize = 16;
@@ -29,6 +37,201 @@ impl ResetToken {
     pub fn encoding_size(&self) -> usize {
         RESET_TOKEN_SIZE
     }
+
+    /// Compare the token with the given bytes in constant time.
+    ///
+    /// An endpoint MUST NOT leak the stateless reset token through timing side channels when
+    /// detecting a potential stateless reset, see [section 10.3.1] of RFC 9000.
+    ///
+    /// [section 10.3.1]: https://www.rfc-editor.org/rfc/rfc9000.html#name-detecting-a-stateless-reset
+    pub fn matches(&self, bytes: &[u8]) -> bool {
+        bytes.len() == RESET_TOKEN_SIZE
+            && self
+                .0
+                .iter()
+                .zip(bytes)
+                .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+                == 0
+    }
+}
+
+/// The static key used to derive [`ResetToken`]s from connection IDs.
+///
+/// An endpoint that uses the same static key and the same connection ID always generates the
+/// same stateless reset token, so that it can send a stateless reset for a connection whose
+/// state has been lost, even after restarting.
+///
+/// The token is derived as `HKDF-Expand(HKDF-Extract(secret), "stateless reset" || cid)`,
+/// which is one of the designs suggested by [section 10.3.2] of RFC 9000.
+///
+/// [section 10.3.2]: https://www.rfc-editor.org/rfc/rfc9000.html#name-calculating-a-stateless-res
+#[derive(Clone)]
+pub struct StatelessResetKey(Arc<dyn HkdfExpander>);
+
+impl StatelessResetKey {
+    const LABEL: &'static [u8] = b"stateless reset";
+
+    /// Create a key from a static `secret`, with the HKDF of the crypto provider.
+    pub fn new(hkdf: &dyn Hkdf, secret: &[u8]) -> Self {
+        Self(hkdf.extract_from_secret(None, secret).into())
+    }
+
+    /// Create a key from a random secret.
+    ///
+    /// Tokens derived from this key are no longer reproducible once the key is dropped.
+    pub fn random(hkdf: &dyn Hkdf) -> Self {
+        let mut secret = [0u8; 32];
+        rand::rng().fill(&mut secret);
+        Self::new(hkdf, &secret)
+    }
+
+    /// Derive the stateless reset token for the connection ID.
+    pub fn reset_token(&self, cid: &ConnectionId) -> ResetToken {
+        let mut token = [0; RESET_TOKEN_SIZE];
+        self.0
+            .expand_slice(&[Self::LABEL, cid], &mut token)
+            .expect("reset token is much shorter than the max output length of HKDF");
+        ResetToken(token)
+    }
+}
+
+impl fmt::Debug for StatelessResetKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("StatelessResetKey").field(&"...").finish()
+    }
+}
+
+/// The static key used to generate and validate the tokens carried in Retry packets.
+///
+/// A Retry token binds the client address and the Original Destination Connection ID of the
+/// connection, it is self-contained so that the server does not need to keep any state for the
+/// client before its address is validated:
+///
+/// ```text
+/// +----------+----------------+-------+------------------+------------+
+/// | Type (8) | Issued Time(64)| ODCIL | ODCID (0..160)   | Tag (128)  |
+/// +----------+----------------+-------+------------------+------------+
+/// ```
+///
+/// The Type byte tells the Retry tokens apart from the tokens sent in NEW_TOKEN frames, a
+/// server must not respond a client with another Retry packet if the Retry token it sent
+/// is invalid, see [section 8.1.1] of RFC 9000.
+///
+/// The tag is derived as `HKDF-Expand(HKDF-Extract(secret), "retry token" || len(address) ||
+/// address || issued time || ODCIL || ODCID)`, so that only the server holding the key can issue
+/// a valid token. The variable-length fields are length-prefixed, otherwise the bytes could be
+/// shifted between the address and the following fields to bind the token to another address.
+///
+/// See [section 8.1.2] of RFC 9000 for more details.
+///
+/// [section 8.1.1]: https://www.rfc-editor.org/rfc/rfc9000.html#name-token-construction
+/// [section 8.1.2]: https://www.rfc-editor.org/rfc/rfc9000.html#name-address-validation-using-r
+#[derive(Clone)]
+pub struct RetryTokenKey(Arc<dyn HkdfExpander>);
+
+impl RetryTokenKey {
+    const LABEL: &'static [u8] = b"retry token";
+    const TYPE: u8 = 0x52;
+    const TAG_SIZE: usize = 16;
+    /// Retry tokens are used immediately by the client, they expire after a short time to
+    /// limit the replay of them.
+    const LIFETIME: Duration = Duration::from_secs(10);
+
+    /// Create a key from a static `secret`, with the HKDF of the crypto provider.
+    pub fn new(hkdf: &dyn Hkdf, secret: &[u8]) -> Self {
+        Self(hkdf.extract_from_secret(None, secret).into())
+    }
+
+    /// Create a key from a random secret.
+    ///
+    /// Tokens generated with this key can no longer be validated once the key is dropped.
+    pub fn random(hkdf: &dyn Hkdf) -> Self {
+        let mut secret = [0u8; 32];
+        rand::rng().fill(&mut secret);
+        Self::new(hkdf, &secret)
+    }
+
+    fn tag(&self, client: &RealAddr, issued: &[u8], origin_dcid: &[u8]) -> [u8; Self::TAG_SIZE] {
+        let client = client.to_string();
+        let mut tag = [0; Self::TAG_SIZE];
+        self.0
+            .expand_slice(
+                &[
+                    Self::LABEL,
+                    &(client.len() as u16).to_be_bytes(),
+                    client.as_bytes(),
+                    issued,
+                    &[origin_dcid.len() as u8],
+                    origin_dcid,
+                ],
+                &mut tag,
+            )
+            .expect("tag is much shorter than the max output length of HKDF");
+        tag
+    }
+
+    /// Generate a Retry token for the `client`, whose first Initial packet is sent to the
+    /// `origin_dcid`.
+    pub fn gen_token(&self, client: &RealAddr, origin_dcid: &ConnectionId) -> Vec<u8> {
+        let issued = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+            .to_be_bytes();
+        let mut token = Vec::with_capacity(1 + 8 + 1 + origin_dcid.len() + Self::TAG_SIZE);
+        token.put_u8(Self::TYPE);
+        token.put_slice(&issued);
+        token.put_u8(origin_dcid.len() as u8);
+        token.put_slice(origin_dcid);
+        token.put_slice(&self.tag(client, &issued, origin_dcid));
+        token
+    }
+
+    /// Whether the `token` is shaped like a Retry token, regardless of whether it is valid.
+    ///
+    /// A client that is retried echoes the Retry token in its Initial packets, so a token that
+    /// fails [`validate`] but is a Retry token must not be responded with another Retry packet.
+    ///
+    /// [`validate`]: Self::validate
+    pub fn is_retry_token(token: &[u8]) -> bool {
+        if token.len() < 1 + 8 + 1 + Self::TAG_SIZE || token[0] != Self::TYPE {
+            return false;
+        }
+        let odcid_len = token[1 + 8] as usize;
+        odcid_len <= MAX_CID_SIZE && token.len() == 1 + 8 + 1 + odcid_len + Self::TAG_SIZE
+    }
+
+    /// Validate the Retry `token` sent by the `client`.
+    ///
+    /// Return the Original Destination Connection ID bound in the token, or `None` if the
+    /// token was not generated by this key for the `client`, or has expired.
+    pub fn validate(&self, client: &RealAddr, token: &[u8]) -> Option<ConnectionId> {
+        if !Self::is_retry_token(token) {
+            return None;
+        }
+        let (issued, rest) = token[1..].split_at(8);
+        let (odcid_len, rest) = (rest[0] as usize, &rest[1..]);
+        let (origin_dcid, tag) = rest.split_at(odcid_len);
+        let expected = self.tag(client, issued, origin_dcid);
+        let diff = expected
+            .iter()
+            .zip(tag)
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b));
+        if diff != 0 {
+            return None;
+        }
+
+        let issued = u64::from_be_bytes(issued.try_into().unwrap());
+        let issued = UNIX_EPOCH + Duration::from_secs(issued);
+        let age = SystemTime::now().duration_since(issued).ok()?;
+        (age <= Self::LIFETIME).then(|| ConnectionId::from_slice(origin_dcid))
+    }
+}
+
+impl fmt::Debug for RetryTokenKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("RetryTokenKey").field(&"...").finish()
+    }
 }
 
 pub fn be_reset_token(input: &[u8]) -> IResult<&[u8], ResetToken> {
@@ -135,6 +338,18 @@ pub mod handy {
 
 #[cfg(test)]
 mod tests {
+    use rustls::crypto::tls13::Hkdf;
+
+    /// The HKDF of the TLS 1.3 cipher suites of ring, to create the keys in tests.
+    fn hkdf() -> &'static dyn Hkdf {
+        rustls::crypto::ring::default_provider()
+            .cipher_suites
+            .iter()
+            .find_map(|suite| suite.tls13())
+            .unwrap()
+            .hkdf_provider
+    }
+
     #[test]
     fn test_create_token() {
         super::ResetToken::new(&[0; 16]);
@@ -176,4 +391,102 @@ mod tests {
         buf.put_reset_token(&token);
         assert_eq!(buf, &[0; 16]);
     }
+
+    #[test]
+    fn test_reset_token_matches() {
+        let token = super::ResetToken::new(&[7; 16]);
+        assert!(token.matches(&[7; 16]));
+        assert!(!token.matches(&[7; 15]));
+        let mut bytes = [7; 16];
+        bytes[15] = 0;
+        assert!(!token.matches(&bytes));
+    }
+
+    #[test]
+    fn test_stateless_reset_key() {
+        use super::StatelessResetKey;
+        use crate::cid::ConnectionId;
+
+        let hkdf = hkdf();
+        let cid = ConnectionId::from_slice(&[1, 2, 3, 4, 5, 6, 7, 8]);
+        let other = ConnectionId::from_slice(&[8, 7, 6, 5, 4, 3, 2, 1]);
+
+        let key = StatelessResetKey::new(hkdf, b"secret");
+        let restarted = StatelessResetKey::new(hkdf, b"secret");
+        assert_eq!(key.reset_token(&cid), restarted.reset_token(&cid));
+        assert_ne!(key.reset_token(&cid), key.reset_token(&other));
+
+        let another = StatelessResetKey::new(hkdf, b"another secret");
+        assert_ne!(key.reset_token(&cid), another.reset_token(&cid));
+        let random = StatelessResetKey::random(hkdf);
+        assert_ne!(key.reset_token(&cid), random.reset_token(&cid));
+    }
+
+    #[test]
+    fn test_retry_token_key() {
+        use super::RetryTokenKey;
+        use crate::{cid::ConnectionId, net::address::RealAddr};
+
+        let hkdf = hkdf();
+        let client: RealAddr = "127.0.0.1:4433".parse().unwrap();
+        let other: RealAddr = "127.0.0.2:4433".parse().unwrap();
+        let origin_dcid = ConnectionId::from_slice(&[1, 2, 3, 4, 5, 6, 7, 8]);
+
+        let key = RetryTokenKey::random(hkdf);
+        let token = key.gen_token(&client, &origin_dcid);
+        assert_eq!(key.validate(&client, &token), Some(origin_dcid));
+        assert_eq!(key.validate(&other, &token), None);
+        assert_eq!(RetryTokenKey::random(hkdf).validate(&client, &token), None);
+
+        let mut tampered = token.clone();
+        tampered[10] ^= 1;
+        assert_eq!(key.validate(&client, &tampered), None);
+        assert_eq!(key.validate(&client, &token[..token.len() - 1]), None);
+        assert_eq!(key.validate(&client, &[]), None);
+
+        // a token issued long ago has expired
+        let mut expired = token.clone();
+        expired[1..9].copy_from_slice(&0u64.to_be_bytes());
+        let tag = key.tag(&client, &expired[1..9], &origin_dcid);
+        let tag_offset = expired.len() - tag.len();
+        expired[tag_offset..].copy_from_slice(&tag);
+        assert_eq!(key.validate(&client, &expired), None);
+
+        assert!(RetryTokenKey::is_retry_token(&token));
+        assert!(RetryTokenKey::is_retry_token(&tampered));
+        assert!(!RetryTokenKey::is_retry_token(&token[1..]));
+        assert!(!RetryTokenKey::is_retry_token(&token[..token.len() - 1]));
+        assert!(!RetryTokenKey::is_retry_token(&[]));
+    }
+
+    #[test]
+    fn test_retry_token_replayed_from_other_address() {
+        use super::RetryTokenKey;
+        use crate::{cid::ConnectionId, net::address::RealAddr};
+
+        let hkdf = hkdf();
+        let key = RetryTokenKey::random(hkdf);
+        let client: RealAddr = "10.0.0.1:1234".parse().unwrap();
+        let origin_dcid = ConnectionId::from_slice(&[1, 2, 3, 4, 5, 6, 7, 8]);
+        let token = key.gen_token(&client, &origin_dcid);
+
+        // the address "10.0.0.1:123" is a prefix of the client address, the last byte of the
+        // client address is shifted into the issued time, and so on
+        let replayer: RealAddr = "10.0.0.1:123".parse().unwrap();
+        assert_eq!(key.validate(&replayer, &token), None);
+        let (issued, rest) = token[1..].split_at(8);
+        let (odcid, tag) = rest[1..].split_at(origin_dcid.len());
+        let mut shifted_issued = vec![b'4'];
+        shifted_issued.extend_from_slice(&issued[..7]);
+        let mut shifted_odcid = vec![issued[7]];
+        shifted_odcid.extend_from_slice(odcid);
+        assert_ne!(key.tag(&replayer, &shifted_issued, &shifted_odcid), tag);
+
+        let mut replayed = vec![token[0]];
+        replayed.extend_from_slice(&shifted_issued);
+        replayed.push(shifted_odcid.len() as u8);
+        replayed.extend_from_slice(&shifted_odcid);
+        replayed.extend_from_slice(tag);
+        assert_eq!(key.validate(&replayer, &replayed), None);
+    }
 }
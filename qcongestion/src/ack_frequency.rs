@@ -0,0 +1,304 @@
+use std::sync::{Arc, Mutex};
+
+use qbase::{
+    error::{Error, ErrorKind, QuicError},
+    frame::{AckFrequencyFrame, GetFrameType, ReceiveFrame, SendFrame},
+    varint::VarInt,
+};
+use tokio::time::{Duration, Instant};
+
+use crate::congestion::{ArcCC, PACKET_THRESHOLD};
+
+/// The acknowledgement behavior requested by the peer, see
+/// [QUIC Acknowledgment Frequency](https://datatracker.ietf.org/doc/html/draft-ietf-quic-ack-frequency).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct AckFrequency {
+    /// The number of ack-eliciting packets that can be received without acknowledging.
+    pub(crate) ack_eliciting_threshold: u64,
+    /// Overrides the local max_ack_delay.
+    pub(crate) max_ack_delay: Duration,
+    /// The number of out-of-order packets tolerated before acknowledging immediately,
+    /// 0 means never.
+    pub(crate) reordering_threshold: u64,
+}
+
+#[derive(Debug, Default)]
+struct RequestedAckFrequency {
+    // The min_ack_delay we advertised, the peer must not send ACK_FREQUENCY frames without it.
+    min_ack_delay: Option<Duration>,
+    largest_sequence: Option<u64>,
+    frequency: Option<AckFrequency>,
+}
+
+/// The acknowledgement behavior requested by the peer with ACK_FREQUENCY frames.
+///
+/// It is shared by the congestion controllers of all the paths, each of them follows
+/// the latest request to decide when to acknowledge the received 1-RTT packets.
+#[derive(Debug, Default, Clone)]
+pub struct ArcAckFrequency(Arc<Mutex<RequestedAckFrequency>>);
+
+impl ArcAckFrequency {
+    /// Create a new [`ArcAckFrequency`], with the min_ack_delay transport parameter
+    /// advertised to the peer, [`None`] if the extension is not enabled.
+    pub fn new(min_ack_delay: Option<Duration>) -> Self {
+        Self(Arc::new(Mutex::new(RequestedAckFrequency {
+            min_ack_delay,
+            ..Default::default()
+        })))
+    }
+
+    /// The latest acknowledgement behavior requested, [`None`] if nothing was requested.
+    pub(crate) fn get(&self) -> Option<AckFrequency> {
+        self.0.lock().unwrap().frequency
+    }
+}
+
+impl ReceiveFrame<AckFrequencyFrame> for ArcAckFrequency {
+    type Output = ();
+
+    fn recv_frame(&self, frame: &AckFrequencyFrame) -> Result<Self::Output, Error> {
+        let mut requested = self.0.lock().unwrap();
+        let Some(min_ack_delay) = requested.min_ack_delay else {
+            tracing::error!("   Cause by: received an unexpected AckFrequencyFrame");
+            return Err(QuicError::new(
+                ErrorKind::ProtocolViolation,
+                frame.frame_type().into(),
+                "AckFrequencyFrame received without min_ack_delay advertised",
+            )
+            .into());
+        };
+        if frame.request_max_ack_delay() < min_ack_delay {
+            tracing::error!("   Cause by: received an invalid AckFrequencyFrame");
+            return Err(QuicError::new(
+                ErrorKind::ProtocolViolation,
+                frame.frame_type().into(),
+                format!(
+                    "Request Max Ack Delay({:?}) in AckFrequencyFrame is less than min_ack_delay({min_ack_delay:?})",
+                    frame.request_max_ack_delay()
+                ),
+            )
+            .into());
+        }
+
+        // Frames with a sequence number not larger than the largest one processed are stale,
+        // they may be retransmitted or reordered.
+        if requested
+            .largest_sequence
+            .is_some_and(|largest| frame.sequence() <= largest)
+        {
+            return Ok(());
+        }
+        requested.largest_sequence = Some(frame.sequence());
+        requested.frequency = Some(AckFrequency {
+            ack_eliciting_threshold: frame.ack_eliciting_threshold(),
+            max_ack_delay: frame.request_max_ack_delay(),
+            reordering_threshold: frame.reordering_threshold(),
+        });
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct Requester {
+    // The min_ack_delay and max_ack_delay of the peer, known once the peer's
+    // transport parameters are received and only if the peer supports the extension.
+    peer_ack_delays: Option<(Duration, Duration)>,
+    next_sequence: u64,
+    ack_eliciting_threshold: u64,
+    last_request_time: Option<Instant>,
+}
+
+/// Asks the peer to acknowledge less frequently with ACK_FREQUENCY frames, following the
+/// [`Control::ack_eliciting_threshold`] of the congestion controller.
+///
+/// [`Control::ack_eliciting_threshold`]: crate::Control::ack_eliciting_threshold
+#[derive(Debug)]
+pub struct AckFrequencyRequester<TX> {
+    requester: Mutex<Requester>,
+    tx: TX,
+}
+
+impl<TX> AckFrequencyRequester<TX>
+where
+    TX: SendFrame<AckFrequencyFrame>,
+{
+    /// Create a new [`AckFrequencyRequester`], the ACK_FREQUENCY frames are sent through `tx`.
+    pub fn new(tx: TX) -> Self {
+        Self {
+            requester: Mutex::new(Requester {
+                peer_ack_delays: None,
+                next_sequence: 0,
+                // the default of RFC 9000, acknowledge every second ack-eliciting packet
+                ack_eliciting_threshold: 1,
+                last_request_time: None,
+            }),
+            tx,
+        }
+    }
+
+    /// Called with the min_ack_delay and max_ack_delay transport parameters of the peer,
+    /// nothing is requested if the peer did not advertise min_ack_delay.
+    pub fn on_peer_params(&self, min_ack_delay: Option<Duration>, max_ack_delay: Duration) {
+        self.requester.lock().unwrap().peer_ack_delays =
+            min_ack_delay.map(|min_ack_delay| (min_ack_delay, max_ack_delay));
+    }
+
+    /// Whether the peer supports the extension, so that IMMEDIATE_ACK frames can be sent.
+    pub fn is_peer_supported(&self) -> bool {
+        self.requester.lock().unwrap().peer_ack_delays.is_some()
+    }
+
+    /// Called when an ACK frame is received on the path of the congestion controller `cc`,
+    /// requests the peer to follow the threshold the controller wants if it changed.
+    pub fn on_ack_rcvd(&self, cc: &ArcCC) {
+        self.request(
+            cc.ack_eliciting_threshold(),
+            cc.smoothed_rtt(),
+            Instant::now(),
+        );
+    }
+
+    fn request(&self, threshold: u64, srtt: Duration, now: Instant) {
+        let mut requester = self.requester.lock().unwrap();
+        let Some((min_ack_delay, max_ack_delay)) = requester.peer_ack_delays else {
+            return;
+        };
+        if threshold == requester.ack_eliciting_threshold {
+            return;
+        }
+        // Request at most once per round trip, the controller needs the acknowledgements
+        // following the previous request to settle down.
+        if requester
+            .last_request_time
+            .is_some_and(|time| now < time + srtt)
+        {
+            return;
+        }
+
+        // A quarter of the RTT bounds the delay of the feedback, while never exceeding the
+        // peer's max_ack_delay so that the PTO computed from it still holds.
+        let request_max_ack_delay = (srtt / 4).min(max_ack_delay).max(min_ack_delay);
+        let frame = AckFrequencyFrame::new(
+            VarInt::from_u64(requester.next_sequence).expect("sequence overflow"),
+            VarInt::from_u64(threshold).unwrap_or(VarInt::MAX),
+            VarInt::from_u128(request_max_ack_delay.as_micros()).unwrap_or(VarInt::MAX),
+            VarInt::from_u32(PACKET_THRESHOLD as u32),
+        );
+        requester.next_sequence += 1;
+        requester.ack_eliciting_threshold = threshold;
+        requester.last_request_time = Some(now);
+        self.tx.send_frame([frame]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Default, Clone)]
+    struct AckFrequencyFrameTx(Arc<Mutex<Vec<AckFrequencyFrame>>>);
+
+    impl AckFrequencyFrameTx {
+        fn take(&self) -> Vec<AckFrequencyFrame> {
+            core::mem::take(&mut self.0.lock().unwrap())
+        }
+    }
+
+    impl SendFrame<AckFrequencyFrame> for AckFrequencyFrameTx {
+        fn send_frame<I: IntoIterator<Item = AckFrequencyFrame>>(&self, iter: I) {
+            self.0.lock().unwrap().extend(iter);
+        }
+    }
+
+    fn frame(sequence: u32, threshold: u32, delay_micros: u32) -> AckFrequencyFrame {
+        AckFrequencyFrame::new(
+            VarInt::from_u32(sequence),
+            VarInt::from_u32(threshold),
+            VarInt::from_u32(delay_micros),
+            VarInt::from_u32(3),
+        )
+    }
+
+    #[test]
+    fn test_recv_ack_frequency_frame() {
+        let ack_frequency = ArcAckFrequency::new(Some(Duration::from_millis(1)));
+        assert_eq!(ack_frequency.get(), None);
+
+        ack_frequency.recv_frame(&frame(1, 10, 20_000)).unwrap();
+        let expected = Some(AckFrequency {
+            ack_eliciting_threshold: 10,
+            max_ack_delay: Duration::from_millis(20),
+            reordering_threshold: 3,
+        });
+        assert_eq!(ack_frequency.get(), expected);
+
+        // stale frames are ignored
+        ack_frequency.recv_frame(&frame(0, 4, 20_000)).unwrap();
+        ack_frequency.recv_frame(&frame(1, 4, 20_000)).unwrap();
+        assert_eq!(ack_frequency.get(), expected);
+
+        ack_frequency.recv_frame(&frame(2, 4, 20_000)).unwrap();
+        assert_eq!(ack_frequency.get().unwrap().ack_eliciting_threshold, 4);
+    }
+
+    #[test]
+    fn test_recv_invalid_ack_frequency_frame() {
+        let unsupported = ArcAckFrequency::new(None);
+        assert!(unsupported.recv_frame(&frame(0, 10, 20_000)).is_err());
+
+        let ack_frequency = ArcAckFrequency::new(Some(Duration::from_millis(1)));
+        assert!(ack_frequency.recv_frame(&frame(0, 10, 500)).is_err());
+        assert_eq!(ack_frequency.get(), None);
+    }
+
+    #[test]
+    fn test_request_ack_frequency() {
+        let tx = AckFrequencyFrameTx::default();
+        let requester = AckFrequencyRequester::new(tx.clone());
+        let srtt = Duration::from_millis(40);
+        let now = Instant::now();
+
+        // nothing is requested before the peer advertises min_ack_delay
+        requester.request(4, srtt, now);
+        assert!(tx.take().is_empty());
+        requester.on_peer_params(None, Duration::from_millis(25));
+        requester.request(4, srtt, now);
+        assert!(tx.take().is_empty());
+        assert!(!requester.is_peer_supported());
+
+        requester.on_peer_params(Some(Duration::from_millis(1)), Duration::from_millis(25));
+        assert!(requester.is_peer_supported());
+        // the default threshold needs no request
+        requester.request(1, srtt, now);
+        assert!(tx.take().is_empty());
+
+        requester.request(4, srtt, now);
+        let [frame] = tx.take()[..] else {
+            panic!("one ACK_FREQUENCY frame is sent")
+        };
+        assert_eq!(frame.sequence(), 0);
+        assert_eq!(frame.ack_eliciting_threshold(), 4);
+        // a quarter of the RTT, within the peer's max_ack_delay
+        assert_eq!(frame.request_max_ack_delay(), Duration::from_millis(10));
+        assert_eq!(frame.reordering_threshold(), PACKET_THRESHOLD as u64);
+
+        // at most once per round trip
+        requester.request(8, srtt, now + srtt / 2);
+        assert!(tx.take().is_empty());
+        requester.request(8, srtt, now + srtt);
+        let [frame] = tx.take()[..] else {
+            panic!("one ACK_FREQUENCY frame is sent")
+        };
+        assert_eq!(frame.sequence(), 1);
+        assert_eq!(frame.ack_eliciting_threshold(), 8);
+
+        // never exceeding the peer's max_ack_delay
+        let srtt = Duration::from_millis(200);
+        requester.request(16, srtt, now + Duration::from_secs(1));
+        let [frame] = tx.take()[..] else {
+            panic!("one ACK_FREQUENCY frame is sent")
+        };
+        assert_eq!(frame.sequence(), 2);
+        assert_eq!(frame.request_max_ack_delay(), Duration::from_millis(25));
+    }
+}
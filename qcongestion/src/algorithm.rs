@@ -1,33 +1,110 @@
 use qbase::{Epoch, frame::AckFrame};
-use tokio::time::Instant;
+use tokio::time::{Duration, Instant};
 
-use crate::packets::SentPacket;
+use crate::{
+    algorithm::{bbr::Bbr, cubic::Cubic, new_reno::NewReno},
+    packets::SentPacket,
+    rtt::ArcRtt,
+    status::Pmtu,
+};
 
-// pub(crate) mod bbr;
+pub(crate) mod bbr;
+pub(crate) mod cubic;
 pub(crate) mod new_reno;
 
 /// The [`Algorithm`] enum represents different congestion control algorithms that can be used.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub enum Algorithm {
+    /// BBR v1, see [draft-cardwell-iccrg-bbr-congestion-control](https://datatracker.ietf.org/doc/html/draft-cardwell-iccrg-bbr-congestion-control-00).
     Bbr,
+    /// CUBIC with HyStart++, see [RFC 9438](https://www.rfc-editor.org/rfc/rfc9438.html)
+    /// and [RFC 9406](https://www.rfc-editor.org/rfc/rfc9406.html).
+    #[default]
+    Cubic,
+    /// NewReno, see [RFC 9002 Appendix B](https://www.rfc-editor.org/rfc/rfc9002.html#name-congestion-control-pseudoco).
     NewReno,
 }
 
+/// The congestion control algorithm of a path.
+///
+/// Each path owns its own controller, created by a [`ProductCongestionController`].
+/// The loss detection and the pacing are done by the caller, the controller only needs to
+/// maintain the congestion window, and optionally a pacing rate.
 pub trait Control: Send {
-    fn on_packet_sent_cc(&mut self, packet: &SentPacket);
+    /// Called when a packet is sent, the packet is the one that will be passed to
+    /// [`Control::on_packet_acked`] or [`Control::on_packets_lost`] later.
+    fn on_packet_sent_cc(&mut self, packet: &mut SentPacket);
 
+    /// Called for each packet newly acknowledged by an ACK frame.
     fn on_packet_acked(&mut self, acked_packet: &SentPacket);
 
+    /// Called when packets are declared lost, `persistent_lost` indicates that the loss of
+    /// these packets establishes persistent congestion.
     fn on_packets_lost(
         &mut self,
         lost_packets: &mut dyn Iterator<Item = &SentPacket>,
         persistent_lost: bool,
     );
 
+    /// Called once per ACK frame, after all newly acknowledged packets have been passed to
+    /// [`Control::on_packet_acked`] and the loss detection triggered by this ACK has finished.
+    ///
+    /// `rtt_sample` is the RTT sample taken from this ACK, adjusted for the acknowledgment delay,
+    /// or [`None`] if this ACK does not newly acknowledge its largest ack-eliciting packet.
+    fn on_ack_processed(&mut self, _rtt_sample: Option<Duration>) {}
+
+    /// Called with every ACK frame that carries ECN counts, `sent_time` is the time the
+    /// largest acknowledged packet was sent.
     fn process_ecn(&mut self, ack: &AckFrame, sent_time: &Instant, epoch: Epoch);
 
+    /// The congestion window in bytes.
     fn congestion_window(&self) -> usize;
 
+    /// The pacing rate in bytes per second, the pacer derives it from the congestion window
+    /// and the smoothed RTT if [`None`] is returned.
     fn pacing_rate(&self) -> Option<usize>;
 
+    /// The number of ack-eliciting packets the peer may receive before acknowledging them,
+    /// requested with ACK_FREQUENCY frames if the peer supports it.
+    ///
+    /// Defaults to 1, acknowledging every second ack-eliciting packet as RFC 9000 does.
+    fn ack_eliciting_threshold(&self) -> u64 {
+        1
+    }
+
+    /// Called when the packets are discarded along with their packet number space, they will
+    /// be neither acknowledged nor declared lost.
     fn remove_from_bytes_in_flight(&mut self, packets: &mut dyn Iterator<Item = &SentPacket>);
 }
+
+/// The factory to create a [`Control`] for each new path.
+///
+/// The controller can read the RTT estimation and the PMTU of the path, through the given
+/// [`ArcRtt`] and [`Pmtu`].
+///
+/// The built-in algorithms are available through [`Algorithm`], and any
+/// `Fn(ArcRtt, Pmtu) -> impl Control` can be used as the factory as well.
+pub trait ProductCongestionController: Send + Sync {
+    fn init(&self, rtt: ArcRtt, pmtu: Pmtu) -> Box<dyn Control>;
+}
+
+impl<F, C> ProductCongestionController for F
+where
+    F: Fn(ArcRtt, Pmtu) -> C + Send + Sync,
+    C: Control + 'static,
+{
+    #[inline]
+    fn init(&self, rtt: ArcRtt, pmtu: Pmtu) -> Box<dyn Control> {
+        Box::new((self)(rtt, pmtu))
+    }
+}
+
+impl ProductCongestionController for Algorithm {
+    fn init(&self, rtt: ArcRtt, pmtu: Pmtu) -> Box<dyn Control> {
+        match self {
+            Algorithm::Bbr => Box::new(Bbr::new(pmtu.0)),
+            Algorithm::Cubic => Box::new(Cubic::new(pmtu.0, rtt)),
+            Algorithm::NewReno => Box::new(NewReno::new(pmtu.0)),
+        }
+    }
+}
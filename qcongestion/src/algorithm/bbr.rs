@@ -1,14 +1,16 @@
-use std::{
-    collections::VecDeque,
-    time::{Duration, Instant},
+use std::sync::{
+    Arc,
+    atomic::{AtomicU16, Ordering},
 };
 
 use delivery_rate::Rate;
 use min_max::MinMax;
+use qbase::{Epoch, frame::AckFrame};
 use qevent::quic::recovery::RecoveryMetricsUpdated;
+use tokio::time::{Duration, Instant};
 
 use super::Control;
-use crate::packets::AckedPackets;
+use crate::packets::{SentPacket, State};
 
 mod delivery_rate;
 mod min_max;
@@ -16,7 +18,6 @@ pub(crate) mod model;
 pub(crate) mod parameters;
 pub(crate) mod state;
 
-const MSS: usize = 1200;
 // RTpropFilterLen: A constant specifying the length of the RTProp min
 // filter window, RTpropFilterLen is `10` secs.
 const RTPROP_FILTER_LEN: Duration = Duration::from_secs(10);
@@ -34,14 +35,17 @@ const PROBE_RTT_DURATION: Duration = Duration::from_millis(200);
 // Pacing rate threshold for select different send quantum. Default `1.2Mbps`.
 const SEND_QUANTUM_THRESHOLD_PACING_RATE: u64 = 1_200_000 / 8;
 
-// Initial congestion window in bytes.
-pub(crate) const INITIAL_CWND: u64 = 80 * MSS as u64;
+// Initial congestion window in packets.
+const INITIAL_CWND_PKTS: usize = 10;
 
 // The minimal cwnd value BBR tries to target using: 4 packets, or 4 * SMSS
 const MIN_PIPE_CWND_PKTS: usize = 4;
 
 const MINIMUM_WINDOW_PACKETS: usize = 2;
 
+// The upper bound of the ack-eliciting threshold requested from the peer, in packets.
+const MAX_ACK_ELICITING_THRESHOLD: u64 = 10;
+
 // BBR State
 //
 // https://datatracker.ietf.org/doc/html/draft-cardwell-iccrg-bbr-congestion-control-00#section-3.4
@@ -54,6 +58,7 @@ enum BbrStateMachine {
 }
 
 pub(crate) struct Bbr {
+    max_datagram_size: Arc<AtomicU16>,
     // StateMachine
     state: BbrStateMachine,
     // BBR.pacing_rate: The current pacing rate for a BBR flow, which
@@ -124,24 +129,21 @@ pub(crate) struct Bbr {
     in_recovery: bool,
     // Time of the last recovery event starts.
     recovery_epoch_start: Option<Instant>,
-    // Ack time.
-    ack_time: Instant,
     // Newly marked lost data size in bytes.
     newly_lost_bytes: u64,
     // lost data size in total bytes.
     bytes_lost_in_total: u64,
     // Newly acked data size in bytes.
     newly_acked_bytes: u64,
-    // The last P.delivered in bytes.
-    packet_delivered: u64,
     // The last P.sent_time to determine whether exit recovery.
-    last_ack_packet_sent_time: Instant,
+    last_ack_packet_sent_time: Option<Instant>,
     // The amount of data that was in flight before processing this ACK.
     prior_bytes_in_flight: u64,
     // The sum of the size in bytes of all sent packets that contain at least
     // one ack-eliciting or PADDING frame and have not been acknowledged or
     // declared lost. The size does not include IP or UDP overhead.
-    pub bytes_in_flight: u64,
+    bytes_in_flight: u64,
+    ecn_ce_counters: [u64; Epoch::count()],
 }
 
 impl From<&Bbr> for RecoveryMetricsUpdated {
@@ -149,27 +151,22 @@ impl From<&Bbr> for RecoveryMetricsUpdated {
         qevent::build!(RecoveryMetricsUpdated {
             congestion_window: value.cwnd,
             bytes_in_flight: value.bytes_in_flight,
-            pacing_rate: value.pacing_rate,
-            custom_fields: Map {
-                // AI补全
-                delivery_rate: value.delivery_rate.sample_delivery_rate(),
-                packet_delivered: value.packet_delivered,
-                newly_acked_bytes: value.newly_acked_bytes,
-                newly_lost_bytes: value.newly_lost_bytes,
-                bytes_lost_in_total: value.bytes_lost_in_total,
-            }
+            pacing_rate: value.pacing_rate * 8,
         })
     }
 }
 
 impl Bbr {
-    pub fn new() -> Self {
+    pub(crate) fn new(max_datagram_size: Arc<AtomicU16>) -> Self {
         let now = Instant::now();
+        let initial_cwnd =
+            (INITIAL_CWND_PKTS * max_datagram_size.load(Ordering::Relaxed) as usize) as u64;
         let mut bbr = Bbr {
+            max_datagram_size,
             state: BbrStateMachine::Startup,
             pacing_rate: 0,
             send_quantum: 0,
-            cwnd: INITIAL_CWND,
+            cwnd: initial_cwnd,
             btlbw: 0,
             btlbwfilter: MinMax::default(),
             delivery_rate: Rate::default(),
@@ -194,43 +191,140 @@ impl Bbr {
             target_cwnd: 0,
             in_recovery: false,
             recovery_epoch_start: None,
-            ack_time: now,
             newly_lost_bytes: 0,
             newly_acked_bytes: 0,
-            last_ack_packet_sent_time: now,
+            last_ack_packet_sent_time: None,
             prior_bytes_in_flight: 0,
-            packet_delivered: 0,
             bytes_in_flight: 0,
             bytes_lost_in_total: 0,
+            ecn_ce_counters: [0; Epoch::count()],
         };
         bbr.on_connection_init();
         bbr
     }
+
+    fn mss(&self) -> u64 {
+        self.max_datagram_size.load(Ordering::Relaxed) as u64
+    }
+
+    fn initial_cwnd(&self) -> u64 {
+        INITIAL_CWND_PKTS as u64 * self.mss()
+    }
 }
 
 impl Control for Bbr {
-    fn on_packet_sent_cc(&mut self, packet: &crate::packets::SentPacket) {
-        todo!()
+    fn on_packet_sent_cc(&mut self, packet: &mut SentPacket) {
+        if !packet.count_for_cc {
+            return;
+        }
+        self.on_transmit();
+        self.delivery_rate.on_packet_sent(
+            packet,
+            self.bytes_in_flight as usize,
+            self.bytes_lost_in_total,
+        );
+        self.bytes_in_flight += packet.sent_bytes as u64;
     }
 
-    fn on_packet_acked(&mut self, acked_packet: &crate::packets::SentPacket) {
-        todo!()
+    fn on_packet_acked(&mut self, acked_packet: &SentPacket) {
+        if !acked_packet.count_for_cc {
+            return;
+        }
+        // 如果不是 inflight 状态，说明已经判定丢包，bytes_in_flight 已经减去
+        if acked_packet.state == State::Inflight {
+            self.bytes_in_flight = self
+                .bytes_in_flight
+                .saturating_sub(acked_packet.sent_bytes as u64);
+        }
+        self.delivery_rate
+            .update_rate_sample(acked_packet, Instant::now());
+        self.newly_acked_bytes += acked_packet.sent_bytes as u64;
+        self.last_ack_packet_sent_time = self
+            .last_ack_packet_sent_time
+            .max(Some(acked_packet.time_sent));
+    }
+
+    fn on_packets_lost(
+        &mut self,
+        lost_packets: &mut dyn Iterator<Item = &SentPacket>,
+        persistent_lost: bool,
+    ) {
+        let mut sent_time_last_loss: Option<Instant> = None;
+        for lost_packet in lost_packets.filter(|p| p.count_for_cc) {
+            let lost_bytes = lost_packet.sent_bytes as u64;
+            self.bytes_in_flight = self.bytes_in_flight.saturating_sub(lost_bytes);
+            self.newly_lost_bytes += lost_bytes;
+            self.bytes_lost_in_total += lost_bytes;
+            sent_time_last_loss = sent_time_last_loss.max(Some(lost_packet.time_sent));
+        }
+        if let Some(sent_time) = sent_time_last_loss {
+            self.on_congestion_event(sent_time);
+        }
+
+        // 4.2.3.4: Upon retransmission timeout, which is persistent congestion in QUIC
+        if persistent_lost {
+            self.save_cwnd();
+            self.cwnd = MINIMUM_WINDOW_PACKETS as u64 * self.mss();
+        }
     }
 
-    fn on_packets_lost(&mut self, lost_packets: &[crate::packets::SentPacket]) {
-        todo!()
+    fn on_ack_processed(&mut self, _rtt_sample: Option<Duration>) {
+        self.prior_bytes_in_flight =
+            self.bytes_in_flight + self.newly_acked_bytes + self.newly_lost_bytes;
+        self.delivery_rate.generate_rate_sample();
+
+        if self.in_recovery
+            && self
+                .last_ack_packet_sent_time
+                .is_some_and(|sent_time| !self.in_recovery_period(sent_time))
+        {
+            self.exit_recovery();
+        }
+
+        self.update_model_and_state();
+        self.update_control_parameters();
+        qevent::event!({ RecoveryMetricsUpdated::from(&*self) });
+
+        self.newly_acked_bytes = 0;
+        self.newly_lost_bytes = 0;
+        self.last_ack_packet_sent_time = None;
     }
 
-    fn on_congestion_event(&mut self, sent_time: &Instant) {
-        todo!()
+    fn process_ecn(&mut self, ack: &AckFrame, sent_time: &Instant, epoch: Epoch) {
+        if let Some(ecn) = ack.ecn() {
+            if ecn.ce() > self.ecn_ce_counters[epoch] {
+                self.ecn_ce_counters[epoch] = ecn.ce();
+                self.on_congestion_event(*sent_time);
+            }
+        }
     }
 
     fn congestion_window(&self) -> usize {
-        todo!()
+        self.cwnd as usize
     }
 
     fn pacing_rate(&self) -> Option<usize> {
-        todo!()
+        Some(self.pacing_rate as usize)
+    }
+
+    // Once the pipe is filled, the bandwidth estimation no longer relies on frequent ACKs,
+    // acknowledging a quarter of the congestion window at a time cuts the ACK processing
+    // overhead on the high-bandwidth paths.
+    fn ack_eliciting_threshold(&self) -> u64 {
+        if !self.is_filled_pipe {
+            return 1;
+        }
+        (self.cwnd / self.mss() / 4).clamp(1, MAX_ACK_ELICITING_THRESHOLD)
+    }
+
+    fn remove_from_bytes_in_flight(&mut self, packets: &mut dyn Iterator<Item = &SentPacket>) {
+        for packet in packets {
+            if packet.count_for_cc && packet.state != State::Retransmitted {
+                self.bytes_in_flight = self
+                    .bytes_in_flight
+                    .saturating_sub(packet.sent_bytes as u64);
+            }
+        }
     }
 }
 
@@ -241,8 +335,8 @@ impl Bbr {
     }
 
     // 3.5.2.  Per-ACK Steps
-    fn update_model_and_state(&mut self, ack: &mut AckedPackets) {
-        self.update_btlbw(ack);
+    fn update_model_and_state(&mut self) {
+        self.update_btlbw();
         self.check_cycle_phase();
         self.check_full_pipe();
         self.check_drain();
@@ -260,114 +354,204 @@ impl Bbr {
     fn on_transmit(&mut self) {
         self.handle_restart_from_idle();
     }
+
+    // 4.2.3.4 Modulating cwnd in Loss Recovery
+    fn in_recovery_period(&self, sent_time: Instant) -> bool {
+        self.recovery_epoch_start
+            .is_some_and(|recovery_start_time| sent_time <= recovery_start_time)
+    }
+
+    fn on_congestion_event(&mut self, sent_time: Instant) {
+        if self.in_recovery_period(sent_time) {
+            return;
+        }
+        self.recovery_epoch_start = Some(Instant::now());
+        if !self.in_recovery {
+            self.enter_recovery();
+        }
+    }
+
+    // Upon entering Fast Recovery, set cwnd to the number of packets still in flight
+    // (allowing at least one for a fast retransmit), and use packet conservation for
+    // one round trip.
+    fn enter_recovery(&mut self) {
+        self.save_cwnd();
+        self.cwnd = self.bytes_in_flight + self.newly_acked_bytes.max(self.mss());
+        self.packet_conservation = true;
+        self.in_recovery = true;
+        // Packet conservation ends after a packet-timed round trip.
+        self.next_round_delivered = self.delivery_rate.delivered();
+    }
+
+    // Upon exiting loss recovery (RTO recovery or Fast Recovery), either by repairing
+    // all losses or undoing recovery, restore the best-known cwnd value.
+    fn exit_recovery(&mut self) {
+        self.recovery_epoch_start = None;
+        self.packet_conservation = false;
+        self.in_recovery = false;
+        self.restore_cwnd();
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::{
-        collections::VecDeque,
-        time::{Duration, Instant},
-    };
-
-    use crate::{
-        algorithm::bbr::{BbrStateMachine, HIGH_GAIN, INITIAL_CWND, MSS},
-        packets::{AckedPackets, SentPacket},
-        rtt::INITIAL_RTT,
-    };
-
-    #[test]
-    fn test_bbr_init() {
-        let mut bbr = super::Bbr::new();
-        bbr.init();
+    use super::*;
+    use crate::rtt::INITIAL_RTT;
+
+    pub(super) const MSS: usize = 1200;
+
+    pub(super) fn new_bbr() -> Bbr {
+        Bbr::new(Arc::new(AtomicU16::new(MSS as u16)))
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_bbr_init() {
+        let bbr = new_bbr();
         assert_eq!(bbr.state, BbrStateMachine::Startup);
         assert_eq!(bbr.pacing_gain, HIGH_GAIN);
         assert_eq!(bbr.cwnd_gain, HIGH_GAIN);
         assert_eq!(bbr.cycle_index, 0);
-        assert_eq!(bbr.cwnd, INITIAL_CWND);
+        assert_eq!(bbr.cwnd, bbr.initial_cwnd());
         assert_eq!(bbr.bytes_in_flight, 0);
         assert_eq!(
             bbr.pacing_rate,
-            (bbr.pacing_gain * INITIAL_CWND as f64 / INITIAL_RTT.as_secs_f64()) as u64
+            (bbr.pacing_gain * bbr.initial_cwnd() as f64 / INITIAL_RTT.as_secs_f64()) as u64
         );
+        assert_eq!(bbr.pacing_rate(), Some(bbr.pacing_rate as usize));
     }
 
-    #[test]
-    fn test_bbr_sent() {
-        let mut bbr = super::Bbr::new();
-        for _ in 0..10 {
-            let mut sent = SentPacket {
-                sent_bytes: MSS,
-                ..Default::default()
-            };
-            bbr.on_sent(&mut sent, MSS);
+    #[tokio::test(start_paused = true)]
+    async fn test_bbr_sent() {
+        let mut bbr = new_bbr();
+        for pn in 0..10 {
+            let mut sent = SentPacket::new(pn, Instant::now(), true, true, MSS);
+            bbr.on_packet_sent_cc(&mut sent);
         }
         assert_eq!(bbr.bytes_in_flight, 10 * MSS as u64);
     }
 
-    #[test]
-    fn test_bbr_ack() {
-        let mut bbr = super::Bbr::new();
-        let mut now = Instant::now();
+    #[tokio::test(start_paused = true)]
+    async fn test_bbr_ack() {
+        let mut bbr = new_bbr();
         let rtt = Duration::from_millis(100);
 
-        simulate_round_trip(&mut bbr, now, rtt, 0, 10, MSS);
+        simulate_round_trip(&mut bbr, rtt, 0, 10, MSS).await;
         assert_eq!(bbr.bytes_in_flight, 0);
         assert_eq!(bbr.delivery_rate.delivered(), 10 * MSS);
+        // 10 packets delivered in 100ms
         assert_eq!(
             bbr.delivery_rate.sample_delivery_rate(),
             (10 * 10 * MSS) as u64
         );
-
-        now += Duration::from_secs(1);
-        // next roud
-        // generate btlbw
-        simulate_round_trip(&mut bbr, now, rtt, 10, 40, MSS);
-        assert_eq!(bbr.delivery_rate.delivered(), 40 * MSS);
-        assert_eq!(
-            bbr.delivery_rate.sample_delivery_rate(),
-            (30 * 10 * MSS) as u64
-        );
         assert_eq!(bbr.btlbw, (10 * 10 * MSS) as u64);
-        assert_eq!(
-            bbr.pacing_rate,
-            (bbr.pacing_gain * INITIAL_CWND as f64 / INITIAL_RTT.as_secs_f64()) as u64
-        );
+        assert_eq!(bbr.rtprop, rtt);
 
-        now += Duration::from_secs(1);
-        // update btlbw
-        simulate_round_trip(&mut bbr, now, rtt, 40, 60, MSS);
+        // next round, more packets are delivered in the same rtt
+        simulate_round_trip(&mut bbr, rtt, 10, 50, MSS).await;
+        assert_eq!(bbr.delivery_rate.delivered(), 50 * MSS);
         assert_eq!(
             bbr.delivery_rate.sample_delivery_rate(),
-            (20 * 10 * MSS) as u64
+            (40 * 10 * MSS) as u64
         );
-        assert_eq!(bbr.btlbw, (3 * 10 * 10 * MSS) as u64);
+        assert_eq!(bbr.btlbw, (40 * 10 * MSS) as u64);
         assert_eq!(bbr.pacing_rate, (bbr.btlbw as f64 * bbr.pacing_gain) as u64);
     }
 
-    pub(super) fn simulate_round_trip(
-        bbr: &mut super::Bbr,
-        start_time: Instant,
+    #[tokio::test(start_paused = true)]
+    async fn test_bbr_recovery() {
+        let mut bbr = new_bbr();
+        let rtt = Duration::from_millis(100);
+        simulate_round_trip(&mut bbr, rtt, 0, 10, MSS).await;
+
+        let mut sents = (10..20)
+            .map(|pn| {
+                let mut sent = SentPacket::new(pn, Instant::now(), true, true, MSS);
+                bbr.on_packet_sent_cc(&mut sent);
+                sent
+            })
+            .collect::<Vec<_>>();
+        tokio::time::advance(rtt).await;
+
+        // 10 and 11 are lost
+        for sent in &mut sents[..2] {
+            sent.state = State::Retransmitted;
+        }
+        bbr.on_packets_lost(&mut sents[..2].iter(), false);
+        assert!(bbr.in_recovery);
+        assert!(bbr.packet_conservation);
+        assert_eq!(bbr.bytes_in_flight, 8 * MSS as u64);
+        assert_eq!(bbr.cwnd, 9 * MSS as u64);
+
+        for sent in &sents[2..] {
+            bbr.on_packet_acked(sent);
+        }
+        bbr.on_ack_processed(None);
+        // all acked packets were sent before the recovery started
+        assert!(bbr.in_recovery);
+        assert_eq!(bbr.bytes_in_flight, 0);
+
+        // a packet sent after the recovery started is acked
+        tokio::time::advance(Duration::from_millis(1)).await;
+        simulate_round_trip(&mut bbr, rtt, 20, 21, MSS).await;
+        assert!(!bbr.in_recovery);
+        assert!(!bbr.packet_conservation);
+        assert!(bbr.cwnd >= bbr.prior_cwnd);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_bbr_persistent_congestion() {
+        let mut bbr = new_bbr();
+        let mut sents = (0..3)
+            .map(|pn| {
+                let mut sent = SentPacket::new(pn, Instant::now(), true, true, MSS);
+                bbr.on_packet_sent_cc(&mut sent);
+                sent
+            })
+            .collect::<Vec<_>>();
+        for sent in &mut sents {
+            sent.state = State::Retransmitted;
+        }
+        bbr.on_packets_lost(&mut sents.iter(), true);
+        assert_eq!(bbr.bytes_in_flight, 0);
+        assert_eq!(bbr.cwnd, (MINIMUM_WINDOW_PACKETS * MSS) as u64);
+        assert_eq!(bbr.prior_cwnd, bbr.initial_cwnd());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_bbr_ack_eliciting_threshold() {
+        let mut bbr = new_bbr();
+        // every second packet is acknowledged while probing the bandwidth
+        assert_eq!(bbr.ack_eliciting_threshold(), 1);
+
+        bbr.is_filled_pipe = true;
+        bbr.cwnd = 12 * MSS as u64;
+        assert_eq!(bbr.ack_eliciting_threshold(), 3);
+        bbr.cwnd = 1000 * MSS as u64;
+        assert_eq!(bbr.ack_eliciting_threshold(), MAX_ACK_ELICITING_THRESHOLD);
+        bbr.cwnd = (MINIMUM_WINDOW_PACKETS * MSS) as u64;
+        assert_eq!(bbr.ack_eliciting_threshold(), 1);
+    }
+
+    /// Send packets `start..end` at once, and ack them all after `rtt`.
+    pub(super) async fn simulate_round_trip(
+        bbr: &mut Bbr,
         rtt: Duration,
-        start: usize,
-        end: usize,
+        start: u64,
+        end: u64,
         packet_size: usize,
     ) {
-        let mut acks = VecDeque::with_capacity(end - start);
-        for i in start..end {
-            let mut sent: SentPackets = SentPackets {
-                packet_number: i as u64,
-                sent_bytes: packet_size,
-                time_sent: start_time,
-                ..Default::default()
-            };
-            bbr.on_sent(&mut sent, 0);
-
-            let mut ack: AckedPackets = sent.into();
-            ack.rtt = rtt;
-            acks.push_back(ack);
+        let sents = (start..end)
+            .map(|pn| {
+                let mut sent = SentPacket::new(pn, Instant::now(), true, true, packet_size);
+                bbr.on_packet_sent_cc(&mut sent);
+                sent
+            })
+            .collect::<Vec<_>>();
+
+        tokio::time::advance(rtt).await;
+        for sent in &sents {
+            bbr.on_packet_acked(sent);
         }
-
-        // let ack_time = start_time + rtt;
-        bbr.on_ack(acks);
+        bbr.on_ack_processed(None);
     }
 }
@@ -1,8 +1,8 @@
 // https://tools.ietf.org/html/draft-cheng-iccrg-delivery-rate-estimation-01
 
-use std::time::{Duration, Instant};
+use tokio::time::{Duration, Instant};
 
-use crate::packets::{AckedPackets, SentPacket};
+use crate::packets::SentPacket;
 
 #[derive(Debug)]
 pub struct Rate {
@@ -21,7 +21,7 @@ pub struct Rate {
 
 impl Default for Rate {
     fn default() -> Self {
-        let now = tokio::time::Instant::now();
+        let now = Instant::now();
 
         Rate {
             delivered: 0,
@@ -60,8 +60,8 @@ impl Rate {
     }
 
     // Update the delivery rate sample when a packet is acked.
-    pub fn update_rate_sample(&mut self, pkt: &AckedPackets, now: Instant) {
-        self.delivered += pkt.size;
+    pub fn update_rate_sample(&mut self, pkt: &SentPacket, now: Instant) {
+        self.delivered += pkt.sent_bytes;
         self.delivered_time = now;
 
         if self.rate_sample.prior_time.is_none() || pkt.delivered > self.rate_sample.prior_delivered
@@ -71,7 +71,7 @@ impl Rate {
             self.rate_sample.is_app_limited = pkt.is_app_limited;
             self.rate_sample.send_elapsed =
                 pkt.time_sent.saturating_duration_since(pkt.first_sent_time);
-            self.rate_sample.rtt = pkt.rtt;
+            self.rate_sample.rtt = now.saturating_duration_since(pkt.time_sent);
             self.rate_sample.ack_elapsed = self
                 .delivered_time
                 .saturating_duration_since(pkt.delivered_time);
@@ -79,7 +79,7 @@ impl Rate {
             self.first_sent_time = pkt.time_sent;
         }
 
-        self.largest_acked = self.largest_acked.max(pkt.pn);
+        self.largest_acked = self.largest_acked.max(pkt.packet_number);
     }
 
     pub fn generate_rate_sample(&mut self) {
@@ -111,7 +111,7 @@ impl Rate {
         self.end_of_app_limited = if v { self.last_sent_packet.max(1) } else { 0 }
     }
 
-    pub fn app_limited(&mut self) -> bool {
+    pub fn app_limited(&self) -> bool {
         self.end_of_app_limited != 0
     }
 
@@ -119,6 +119,10 @@ impl Rate {
         self.delivered
     }
 
+    pub fn sample_prior_delivered(&self) -> usize {
+        self.rate_sample.prior_delivered
+    }
+
     pub fn sample_delivery_rate(&self) -> u64 {
         self.rate_sample.delivery_rate
     }
@@ -149,19 +153,14 @@ struct RateSample {
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_rate() {
+    #[tokio::test(start_paused = true)]
+    async fn test_rate() {
         let mut rate = Rate::default();
 
         let now = Instant::now();
 
         let mut sents: Vec<SentPacket> = (0..5)
-            .map(|i| SentPacket {
-                packet_number: i,
-                sent_bytes: 100,
-                time_sent: now,
-                ..Default::default()
-            })
+            .map(|pn| SentPacket::new(pn, now, true, true, 100))
             .collect();
 
         for sent in &mut sents {
@@ -170,13 +169,12 @@ mod tests {
         }
 
         let delay = Duration::from_millis(100);
-        let recv_ack_time = now + delay;
+        tokio::time::advance(delay).await;
+        let recv_ack_time = Instant::now();
 
         for _ in 0..3 {
             let sent = sents.pop().unwrap();
-            let mut acked: AckedPackets = sent.into();
-            acked.rtt = delay;
-            rate.update_rate_sample(&acked, recv_ack_time);
+            rate.update_rate_sample(&sent, recv_ack_time);
             rate.generate_rate_sample();
         }
         // 300 / 0.1
@@ -1,9 +1,8 @@
-use std::time::Instant;
-
 // 4.1.  Maintaining the Network Path Model
 // This model includes two estimated parameters: self.BtlBw, and self.RTprop.
+use tokio::time::Instant;
+
 use super::{Bbr, RTPROP_FILTER_LEN};
-use crate::packets::AckedPackets;
 
 impl Bbr {
     // 4.1.1.3.  Tracking Time for the self.BtlBw Max Filter
@@ -15,8 +14,9 @@ impl Bbr {
     }
 
     // Upon receiving an ACK for a given data packet:
-    fn update_round(&mut self, packet: &AckedPackets) {
-        if packet.delivered >= self.next_round_delivered {
+    fn update_round(&mut self) {
+        // packet.delivered of the most recently delivered packet in this ACK
+        if self.delivery_rate.sample_prior_delivered() >= self.next_round_delivered {
             self.next_round_delivered = self.delivery_rate.delivered();
             self.round_count += 1;
             self.is_round_start = true;
@@ -27,8 +27,8 @@ impl Bbr {
     }
 
     // 4.1.1.5.  Updating the BBR.BtlBw Max Filter
-    pub(super) fn update_btlbw(&mut self, packet: &AckedPackets) {
-        self.update_round(packet);
+    pub(super) fn update_btlbw(&mut self) {
+        self.update_round();
 
         if self.delivery_rate.sample_delivery_rate() >= self.btlbw
             || !self.delivery_rate.sample_is_app_limited()
@@ -42,7 +42,7 @@ impl Bbr {
     // 4.1.2.2.  BBR.RTprop Min Filter
     pub(super) fn update_rtprop(&mut self) {
         let sample_rtt = self.delivery_rate.sample_rtt();
-        let now = tokio::time::Instant::now();
+        let now = Instant::now();
         self.is_rtprop_expired =
             now.saturating_duration_since(self.rtprop_stamp) > RTPROP_FILTER_LEN;
 
@@ -2,10 +2,10 @@
 // BBR uses three distinct but interrelated control parameters: pacing rate,
 // send quantum, and congestion window (cwnd).
 
-use std::time::Duration;
+use tokio::time::Duration;
 
 use super::{
-    Bbr, BbrStateMachine, INITIAL_CWND, MIN_PIPE_CWND_PKTS, MINIMUM_WINDOW_PACKETS, MSS,
+    Bbr, BbrStateMachine, MIN_PIPE_CWND_PKTS, MINIMUM_WINDOW_PACKETS,
     SEND_QUANTUM_THRESHOLD_PACING_RATE,
 };
 use crate::rtt::INITIAL_RTT;
@@ -14,7 +14,7 @@ impl Bbr {
     // 4.2.1.  Pacing Rate
     pub(super) fn init_pacing_rate(&mut self) {
         let srtt = INITIAL_RTT;
-        let nominal_bandwidth = INITIAL_CWND as f64 / srtt.as_secs_f64();
+        let nominal_bandwidth = self.initial_cwnd() as f64 / srtt.as_secs_f64();
         self.pacing_rate = (self.pacing_gain * nominal_bandwidth) as u64;
     }
 
@@ -32,20 +32,20 @@ impl Bbr {
     // 4.2.2.  Send Quantum
     pub(super) fn set_send_quantum(&mut self) {
         let floor = if self.pacing_rate < SEND_QUANTUM_THRESHOLD_PACING_RATE {
-            MSS
+            self.mss()
         } else {
-            2 * MSS
+            2 * self.mss()
         };
 
         // BBR.send_quantum  = min(BBR.pacing_rate * 1ms, 64KBytes)
-        self.send_quantum = (self.pacing_rate / 1000).clamp(floor as u64, 64 * 1024);
+        self.send_quantum = (self.pacing_rate / 1000).clamp(floor, 64 * 1024);
     }
 
     // 4.2.3.  Congestion Window
     // 4.2.3.2.  Target cwnd
-    pub fn inflight(&self, gain: f64) -> u64 {
+    pub(super) fn inflight(&self, gain: f64) -> u64 {
         if self.rtprop == Duration::MAX {
-            return INITIAL_CWND;
+            return self.initial_cwnd();
         }
 
         let quanta = 3 * self.send_quantum;
@@ -66,7 +66,7 @@ impl Bbr {
         }
     }
 
-    pub fn restore_cwnd(&mut self) {
+    pub(super) fn restore_cwnd(&mut self) {
         self.cwnd = self.cwnd.max(self.prior_cwnd)
     }
 
@@ -75,7 +75,7 @@ impl Bbr {
             self.cwnd = self
                 .cwnd
                 .saturating_sub(self.newly_lost_bytes)
-                .max((MSS * MINIMUM_WINDOW_PACKETS) as u64);
+                .max(MINIMUM_WINDOW_PACKETS as u64 * self.mss());
         }
 
         if self.packet_conservation {
@@ -101,7 +101,7 @@ impl Bbr {
             if self.is_filled_pipe {
                 self.cwnd = self.target_cwnd.min(self.cwnd + self.newly_acked_bytes);
             } else if self.cwnd < self.target_cwnd
-                || self.delivery_rate.delivered() < INITIAL_CWND as usize
+                || (self.delivery_rate.delivered() as u64) < self.initial_cwnd()
             {
                 self.cwnd += self.newly_acked_bytes;
             }
@@ -113,7 +113,7 @@ impl Bbr {
 
     /// The minimal cwnd value BBR tries to target, in bytes
     pub(super) fn min_pipe_cwnd(&self) -> u64 {
-        (MIN_PIPE_CWND_PKTS * MSS) as u64
+        MIN_PIPE_CWND_PKTS as u64 * self.mss()
     }
 }
 
@@ -121,29 +121,30 @@ impl Bbr {
 mod tests {
 
     use super::*;
+    use crate::algorithm::bbr::tests::{MSS, new_bbr};
 
-    #[test]
-    fn test_init_pacing_rate() {
-        let mut bbr = Bbr::new();
+    #[tokio::test(start_paused = true)]
+    async fn test_init_pacing_rate() {
+        let mut bbr = new_bbr();
         bbr.init();
         assert_eq!(
             bbr.pacing_rate,
-            (bbr.pacing_gain * INITIAL_CWND as f64 / INITIAL_RTT.as_secs_f64()) as u64
+            (bbr.pacing_gain * bbr.initial_cwnd() as f64 / INITIAL_RTT.as_secs_f64()) as u64
         );
     }
 
-    #[test]
-    fn test_bbr_set_pacing_rate() {
-        let mut bbr = Bbr::new();
+    #[tokio::test(start_paused = true)]
+    async fn test_bbr_set_pacing_rate() {
+        let mut bbr = new_bbr();
         bbr.btlbw = 1000;
         bbr.is_filled_pipe = true;
         bbr.set_pacing_rate();
         assert_eq!(bbr.pacing_rate, (bbr.btlbw as f64 * bbr.pacing_gain) as u64);
     }
 
-    #[test]
-    fn test_bbr_set_send_quantum() {
-        let mut bbr = Bbr::new();
+    #[tokio::test(start_paused = true)]
+    async fn test_bbr_set_send_quantum() {
+        let mut bbr = new_bbr();
         bbr.pacing_rate = SEND_QUANTUM_THRESHOLD_PACING_RATE + 1;
 
         bbr.set_send_quantum();
@@ -162,9 +163,9 @@ mod tests {
         assert_eq!(bbr.send_quantum, 10000);
     }
 
-    #[test]
-    fn test_bbr_inflight() {
-        let mut bbr = Bbr::new();
+    #[tokio::test(start_paused = true)]
+    async fn test_bbr_inflight() {
+        let mut bbr = new_bbr();
         bbr.btlbw = 10_000_000;
         bbr.rtprop = Duration::from_millis(100);
         let bdp = bbr.inflight(1.0);
@@ -176,9 +177,9 @@ mod tests {
         assert_eq!(bdp, 1_000_000 + bbr.send_quantum * 3);
     }
 
-    #[test]
-    fn test_bbr_modulate_cwnd_for_recovery() {
-        let mut bbr = Bbr::new();
+    #[tokio::test(start_paused = true)]
+    async fn test_bbr_modulate_cwnd_for_recovery() {
+        let mut bbr = new_bbr();
 
         bbr.cwnd = 10000;
         bbr.packet_conservation = false;
@@ -197,9 +198,9 @@ mod tests {
         assert_eq!(bbr.cwnd, 10000);
     }
 
-    #[test]
-    fn test_modulate_cwnd_for_probe_rtt() {
-        let mut bbr = Bbr::new();
+    #[tokio::test(start_paused = true)]
+    async fn test_modulate_cwnd_for_probe_rtt() {
+        let mut bbr = new_bbr();
         bbr.cwnd = 10000;
         // min(4 * MSS, cwnd)
         bbr.state = BbrStateMachine::ProbeRTT;
@@ -208,9 +209,9 @@ mod tests {
         assert_eq!(bbr.cwnd, (4 * MSS) as u64);
     }
 
-    #[test]
-    fn test_bbr_set_cwnd() {
-        let mut bbr = Bbr::new();
+    #[tokio::test(start_paused = true)]
+    async fn test_bbr_set_cwnd() {
+        let mut bbr = new_bbr();
 
         bbr.bytes_in_flight = 1000;
         bbr.packet_conservation = false;
@@ -224,6 +225,6 @@ mod tests {
         // init cwnd < tartget_cwnd
         // when receive ack, adjust cwnd
         bbr.set_cwnd();
-        assert_eq!(bbr.cwnd, 100000);
+        assert_eq!(bbr.cwnd, (10 * MSS + 4000) as u64);
     }
 }
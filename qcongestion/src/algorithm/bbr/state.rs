@@ -1,9 +1,7 @@
-use std::time::Instant;
-
 use rand::Rng;
+use tokio::time::{Duration, Instant};
 
 use super::{Bbr, BbrStateMachine, HIGH_GAIN, PROBE_RTT_DURATION};
-use crate::rtt::INITIAL_RTT;
 
 // BBRGainCycleLen: the number of phases in the BBR ProbeBW gain cycle: 8.
 const GAIN_CYCLE_LEN: usize = 8;
@@ -13,8 +11,9 @@ const PACING_GAIN_CYCLE: [f64; GAIN_CYCLE_LEN] = [1.25, 0.75, 1.0, 1.0, 1.0, 1.0
 
 impl Bbr {
     pub(super) fn init(&mut self) {
-        self.rtprop = INITIAL_RTT;
-        self.rtprop_stamp = tokio::time::Instant::now();
+        // BBR.RTprop = SRTT ? SRTT : Inf, there is no rtt sample yet
+        self.rtprop = Duration::MAX;
+        self.rtprop_stamp = Instant::now();
         self.probe_rtt_done_stamp = None;
         self.probe_rtt_round_done = false;
         self.packet_conservation = false;
@@ -78,7 +77,7 @@ impl Bbr {
     }
 
     // 4.3.4.  ProbeBW
-    pub fn enter_probe_bw(&mut self) {
+    pub(super) fn enter_probe_bw(&mut self) {
         self.state = BbrStateMachine::ProbeBW;
         self.pacing_gain = 1.0;
         self.cwnd_gain = 2.0;
@@ -97,14 +96,14 @@ impl Bbr {
     }
 
     fn advance_cycle_phase(&mut self) {
-        self.cycle_stamp = tokio::time::Instant::now();
+        self.cycle_stamp = Instant::now();
         self.cycle_index = (self.cycle_index + 1) % GAIN_CYCLE_LEN;
         self.pacing_gain = PACING_GAIN_CYCLE[self.cycle_index];
     }
 
     // 是否要进入下一阶段
     fn is_next_cycle_phase(&mut self) -> bool {
-        let now = tokio::time::Instant::now();
+        let now = Instant::now();
         let is_full_length = now.saturating_duration_since(self.cycle_stamp) > self.rtprop;
 
         // pacing_gain == 1.0 持续 rtprop
@@ -163,7 +162,7 @@ impl Bbr {
         // C.app_limited = (BW.delivered + packets_in_flight) ? : 1
         self.delivery_rate.update_app_limited(true);
 
-        let now = tokio::time::Instant::now();
+        let now = Instant::now();
         if let Some(probe_rtt_done_stamp) = self.probe_rtt_done_stamp {
             if self.is_round_start {
                 self.probe_rtt_round_done = true;
@@ -193,88 +192,78 @@ impl Bbr {
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use crate::algorithm::bbr::tests::{MSS, new_bbr, simulate_round_trip};
 
-    use std::time::{Duration, Instant};
-
-    use crate::algorithm::bbr::{
-        BbrStateMachine, HIGH_GAIN, INITIAL_CWND, MSS, tests::simulate_round_trip,
-    };
-
-    #[test]
-    fn test_bbr_init() {
-        let mut bbr = super::Bbr::new();
+    #[tokio::test(start_paused = true)]
+    async fn test_bbr_init() {
+        let mut bbr = new_bbr();
         bbr.init();
         assert_eq!(bbr.state, BbrStateMachine::Startup);
         assert_eq!(bbr.pacing_gain, HIGH_GAIN);
         assert_eq!(bbr.cwnd_gain, HIGH_GAIN);
-        assert_eq!(bbr.cwnd, INITIAL_CWND);
+        assert_eq!(bbr.cwnd, bbr.initial_cwnd());
+        assert_eq!(bbr.rtprop, Duration::MAX);
     }
 
-    #[test]
-    fn test_bbr_enter_startup() {
-        let mut bbr = super::Bbr::new();
+    #[tokio::test(start_paused = true)]
+    async fn test_bbr_enter_startup() {
+        let mut bbr = new_bbr();
         bbr.enter_startup();
         assert_eq!(bbr.state, BbrStateMachine::Startup);
         assert_eq!(bbr.pacing_gain, HIGH_GAIN);
         assert_eq!(bbr.cwnd_gain, HIGH_GAIN);
     }
 
-    #[test]
-    fn test_bbr_check_full_pipe() {
-        let mut bbr = super::Bbr::new();
-
-        let mut now = tokio::time::Instant::now();
+    #[tokio::test(start_paused = true)]
+    async fn test_bbr_check_full_pipe() {
+        let mut bbr = new_bbr();
         let rtt = Duration::from_millis(100);
-        simulate_round_trip(&mut bbr, now, rtt, 0, 10, MSS);
-        now += Duration::from_secs(1);
-        simulate_round_trip(&mut bbr, now, rtt, 0, 10, MSS);
 
+        // the bandwidth does not grow in the following rounds
+        simulate_round_trip(&mut bbr, rtt, 0, 10, MSS).await;
         assert_eq!(bbr.btlbw, (10 * 10 * MSS) as u64);
-        bbr.check_full_pipe();
         assert!(!bbr.is_filled_pipe);
 
-        now += Duration::from_secs(1);
-        simulate_round_trip(&mut bbr, now, rtt, 0, 10, MSS);
+        simulate_round_trip(&mut bbr, rtt, 10, 20, MSS).await;
         assert_eq!(bbr.btlbw, (10 * 10 * MSS) as u64);
-
-        bbr.check_full_pipe();
         assert!(!bbr.is_filled_pipe);
 
-        now += Duration::from_secs(1);
-        simulate_round_trip(&mut bbr, now, rtt, 0, 10, MSS);
-
-        bbr.check_full_pipe();
+        simulate_round_trip(&mut bbr, rtt, 20, 30, MSS).await;
         assert!(bbr.is_filled_pipe);
+        // nothing in flight, drain is done immediately
+        assert_eq!(bbr.state, BbrStateMachine::ProbeBW);
     }
 
-    #[test]
-    fn test_bbr_check_drain() {
-        let mut bbr = super::Bbr::new();
+    #[tokio::test(start_paused = true)]
+    async fn test_bbr_check_drain() {
+        let mut bbr = new_bbr();
         bbr.init();
+        bbr.rtprop = Duration::from_millis(100);
         bbr.is_filled_pipe = true;
         bbr.bytes_in_flight = 100;
         bbr.check_drain();
         assert_eq!(bbr.state, BbrStateMachine::Drain);
 
-        let mut bbr = super::Bbr::new();
+        let mut bbr = new_bbr();
         bbr.init();
         bbr.is_filled_pipe = true;
         bbr.check_drain();
         assert_eq!(bbr.state, BbrStateMachine::ProbeBW);
     }
 
-    #[test]
-    fn test_bbr_enter_probe_bw() {
-        let mut bbr = super::Bbr::new();
+    #[tokio::test(start_paused = true)]
+    async fn test_bbr_enter_probe_bw() {
+        let mut bbr = new_bbr();
         bbr.init();
         bbr.enter_probe_bw();
         assert_eq!(bbr.state, BbrStateMachine::ProbeBW);
         assert_eq!(bbr.cwnd_gain, 2.0);
     }
 
-    #[test]
-    fn test_bbr_advance_cycle_phase() {
-        let mut bbr = super::Bbr::new();
+    #[tokio::test(start_paused = true)]
+    async fn test_bbr_advance_cycle_phase() {
+        let mut bbr = new_bbr();
         bbr.init();
         bbr.cycle_index = 0;
         bbr.advance_cycle_phase();
@@ -285,11 +274,12 @@ mod tests {
         assert_eq!(bbr.pacing_gain, 1.25)
     }
 
-    #[test]
-    fn test_bbr_is_next_cycle_phase() {
-        let mut bbr = super::Bbr::new();
+    #[tokio::test(start_paused = true)]
+    async fn test_bbr_is_next_cycle_phase() {
+        let mut bbr = new_bbr();
         bbr.init();
         bbr.enter_probe_bw();
+        bbr.rtprop = Duration::from_millis(100);
         let now = Instant::now();
 
         bbr.pacing_gain = 1.0;
@@ -306,9 +296,9 @@ mod tests {
         assert!(bbr.is_next_cycle_phase());
     }
 
-    #[test]
-    fn test_restart_from_idle() {
-        let mut bbr = super::Bbr::new();
+    #[tokio::test(start_paused = true)]
+    async fn test_restart_from_idle() {
+        let mut bbr = new_bbr();
         bbr.init();
 
         bbr.bytes_in_flight = 0;
@@ -316,4 +306,22 @@ mod tests {
 
         assert!(!bbr.is_idle_restart);
     }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_bbr_probe_rtt() {
+        let mut bbr = new_bbr();
+        let rtt = Duration::from_millis(100);
+        simulate_round_trip(&mut bbr, rtt, 0, 10, MSS).await;
+
+        // the rtprop is not refreshed during the filter window
+        tokio::time::advance(Duration::from_secs(11)).await;
+        simulate_round_trip(&mut bbr, rtt * 2, 10, 20, MSS).await;
+        assert_eq!(bbr.state, BbrStateMachine::ProbeRTT);
+        assert_eq!(bbr.cwnd, bbr.min_pipe_cwnd());
+        assert!(bbr.probe_rtt_done_stamp.is_some());
+
+        // hold for PROBE_RTT_DURATION and at least one round
+        simulate_round_trip(&mut bbr, PROBE_RTT_DURATION, 20, 21, MSS).await;
+        assert_ne!(bbr.state, BbrStateMachine::ProbeRTT);
+    }
 }
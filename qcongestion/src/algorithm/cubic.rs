@@ -0,0 +1,512 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicU16, Ordering},
+};
+
+use qbase::{Epoch, frame::AckFrame};
+use qevent::quic::recovery::RecoveryMetricsUpdated;
+use tokio::time::{Duration, Instant};
+
+use crate::{
+    algorithm::Control,
+    packets::{SentPacket, State},
+    rtt::ArcRtt,
+};
+
+mod hystart;
+
+use hystart::{HyStart, Phase};
+
+// 5. Constants and Parameters
+// C determines the aggressiveness of window increase in high-BDP networks.
+const C: f64 = 0.4;
+// beta_cubic is the multiplicative window decrease factor.
+const BETA_CUBIC: f64 = 0.7;
+// alpha_cubic = 3 * (1 - beta_cubic) / (1 + beta_cubic), the additive increase factor
+// of the Reno-friendly region.
+const ALPHA_CUBIC: f64 = 3.0 * (1.0 - BETA_CUBIC) / (1.0 + BETA_CUBIC);
+
+const INFINITE_SSTHRESH: usize = usize::MAX;
+
+/// CUBIC congestion control, see [RFC 9438](https://www.rfc-editor.org/rfc/rfc9438.html).
+///
+/// Slow start exits with HyStart++, see [RFC 9406](https://www.rfc-editor.org/rfc/rfc9406.html).
+pub(crate) struct Cubic {
+    max_datagram_size: Arc<AtomicU16>,
+    rtt: ArcRtt,
+    ecn_ce_counters: [u64; Epoch::count()],
+    bytes_in_flight: usize,
+    congestion_window: usize,
+    congestion_recovery_start_time: Option<Instant>,
+    ssthresh: usize,
+    hystart: HyStart,
+
+    // 4.1.2. Variables of Interest, all windows are in bytes
+    // The time when the current congestion avoidance stage started.
+    t_epoch: Option<Instant>,
+    // Size of cwnd before the last window reduction.
+    cwnd_prior: f64,
+    // Size of cwnd just before cwnd was reduced in the last congestion event
+    // when fast convergence is disabled.
+    w_max: f64,
+    // The time period in seconds it takes to increase the congestion window
+    // size at the beginning of the current congestion avoidance stage to W_max.
+    k: f64,
+    // The estimate for the congestion window in bytes in the Reno-friendly region.
+    w_est: f64,
+
+    // Per ACK states, reset in `on_ack_processed`
+    newly_acked_bytes: usize,
+    largest_acked_sent_time: Option<Instant>,
+}
+
+impl From<&Cubic> for RecoveryMetricsUpdated {
+    fn from(cubic: &Cubic) -> Self {
+        qevent::build!(RecoveryMetricsUpdated {
+            congestion_window: cubic.congestion_window as u64,
+            bytes_in_flight: cubic.bytes_in_flight as u64,
+            ssthresh: cubic.ssthresh as u64,
+        })
+    }
+}
+
+impl Cubic {
+    pub(crate) fn new(max_datagram_size: Arc<AtomicU16>, rtt: ArcRtt) -> Self {
+        let mut cubic = Cubic {
+            max_datagram_size,
+            rtt,
+            ecn_ce_counters: [0, 0, 0],
+            bytes_in_flight: 0,
+            congestion_window: 0,
+            congestion_recovery_start_time: None,
+            ssthresh: INFINITE_SSTHRESH,
+            hystart: HyStart::new(),
+            t_epoch: None,
+            cwnd_prior: 0.0,
+            w_max: 0.0,
+            k: 0.0,
+            w_est: 0.0,
+            newly_acked_bytes: 0,
+            largest_acked_sent_time: None,
+        };
+        // The upper bound for the initial window will be
+        // min (10*MSS, max (2*MSS, 14600))
+        // See https://datatracker.ietf.org/doc/html/rfc6928#autoid-3
+        let mss = cubic.max_datagram_size();
+        cubic.congestion_window = (mss * 10).min((mss * 2).max(14600));
+        cubic
+    }
+
+    fn max_datagram_size(&self) -> usize {
+        self.max_datagram_size.load(Ordering::Relaxed) as usize
+    }
+
+    // The RECOMMENDED value is 2 * max_datagram_size.
+    // See https://datatracker.ietf.org/doc/html/rfc9002#name-initial-and-minimum-congest
+    fn minimum_window(&self) -> usize {
+        2 * self.max_datagram_size()
+    }
+
+    fn in_congestion_recovery(&self, sent_time: &Instant) -> bool {
+        self.congestion_recovery_start_time
+            .is_some_and(|recovery_start_time| *sent_time <= recovery_start_time)
+    }
+
+    /// 4.2. Window Increase Function
+    /// W_cubic(t) = C * (t - K)^3 + W_max
+    ///
+    /// `t` is in seconds, and the result is in bytes.
+    fn w_cubic(&self, t: f64) -> f64 {
+        let mss = self.max_datagram_size() as f64;
+        C * (t - self.k).powi(3) * mss + self.w_max
+    }
+
+    fn on_packet_acked(&mut self, acked_packet: &SentPacket) {
+        if !acked_packet.count_for_cc {
+            return;
+        }
+        if acked_packet.state == State::Inflight {
+            self.bytes_in_flight = self.bytes_in_flight.saturating_sub(acked_packet.sent_bytes);
+        }
+        // Do not increase congestion window in recovery period.
+        if self.in_congestion_recovery(&acked_packet.time_sent) {
+            return;
+        }
+        self.newly_acked_bytes += acked_packet.sent_bytes;
+        self.largest_acked_sent_time = self
+            .largest_acked_sent_time
+            .max(Some(acked_packet.time_sent));
+    }
+
+    fn on_ack_processed(&mut self, rtt_sample: Option<Duration>) {
+        let acked_bytes = std::mem::take(&mut self.newly_acked_bytes);
+        let Some(largest_sent_time) = self.largest_acked_sent_time.take() else {
+            return;
+        };
+        let now = Instant::now();
+
+        if self.congestion_window < self.ssthresh {
+            self.slow_start(acked_bytes, largest_sent_time, rtt_sample, now);
+        } else {
+            self.congestion_avoidance(acked_bytes, now);
+        }
+        qevent::event!({ RecoveryMetricsUpdated::from(&*self) });
+    }
+
+    /// 4.10. Slow Start, with HyStart++ (RFC 9406 4.2):
+    /// cwnd = cwnd + (min(N, L * SMSS) / CSS_GROWTH_DIVISOR)
+    ///
+    /// The packets are paced, so L is infinity. Only the ACKs carrying an RTT sample are
+    /// counted by HyStart++.
+    fn slow_start(
+        &mut self,
+        acked_bytes: usize,
+        sent_time: Instant,
+        rtt_sample: Option<Duration>,
+        now: Instant,
+    ) {
+        self.congestion_window += acked_bytes / self.hystart.growth_divisor();
+        if let Some(rtt) = rtt_sample {
+            self.hystart.on_ack(sent_time, rtt, now);
+        }
+        // If CSS_ROUNDS rounds are complete, enter congestion avoidance by setting the ssthresh
+        // to the current cwnd.
+        if self.hystart.phase() == Phase::Done {
+            self.ssthresh = self.congestion_window;
+        }
+    }
+
+    /// 4.3. Reno-Friendly Region
+    /// 4.4. Concave Region
+    /// 4.5. Convex Region
+    fn congestion_avoidance(&mut self, acked_bytes: usize, now: Instant) {
+        let mss = self.max_datagram_size() as f64;
+        let cwnd = self.congestion_window as f64;
+
+        // At the beginning of a congestion avoidance stage:
+        //   K = cubic_root((W_max - cwnd_epoch) / C)
+        // where cwnd_epoch is the cwnd at the beginning of the current congestion avoidance
+        // stage. W_max is set to cwnd_epoch if it is not greater than cwnd_epoch, i.e. no
+        // congestion event happened yet.
+        let t_epoch = match self.t_epoch {
+            Some(t_epoch) => t_epoch,
+            None => {
+                if self.w_max > cwnd {
+                    self.k = ((self.w_max - cwnd) / mss / C).cbrt();
+                } else {
+                    self.k = 0.0;
+                    self.w_max = cwnd;
+                }
+                self.w_est = cwnd;
+                *self.t_epoch.insert(now)
+            }
+        };
+
+        // W_est = W_est + alpha_cubic * segments_acked / cwnd
+        // alpha_cubic is set to 1 once W_est reaches cwnd_prior.
+        let alpha = if self.w_est >= self.cwnd_prior {
+            1.0
+        } else {
+            ALPHA_CUBIC
+        };
+        self.w_est += alpha * mss * acked_bytes as f64 / cwnd;
+
+        let t = now.saturating_duration_since(t_epoch).as_secs_f64();
+        if self.w_cubic(t) < self.w_est {
+            // Reno-friendly region
+            self.congestion_window = self.w_est as usize;
+        } else {
+            // Concave and convex regions, the target is the window size after one RTT:
+            //   cwnd <= target <= 1.5 * cwnd
+            //   cwnd = cwnd + (target - cwnd) / cwnd for each acknowledged segment
+            let rtt = self.rtt.smoothed_rtt().as_secs_f64();
+            let target = self.w_cubic(t + rtt).clamp(cwnd, 1.5 * cwnd);
+            self.congestion_window += ((target - cwnd) * acked_bytes as f64 / cwnd) as usize;
+        }
+    }
+
+    /// 4.6. Multiplicative Decrease
+    /// 4.7. Fast Convergence
+    fn on_congestion_event(&mut self, sent_time: &Instant) {
+        // No reaction if already in a recovery period.
+        if self.in_congestion_recovery(sent_time) {
+            return;
+        }
+        self.congestion_recovery_start_time = Some(Instant::now());
+
+        let cwnd = self.congestion_window as f64;
+        // With fast convergence, release more bandwidth for new flows if the window is
+        // still below the W_max of the last congestion event.
+        self.w_max = if cwnd < self.w_max {
+            cwnd * (1.0 + BETA_CUBIC) / 2.0
+        } else {
+            cwnd
+        };
+        self.cwnd_prior = cwnd;
+        // ssthresh = cwnd * beta_cubic
+        // cwnd = max(ssthresh, kMinimumWindow)
+        self.ssthresh = ((cwnd * BETA_CUBIC).round() as usize).max(self.minimum_window());
+        self.congestion_window = self.ssthresh;
+        // A new congestion avoidance stage starts on the next ACK.
+        self.t_epoch = None;
+        qevent::event!({ RecoveryMetricsUpdated::from(&*self) });
+    }
+
+    fn process_ecn(&mut self, ack: &AckFrame, sent_time: &Instant, epoch: Epoch) {
+        if let Some(ecn) = ack.ecn() {
+            if ecn.ce() > self.ecn_ce_counters[epoch] {
+                self.ecn_ce_counters[epoch] = ecn.ce();
+                self.on_congestion_event(sent_time);
+            }
+        }
+    }
+
+    fn on_packets_lost(
+        &mut self,
+        lost_packets: &mut dyn Iterator<Item = &SentPacket>,
+        persistent_lost: bool,
+    ) {
+        let mut sent_time_last_loss: Option<Instant> = None;
+        for lost_packet in lost_packets {
+            if lost_packet.count_for_cc {
+                self.bytes_in_flight = self.bytes_in_flight.saturating_sub(lost_packet.sent_bytes);
+                sent_time_last_loss = sent_time_last_loss.max(Some(lost_packet.time_sent));
+            }
+        }
+        if let Some(time) = sent_time_last_loss {
+            self.on_congestion_event(&time);
+        }
+
+        // 4.8. Timeout, the window collapses to the minimum window and slow start
+        // starts over.
+        if persistent_lost {
+            self.congestion_window = self.minimum_window();
+            self.congestion_recovery_start_time = None;
+            self.t_epoch = None;
+            self.hystart = HyStart::new();
+            qevent::event!({ RecoveryMetricsUpdated::from(&*self) });
+        }
+    }
+
+    fn remove_from_bytes_in_flight(
+        &mut self,
+        discard_packets: &mut dyn Iterator<Item = &SentPacket>,
+    ) {
+        for packet in discard_packets {
+            if packet.count_for_cc && packet.state != State::Retransmitted {
+                self.bytes_in_flight = self.bytes_in_flight.saturating_sub(packet.sent_bytes);
+            }
+        }
+    }
+}
+
+impl Control for Cubic {
+    fn on_packet_sent_cc(&mut self, packet: &mut SentPacket) {
+        self.bytes_in_flight += packet.sent_bytes;
+    }
+
+    fn on_packet_acked(&mut self, acked_packet: &SentPacket) {
+        self.on_packet_acked(acked_packet);
+    }
+
+    fn on_packets_lost(
+        &mut self,
+        lost_packets: &mut dyn Iterator<Item = &SentPacket>,
+        persistent_lost: bool,
+    ) {
+        self.on_packets_lost(lost_packets, persistent_lost);
+    }
+
+    fn on_ack_processed(&mut self, rtt_sample: Option<Duration>) {
+        self.on_ack_processed(rtt_sample);
+    }
+
+    fn process_ecn(&mut self, ack: &AckFrame, sent_time: &Instant, epoch: Epoch) {
+        self.process_ecn(ack, sent_time, epoch);
+    }
+
+    fn congestion_window(&self) -> usize {
+        self.congestion_window
+    }
+
+    fn pacing_rate(&self) -> Option<usize> {
+        None
+    }
+
+    fn remove_from_bytes_in_flight(&mut self, packets: &mut dyn Iterator<Item = &SentPacket>) {
+        self.remove_from_bytes_in_flight(packets);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MSS: usize = 1200;
+
+    fn new_cubic() -> Cubic {
+        Cubic::new(Arc::new(AtomicU16::new(MSS as u16)), ArcRtt::new())
+    }
+
+    // Sends `count` packets, and acknowledges all of them with a single ACK after `rtt`.
+    async fn simulate_round_trip(cubic: &mut Cubic, rtt: Duration, count: u64) {
+        let sents = (0..count)
+            .map(|pn| {
+                let mut sent = SentPacket::new(pn, Instant::now(), true, true, MSS);
+                cubic.on_packet_sent_cc(&mut sent);
+                sent
+            })
+            .collect::<Vec<_>>();
+
+        tokio::time::advance(rtt).await;
+        for sent in &sents {
+            cubic.on_packet_acked(sent);
+        }
+        cubic.on_ack_processed(Some(rtt));
+    }
+
+    fn lose_packet(cubic: &mut Cubic, persistent_lost: bool) {
+        let mut lost = SentPacket::new(0, Instant::now(), true, true, MSS);
+        cubic.on_packet_sent_cc(&mut lost);
+        cubic.on_packets_lost(&mut [lost].iter(), persistent_lost);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cubic_init() {
+        let cubic = new_cubic();
+        assert_eq!(cubic.congestion_window(), 10 * MSS);
+        assert_eq!(cubic.ssthresh, INFINITE_SSTHRESH);
+        assert_eq!(cubic.bytes_in_flight, 0);
+        assert_eq!(cubic.pacing_rate(), None);
+        assert!(cubic.t_epoch.is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cubic_slow_start() {
+        let mut cubic = new_cubic();
+        let rtt = Duration::from_millis(100);
+
+        simulate_round_trip(&mut cubic, rtt, 10).await;
+        assert_eq!(cubic.bytes_in_flight, 0);
+        assert_eq!(cubic.congestion_window(), 20 * MSS);
+
+        simulate_round_trip(&mut cubic, rtt, 20).await;
+        assert_eq!(cubic.congestion_window(), 40 * MSS);
+        assert_eq!(cubic.hystart.phase(), Phase::SlowStart);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cubic_congestion_event() {
+        let mut cubic = new_cubic();
+        simulate_round_trip(&mut cubic, Duration::from_millis(100), 10).await;
+        assert_eq!(cubic.congestion_window(), 20 * MSS);
+
+        lose_packet(&mut cubic, false);
+        assert_eq!(cubic.w_max, (20 * MSS) as f64);
+        assert_eq!(cubic.ssthresh, 14 * MSS);
+        assert_eq!(cubic.congestion_window(), 14 * MSS);
+        assert_eq!(cubic.bytes_in_flight, 0);
+
+        // no reaction for packets sent before the recovery period
+        lose_packet(&mut cubic, false);
+        assert_eq!(cubic.congestion_window(), 14 * MSS);
+
+        // fast convergence, the window is below the last W_max
+        tokio::time::advance(Duration::from_millis(1)).await;
+        lose_packet(&mut cubic, false);
+        assert_eq!(cubic.w_max, (14 * MSS) as f64 * (1.0 + BETA_CUBIC) / 2.0);
+        assert_eq!(cubic.cwnd_prior, (14 * MSS) as f64);
+        assert_eq!(cubic.congestion_window(), 14 * MSS * 7 / 10);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cubic_congestion_avoidance() {
+        let mut cubic = new_cubic();
+        let rtt = Duration::from_millis(100);
+        cubic.congestion_window = 100 * MSS;
+        lose_packet(&mut cubic, false);
+        assert_eq!(cubic.congestion_window(), 70 * MSS);
+
+        tokio::time::advance(Duration::from_millis(1)).await;
+        simulate_round_trip(&mut cubic, rtt, 70).await;
+        // K = cubic_root(W_max * (1 - beta_cubic) / C)
+        assert!((cubic.k - (100.0 * (1.0 - BETA_CUBIC) / C).cbrt()).abs() < 1e-9);
+        assert!(cubic.congestion_window() > 70 * MSS);
+
+        // concave region, the window approaches W_max at time K
+        while Instant::now() < cubic.t_epoch.unwrap() + Duration::from_secs_f64(cubic.k) {
+            let cwnd = cubic.congestion_window();
+            simulate_round_trip(&mut cubic, rtt, (cwnd / MSS) as u64).await;
+            assert!(cubic.congestion_window() >= cwnd);
+            assert!(cubic.congestion_window() <= 100 * MSS);
+        }
+        assert!(cubic.congestion_window() >= 99 * MSS);
+
+        // convex region, the window grows beyond W_max
+        for _ in 0..40 {
+            let cwnd = cubic.congestion_window();
+            simulate_round_trip(&mut cubic, rtt, (cwnd / MSS) as u64).await;
+        }
+        assert!(cubic.congestion_window() > 110 * MSS);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cubic_reno_friendly() {
+        let mut cubic = new_cubic();
+        // on a short rtt path, the window grows faster in the Reno-friendly region
+        let rtt = Duration::from_millis(1);
+        cubic.congestion_window = 100 * MSS;
+        lose_packet(&mut cubic, false);
+
+        tokio::time::advance(rtt).await;
+        for _ in 0..100 {
+            let cwnd = cubic.congestion_window();
+            simulate_round_trip(&mut cubic, rtt, (cwnd / MSS) as u64).await;
+        }
+        let t = (Instant::now() - cubic.t_epoch.unwrap()).as_secs_f64();
+        assert!(cubic.w_cubic(t) < cubic.w_est);
+        assert_eq!(cubic.congestion_window(), cubic.w_est as usize);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cubic_hystart_exit() {
+        let mut cubic = new_cubic();
+        let mut rtt = Duration::from_millis(100);
+        // ack each packet separately to get enough rtt samples
+        for _ in 0..10 {
+            let cwnd = cubic.congestion_window();
+            let sents = (0..(cwnd / MSS) as u64)
+                .map(|pn| {
+                    let mut sent = SentPacket::new(pn, Instant::now(), true, true, MSS);
+                    cubic.on_packet_sent_cc(&mut sent);
+                    sent
+                })
+                .collect::<Vec<_>>();
+            tokio::time::advance(rtt).await;
+            for sent in &sents {
+                cubic.on_packet_acked(sent);
+                cubic.on_ack_processed(Some(rtt));
+            }
+            // the queue builds up
+            rtt += Duration::from_millis(20);
+        }
+        assert_eq!(cubic.hystart.phase(), Phase::Done);
+        assert!(cubic.ssthresh < INFINITE_SSTHRESH);
+        assert!(cubic.congestion_window() < 10 * MSS * (1 << 10));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cubic_persistent_congestion() {
+        let mut cubic = new_cubic();
+        simulate_round_trip(&mut cubic, Duration::from_millis(100), 10).await;
+        lose_packet(&mut cubic, true);
+        assert_eq!(cubic.congestion_window(), 2 * MSS);
+        assert_eq!(cubic.ssthresh, 14 * MSS);
+        assert!(cubic.congestion_recovery_start_time.is_none());
+        assert!(cubic.t_epoch.is_none());
+
+        // slow start again
+        simulate_round_trip(&mut cubic, Duration::from_millis(100), 2).await;
+        assert_eq!(cubic.congestion_window(), 4 * MSS);
+    }
+}
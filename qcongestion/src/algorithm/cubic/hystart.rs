@@ -0,0 +1,195 @@
+// HyStart++: Modified Slow Start for TCP
+// See [RFC 9406](https://www.rfc-editor.org/rfc/rfc9406.html)
+use tokio::time::{Duration, Instant};
+
+// 4.3. Tuning Constants and Other Considerations
+const MIN_RTT_THRESH: Duration = Duration::from_millis(4);
+const MAX_RTT_THRESH: Duration = Duration::from_millis(16);
+const MIN_RTT_DIVISOR: u32 = 8;
+const N_RTT_SAMPLE: usize = 8;
+const CSS_GROWTH_DIVISOR: usize = 4;
+const CSS_ROUNDS: usize = 5;
+
+/// The slow start phase HyStart++ is currently in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Phase {
+    /// Standard slow start, the congestion window grows by the acknowledged bytes.
+    SlowStart,
+    /// Conservative Slow Start, entered when an RTT increase is observed, the congestion
+    /// window grows by 1/CSS_GROWTH_DIVISOR of the acknowledged bytes.
+    Css { rounds: usize },
+    /// Slow start is done, the sender should enter congestion avoidance.
+    Done,
+}
+
+#[derive(Debug)]
+pub(super) struct HyStart {
+    phase: Phase,
+    // A round ends when a packet sent at or after `window_end` is acknowledged,
+    // which is equivalent to `windowEnd = SND.NXT` in the RFC.
+    window_end: Option<Instant>,
+    last_round_min_rtt: Duration,
+    current_round_min_rtt: Duration,
+    css_baseline_min_rtt: Duration,
+    rtt_sample_count: usize,
+}
+
+impl HyStart {
+    // 4.2. Algorithm Details
+    // lastRoundMinRTT = infinity
+    // currentRoundMinRTT = infinity
+    // cssBaselineMinRtt = infinity
+    // rttSampleCount = 0
+    pub(super) fn new() -> Self {
+        Self {
+            phase: Phase::SlowStart,
+            window_end: None,
+            last_round_min_rtt: Duration::MAX,
+            current_round_min_rtt: Duration::MAX,
+            css_baseline_min_rtt: Duration::MAX,
+            rtt_sample_count: 0,
+        }
+    }
+
+    pub(super) fn phase(&self) -> Phase {
+        self.phase
+    }
+
+    /// The divisor to apply to the acknowledged bytes when growing the congestion window.
+    pub(super) fn growth_divisor(&self) -> usize {
+        match self.phase {
+            Phase::Css { .. } => CSS_GROWTH_DIVISOR,
+            Phase::SlowStart | Phase::Done => 1,
+        }
+    }
+
+    /// Called once per ACK received in slow start.
+    ///
+    /// `largest_sent_time` is the latest send time of the packets newly acknowledged by this
+    /// ACK, and `rtt` is the RTT sample taken from it.
+    pub(super) fn on_ack(&mut self, largest_sent_time: Instant, rtt: Duration, now: Instant) {
+        if self.phase == Phase::Done {
+            return;
+        }
+
+        // At the start of each round during standard slow start and CSS:
+        //   lastRoundMinRTT = currentRoundMinRTT
+        //   currentRoundMinRTT = infinity
+        //   rttSampleCount = 0
+        if self
+            .window_end
+            .map_or(true, |window_end| largest_sent_time >= window_end)
+        {
+            self.window_end = Some(now);
+            self.last_round_min_rtt = self.current_round_min_rtt;
+            self.current_round_min_rtt = Duration::MAX;
+            self.rtt_sample_count = 0;
+
+            // If CSS_ROUNDS rounds are complete, enter congestion avoidance.
+            if let Phase::Css { rounds } = &mut self.phase {
+                *rounds += 1;
+                if *rounds >= CSS_ROUNDS {
+                    self.phase = Phase::Done;
+                    return;
+                }
+            }
+        }
+
+        // For each arriving ACK in slow start, where N is the number of previously
+        // unacknowledged bytes acknowledged in the arriving ACK:
+        //   currentRoundMinRTT = min(currentRoundMinRTT, currentRTT)
+        //   rttSampleCount += 1
+        self.current_round_min_rtt = self.current_round_min_rtt.min(rtt);
+        self.rtt_sample_count += 1;
+        if self.rtt_sample_count < N_RTT_SAMPLE
+            || self.current_round_min_rtt == Duration::MAX
+            || self.last_round_min_rtt == Duration::MAX
+        {
+            return;
+        }
+
+        match self.phase {
+            // RttThresh = max(MIN_RTT_THRESH,
+            //   min(lastRoundMinRTT / MIN_RTT_DIVISOR, MAX_RTT_THRESH))
+            // if (currentRoundMinRTT >= (lastRoundMinRTT + RttThresh))
+            //   cssBaselineMinRtt = currentRoundMinRTT
+            //   exit slow start and enter CSS
+            Phase::SlowStart => {
+                let rtt_thresh = (self.last_round_min_rtt / MIN_RTT_DIVISOR)
+                    .clamp(MIN_RTT_THRESH, MAX_RTT_THRESH);
+                if self.current_round_min_rtt >= self.last_round_min_rtt + rtt_thresh {
+                    self.css_baseline_min_rtt = self.current_round_min_rtt;
+                    self.phase = Phase::Css { rounds: 0 };
+                }
+            }
+            // if (currentRoundMinRTT < cssBaselineMinRtt)
+            //   cssBaselineMinRtt = infinity
+            //   resume slow start including HyStart++
+            Phase::Css { .. } => {
+                if self.current_round_min_rtt < self.css_baseline_min_rtt {
+                    self.css_baseline_min_rtt = Duration::MAX;
+                    self.phase = Phase::SlowStart;
+                }
+            }
+            Phase::Done => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Simulates one round with `N_RTT_SAMPLE` ACKs, each carrying an RTT sample of `rtt`.
+    async fn simulate_round(hystart: &mut HyStart, rtt: Duration) {
+        let sent_time = Instant::now();
+        tokio::time::advance(rtt).await;
+        for _ in 0..N_RTT_SAMPLE {
+            hystart.on_ack(sent_time, rtt, Instant::now());
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_hystart_stable_rtt() {
+        let mut hystart = HyStart::new();
+        for _ in 0..10 {
+            simulate_round(&mut hystart, Duration::from_millis(100)).await;
+        }
+        assert_eq!(hystart.phase(), Phase::SlowStart);
+        assert_eq!(hystart.growth_divisor(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_hystart_enter_and_exit_css() {
+        let mut hystart = HyStart::new();
+        simulate_round(&mut hystart, Duration::from_millis(100)).await;
+        simulate_round(&mut hystart, Duration::from_millis(100)).await;
+
+        // the threshold is clamped to 16ms for a 100ms rtt
+        simulate_round(&mut hystart, Duration::from_millis(110)).await;
+        assert_eq!(hystart.phase(), Phase::SlowStart);
+        simulate_round(&mut hystart, Duration::from_millis(130)).await;
+        assert_eq!(hystart.phase(), Phase::Css { rounds: 0 });
+        assert_eq!(hystart.growth_divisor(), CSS_GROWTH_DIVISOR);
+
+        // the rtt increase was spurious, back to slow start
+        simulate_round(&mut hystart, Duration::from_millis(120)).await;
+        assert_eq!(hystart.phase(), Phase::SlowStart);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_hystart_css_done() {
+        let mut hystart = HyStart::new();
+        simulate_round(&mut hystart, Duration::from_millis(100)).await;
+        simulate_round(&mut hystart, Duration::from_millis(100)).await;
+        simulate_round(&mut hystart, Duration::from_millis(120)).await;
+        assert_eq!(hystart.phase(), Phase::Css { rounds: 0 });
+
+        for rounds in 1..CSS_ROUNDS {
+            simulate_round(&mut hystart, Duration::from_millis(120)).await;
+            assert_eq!(hystart.phase(), Phase::Css { rounds });
+        }
+        simulate_round(&mut hystart, Duration::from_millis(120)).await;
+        assert_eq!(hystart.phase(), Phase::Done);
+    }
+}
@@ -225,7 +225,7 @@ impl NewReno {
 }
 
 impl Control for NewReno {
-    fn on_packet_sent_cc(&mut self, packet: &SentPacket) {
+    fn on_packet_sent_cc(&mut self, packet: &mut SentPacket) {
         self.on_packet_sent_cc(packet.sent_bytes);
     }
 
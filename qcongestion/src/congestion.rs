@@ -3,7 +3,10 @@ use std::sync::{Arc, Mutex};
 use qbase::{
     Epoch,
     frame::AckFrame,
-    net::tx::{ArcSendWaker, Signals},
+    net::{
+        route::Ecn,
+        tx::{ArcSendWaker, Signals},
+    },
 };
 use qevent::{quic::recovery::PacketLostTrigger, telemetry::Instrument};
 use tokio::{
@@ -13,16 +16,21 @@ use tokio::{
 use tracing::Instrument as _;
 
 use crate::{
-    Algorithm, Feedback, MSS,
-    algorithm::{Control, new_reno::NewReno},
+    Feedback, MSS, ProductCongestionController,
+    ack_frequency::ArcAckFrequency,
+    algorithm::Control,
+    ecn::EcnValidator,
     pacing::{self, Pacer},
-    packets::{PacketSpace, SentPacket},
+    packets::{PacketCounters, PacketSpace, SentPacket},
     rtt::{ArcRtt, INITIAL_RTT},
-    status::PathStatus,
+    status::{PathStatus, Pmtu},
 };
 
 const INIT_CWND: usize = MSS * 10;
-const PACKET_THRESHOLD: usize = 3;
+pub(crate) const PACKET_THRESHOLD: usize = 3;
+// The number of packets larger than the base PMTU that must be lost in a row, before a black
+// hole is declared.
+const BLACK_HOLE_THRESHOLD: usize = 3;
 
 /// Imple RFC 9002 Appendix A. Loss Recovery
 /// See [Appendix A](https://datatracker.ietf.org/doc/html/rfc9002#name-loss-recovery-pseudocode)
@@ -45,33 +53,33 @@ pub struct CongestionController {
     need_send_ack_eliciting_packets: [usize; Epoch::count()],
     path_status: PathStatus,
     tx_waker: ArcSendWaker,
+    ecn: EcnValidator,
 }
 
 impl CongestionController {
     /// A.4. Initialization
     fn init(
-        algorithm: Algorithm,
+        controller: &dyn ProductCongestionController,
         max_ack_delay: Duration,
+        ack_frequency: ArcAckFrequency,
         trackers: [Arc<dyn Feedback>; 3],
         path_status: PathStatus,
         tx_waker: ArcSendWaker,
     ) -> Self {
-        let algorithm: Box<dyn Control> = match algorithm {
-            Algorithm::Bbr => todo!("implement BBR"),
-            Algorithm::NewReno => Box::new(NewReno::new(path_status.pmtu.clone())),
-        };
+        let rtt = ArcRtt::new();
+        let algorithm = controller.init(rtt.clone(), Pmtu(path_status.pmtu.clone()));
 
         let now = Instant::now();
         CongestionController {
             algorithm,
-            rtt: ArcRtt::new(),
+            rtt,
             loss_detection_timer: None,
             pto_count: 0,
             max_ack_delay,
             packet_spaces: [
-                PacketSpace::with_epoch(Epoch::Initial, max_ack_delay),
-                PacketSpace::with_epoch(Epoch::Handshake, max_ack_delay),
-                PacketSpace::with_epoch(Epoch::Data, max_ack_delay),
+                PacketSpace::with_epoch(Epoch::Initial, max_ack_delay, ack_frequency.clone()),
+                PacketSpace::with_epoch(Epoch::Handshake, max_ack_delay, ack_frequency.clone()),
+                PacketSpace::with_epoch(Epoch::Data, max_ack_delay, ack_frequency),
             ],
             pacer: Pacer::new(INITIAL_RTT, INIT_CWND, path_status.mtu(), now, None),
             pending_burst: false,
@@ -79,6 +87,7 @@ impl CongestionController {
             need_send_ack_eliciting_packets: [0; Epoch::count()],
             path_status,
             tx_waker,
+            ecn: EcnValidator::new(),
         }
     }
 
@@ -106,19 +115,24 @@ impl CongestionController {
         sent_bytes: usize,
     ) {
         let now = Instant::now();
-        let sent = SentPacket::new(packet_number, now, ack_eliciting, in_flight, sent_bytes);
+        let mut sent = SentPacket::new(packet_number, now, ack_eliciting, in_flight, sent_bytes);
+        sent.is_mtu_probe = sent_bytes > self.path_status.mtu();
+        sent.is_ecn_marked = self.ecn.on_packet_sent();
         if in_flight {
             if ack_eliciting {
                 self.packet_spaces[epoch].time_of_last_ack_eliciting_packet = Some(now);
                 self.need_send_ack_eliciting_packets[epoch] =
                     self.need_send_ack_eliciting_packets[epoch].saturating_sub(1);
             }
-            self.algorithm.on_packet_sent_cc(&sent);
+            self.algorithm.on_packet_sent_cc(&mut sent);
             self.packet_spaces[epoch]
                 .loss_time
                 .get_or_insert_with(|| now + self.rtt.loss_delay());
             self.set_loss_detection_timer();
         }
+        let counters = &mut self.packet_spaces[epoch].counters;
+        counters.sent_packets += 1;
+        counters.sent_bytes += sent_bytes as u64;
         self.packet_spaces[epoch].sent_packets.push_back(sent);
         self.pacer.on_sent(sent_bytes);
     }
@@ -187,19 +201,26 @@ impl CongestionController {
     pub fn on_ack_rcvd(&mut self, epoch: Epoch, ack_frame: &AckFrame, now: Instant) {
         self.packet_spaces[epoch].update_largest_acked_packet(ack_frame.largest());
 
+        let mut rtt_sample = None;
         match self.packet_spaces[epoch].on_ack_rcvd(ack_frame, &mut self.algorithm) {
             None => return,
             Some(newly_acked_packets) => {
                 let (largest_pn, largest_time_sent) = newly_acked_packets.largest;
                 if largest_pn == ack_frame.largest() && newly_acked_packets.include_ack_eliciting {
-                    self.rtt.update(
+                    rtt_sample = Some(self.rtt.update(
                         now - largest_time_sent,
                         Duration::from_micros(ack_frame.delay()),
                         self.path_status.is_handshake_confirmed(),
-                    );
+                    ));
                 }
-                // Process ECN information if present.
-                if ack_frame.ecn().is_some() {
+                // Process ECN information if present, and valid.
+                // An ACK frame that does not increase the largest acknowledged packet number
+                // may be reordered, and must not fail the ECN validation.
+                if largest_pn == ack_frame.largest()
+                    && self
+                        .ecn
+                        .on_ack_rcvd(epoch, ack_frame.ecn(), newly_acked_packets.ecn_marked)
+                {
                     self.process_ecn(ack_frame, &largest_time_sent, epoch)
                 }
             }
@@ -213,6 +234,9 @@ impl CongestionController {
                 &mut self.algorithm,
             ),
         );
+        self.detect_black_hole(epoch);
+        self.detect_ecn_marked_lost(epoch);
+        self.algorithm.on_ack_processed(rtt_sample);
 
         if self.peer_completed_address_validation() {
             self.pto_count = 0;
@@ -300,6 +324,8 @@ impl CongestionController {
                     &mut self.algorithm,
                 ),
             );
+            self.detect_black_hole(epoch);
+            self.detect_ecn_marked_lost(epoch);
             self.set_loss_detection_timer();
             return;
         }
@@ -424,6 +450,30 @@ impl CongestionController {
             || self.path_status.is_handshake_confirmed()
     }
 
+    /// Packets larger than the base PMTU keep being lost while none of them is acknowledged,
+    /// the path can no longer deliver packets of the discovered PMTU.
+    ///
+    /// See [Section 4.3](https://www.rfc-editor.org/rfc/rfc8899#section-4.3) of RFC 8899.
+    fn detect_black_hole(&mut self, epoch: Epoch) {
+        if self.packet_spaces[epoch].large_packets_lost < BLACK_HOLE_THRESHOLD {
+            return;
+        }
+        self.packet_spaces[epoch].large_packets_lost = 0;
+        // the packets sent before falling back to the base PMTU are still being declared lost
+        if self.path_status.mtu() > MSS {
+            self.path_status.on_black_hole_detected();
+        }
+    }
+
+    /// The ECN validation fails if all the packets marked for testing are lost, the marking may
+    /// be dropped by the network.
+    fn detect_ecn_marked_lost(&mut self, epoch: Epoch) {
+        let lost = core::mem::take(&mut self.packet_spaces[epoch].ecn_marked_lost);
+        if lost > 0 {
+            self.ecn.on_packets_lost(lost);
+        }
+    }
+
     fn process_ecn(&mut self, ack: &AckFrame, sent_time: &Instant, epoch: Epoch) {
         self.algorithm.process_ecn(ack, sent_time, epoch);
     }
@@ -467,6 +517,49 @@ impl CongestionController {
         self.set_loss_detection_timer();
     }
 
+    fn abandon_in_flight(&mut self) {
+        for &epoch in Epoch::iter() {
+            let abandoned = self.packet_spaces[epoch].abandon_in_flight(&mut self.algorithm);
+            if !abandoned.is_empty() {
+                self.trackers[epoch]
+                    .may_loss(PacketLostTrigger::PtoExpired, &mut abandoned.into_iter());
+            }
+        }
+        self.set_loss_detection_timer();
+    }
+
+    fn stats(&self) -> CongestionStats {
+        let smoothed_rtt = self.rtt.smoothed_rtt();
+        let congestion_window = self.algorithm.congestion_window();
+        let counters: [PacketCounters; Epoch::count()] =
+            core::array::from_fn(|index| self.packet_spaces[index].counters);
+        CongestionStats {
+            smoothed_rtt,
+            min_rtt: self.rtt.min_rtt(),
+            latest_rtt: self.rtt.latest_rtt(),
+            rttvar: self.rtt.rttvar(),
+            congestion_window,
+            bytes_in_flight: self
+                .packet_spaces
+                .iter()
+                .map(PacketSpace::bytes_in_flight)
+                .sum(),
+            pacing_rate: pacing::rate_of(
+                smoothed_rtt,
+                congestion_window,
+                self.algorithm.pacing_rate(),
+            ),
+            sent_packets: counters.iter().map(|c| c.sent_packets).sum(),
+            sent_bytes: counters.map(|c| c.sent_bytes),
+            lost_packets: counters.iter().map(|c| c.lost_packets).sum(),
+            lost_bytes: counters.iter().map(|c| c.lost_bytes).sum(),
+            retransmitted_packets: counters
+                .iter()
+                .map(|c| c.lost_packets + c.abandoned_packets)
+                .sum(),
+        }
+    }
+
     fn get_pto(&self, epoch: Epoch) -> Duration {
         let mut pto_time = self.rtt.base_pto(self.pto_count);
         if epoch == Epoch::Data {
@@ -476,20 +569,75 @@ impl CongestionController {
     }
 }
 
+/// A snapshot of the RTT estimation, the congestion control and the loss recovery of a path.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CongestionStats {
+    pub smoothed_rtt: Duration,
+    pub min_rtt: Duration,
+    pub latest_rtt: Duration,
+    pub rttvar: Duration,
+    /// The congestion window in bytes.
+    pub congestion_window: usize,
+    /// The bytes of the packets in flight, the ones only carrying ACK frames are not counted.
+    pub bytes_in_flight: usize,
+    /// The pacing rate in bytes per second.
+    pub pacing_rate: usize,
+    /// The packets sent in all the packet number spaces.
+    pub sent_packets: u64,
+    /// The bytes of the packets sent in each packet number space, indexed by [`Epoch`].
+    pub sent_bytes: [u64; Epoch::count()],
+    /// The packets declared lost by the loss detection.
+    pub lost_packets: u64,
+    pub lost_bytes: u64,
+    /// The packets whose frames are handed back to be sent again, the ones declared lost, and
+    /// the ones still in flight when the path stopped carrying the data.
+    pub retransmitted_packets: u64,
+}
+
 #[derive(Clone)]
 pub struct ArcCC(Arc<Mutex<CongestionController>>);
 
+impl ArcCC {
+    /// Take a snapshot of the congestion controller.
+    pub fn stats(&self) -> CongestionStats {
+        self.0.lock().unwrap().stats()
+    }
+
+    /// The smoothed RTT of the path, or the initial RTT if no RTT sample is taken yet.
+    pub fn smoothed_rtt(&self) -> Duration {
+        self.0.lock().unwrap().rtt.smoothed_rtt()
+    }
+
+    /// The number of ack-eliciting packets the algorithm allows the peer to receive before
+    /// acknowledging, see [`Control::ack_eliciting_threshold`].
+    pub fn ack_eliciting_threshold(&self) -> u64 {
+        self.0.lock().unwrap().algorithm.ack_eliciting_threshold()
+    }
+
+    /// Acknowledges the received 1-RTT packets without delay, called when an IMMEDIATE_ACK
+    /// frame is received.
+    pub fn ack_immediately(&self) {
+        let mut guard = self.0.lock().unwrap();
+        guard.packet_spaces[Epoch::Data]
+            .rcvd_packets
+            .ack_immediately();
+        guard.tx_waker.wake_by(Signals::TRANSPORT);
+    }
+}
+
 impl ArcCC {
     pub fn new(
-        algorithm: Algorithm,
+        controller: &dyn ProductCongestionController,
         max_ack_delay: Duration,
+        ack_frequency: ArcAckFrequency,
         trackers: [Arc<dyn Feedback>; 3],
         path_status: PathStatus,
         tx_waker: ArcSendWaker,
     ) -> Self {
         ArcCC(Arc::new(Mutex::new(CongestionController::init(
-            algorithm,
+            controller,
             max_ack_delay,
+            ack_frequency,
             trackers,
             path_status,
             tx_waker,
@@ -545,6 +693,11 @@ impl super::Transport for ArcCC {
         )
     }
 
+    fn ecn_codepoint(&self) -> Option<Ecn> {
+        let mut guard = self.0.lock().unwrap();
+        guard.ecn.codepoint()
+    }
+
     fn need_ack(&self, epoch: Epoch) -> Option<(u64, Instant)> {
         let guard = self.0.lock().unwrap();
         guard.packet_spaces[epoch].rcvd_packets.need_ack()
@@ -585,12 +738,14 @@ impl super::Transport for ArcCC {
     }
 
     fn on_pkt_rcvd(&self, epoch: Epoch, pn: u64, is_ack_eliciting: bool) {
-        if !is_ack_eliciting {
-            return;
-        }
         let mut guard = self.0.lock().unwrap();
-        guard.packet_spaces[epoch].rcvd_packets.on_pkt_rcvd(pn);
-        guard.on_datagram_rcvd();
+        // the packets not ack-eliciting still fill the gaps of the missing packets
+        guard.packet_spaces[epoch]
+            .rcvd_packets
+            .on_pkt_rcvd(pn, is_ack_eliciting);
+        if is_ack_eliciting {
+            guard.on_datagram_rcvd();
+        }
     }
 
     fn get_pto(&self, epoch: Epoch) -> Duration {
@@ -603,6 +758,11 @@ impl super::Transport for ArcCC {
         guard.discard_epoch(epoch);
     }
 
+    fn abandon_in_flight(&self) {
+        let mut guard = self.0.lock().unwrap();
+        guard.abandon_in_flight();
+    }
+
     fn need_send_ack_eliciting(&self, epoch: Epoch) -> usize {
         let guard = self.0.lock().unwrap();
         guard.need_send_ack_eliciting_packets[epoch]
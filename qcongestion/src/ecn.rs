@@ -0,0 +1,193 @@
+use qbase::{Epoch, frame::EcnCounts, net::route::Ecn};
+use qevent::quic::recovery::{ECNState, ECNStateUpdated};
+
+// The number of packets marked ECT(0) to test whether the path supports ECN.
+const TESTING_PACKETS: usize = 10;
+
+/// ECN validation of a path.
+///
+/// The first packets are marked ECT(0) to test the path, then the marking pauses until any of
+/// them is acknowledged with the valid ECN counts. ECN is disabled if the counts show the marking is
+/// bleached or mangled, or all the testing packets are lost.
+///
+/// See [Section 13.4.2](https://www.rfc-editor.org/rfc/rfc9000#section-13.4.2) and
+/// [Appendix A.4](https://www.rfc-editor.org/rfc/rfc9000#appendix-A.4) of RFC 9000.
+pub(crate) struct EcnValidator {
+    state: ECNState,
+    // Whether the packets being sent are marked ECT(0), decided before a burst of packets.
+    marking: bool,
+    testing_sent: usize,
+    testing_lost: usize,
+    // The ECN counts of the last validated ACK frame in each packet number space.
+    counts: [EcnCounts; Epoch::count()],
+}
+
+impl EcnValidator {
+    pub(crate) fn new() -> Self {
+        qevent::event!(ECNStateUpdated {
+            new: ECNState::Testing
+        });
+        Self {
+            state: ECNState::Testing,
+            marking: false,
+            testing_sent: 0,
+            testing_lost: 0,
+            counts: Default::default(),
+        }
+    }
+
+    fn set_state(&mut self, state: ECNState) {
+        qevent::event!(ECNStateUpdated {
+            old: self.state,
+            new: state
+        });
+        self.state = state;
+    }
+
+    fn fail(&mut self, reason: &str) {
+        tracing::debug!("   Cause by: ECN validation failed, {reason}");
+        self.set_state(ECNState::Failed);
+    }
+
+    /// Decide the ECN codepoint of the packets to send next.
+    pub(crate) fn codepoint(&mut self) -> Option<Ecn> {
+        self.marking = matches!(self.state, ECNState::Testing | ECNState::Capable);
+        self.marking.then_some(Ecn::Ect0)
+    }
+
+    /// Returns whether the sent packet is marked ECT(0).
+    pub(crate) fn on_packet_sent(&mut self) -> bool {
+        if self.marking && self.state == ECNState::Testing {
+            self.testing_sent += 1;
+            if self.testing_sent >= TESTING_PACKETS {
+                self.set_state(ECNState::Unknown);
+            }
+        }
+        self.marking
+    }
+
+    /// Called with the number of newly lost packets marked ECT(0).
+    pub(crate) fn on_packets_lost(&mut self, marked: usize) {
+        if !matches!(self.state, ECNState::Testing | ECNState::Unknown) {
+            return;
+        }
+        self.testing_lost += marked;
+        if self.state == ECNState::Unknown && self.testing_lost >= self.testing_sent {
+            self.fail("all testing packets are lost");
+        }
+    }
+
+    /// Validate the ECN counts of an ACK frame that newly acknowledges its largest packet,
+    /// `newly_acked_marked` packets marked ECT(0) are newly acknowledged by the frame.
+    ///
+    /// Returns whether the counts are valid, and the congestion controller should respond to
+    /// the CE count.
+    pub(crate) fn on_ack_rcvd(
+        &mut self,
+        epoch: Epoch,
+        ecn: Option<EcnCounts>,
+        newly_acked_marked: usize,
+    ) -> bool {
+        if self.state == ECNState::Failed {
+            return false;
+        }
+        let Some(counts) = ecn else {
+            if newly_acked_marked > 0 {
+                self.fail("the ECN counts are absent");
+            }
+            return false;
+        };
+        let last = self.counts[epoch];
+        if counts.ect0() < last.ect0() || counts.ect1() < last.ect1() || counts.ce() < last.ce() {
+            self.fail("the ECN counts decrease");
+            return false;
+        }
+        // The counts also include the packets sent on other paths, which can only increase them
+        if counts.ect0() - last.ect0() + counts.ce() - last.ce() < newly_acked_marked as u64 {
+            self.fail("the ECT(0) marking is bleached");
+            return false;
+        }
+        if counts.ect1() > last.ect1() {
+            self.fail("the ECT(0) marking is mangled into ECT(1)");
+            return false;
+        }
+        self.counts[epoch] = counts;
+        if newly_acked_marked > 0 && self.state != ECNState::Capable {
+            self.set_state(ECNState::Capable);
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counts(ect0: u32, ect1: u32, ce: u32) -> Option<EcnCounts> {
+        Some(EcnCounts::new(ect0.into(), ect1.into(), ce.into()))
+    }
+
+    fn send_testing_packets(validator: &mut EcnValidator) {
+        assert_eq!(validator.codepoint(), Some(Ecn::Ect0));
+        for _ in 0..TESTING_PACKETS {
+            assert!(validator.on_packet_sent());
+        }
+        assert_eq!(validator.state, ECNState::Unknown);
+        assert_eq!(validator.codepoint(), None);
+        assert!(!validator.on_packet_sent());
+    }
+
+    #[test]
+    fn capable() {
+        let mut validator = EcnValidator::new();
+        send_testing_packets(&mut validator);
+
+        assert!(validator.on_ack_rcvd(Epoch::Data, counts(3, 0, 1), 4));
+        assert_eq!(validator.state, ECNState::Capable);
+        assert_eq!(validator.codepoint(), Some(Ecn::Ect0));
+
+        // ACK-only packets are not marked
+        assert!(validator.on_ack_rcvd(Epoch::Data, counts(3, 0, 1), 0));
+        assert!(validator.on_ack_rcvd(Epoch::Data, counts(5, 0, 2), 3));
+        assert_eq!(validator.state, ECNState::Capable);
+    }
+
+    #[test]
+    fn bleached() {
+        let mut validator = EcnValidator::new();
+        send_testing_packets(&mut validator);
+
+        assert!(!validator.on_ack_rcvd(Epoch::Data, None, 2));
+        assert_eq!(validator.state, ECNState::Failed);
+        assert_eq!(validator.codepoint(), None);
+
+        let mut validator = EcnValidator::new();
+        send_testing_packets(&mut validator);
+        assert!(!validator.on_ack_rcvd(Epoch::Data, counts(1, 0, 0), 2));
+        assert_eq!(validator.state, ECNState::Failed);
+    }
+
+    #[test]
+    fn mangled() {
+        let mut validator = EcnValidator::new();
+        send_testing_packets(&mut validator);
+        assert!(validator.on_ack_rcvd(Epoch::Data, counts(2, 0, 0), 2));
+        assert!(!validator.on_ack_rcvd(Epoch::Data, counts(1, 0, 0), 0));
+        assert_eq!(validator.state, ECNState::Failed);
+
+        let mut validator = EcnValidator::new();
+        send_testing_packets(&mut validator);
+        assert!(!validator.on_ack_rcvd(Epoch::Data, counts(0, 2, 0), 2));
+        assert_eq!(validator.state, ECNState::Failed);
+    }
+
+    #[test]
+    fn testing_packets_lost() {
+        let mut validator = EcnValidator::new();
+        send_testing_packets(&mut validator);
+        validator.on_packets_lost(TESTING_PACKETS - 1);
+        assert_eq!(validator.state, ECNState::Unknown);
+        validator.on_packets_lost(1);
+        assert_eq!(validator.state, ECNState::Failed);
+    }
+}
@@ -1,19 +1,28 @@
-use qbase::{Epoch, frame::AckFrame, net::tx::Signals};
+use qbase::{
+    Epoch,
+    frame::AckFrame,
+    net::{route::Ecn, tx::Signals},
+};
 use qevent::quic::recovery::PacketLostTrigger;
 use tokio::{
     task::AbortHandle,
     time::{Duration, Instant},
 };
 
+mod ack_frequency;
+pub use ack_frequency::{AckFrequencyRequester, ArcAckFrequency};
 mod algorithm;
-pub use algorithm::Algorithm;
+pub use algorithm::{Algorithm, Control, ProductCongestionController};
 mod congestion;
-pub use congestion::ArcCC;
+pub use congestion::{ArcCC, CongestionStats};
+mod ecn;
 mod pacing;
 mod packets;
+pub use packets::SentPacket;
 mod rtt;
+pub use rtt::ArcRtt;
 mod status;
-pub use status::{HandshakeStatus, PathStatus};
+pub use status::{HandshakeStatus, PathStatus, Pmtu};
 
 /// default datagram size in bytes.
 pub const MSS: usize = 1200;
@@ -27,6 +36,13 @@ pub trait Transport {
 
     fn retransmit_and_expire_time(&self, epoch: Epoch) -> (Duration, Duration);
 
+    /// Returns the ECN codepoint to mark the next burst of packets with, [`None`] if the path
+    /// is not known to support ECN.
+    ///
+    /// The packets sent after this call are recorded as marked with the codepoint, which are
+    /// used to validate the ECN counts in the acknowledgements.
+    fn ecn_codepoint(&self) -> Option<Ecn>;
+
     /// Records the sending of a packet, which may affect congestion control state.
     /// # Parameters
     /// - `pn`: The packet number of the sent packet.
@@ -67,6 +83,10 @@ pub trait Transport {
 
     fn discard_epoch(&self, epoch: Epoch);
 
+    /// Declares all the packets in flight lost, called when the path no longer carries the
+    /// data, so that the frames in them are sent on the other paths.
+    fn abandon_in_flight(&self);
+
     fn grant_anti_amplification(&self);
 }
 
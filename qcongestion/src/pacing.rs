@@ -57,12 +57,7 @@ impl Pacer {
         self.cwnd = cwnd;
         self.rate = rate;
 
-        let rate = match rate {
-            Some(r) => r,
-            // RFC 9002 7.7. Pacing
-            // rate = N * congestion_window / smoothed_rtt
-            None => (N * cwnd as f64 / srtt.as_secs_f64()) as usize,
-        };
+        let rate = rate_of(srtt, cwnd, rate);
 
         // Update the last_burst_time and tokens
         let elapsed = now.duration_since(self.last_burst_time);
@@ -96,6 +91,16 @@ impl Pacer {
     }
 }
 
+/// The pacing rate in bytes per second, the `rate` of the congestion controller if provided.
+pub(super) fn rate_of(srtt: Duration, cwnd: usize, rate: Option<usize>) -> usize {
+    match rate {
+        Some(r) => r,
+        // RFC 9002 7.7. Pacing
+        // rate = N * congestion_window / smoothed_rtt
+        None => (N * cwnd as f64 / srtt.as_secs_f64()) as usize,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1,9 +1,9 @@
-use std::{cmp::Ordering, collections::VecDeque, time::Duration};
+use std::{cmp::Ordering, collections::VecDeque, ops::Range, time::Duration};
 
 use qbase::{Epoch, frame::AckFrame};
 use tokio::time::Instant;
 
-use crate::algorithm::Control;
+use crate::{MSS, ack_frequency::ArcAckFrequency, algorithm::Control};
 
 #[derive(Default, PartialEq, Eq, Clone, Debug)]
 pub(crate) enum State {
@@ -21,6 +21,19 @@ pub struct SentPacket {
     pub(crate) sent_bytes: usize,
     pub(crate) state: State,
     pub(crate) count_for_cc: bool,
+    // Whether the packet is larger than the PMTU when sent, its loss says nothing about the
+    // congestion nor the black hole.
+    pub(crate) is_mtu_probe: bool,
+    // Whether the packet is marked ECT(0), its acknowledgement validates the ECN counts.
+    pub(crate) is_ecn_marked: bool,
+    // Delivery rate estimation states, filled by the algorithm when the packet is sent.
+    // See [Delivery Rate Estimation](https://datatracker.ietf.org/doc/html/draft-cheng-iccrg-delivery-rate-estimation)
+    pub(crate) first_sent_time: Instant,
+    pub(crate) delivered_time: Instant,
+    pub(crate) delivered: usize,
+    pub(crate) is_app_limited: bool,
+    pub(crate) tx_in_flight: usize,
+    pub(crate) lost: u64,
 }
 
 impl SentPacket {
@@ -38,8 +51,44 @@ impl SentPacket {
             count_for_cc,
             sent_bytes,
             state: State::Inflight,
+            is_mtu_probe: false,
+            is_ecn_marked: false,
+            first_sent_time: time_sent,
+            delivered_time: time_sent,
+            delivered: 0,
+            is_app_limited: false,
+            tx_in_flight: 0,
+            lost: 0,
         }
     }
+
+    pub fn packet_number(&self) -> u64 {
+        self.packet_number
+    }
+
+    pub fn time_sent(&self) -> Instant {
+        self.time_sent
+    }
+
+    pub fn is_ack_eliciting(&self) -> bool {
+        self.ack_eliciting
+    }
+
+    /// The size of the packet in bytes.
+    pub fn sent_bytes(&self) -> usize {
+        self.sent_bytes
+    }
+
+    /// Whether the packet counts toward bytes in flight, only ACK-only packets do not.
+    pub fn count_for_cc(&self) -> bool {
+        self.count_for_cc
+    }
+
+    /// Whether the packet has been declared lost before, and no longer counts toward
+    /// bytes in flight.
+    pub fn is_declared_lost(&self) -> bool {
+        self.state == State::Retransmitted
+    }
 }
 
 impl PartialOrd for SentPacket {
@@ -70,36 +119,101 @@ pub(crate) struct RcvdRecords {
     latest_rcvd_time: Option<Instant>,
     largest_rcvd_packet: Option<(u64, Instant)>,
     max_ack_delay: Duration,
+    ack_frequency: ArcAckFrequency,
+    // The number of ack-eliciting packets received since the last ACK frame was sent.
+    unacked_ack_eliciting: u64,
+    // The largest packet number ever received, and the missing packets below it which have
+    // not been reported by an ACK frame yet, used to apply the reordering threshold.
+    largest_pn: Option<u64>,
+    unreported_missing: VecDeque<Range<u64>>,
 }
 
 impl RcvdRecords {
-    pub(crate) fn new(epoch: Epoch, max_ack_delay: Duration) -> Self {
+    pub(crate) fn new(
+        epoch: Epoch,
+        max_ack_delay: Duration,
+        ack_frequency: ArcAckFrequency,
+    ) -> Self {
         Self {
             epoch,
             ack_immedietly: false,
             latest_rcvd_time: None,
             largest_rcvd_packet: None,
             max_ack_delay,
+            ack_frequency,
+            unacked_ack_eliciting: 0,
+            largest_pn: None,
+            unreported_missing: VecDeque::new(),
+        }
+    }
+
+    fn track_missing(&mut self, pn: u64) {
+        match self.largest_pn {
+            Some(largest) if pn > largest + 1 => {
+                self.unreported_missing.push_back(largest + 1..pn);
+                self.largest_pn = Some(pn);
+            }
+            Some(largest) if pn < largest => {
+                if let Some(idx) = self
+                    .unreported_missing
+                    .iter()
+                    .position(|range| range.contains(&pn))
+                {
+                    let range = self.unreported_missing.remove(idx).unwrap();
+                    if pn + 1 < range.end {
+                        self.unreported_missing.insert(idx, pn + 1..range.end);
+                    }
+                    if range.start < pn {
+                        self.unreported_missing.insert(idx, range.start..pn);
+                    }
+                }
+            }
+            _ => self.largest_pn = self.largest_pn.max(Some(pn)),
         }
     }
 
-    pub(crate) fn on_pkt_rcvd(&mut self, pn: u64) {
+    pub(crate) fn on_pkt_rcvd(&mut self, pn: u64, is_ack_eliciting: bool) {
+        self.track_missing(pn);
+        if !is_ack_eliciting {
+            return;
+        }
         // An endpoint MUST acknowledge all ack-eliciting Initial and Handshake packets immediately
         if self.epoch == Epoch::Initial || self.epoch == Epoch::Handshake {
             self.ack_immedietly = true;
         }
-        // See [Section 13.2.1](https://www.rfc-editor.org/rfc/rfc9000.html#name-sending-ack-frames)
-        // An endpoint SHOULD generate and send an ACK frame without delay when it receives an ack-eliciting packet either:
-        // 1. When the received packet has a packet number less than another ack-eliciting packet that has been received
-        // 2. when the packet has a packet number larger than the highest-numbered ack-eliciting packet that has been
-        // received and there are missing packets between that packet and this packet.
         let now = Instant::now();
         if self.latest_rcvd_time.is_none() {
             self.latest_rcvd_time = Some(now);
         }
-        self.ack_immedietly |= self
-            .largest_rcvd_packet
-            .is_some_and(|(largest_pn, _)| pn < largest_pn);
+
+        // See [Section 6](https://datatracker.ietf.org/doc/html/draft-ietf-quic-ack-frequency#name-ack-frequency-frame-handling)
+        // of QUIC Acknowledgment Frequency. Until the peer requests, the packets are acknowledged
+        // after max_ack_delay, or immediately when they arrive out of order.
+        let frequency = self.ack_frequency.get();
+        self.unacked_ack_eliciting += 1;
+        self.ack_immedietly |= frequency.is_some_and(|frequency| {
+            self.unacked_ack_eliciting > frequency.ack_eliciting_threshold
+        });
+        match frequency.map_or(1, |frequency| frequency.reordering_threshold) {
+            0 => {}
+            // See [Section 13.2.1](https://www.rfc-editor.org/rfc/rfc9000.html#name-sending-ack-frames)
+            // An endpoint SHOULD generate and send an ACK frame without delay when it receives an ack-eliciting packet either:
+            // 1. When the received packet has a packet number less than another ack-eliciting packet that has been received
+            // 2. when the packet has a packet number larger than the highest-numbered ack-eliciting packet that has been
+            // received and there are missing packets between that packet and this packet.
+            1 => {
+                self.ack_immedietly |= self.largest_pn.is_some_and(|largest| pn < largest);
+                self.ack_immedietly |= !self.unreported_missing.is_empty();
+            }
+            // Acknowledge immediately once the sender would declare a missing packet lost.
+            threshold => {
+                self.ack_immedietly |= self
+                    .unreported_missing
+                    .front()
+                    .zip(self.largest_pn)
+                    .is_some_and(|(missing, largest)| largest - missing.start >= threshold);
+            }
+        }
 
         self.largest_rcvd_packet =
             self.largest_rcvd_packet
@@ -112,6 +226,11 @@ impl RcvdRecords {
                 });
     }
 
+    /// Acknowledges the received packets without delay, requested by an IMMEDIATE_ACK frame.
+    pub(crate) fn ack_immediately(&mut self) {
+        self.ack_immedietly = true;
+    }
+
     /// Checks whether an ACK frame needs to be sent.
     /// Returns [`Some`] if it's time to send an ACK based on the maximum delay.
     pub(crate) fn need_ack(&self) -> Option<(u64, Instant)> {
@@ -120,9 +239,17 @@ impl RcvdRecords {
             return self.largest_rcvd_packet;
         }
 
+        let max_ack_delay = match self.epoch {
+            Epoch::Data => self
+                .ack_frequency
+                .get()
+                .map(|frequency| frequency.max_ack_delay),
+            _ => None,
+        }
+        .unwrap_or(self.max_ack_delay);
         if self
             .latest_rcvd_time
-            .is_some_and(|t| t + self.max_ack_delay < now)
+            .is_some_and(|t| t + max_ack_delay < now)
         {
             return self.largest_rcvd_packet;
         }
@@ -135,6 +262,8 @@ impl RcvdRecords {
         self.largest_rcvd_packet = None;
         self.latest_rcvd_time = None;
         self.ack_immedietly = false;
+        self.unacked_ack_eliciting = 0;
+        self.unreported_missing.clear();
     }
 }
 
@@ -145,21 +274,48 @@ pub(crate) struct PacketSpace {
     pub(crate) loss_time: Option<Instant>,
     pub(crate) sent_packets: VecDeque<SentPacket>,
     pub(crate) rcvd_packets: RcvdRecords,
+    // The number of packets larger than the base PMTU lost since such a packet was acked last,
+    // used to detect the black hole.
+    pub(crate) large_packets_lost: usize,
+    // The number of packets marked ECT(0) lost since last checked, used to validate ECN.
+    pub(crate) ecn_marked_lost: usize,
+    pub(crate) counters: PacketCounters,
+}
+
+/// The packets sent in a packet number space, and their fates, since the path was created.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct PacketCounters {
+    pub(crate) sent_packets: u64,
+    pub(crate) sent_bytes: u64,
+    // Declared lost by the loss detection
+    pub(crate) lost_packets: u64,
+    pub(crate) lost_bytes: u64,
+    // Still in flight when the path was abandoned
+    pub(crate) abandoned_packets: u64,
 }
 
 pub(crate) struct NewlyAckedPackets {
     pub(crate) include_ack_eliciting: bool,
+    // The number of newly acked packets marked ECT(0).
+    pub(crate) ecn_marked: usize,
     pub(crate) largest: (u64, Instant),
 }
 
 impl PacketSpace {
-    pub(crate) fn with_epoch(epoch: Epoch, max_ack_delay: Duration) -> Self {
+    pub(crate) fn with_epoch(
+        epoch: Epoch,
+        max_ack_delay: Duration,
+        ack_frequency: ArcAckFrequency,
+    ) -> Self {
         Self {
             largest_acked_packet: None,
             time_of_last_ack_eliciting_packet: None,
             loss_time: None,
             sent_packets: VecDeque::with_capacity(4),
-            rcvd_packets: RcvdRecords::new(epoch, max_ack_delay),
+            rcvd_packets: RcvdRecords::new(epoch, max_ack_delay, ack_frequency),
+            large_packets_lost: 0,
+            ecn_marked_lost: 0,
+            counters: PacketCounters::default(),
         }
     }
 
@@ -176,6 +332,7 @@ impl PacketSpace {
             return None;
         }
         let mut include_ack_eliciting = false;
+        let mut ecn_marked = 0;
         let mut largest_acked = None;
         let mut index = self
             .sent_packets
@@ -191,8 +348,12 @@ impl PacketSpace {
                     && self.sent_packets[index].state != State::Acked
                 {
                     algorithm.on_packet_acked(&self.sent_packets[index]);
+                    if self.sent_packets[index].sent_bytes > MSS {
+                        self.large_packets_lost = 0;
+                    }
                     self.sent_packets[index].state = State::Acked;
                     include_ack_eliciting |= self.sent_packets[index].ack_eliciting;
+                    ecn_marked += self.sent_packets[index].is_ecn_marked as usize;
                     largest_acked = largest_acked
                         .map(|(n, t)| {
                             if n < pn {
@@ -216,10 +377,20 @@ impl PacketSpace {
 
         Some(NewlyAckedPackets {
             include_ack_eliciting,
+            ecn_marked,
             largest: largest_acked?,
         })
     }
 
+    /// The bytes of the packets in flight, which count toward the congestion window.
+    pub(crate) fn bytes_in_flight(&self) -> usize {
+        self.sent_packets
+            .iter()
+            .filter(|sent| sent.state == State::Inflight && sent.count_for_cc)
+            .map(|sent| sent.sent_bytes)
+            .sum()
+    }
+
     pub(crate) fn no_ack_eliciting_in_flight(&self) -> bool {
         self.sent_packets
             .iter()
@@ -249,8 +420,13 @@ impl PacketSpace {
             .enumerate()
             .take_while(move |(_, pkt)| pkt.packet_number <= largest_acked)
             .filter(|(_, pkt)| pkt.state == State::Inflight)
-            .map(move |(idx, unacked)| {
+            .map(|(idx, unacked)| {
                 if unacked.time_sent < lost_sent_time || largest_index >= idx + packet_threshold {
+                    // See [Section 14.4](https://www.rfc-editor.org/rfc/rfc9000#section-14.4) of RFC 9000,
+                    // the loss of a PMTU probe should not trigger a congestion control reaction.
+                    if unacked.is_mtu_probe {
+                        algorithm.remove_from_bytes_in_flight(&mut core::iter::once(&*unacked));
+                    }
                     unacked.state = State::Retransmitted;
                     Ok((idx, &*unacked))
                 } else {
@@ -280,16 +456,51 @@ impl PacketSpace {
             })
             .is_err();
 
+        self.large_packets_lost += loss
+            .iter()
+            .filter(|(_, pkt)| !pkt.is_mtu_probe && pkt.sent_bytes > MSS)
+            .count();
+        self.ecn_marked_lost += loss.iter().filter(|(_, pkt)| pkt.is_ecn_marked).count();
+        self.counters.lost_packets += loss.len() as u64;
+        self.counters.lost_bytes += loss
+            .iter()
+            .map(|(_, pkt)| pkt.sent_bytes as u64)
+            .sum::<u64>();
+
         let (packet_numbers, loss_packet): (Vec<_>, Vec<_>) = loss
             .into_iter()
             .map(|(_, pkt)| (pkt.packet_number, pkt))
             .unzip();
-        if !loss_packet.is_empty() {
-            algorithm.on_packets_lost(&mut loss_packet.into_iter(), persistent_lost);
+        let mut loss_packet = loss_packet
+            .into_iter()
+            .filter(|pkt| !pkt.is_mtu_probe)
+            .peekable();
+        if loss_packet.peek().is_some() {
+            algorithm.on_packets_lost(&mut loss_packet, persistent_lost);
         }
         packet_numbers.into_iter()
     }
 
+    /// Declare all the packets in flight lost, returns their packet numbers.
+    ///
+    /// The path is abandoned rather than congested, so the packets are only removed from the
+    /// bytes in flight, without a congestion reaction.
+    pub(crate) fn abandon_in_flight(&mut self, algorithm: &mut Box<dyn Control>) -> Vec<u64> {
+        let abandoned = self
+            .sent_packets
+            .iter_mut()
+            .filter(|sent| sent.state == State::Inflight)
+            .map(|sent| {
+                sent.state = State::Retransmitted;
+                &*sent
+            })
+            .collect::<Vec<_>>();
+        algorithm.remove_from_bytes_in_flight(&mut abandoned.iter().copied());
+        self.counters.abandoned_packets += abandoned.len() as u64;
+        self.loss_time = None;
+        abandoned.iter().map(|sent| sent.packet_number).collect()
+    }
+
     pub(crate) fn discard(&mut self, algorithm: &mut Box<dyn Control>) {
         let mut remove_from_inflight = self
             .sent_packets
@@ -315,7 +526,11 @@ mod tests {
 
     #[test]
     fn test_packet_space() {
-        let mut packet_space = PacketSpace::with_epoch(Epoch::Initial, Duration::from_millis(100));
+        let mut packet_space = PacketSpace::with_epoch(
+            Epoch::Initial,
+            Duration::from_millis(100),
+            ArcAckFrequency::default(),
+        );
         // let now = Instant::now();
 
         for i in 0..10 {
@@ -385,25 +600,192 @@ mod tests {
         assert_eq!(reno.congestion_window(), (20817 - 1200) / 2);
     }
 
+    #[test]
+    fn test_large_packets_lost() {
+        let mut packet_space = PacketSpace::with_epoch(
+            Epoch::Data,
+            Duration::from_millis(100),
+            ArcAckFrequency::default(),
+        );
+        for i in 0..6 {
+            let mut sent = SentPacket::new(i, Instant::now(), true, true, 1400);
+            // the packet 1 is a probe larger than the PMTU
+            sent.is_mtu_probe = i == 1;
+            packet_space.sent_packets.push_back(sent);
+        }
+        let mut reno: Box<dyn Control> = Box::new(NewReno::new(Arc::new(AtomicU16::new(1400))));
+        for sent in packet_space.sent_packets.iter_mut() {
+            reno.on_packet_sent_cc(sent);
+        }
+
+        // ack 5 ~ 4, loss 0 ~ 2, the lost probe is not counted
+        let ack_frame = AckFrame::new(5_u32.into(), 100_u32.into(), 1_u32.into(), vec![], None);
+        packet_space.on_ack_rcvd(&ack_frame, &mut reno);
+        let cwnd = reno.congestion_window();
+        packet_space.largest_acked_packet = Some(ack_frame.largest());
+        let loss = packet_space.detect_lost_packets(Duration::from_millis(100), 3, &mut reno);
+        assert_eq!(loss.collect::<Vec<_>>(), vec![0, 1, 2]);
+        assert_eq!(packet_space.large_packets_lost, 2);
+        // the loss of non-probe packets still reduces the congestion window
+        assert!(reno.congestion_window() < cwnd);
+
+        // the ack of a large packet resets the counter
+        packet_space
+            .sent_packets
+            .push_back(SentPacket::new(6, Instant::now(), true, true, 1400));
+        let ack_frame = AckFrame::new(6_u32.into(), 100_u32.into(), 0_u32.into(), vec![], None);
+        packet_space.on_ack_rcvd(&ack_frame, &mut reno);
+        assert_eq!(packet_space.large_packets_lost, 0);
+    }
+
+    #[test]
+    fn test_packet_counters() {
+        let mut packet_space = PacketSpace::with_epoch(
+            Epoch::Data,
+            Duration::from_millis(100),
+            ArcAckFrequency::default(),
+        );
+        for i in 0..8 {
+            // the packet 7 only carries ACK frames
+            let count_for_cc = i != 7;
+            let sent = SentPacket::new(i, Instant::now(), count_for_cc, count_for_cc, 1000);
+            packet_space.sent_packets.push_back(sent);
+        }
+        let mut reno: Box<dyn Control> = Box::new(NewReno::new(Arc::new(AtomicU16::new(1200))));
+        assert_eq!(packet_space.bytes_in_flight(), 7000);
+
+        // ack 5 ~ 3, loss 0 ~ 2
+        let ack_frame = AckFrame::new(5_u32.into(), 100_u32.into(), 2_u32.into(), vec![], None);
+        packet_space.on_ack_rcvd(&ack_frame, &mut reno);
+        packet_space.largest_acked_packet = Some(ack_frame.largest());
+        let loss = packet_space.detect_lost_packets(Duration::from_millis(100), 3, &mut reno);
+        assert_eq!(loss.count(), 3);
+        assert_eq!(packet_space.counters.lost_packets, 3);
+        assert_eq!(packet_space.counters.lost_bytes, 3000);
+        assert_eq!(packet_space.bytes_in_flight(), 1000);
+
+        let abandoned = packet_space.abandon_in_flight(&mut reno);
+        assert_eq!(abandoned, vec![6, 7]);
+        assert_eq!(packet_space.counters.abandoned_packets, 2);
+        assert_eq!(packet_space.bytes_in_flight(), 0);
+    }
+
     #[tokio::test(flavor = "current_thread")]
     async fn test_rcvd_records() {
-        let mut rcvd_records = RcvdRecords::new(Epoch::Data, Duration::from_millis(100));
-        for i in 0..10 {
-            rcvd_records.on_pkt_rcvd(i);
-        }
+        let mut rcvd_records = RcvdRecords::new(
+            Epoch::Data,
+            Duration::from_millis(100),
+            ArcAckFrequency::default(),
+        );
+        rcvd_records.on_pkt_rcvd(0, true);
+        assert_eq!(rcvd_records.need_ack(), None);
 
         tokio::time::pause();
         tokio::time::advance(Duration::from_millis(100)).await;
-        assert_eq!(rcvd_records.need_ack().unwrap().0, 9);
-        rcvd_records.on_ack_sent(9, 9);
+        assert_eq!(rcvd_records.need_ack().unwrap().0, 0);
+        rcvd_records.on_ack_sent(0, 0);
+        assert_eq!(rcvd_records.need_ack(), None);
+
+        tokio::time::resume();
+        // in order packets are acknowledged after max_ack_delay
+        rcvd_records.on_pkt_rcvd(1, true);
+        rcvd_records.on_pkt_rcvd(2, false);
+        rcvd_records.on_pkt_rcvd(3, true);
         assert_eq!(rcvd_records.need_ack(), None);
+        rcvd_records.on_ack_sent(3, 3);
+
+        // a missing packet triggers an ACK
+        rcvd_records.on_pkt_rcvd(5, true);
+        assert_eq!(rcvd_records.need_ack().unwrap().0, 5);
+        rcvd_records.on_ack_sent(5, 5);
 
+        // so does a packet arriving out of order
+        rcvd_records.on_pkt_rcvd(4, true);
+        assert_eq!(rcvd_records.need_ack().unwrap().0, 4);
+    }
+
+    #[tokio::test]
+    async fn test_rcvd_records_with_ack_frequency() {
+        use qbase::{
+            frame::{AckFrequencyFrame, ReceiveFrame},
+            varint::VarInt,
+        };
+
+        use crate::ack_frequency::ArcAckFrequency;
+
+        let ack_frequency = ArcAckFrequency::new(Some(Duration::from_millis(1)));
+        ack_frequency
+            .recv_frame(&AckFrequencyFrame::new(
+                VarInt::from_u32(0),
+                VarInt::from_u32(3),
+                VarInt::from_u32(50_000),
+                VarInt::from_u32(3),
+            ))
+            .unwrap();
+        let mut rcvd_records =
+            RcvdRecords::new(Epoch::Data, Duration::from_millis(25), ack_frequency);
+
+        // the requested max_ack_delay overrides the local one
+        rcvd_records.on_pkt_rcvd(0, true);
+        tokio::time::pause();
+        tokio::time::advance(Duration::from_millis(30)).await;
+        assert_eq!(rcvd_records.need_ack(), None);
+        tokio::time::advance(Duration::from_millis(30)).await;
+        assert_eq!(rcvd_records.need_ack().unwrap().0, 0);
+        rcvd_records.on_ack_sent(0, 0);
         tokio::time::resume();
-        rcvd_records.on_pkt_rcvd(10);
+
+        // up to 3 ack-eliciting packets are received without acknowledging
+        for pn in 1..4 {
+            rcvd_records.on_pkt_rcvd(pn, true);
+            assert_eq!(rcvd_records.need_ack(), None);
+        }
+        rcvd_records.on_pkt_rcvd(4, true);
+        assert_eq!(rcvd_records.need_ack().unwrap().0, 4);
+        rcvd_records.on_ack_sent(4, 4);
+
+        // the missing packet 5 is tolerated until an ack-eliciting packet 3 beyond it arrives
+        rcvd_records.on_pkt_rcvd(6, true);
+        rcvd_records.on_pkt_rcvd(7, true);
+        assert_eq!(rcvd_records.need_ack(), None);
+        rcvd_records.on_pkt_rcvd(8, false);
         assert_eq!(rcvd_records.need_ack(), None);
-        rcvd_records.on_pkt_rcvd(15);
+        rcvd_records.on_pkt_rcvd(9, true);
+        assert_eq!(rcvd_records.need_ack().unwrap().0, 9);
+        rcvd_records.on_ack_sent(9, 9);
+
+        // the reordered packet 5 arrives
+        rcvd_records.on_pkt_rcvd(5, true);
         assert_eq!(rcvd_records.need_ack(), None);
-        rcvd_records.on_pkt_rcvd(11);
-        assert_eq!(rcvd_records.need_ack().unwrap().0, 15);
+    }
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn test_track_missing() {
+        let mut rcvd_records = RcvdRecords::new(
+            Epoch::Data,
+            Duration::from_millis(25),
+            ArcAckFrequency::default(),
+        );
+        rcvd_records.on_pkt_rcvd(0, false);
+        rcvd_records.on_pkt_rcvd(5, false);
+        assert_eq!(rcvd_records.unreported_missing, [1..5]);
+
+        // the reordered packets split or shrink the missing ranges
+        rcvd_records.on_pkt_rcvd(3, false);
+        assert_eq!(rcvd_records.unreported_missing, [1..3, 4..5]);
+        rcvd_records.on_pkt_rcvd(1, false);
+        rcvd_records.on_pkt_rcvd(4, false);
+        assert_eq!(rcvd_records.unreported_missing, [2..3]);
+        rcvd_records.on_pkt_rcvd(8, false);
+        assert_eq!(rcvd_records.unreported_missing, [2..3, 6..8]);
+        rcvd_records.on_pkt_rcvd(2, false);
+        assert_eq!(rcvd_records.unreported_missing, [6..8]);
+        assert_eq!(rcvd_records.largest_pn, Some(8));
+
+        // duplicated packets change nothing
+        rcvd_records.on_pkt_rcvd(8, false);
+        rcvd_records.on_pkt_rcvd(2, false);
+        assert_eq!(rcvd_records.unreported_missing, [6..8]);
     }
 }
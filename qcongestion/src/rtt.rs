@@ -42,13 +42,15 @@ impl Default for Rtt {
 }
 
 impl Rtt {
+    /// Returns the RTT sample adjusted for the acknowledgment delay.
     fn update(
         &mut self,
         latest_rtt: Duration,
         mut ack_delay: Duration,
         is_handshake_confirmed: bool,
-    ) {
+    ) -> Duration {
         self.latest_rtt = latest_rtt;
+        let mut adjusted_rtt = latest_rtt;
         if self.first_rtt_sample.is_none() {
             self.min_rtt = latest_rtt;
             self.smoothed_rtt = latest_rtt;
@@ -64,7 +66,6 @@ impl Rtt {
             }
 
             // Adjust for acknowledgment delay if plausible.
-            let mut adjusted_rtt = latest_rtt;
             if latest_rtt >= self.min_rtt + ack_delay {
                 adjusted_rtt = latest_rtt - ack_delay;
             }
@@ -80,6 +81,7 @@ impl Rtt {
 
         let event = RecoveryMetricsUpdated::from(&*self);
         qevent::event!(event);
+        adjusted_rtt
     }
 
     fn loss_delay(&self) -> Duration {
@@ -96,26 +98,44 @@ impl Rtt {
     }
 }
 
+/// The RTT estimation of a path, see [RFC 9002 Section 5](https://www.rfc-editor.org/rfc/rfc9002.html#name-estimating-the-round-trip-t).
+///
+/// It is updated by the loss detection of the path, and read-only outside of this crate.
 #[derive(Debug, Clone, Default)]
 pub struct ArcRtt(Arc<Mutex<Rtt>>);
 
 /// 对外只需暴露ArcRtt，Rtt成为内部实现
 impl ArcRtt {
-    pub fn new() -> Self {
+    pub(crate) fn new() -> Self {
         Self(Arc::new(Mutex::new(Rtt::default())))
     }
 
-    pub fn update(&self, latest_rtt: Duration, ack_delay: Duration, is_handshake_confirmed: bool) {
+    pub(crate) fn update(
+        &self,
+        latest_rtt: Duration,
+        ack_delay: Duration,
+        is_handshake_confirmed: bool,
+    ) -> Duration {
         self.0
             .lock()
             .unwrap()
-            .update(latest_rtt, ack_delay, is_handshake_confirmed);
+            .update(latest_rtt, ack_delay, is_handshake_confirmed)
     }
 
-    pub fn loss_delay(&self) -> Duration {
+    pub(crate) fn loss_delay(&self) -> Duration {
         self.0.lock().unwrap().loss_delay()
     }
 
+    /// The most recent RTT sample.
+    pub fn latest_rtt(&self) -> Duration {
+        self.0.lock().unwrap().latest_rtt
+    }
+
+    /// The minimum RTT observed, zero if there is no RTT sample yet.
+    pub fn min_rtt(&self) -> Duration {
+        self.0.lock().unwrap().min_rtt
+    }
+
     pub fn smoothed_rtt(&self) -> Duration {
         self.0.lock().unwrap().smoothed_rtt
     }
@@ -124,10 +144,27 @@ impl ArcRtt {
         self.0.lock().unwrap().rttvar
     }
 
-    pub fn base_pto(&self, pto_count: u32) -> Duration {
+    pub(crate) fn base_pto(&self, pto_count: u32) -> Duration {
         self.0.lock().unwrap().base_pto(pto_count)
     }
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_adjusted_rtt_sample() {
+        let ms = Duration::from_millis;
+        let rtt = ArcRtt::new();
+        // the first sample is not adjusted
+        assert_eq!(rtt.update(ms(100), ms(20), false), ms(100));
+        assert_eq!(rtt.update(ms(120), ms(20), false), ms(100));
+        // the ack delay is implausible if the sample would be less than min_rtt
+        assert_eq!(rtt.update(ms(110), ms(20), false), ms(110));
+        // the ack delay is limited by max_ack_delay after the handshake is confirmed
+        assert_eq!(rtt.update(ms(120), ms(20), true), ms(120));
+        assert_eq!(rtt.min_rtt(), ms(100));
+        assert_eq!(rtt.latest_rtt(), ms(120));
+    }
+}
@@ -3,12 +3,17 @@ use std::sync::{
     atomic::{AtomicBool, AtomicU16, Ordering},
 };
 
+use tokio::sync::Notify;
+
+use crate::MSS;
+
 #[derive(Debug)]
 pub struct HandshakeStatus {
     is_server: AtomicBool,
     has_handshake_key: AtomicBool,
     has_received_handshake_ack: AtomicBool,
     is_handshake_confirmed: AtomicBool,
+    confirmed: Notify,
 }
 
 impl HandshakeStatus {
@@ -18,6 +23,7 @@ impl HandshakeStatus {
             has_handshake_key: AtomicBool::new(false),
             has_received_handshake_ack: AtomicBool::new(false),
             is_handshake_confirmed: AtomicBool::new(false),
+            confirmed: Notify::new(),
         }
     }
 }
@@ -34,6 +40,22 @@ impl HandshakeStatus {
 
     pub fn handshake_confirmed(&self) {
         self.is_handshake_confirmed.store(true, Ordering::Relaxed);
+        self.confirmed.notify_waiters();
+    }
+
+    pub fn is_handshake_confirmed(&self) -> bool {
+        self.is_handshake_confirmed.load(Ordering::Relaxed)
+    }
+
+    /// Wait until the handshake is confirmed.
+    pub async fn confirmed(&self) {
+        let confirmed = self.confirmed.notified();
+        tokio::pin!(confirmed);
+        // register before checking, or the notification may be missed
+        confirmed.as_mut().enable();
+        if !self.is_handshake_confirmed() {
+            confirmed.await;
+        }
     }
 }
 
@@ -42,6 +64,7 @@ pub struct PathStatus {
     handshake: Arc<HandshakeStatus>,
     is_at_anti_amplification_limit: Arc<AtomicBool>,
     pub(super) pmtu: Arc<AtomicU16>,
+    black_hole: Arc<Notify>,
 }
 
 impl PathStatus {
@@ -50,6 +73,7 @@ impl PathStatus {
             handshake,
             is_at_anti_amplification_limit: Arc::new(AtomicBool::new(true)),
             pmtu: pmut,
+            black_hole: Arc::new(Notify::new()),
         }
     }
 
@@ -90,4 +114,31 @@ impl PathStatus {
     pub(crate) fn mtu(&self) -> usize {
         self.pmtu.load(Ordering::Relaxed) as usize
     }
+
+    /// Fall back to the base PMTU, packets larger than it are persistently lost.
+    ///
+    /// See [Section 4.3](https://www.rfc-editor.org/rfc/rfc8899#section-4.3) of RFC 8899.
+    pub(crate) fn on_black_hole_detected(&self) {
+        self.pmtu.store(MSS as u16, Ordering::Relaxed);
+        self.black_hole.notify_one();
+    }
+
+    /// Wait until a black hole is detected on the path, the PMTU has been reset to the base
+    /// PMTU by then, and the search should start over.
+    pub async fn black_hole_detected(&self) {
+        self.black_hole.notified().await
+    }
+}
+
+/// A read-only view of the PMTU of a path, in bytes.
+///
+/// The PMTU may change during the lifetime of the path, so it should be read every time
+/// it is used.
+#[derive(Debug, Clone)]
+pub struct Pmtu(pub(crate) Arc<AtomicU16>);
+
+impl Pmtu {
+    pub fn get(&self) -> usize {
+        self.0.load(Ordering::Relaxed) as usize
+    }
 }
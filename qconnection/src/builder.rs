@@ -1,6 +1,7 @@
 use std::{
     future::Future,
     io,
+    net::{SocketAddrV4, SocketAddrV6},
     sync::{Arc, RwLock},
     time::Duration,
 };
@@ -18,20 +19,22 @@ pub use qbase::{
     token::{TokenProvider, TokenSink, handy::*},
 };
 use qbase::{
-    error::Error,
+    error::{Error, ErrorKind, QuicError},
+    flow::RecvWindowLimits,
     frame::ConnectionCloseFrame,
     net::{address::BindAddr, tx::ArcSendWakers},
-    param::{ArcParameters, ParameterId, RememberedParameters, StoreParameterExt},
+    packet::{keys::KeyUpdatePolicy, r#type::long::VERSION_1},
+    param::{ArcParameters, ParameterId, PreferredAddress, StoreParameterExt, VersionInformation},
     sid::{self, ProductStreamsConcurrencyController},
-    token::ArcTokenRegistry,
+    token::{ArcTokenRegistry, ResetToken, StatelessResetKey},
     varint::VarInt,
 };
-use qcongestion::HandshakeStatus;
+use qcongestion::{Algorithm, HandshakeStatus, ProductCongestionController};
 use qevent::{
     GroupID, VantagePointType,
     quic::{
         Owner,
-        connectivity::{ConnectionClosed, PathAssigned},
+        connectivity::{ConnectionCloseTrigger, ConnectionClosed, PathAssigned},
         transport::ParametersSet,
     },
     telemetry::{Instrument, Log, Span},
@@ -43,11 +46,18 @@ use tracing::Instrument as _;
 pub use crate::tls::AuthClient;
 use crate::{
     ArcLocalCids, ArcReliableFrameDeque, ArcRemoteCids, CidRegistry, Components, Connection,
-    FlowController, Handshake, RawHandshake, ServerComponents, SpecificComponents, Termination,
+    FlowController, Handshake, RawHandshake, ResetTerminator, ServerComponents, SpecificComponents,
+    Termination,
     events::{ArcEventBroker, EmitEvent, Event},
-    path::{ArcPathContexts, Path},
+    multipath::{ArcMultipath, PathCidFrames},
+    path::{
+        ArcPathContexts, Path,
+        scheduler::{PathScheduling, ProductPathScheduler},
+    },
     prelude::HeartbeatConfig,
-    space::{self, Spaces, data::DataSpace, handshake::HandshakeSpace, initial::InitialSpace},
+    space::{
+        self, ArcVersion, Spaces, data::DataSpace, handshake::HandshakeSpace, initial::InitialSpace,
+    },
     state::ConnState,
     termination::Terminator,
     tls::{
@@ -68,7 +78,6 @@ impl Connection {
             token_registry: ArcTokenRegistry::with_sink(server_name.clone(), token_sink),
             server_name,
             client_params: ClientParameters::default(),
-            remembered: None,
         }
     }
 
@@ -78,6 +87,10 @@ impl Connection {
             server_params: ServerParameters::default(),
             silent_rejection: false,
             client_authers: vec![],
+            retry: None,
+            version: VERSION_1,
+            early_data: true,
+            preferred_address: None,
         }
     }
 }
@@ -88,18 +101,17 @@ pub struct ClientFoundation {
     token_registry: ArcTokenRegistry,
     client_params: ClientParameters,
     server_name: String,
-    remembered: Option<RememberedParameters>,
 }
 
 impl ClientFoundation {
-    pub fn with_parameters(
-        self,
-        client_params: ClientParameters,
-        remembered: Option<RememberedParameters>,
-    ) -> Self {
+    /// The client transport parameters.
+    ///
+    /// The server transport parameters used to send early data are not specified here, they are
+    /// remembered with the session ticket of the `tls_config`, because they must be the ones from
+    /// the connection where the session ticket was issued.
+    pub fn with_parameters(self, client_params: ClientParameters) -> Self {
         ClientFoundation {
             client_params,
-            remembered,
             ..self
         }
     }
@@ -121,6 +133,10 @@ pub struct ServerFoundation {
     server_params: ServerParameters,
     silent_rejection: bool,
     client_authers: ClientAuthers,
+    retry: Option<(ConnectionId, Link)>,
+    version: u32,
+    early_data: bool,
+    preferred_address: Option<(SocketAddrV4, SocketAddrV6)>,
 }
 
 impl ServerFoundation {
@@ -148,6 +164,54 @@ impl ServerFoundation {
         }
     }
 
+    /// The client has been validated by a Retry packet from the `link`, whose source
+    /// connection ID is `retry_scid`.
+    ///
+    /// See [section 8.1.2](https://www.rfc-editor.org/rfc/rfc9000.html#name-address-validation-using-r)
+    /// of [RFC9000](https://www.rfc-editor.org/rfc/rfc9000.html).
+    pub fn with_retry(self, retry_scid: ConnectionId, link: Link) -> Self {
+        ServerFoundation {
+            retry: Some((retry_scid, link)),
+            ..self
+        }
+    }
+
+    /// The QUIC version of the Initial packet from the client, which is used by the connection.
+    ///
+    /// Default: QUIC version 1.
+    pub fn with_version(self, version: u32) -> Self {
+        ServerFoundation { version, ..self }
+    }
+
+    /// Whether to accept the early data of the client resuming a session, which is sent in
+    /// 0-RTT packets. It takes effect only if the `tls_config` allows early data.
+    ///
+    /// Early data can be replayed by attackers, the rejected early data is resent by the client
+    /// after the handshake.
+    ///
+    /// Default: true.
+    pub fn with_early_data(self, early_data: bool) -> Self {
+        ServerFoundation { early_data, ..self }
+    }
+
+    /// Provide the preferred addresses in the preferred_address transport parameter, along with
+    /// the connection ID of sequence number 1 and its stateless reset token.
+    ///
+    /// The address of a family that the server does not prefer is all-zero, such as `0.0.0.0:0`.
+    ///
+    /// See [section 9.6](https://www.rfc-editor.org/rfc/rfc9000.html#name-server-s-preferred-address)
+    /// of [RFC9000](https://www.rfc-editor.org/rfc/rfc9000.html).
+    pub fn with_preferred_address(
+        self,
+        address_v4: SocketAddrV4,
+        address_v6: SocketAddrV6,
+    ) -> Self {
+        ServerFoundation {
+            preferred_address: Some((address_v4, address_v6)),
+            ..self
+        }
+    }
+
     pub fn with_tls_config(
         self,
         tls_config: Arc<rustls::ServerConfig>,
@@ -166,12 +230,10 @@ pub struct TlsReady<Foundation, Config> {
     streams_ctrl: Box<dyn ControlStreamsConcurrency>,
 }
 
-fn initial_keys_with(
-    crypto_provider: &Arc<CryptoProvider>,
-    client_dcid: &ConnectionId,
-    side: rustls::Side,
-    version: rustls::quic::Version,
-) -> rustls::quic::Keys {
+/// The cipher suite protecting the Initial packets, and the Retry packets.
+///
+/// It is always `TLS_AES_128_GCM_SHA256` of the `crypto_provider`.
+pub fn initial_suite(crypto_provider: &Arc<CryptoProvider>) -> rustls::quic::Suite {
     crypto_provider
         .cipher_suites
         .iter()
@@ -183,7 +245,6 @@ fn initial_keys_with(
         })
         .flatten()
         .expect("crypto provider does not provide supported cipher suite")
-        .keys(client_dcid, side, version)
 }
 
 impl TlsReady<ClientFoundation, Arc<rustls::ClientConfig>> {
@@ -209,7 +270,12 @@ impl TlsReady<ClientFoundation, Arc<rustls::ClientConfig>> {
             tls_config: self.tls_config,
             streams_ctrl: self.streams_ctrl,
             proto,
+            reset_key: None,
             defer_idle_timeout: HeartbeatConfig::default(),
+            congestion_controller: Arc::new(Algorithm::default()),
+            key_update: KeyUpdatePolicy::default(),
+            recv_window_limits: RecvWindowLimits::default(),
+            path_scheduler: Arc::new(PathScheduling::default()),
         }
     }
 }
@@ -237,7 +303,12 @@ impl TlsReady<ServerFoundation, Arc<rustls::ServerConfig>> {
             tls_config: self.tls_config,
             streams_ctrl: self.streams_ctrl,
             proto,
+            reset_key: None,
             defer_idle_timeout: HeartbeatConfig::default(),
+            congestion_controller: Arc::new(Algorithm::default()),
+            key_update: KeyUpdatePolicy::default(),
+            recv_window_limits: RecvWindowLimits::default(),
+            path_scheduler: Arc::new(PathScheduling::default()),
         }
     }
 }
@@ -247,31 +318,90 @@ pub struct ProtoReady<Foundation, Config> {
     tls_config: Config,
     streams_ctrl: Box<dyn ControlStreamsConcurrency>,
     proto: Arc<QuicProto>,
+    reset_key: Option<StatelessResetKey>,
     defer_idle_timeout: HeartbeatConfig,
+    congestion_controller: Arc<dyn ProductCongestionController>,
+    key_update: KeyUpdatePolicy,
+    recv_window_limits: RecvWindowLimits,
+    path_scheduler: Arc<dyn ProductPathScheduler>,
 }
 
 impl<Foundation, Config> ProtoReady<Foundation, Config> {
+    /// Derive the stateless reset tokens of the connection IDs issued by this connection from the
+    /// `reset_key` of its endpoint, the tokens are generated randomly if [`None`] is given.
+    pub fn with_stateless_reset_key(self, reset_key: Option<StatelessResetKey>) -> Self {
+        Self { reset_key, ..self }
+    }
+
     pub fn defer_idle_timeout(self, config: HeartbeatConfig) -> Self {
         Self {
             defer_idle_timeout: config,
             ..self
         }
     }
+
+    pub fn with_congestion_algorithm(self, algorithm: Algorithm) -> Self {
+        self.with_congestion_controller(Arc::new(algorithm))
+    }
+
+    pub fn with_congestion_controller(
+        self,
+        controller: Arc<dyn ProductCongestionController>,
+    ) -> Self {
+        Self {
+            congestion_controller: controller,
+            ..self
+        }
+    }
+
+    /// Specify when to initiate the 1-RTT key updates proactively, see [`KeyUpdatePolicy`].
+    pub fn with_key_update_policy(self, policy: KeyUpdatePolicy) -> Self {
+        Self {
+            key_update: policy,
+            ..self
+        }
+    }
+
+    /// Specify how large the receive windows can grow to, which limits the memory used to buffer
+    /// the data not read yet, see [`RecvWindowLimits`].
+    pub fn with_recv_window_limits(self, limits: RecvWindowLimits) -> Self {
+        Self {
+            recv_window_limits: limits,
+            ..self
+        }
+    }
+
+    /// Specify which paths carry the data when multipath is enabled, see [`PathScheduling`].
+    pub fn with_path_scheduling(self, scheduling: PathScheduling) -> Self {
+        self.with_path_scheduler(Arc::new(scheduling))
+    }
+
+    pub fn with_path_scheduler(self, scheduler: Arc<dyn ProductPathScheduler>) -> Self {
+        Self {
+            path_scheduler: scheduler,
+            ..self
+        }
+    }
 }
 
 impl ProtoReady<ClientFoundation, Arc<rustls::ClientConfig>> {
     pub fn with_cids(self, origin_dcid: ConnectionId) -> ComponentsReady {
+        use qbase::frame::{MaxStreamsFrame, ReceiveFrame, StreamCtlFrame};
+
         let mut client_params = self.foundation.client_params;
-        let remembered = self.foundation.remembered;
 
         let tx_wakers = ArcSendWakers::default();
         let reliable_frames = ArcReliableFrameDeque::with_capacity_and_wakers(8, tx_wakers.clone());
 
         let rcvd_pkt_q = Arc::new(RcvdPacketQueue::new());
 
-        let router_registry: qinterface::router::RouterRegistry<ArcReliableFrameDeque> = self
-            .proto
-            .registry(rcvd_pkt_q.clone(), reliable_frames.clone());
+        let router_registry: qinterface::router::RouterRegistry<PathCidFrames> = (self.proto)
+            .registry(
+                rcvd_pkt_q.clone(),
+                PathCidFrames::new(0, reliable_frames.clone()),
+            )
+            .with_reset_key(self.reset_key);
+        let path_registry = router_registry.with_issued_cids(());
         let initial_scid = router_registry.gen_unique_cid();
 
         client_params.set_initial_source_connection_id(initial_scid);
@@ -281,20 +411,40 @@ impl ProtoReady<ClientFoundation, Arc<rustls::ClientConfig>> {
             ArcRemoteCids::new(
                 origin_dcid,
                 client_params.active_connection_id_limit().into(),
-                reliable_frames.clone(),
+                PathCidFrames::new(0, reliable_frames.clone()),
             ),
         );
 
-        let initial_keys = initial_keys_with(
-            self.tls_config.crypto_provider(),
-            &origin_dcid,
-            rustls::Side::Client,
-            rustls::quic::Version::V1,
+        let initial_suite = initial_suite(self.tls_config.crypto_provider());
+
+        // The client initiates the connection with the chosen version of its version information.
+        let version = client_params
+            .version_information()
+            .map_or(VERSION_1, |version_information| {
+                version_information.chosen_version()
+            });
+        let arc_version = ArcVersion::new(version);
+
+        let tls_session = ArcTlsSession::new_client(
+            self.foundation.server,
+            self.tls_config,
+            version,
+            &client_params,
         );
 
+        // The client resuming a session sends early data in 0-RTT packets, within the limits of
+        // the server transport parameters remembered with the session ticket.
+        let (zero_rtt_keys, remembered) = tls_session
+            .zero_rtt_keys()
+            .zip(tls_session.remembered_parameters())
+            .unzip();
+
         let flow_ctrl = FlowController::new(
-            0, // TODO: 0rtt
+            remembered.as_ref().map_or(0, |remembered| {
+                remembered.get_as_ensured::<u64>(ParameterId::InitialMaxData)
+            }),
             client_params.initial_max_data().into_inner(),
+            self.recv_window_limits,
             reliable_frames.clone(),
             tx_wakers.clone(),
         );
@@ -302,23 +452,51 @@ impl ProtoReady<ClientFoundation, Arc<rustls::ClientConfig>> {
         let max_ack_delay = client_params.max_ack_delay();
 
         let spaces = Spaces::new(
-            InitialSpace::new(initial_keys, self.foundation.token, tx_wakers.clone()),
-            HandshakeSpace::new(tx_wakers.clone()),
+            InitialSpace::new(
+                initial_suite,
+                &origin_dcid,
+                rustls::Side::Client,
+                self.foundation.token,
+                arc_version.clone(),
+                tx_wakers.clone(),
+            ),
+            HandshakeSpace::new(arc_version.clone(), tx_wakers.clone()),
             DataSpace::new(
                 sid::Role::Client,
                 reliable_frames.clone(),
                 &client_params,
                 self.streams_ctrl,
+                flow_ctrl.recver.streams_flow(),
+                arc_version,
                 tx_wakers.clone(),
                 max_ack_delay,
+                self.key_update,
             ),
         );
 
+        if let Some((keys, remembered)) = zero_rtt_keys.zip(remembered.as_ref()) {
+            spaces.data().zero_rtt_keys().set_keys(keys);
+            // pretend to receive the MAX_STREAM frames
+            let streams = spaces.data().streams();
+            _ = streams.recv_frame(&StreamCtlFrame::MaxStreams(MaxStreamsFrame::Bi(
+                remembered.get_as_ensured::<VarInt>(ParameterId::InitialMaxStreamsBidi),
+            )));
+            _ = streams.recv_frame(&StreamCtlFrame::MaxStreams(MaxStreamsFrame::Uni(
+                remembered.get_as_ensured::<VarInt>(ParameterId::InitialMaxStreamsUni),
+            )));
+        }
+
         let client_name = ArcClientName::from(&client_params);
         let parameters = ArcParameters::new_client(client_params, remembered, origin_dcid);
-
-        let tls_session =
-            ArcTlsSession::new_client(self.foundation.server, self.tls_config, &parameters);
+        let multipath = ArcMultipath::new(
+            parameters.clone(),
+            cid_registry.clone(),
+            path_registry,
+            rcvd_pkt_q.clone(),
+            reliable_frames.clone(),
+            tx_wakers.clone(),
+            self.path_scheduler.init(),
+        );
 
         let raw_handshake = RawHandshake::new(sid::Role::Client, reliable_frames.clone());
 
@@ -328,12 +506,14 @@ impl ProtoReady<ClientFoundation, Arc<rustls::ClientConfig>> {
             raw_handshake,
             token_registry: self.foundation.token_registry,
             cid_registry,
+            multipath,
             flow_ctrl,
             spaces,
             proto: self.proto,
             rcvd_pkt_q,
             tx_wakers,
             defer_idle_timeout: self.defer_idle_timeout,
+            congestion_controller: self.congestion_controller,
             client_name,
             server_name: ArcEndpointName::from(self.foundation.server_name),
             qlog_span: None,
@@ -349,63 +529,120 @@ impl ProtoReady<ServerFoundation, Arc<rustls::ServerConfig>> {
         client_scid: ConnectionId,
     ) -> ComponentsReady {
         let mut server_params = self.foundation.server_params;
+        // The server uses the version of the client's Initial packet, which must be the chosen
+        // version of its version information.
+        let version = self.foundation.version;
+        if let Some(version_information) = server_params.version_information() {
+            server_params.set_version_information(VersionInformation::new(
+                version,
+                version_information.available_versions().to_vec(),
+            ));
+        }
+        let arc_version = ArcVersion::new(version);
 
         let tx_wakers = ArcSendWakers::default();
         let reliable_frames = ArcReliableFrameDeque::with_capacity_and_wakers(8, tx_wakers.clone());
 
         let rcvd_pkt_q = Arc::new(RcvdPacketQueue::new());
 
-        let router_registry: qinterface::router::RouterRegistry<ArcReliableFrameDeque> = self
-            .proto
-            .registry(rcvd_pkt_q.clone(), reliable_frames.clone());
+        let router_registry: qinterface::router::RouterRegistry<PathCidFrames> = (self.proto)
+            .registry(
+                rcvd_pkt_q.clone(),
+                PathCidFrames::new(0, reliable_frames.clone()),
+            )
+            .with_reset_key(self.reset_key);
+        let path_registry = router_registry.with_issued_cids(());
         let initial_scid = router_registry.gen_unique_cid();
 
         server_params.set_initial_source_connection_id(initial_scid);
-        self.proto
-            .add_router_entry(origin_dcid.into(), rcvd_pkt_q.clone());
+        server_params.set_statelss_reset_token(router_registry.gen_reset_token(&initial_scid));
         server_params.set_original_destination_connection_id(origin_dcid);
+        // The client sends the subsequent Initial packets to the source connection ID of the
+        // Retry packet, and derives the Initial keys from it.
+        let (initial_dcid, retried_link) = match self.foundation.retry {
+            Some((retry_scid, link)) => {
+                server_params.set_retry_source_connection_id(retry_scid);
+                (retry_scid, Some(link))
+            }
+            None => (origin_dcid, None),
+        };
+        self.proto
+            .add_router_entry(initial_dcid.into(), rcvd_pkt_q.clone());
+
+        let local_cids = ArcLocalCids::new(initial_scid, router_registry);
+        if let Some((address_v4, address_v6)) = self.foundation.preferred_address {
+            let (cid, reset_token) = local_cids
+                .preferred_address_cid()
+                .expect("the connection ID of sequence number 1 is issued initially");
+            server_params.set_preferred_address(PreferredAddress::new(
+                address_v4,
+                address_v6,
+                cid,
+                reset_token,
+            ));
+        }
 
         let cid_registry = CidRegistry::new(
-            ArcLocalCids::new(initial_scid, router_registry),
+            local_cids,
             ArcRemoteCids::new(
                 client_scid,
                 server_params.active_connection_id_limit().into(),
-                reliable_frames.clone(),
+                PathCidFrames::new(0, reliable_frames.clone()),
             ),
         );
 
-        let initial_keys = initial_keys_with(
-            self.tls_config.crypto_provider(),
-            &origin_dcid,
-            rustls::Side::Server,
-            rustls::quic::Version::V1,
-        );
+        let initial_suite = initial_suite(self.tls_config.crypto_provider());
 
         let flow_ctrl = FlowController::new(
             0,
             server_params.initial_max_data().into_inner(),
+            self.recv_window_limits,
             reliable_frames.clone(),
             tx_wakers.clone(),
         );
 
         let max_ack_delay = server_params.max_ack_delay();
         let spaces = Spaces::new(
-            InitialSpace::new(initial_keys, Vec::with_capacity(0), tx_wakers.clone()),
-            HandshakeSpace::new(tx_wakers.clone()),
+            InitialSpace::new(
+                initial_suite,
+                &initial_dcid,
+                rustls::Side::Server,
+                Vec::with_capacity(0),
+                arc_version.clone(),
+                tx_wakers.clone(),
+            ),
+            HandshakeSpace::new(arc_version.clone(), tx_wakers.clone()),
             DataSpace::new(
                 sid::Role::Server,
                 reliable_frames.clone(),
                 &server_params,
                 self.streams_ctrl,
+                flow_ctrl.recver.streams_flow(),
+                arc_version,
                 tx_wakers.clone(),
                 max_ack_delay,
+                self.key_update,
             ),
         );
 
         let parameters = ArcParameters::new_server(server_params);
         parameters.initial_scid_from_peer_need_equal(client_scid);
+        let multipath = ArcMultipath::new(
+            parameters.clone(),
+            cid_registry.clone(),
+            path_registry,
+            rcvd_pkt_q.clone(),
+            reliable_frames.clone(),
+            tx_wakers.clone(),
+            self.path_scheduler.init(),
+        );
 
-        let tls_session = ArcTlsSession::new_server(self.tls_config, &parameters);
+        let tls_session = ArcTlsSession::new_server(
+            self.tls_config,
+            version,
+            &parameters,
+            self.foundation.early_data,
+        );
 
         let raw_handshake = RawHandshake::new(sid::Role::Server, reliable_frames.clone());
 
@@ -415,12 +652,14 @@ impl ProtoReady<ServerFoundation, Arc<rustls::ServerConfig>> {
             raw_handshake,
             token_registry: self.foundation.token_registry,
             cid_registry,
+            multipath,
             flow_ctrl,
             spaces,
             proto: self.proto,
             rcvd_pkt_q,
             tx_wakers,
             defer_idle_timeout: self.defer_idle_timeout,
+            congestion_controller: self.congestion_controller,
             client_name: ArcClientName::default(),
             server_name: ArcServerName::default(),
             qlog_span: None,
@@ -431,6 +670,7 @@ impl ProtoReady<ServerFoundation, Arc<rustls::ServerConfig>> {
                     ArcSendGate::unrestricted()
                 },
                 client_authers: self.foundation.client_authers,
+                retried_link,
             }),
         }
     }
@@ -441,12 +681,14 @@ pub struct ComponentsReady {
     token_registry: ArcTokenRegistry,
     rcvd_pkt_q: Arc<RcvdPacketQueue>,
     cid_registry: CidRegistry,
+    multipath: ArcMultipath,
     flow_ctrl: FlowController,
     spaces: Spaces,
     parameters: ArcParameters,
     tls_session: ArcTlsSession,
     raw_handshake: RawHandshake,
     defer_idle_timeout: HeartbeatConfig,
+    congestion_controller: Arc<dyn ProductCongestionController>,
     tx_wakers: ArcSendWakers,
     client_name: ArcClientName,
     server_name: ArcServerName,
@@ -481,37 +723,47 @@ impl ComponentsReady {
         let is_server = role == sid::Role::Server;
         let inform_cc = Arc::new(HandshakeStatus::new(is_server));
         let conn_state = ConnState::new();
-        let event_broker = ArcEventBroker::new(conn_state.clone(), event_broker);
-        let components = Components {
-            parameters: self.parameters,
-            tls_session: self.tls_session,
-            handshake: Handshake::new(self.raw_handshake, inform_cc, event_broker.clone()),
-            token_registry: self.token_registry,
-            cid_registry: self.cid_registry,
-            flow_ctrl: self.flow_ctrl,
-            spaces: self.spaces,
-            proto: self.proto,
-            rcvd_pkt_q: self.rcvd_pkt_q,
-            paths: ArcPathContexts::new(self.tx_wakers, event_broker.clone()),
-            defer_idle_timeout: self.defer_idle_timeout,
-            event_broker,
-            conn_state,
-            client_name: self.client_name,
-            server_name: self.server_name,
-            peer_certs: ArcPeerCerts::default(),
-            specific: self.specific,
-        };
+        // The connection is terminated on receiving a stateless reset before the application is
+        // notified, nothing but the connection itself can reset it
+        let state = Arc::new_cyclic(|state| {
+            let event_broker = ArcEventBroker::new(
+                conn_state.clone(),
+                ResetTerminator::new(state.clone(), event_broker),
+            );
+            let components = Components {
+                parameters: self.parameters,
+                tls_session: self.tls_session,
+                handshake: Handshake::new(self.raw_handshake, inform_cc, event_broker.clone()),
+                token_registry: self.token_registry,
+                cid_registry: self.cid_registry,
+                flow_ctrl: self.flow_ctrl,
+                spaces: self.spaces,
+                proto: self.proto,
+                rcvd_pkt_q: self.rcvd_pkt_q,
+                paths: ArcPathContexts::new(self.tx_wakers, event_broker.clone()),
+                multipath: self.multipath,
+                defer_idle_timeout: self.defer_idle_timeout,
+                congestion_controller: self.congestion_controller,
+                event_broker,
+                conn_state,
+                client_name: self.client_name,
+                server_name: self.server_name,
+                peer_certs: ArcPeerCerts::default(),
+                specific: self.specific,
+            };
 
-        tracing_span.in_scope(|| {
-            qlog_span.in_scope(|| {
-                tokio::spawn(tls::keys_upgrade(&components));
-                tokio::spawn(accept_transport_parameters(&components));
-                space::spawn_deliver_and_parse(&components);
-            })
+            tracing_span.in_scope(|| {
+                qlog_span.in_scope(|| {
+                    tokio::spawn(tls::keys_upgrade(&components));
+                    tokio::spawn(accept_transport_parameters(&components));
+                    space::spawn_deliver_and_parse(&components);
+                })
+            });
+            RwLock::new(Ok(components))
         });
 
         Connection {
-            state: RwLock::new(Ok(components)),
+            state,
             qlog_span,
             tracing_span,
         }
@@ -520,12 +772,17 @@ impl ComponentsReady {
 
 fn accept_transport_parameters(components: &Components) -> impl Future<Output = ()> + Send {
     let params = components.parameters.clone();
-    let streams = components.spaces.data().streams().clone();
+    let data_space = components.spaces.data().clone();
+    let streams = data_space.streams().clone();
     let cid_registry = components.cid_registry.clone();
+    let multipath = components.multipath.clone();
+    let paths = components.paths.clone();
     let flow_ctrl = components.flow_ctrl.clone();
+    let proto = components.proto.clone();
+    let rcvd_pkt_q = components.rcvd_pkt_q.clone();
     let role = components.handshake.role();
     let task = async move {
-        use qbase::frame::{MaxStreamsFrame, ReceiveFrame, StreamCtlFrame};
+        use qbase::frame::{MaxStreamsFrame, NewConnectionIdFrame, ReceiveFrame, StreamCtlFrame};
         let remote_parameters = params.remote().await?;
 
         match role {
@@ -563,10 +820,43 @@ fn accept_transport_parameters(components: &Components) -> impl Future<Output =
             remote_parameters.get_as_ensured::<u64>(ParameterId::InitialMaxData),
         );
 
+        data_space.ack_frequency_requester().on_peer_params(
+            remote_parameters.get_as::<Duration>(ParameterId::MinAckDelay),
+            remote_parameters.get_as_ensured::<Duration>(ParameterId::MaxAckDelay),
+        );
+
         cid_registry.local.set_limit(
             remote_parameters.get_as_ensured::<u64>(ParameterId::ActiveConnectionIdLimit),
         )?;
 
+        // The server's reset token for the connection ID selected during the handshake
+        if let Some(reset_token) =
+            remote_parameters.get_as::<ResetToken>(ParameterId::StatelssResetToken)
+        {
+            cid_registry.remote.set_initial_reset_token(reset_token);
+            proto.add_reset_entry(reset_token, rcvd_pkt_q.clone());
+        }
+
+        // pretend to receive the NEW_CONNECTION_ID frame of the connection ID in the server's
+        // preferred address, whose sequence number is 1 (rfc9000 5.1.1)
+        if let Some(preferred_address) =
+            remote_parameters.get_as::<PreferredAddress>(ParameterId::PreferredAddress)
+        {
+            let frame = NewConnectionIdFrame::new(
+                preferred_address.connection_id(),
+                VarInt::from_u32(1),
+                VarInt::from_u32(0),
+                preferred_address.stateless_reset_token(),
+            );
+            if let Some(reset_token) = cid_registry.remote.recv_frame(&frame)? {
+                proto.add_reset_entry(reset_token, rcvd_pkt_q);
+            }
+        }
+
+        if multipath.try_enable()? {
+            paths.enable_multipath();
+        }
+
         Result::<_, Error>::Ok(())
     };
     let event_broker = components.event_broker.clone();
@@ -585,9 +875,18 @@ impl Components {
         bind_addr: BindAddr,
         link: Link,
         pathway: Pathway,
+        path_id: u32,
         is_probed: bool,
     ) -> io::Result<Arc<Path>> {
         let try_create = || {
+            // The connection IDs of the path are issued once multipath is enabled, and released
+            // after the path is abandoned
+            if path_id != 0 && self.multipath.remote_cids(path_id).is_none() {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("path {path_id} is unknown or abandoned"),
+                ));
+            }
             let do_validate = !self.conn_state.try_entry_attempted(self, link)?;
             qevent::event!(PathAssigned {
                 path_id: pathway.to_string(),
@@ -603,11 +902,14 @@ impl Components {
                 bind_addr,
                 link,
                 pathway,
+                path_id,
+                self.congestion_controller.as_ref(),
                 max_ack_delay,
+                self.spaces.data().ack_frequency().clone(),
                 [
                     self.spaces.initial().clone(),
                     self.spaces.handshake().clone(),
-                    self.spaces.data().clone(),
+                    self.spaces.data().path_feedback(path_id),
                 ],
                 self.handshake.status(),
             )?);
@@ -618,6 +920,7 @@ impl Components {
 
             let burst = path.new_burst(self);
             let idle_timeout = path.idle_timeout(self);
+            let discover_mtu = path.discover_mtu(self);
 
             let task = {
                 let path = path.clone();
@@ -636,6 +939,7 @@ impl Components {
                         true = idle_timeout => "idle timeout".into(),
                         Err(e) = burst.launch() => format!("failed to send packets: {:?}", e),
                         _ = path.defer_idle_timeout(defer_idle_timeout) => "failed to defer idle timeout".into(),
+                        _ = discover_mtu => unreachable!("PMTU discovery never completes"),
                     };
                     Err(reason)
                 }
@@ -645,14 +949,77 @@ impl Components {
                 Instrument::instrument(task, qevent::span!(@current, path=pathway.to_string()))
                     .instrument_in_current();
 
-            tracing::info!(%pathway, %link, is_probed, do_validate, "add new path:");
+            tracing::info!(%pathway, %link, path_id, is_probed, do_validate, "add new path:");
             Ok((path, task))
         };
         self.paths.get_or_try_create_with(pathway, try_create)
     }
+
+    /// Whether the server refuses the packets received on the new `pathway`, because the
+    /// active migration is disabled by the server.
+    ///
+    /// rfc9000 9
+    /// An endpoint that has sent this transport parameter, but detects that a peer has
+    /// nonetheless migrated to a different remote address, MUST either drop the incoming packets
+    /// on that path without generating a Stateless Reset or proceed with path validation and
+    /// allow the peer to migrate.
+    ///
+    /// rfc9000 18.2
+    /// This parameter does not prohibit connection migration after a client has acted on a
+    /// preferred_address transport parameter.
+    ///
+    /// The client migrating to the preferred address keeps its address, only the new pathways
+    /// from a different remote address are refused.
+    pub fn is_migration_refused(&self, pathway: &Pathway) -> bool {
+        self.handshake.role() == sid::Role::Server
+            && !self.paths.is_empty()
+            && self
+                .paths
+                .iter()
+                .all(|path| path.pathway().remote() != pathway.remote())
+            && self
+                .parameters
+                .get_local_as::<bool>(ParameterId::DisableActiveMigration)
+                .unwrap_or(false)
+    }
+
+    /// Whether the client discards the packets received on the new `pathway`, because they
+    /// come from a server address that is not in use, such as the address of the server
+    /// before the client migrated to its preferred address.
+    ///
+    /// rfc9000 9
+    /// If a client receives packets from an unknown server address, the client MUST discard
+    /// these packets.
+    pub fn is_from_unknown_server(&self, pathway: &Pathway) -> bool {
+        self.handshake.role() == sid::Role::Client
+            && self
+                .paths
+                .iter()
+                .all(|path| path.pathway().remote() != pathway.remote())
+    }
 }
 
 impl Components {
+    fn spawn_terminate_after_3pto(&self) {
+        tokio::spawn({
+            let local_cids = self.cid_registry.local.clone();
+            let multipath = self.multipath.clone();
+            let proto = self.proto.clone();
+            let rcvd_pkt_q = self.rcvd_pkt_q.clone();
+            let event_broker = self.event_broker.clone();
+            let pto_duration = self.paths.max_pto_duration().unwrap_or_default();
+            async move {
+                tokio::time::sleep(pto_duration * 3).await;
+                local_cids.clear();
+                multipath.clear();
+                proto.del_reset_entries(&rcvd_pkt_q);
+                event_broker.emit(Event::Terminated);
+            }
+            .instrument_in_current()
+            .in_current_span()
+        });
+    }
+
     // 对于server，第一条路径也通过add_path添加
     pub fn enter_closing(self, ccf: ConnectionCloseFrame) -> Termination {
         qevent::event!(ConnectionClosed {
@@ -664,28 +1031,17 @@ impl Components {
         self.flow_ctrl.on_conn_error(&error);
         self.tls_session.on_conn_error(&error);
         if self.handshake.role() == sid::Role::Server {
-            let origin_dcid = self
+            let initial_dcid = self
                 .parameters
-                .get_origin_dcid()
+                .get_initial_dcid()
                 .expect("connection not close yet");
-            self.proto.del_router_entry(&origin_dcid.into());
+            self.proto.del_router_entry(&initial_dcid.into());
         }
         self.parameters.on_conn_error(&error);
         self.server_name.on_conn_error(&error);
         self.peer_certs.on_conn_error(&error);
 
-        tokio::spawn({
-            let local_cids = self.cid_registry.local.clone();
-            let event_broker = self.event_broker.clone();
-            let pto_duration = self.paths.max_pto_duration().unwrap_or_default();
-            async move {
-                tokio::time::sleep(pto_duration * 3).await;
-                local_cids.clear();
-                event_broker.emit(Event::Terminated);
-            }
-            .instrument_in_current()
-            .in_current_span()
-        });
+        self.spawn_terminate_after_3pto();
 
         let terminator = Arc::new(Terminator::new(ccf, &self));
 
@@ -713,28 +1069,17 @@ impl Components {
         self.flow_ctrl.on_conn_error(&error);
         self.tls_session.on_conn_error(&error);
         if self.handshake.role() == sid::Role::Server {
-            let origin_dcid = self
+            let initial_dcid = self
                 .parameters
-                .get_origin_dcid()
+                .get_initial_dcid()
                 .expect("connection not close yet");
-            self.proto.del_router_entry(&origin_dcid.into());
+            self.proto.del_router_entry(&initial_dcid.into());
         }
         self.parameters.on_conn_error(&error);
         self.server_name.on_conn_error(&error);
         self.peer_certs.on_conn_error(&error);
 
-        tokio::spawn({
-            let local_cids = self.cid_registry.local.clone();
-            let event_broker = self.event_broker.clone();
-            let pto_duration = self.paths.max_pto_duration().unwrap_or_default();
-            async move {
-                tokio::time::sleep(pto_duration * 3).await;
-                local_cids.clear();
-                event_broker.emit(Event::Terminated);
-            }
-            .instrument_in_current()
-            .in_current_span()
-        });
+        self.spawn_terminate_after_3pto();
 
         // for server, send ccf only if the send gate is permitted.
         if !matches!(self.specific, SpecificComponents::Server(ref s) if !s.send_gate.is_permitted())
@@ -750,4 +1095,58 @@ impl Components {
 
         Termination::draining(error, self.cid_registry.local)
     }
+
+    /// Enter the draining state on receiving a stateless reset, and never send any packet.
+    ///
+    /// See [section 10.3.1](https://www.rfc-editor.org/rfc/rfc9000.html#name-detecting-a-stateless-reset)
+    /// of [RFC9000](https://www.rfc-editor.org/rfc/rfc9000.html).
+    pub(crate) fn on_stateless_reset(self) -> Termination {
+        qevent::event!(ConnectionClosed {
+            owner: Owner::Remote,
+            trigger: ConnectionCloseTrigger::StatelessReset
+        });
+        let error = QuicError::with_default_fty(ErrorKind::None, "stateless reset").into();
+        self.terminate_silently(error)
+    }
+
+    /// Abandon the connection attempt on receiving a Version Negotiation packet, which lists
+    /// the `versions` supported by the server, and never send any packet.
+    ///
+    /// See [section 6.2](https://www.rfc-editor.org/rfc/rfc9000.html#name-handling-version-negotiati)
+    /// of [RFC9000](https://www.rfc-editor.org/rfc/rfc9000.html).
+    pub fn on_version_mismatch(self, versions: Vec<u32>) -> Termination {
+        qevent::event!(ConnectionClosed {
+            owner: Owner::Remote,
+            trigger: ConnectionCloseTrigger::VersionMismatch
+        });
+        let error = QuicError::with_default_fty(
+            ErrorKind::VersionNegotiation,
+            format!("no mutually supported version, the server supports {versions:#x?}"),
+        )
+        .into();
+        self.terminate_silently(error)
+    }
+
+    fn terminate_silently(self, error: Error) -> Termination {
+        self.spaces.data().on_conn_error(&error);
+        self.flow_ctrl.on_conn_error(&error);
+        self.tls_session.on_conn_error(&error);
+        if self.handshake.role() == sid::Role::Server {
+            let initial_dcid = self
+                .parameters
+                .get_initial_dcid()
+                .expect("connection not close yet");
+            self.proto.del_router_entry(&initial_dcid.into());
+        }
+        self.parameters.on_conn_error(&error);
+        self.server_name.on_conn_error(&error);
+        self.peer_certs.on_conn_error(&error);
+
+        self.spawn_terminate_after_3pto();
+
+        self.rcvd_pkt_q.close_all();
+        self.paths.clear();
+
+        Termination::draining(error, self.cid_registry.local)
+    }
 }
@@ -30,6 +30,9 @@ pub enum Event {
     Closed(ConnectionCloseFrame),
     // Received a stateless reset, will enter the draining state
     StatelessReset,
+    // Received a version negotiation packet listing the versions supported by the server,
+    // the connection attempt is abandoned and will enter the draining state
+    VersionMismatch(Vec<u32>),
     // The connection is terminated completely
     Terminated,
 }
@@ -68,7 +71,7 @@ impl EmitEvent for ArcEventBroker {
                     return;
                 }
             }
-            Event::Closed(..) => {
+            Event::Closed(..) | Event::StatelessReset | Event::VersionMismatch(..) => {
                 let draining_state = GranularConnectionStates::Draining;
                 if self.conn_state.update(draining_state.into()).is_none() {
                     return;
@@ -78,7 +81,6 @@ impl EmitEvent for ArcEventBroker {
                 let terminated_state = BaseConnectionStates::Closed;
                 self.conn_state.update(terminated_state.into());
             }
-            Event::StatelessReset => todo!("unsupported"),
             _ => { /* path create/inactive: no need */ }
         };
         tracing::info!(status = ?event, "connection");
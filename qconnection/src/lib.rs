@@ -1,8 +1,10 @@
 pub mod events;
 pub mod handshake;
+pub mod multipath;
 pub mod path;
 pub mod space;
 pub mod state;
+pub mod stats;
 pub mod termination;
 pub mod tls;
 pub mod tx;
@@ -10,15 +12,30 @@ pub mod tx;
 pub mod prelude {
     pub use qbase::{
         cid::ConnectionId,
+        flow::{FlowWindow, RecvWindowLimits},
         frame::ConnectionCloseFrame,
         net::{address::*, route::*},
-        sid::{ControlStreamsConcurrency, ProductStreamsConcurrencyController, StreamId},
+        packet::{
+            keys::KeyUpdatePolicy,
+            r#type::long::{VERSION_1, VERSION_2},
+        },
+        sid::{
+            ControlStreamsConcurrency, ProductStreamsConcurrencyController, StreamId, StreamsCount,
+        },
         varint::VarInt,
     };
+    pub use qcongestion::{Algorithm, CongestionStats, ProductCongestionController};
     pub use qinterface::{QuicInterface, router::QuicProto};
+    pub use qrecovery::send::Priority;
     #[cfg(feature = "unreliable")]
     pub use qunreliable::{DatagramReader, DatagramWriter};
 
+    /// The interfaces to implement a custom congestion controller.
+    pub mod congestion {
+        pub use qbase::{Epoch, frame::AckFrame};
+        pub use qcongestion::{ArcRtt, Control, Pmtu, SentPacket};
+    }
+
     #[allow(unused_imports)]
     pub mod handy {
         pub use qbase::{param::handy::*, sid::handy::*, token::handy::*};
@@ -28,7 +45,11 @@ pub mod prelude {
     pub use crate::{
         Connection, StreamReader, StreamWriter,
         events::{EmitEvent, Event},
-        path::idle::HeartbeatConfig,
+        path::{
+            idle::HeartbeatConfig,
+            scheduler::{PathScheduling, PathSnapshot, ProductPathScheduler, SchedulePaths},
+        },
+        stats::{ConnectionStats, PathStats},
         tls::PeerCert,
     };
 }
@@ -39,11 +60,12 @@ use std::{
     borrow::Cow,
     future::Future,
     io,
-    sync::{Arc, RwLock},
+    sync::{Arc, RwLock, Weak},
 };
 
 use enum_dispatch::enum_dispatch;
 use events::{ArcEventBroker, EmitEvent, Event};
+use multipath::{ArcMultipath, PathCidFrames};
 use path::{ArcPathContexts, idle::HeartbeatConfig};
 use qbase::{
     cid,
@@ -51,13 +73,14 @@ use qbase::{
     flow,
     frame::{ConnectionCloseFrame, CryptoFrame, ReliableFrame, StreamFrame},
     net::{
-        address::BindAddr,
-        route::{Link, Pathway},
+        address::{AddrKind, BindAddr},
+        route::{EndpointAddr, Link, Pathway},
     },
-    param::{ArcParameters, ParameterId},
-    sid::StreamId,
+    param::{ArcParameters, ParameterId, PreferredAddress, StoreParameterExt},
+    sid::{Dir, Role, StreamId},
     token::ArcTokenRegistry,
 };
+use qcongestion::ProductCongestionController;
 use qevent::telemetry::Instrument;
 use qinterface::{
     queue::RcvdPacketQueue,
@@ -71,6 +94,7 @@ use qrecovery::{
 use qunreliable::{DatagramReader, DatagramWriter};
 use space::Spaces;
 use state::ConnState;
+use stats::ConnectionStats;
 use termination::Termination;
 use tls::{
     ArcClientName, ArcPeerCerts, ArcSendGate, ArcServerName, ArcTlsSession, ClientAuthers, PeerCert,
@@ -96,10 +120,10 @@ pub type HandshakeJournal = journal::Journal<CryptoFrame>;
 pub type DataJournal = journal::Journal<GuaranteedFrame>;
 
 pub type ArcReliableFrameDeque = reliable::ArcReliableFrameDeque<ReliableFrame>;
-pub type ArcLocalCids = cid::ArcLocalCids<RouterRegistry<ArcReliableFrameDeque>>;
-pub type ArcRemoteCids = cid::ArcRemoteCids<ArcReliableFrameDeque>;
+pub type ArcLocalCids = cid::ArcLocalCids<RouterRegistry<PathCidFrames>>;
+pub type ArcRemoteCids = cid::ArcRemoteCids<PathCidFrames>;
 pub type CidRegistry = cid::Registry<ArcLocalCids, ArcRemoteCids>;
-pub type ArcDcidCell = cid::ArcCidCell<ArcReliableFrameDeque>;
+pub type ArcDcidCell = cid::ArcCidCell<PathCidFrames>;
 
 pub type FlowController = flow::FlowController<ArcReliableFrameDeque>;
 pub type Credit<'a> = flow::Credit<'a, ArcReliableFrameDeque>;
@@ -121,9 +145,11 @@ pub struct Components {
     flow_ctrl: FlowController,
     spaces: Spaces,
     paths: ArcPathContexts,
+    multipath: ArcMultipath,
     proto: Arc<QuicProto>,
     rcvd_pkt_q: Arc<RcvdPacketQueue>,
     defer_idle_timeout: HeartbeatConfig,
+    congestion_controller: Arc<dyn ProductCongestionController>,
     event_broker: ArcEventBroker,
     conn_state: ConnState,
 
@@ -143,6 +169,7 @@ enum SpecificComponents {
 struct ServerComponents {
     send_gate: ArcSendGate,
     client_authers: ClientAuthers,
+    retried_link: Option<Link>,
 }
 
 impl Components {
@@ -154,7 +181,7 @@ impl Components {
         let streams = self.spaces.data().streams().clone();
         async move {
             let snd_wnd_size = params
-                .get_remote_as::<u64>(ParameterId::InitialMaxStreamDataBidiRemote)
+                .get_remote_or_remembered_as::<u64>(ParameterId::InitialMaxStreamDataBidiRemote)
                 .await?;
             Ok(streams.open_bi(snd_wnd_size).await?)
         }
@@ -169,7 +196,7 @@ impl Components {
         let streams = self.spaces.data().streams().clone();
         async move {
             let snd_wnd_size = params
-                .get_remote_as::<u64>(ParameterId::InitialMaxStreamDataUni)
+                .get_remote_or_remembered_as::<u64>(ParameterId::InitialMaxStreamDataUni)
                 .await?;
             Ok(streams.open_uni(snd_wnd_size).await?)
         }
@@ -213,7 +240,7 @@ impl Components {
         let datagrams = self.spaces.data().datagrams().clone();
         async move {
             let max_datagram_frame_size = params
-                .get_remote_as::<u64>(ParameterId::MaxDatagramFrameSize)
+                .get_remote_or_remembered_as::<u64>(ParameterId::MaxDatagramFrameSize)
                 .await?;
             datagrams.writer(max_datagram_frame_size)
         }
@@ -222,14 +249,220 @@ impl Components {
     }
 
     pub fn add_path(&self, ifaca_addr: BindAddr, link: Link, pathway: Pathway) -> io::Result<()> {
-        self.get_or_try_create_path(ifaca_addr, link, pathway, false)
-            .map(|_| ())
+        if self.paths.get(&pathway).is_some() {
+            return Ok(());
+        }
+        let path_id = self.new_path_id()?;
+        match self.get_or_try_create_path(ifaca_addr, link, pathway, path_id, false) {
+            Ok(_) => Ok(()),
+            Err(error) => {
+                self.multipath.release_path(path_id);
+                Err(error)
+            }
+        }
+    }
+
+    /// The path ID of a new path opened by this endpoint, always 0 if multipath is not enabled.
+    ///
+    /// The path ID is given back to [`ArcMultipath`] if the path fails to be created.
+    fn new_path_id(&self) -> io::Result<u32> {
+        if self.multipath.is_enabled() {
+            self.multipath.open_path()
+        } else {
+            Ok(0)
+        }
+    }
+
+    pub fn set_path_status(&self, pathway: &Pathway, is_backup: bool) -> io::Result<()> {
+        let path = self
+            .paths
+            .get(pathway)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "the path does not exist"))?;
+        self.multipath.set_backup(path.path_id(), is_backup)
     }
 
     pub fn del_path(&self, pathway: &Pathway) {
         self.paths.remove(pathway, "application removed");
     }
 
+    pub fn migrate(&self, bind_addr: BindAddr) -> impl Future<Output = io::Result<Pathway>> + Send {
+        let components = self.clone();
+        async move {
+            if components.handshake.role() != Role::Client {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "only the client can migrate the connection",
+                ));
+            }
+            // rfc9000 9
+            // An endpoint MUST NOT initiate connection migration before the handshake is
+            // confirmed
+            //
+            // The client has received the HANDSHAKE_DONE frame once the handshake is confirmed
+            if !components.conn_state.handshaked().await {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotConnected,
+                    "connection closed before the handshake is completed",
+                ));
+            }
+            // If the peer sent the disable_active_migration transport parameter, an endpoint
+            // also MUST NOT send packets (including probing packets) from a different local
+            // address to the address the peer used during the handshake
+            if components
+                .parameters
+                .get_remote_as::<bool>(ParameterId::DisableActiveMigration)
+                .await?
+            {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    "the peer disabled active migration",
+                ));
+            }
+
+            let active_path = components.active_path()?;
+            let iface = components
+                .proto
+                .get_interface(bind_addr.clone())
+                .ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::NotFound, "the interface is not bound")
+                })?;
+            let local_addr = iface.read_addr()?;
+            let link = Link::new(local_addr, active_path.link().dst());
+            let pathway = Pathway::new(
+                EndpointAddr::direct(local_addr),
+                active_path.pathway().remote(),
+            );
+            components.migrate_to(bind_addr, link, pathway).await
+        }
+        .instrument_in_current()
+        .in_current_span()
+    }
+
+    pub fn migrate_to_preferred_address(
+        &self,
+    ) -> impl Future<Output = io::Result<Option<Pathway>>> + Send {
+        let components = self.clone();
+        async move {
+            if components.handshake.role() != Role::Client {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "only the client can migrate to the preferred address",
+                ));
+            }
+            // rfc9000 9.6.1
+            // Once the handshake is confirmed, the client SHOULD select one of the two addresses
+            // provided by the server and initiate path validation
+            if !components.conn_state.handshaked().await {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotConnected,
+                    "connection closed before the handshake is completed",
+                ));
+            }
+            let preferred_address = components
+                .parameters
+                .remote()
+                .await?
+                .get_as::<PreferredAddress>(ParameterId::PreferredAddress);
+            let Some(preferred_address) = preferred_address else {
+                return Ok(None);
+            };
+
+            // A client that migrates to a new address SHOULD use a preferred address from the
+            // same address family for the server.
+            let active_path = components.active_path()?;
+            let AddrKind::Ip(family) = active_path.link().dst().kind() else {
+                return Ok(None);
+            };
+            let Some(server_addr) = preferred_address.address(family) else {
+                return Ok(None);
+            };
+            let link = Link::new(active_path.link().src(), server_addr);
+            let pathway = Pathway::new(
+                active_path.pathway().local(),
+                EndpointAddr::direct(server_addr),
+            );
+            components
+                .migrate_to(active_path.bind_addr(), link, pathway)
+                .await
+                .map(Some)
+        }
+        .instrument_in_current()
+        .in_current_span()
+    }
+
+    fn active_path(&self) -> io::Result<Arc<path::Path>> {
+        self.paths
+            .iter()
+            .find(|path| self.paths.is_active(&path.pathway()))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotConnected, "no path to migrate"))
+    }
+
+    async fn migrate_to(
+        &self,
+        bind_addr: BindAddr,
+        link: Link,
+        pathway: Pathway,
+    ) -> io::Result<Pathway> {
+        let old_paths = self.paths.iter().collect::<Vec<_>>();
+        if old_paths.iter().any(|path| path.pathway() == pathway) {
+            return Ok(pathway);
+        }
+        // Pin the data to the current path, until validated the new path only sends the probing
+        // packets, otherwise the peer would switch to it too early (rfc9000 9.3)
+        self.paths.activate(self.active_path()?.pathway());
+
+        // The new path applies a new connection ID of the peer, and starts with a new
+        // congestion controller (rfc9000 9.4)
+        let path_id = self.new_path_id()?;
+        let path = self
+            .get_or_try_create_path(bind_addr, link, pathway, path_id, false)
+            .map_err(|error| {
+                self.multipath.release_path(path_id);
+                error
+            })?;
+        if !path.validated().await {
+            self.paths.remove(&pathway, "failed to validate");
+            return Err(io::Error::new(
+                io::ErrorKind::ConnectionRefused,
+                "failed to validate the new path",
+            ));
+        }
+
+        self.paths.activate(pathway);
+        // The old paths retire their connection IDs of the peer when removed (rfc9000 9.5)
+        for old_path in old_paths {
+            self.paths.remove(&old_path.pathway(), "migrated");
+        }
+        Ok(pathway)
+    }
+
+    pub fn set_stream_priority(&self, sid: StreamId, priority: send::Priority) {
+        self.spaces.data().streams().set_priority(sid, priority);
+    }
+
+    pub fn cancel_stream(&self, sid: StreamId, error_code: u64) {
+        self.spaces.data().streams().cancel_stream(sid, error_code);
+    }
+
+    pub fn stats(&self) -> ConnectionStats {
+        let role = self.handshake.role();
+        let streams = self.spaces.data().streams();
+        ConnectionStats {
+            paths: self
+                .paths
+                .iter()
+                .map(|path| path.stats(self.paths.is_active(&path.pathway())))
+                .collect(),
+            send_flow: self.flow_ctrl.sender.window(),
+            recv_flow: self.flow_ctrl.recver.window(),
+            local_bi_streams: streams.streams_count(role, Dir::Bi),
+            local_uni_streams: streams.streams_count(role, Dir::Uni),
+            remote_bi_streams: streams.streams_count(!role, Dir::Bi),
+            remote_uni_streams: streams.streams_count(!role, Dir::Uni),
+            key_updates: self.spaces.data().one_rtt_key_updates(),
+        }
+    }
+
     pub fn peer_certs(&self) -> impl Future<Output = Result<Arc<PeerCert>, Error>> + Send {
         let peer_certs = self.peer_certs.clone();
         async move { peer_certs.get().await }
@@ -239,12 +472,47 @@ impl Components {
         let server_name = self.server_name.clone();
         async move { server_name.get().await }
     }
+
+    pub fn early_data_accepted(&self) -> impl Future<Output = Result<bool, Error>> + Send {
+        let conn_state = self.conn_state.clone();
+        let tls_session = self.tls_session.clone();
+        async move {
+            conn_state.handshaked().await;
+            tls_session.is_early_data_accepted()
+        }
+    }
 }
 
 type ConnectionState = RwLock<Result<Components, Termination>>;
 
+/// Terminates the connection on receiving a stateless reset, before the event is emitted to the
+/// application.
+#[derive(Clone)]
+struct ResetTerminator<E> {
+    state: Weak<ConnectionState>,
+    event_broker: E,
+}
+
+impl<E> ResetTerminator<E> {
+    fn new(state: Weak<ConnectionState>, event_broker: E) -> Self {
+        Self {
+            state,
+            event_broker,
+        }
+    }
+}
+
+impl<E: EmitEvent> EmitEvent for ResetTerminator<E> {
+    fn emit(&self, event: Event) {
+        if let (Event::StatelessReset, Some(state)) = (&event, self.state.upgrade()) {
+            Connection::on_stateless_reset(&state);
+        }
+        self.event_broker.emit(event);
+    }
+}
+
 pub struct Connection {
-    state: ConnectionState,
+    state: Arc<ConnectionState>,
     qlog_span: qevent::telemetry::Span,
     tracing_span: tracing::Span,
 }
@@ -267,6 +535,25 @@ impl Connection {
         }
     }
 
+    /// Enter the draining state on receiving a stateless reset, it's called in the context of
+    /// the connection by the [`ResetTerminator`].
+    pub(crate) fn on_stateless_reset(state: &ConnectionState) {
+        let mut conn = state.write().unwrap();
+        match conn.as_mut() {
+            Ok(core_conn) => *conn = Err(core_conn.clone().on_stateless_reset()),
+            Err(termination) => termination.enter_draining(),
+        }
+    }
+
+    pub fn on_version_mismatch(&self, versions: Vec<u32>) {
+        let _span = (self.qlog_span.enter(), self.tracing_span.enter());
+        let mut conn = self.state.write().unwrap();
+        match conn.as_mut() {
+            Ok(core_conn) => *conn = Err(core_conn.clone().on_version_mismatch(versions)),
+            Err(termination) => termination.enter_draining(),
+        }
+    }
+
     pub fn close(&self, reason: impl Into<Cow<'static, str>>, code: u64) {
         let _span = (self.qlog_span.enter(), self.tracing_span.enter());
 
@@ -333,6 +620,66 @@ impl Connection {
         self.try_map_components(|core_conn| core_conn.del_path(pathway))
     }
 
+    /// Mark the path as a backup path, or as an available path again, and tell the peer with a
+    /// PATH_STATUS_BACKUP or PATH_STATUS_AVAILABLE frame.
+    ///
+    /// The backup paths carry the data only if there is no available path. Returns an error if
+    /// multipath is not enabled, or the path does not exist.
+    pub fn set_path_status(&self, pathway: &Pathway, is_backup: bool) -> io::Result<()> {
+        self.try_map_components(|core_conn| core_conn.set_path_status(pathway, is_backup))?
+    }
+
+    /// Migrate the connection to a new path from the interface bound to `bind_addr`, only the
+    /// client can migrate the connection after the handshake is completed.
+    ///
+    /// The new path is validated with a new connection ID of the peer, then the new data is sent
+    /// on it, and the old paths are removed. The pathway of the new path is returned.
+    ///
+    /// Returns an error if the peer disabled active migration, or the new path failed to be
+    /// validated, the connection keeps using the old paths in these cases.
+    pub async fn migrate(&self, bind_addr: BindAddr) -> io::Result<Pathway> {
+        self.try_map_components(|core_conn| core_conn.migrate(bind_addr))?
+            .await
+    }
+
+    /// Migrate the connection to the preferred address of the server, from the local address
+    /// of the active path. Only the client can migrate after the handshake is completed.
+    ///
+    /// The pathway of the new path is returned, or None if the server did not provide a
+    /// preferred address of the IP family in use.
+    ///
+    /// See [section 9.6](https://www.rfc-editor.org/rfc/rfc9000.html#name-server-s-preferred-address)
+    /// of [RFC9000](https://www.rfc-editor.org/rfc/rfc9000.html).
+    pub async fn migrate_to_preferred_address(&self) -> io::Result<Option<Pathway>> {
+        self.try_map_components(|core_conn| core_conn.migrate_to_preferred_address())?
+            .await
+    }
+
+    /// Change the priority of the stream `sid`, as [`StreamWriter::set_priority`] does.
+    ///
+    /// It's useful to apply the priority updated by the peer, such as the PRIORITY_UPDATE frame
+    /// of HTTP/3. The stream that has not been opened or has sent all its data is ignored.
+    pub fn set_stream_priority(&self, sid: StreamId, priority: send::Priority) -> io::Result<()> {
+        self.try_map_components(|core_conn| core_conn.set_stream_priority(sid, priority))
+    }
+
+    /// Reset the stream `sid` with the `error_code`, as [`StreamWriter::cancel`] and
+    /// [`StreamReader::stop`] do.
+    ///
+    /// It's useful to reset the streams handed over to the application by the protocol above, such
+    /// as the streams of a closed WebTransport session. The stream that has not been opened or has
+    /// been closed is ignored.
+    pub fn cancel_stream(&self, sid: StreamId, error_code: u64) -> io::Result<()> {
+        self.try_map_components(|core_conn| core_conn.cancel_stream(sid, error_code))
+    }
+
+    /// Take a snapshot of the statistics of the connection and its paths.
+    ///
+    /// Returns an error if the connection is closing, draining or closed.
+    pub fn stats(&self) -> io::Result<ConnectionStats> {
+        self.try_map_components(|core_conn| core_conn.stats())
+    }
+
     pub fn is_active(&self) -> bool {
         self.try_map_components(|_| true).unwrap_or_default()
     }
@@ -365,6 +712,18 @@ impl Connection {
             .try_map_components(|core_conn| core_conn.server_name())?
             .await?)
     }
+
+    /// Whether the early data sent in 0-RTT packets is accepted by the server, it resolves
+    /// after the handshake is completed.
+    ///
+    /// Returns `false` if the client did not send early data, or the server rejected it. The
+    /// rejected stream data is resent in 1-RTT packets as if it was lost, but the datagrams
+    /// sent in 0-RTT packets are lost and must be resent by the application if necessary.
+    pub async fn early_data_accepted(&self) -> io::Result<bool> {
+        Ok(self
+            .try_map_components(|core_conn| core_conn.early_data_accepted())?
+            .await?)
+    }
 }
 
 impl Drop for Connection {
@@ -0,0 +1,835 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    io,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use qbase::{
+    cid::ConnectionId,
+    error::{Error, ErrorKind, QuicError},
+    frame::{
+        GetFrameType, MaxPathIdFrame, NewConnectionIdFrame, PathAbandonFrame, PathCidsBlockedFrame,
+        PathNewConnectionIdFrame, PathRetireConnectionIdFrame, PathStatusFrame, PathsBlockedFrame,
+        ReceiveFrame, RetireConnectionIdFrame, SendFrame,
+    },
+    net::tx::{ArcSendWakers, Signals},
+    param::{ArcParameters, ParameterId, StoreParameterExt},
+    varint::VarInt,
+};
+use qevent::telemetry::Instrument;
+use qinterface::{queue::RcvdPacketQueue, router::RouterRegistry};
+use tracing::Instrument as _;
+
+use crate::{
+    ArcLocalCids, ArcReliableFrameDeque, ArcRemoteCids, CidRegistry,
+    path::{
+        ArcPathContexts, Path,
+        scheduler::{Carriage, PathSnapshot, SchedulePaths},
+    },
+    space::data::DataSpace,
+};
+
+/// The error code of the PATH_ABANDON frames sent when the path is removed locally, by the
+/// application or because it failed.
+///
+/// See [error codes](https://datatracker.ietf.org/doc/html/draft-ietf-quic-multipath#name-error-codes)
+/// of [Multipath Extension for QUIC](https://datatracker.ietf.org/doc/html/draft-ietf-quic-multipath).
+pub const APPLICATION_ABANDON_PATH: u64 = 0x004150504142;
+
+/// The connection IDs issued to the peer for the paths other than the path 0, to find the path
+/// of the packets received by their destination connection IDs.
+#[derive(Debug, Default)]
+struct IssuedPathCids {
+    paths: HashMap<ConnectionId, u32>,
+    cids: HashMap<(u32, u64), ConnectionId>,
+}
+
+impl IssuedPathCids {
+    fn insert(&mut self, path_id: u32, frame: &NewConnectionIdFrame) {
+        let cid = *frame.connection_id();
+        self.paths.insert(cid, path_id);
+        self.cids.insert((path_id, frame.sequence()), cid);
+    }
+
+    fn retire(&mut self, path_id: u32, sequence: u64) {
+        if let Some(cid) = self.cids.remove(&(path_id, sequence)) {
+            self.paths.remove(&cid);
+        }
+    }
+
+    fn remove_path(&mut self, path_id: u32) {
+        self.paths.retain(|_, id| *id != path_id);
+        self.cids.retain(|(id, _), _| *id != path_id);
+    }
+}
+
+/// The NEW_CONNECTION_ID and RETIRE_CONNECTION_ID frames of a path, which are sent as the
+/// PATH_NEW_CONNECTION_ID and PATH_RETIRE_CONNECTION_ID frames on the paths other than the
+/// path 0.
+#[derive(Debug, Clone)]
+pub struct PathCidFrames {
+    path_id: u32,
+    frames: ArcReliableFrameDeque,
+    issued: Option<Arc<Mutex<IssuedPathCids>>>,
+}
+
+impl PathCidFrames {
+    pub fn new(path_id: u32, frames: ArcReliableFrameDeque) -> Self {
+        Self {
+            path_id,
+            frames,
+            issued: None,
+        }
+    }
+
+    /// The connection IDs sent in the PATH_NEW_CONNECTION_ID frames are recorded in `issued`.
+    fn recorded(
+        path_id: u32,
+        frames: ArcReliableFrameDeque,
+        issued: Arc<Mutex<IssuedPathCids>>,
+    ) -> Self {
+        Self {
+            path_id,
+            frames,
+            issued: Some(issued),
+        }
+    }
+}
+
+impl SendFrame<NewConnectionIdFrame> for PathCidFrames {
+    fn send_frame<I: IntoIterator<Item = NewConnectionIdFrame>>(&self, iter: I) {
+        if self.path_id == 0 {
+            self.frames.send_frame(iter);
+        } else {
+            let path_id = VarInt::from_u32(self.path_id);
+            self.frames.send_frame(iter.into_iter().map(|frame| {
+                if let Some(issued) = &self.issued {
+                    issued.lock().unwrap().insert(self.path_id, &frame);
+                }
+                PathNewConnectionIdFrame::new(path_id, frame)
+            }));
+        }
+    }
+}
+
+impl SendFrame<RetireConnectionIdFrame> for PathCidFrames {
+    fn send_frame<I: IntoIterator<Item = RetireConnectionIdFrame>>(&self, iter: I) {
+        if self.path_id == 0 {
+            self.frames.send_frame(iter);
+        } else {
+            let path_id = VarInt::from_u32(self.path_id);
+            self.frames.send_frame(iter.into_iter().map(|frame| {
+                PathRetireConnectionIdFrame::new(
+                    path_id,
+                    VarInt::from_u64(frame.sequence()).unwrap(),
+                )
+            }));
+        }
+    }
+}
+
+/// The status of a path sent in the PATH_STATUS frames, by this endpoint and by the peer.
+#[derive(Default)]
+struct PathStatus {
+    local_sequence: u64,
+    local_backup: bool,
+    remote_sequence: Option<u64>,
+    remote_backup: bool,
+}
+
+struct RawMultipath {
+    enabled: bool,
+    // The maximum path ID advertised to the peer, None if multipath is not supported locally
+    local_max_path_id: Option<u32>,
+    remote_max_path_id: u32,
+    // The connection IDs have been issued to the peer for the path IDs up to this one, which
+    // are allowed by both endpoints
+    issued_max_path_id: u32,
+    // The connection IDs of the peer are accepted for the path IDs up to this one, which is
+    // the maximum path ID advertised to the peer
+    accepted_max_path_id: u32,
+    // The number of the connection IDs issued to the peer for each path
+    peer_cid_limit: u64,
+    // The number of the connection IDs of the peer accepted for each path
+    local_cid_limit: u64,
+    local_cids: BTreeMap<u32, ArcLocalCids>,
+    remote_cids: BTreeMap<u32, ArcRemoteCids>,
+    // The path IDs in use by the paths opened by this endpoint
+    opened: HashSet<u32>,
+    status: HashMap<u32, PathStatus>,
+    abandoned: HashSet<u32>,
+}
+
+/// The state of the multipath extension of the connection.
+///
+/// The path 0 uses the connection IDs of the [`CidRegistry`], each of the other paths has its
+/// own connection IDs and packet number space, and is identified by the connection IDs the
+/// packets are sent to.
+///
+/// See [Multipath Extension for QUIC](https://datatracker.ietf.org/doc/html/draft-ietf-quic-multipath).
+#[derive(Clone)]
+pub struct ArcMultipath {
+    raw: Arc<Mutex<RawMultipath>>,
+    issued_cids: Arc<Mutex<IssuedPathCids>>,
+    parameters: ArcParameters,
+    cid_registry: CidRegistry,
+    // Registers the connection IDs of the other paths, with the reset key of the connection
+    path_registry: RouterRegistry<()>,
+    rcvd_pkt_q: Arc<RcvdPacketQueue>,
+    reliable_frames: ArcReliableFrameDeque,
+    tx_wakers: ArcSendWakers,
+    scheduler: Arc<dyn SchedulePaths>,
+}
+
+impl ArcMultipath {
+    pub fn new(
+        parameters: ArcParameters,
+        cid_registry: CidRegistry,
+        path_registry: RouterRegistry<()>,
+        rcvd_pkt_q: Arc<RcvdPacketQueue>,
+        reliable_frames: ArcReliableFrameDeque,
+        tx_wakers: ArcSendWakers,
+        scheduler: Box<dyn SchedulePaths>,
+    ) -> Self {
+        let local_max_path_id = parameters
+            .get_local()
+            .ok()
+            .and_then(|local| local.get_as::<u64>(ParameterId::InitialMaxPathId))
+            .map(|max_path_id| max_path_id as u32);
+        Self {
+            raw: Arc::new(Mutex::new(RawMultipath {
+                enabled: false,
+                local_max_path_id,
+                remote_max_path_id: 0,
+                issued_max_path_id: 0,
+                accepted_max_path_id: 0,
+                peer_cid_limit: 0,
+                local_cid_limit: 0,
+                local_cids: BTreeMap::new(),
+                remote_cids: BTreeMap::new(),
+                opened: HashSet::new(),
+                status: HashMap::new(),
+                abandoned: HashSet::new(),
+            })),
+            issued_cids: Arc::default(),
+            parameters,
+            cid_registry,
+            path_registry,
+            rcvd_pkt_q,
+            reliable_frames,
+            tx_wakers,
+            scheduler: Arc::from(scheduler),
+        }
+    }
+
+    /// Enable multipath if both endpoints sent the initial_max_path_id transport parameter,
+    /// returns whether multipath is enabled.
+    ///
+    /// The connection IDs of the paths allowed by both endpoints are issued to the peer once
+    /// multipath is enabled, and the connection IDs of the peer are accepted for all the paths
+    /// allowed by this endpoint.
+    pub fn try_enable(&self) -> Result<bool, Error> {
+        let mut raw = self.raw.lock().unwrap();
+        if raw.enabled {
+            return Ok(true);
+        }
+        let Some(local_max_path_id) = raw.local_max_path_id else {
+            return Ok(false);
+        };
+        let Some(remote) = self.parameters.try_get_remote()? else {
+            return Ok(false);
+        };
+        let Some(remote_max_path_id) = remote.get_as::<u64>(ParameterId::InitialMaxPathId) else {
+            return Ok(false);
+        };
+        raw.enabled = true;
+        raw.remote_max_path_id = remote_max_path_id as u32;
+        raw.peer_cid_limit = remote.get_as_ensured::<u64>(ParameterId::ActiveConnectionIdLimit);
+        raw.local_cid_limit = self
+            .parameters
+            .get_local()?
+            .get_as_ensured::<u64>(ParameterId::ActiveConnectionIdLimit);
+        tracing::info!(local_max_path_id, remote_max_path_id, "multipath enabled");
+        self.issue_cids(&mut raw);
+        Ok(true)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.raw.lock().unwrap().enabled
+    }
+
+    fn issue_cids(&self, raw: &mut RawMultipath) {
+        let local_max_path_id = raw.local_max_path_id.unwrap_or_default();
+        // The peer may issue the connection IDs for any path ID up to the maximum path ID
+        // advertised by this endpoint, even if its own limit is lower
+        for path_id in raw.accepted_max_path_id + 1..=local_max_path_id {
+            let remote_cids = ArcRemoteCids::with_limit(
+                raw.local_cid_limit,
+                PathCidFrames::new(path_id, self.reliable_frames.clone()),
+            );
+            raw.remote_cids.insert(path_id, remote_cids);
+        }
+        raw.accepted_max_path_id = raw.accepted_max_path_id.max(local_max_path_id);
+
+        let max_path_id = local_max_path_id.min(raw.remote_max_path_id);
+        for path_id in raw.issued_max_path_id + 1..=max_path_id {
+            let local_cids = ArcLocalCids::with_limit(
+                self.path_registry.with_issued_cids(PathCidFrames::recorded(
+                    path_id,
+                    self.reliable_frames.clone(),
+                    self.issued_cids.clone(),
+                )),
+                raw.peer_cid_limit,
+            );
+            raw.local_cids.insert(path_id, local_cids);
+        }
+        raw.issued_max_path_id = raw.issued_max_path_id.max(max_path_id);
+    }
+
+    /// The path ID of the packets sent to the connection ID `dcid`, 0 if it is not issued for
+    /// the other paths.
+    pub fn path_id_of(&self, dcid: &ConnectionId) -> u32 {
+        let issued_cids = self.issued_cids.lock().unwrap();
+        issued_cids.paths.get(dcid).copied().unwrap_or_default()
+    }
+
+    /// Choose an unused path ID for a new path opened by this endpoint.
+    ///
+    /// The path ID is reserved for the new path, and must be given back by
+    /// [`ArcMultipath::release_path`] if the path fails to be created.
+    ///
+    /// Returns an error and sends a PATHS_BLOCKED frame if all the path IDs allowed by the peer
+    /// are used up.
+    pub fn open_path(&self) -> io::Result<u32> {
+        let mut raw = self.raw.lock().unwrap();
+        let path_id = raw
+            .local_cids
+            .keys()
+            .copied()
+            .find(|path_id| !raw.abandoned.contains(path_id) && !raw.opened.contains(path_id));
+        match path_id {
+            Some(path_id) => {
+                raw.opened.insert(path_id);
+                Ok(path_id)
+            }
+            None => {
+                let max_path_id = VarInt::from_u32(raw.remote_max_path_id);
+                self.reliable_frames
+                    .send_frame([PathsBlockedFrame::new(max_path_id)]);
+                Err(io::Error::new(
+                    io::ErrorKind::WouldBlock,
+                    "no path ID available, blocked by the peer",
+                ))
+            }
+        }
+    }
+
+    /// Give back the path ID chosen by [`ArcMultipath::open_path`], the path failed to be
+    /// created and the path ID can be used by the next path.
+    pub fn release_path(&self, path_id: u32) {
+        self.raw.lock().unwrap().opened.remove(&path_id);
+    }
+
+    /// The connection IDs of the peer to send the packets of the path with.
+    pub fn remote_cids(&self, path_id: u32) -> Option<ArcRemoteCids> {
+        if path_id == 0 {
+            return Some(self.cid_registry.remote.clone());
+        }
+        let raw = self.raw.lock().unwrap();
+        raw.remote_cids.get(&path_id).cloned()
+    }
+
+    /// Check whether the datagram is a stateless reset with the connection IDs of the peer of
+    /// all the paths, see [`ArcRemoteCids::is_stateless_reset`].
+    pub fn is_stateless_reset(&self, tail: &[u8]) -> bool {
+        self.cid_registry.remote.is_stateless_reset(tail)
+            || (self.raw.lock().unwrap().remote_cids.values())
+                .any(|remote| remote.is_stateless_reset(tail))
+    }
+
+    /// Abandon the path, the PATH_ABANDON frame is sent to the peer only once, and the peer is
+    /// allowed to use a new path ID instead.
+    ///
+    /// The resources of the path are released after 3 times the `pto`, to receive the packets
+    /// still in flight, see [`ArcMultipath::forget`].
+    pub fn abandon(&self, path_id: u32, pto: Duration, data_space: &Arc<DataSpace>) {
+        {
+            let mut raw = self.raw.lock().unwrap();
+            if !raw.enabled || !raw.abandoned.insert(path_id) {
+                return;
+            }
+            tracing::info!(path_id, "path abandoned");
+            self.reliable_frames.send_frame([PathAbandonFrame::new(
+                VarInt::from_u32(path_id),
+                VarInt::from_u64(APPLICATION_ABANDON_PATH).unwrap(),
+            )]);
+            if let Some(local_max_path_id) = raw.local_max_path_id.as_mut() {
+                *local_max_path_id = local_max_path_id.saturating_add(1);
+                let max_path_id = VarInt::from_u32(*local_max_path_id);
+                self.reliable_frames
+                    .send_frame([MaxPathIdFrame::new(max_path_id)]);
+            }
+            self.issue_cids(&mut raw);
+        }
+
+        let multipath = self.clone();
+        let data_space = data_space.clone();
+        tokio::spawn(
+            async move {
+                tokio::time::sleep(pto * 3).await;
+                multipath.forget(path_id);
+                data_space.forget_path(path_id);
+            }
+            .instrument_in_current()
+            .in_current_span(),
+        );
+    }
+
+    /// Release the connection IDs of the abandoned path.
+    pub fn forget(&self, path_id: u32) {
+        let mut raw = self.raw.lock().unwrap();
+        if let Some(local_cids) = raw.local_cids.remove(&path_id) {
+            local_cids.clear();
+        }
+        raw.remote_cids.remove(&path_id);
+        raw.opened.remove(&path_id);
+        raw.status.remove(&path_id);
+        self.issued_cids.lock().unwrap().remove_path(path_id);
+    }
+
+    /// Release the connection IDs of all the paths other than the path 0, when the connection
+    /// is terminated.
+    pub fn clear(&self) {
+        let mut raw = self.raw.lock().unwrap();
+        raw.enabled = false;
+        for (_, local_cids) in core::mem::take(&mut raw.local_cids) {
+            local_cids.clear();
+        }
+        raw.remote_cids.clear();
+        *self.issued_cids.lock().unwrap() = IssuedPathCids::default();
+    }
+
+    /// Mark the path as a backup path or an available path, and tell the peer with a
+    /// PATH_STATUS_BACKUP or PATH_STATUS_AVAILABLE frame.
+    pub fn set_backup(&self, path_id: u32, is_backup: bool) -> io::Result<()> {
+        let mut raw = self.raw.lock().unwrap();
+        if !raw.enabled {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "multipath is not enabled",
+            ));
+        }
+        let status = raw.status.entry(path_id).or_default();
+        status.local_sequence += 1;
+        status.local_backup = is_backup;
+        self.reliable_frames.send_frame([PathStatusFrame::new(
+            VarInt::from_u32(path_id),
+            VarInt::from_u64(status.local_sequence).unwrap(),
+            is_backup,
+        )]);
+        // the schedulers choose the paths carrying the data again
+        self.tx_wakers.wake_all_by(Signals::TRANSPORT);
+        Ok(())
+    }
+
+    /// Whether the path is marked as a backup path by either endpoint.
+    pub fn is_backup(&self, path_id: u32) -> bool {
+        let raw = self.raw.lock().unwrap();
+        raw.status
+            .get(&path_id)
+            .is_some_and(|status| status.local_backup || status.remote_backup)
+    }
+
+    fn snapshot(&self, path: &Path) -> PathSnapshot {
+        let congestion = path.cc().stats();
+        PathSnapshot {
+            path_id: path.path_id(),
+            pathway: path.pathway(),
+            smoothed_rtt: congestion.smoothed_rtt,
+            is_congested: congestion.bytes_in_flight + path.mtu() as usize
+                > congestion.congestion_window,
+            is_backup: self.is_backup(path.path_id()),
+        }
+    }
+
+    /// Ask the scheduler what the next burst of the `path` carries.
+    ///
+    /// The path carries the data as usual, if multipath is not enabled or it's the only
+    /// validated path.
+    pub fn schedule(&self, path: &Path, paths: &ArcPathContexts) -> Carriage {
+        if !self.is_enabled() {
+            return Carriage::Data;
+        }
+        let validated = paths
+            .iter()
+            .filter(|path| path.is_validated())
+            .collect::<Vec<_>>();
+        let snapshots = validated
+            .iter()
+            .map(|path| self.snapshot(path))
+            .collect::<Vec<_>>();
+        let Some(this) = snapshots
+            .iter()
+            .find(|snapshot| snapshot.pathway == path.pathway())
+        else {
+            return Carriage::Data;
+        };
+        if snapshots.len() == 1 {
+            return Carriage::Data;
+        }
+        let may_carry_data = self.scheduler.may_carry_data(this, &snapshots);
+        if !may_carry_data {
+            // The decision changes as the RTTs and the congestion windows change, the paths
+            // carrying the data now may be waiting for the TRANSPORT signal
+            validated
+                .iter()
+                .zip(&snapshots)
+                .filter(|(_, other)| other.path_id != this.path_id)
+                .filter(|(_, other)| self.scheduler.may_carry_data(other, &snapshots))
+                .for_each(|(other, _)| other.tx_waker().wake_by(Signals::TRANSPORT));
+        }
+        match (may_carry_data, self.scheduler.is_redundant()) {
+            (true, false) => Carriage::Data,
+            (true, true) => Carriage::DuplicatedData,
+            (false, true) => Carriage::Duplicates,
+            (false, false) => Carriage::Nothing,
+        }
+    }
+
+    /// Called after a burst carrying the new data is sent on the `path`, the other paths are
+    /// woken up to be scheduled again.
+    pub fn on_data_sent(&self, path: &Path) {
+        if !self.is_enabled() {
+            return;
+        }
+        self.scheduler.on_data_sent(&self.snapshot(path));
+        self.tx_wakers.wake_all_by(Signals::TRANSPORT);
+    }
+
+    /// Called when the congestion window of a path is used up, the other paths are woken up
+    /// to be scheduled again.
+    pub fn on_congested(&self) {
+        self.tx_wakers.wake_all_by(Signals::TRANSPORT);
+    }
+
+    /// Check the path ID of the frame received, returns the connection IDs issued to the peer
+    /// and the connection IDs of the peer of the path, or None if the path has been abandoned or
+    /// is not allowed by the peer.
+    fn path_cids(
+        &self,
+        path_id: u64,
+        frame: &impl GetFrameType,
+    ) -> Result<(Option<ArcLocalCids>, Option<ArcRemoteCids>), Error> {
+        let is_enabled = self.try_enable()?;
+        let raw = self.raw.lock().unwrap();
+        let max_path_id = raw.local_max_path_id.unwrap_or_default() as u64;
+        if !is_enabled || path_id > max_path_id {
+            tracing::error!("   Cause by: received a frame of the path {path_id} not allowed");
+            return Err(QuicError::new(
+                ErrorKind::ProtocolViolation,
+                frame.frame_type().into(),
+                format!("path ID {path_id} exceeds the maximum path ID {max_path_id}"),
+            )
+            .into());
+        }
+        if path_id == 0 {
+            return Ok((
+                Some(self.cid_registry.local.clone()),
+                Some(self.cid_registry.remote.clone()),
+            ));
+        }
+        let path_id = path_id as u32;
+        Ok((
+            raw.local_cids.get(&path_id).cloned(),
+            raw.remote_cids.get(&path_id).cloned(),
+        ))
+    }
+}
+
+impl ReceiveFrame<PathNewConnectionIdFrame> for ArcMultipath {
+    type Output = ();
+
+    fn recv_frame(&self, frame: &PathNewConnectionIdFrame) -> Result<Self::Output, Error> {
+        let (_, Some(remote_cids)) = self.path_cids(frame.path_id(), frame)? else {
+            return Ok(());
+        };
+        if let Some(reset_token) = remote_cids.recv_frame(frame.new_connection_id())? {
+            self.path_registry
+                .proto()
+                .add_reset_entry(reset_token, self.rcvd_pkt_q.clone());
+        }
+        Ok(())
+    }
+}
+
+impl ReceiveFrame<PathRetireConnectionIdFrame> for ArcMultipath {
+    type Output = ();
+
+    fn recv_frame(&self, frame: &PathRetireConnectionIdFrame) -> Result<Self::Output, Error> {
+        let (Some(local_cids), _) = self.path_cids(frame.path_id(), frame)? else {
+            return Ok(());
+        };
+        let retire_connection_id = frame.retire_connection_id();
+        local_cids.recv_frame(&retire_connection_id)?;
+        self.issued_cids
+            .lock()
+            .unwrap()
+            .retire(frame.path_id() as u32, retire_connection_id.sequence());
+        Ok(())
+    }
+}
+
+impl ReceiveFrame<PathStatusFrame> for ArcMultipath {
+    type Output = ();
+
+    fn recv_frame(&self, frame: &PathStatusFrame) -> Result<Self::Output, Error> {
+        if let (None, None) = self.path_cids(frame.path_id(), frame)? {
+            return Ok(());
+        }
+        let mut raw = self.raw.lock().unwrap();
+        let status = raw.status.entry(frame.path_id() as u32).or_default();
+        // the PATH_STATUS frames may be reordered, only the latest one takes effect
+        if status
+            .remote_sequence
+            .is_some_and(|sequence| frame.sequence() <= sequence)
+        {
+            return Ok(());
+        }
+        status.remote_sequence = Some(frame.sequence());
+        status.remote_backup = frame.is_backup();
+        self.tx_wakers.wake_all_by(Signals::TRANSPORT);
+        Ok(())
+    }
+}
+
+impl ReceiveFrame<MaxPathIdFrame> for ArcMultipath {
+    type Output = ();
+
+    fn recv_frame(&self, frame: &MaxPathIdFrame) -> Result<Self::Output, Error> {
+        if !self.try_enable()? {
+            tracing::error!("   Cause by: received MAX_PATH_ID without multipath enabled");
+            return Err(QuicError::new(
+                ErrorKind::ProtocolViolation,
+                frame.frame_type().into(),
+                "multipath is not enabled",
+            )
+            .into());
+        }
+        let mut raw = self.raw.lock().unwrap();
+        let max_path_id = frame.max_path_id().min(u32::MAX as u64) as u32;
+        if max_path_id > raw.remote_max_path_id {
+            raw.remote_max_path_id = max_path_id;
+            self.issue_cids(&mut raw);
+        }
+        Ok(())
+    }
+}
+
+impl ReceiveFrame<PathsBlockedFrame> for ArcMultipath {
+    type Output = ();
+
+    fn recv_frame(&self, _frame: &PathsBlockedFrame) -> Result<Self::Output, Error> {
+        // the new path IDs are allowed once the paths in use are abandoned
+        Ok(())
+    }
+}
+
+impl ReceiveFrame<PathCidsBlockedFrame> for ArcMultipath {
+    type Output = ();
+
+    fn recv_frame(&self, _frame: &PathCidsBlockedFrame) -> Result<Self::Output, Error> {
+        // the connection IDs are issued up to the limit of the peer at once
+        Ok(())
+    }
+}
+
+/// Remove the paths abandoned by the peer, and abandon them as well.
+pub struct AbandonPaths {
+    multipath: ArcMultipath,
+    paths: ArcPathContexts,
+    data_space: Arc<DataSpace>,
+}
+
+impl AbandonPaths {
+    pub fn new(
+        multipath: ArcMultipath,
+        paths: ArcPathContexts,
+        data_space: Arc<DataSpace>,
+    ) -> Self {
+        Self {
+            multipath,
+            paths,
+            data_space,
+        }
+    }
+}
+
+impl ReceiveFrame<PathAbandonFrame> for AbandonPaths {
+    type Output = ();
+
+    fn recv_frame(&self, frame: &PathAbandonFrame) -> Result<Self::Output, Error> {
+        if let (None, None) = self.multipath.path_cids(frame.path_id(), frame)? {
+            return Ok(());
+        }
+        let path_id = frame.path_id() as u32;
+        tracing::info!(
+            path_id,
+            error_code = frame.error_code(),
+            "path abandoned by peer"
+        );
+        match self.paths.get_by_id(path_id) {
+            // the path abandons itself when removed
+            Some(path) => self.paths.remove(&path.pathway(), "abandoned by peer"),
+            None if path_id != 0 => {
+                let pto = self.paths.max_pto_duration().unwrap_or_default();
+                self.multipath.abandon(path_id, pto, &self.data_space);
+            }
+            None => {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use qbase::{
+        param::{ClientParameters, ServerParameters, WriteParameters},
+        token::ResetToken,
+    };
+    use qinterface::router::QuicProto;
+
+    use super::*;
+    use crate::path::scheduler::{PathScheduling, ProductPathScheduler};
+
+    /// The multipath state of a server advertising `local_max_path_id`, after receiving the
+    /// transport parameters of a client advertising `remote_max_path_id`.
+    fn server_multipath(local_max_path_id: u32, remote_max_path_id: u32) -> ArcMultipath {
+        let mut server_params = ServerParameters::default();
+        server_params.set_initial_max_path_id(local_max_path_id);
+        server_params.set_active_connection_id_limit(2u32);
+        let parameters = ArcParameters::new_server(server_params);
+
+        let mut client_params = ClientParameters::default();
+        client_params.set_initial_max_path_id(remote_max_path_id);
+        client_params.set_active_connection_id_limit(2u32);
+        client_params.set_initial_source_connection_id(ConnectionId::random_gen(8));
+        let mut buf = Vec::new();
+        buf.put_parameters(client_params.as_ref());
+        parameters.recv_remote_params(&buf, |_| Ok(())).unwrap();
+
+        let proto = Arc::new(QuicProto::new());
+        let rcvd_pkt_q = Arc::new(RcvdPacketQueue::new());
+        let tx_wakers = ArcSendWakers::default();
+        let reliable_frames = ArcReliableFrameDeque::with_capacity_and_wakers(8, tx_wakers.clone());
+        let router_registry = proto.registry(
+            rcvd_pkt_q.clone(),
+            PathCidFrames::new(0, reliable_frames.clone()),
+        );
+        let path_registry = router_registry.with_issued_cids(());
+        let cid_registry = CidRegistry::new(
+            ArcLocalCids::new(ConnectionId::random_gen(8), router_registry),
+            ArcRemoteCids::new(
+                ConnectionId::random_gen(8),
+                2,
+                PathCidFrames::new(0, reliable_frames.clone()),
+            ),
+        );
+        let multipath = ArcMultipath::new(
+            parameters,
+            cid_registry,
+            path_registry,
+            rcvd_pkt_q,
+            reliable_frames,
+            tx_wakers,
+            PathScheduling::MinRtt.init(),
+        );
+        assert!(multipath.try_enable().unwrap());
+        multipath
+    }
+
+    fn path_new_connection_id(path_id: u32) -> PathNewConnectionIdFrame {
+        PathNewConnectionIdFrame::new(
+            VarInt::from_u32(path_id),
+            NewConnectionIdFrame::new(
+                ConnectionId::random_gen(8),
+                VarInt::from_u32(0),
+                VarInt::from_u32(0),
+                ResetToken::random_gen(),
+            ),
+        )
+    }
+
+    #[test]
+    fn different_max_path_ids() {
+        let multipath = server_multipath(3, 1);
+
+        // The connection IDs of the peer are accepted for all the paths allowed by this endpoint
+        for path_id in 1..=3 {
+            let frame = path_new_connection_id(path_id);
+            multipath.recv_frame(&frame).unwrap();
+            let remote_cids = multipath.remote_cids(path_id).unwrap();
+            assert_eq!(
+                remote_cids.latest_dcid(),
+                Some(*frame.new_connection_id().connection_id())
+            );
+        }
+        assert!(multipath.recv_frame(&path_new_connection_id(4)).is_err());
+
+        // Only the paths allowed by the peer can be opened
+        assert_eq!(multipath.open_path().unwrap(), 1);
+        assert_eq!(
+            multipath.open_path().unwrap_err().kind(),
+            io::ErrorKind::WouldBlock
+        );
+
+        // The peer raises its limit
+        multipath
+            .recv_frame(&MaxPathIdFrame::new(VarInt::from_u32(2)))
+            .unwrap();
+        assert_eq!(multipath.open_path().unwrap(), 2);
+    }
+
+    #[test]
+    fn release_path_id() {
+        let multipath = server_multipath(2, 2);
+        assert_eq!(multipath.open_path().unwrap(), 1);
+        // The path failed to be created, the path ID is not used up
+        multipath.release_path(1);
+        assert_eq!(multipath.open_path().unwrap(), 1);
+        assert_eq!(multipath.open_path().unwrap(), 2);
+        assert!(multipath.open_path().is_err());
+    }
+
+    #[test]
+    fn path_id_of_issued_cids() {
+        let multipath = server_multipath(2, 2);
+        let issued = (multipath.issued_cids.lock().unwrap().cids.clone())
+            .into_iter()
+            .collect::<Vec<_>>();
+        // 2 connection IDs are issued for each of the paths 1 and 2
+        assert_eq!(issued.len(), 4);
+        for ((path_id, _), cid) in &issued {
+            assert_eq!(multipath.path_id_of(cid), *path_id);
+        }
+        assert_eq!(multipath.path_id_of(&ConnectionId::random_gen(8)), 0);
+
+        // The retired connection IDs are forgotten
+        let ((path_id, sequence), cid) = issued[0];
+        multipath
+            .recv_frame(&PathRetireConnectionIdFrame::new(
+                VarInt::from_u32(path_id),
+                VarInt::from_u64(sequence).unwrap(),
+            ))
+            .unwrap();
+        assert_eq!(multipath.path_id_of(&cid), 0);
+
+        multipath.forget(path_id);
+        assert!(
+            issued
+                .iter()
+                .filter(|((id, _), _)| *id == path_id)
+                .all(|(_, cid)| multipath.path_id_of(cid) == 0)
+        );
+    }
+}
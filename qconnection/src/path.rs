@@ -3,7 +3,7 @@ use std::{
     ops::Deref,
     sync::{
         Arc, Mutex,
-        atomic::{AtomicBool, AtomicU16, Ordering},
+        atomic::{AtomicU16, AtomicU64, Ordering},
     },
 };
 
@@ -13,18 +13,24 @@ use qbase::{
     frame::{PathChallengeFrame, PathResponseFrame, ReceiveFrame},
     net::{
         address::BindAddr,
-        route::{Link, PacketHeader, Pathway},
-        tx::ArcSendWaker,
+        route::{Ecn, Link, PacketHeader, Pathway},
+        tx::{ArcSendWaker, Signals},
     },
     packet::PacketContains,
 };
-use qcongestion::{Algorithm, ArcCC, Feedback, HandshakeStatus, MSS, PathStatus, Transport};
+use qcongestion::{
+    ArcAckFrequency, ArcCC, Feedback, HandshakeStatus, MSS, PathStatus,
+    ProductCongestionController, Transport,
+};
 use qinterface::{QuicInterface, router::QuicProto};
 use tokio::{
+    sync::watch,
     task::AbortHandle,
     time::{Duration, Instant},
 };
 
+use crate::stats::PathStats;
+
 mod aa;
 mod paths;
 mod util;
@@ -33,10 +39,15 @@ pub use paths::*;
 pub use util::*;
 pub mod burst;
 pub mod idle;
+pub mod mtu;
+pub mod scheduler;
 
 pub struct Path {
     interface: Arc<dyn QuicInterface>,
-    validated: AtomicBool,
+    // The path identifier of the multipath extension, 0 if multipath is not enabled
+    path_id: u32,
+    // None until the path is validated, or the validation failed
+    validated: watch::Sender<Option<bool>>,
     link: Link,
     pathway: Pathway,
     cc: (ArcCC, AbortHandle),
@@ -47,16 +58,23 @@ pub struct Path {
     response_rcvbuf: RecvBuffer<PathResponseFrame>,
     tx_waker: ArcSendWaker,
     pmtu: Arc<AtomicU16>,
+    mtu_prober: mtu::MtuProber,
     status: PathStatus,
+    rcvd_packets: AtomicU64,
+    rcvd_bytes: [AtomicU64; Epoch::count()],
 }
 
 impl Path {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         proto: &QuicProto,
         bind_addr: BindAddr,
         link: Link,
         pathway: Pathway,
+        path_id: u32,
+        congestion_controller: &dyn ProductCongestionController,
         max_ack_delay: Duration,
+        ack_frequency: ArcAckFrequency,
         feedbacks: [Arc<dyn Feedback>; 3],
         handshake_status: Arc<HandshakeStatus>,
     ) -> io::Result<Self> {
@@ -69,8 +87,9 @@ impl Path {
         let tx_waker = ArcSendWaker::new();
 
         let cc = ArcCC::new(
-            Algorithm::NewReno,
+            congestion_controller,
             max_ack_delay,
+            ack_frequency,
             feedbacks,
             path_status.clone(),
             tx_waker.clone(),
@@ -78,23 +97,33 @@ impl Path {
         let handle = cc.launch();
         Ok(Self {
             interface,
+            path_id,
             link,
             pathway,
             cc: (cc, handle),
-            validated: AtomicBool::new(false),
+            validated: watch::Sender::new(None),
             anti_amplifier: AntiAmplifier::new(tx_waker.clone()),
             last_active_time: tokio::time::Instant::now().into(),
             challenge_sndbuf: SendBuffer::new(tx_waker.clone()),
             response_sndbuf: SendBuffer::new(tx_waker.clone()),
             response_rcvbuf: Default::default(),
+            mtu_prober: mtu::MtuProber::new(tx_waker.clone()),
             tx_waker,
             pmtu,
             status: path_status,
+            rcvd_packets: AtomicU64::new(0),
+            rcvd_bytes: Default::default(),
         })
     }
 
     pub fn skip_validation(&self) {
-        self.validated.store(true, Ordering::Release);
+        self.on_validated();
+    }
+
+    fn on_validated(&self) {
+        self.conclude_validation(true);
+        // the path can carry the data now
+        self.tx_waker.wake_by(Signals::TRANSPORT);
     }
 
     pub async fn validate(&self) -> bool {
@@ -105,17 +134,59 @@ impl Path {
             match tokio::time::timeout(pto, self.response_rcvbuf.receive()).await {
                 Ok(Some(response)) if *response == *challenge => {
                     self.anti_amplifier.grant();
+                    self.on_validated();
                     return true;
                 }
                 // 外部发生变化，导致路径验证任务作废
-                Ok(None) => return false,
+                Ok(None) => break,
                 // 超时或者收到不对的response，按"停-等协议"，继续再发一次Challenge，最多3次
                 _ => continue,
             }
         }
+        self.conclude_validation(false);
         false
     }
 
+    /// Record the result of the path validation, only the first one takes effect.
+    pub(crate) fn conclude_validation(&self, is_validated: bool) {
+        self.validated.send_if_modified(|state| match state {
+            None => {
+                *state = Some(is_validated);
+                true
+            }
+            Some(_) => false,
+        });
+    }
+
+    pub fn is_validated(&self) -> bool {
+        *self.validated.borrow() == Some(true)
+    }
+
+    /// Wait for the path to be validated, returns false if the validation failed, or the path
+    /// has been removed.
+    pub async fn validated(&self) -> bool {
+        let mut validated = self.validated.subscribe();
+        let result = validated.wait_for(Option::is_some).await;
+        result.is_ok_and(|state| *state == Some(true))
+    }
+
+    pub fn link(&self) -> Link {
+        self.link
+    }
+
+    pub fn bind_addr(&self) -> BindAddr {
+        self.interface.bind_addr()
+    }
+
+    pub fn pathway(&self) -> Pathway {
+        self.pathway
+    }
+
+    /// The path identifier of the multipath extension, always 0 if multipath is not enabled.
+    pub fn path_id(&self) -> u32 {
+        self.path_id
+    }
+
     pub fn cc(&self) -> &ArcCC {
         &self.cc.0
     }
@@ -136,6 +207,8 @@ impl Path {
         packet_contains: PacketContains,
     ) {
         self.anti_amplifier.on_rcvd(size);
+        self.rcvd_packets.fetch_add(1, Ordering::Relaxed);
+        self.rcvd_bytes[epoch].fetch_add(size as u64, Ordering::Relaxed);
         if size > 0 {
             self.status.release_anti_amplification_limit();
         }
@@ -153,16 +226,54 @@ impl Path {
         self.pmtu.load(Ordering::Acquire)
     }
 
-    pub async fn send_packets(&self, mut segments: &[io::IoSlice<'_>]) -> io::Result<()> {
+    pub fn mtu_prober(&self) -> &mtu::MtuProber {
+        &self.mtu_prober
+    }
+
+    /// Take a snapshot of the path, `is_active` tells whether the path carries the new data.
+    pub fn stats(&self, is_active: bool) -> PathStats {
+        PathStats {
+            path_id: self.path_id,
+            pathway: self.pathway,
+            link: self.link,
+            is_active,
+            is_validated: self.is_validated(),
+            pmtu: self.mtu() as usize,
+            rcvd_packets: self.rcvd_packets.load(Ordering::Relaxed),
+            rcvd_bytes: core::array::from_fn(|index| {
+                self.rcvd_bytes[index].load(Ordering::Relaxed)
+            }),
+            congestion: self.cc().stats(),
+        }
+    }
+
+    pub async fn send_packets(
+        &self,
+        mut segments: &[io::IoSlice<'_>],
+        ecn: Option<Ecn>,
+    ) -> io::Result<()> {
         self.anti_amplifier
             .on_sent(segments.iter().map(|s| s.len()).sum());
         if self.anti_amplifier.balance().is_err() {
             self.status.enter_anti_amplification_limit();
         }
         while !segments.is_empty() {
-            let hdr = PacketHeader::new(self.pathway, self.link, 64, None, self.mtu() as _);
-            let sent =
-                core::future::poll_fn(|cx| self.interface.poll_send(cx, segments, hdr)).await?;
+            // the segments are equal-sized except the last one, but a PMTU probe exceeds the PMTU
+            let seg_size = segments.iter().map(|s| s.len()).max().unwrap_or_default();
+            let hdr = PacketHeader::new(self.pathway, self.link, 64, ecn, seg_size as _);
+            let sent = match core::future::poll_fn(|cx| self.interface.poll_send(cx, segments, hdr))
+                .await
+            {
+                Ok(sent) => sent,
+                // The PMTU probe is sent alone, if it is larger than the MTU of the network
+                // device, it is dropped just like by the network, and the PMTU discovery
+                // finds it lost
+                Err(error) if segments.len() == 1 && seg_size > self.mtu() as usize => {
+                    tracing::debug!(seg_size, "PMTU probe rejected by the interface: {error}");
+                    1
+                }
+                Err(error) => return Err(error),
+            };
             segments = &segments[sent..];
         }
         Ok(())
@@ -172,6 +283,7 @@ impl Path {
 impl Drop for Path {
     fn drop(&mut self) {
         self.response_rcvbuf.dismiss();
+        self.conclude_validation(false);
         self.cc.1.abort();
     }
 }
@@ -1,33 +1,53 @@
 use std::{
     io,
-    ops::ControlFlow,
-    sync::{Arc, atomic::Ordering},
+    sync::Arc,
     task::{Context, Poll, ready},
 };
 
-use qbase::net::tx::Signals;
+use qbase::{
+    Epoch,
+    error::{ErrorKind, QuicError},
+    net::{route::Ecn, tx::Signals},
+};
+use qcongestion::Transport;
 
+use super::ArcPathContexts;
 use crate::{
-    ArcDcidCell, ArcLocalCids, Components, FlowController, space::Spaces, tls::ArcSendGate,
+    ArcDcidCell, ArcLocalCids, Components, FlowController,
+    events::{ArcEventBroker, EmitEvent, Event},
+    multipath::ArcMultipath,
+    space::Spaces,
+    tls::ArcSendGate,
     tx::Transaction,
 };
 
 pub struct Burst {
     path: Arc<super::Path>,
+    paths: ArcPathContexts,
+    multipath: ArcMultipath,
     local_cids: ArcLocalCids,
     dcid: ArcDcidCell,
     spin: bool,
     flow_ctrl: FlowController,
     spaces: Spaces,
     send_gate: Option<ArcSendGate>,
+    event_broker: ArcEventBroker,
 }
 
 impl super::Path {
     pub fn new_burst(self: &Arc<Self>, components: &Components) -> Burst {
         let local_cids = components.cid_registry.local.clone();
-        let dcid = components.cid_registry.remote.apply_dcid();
+        // The paths other than the path 0 use the connection IDs issued for them by the peer
+        let dcid = match self.path_id {
+            0 => components.cid_registry.remote.apply_dcid(),
+            path_id => (components.multipath.remote_cids(path_id))
+                .expect("the connection IDs of the path must be issued before it is created")
+                .apply_dcid(),
+        };
         let flow_ctrl = components.flow_ctrl.clone();
         let path = self.clone();
+        let paths = components.paths.clone();
+        let multipath = components.multipath.clone();
         let spin = false;
         let spaces = components.spaces.clone();
         let send_gate = match &components.specific {
@@ -36,14 +56,32 @@ impl super::Path {
                 Some(server_components.send_gate.clone())
             }
         };
+        let event_broker = components.event_broker.clone();
         Burst {
             path,
+            paths,
+            multipath,
             local_cids,
             dcid,
             spin,
             flow_ctrl,
             spaces,
             send_gate,
+            event_broker,
+        }
+    }
+}
+
+impl Drop for Burst {
+    fn drop(&mut self) {
+        // rfc9000 9.5
+        // the connection ID used on the removed path is retired, and never used on other paths
+        self.dcid.retire();
+        // The path removed without a PATH_ABANDON frame from the peer is abandoned by us
+        let path_id = self.path.path_id();
+        if path_id != 0 && self.paths.get_by_id(path_id).is_none() {
+            let pto = self.path.cc().get_pto(Epoch::Data);
+            self.multipath.abandon(path_id, pto, self.spaces.data());
         }
     }
 }
@@ -51,26 +89,23 @@ impl super::Path {
 impl Burst {
     fn prepare<'b>(
         &self,
-        buffers: &'b mut Vec<Vec<u8>>,
-    ) -> Result<Option<(impl Iterator<Item = &'b mut [u8]>, Transaction)>, Signals> {
+        buffer: &'b mut Vec<u8>,
+    ) -> Result<Option<(&'b mut [u8], Transaction<'_>)>, Signals> {
         let max_segments = self.path.interface.max_segments();
         let max_segment_size = self.path.interface.max_segment_size();
 
-        if buffers.len() < max_segments {
-            buffers.resize_with(max_segments, || vec![0; max_segment_size]);
+        let buffer_size = max_segments * max_segment_size;
+        if buffer.len() < buffer_size {
+            buffer.resize(buffer_size, 0);
         }
 
-        let buffers = buffers.iter_mut().map(move |buffer| {
-            if buffer.len() < max_segment_size {
-                buffer.resize(max_segment_size, 0);
-            }
-            &mut buffer[..max_segment_size]
-        });
-
         let scid = self.local_cids.initial_scid();
+        let carriage = self.multipath.schedule(&self.path, &self.paths);
         let transaction = Transaction::prepare(
             scid.unwrap_or_default(),
             &self.dcid,
+            self.path.path_id(),
+            carriage,
             self.path.cc(),
             &self.path.anti_amplifier,
             &self.flow_ctrl,
@@ -79,99 +114,145 @@ impl Burst {
         if transaction.is_none() {
             return Ok(None);
         }
-        Ok(Some((buffers, transaction.unwrap())))
+        Ok(Some((&mut buffer[..buffer_size], transaction.unwrap())))
+    }
+
+    fn load_segment<'b>(
+        &'b self,
+        buffer: &mut [u8],
+        transaction: &mut Transaction<'b>,
+    ) -> Result<usize, Signals> {
+        let scid = self.local_cids.initial_scid();
+        let reversed_size = 0; // TODO
+        // The paths other than the active one only send the probing packets, the packets sent on
+        // them would never be acknowledged once the peer moves away
+        let is_active = self.paths.is_active(&self.path.pathway);
+        // The long header packets are only sent on the path 0
+        if scid.is_some() && is_active && self.path.path_id() == 0 {
+            transaction.load_spaces(
+                &mut buffer[reversed_size..],
+                &self.spaces,
+                self.spin.into(),
+                &self.path.challenge_sndbuf,
+                &self.path.response_sndbuf,
+            )
+        } else if is_active && self.path.is_validated() {
+            transaction.load_one_rtt(
+                &mut buffer[reversed_size..],
+                self.spin.into(),
+                &self.path.challenge_sndbuf,
+                &self.path.response_sndbuf,
+                self.spaces.data(),
+            )
+        } else {
+            transaction.load_validation(
+                &mut buffer[reversed_size..],
+                self.spin.into(),
+                &self.path.challenge_sndbuf,
+                &self.path.response_sndbuf,
+                self.spaces.data(),
+            )
+        }
+        .or_else(|signals| {
+            // PING is not a probing frame, the peer would switch to the path receiving it
+            if !is_active {
+                return Err(signals);
+            }
+            transaction
+                .load_ping(&mut buffer[reversed_size..], self.spin.into(), &self.spaces)
+                .map_err(|s| s | signals)
+        })
+        .map(|packet_size| reversed_size + packet_size)
     }
 
     fn load_into_buffers<'b>(
         &'b self,
-        prepared_buffers: impl Iterator<Item = &'b mut [u8]> + 'b,
+        buffer: &'b mut [u8],
         mut transaction: Transaction<'b>,
     ) -> io::Result<Result<Vec<usize>, Signals>> {
-        use core::ops::ControlFlow::*;
-
-        let scid = self.local_cids.initial_scid();
-        let reversed_size = 0; // TODO
         let max_segments = self.path.interface.max_segments();
+        let max_segment_size = self.path.interface.max_segment_size();
 
-        let (ControlFlow::Break(result) | ControlFlow::Continue(result)) = prepared_buffers
-            .map(move |segment| {
-                let buffer_size = segment.len().min(self.path.mtu() as _);
-                let buffer = &mut segment[..buffer_size];
-                if scid.is_some() {
-                    transaction.load_spaces(
-                        &mut buffer[reversed_size..],
-                        &self.spaces,
-                        self.spin.into(),
-                        &self.path.challenge_sndbuf,
-                        &self.path.response_sndbuf,
-                    )
-                } else if self.path.validated.load(Ordering::Acquire) {
-                    transaction.load_one_rtt(
-                        &mut buffer[reversed_size..],
-                        self.spin.into(),
-                        &self.path.challenge_sndbuf,
-                        &self.path.response_sndbuf,
-                        self.spaces.data(),
-                    )
-                } else {
-                    transaction.load_validation(
-                        &mut buffer[reversed_size..],
-                        self.spin.into(),
-                        &self.path.challenge_sndbuf,
-                        &self.path.response_sndbuf,
-                        self.spaces.data(),
-                    )
-                }
-                .or_else(|signals| {
-                    transaction
-                        .load_ping(&mut buffer[reversed_size..], self.spin.into(), &self.spaces)
-                        .map_err(|s| s | signals)
+        // the PMTU probe is larger than the other packets, so it is sent in a datagram alone.
+        // It carries a PING frame, so it is only sent on the active path as well
+        if let Some(size) = self.path.mtu_prober.pending() {
+            if let Some(Ok((pn, probe_size))) = buffer
+                .get_mut(..size as usize)
+                .filter(|_| self.paths.is_active(&self.path.pathway))
+                .map(|buffer| {
+                    transaction.load_mtu_probe(buffer, self.spin.into(), self.spaces.data())
                 })
-                .map(|packet_size| io::IoSlice::new(&buffer[..reversed_size + packet_size]))
-            })
-            .try_fold(
-                Ok(Vec::with_capacity(max_segments)),
-                |segments, load_result| match (segments, load_result) {
-                    (Ok(segments), Err(signals)) if segments.is_empty() => Break(Err(signals)),
-                    (Ok(segments), Err(_signals)) => Break(Ok(segments)),
-                    (Ok(mut segments), Ok(segment))
-                        if segment.len() < segments.last().copied().unwrap_or_default() =>
-                    {
-                        segments.push(segment.len());
-                        Break(Ok(segments))
-                    }
-                    (Ok(mut segments), Ok(segment)) => {
-                        segments.push(segment.len());
-                        Continue(Ok(segments))
+            {
+                self.path.mtu_prober.on_probe_sent(pn, size);
+                return Ok(Ok(vec![probe_size]));
+            }
+        }
+
+        // The segments are laid out back to back, all of them fill the segment size except the
+        // last one, so that the interface can send them in a single GSO buffer
+        let segment_size = max_segment_size.min(self.path.mtu() as _);
+        let mut segments = Vec::with_capacity(max_segments);
+        let mut remaining = buffer;
+        while segments.len() < max_segments && remaining.len() >= segment_size {
+            let (segment, rest) = core::mem::take(&mut remaining).split_at_mut(segment_size);
+            match self.load_segment(segment, &mut transaction) {
+                Err(signals) if segments.is_empty() => return Ok(Err(signals)),
+                Err(_signals) => break,
+                Ok(packet_size) => {
+                    segments.push(packet_size);
+                    if packet_size < segment_size {
+                        break;
                     }
-                    (Err(_), _) => unreachable!(),
-                },
-            );
-        Ok(result)
+                }
+            }
+            remaining = rest;
+        }
+        Ok(Ok(segments))
     }
 
     fn poll_burst<'b>(
         &'b self,
         cx: &mut Context<'_>,
-        buffers: &'b mut Vec<Vec<u8>>,
-    ) -> Poll<io::Result<Vec<usize>>> {
+        buffer: &'b mut Vec<u8>,
+    ) -> Poll<io::Result<(Vec<usize>, Option<Ecn>)>> {
         if let Some(send_gate) = &self.send_gate {
             ready!(send_gate.poll_request_permit(cx));
         }
-        let (buffers, transaction) = match self.prepare(buffers) {
-            Ok(Some((buffers, transaction))) => (buffers, transaction),
+        let (buffer, transaction) = match self.prepare(buffer) {
+            Ok(Some((buffer, transaction))) => (buffer, transaction),
             Ok(None) => return Poll::Pending, // 发送任务结束。阻止路径因为连接关闭而被移除
             Err(siginals) => {
+                // Another path may carry the data while this one is congested
+                if siginals.contains(Signals::CONGESTION) && self.multipath.is_enabled() {
+                    self.multipath.on_congested();
+                }
                 self.path.tx_waker.wait_for(cx, siginals);
                 return Poll::Pending;
             }
         };
-        match self.load_into_buffers(buffers, transaction)? {
+        // All packets of the burst are sent in the same datagram header
+        let ecn = self.path.cc().ecn_codepoint();
+        let carriage = transaction.carriage();
+        match self.load_into_buffers(buffer, transaction)? {
             Ok(segments) => {
                 debug_assert!(!segments.is_empty());
-                Poll::Ready(Ok(segments))
+                if carriage.carries_new_data() {
+                    self.multipath.on_data_sent(&self.path);
+                }
+                Poll::Ready(Ok((segments, ecn)))
             }
             Err(signals) => {
+                // rfc9001 6.6
+                // If a key update is not possible or integrity limits are reached, the endpoint
+                // MUST stop using the connection
+                if signals.contains(Signals::KEYS) && self.spaces.data().is_one_rtt_key_exhausted()
+                {
+                    let error = QuicError::with_default_fty(
+                        ErrorKind::AeadLimitReached,
+                        "the confidentiality limit is reached before the keys can be updated",
+                    );
+                    self.event_broker.emit(Event::Failed(error));
+                }
                 self.path.tx_waker.wait_for(cx, signals);
                 Poll::Pending
             }
@@ -179,16 +260,19 @@ impl Burst {
     }
 
     pub async fn launch(self) -> io::Result<()> {
-        let mut buffers = vec![];
+        let mut buffer = vec![];
         loop {
-            let segment_lens =
-                core::future::poll_fn(|cx| self.poll_burst(cx, &mut buffers)).await?;
+            let (segment_lens, ecn) =
+                core::future::poll_fn(|cx| self.poll_burst(cx, &mut buffer)).await?;
             let segments = segment_lens
                 .into_iter()
-                .enumerate()
-                .map(|(seg_idx, seg_len)| io::IoSlice::new(&buffers[seg_idx][..seg_len]))
+                .scan(0, |offset, seg_len| {
+                    let segment = io::IoSlice::new(&buffer[*offset..*offset + seg_len]);
+                    *offset += seg_len;
+                    Some(segment)
+                })
                 .collect::<Vec<_>>();
-            self.path.send_packets(&segments).await?;
+            self.path.send_packets(&segments, ecn).await?;
         }
     }
 }
@@ -0,0 +1,288 @@
+use std::{
+    future::Future,
+    sync::{Arc, Mutex, atomic::Ordering},
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+
+use qbase::{
+    Epoch,
+    frame::AckFrame,
+    net::tx::{ArcSendWaker, Signals},
+    param::ParameterId,
+    varint::VarInt,
+};
+use qcongestion::{MSS, Transport};
+use qevent::quic::connectivity::MtuUpdated;
+
+use crate::Components;
+
+/// The number of probes of the same size sent before the size is considered unsupported.
+const MAX_PROBES: usize = 3;
+/// The search completes once the gap between the supported and unsupported sizes is small enough.
+const SEARCH_PRECISION: u16 = 20;
+/// The time to wait before searching for a larger PMTU again after a search completed.
+///
+/// See [Section 5.1.1](https://www.rfc-editor.org/rfc/rfc8899#section-5.1.1) of RFC 8899.
+const PMTU_RAISE_TIMER: Duration = Duration::from_secs(600);
+
+/// The search space of the PMTU.
+///
+/// The largest possible size is probed first, it will be the PMTU on most of the paths that
+/// support more than the base PMTU. If it is not supported, the PMTU is searched with binary
+/// search between the largest supported size and the smallest unsupported size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MtuSearch {
+    max: u16,
+    supported: u16,
+    unsupported: Option<u16>,
+}
+
+impl MtuSearch {
+    /// Create a new search from the base PMTU to the `max` size.
+    pub fn new(max: u16) -> Self {
+        Self {
+            max: max.max(MSS as u16),
+            supported: MSS as u16,
+            unsupported: None,
+        }
+    }
+
+    /// The largest size confirmed to be supported by the path.
+    pub fn pmtu(&self) -> u16 {
+        self.supported
+    }
+
+    /// The size of the next probe, or [`None`] if the search is completed.
+    pub fn next_probe(&self) -> Option<u16> {
+        match self.unsupported {
+            None => (self.supported < self.max).then_some(self.max),
+            Some(unsupported) => (unsupported - self.supported > SEARCH_PRECISION)
+                .then_some(self.supported + (unsupported - self.supported) / 2),
+        }
+    }
+
+    pub fn on_probe_acked(&mut self, size: u16) {
+        self.supported = self.supported.max(size);
+    }
+
+    pub fn on_probe_lost(&mut self, size: u16) {
+        self.unsupported = Some(self.unsupported.map_or(size, |s| s.min(size)));
+    }
+
+    /// Forget the unsupported size, the path may support larger packets now.
+    pub fn raise(&mut self) {
+        self.unsupported = None;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ProbeState {
+    Idle,
+    Pending(u16),
+    Sent { pn: u64, size: u16 },
+    Acked,
+}
+
+/// The PMTU probe to be sent on a path.
+///
+/// The probe is an 1-RTT packet that contains only a PING frame and PADDING frames, it is
+/// written by [`Path::discover_mtu`], and loaded by the [`Burst`] as an individual datagram.
+///
+/// [`Path::discover_mtu`]: super::Path::discover_mtu
+/// [`Burst`]: super::burst::Burst
+pub struct MtuProber {
+    state: Mutex<(ProbeState, Option<Waker>)>,
+    tx_waker: ArcSendWaker,
+}
+
+impl MtuProber {
+    pub fn new(tx_waker: ArcSendWaker) -> Self {
+        Self {
+            state: Mutex::new((ProbeState::Idle, None)),
+            tx_waker,
+        }
+    }
+
+    fn probe(&self, size: u16) {
+        self.state.lock().unwrap().0 = ProbeState::Pending(size);
+        self.tx_waker.wake_by(Signals::PING);
+    }
+
+    /// The size of the probe waiting to be sent.
+    pub fn pending(&self) -> Option<u16> {
+        match self.state.lock().unwrap().0 {
+            ProbeState::Pending(size) => Some(size),
+            _ => None,
+        }
+    }
+
+    pub fn on_probe_sent(&self, pn: u64, size: u16) {
+        let mut state = self.state.lock().unwrap();
+        if state.0 == ProbeState::Pending(size) {
+            state.0 = ProbeState::Sent { pn, size };
+        }
+    }
+
+    pub fn on_ack_rcvd(&self, ack_frame: &AckFrame) {
+        let mut state = self.state.lock().unwrap();
+        if let ProbeState::Sent { pn, .. } = state.0 {
+            if ack_frame.iter().any(|range| range.contains(&pn)) {
+                state.0 = ProbeState::Acked;
+                if let Some(waker) = state.1.take() {
+                    waker.wake();
+                }
+            }
+        }
+    }
+
+    fn is_sent(&self) -> bool {
+        matches!(self.state.lock().unwrap().0, ProbeState::Sent { .. })
+    }
+
+    fn poll_acked(&self, cx: &mut Context<'_>) -> Poll<()> {
+        let mut state = self.state.lock().unwrap();
+        if state.0 == ProbeState::Acked {
+            state.0 = ProbeState::Idle;
+            return Poll::Ready(());
+        }
+        state.1 = Some(cx.waker().clone());
+        Poll::Pending
+    }
+
+    fn stop(&self) {
+        self.state.lock().unwrap().0 = ProbeState::Idle;
+    }
+}
+
+impl super::Path {
+    /// Send the probe of `size` bytes up to [`MAX_PROBES`] times, return whether it is acknowledged.
+    ///
+    /// The probe is considered lost if it is not acknowledged within a PTO, only the probes
+    /// that have been sent are counted.
+    async fn probe_mtu(&self, size: u16) -> bool {
+        let mut probes = 0;
+        while probes < MAX_PROBES {
+            let pto = self.cc().get_pto(Epoch::Data);
+            self.mtu_prober.probe(size);
+            let acked = core::future::poll_fn(|cx| self.mtu_prober.poll_acked(cx));
+            match tokio::time::timeout(pto, acked).await {
+                Ok(()) => return true,
+                Err(_) if self.mtu_prober.is_sent() => probes += 1,
+                // blocked by the congestion controller, wait for another PTO
+                Err(_) => continue,
+            }
+        }
+        self.mtu_prober.stop();
+        false
+    }
+
+    fn update_mtu(&self, mtu: u16, done: bool) {
+        let old = self.pmtu.swap(mtu, Ordering::AcqRel);
+        if old != mtu || done {
+            qevent::event!(MtuUpdated {
+                old: old as u32,
+                new: mtu as u32,
+                done,
+            });
+        }
+    }
+
+    /// Datagram Packetization Layer PMTU Discovery, see [RFC 8899](https://www.rfc-editor.org/rfc/rfc8899.html).
+    ///
+    /// The discovery starts after the handshake is confirmed, the PMTU is searched up to the
+    /// smaller one of the interface's max segment size and the peer's max_udp_payload_size.
+    /// The search starts over when a black hole is detected, and the path is probed for a
+    /// larger PMTU periodically after the search completed.
+    ///
+    /// The returned future never completes.
+    pub fn discover_mtu(self: &Arc<Self>, components: &Components) -> impl Future<Output = ()> {
+        let parameters = components.parameters.clone();
+        let handshake = components.handshake.status();
+        let this = self.clone();
+        async move {
+            let Ok(max_udp_payload_size) = parameters
+                .get_remote_as::<VarInt>(ParameterId::MaxUdpPayloadSize)
+                .await
+            else {
+                return core::future::pending().await;
+            };
+            let max = (max_udp_payload_size.into_inner() as usize)
+                .min(this.interface.max_segment_size())
+                .min(u16::MAX as usize) as u16;
+            handshake.confirmed().await;
+
+            let mut search = MtuSearch::new(max);
+            loop {
+                let search_and_wait = async {
+                    while let Some(size) = search.next_probe() {
+                        if this.probe_mtu(size).await {
+                            search.on_probe_acked(size);
+                            this.update_mtu(search.pmtu(), false);
+                        } else {
+                            search.on_probe_lost(size);
+                        }
+                    }
+                    this.update_mtu(search.pmtu(), true);
+                    tokio::time::sleep(PMTU_RAISE_TIMER).await;
+                };
+                let black_hole_detected = tokio::select! {
+                    _ = search_and_wait => false,
+                    _ = this.status.black_hole_detected() => true,
+                };
+                if black_hole_detected {
+                    this.mtu_prober.stop();
+                    search = MtuSearch::new(max);
+                } else {
+                    search.raise();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn probe_max_first() {
+        let mut search = MtuSearch::new(1500);
+        assert_eq!(search.pmtu(), 1200);
+        assert_eq!(search.next_probe(), Some(1500));
+        search.on_probe_acked(1500);
+        assert_eq!(search.pmtu(), 1500);
+        assert_eq!(search.next_probe(), None);
+
+        search.raise();
+        assert_eq!(search.next_probe(), None);
+    }
+
+    #[test]
+    fn binary_search() {
+        let mut search = MtuSearch::new(1500);
+        let path_mtu = 1472;
+        while let Some(size) = search.next_probe() {
+            if size <= path_mtu {
+                search.on_probe_acked(size);
+            } else {
+                search.on_probe_lost(size);
+            }
+        }
+        assert!(search.pmtu() <= path_mtu);
+        assert!(path_mtu - search.pmtu() <= SEARCH_PRECISION);
+
+        // probe the max size again after raising
+        search.raise();
+        assert_eq!(search.next_probe(), Some(1500));
+    }
+
+    #[test]
+    fn nothing_to_search() {
+        let search = MtuSearch::new(1200);
+        assert_eq!(search.next_probe(), None);
+        let search = MtuSearch::new(1000);
+        assert_eq!(search.pmtu(), 1200);
+        assert_eq!(search.next_probe(), None);
+    }
+}
@@ -1,11 +1,22 @@
-use std::{future::Future, io, sync::Arc, time::Duration};
+use std::{
+    future::Future,
+    io,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
 
 use dashmap::DashMap;
 use derive_more::Deref;
 use qbase::{
     Epoch,
     error::{ErrorKind, QuicError},
-    net::{route::Pathway, tx::ArcSendWakers},
+    net::{
+        route::Pathway,
+        tx::{ArcSendWakers, Signals},
+    },
 };
 use qcongestion::Transport;
 use qevent::telemetry::Instrument;
@@ -28,6 +39,8 @@ pub struct PathContext {
 impl Drop for PathContext {
     fn drop(&mut self) {
         self.task.abort();
+        // wake up the tasks waiting for the validation of the removed path
+        self.path.conclude_validation(false);
     }
 }
 
@@ -37,9 +50,20 @@ impl PathContext {
     }
 }
 
+/// The path carrying the new data, and the largest packet number of the non-probing packets
+/// received, see [`ArcPathContexts::on_non_probing_packet_rcvd`].
+#[derive(Default)]
+struct ActivePath {
+    pathway: Option<Pathway>,
+    largest_non_probing_pn: Option<u64>,
+}
+
 #[derive(Clone)]
 pub struct ArcPathContexts {
     paths: Arc<DashMap<Pathway, PathContext>>,
+    active: Arc<Mutex<ActivePath>>,
+    // With multipath, all the validated paths carry the new data at the same time
+    multipath: Arc<AtomicBool>,
     tx_wakers: ArcSendWakers,
     broker: ArcEventBroker,
 }
@@ -48,11 +72,84 @@ impl ArcPathContexts {
     pub fn new(tx_wakers: ArcSendWakers, broker: ArcEventBroker) -> Self {
         Self {
             paths: Default::default(),
+            active: Default::default(),
+            multipath: Default::default(),
             tx_wakers,
             broker,
         }
     }
 
+    /// Called when the multipath extension is negotiated, then all the paths are active, which
+    /// of them carry the new data is decided by the path scheduler.
+    pub fn enable_multipath(&self) {
+        self.multipath.store(true, Ordering::Release);
+        self.tx_wakers.wake_all_by(Signals::TRANSPORT);
+    }
+
+    /// Whether the path can carry the new data.
+    ///
+    /// Until a path is activated, all the validated paths carry the new data, and so do they
+    /// if multipath is enabled.
+    pub fn is_active(&self, pathway: &Pathway) -> bool {
+        if self.multipath.load(Ordering::Acquire) {
+            return true;
+        }
+        let active = self.active.lock().unwrap();
+        active.pathway.map_or(true, |active| active == *pathway)
+    }
+
+    /// Switch the data to the path, the other paths only send the probing packets until they
+    /// are removed.
+    pub fn activate(&self, pathway: Pathway) {
+        let mut active = self.active.lock().unwrap();
+        if active.pathway != Some(pathway) {
+            tracing::info!(%pathway, "path activated");
+            self.switch_active(&mut active, pathway);
+        }
+    }
+
+    fn switch_active(&self, active: &mut ActivePath, pathway: Pathway) {
+        // The packets in flight on the previous active path are declared lost, their frames are
+        // retransmitted on the new active path
+        if let Some(previous) = active.pathway.replace(pathway) {
+            if let Some(previous) = self.get(&previous) {
+                previous.cc().abandon_in_flight();
+            }
+        }
+        self.tx_wakers.wake_all_by(Signals::TRANSPORT);
+    }
+
+    /// Called when a non-probing packet is received on the path, returns whether the path is
+    /// activated by the packet.
+    ///
+    /// rfc9000 9.3
+    /// An endpoint only changes the address to which it sends packets in response to the
+    /// highest-numbered non-probing packet.
+    ///
+    /// The paths are never switched if multipath is enabled, the packet numbers of different
+    /// paths are not comparable either.
+    pub fn on_non_probing_packet_rcvd(&self, pathway: Pathway, pn: u64) -> bool {
+        if self.multipath.load(Ordering::Acquire) {
+            return false;
+        }
+        let mut active = self.active.lock().unwrap();
+        if active
+            .largest_non_probing_pn
+            .is_some_and(|largest| pn < largest)
+        {
+            return false;
+        }
+        active.largest_non_probing_pn = Some(pn);
+        if active.pathway.is_some_and(|active| active != pathway) {
+            tracing::info!(%pathway, "path activated by the peer");
+            self.switch_active(&mut active, pathway);
+            return true;
+        }
+        // The first path receiving the non-probing packet is activated implicitly
+        active.pathway = Some(pathway);
+        false
+    }
+
     pub fn get_or_try_create_with<T>(
         &self,
         pathway: Pathway,
@@ -85,8 +182,29 @@ impl ArcPathContexts {
         self.paths.get(pathway).map(|p| p.path.clone())
     }
 
+    /// Get the path with the path identifier of the multipath extension.
+    pub fn get_by_id(&self, path_id: u32) -> Option<Arc<Path>> {
+        self.paths
+            .iter()
+            .find(|p| p.path_id() == path_id)
+            .map(|p| p.path.clone())
+    }
+
     pub fn remove(&self, pathway: &Pathway, reason: &str) {
         if let Some((_, path)) = self.paths.remove(pathway) {
+            self.tx_wakers.remove(pathway);
+            // The frames in flight on the removed path are retransmitted on the other paths
+            path.cc().abandon_in_flight();
+            {
+                let mut active = self.active.lock().unwrap();
+                // rfc9000 9.3.2
+                // the endpoint falls back to the other paths if the active path is removed,
+                // for example, fails to be validated
+                if active.pathway == Some(*pathway) {
+                    active.pathway = None;
+                    self.tx_wakers.wake_all_by(Signals::TRANSPORT);
+                }
+            }
             self.broker.emit(Event::PathInactivated(
                 path.interface.bind_addr(),
                 path.pathway,
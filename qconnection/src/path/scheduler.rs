@@ -0,0 +1,266 @@
+use std::{
+    sync::atomic::{AtomicU32, Ordering},
+    time::Duration,
+};
+
+use qbase::net::route::Pathway;
+
+/// A snapshot of a validated path, for the [`SchedulePaths`] to decide which paths carry the
+/// new data when multipath is enabled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PathSnapshot {
+    pub path_id: u32,
+    pub pathway: Pathway,
+    pub smoothed_rtt: Duration,
+    /// Whether the congestion window of the path is used up.
+    pub is_congested: bool,
+    /// Whether the path is marked as a backup path, by either endpoint with a
+    /// PATH_STATUS_BACKUP frame.
+    pub is_backup: bool,
+}
+
+/// The scheduler deciding which paths carry the stream data and the datagrams when multipath
+/// is enabled, it is asked before each burst of packets of a path.
+///
+/// The paths always carry the acknowledgements, the control frames and the validation of
+/// themselves, no matter what the scheduler decides.
+pub trait SchedulePaths: Send + Sync {
+    /// Whether the `path` carries the new data in its next burst, `paths` are all the
+    /// validated paths of the connection, including the `path` itself.
+    fn may_carry_data(&self, path: &PathSnapshot, paths: &[PathSnapshot]) -> bool;
+
+    /// Called after a burst of the `path`, which was allowed to carry the new data, is sent.
+    fn on_data_sent(&self, path: &PathSnapshot) {
+        _ = path;
+    }
+
+    /// Whether the stream data carried by a path is duplicated onto another path.
+    ///
+    /// The paths not carrying the new data send the duplicated data instead, which trades the
+    /// bandwidth for the latency and the resilience to the loss of a path.
+    fn is_redundant(&self) -> bool {
+        false
+    }
+}
+
+/// The factory to create a [`SchedulePaths`] for each connection.
+///
+/// The built-in schedulers are available through [`PathScheduling`], and any
+/// `Fn() -> impl SchedulePaths` can be used as the factory as well.
+pub trait ProductPathScheduler: Send + Sync {
+    fn init(&self) -> Box<dyn SchedulePaths>;
+}
+
+impl<F, S> ProductPathScheduler for F
+where
+    F: Fn() -> S + Send + Sync,
+    S: SchedulePaths + 'static,
+{
+    #[inline]
+    fn init(&self) -> Box<dyn SchedulePaths> {
+        Box::new((self)())
+    }
+}
+
+/// The built-in path schedulers.
+///
+/// All of them prefer the available paths to the backup ones, the backup paths carry the
+/// data only if there is no available path, see
+/// [Path Status Management](https://datatracker.ietf.org/doc/html/draft-ietf-quic-multipath#name-path-status-management).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PathScheduling {
+    /// The path with the lowest smoothed RTT carries the data, until its congestion window is
+    /// used up, then the path with the next lowest RTT does, which aggregates the bandwidth of
+    /// the paths.
+    #[default]
+    MinRtt,
+    /// The paths carry the data in turns, skipping the congested ones.
+    RoundRobin,
+    /// The path with the lowest smoothed RTT carries the new data, and the stream data is
+    /// duplicated onto another path.
+    Redundant,
+    /// Only one path carries the data at a time, the one with the lowest path ID, the other
+    /// paths stand by until it is abandoned.
+    BackupOnly,
+}
+
+impl ProductPathScheduler for PathScheduling {
+    fn init(&self) -> Box<dyn SchedulePaths> {
+        match self {
+            PathScheduling::MinRtt => Box::new(MinRtt { redundant: false }),
+            PathScheduling::RoundRobin => Box::new(RoundRobin::default()),
+            PathScheduling::Redundant => Box::new(MinRtt { redundant: true }),
+            PathScheduling::BackupOnly => Box::new(BackupOnly),
+        }
+    }
+}
+
+/// What a burst of packets of a path carries besides the acknowledgements and the control
+/// frames, decided by the [`SchedulePaths`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Carriage {
+    /// The new data, and the data to be retransmitted.
+    #[default]
+    Data,
+    /// As [`Carriage::Data`], and the stream data sent is duplicated onto another path.
+    DuplicatedData,
+    /// The stream data duplicated from the other paths, and the data to be retransmitted.
+    Duplicates,
+    /// Neither the stream data nor the datagrams.
+    Nothing,
+}
+
+impl Carriage {
+    /// Whether the burst carries the new data, limited by the flow control.
+    pub fn carries_new_data(&self) -> bool {
+        matches!(self, Carriage::Data | Carriage::DuplicatedData)
+    }
+}
+
+/// The paths eligible to carry the data, the available ones if any, otherwise the backups.
+fn eligible(paths: &[PathSnapshot]) -> impl Iterator<Item = &PathSnapshot> + Clone {
+    let has_available = paths.iter().any(|path| !path.is_backup);
+    paths
+        .iter()
+        .filter(move |path| path.is_backup != has_available)
+}
+
+struct MinRtt {
+    redundant: bool,
+}
+
+impl SchedulePaths for MinRtt {
+    fn may_carry_data(&self, path: &PathSnapshot, paths: &[PathSnapshot]) -> bool {
+        let eligible = eligible(paths);
+        let fastest = eligible
+            .clone()
+            .filter(|path| !path.is_congested)
+            .min_by_key(|path| (path.smoothed_rtt, path.path_id))
+            .or_else(|| eligible.min_by_key(|path| (path.smoothed_rtt, path.path_id)));
+        fastest.is_some_and(|fastest| fastest.path_id == path.path_id)
+    }
+
+    fn is_redundant(&self) -> bool {
+        self.redundant
+    }
+}
+
+#[derive(Default)]
+struct RoundRobin {
+    // The path ID of the path carried the data last time
+    last: AtomicU32,
+}
+
+impl SchedulePaths for RoundRobin {
+    fn may_carry_data(&self, path: &PathSnapshot, paths: &[PathSnapshot]) -> bool {
+        let last = self.last.load(Ordering::Acquire);
+        let mut candidates = eligible(paths)
+            .filter(|path| !path.is_congested)
+            .map(|path| path.path_id)
+            .collect::<Vec<_>>();
+        if candidates.is_empty() {
+            return eligible(paths).any(|eligible| eligible.path_id == path.path_id);
+        }
+        candidates.sort_unstable();
+        let next = candidates
+            .iter()
+            .find(|path_id| **path_id > last)
+            .unwrap_or(&candidates[0]);
+        *next == path.path_id
+    }
+
+    fn on_data_sent(&self, path: &PathSnapshot) {
+        self.last.store(path.path_id, Ordering::Release);
+    }
+}
+
+struct BackupOnly;
+
+impl SchedulePaths for BackupOnly {
+    fn may_carry_data(&self, path: &PathSnapshot, paths: &[PathSnapshot]) -> bool {
+        eligible(paths)
+            .map(|path| path.path_id)
+            .min()
+            .is_some_and(|path_id| path_id == path.path_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use qbase::net::route::Pathway;
+
+    use super::*;
+
+    fn snapshot(path_id: u32, rtt_ms: u64, is_congested: bool, is_backup: bool) -> PathSnapshot {
+        let pathway = Pathway::new(
+            format!("127.0.0.1:{}", 10000 + path_id).parse().unwrap(),
+            "127.0.0.1:443".parse().unwrap(),
+        );
+        PathSnapshot {
+            path_id,
+            pathway,
+            smoothed_rtt: Duration::from_millis(rtt_ms),
+            is_congested,
+            is_backup,
+        }
+    }
+
+    fn carriers(scheduler: &dyn SchedulePaths, paths: &[PathSnapshot]) -> Vec<u32> {
+        paths
+            .iter()
+            .filter(|path| scheduler.may_carry_data(path, paths))
+            .map(|path| path.path_id)
+            .collect()
+    }
+
+    #[test]
+    fn min_rtt() {
+        let scheduler = PathScheduling::MinRtt.init();
+        let paths = [snapshot(0, 50, false, false), snapshot(1, 20, false, false)];
+        assert_eq!(carriers(scheduler.as_ref(), &paths), [1]);
+        // spill over to the slower path once the faster one is congested
+        let paths = [snapshot(0, 50, false, false), snapshot(1, 20, true, false)];
+        assert_eq!(carriers(scheduler.as_ref(), &paths), [0]);
+        // the backup path is not used while an available path exists
+        let paths = [snapshot(0, 50, true, false), snapshot(1, 20, false, true)];
+        assert_eq!(carriers(scheduler.as_ref(), &paths), [0]);
+        assert!(!scheduler.is_redundant());
+        assert!(PathScheduling::Redundant.init().is_redundant());
+    }
+
+    #[test]
+    fn round_robin() {
+        let scheduler = PathScheduling::RoundRobin.init();
+        let paths = [
+            snapshot(0, 50, false, false),
+            snapshot(1, 20, false, false),
+            snapshot(2, 30, false, false),
+        ];
+        let mut turns = vec![];
+        for _ in 0..4 {
+            let carrier = carriers(scheduler.as_ref(), &paths);
+            assert_eq!(carrier.len(), 1);
+            scheduler.on_data_sent(&paths[carrier[0] as usize]);
+            turns.push(carrier[0]);
+        }
+        assert_eq!(turns, [1, 2, 0, 1]);
+
+        let paths = [
+            snapshot(0, 50, false, false),
+            snapshot(1, 20, false, false),
+            snapshot(2, 30, true, false),
+        ];
+        assert_eq!(carriers(scheduler.as_ref(), &paths), [0]);
+    }
+
+    #[test]
+    fn backup_only() {
+        let scheduler = PathScheduling::BackupOnly.init();
+        let paths = [snapshot(0, 50, true, false), snapshot(1, 20, false, false)];
+        assert_eq!(carriers(scheduler.as_ref(), &paths), [0]);
+        let paths = [snapshot(0, 50, false, true), snapshot(1, 20, false, false)];
+        assert_eq!(carriers(scheduler.as_ref(), &paths), [1]);
+        let paths = [snapshot(1, 20, false, true), snapshot(2, 20, false, true)];
+        assert_eq!(carriers(scheduler.as_ref(), &paths), [1]);
+    }
+}
@@ -2,18 +2,24 @@ pub mod data;
 pub mod handshake;
 pub mod initial;
 
-use std::{fmt::Debug, sync::Arc};
+use std::{
+    fmt::Debug,
+    sync::{
+        Arc,
+        atomic::{AtomicU32, Ordering},
+    },
+};
 
 use bytes::Bytes;
 use qbase::{
     error::Error,
     frame::{
-        AckFrame, CryptoFrame, GetFrameType, ReceiveFrame, ReliableFrame, StreamCtlFrame,
-        StreamFrame,
+        AckFrame, CryptoFrame, GetFrameType, NewConnectionIdFrame, ReceiveFrame, ReliableFrame,
+        StreamCtlFrame, StreamFrame,
     },
 };
 use qevent::{quic::transport::PacketsAcked, telemetry::Instrument};
-use qinterface::queue::RcvdPacketQueue;
+use qinterface::{queue::RcvdPacketQueue, router::QuicProto};
 use qrecovery::{
     crypto::{CryptoStream, CryptoStreamOutgoing},
     journal::{ArcSentJournal, Journal},
@@ -22,11 +28,32 @@ use tokio::sync::mpsc::UnboundedReceiver;
 use tracing::Instrument as _;
 
 use crate::{
-    Components, DataStreams, FlowController, GuaranteedFrame,
+    ArcRemoteCids, Components, DataStreams, FlowController, GuaranteedFrame,
     events::{ArcEventBroker, EmitEvent, Event},
     termination::Terminator,
 };
 
+/// The QUIC version of the connection, shared by the spaces to build the long headers.
+///
+/// The client may switch to another version after receiving a Version Negotiation packet,
+/// see [`initial::spawn_deliver_and_parse_vn`].
+#[derive(Debug, Clone)]
+pub struct ArcVersion(Arc<AtomicU32>);
+
+impl ArcVersion {
+    pub fn new(version: u32) -> Self {
+        Self(Arc::new(AtomicU32::new(version)))
+    }
+
+    pub fn get(&self) -> u32 {
+        self.0.load(Ordering::Acquire)
+    }
+
+    pub fn set(&self, version: u32) {
+        self.0.store(version, Ordering::Release)
+    }
+}
+
 #[derive(Clone)]
 pub struct Spaces {
     initial: Arc<initial::InitialSpace>,
@@ -85,6 +112,8 @@ impl Spaces {
         }
 
         rcvd_pkt_q.zero_rtt().close();
+        rcvd_pkt_q.retry().close();
+        rcvd_pkt_q.vn().close();
 
         match self.handshake.close() {
             None => rcvd_pkt_q.handshake().close(),
@@ -228,6 +257,37 @@ impl ReceiveFrame<StreamCtlFrame> for FlowControlledDataStreams {
     }
 }
 
+/// When receiving a [`NewConnectionIdFrame`], the stateless reset token in it
+/// must be routed to this connection, so that the stateless reset can be detected.
+#[derive(Clone)]
+struct ResetRoutedRemoteCids {
+    remote_cids: ArcRemoteCids,
+    proto: Arc<QuicProto>,
+    rcvd_pkt_q: Arc<RcvdPacketQueue>,
+}
+
+impl ResetRoutedRemoteCids {
+    fn new(components: &Components) -> Self {
+        Self {
+            remote_cids: components.cid_registry.remote.clone(),
+            proto: components.proto.clone(),
+            rcvd_pkt_q: components.rcvd_pkt_q.clone(),
+        }
+    }
+}
+
+impl ReceiveFrame<NewConnectionIdFrame> for ResetRoutedRemoteCids {
+    type Output = ();
+
+    fn recv_frame(&self, frame: &NewConnectionIdFrame) -> Result<Self::Output, Error> {
+        if let Some(reset_token) = self.remote_cids.recv_frame(frame)? {
+            self.proto
+                .add_reset_entry(reset_token, self.rcvd_pkt_q.clone());
+        }
+        Ok(())
+    }
+}
+
 struct AckInitialSpace {
     sent_journal: ArcSentJournal<CryptoFrame>,
     crypto_stream_outgoing: CryptoStreamOutgoing,
@@ -360,6 +420,26 @@ pub fn spawn_deliver_and_parse(components: &Components) {
         components,
         components.event_broker.clone(),
     );
+    match components.handshake.role() {
+        qbase::sid::Role::Client => {
+            initial::spawn_deliver_and_parse_retry(
+                received_packets_queue.retry().clone(),
+                components.spaces.initial.clone(),
+                components,
+            );
+            initial::spawn_deliver_and_parse_vn(
+                received_packets_queue.vn().clone(),
+                components.spaces.initial.clone(),
+                components,
+                components.event_broker.clone(),
+            );
+        }
+        // Only the server sends Retry packets and Version Negotiation packets.
+        qbase::sid::Role::Server => {
+            received_packets_queue.retry().close();
+            received_packets_queue.vn().close();
+        }
+    }
     handshake::spawn_deliver_and_parse(
         received_packets_queue.handshake().clone(),
         components.spaces.handshake.clone(),
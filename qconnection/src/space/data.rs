@@ -1,13 +1,18 @@
-use std::{sync::Arc, time::Duration};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 use bytes::BufMut;
 use qbase::{
     Epoch,
     cid::ConnectionId,
     error::{Error, QuicError},
+    flow::StreamsRecvFlow,
     frame::{
-        ConnectionCloseFrame, Frame, FrameReader, PathChallengeFrame, PathResponseFrame,
-        ReceiveFrame, SendFrame,
+        AckFrame, ConnectionCloseFrame, ContainSpec, Frame, FrameFeture, FrameReader, PathAckFrame,
+        PathChallengeFrame, PathResponseFrame, ReceiveFrame, SendFrame, Spec, StreamFrame,
     },
     net::{
         address::BindAddr,
@@ -17,23 +22,28 @@ use qbase::{
     packet::{
         self, FinalPacketLayout, MarshalFrame, PacketContains, PacketWriter,
         header::{
-            GetType, OneRttHeader,
+            GetDcid, GetType, OneRttHeader,
             long::{ZeroRttHeader, io::LongHeaderBuilder},
         },
-        keys::{ArcKeys, ArcOneRttKeys, ArcOneRttPacketKeys, HeaderProtectionKeys},
+        keys::{
+            ArcKeys, ArcOneRttKeys, ArcOneRttPacketKeys, HeaderProtectionKeys, KeyUpdatePolicy,
+            KeyUpdateTrigger,
+        },
         number::PacketNumber,
         signal::SpinBit,
         r#type::Type,
     },
-    param::StoreParameter,
+    param::{ParameterId, StoreParameter, StoreParameterExt},
     sid::{ControlStreamsConcurrency, Role},
     util::BoundQueue,
+    varint::VarInt,
 };
-use qcongestion::{Feedback, Transport};
+use qcongestion::{AckFrequencyRequester, ArcAckFrequency, Feedback, Transport};
 use qevent::{
     quic::{
-        PacketHeader, PacketType, QuicFramesCollector,
+        KeyType, PacketHeader, PacketType, QuicFramesCollector,
         recovery::{PacketLost, PacketLostTrigger},
+        security::{KeyDiscarded, KeyDiscardedTrigger, KeyUpdated, KeyUpdatedTrigger},
         transport::PacketReceived,
     },
     telemetry::Instrument,
@@ -45,11 +55,13 @@ use qunreliable::DatagramFlow;
 use tokio::sync::mpsc;
 use tracing::Instrument as _;
 
+use super::ArcVersion;
 use crate::{
     ArcReliableFrameDeque, Components, DataJournal, DataStreams, GuaranteedFrame,
     events::{ArcEventBroker, EmitEvent, Event},
-    path::{Path, SendBuffer},
-    space::{AckDataSpace, FlowControlledDataStreams, pipe},
+    multipath::AbandonPaths,
+    path::{Path, SendBuffer, scheduler::Carriage},
+    space::{AckDataSpace, FlowControlledDataStreams, ResetRoutedRemoteCids, pipe},
     termination::Terminator,
     tx::{PacketBuffer, PaddablePacket, Transaction},
 };
@@ -63,6 +75,8 @@ pub type PlainOneRttPacket = PlainPacket<OneRttHeader>;
 pub type ReceivedOneRttFrom = (BindAddr, CipherOneRttPacket, Pathway, Link);
 
 pub struct DataSpace {
+    role: Role,
+    version: ArcVersion,
     zero_rtt_keys: ArcKeys,
     one_rtt_keys: ArcOneRttKeys,
     crypto_stream: CryptoStream,
@@ -70,28 +84,53 @@ pub struct DataSpace {
     #[cfg(feature = "unreliable")]
     datagrams: DatagramFlow,
     journal: DataJournal,
+    // The journals of the paths other than the path 0, when multipath is enabled
+    journals: Mutex<HashMap<u32, DataJournal>>,
+    max_ack_delay: Duration,
+    // The stream frames sent on a path, to be duplicated onto the other paths
+    reinjections: Mutex<VecDeque<(u32, StreamFrame)>>,
     reliable_frames: ArcReliableFrameDeque,
+    ack_frequency: ArcAckFrequency,
+    ack_frequency_requester: AckFrequencyRequester<ArcReliableFrameDeque>,
 }
 
+/// The stream frames waiting to be duplicated are dropped beyond this limit, the duplication
+/// is a best effort.
+const MAX_REINJECTIONS: usize = 1024;
+
 impl DataSpace {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         role: Role,
         reliable_frames: ArcReliableFrameDeque,
         local_params: &impl StoreParameter,
         streams_ctrl: Box<dyn ControlStreamsConcurrency>,
+        recv_flow: StreamsRecvFlow,
+        version: ArcVersion,
         tx_wakers: ArcSendWakers,
         max_ack_delay: Duration,
+        key_update: KeyUpdatePolicy,
     ) -> Self {
         Self {
+            role,
+            version,
             zero_rtt_keys: ArcKeys::new_pending(),
-            one_rtt_keys: ArcOneRttKeys::new_pending(),
+            one_rtt_keys: ArcOneRttKeys::new_pending(key_update),
             journal: DataJournal::with_capacity(16, Some(max_ack_delay)),
+            journals: Mutex::default(),
+            max_ack_delay,
+            reinjections: Mutex::default(),
             crypto_stream: CryptoStream::new(4096, 4096, tx_wakers.clone()),
             reliable_frames: reliable_frames.clone(),
+            ack_frequency: ArcAckFrequency::new(
+                local_params.get_as::<Duration>(ParameterId::MinAckDelay),
+            ),
+            ack_frequency_requester: AckFrequencyRequester::new(reliable_frames.clone()),
             streams: DataStreams::new(
                 role,
                 local_params,
                 streams_ctrl,
+                recv_flow,
                 reliable_frames,
                 tx_wakers.clone(),
             ),
@@ -117,13 +156,45 @@ impl DataSpace {
         }
     }
 
+    /// The journal of the packets sent and received on the path, each path has its own packet
+    /// number space when multipath is enabled.
+    pub fn journal(&self, path_id: u32) -> DataJournal {
+        if path_id == 0 {
+            return self.journal.clone();
+        }
+        let mut journals = self.journals.lock().unwrap();
+        journals
+            .entry(path_id)
+            .or_insert_with(|| DataJournal::with_capacity(16, Some(self.max_ack_delay)))
+            .clone()
+    }
+
+    /// Release the packet number space of the abandoned path.
+    pub fn forget_path(&self, path_id: u32) {
+        self.journals.lock().unwrap().remove(&path_id);
+        let mut reinjections = self.reinjections.lock().unwrap();
+        reinjections.retain(|(from, _)| *from != path_id);
+    }
+
+    /// The [`Feedback`] of the losses of the packets sent on the path.
+    pub fn path_feedback(self: &Arc<Self>, path_id: u32) -> Arc<dyn Feedback> {
+        match path_id {
+            0 => self.clone(),
+            path_id => Arc::new(PathFeedback {
+                space: self.clone(),
+                path_id,
+            }),
+        }
+    }
+
     pub async fn decrypt_1rtt_packet(
         &self,
         packet: CipherOneRttPacket,
+        path_id: u32,
     ) -> Option<Result<PlainOneRttPacket, QuicError>> {
         match self.one_rtt_keys.get_remote_keys().await {
-            Some((hpk, pk)) => packet.decrypt_short_packet(hpk.as_ref(), &pk, |pn| {
-                self.journal.of_rcvd_packets().decode_pn(pn)
+            Some((hpk, pk)) => packet.decrypt_short_packet(hpk.as_ref(), &pk, path_id, |pn| {
+                self.journal(path_id).of_rcvd_packets().decode_pn(pn)
             }),
             None => {
                 packet.drop_on_key_unavailable();
@@ -138,7 +209,8 @@ impl DataSpace {
         path_challenge_frames: &SendBuffer<PathChallengeFrame>,
         buf: &mut [u8],
     ) -> Result<(PaddablePacket, usize), Signals> {
-        if self.one_rtt_keys.get_local_keys().is_some() {
+        // Only the client sends 0-RTT packets, before the 1-RTT keys are available.
+        if self.role == Role::Server || self.one_rtt_keys.get_local_keys().is_some() {
             return Err(Signals::empty()); // not error, just skip 0rtt
         }
 
@@ -146,7 +218,9 @@ impl DataSpace {
         let (retran_timeout, expire_timeout) = tx.retransmit_and_expire_time(Epoch::Data);
         let sent_journal = self.journal.of_sent_packets();
         let mut packet = PacketBuffer::new_long(
-            LongHeaderBuilder::with_cid(tx.dcid(), tx.scid()).zero_rtt(),
+            LongHeaderBuilder::with_cid(tx.dcid(), tx.scid())
+                .version(self.version.get())
+                .zero_rtt(),
             buf,
             keys,
             &sent_journal,
@@ -154,14 +228,11 @@ impl DataSpace {
 
         let mut signals = Signals::empty();
 
+        // rfc9000 12.4
+        // ACK frames and CRYPTO frames are not permitted in 0-RTT packets.
         _ = path_challenge_frames
             .try_load_frames_into(&mut packet)
             .map_err(|s| signals |= s);
-        _ = self
-            .crypto_stream
-            .outgoing()
-            .try_load_data_into(&mut packet)
-            .map_err(|s| signals |= s);
         // try to load reliable frames into this 0RTT packet to send
         _ = self
             .reliable_frames
@@ -197,8 +268,15 @@ impl DataSpace {
         buf: &mut [u8],
     ) -> Result<(PaddablePacket, Option<u64>, usize), Signals> {
         let (hpk, pk) = self.one_rtt_keys.get_local_keys().ok_or(Signals::KEYS)?;
-        let (key_phase, pk) = pk.lock_guard().get_local();
-        let sent_journal = self.journal.of_sent_packets();
+        let (key_phase, pk) = {
+            let mut keys = pk.lock_guard();
+            if keys.try_update() {
+                self.log_key_updated(keys.generation(), KeyUpdatedTrigger::LocalUpdate);
+            }
+            keys.try_get_local(tx.path_id()).ok_or(Signals::KEYS)?
+        };
+        let journal = self.journal(tx.path_id());
+        let sent_journal = journal.of_sent_packets();
         // (1) may_loss被调用时cc已经被锁定，may_loss会尝试锁定sent_journal
         // (2) PacketMemory会持有sent_journal的guard，而need_ack会尝试锁定cc
         // 在PacketMemory存在时尝试锁定cc，可能会和 (1) 冲突:
@@ -217,21 +295,38 @@ impl DataSpace {
 
         let mut signals = Signals::empty();
 
+        // The packets of the path are acknowledged on the path itself, in the PATH_ACK frames
+        // for the paths other than the path 0
+        let path_id = tx.path_id();
+        // The frame type of the PATH_ACK frame takes 3 more bytes than the ACK frame's
+        let path_ack_overhead = match path_id {
+            0 => 0,
+            path_id => 3 + VarInt::from_u32(path_id).encoding_size(),
+        };
         let ack = need_ack
             .or_else(|| {
-                let rcvd_journal = self.journal.of_rcvd_packets();
+                let rcvd_journal = journal.of_rcvd_packets();
                 rcvd_journal.trigger_ack_frame()
             })
             .ok_or(Signals::TRANSPORT)
             .and_then(|(largest, rcvd_time)| {
-                let rcvd_journal = self.journal.of_rcvd_packets();
+                let rcvd_journal = journal.of_rcvd_packets();
+                let capacity = packet.remaining_mut();
                 let ack_frame = rcvd_journal.gen_ack_frame_util(
                     packet.pn(),
                     largest,
                     rcvd_time,
-                    packet.remaining_mut(),
+                    capacity
+                        .checked_sub(path_ack_overhead)
+                        .ok_or(Signals::CONGESTION)?,
                 )?;
-                packet.dump_ack_frame(ack_frame);
+                match path_id {
+                    0 => packet.dump_ack_frame(ack_frame),
+                    path_id => packet.dump_path_ack_frame(PathAckFrame::new(
+                        VarInt::from_u32(path_id),
+                        ack_frame,
+                    )),
+                }
                 Ok(largest)
             })
             .map_err(|s| signals |= s)
@@ -253,18 +348,36 @@ impl DataSpace {
             .reliable_frames
             .try_load_frames_into(&mut packet)
             .map_err(|s| signals |= s);
-        // try to load stream frames into this 1RTT packet to send
-        let fresh_data = self
-            .streams
-            .try_load_data_into(&mut packet, tx.flow_limit())
-            .map_err(|s| signals |= s)
-            .unwrap_or_default();
+        // try to load stream frames into this 1RTT packet to send, as the path scheduler decides
+        let fresh_data = match tx.carriage() {
+            Carriage::Nothing => {
+                // wait for the path to be scheduled to carry the data
+                signals |= Signals::TRANSPORT;
+                0
+            }
+            carriage => {
+                if carriage == Carriage::Duplicates {
+                    self.reinject_duplicates(path_id);
+                } else if carriage == Carriage::DuplicatedData {
+                    packet.duplicate_stream_frames();
+                }
+                let fresh_data = self
+                    .streams
+                    .try_load_data_into(&mut packet, tx.flow_limit())
+                    .map_err(|s| signals |= s)
+                    .unwrap_or_default();
+                self.record_duplicates(path_id, packet.take_duplicated());
+                fresh_data
+            }
+        };
 
         #[cfg(feature = "unreliable")]
-        let _ = self
-            .datagrams
-            .try_load_data_into(&mut packet)
-            .map_err(|s| signals |= s);
+        if tx.carriage().carries_new_data() {
+            let _ = self
+                .datagrams
+                .try_load_data_into(&mut packet)
+                .map_err(|s| signals |= s);
+        }
 
         Ok((
             packet
@@ -284,9 +397,12 @@ impl DataSpace {
         buf: &mut [u8],
     ) -> Result<PaddablePacket, Signals> {
         let (hpk, pk) = self.one_rtt_keys.get_local_keys().ok_or(Signals::KEYS)?;
-        let (key_phase, pk) = pk.lock_guard().get_local();
+        let (key_phase, pk) = (pk.lock_guard())
+            .try_get_local(tx.path_id())
+            .ok_or(Signals::KEYS)?;
         let (retran_timeout, expire_timeout) = tx.retransmit_and_expire_time(Epoch::Data);
-        let sent_journal = self.journal.of_sent_packets();
+        let journal = self.journal(tx.path_id());
+        let sent_journal = journal.of_sent_packets();
         let mut packet = PacketBuffer::new_short(
             OneRttHeader::new(spin, tx.dcid()),
             buf,
@@ -317,9 +433,12 @@ impl DataSpace {
         buf: &mut [u8],
     ) -> Result<PaddablePacket, Signals> {
         let (hpk, pk) = self.one_rtt_keys.get_local_keys().ok_or(Signals::KEYS)?;
-        let (key_phase, pk) = pk.lock_guard().get_local();
+        let (key_phase, pk) = (pk.lock_guard())
+            .try_get_local(tx.path_id())
+            .ok_or(Signals::KEYS)?;
         let (retran_timeout, expire_timeout) = tx.retransmit_and_expire_time(Epoch::Data);
-        let sent_journal = self.journal.of_sent_packets();
+        let journal = self.journal(tx.path_id());
+        let sent_journal = journal.of_sent_packets();
         let mut packet = PacketBuffer::new_short(
             OneRttHeader::new(spin, tx.dcid()),
             buf,
@@ -329,17 +448,115 @@ impl DataSpace {
             &sent_journal,
         )?;
 
-        packet.dump_ping_frame();
+        // The ack-eliciting packets are sent on PTO. The peer may be delaying the
+        // acknowledgements as requested by the ACK_FREQUENCY frames, IMMEDIATE_ACK asks it to
+        // acknowledge the probe without delay, so the losses are detected in time.
+        match self.ack_frequency_requester.is_peer_supported() {
+            true => packet.dump_immediate_ack_frame(),
+            false => packet.dump_ping_frame(),
+        }
 
         packet
             .prepare_with_time(retran_timeout, expire_timeout)
             .map_err(|_| unreachable!("packet is not empty"))
     }
 
+    /// Mark the stream data sent on the other paths as lost, to be sent again on the path
+    /// carrying the duplicates.
+    fn reinject_duplicates(&self, path_id: u32) {
+        let mut reinjections = self.reinjections.lock().unwrap();
+        reinjections.retain(|(from, frame)| {
+            if *from == path_id {
+                return true;
+            }
+            self.streams.may_loss_data(frame);
+            false
+        });
+    }
+
+    fn record_duplicates(&self, path_id: u32, frames: Vec<StreamFrame>) {
+        if frames.is_empty() {
+            return;
+        }
+        let mut reinjections = self.reinjections.lock().unwrap();
+        reinjections.extend(frames.into_iter().map(|frame| (path_id, frame)));
+        let overflow = reinjections.len().saturating_sub(MAX_REINJECTIONS);
+        reinjections.drain(..overflow);
+    }
+
+    fn log_key_updated(&self, generation: u64, trigger: KeyUpdatedTrigger) {
+        for key_type in [KeyType::Client1RttSecret, KeyType::Server1RttSecret] {
+            qevent::event!(KeyUpdated {
+                key_type,
+                key_phase: generation,
+                trigger,
+            });
+        }
+    }
+
+    /// Phase out the old 1-RTT remote keys after the peer has started to use the new keys.
+    ///
+    /// See [Section 6.5](https://www.rfc-editor.org/rfc/rfc9001#section-6.5) of RFC 9001,
+    /// the old keys are retained for three times the PTO to decrypt the delayed packets.
+    fn phase_out_old_keys(&self, pto: Duration) {
+        let Some((_, pk)) = self.one_rtt_keys.get_local_keys() else {
+            return;
+        };
+        let Some((generation, trigger)) = pk.lock_guard().take_new_phase() else {
+            return;
+        };
+        if trigger == KeyUpdateTrigger::Remote {
+            self.log_key_updated(generation, KeyUpdatedTrigger::RemoteUpdate);
+        }
+        let key_type = match self.role {
+            Role::Client => KeyType::Server1RttSecret,
+            Role::Server => KeyType::Client1RttSecret,
+        };
+        let trigger = match trigger {
+            KeyUpdateTrigger::Local => KeyDiscardedTrigger::LocalUpdate,
+            KeyUpdateTrigger::Remote => KeyDiscardedTrigger::RemoteUpdate,
+        };
+        tokio::spawn(
+            async move {
+                tokio::time::sleep(pto * 3).await;
+                if pk.lock_guard().phase_out(generation) {
+                    qevent::event!(KeyDiscarded {
+                        key_type,
+                        key_phase: generation - 1,
+                        trigger,
+                    });
+                }
+            }
+            .instrument_in_current()
+            .in_current_span(),
+        );
+    }
+
+    /// Whether the local 1-RTT key can protect no more packets, see
+    /// [`OneRttPacketKeys::is_exhausted`].
+    ///
+    /// [`OneRttPacketKeys::is_exhausted`]: qbase::packet::keys::OneRttPacketKeys::is_exhausted
+    pub fn is_one_rtt_key_exhausted(&self) -> bool {
+        self.one_rtt_keys
+            .get_local_keys()
+            .is_some_and(|(_, pk)| pk.lock_guard().is_exhausted())
+    }
+
+    /// The number of the 1-RTT key updates so far, initiated by either endpoint.
+    pub fn one_rtt_key_updates(&self) -> u64 {
+        self.one_rtt_keys
+            .get_local_keys()
+            .map_or(0, |(_, pk)| pk.lock_guard().generation())
+    }
+
     pub fn is_one_rtt_ready(&self) -> bool {
         self.one_rtt_keys.get_local_keys().is_some()
     }
 
+    pub fn zero_rtt_keys(&self) -> ArcKeys {
+        self.zero_rtt_keys.clone()
+    }
+
     pub fn one_rtt_keys(&self) -> ArcOneRttKeys {
         self.one_rtt_keys.clone()
     }
@@ -362,6 +579,14 @@ impl DataSpace {
     pub fn datagrams(&self) -> &DatagramFlow {
         &self.datagrams
     }
+
+    pub fn ack_frequency(&self) -> &ArcAckFrequency {
+        &self.ack_frequency
+    }
+
+    pub fn ack_frequency_requester(&self) -> &AckFrequencyRequester<ArcReliableFrameDeque> {
+        &self.ack_frequency_requester
+    }
 }
 
 pub fn spawn_deliver_and_parse(
@@ -379,12 +604,21 @@ pub fn spawn_deliver_and_parse(
     let (retire_cid_frames_entry, rcvd_retire_cid_frames) = mpsc::unbounded_channel();
     let (handshake_done_frames_entry, rcvd_handshake_done_frames) = mpsc::unbounded_channel();
     let (new_token_frames_entry, rcvd_new_token_frames) = mpsc::unbounded_channel();
+    let (ack_frequency_frames_entry, rcvd_ack_frequency_frames) = mpsc::unbounded_channel();
     // 数据级的
     let (crypto_frames_entry, rcvd_crypto_frames) = mpsc::unbounded_channel();
     let (stream_ctrl_frames_entry, rcvd_stream_ctrl_frames) = mpsc::unbounded_channel();
     let (stream_frames_entry, rcvd_stream_frames) = mpsc::unbounded_channel();
     #[cfg(feature = "unreliable")]
     let (datagram_frames_entry, rcvd_datagram_frames) = mpsc::unbounded_channel();
+    // 多路径的
+    let (path_abandon_frames_entry, rcvd_path_abandon_frames) = mpsc::unbounded_channel();
+    let (path_status_frames_entry, rcvd_path_status_frames) = mpsc::unbounded_channel();
+    let (path_new_cid_frames_entry, rcvd_path_new_cid_frames) = mpsc::unbounded_channel();
+    let (path_retire_cid_frames_entry, rcvd_path_retire_cid_frames) = mpsc::unbounded_channel();
+    let (max_path_id_frames_entry, rcvd_max_path_id_frames) = mpsc::unbounded_channel();
+    let (paths_blocked_frames_entry, rcvd_paths_blocked_frames) = mpsc::unbounded_channel();
+    let (path_cids_blocked_frames_entry, rcvd_path_cids_blocked_frames) = mpsc::unbounded_channel();
 
     let flow_controlled_data_streams =
         FlowControlledDataStreams::new(space.streams.clone(), components.flow_ctrl.clone());
@@ -397,7 +631,7 @@ pub fn spawn_deliver_and_parse(
     );
     pipe(
         rcvd_new_cid_frames,
-        components.cid_registry.remote.clone(),
+        ResetRoutedRemoteCids::new(components),
         event_broker.clone(),
     );
     pipe(
@@ -448,16 +682,118 @@ pub fn spawn_deliver_and_parse(
         components.token_registry.clone(),
         event_broker.clone(),
     );
+    pipe(
+        rcvd_ack_frequency_frames,
+        space.ack_frequency.clone(),
+        event_broker.clone(),
+    );
+    pipe(
+        rcvd_path_abandon_frames,
+        AbandonPaths::new(
+            components.multipath.clone(),
+            components.paths.clone(),
+            space.clone(),
+        ),
+        event_broker.clone(),
+    );
+    pipe(
+        rcvd_path_status_frames,
+        components.multipath.clone(),
+        event_broker.clone(),
+    );
+    pipe(
+        rcvd_path_new_cid_frames,
+        components.multipath.clone(),
+        event_broker.clone(),
+    );
+    pipe(
+        rcvd_path_retire_cid_frames,
+        components.multipath.clone(),
+        event_broker.clone(),
+    );
+    pipe(
+        rcvd_max_path_id_frames,
+        components.multipath.clone(),
+        event_broker.clone(),
+    );
+    pipe(
+        rcvd_paths_blocked_frames,
+        components.multipath.clone(),
+        event_broker.clone(),
+    );
+    pipe(
+        rcvd_path_cids_blocked_frames,
+        components.multipath.clone(),
+        event_broker.clone(),
+    );
 
     let dispatch_data_frame = {
         let event_broker = event_broker.clone();
         let rcvd_joural = space.journal.of_rcvd_packets();
+        let one_rtt_keys = space.one_rtt_keys();
+        let handshake = components.handshake.status();
+        let smoothed_rtt = components.flow_ctrl.recver.smoothed_rtt();
+        let paths = components.paths.clone();
+        let multipath = components.multipath.clone();
+        let on_ack_rcvd = {
+            let space = space.clone();
+            move |acked_path: Option<&Path>, path_id: u32, f: &AckFrame| {
+                if let Some(acked_path) = acked_path {
+                    acked_path.cc().on_ack_rcvd(Epoch::Data, f);
+                    space.ack_frequency_requester.on_ack_rcvd(acked_path.cc());
+                    // the receive windows are tuned by the RTT of the paths carrying the data
+                    smoothed_rtt.set(acked_path.cc().smoothed_rtt());
+                    acked_path.mtu_prober().on_ack_rcvd(f);
+                }
+                // rfc9001 6.1
+                // An endpoint MUST NOT initiate a key update prior to having confirmed the
+                // handshake.
+                if handshake.is_handshake_confirmed() {
+                    if let Some((_, pk)) = one_rtt_keys.get_local_keys() {
+                        pk.lock_guard().on_ack_rcvd(path_id, f.largest());
+                    }
+                }
+            }
+        };
+        let space = space.clone();
         move |frame: Frame, pty: packet::Type, path: &Path| match frame {
             Frame::Ack(f) => {
-                path.cc().on_ack_rcvd(Epoch::Data, &f);
+                // With multipath, the ACK frames acknowledge the packets sent on the path 0,
+                // no matter which path carries them
+                let path_0 = multipath.is_enabled().then(|| paths.get_by_id(0));
+                let acked_path = match &path_0 {
+                    Some(path_0) => path_0.as_deref(),
+                    None => Some(path),
+                };
+                on_ack_rcvd(acked_path, 0, &f);
                 rcvd_joural.on_rcvd_ack(&f);
                 _ = ack_frames_entry.send(f)
             }
+            Frame::PathAck(f) => {
+                // The packets sent on the abandoned paths are not cared about anymore
+                let Some(acked_path) = u32::try_from(f.path_id())
+                    .ok()
+                    .and_then(|path_id| paths.get_by_id(path_id))
+                else {
+                    return;
+                };
+                let path_id = acked_path.path_id();
+                on_ack_rcvd(Some(&acked_path), path_id, f.ack());
+                let journal = space.journal(path_id);
+                journal.of_rcvd_packets().on_rcvd_ack(f.ack());
+                let ack_data_space =
+                    AckDataSpace::new(&journal, &space.streams, &space.crypto_stream);
+                if let Err(Error::Quic(error)) = ack_data_space.recv_frame(f.ack()) {
+                    event_broker.emit(Event::Failed(error));
+                }
+            }
+            Frame::PathAbandon(f) => _ = path_abandon_frames_entry.send(f),
+            Frame::PathStatus(f) => _ = path_status_frames_entry.send(f),
+            Frame::PathNewConnectionId(f) => _ = path_new_cid_frames_entry.send(f),
+            Frame::PathRetireConnectionId(f) => _ = path_retire_cid_frames_entry.send(f),
+            Frame::MaxPathId(f) => _ = max_path_id_frames_entry.send(f),
+            Frame::PathsBlocked(f) => _ = paths_blocked_frames_entry.send(f),
+            Frame::PathCidsBlocked(f) => _ = path_cids_blocked_frames_entry.send(f),
             Frame::NewToken(f) => _ = new_token_frames_entry.send(f),
             Frame::MaxData(f) => _ = max_data_frames_entry.send(f),
             Frame::NewConnectionId(f) => _ = new_cid_frames_entry.send(f),
@@ -469,6 +805,8 @@ pub fn spawn_deliver_and_parse(
                 }
             }
             Frame::DataBlocked(f) => _ = data_blocked_frames_entry.send(f),
+            Frame::AckFrequency(f) => _ = ack_frequency_frames_entry.send(f),
+            Frame::ImmediateAck(_) => path.cc().ack_immediately(),
             Frame::Challenge(f) => _ = path.recv_frame(&f),
             Frame::Response(f) => _ = path.recv_frame(&f),
             Frame::StreamCtl(f) => _ = stream_ctrl_frames_entry.send(f),
@@ -492,7 +830,7 @@ pub fn spawn_deliver_and_parse(
                     let _qlog_span = qevent::span!(@current, path=pathway.to_string()).enter();
                     if let Some(packet) = space.decrypt_0rtt_packet(packet).await.transpose()? {
                         let path = match components
-                            .get_or_try_create_path(bind_addr, link, pathway, true)
+                            .get_or_try_create_path(bind_addr, link, pathway, 0, true)
                         {
                             Ok(path) => path,
                             Err(_) => {
@@ -514,6 +852,7 @@ pub fn spawn_deliver_and_parse(
                         space.journal.of_rcvd_packets().register_pn(
                             packet.pn(),
                             packet_contains.ack_eliciting(),
+                            packet.ecn(),
                             path.cc().get_pto(Epoch::Data),
                         );
                         path.on_packet_rcvd(
@@ -542,9 +881,34 @@ pub fn spawn_deliver_and_parse(
             while let Some((bind_addr, packet, pathway, link)) = one_rtt_packets.recv().await {
                 let parse = async {
                     let _qlog_span = qevent::span!(@current, path=pathway.to_string()).enter();
-                    if let Some(packet) = space.decrypt_1rtt_packet(packet).await.transpose()? {
+                    let trailing_reset_token = packet.trailing_reset_token();
+                    // With multipath, the connection ID identifies the path of the packet, and
+                    // each path has its own packet number space
+                    let path_id = components.multipath.path_id_of(packet.dcid());
+                    let packet = space.decrypt_1rtt_packet(packet, path_id).await;
+                    // rfc9000 10.3.1
+                    // An endpoint detects a potential Stateless Reset using the trailing 16 bytes
+                    // of the UDP datagram. ... the endpoint MUST perform this comparison when the
+                    // first packet in an incoming datagram either cannot be associated with a
+                    // connection or cannot be decrypted.
+                    if !matches!(packet, Some(Ok(..)))
+                        && trailing_reset_token
+                            .is_some_and(|token| components.multipath.is_stateless_reset(&*token))
+                    {
+                        event_broker.emit(Event::StatelessReset);
+                        return Ok(());
+                    }
+                    if let Some(packet) = packet.transpose()? {
+                        if components.is_migration_refused(&pathway) {
+                            packet.drop_on_migration_refused();
+                            return Ok(());
+                        }
+                        if components.is_from_unknown_server(&pathway) {
+                            packet.drop_on_unknown_server();
+                            return Ok(());
+                        }
                         let path = match components
-                            .get_or_try_create_path(bind_addr, link, pathway, true)
+                            .get_or_try_create_path(bind_addr, link, pathway, path_id, true)
                         {
                             Ok(path) => path,
                             Err(_) => {
@@ -555,20 +919,24 @@ pub fn spawn_deliver_and_parse(
                         components
                             .handshake
                             .discard_spaces_on_server_handshake_done(&components.paths);
+                        space.phase_out_old_keys(path.cc().get_pto(Epoch::Data));
 
                         let mut frames = QuicFramesCollector::<PacketReceived>::new();
+                        let mut is_probing = true;
                         let packet_contains = FrameReader::new(packet.body(), packet.get_type())
                             .try_fold(PacketContains::default(), |packet_contains, frame| {
                                 let (frame, frame_type) = frame?;
                                 frames.extend(Some(&frame));
+                                is_probing &= frame_type.specs().contain(Spec::ProbeNewPath);
                                 dispatch_data_frame(frame, packet.get_type(), &path);
                                 Result::<_, QuicError>::Ok(packet_contains.include(frame_type))
                             })?;
                         packet.log_received(frames);
 
-                        space.journal.of_rcvd_packets().register_pn(
+                        space.journal(path_id).of_rcvd_packets().register_pn(
                             packet.pn(),
                             packet_contains.ack_eliciting(),
+                            packet.ecn(),
                             path.cc().get_pto(Epoch::Data),
                         );
                         path.on_packet_rcvd(
@@ -577,6 +945,15 @@ pub fn spawn_deliver_and_parse(
                             packet.size(),
                             packet_contains,
                         );
+                        // rfc9000 9.3
+                        // the server follows the client to the new address on receiving a
+                        // non-probing packet from it, the new path is validated before
+                        // carrying the new data
+                        if !is_probing && components.handshake.role() == Role::Server {
+                            components
+                                .paths
+                                .on_non_probing_packet_rcvd(pathway, packet.pn());
+                        }
                     }
                     Result::<(), Error>::Ok(())
                 };
@@ -613,7 +990,32 @@ pub fn spawn_deliver_and_parse(
 
 impl Feedback for DataSpace {
     fn may_loss(&self, trigger: PacketLostTrigger, pns: &mut dyn Iterator<Item = u64>) {
-        let sent_jornal = self.journal.of_sent_packets();
+        self.may_loss_in(&self.journal, trigger, pns);
+    }
+}
+
+/// The [`Feedback`] of the losses of the packets sent on a path other than the path 0, in its
+/// own packet number space.
+struct PathFeedback {
+    space: Arc<DataSpace>,
+    path_id: u32,
+}
+
+impl Feedback for PathFeedback {
+    fn may_loss(&self, trigger: PacketLostTrigger, pns: &mut dyn Iterator<Item = u64>) {
+        let journal = self.space.journal(self.path_id);
+        self.space.may_loss_in(&journal, trigger, pns);
+    }
+}
+
+impl DataSpace {
+    fn may_loss_in(
+        &self,
+        journal: &DataJournal,
+        trigger: PacketLostTrigger,
+        pns: &mut dyn Iterator<Item = u64>,
+    ) {
+        let sent_jornal = journal.of_sent_packets();
         let crypto_outgoing = self.crypto_stream.outgoing();
         let mut sent_packets = sent_jornal.rotate();
         for pn in pns {
@@ -672,7 +1074,7 @@ impl DataSpace {
 impl ClosingDataSpace {
     pub fn recv_packet(&self, packet: CipherOneRttPacket) -> Option<ConnectionCloseFrame> {
         let packet = packet
-            .decrypt_short_packet(self.keys.0.remote.as_ref(), &self.keys.1, |pn| {
+            .decrypt_short_packet(self.keys.0.remote.as_ref(), &self.keys.1, 0, |pn| {
                 self.rcvd_journal.decode_pn(pn)
             })
             .and_then(Result::ok)?;
@@ -697,7 +1099,7 @@ impl ClosingDataSpace {
         buf: &mut [u8],
     ) -> Option<FinalPacketLayout> {
         let (hpk, pk) = &self.keys;
-        let (key_phase, pk) = pk.lock_guard().get_local();
+        let (key_phase, pk) = pk.lock_guard().get_local(0);
         let header = OneRttHeader::new(Default::default(), dcid);
         let pn = self.ccf_packet_pn;
         // 装填ccf时ccf不在乎Limiter
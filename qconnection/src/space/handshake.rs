@@ -37,7 +37,7 @@ use rustls::quic::Keys;
 use tokio::sync::mpsc;
 use tracing::Instrument as _;
 
-use super::AckHandshakeSpace;
+use super::{AckHandshakeSpace, ArcVersion};
 use crate::{
     Components, HandshakeJournal,
     events::{ArcEventBroker, EmitEvent, Event},
@@ -52,14 +52,16 @@ pub type PlainHandshakePacket = PlainPacket<HandshakeHeader>;
 pub type ReceivedFrom = (BindAddr, CipherHanshakePacket, Pathway, Link);
 
 pub struct HandshakeSpace {
+    version: ArcVersion,
     keys: ArcKeys,
     crypto_stream: CryptoStream,
     journal: HandshakeJournal,
 }
 
 impl HandshakeSpace {
-    pub fn new(tx_wakers: ArcSendWakers) -> Self {
+    pub fn new(version: ArcVersion, tx_wakers: ArcSendWakers) -> Self {
         Self {
+            version,
             keys: ArcKeys::new_pending(),
             crypto_stream: CryptoStream::new(4096, 4096, tx_wakers),
             journal: HandshakeJournal::with_capacity(16, None),
@@ -99,7 +101,9 @@ impl HandshakeSpace {
         let keys = self.keys.get_local_keys().ok_or(Signals::KEYS)?;
         let (retran_timeout, expire_timeout) = tx.retransmit_and_expire_time(Epoch::Handshake);
         let sent_journal = self.journal.of_sent_packets();
-        let header = LongHeaderBuilder::with_cid(tx.dcid(), tx.scid()).handshake();
+        let header = LongHeaderBuilder::with_cid(tx.dcid(), tx.scid())
+            .version(self.version.get())
+            .handshake();
         let need_ack = tx.need_ack(Epoch::Handshake);
         let mut packet = PacketBuffer::new_long(header, buf, keys, &sent_journal)?;
 
@@ -147,7 +151,9 @@ impl HandshakeSpace {
         let keys = self.keys.get_local_keys().ok_or(Signals::KEYS)?;
         let (retran_timeout, expire_timeout) = tx.retransmit_and_expire_time(Epoch::Handshake);
         let sent_journal = self.journal.of_sent_packets();
-        let header = LongHeaderBuilder::with_cid(tx.dcid(), tx.scid()).handshake();
+        let header = LongHeaderBuilder::with_cid(tx.dcid(), tx.scid())
+            .version(self.version.get())
+            .handshake();
         let mut packet = PacketBuffer::new_long(header, buf, keys, &sent_journal)?;
 
         packet.dump_ping_frame();
@@ -204,14 +210,15 @@ pub fn spawn_deliver_and_parse(
             let parse = async {
                 let _qlog_span = qevent::span!(@current, path=pathway.to_string()).enter();
                 if let Some(packet) = space.decrypt_packet(packet).await.transpose()? {
-                    let path =
-                        match components.get_or_try_create_path(bind_addr, link, pathway, true) {
-                            Ok(path) => path,
-                            Err(_) => {
-                                packet.drop_on_conenction_closed();
-                                return Ok(());
-                            }
-                        };
+                    let path = match components
+                        .get_or_try_create_path(bind_addr, link, pathway, 0, true)
+                    {
+                        Ok(path) => path,
+                        Err(_) => {
+                            packet.drop_on_conenction_closed();
+                            return Ok(());
+                        }
+                    };
                     // See [RFC 9000 section 8.1](https://www.rfc-editor.org/rfc/rfc9000.html#name-address-validation-during-c)
                     // Once an endpoint has successfully processed a Handshake packet from the peer, it can consider the peer
                     // address to have been validated.
@@ -231,6 +238,7 @@ pub fn spawn_deliver_and_parse(
                     space.journal.of_rcvd_packets().register_pn(
                         packet.pn(),
                         packet_contains != PacketContains::NonAckEliciting,
+                        packet.ecn(),
                         path.cc().get_pto(Epoch::Handshake),
                     );
                     path.on_packet_rcvd(
@@ -240,13 +248,13 @@ pub fn spawn_deliver_and_parse(
                         packet_contains,
                     );
 
-                    // the origin dcid doesnot own a sequences number, so remove its router entry after the connection id
-                    // negotiating done.
+                    // the initial dcid(the origin dcid, or the retry scid) doesnot own a sequences number, so remove
+                    // its router entry after the connection id negotiating done.
                     // https://www.rfc-editor.org/rfc/rfc9000.html#name-negotiating-connection-ids
                     if role == qbase::sid::Role::Server {
-                        let origin_dcid = components.parameters.get_origin_dcid()?;
-                        if origin_dcid != *packet.dcid() {
-                            components.proto.del_router_entry(&origin_dcid.into());
+                        let initial_dcid = components.parameters.get_initial_dcid()?;
+                        if initial_dcid != *packet.dcid() {
+                            components.proto.del_router_entry(&initial_dcid.into());
                         }
                     }
                 }
@@ -296,6 +304,7 @@ impl Feedback for HandshakeSpace {
 
 #[derive(Clone)]
 pub struct ClosingHandshakeSpace {
+    version: u32,
     rcvd_journal: ArcRcvdJournal,
     ccf_packet_pn: (u64, PacketNumber),
     keys: Arc<Keys>,
@@ -309,6 +318,7 @@ impl HandshakeSpace {
         let ccf_packet_pn = new_packet_guard.pn();
         let rcvd_journal = self.journal.of_rcvd_packets();
         Some(ClosingHandshakeSpace {
+            version: self.version.get(),
             rcvd_journal,
             ccf_packet_pn,
             keys,
@@ -346,7 +356,9 @@ impl ClosingHandshakeSpace {
         ccf: &ConnectionCloseFrame,
         buf: &mut [u8],
     ) -> Option<FinalPacketLayout> {
-        let header = LongHeaderBuilder::with_cid(dcid, scid).handshake();
+        let header = LongHeaderBuilder::with_cid(dcid, scid)
+            .version(self.version)
+            .handshake();
         let pn = self.ccf_packet_pn;
         let mut packet_writer = PacketWriter::new_long(&header, buf, pn, self.keys.clone()).ok()?;
 
@@ -15,13 +15,16 @@ use qbase::{
         tx::{ArcSendWakers, Signals},
     },
     packet::{
-        FinalPacketLayout, MarshalFrame, PacketContains, PacketWriter,
+        FinalPacketLayout, MarshalFrame, PacketContains, PacketWriter, RetryPacket,
+        VersionNegotiationHeader,
         header::{
             GetDcid, GetScid, GetType,
             long::{InitialHeader, io::LongHeaderBuilder},
         },
         keys::ArcKeys,
         number::PacketNumber,
+        retry::RetryIntegrity,
+        r#type::long::{SUPPORTED_VERSIONS, rustls_version},
     },
     token::TokenRegistry,
     util::BoundQueue,
@@ -41,9 +44,9 @@ use rustls::quic::Keys;
 use tokio::sync::mpsc;
 use tracing::Instrument as _;
 
-use super::{AckInitialSpace, pipe};
+use super::{AckInitialSpace, ArcVersion, pipe};
 use crate::{
-    Components, InitialJournal,
+    Components, InitialJournal, SpecificComponents,
     events::{ArcEventBroker, EmitEvent, Event},
     path::Path,
     termination::Terminator,
@@ -55,6 +58,8 @@ pub type PlainInitialPacket = PlainPacket<InitialHeader>;
 pub type ReceivedFrom = (BindAddr, CipherInitialPacket, Pathway, Link);
 
 pub struct InitialSpace {
+    suite: rustls::quic::Suite,
+    version: ArcVersion,
     keys: ArcKeys,
     crypto_stream: CryptoStream,
     token: Mutex<Vec<u8>>,
@@ -62,12 +67,23 @@ pub struct InitialSpace {
 }
 
 impl InitialSpace {
-    // Initial keys应该是预先知道的，或者传入dcid，可以构造出来
-    pub fn new(keys: rustls::quic::Keys, token: Vec<u8>, tx_wakers: ArcSendWakers) -> Self {
+    /// Create the Initial space, whose keys are derived from the destination connection ID of
+    /// the Initial packets sent by the client, and the QUIC version in use.
+    pub fn new(
+        suite: rustls::quic::Suite,
+        initial_dcid: &ConnectionId,
+        side: rustls::Side,
+        token: Vec<u8>,
+        version: ArcVersion,
+        tx_wakers: ArcSendWakers,
+    ) -> Self {
         let journal = InitialJournal::with_capacity(16, None);
         let crypto_stream = CryptoStream::new(4096, 4096, tx_wakers);
+        let keys = suite.keys(initial_dcid, side, rustls_version(version.get()));
 
         Self {
+            suite,
+            version,
             token: Mutex::new(token),
             keys: ArcKeys::with_keys(keys),
             journal,
@@ -100,6 +116,60 @@ impl InitialSpace {
         }
     }
 
+    /// Restart the handshake after receiving a valid Retry packet from the server.
+    ///
+    /// The Initial keys are re-derived from the `retry_scid`, the `token` will be carried in
+    /// all subsequent Initial packets, and the CRYPTO data sent in the Initial packets before
+    /// will be resent, because the server discarded them.
+    pub fn on_retry(&self, retry_scid: &ConnectionId, token: Vec<u8>) {
+        // rfc9001 5.2
+        // The secrets used for constructing subsequent Initial packets change when a server sends a Retry
+        // packet to use the connection ID value selected by the server.
+        self.keys.replace_keys(self.suite.keys(
+            retry_scid,
+            rustls::Side::Client,
+            rustls_version(self.version.get()),
+        ));
+        *self.token.lock().unwrap() = token;
+
+        // rfc9000 17.2.5.3
+        // The client MUST NOT reset the packet number for any packet number space after processing a Retry
+        // packet.
+        let sent_journal = self.journal.of_sent_packets();
+        let (next_pn, _) = sent_journal.new_packet().pn();
+        let outgoing = self.crypto_stream.outgoing();
+        let mut sent_packets = sent_journal.rotate();
+        for pn in 0..next_pn {
+            for frame in sent_packets.may_loss_packet(pn) {
+                outgoing.may_loss_data(&frame);
+            }
+        }
+    }
+
+    /// Start a new connection attempt with the `version` after receiving a Version Negotiation
+    /// packet from the server.
+    ///
+    /// The Initial keys are re-derived for the `version`, and the CRYPTO data sent in the Initial
+    /// packets before is discarded, a new ClientHello will be sent by the new TLS session.
+    pub fn on_version_negotiation(&self, origin_dcid: &ConnectionId, version: u32) {
+        self.version.set(version);
+        self.keys.replace_keys(self.suite.keys(
+            origin_dcid,
+            rustls::Side::Client,
+            rustls_version(version),
+        ));
+
+        // The server discarded the Initial packets sent before, they will never be acknowledged
+        // or retransmitted.
+        let sent_journal = self.journal.of_sent_packets();
+        let (next_pn, _) = sent_journal.new_packet().pn();
+        let mut sent_packets = sent_journal.rotate();
+        for pn in 0..next_pn {
+            sent_packets.may_loss_packet(pn).for_each(drop);
+        }
+        self.crypto_stream.outgoing().reset();
+    }
+
     pub fn try_assemble_initial_packet(
         &self,
         tx: &mut Transaction<'_>,
@@ -111,6 +181,7 @@ impl InitialSpace {
         let (retran_timeout, expire_timeout) = tx.retransmit_and_expire_time(Epoch::Initial);
         let mut packet = PacketBuffer::new_long(
             LongHeaderBuilder::with_cid(tx.dcid(), tx.scid())
+                .version(self.version.get())
                 .initial(self.token.lock().unwrap().clone()),
             buf,
             keys,
@@ -163,6 +234,7 @@ impl InitialSpace {
         let sent_journal = self.journal.of_sent_packets();
         let mut packet = PacketBuffer::new_long(
             LongHeaderBuilder::with_cid(tx.dcid(), tx.scid())
+                .version(self.version.get())
                 .initial(self.token.lock().unwrap().clone()),
             buf,
             keys,
@@ -216,7 +288,16 @@ pub fn spawn_deliver_and_parse(
     let validate = {
         let tls_session = components.tls_session.clone();
         let token_registry = components.token_registry.clone();
-        move |initial_token: &[u8], path: &Path| {
+        let retried_link = match &components.specific {
+            SpecificComponents::Server(server) => server.retried_link,
+            SpecificComponents::Client => None,
+        };
+        move |initial_token: &[u8], link: Link, path: &Path| {
+            // The retry token has been validated before the connection is created.
+            if retried_link == Some(link) {
+                path.grant_anti_amplification();
+                return;
+            }
             if let TokenRegistry::Server(provider) = token_registry.deref() {
                 if let Some(server_name) = tls_session.server_name() {
                     if provider.verify_token(server_name, initial_token) {
@@ -249,14 +330,15 @@ pub fn spawn_deliver_and_parse(
                 }
 
                 if let Some(packet) = space.decrypt_packet(packet).await.transpose()? {
-                    let path =
-                        match components.get_or_try_create_path(bind_addr, link, pathway, true) {
-                            Ok(path) => path,
-                            Err(_) => {
-                                packet.drop_on_conenction_closed();
-                                return Ok(());
-                            }
-                        };
+                    let path = match components
+                        .get_or_try_create_path(bind_addr, link, pathway, 0, true)
+                    {
+                        Ok(path) => path,
+                        Err(_) => {
+                            packet.drop_on_conenction_closed();
+                            return Ok(());
+                        }
+                    };
 
                     let mut frames = QuicFramesCollector::<PacketReceived>::new();
                     let packet_contains = FrameReader::new(packet.body(), packet.get_type())
@@ -271,6 +353,7 @@ pub fn spawn_deliver_and_parse(
                     space.journal.of_rcvd_packets().register_pn(
                         packet.pn(),
                         packet_contains != PacketContains::NonAckEliciting,
+                        packet.ecn(),
                         path.cc().get_pto(Epoch::Initial),
                     );
                     path.on_packet_rcvd(
@@ -291,16 +374,16 @@ pub fn spawn_deliver_and_parse(
                     // This token is delivered to the client during connection establishment with a Retry packet (see Section 8.1.2)
                     // or in a previous connection using the NEW_TOKEN frame (see Section 8.1.3).
                     if !packet.token().is_empty() {
-                        validate(packet.token(), &path);
+                        validate(packet.token(), link, &path);
                     }
 
-                    // the origin dcid doesnot own a sequences number, so remove its router entry after the connection id
-                    // negotiating done.
+                    // the initial dcid(the origin dcid, or the retry scid) doesnot own a sequences number, so remove
+                    // its router entry after the connection id negotiating done.
                     // https://www.rfc-editor.org/rfc/rfc9000.html#name-negotiating-connection-ids
                     if role == qbase::sid::Role::Server {
-                        let origin_dcid = components.parameters.get_origin_dcid()?;
-                        if origin_dcid != *packet.dcid() {
-                            components.proto.del_router_entry(&origin_dcid.into());
+                        let initial_dcid = components.parameters.get_initial_dcid()?;
+                        if initial_dcid != *packet.dcid() {
+                            components.proto.del_router_entry(&initial_dcid.into());
                         }
                     }
                 }
@@ -325,6 +408,174 @@ pub fn spawn_deliver_and_parse(
     );
 }
 
+/// Receive the Retry packet from the server, which is only meaningful for the client.
+///
+/// See [section 17.2.5.2](https://www.rfc-editor.org/rfc/rfc9000.html#name-handling-a-retry-packet)
+/// of [RFC9000](https://www.rfc-editor.org/rfc/rfc9000.html).
+pub fn spawn_deliver_and_parse_retry(
+    packets: BoundQueue<(BindAddr, RetryPacket, Pathway, Link)>,
+    space: Arc<InitialSpace>,
+    components: &Components,
+) {
+    let components = components.clone();
+    let conn_state = components.conn_state.clone();
+    let deliver_and_parse = async move {
+        let origin_dcid = components.parameters.get_origin_dcid()?;
+        while let Some((_, packet, ..)) = packets.recv().await {
+            // rfc9000 17.2.5.2
+            // A client MUST accept and process at most one Retry packet for each connection attempt. After the
+            // client has received and processed an Initial or Retry packet from the server, it MUST discard any
+            // subsequent Retry packets that it receives.
+            if components.parameters.initial_scid_from_peer()?.is_some() {
+                break;
+            }
+            // rfc9000 17.2.5.2
+            // Clients MUST discard Retry packets that have a Retry Integrity Tag that cannot be validated; see
+            // Section 5.8 of [QUIC-TLS]. ... A client MUST discard a Retry packet with a zero-length Retry Token
+            // field.
+            // The Retry packet is protected with the version in use, which may be changed by a
+            // Version Negotiation packet.
+            let integrity = RetryIntegrity::new(&space.suite, rustls_version(space.version.get()));
+            if packet.token().is_empty() || !integrity.verify(&origin_dcid, &packet.bytes) {
+                tracing::warn!("discard an invalid Retry packet");
+                continue;
+            }
+
+            // rfc9000 7.3
+            // A client that processes a Retry packet MUST use the Source Connection ID field from that packet
+            // as the Destination Connection ID field for subsequent packets it sends. ... and the client MUST
+            // NOT use any other value than the retry_source_connection_id.
+            let retry_scid = *packet.scid();
+            tracing::debug!(
+                retry_scid = format!("{retry_scid:x}"),
+                "received Retry packet"
+            );
+            space.on_retry(&retry_scid, packet.token().clone());
+            components
+                .cid_registry
+                .remote
+                .revise_initial_dcid(retry_scid);
+            components
+                .parameters
+                .retry_scid_from_server_need_equal(retry_scid);
+            // The Initial packets sent before are no longer in flight, they will never be acknowledged.
+            for path in components.paths.iter() {
+                path.cc().discard_epoch(Epoch::Initial);
+            }
+            break;
+        }
+        packets.close();
+        Result::<(), Error>::Ok(())
+    };
+
+    tokio::spawn(
+        async move {
+            tokio::select! {
+                _ = deliver_and_parse => {},
+                _ = conn_state.terminated() => {}
+            };
+        }
+        .instrument_in_current()
+        .in_current_span(),
+    );
+}
+
+/// Receive the Version Negotiation packet from the server, which is only meaningful for the client.
+///
+/// If the server supports another version of the client's available versions, the client starts a
+/// new connection attempt with the most preferred one, otherwise the connection attempt is abandoned.
+///
+/// See [section 6.2](https://www.rfc-editor.org/rfc/rfc9000.html#name-handling-version-negotiati)
+/// of [RFC9000](https://www.rfc-editor.org/rfc/rfc9000.html), and
+/// [section 2.1](https://www.rfc-editor.org/rfc/rfc9368.html#name-version-negotiation-between)
+/// of [RFC9368](https://www.rfc-editor.org/rfc/rfc9368.html).
+pub fn spawn_deliver_and_parse_vn(
+    packets: BoundQueue<(BindAddr, VersionNegotiationHeader, Pathway, Link)>,
+    space: Arc<InitialSpace>,
+    components: &Components,
+    event_broker: ArcEventBroker,
+) {
+    let components = components.clone();
+    let conn_state = components.conn_state.clone();
+    let deliver_and_parse = async move {
+        let origin_dcid = components.parameters.get_origin_dcid()?;
+        let selected_version = space.version.get();
+        let available_versions = match components.parameters.version_information()? {
+            Some(version_information) => version_information.available_versions().to_vec(),
+            None => vec![selected_version],
+        };
+        while let Some((_, packet, pathway, _)) = packets.recv().await {
+            // rfc9000 6.2
+            // A client MUST discard any Version Negotiation packet if it has received and successfully processed
+            // any other packet, including an earlier Version Negotiation packet.
+            if components.parameters.initial_scid_from_peer()?.is_some()
+                || components.parameters.get_initial_dcid()? != origin_dcid
+            {
+                break;
+            }
+            // rfc9000 17.2.1
+            // The server MUST include the value from the Source Connection ID field of the packet it receives in the
+            // Destination Connection ID field. The value for Source Connection ID MUST be copied from the Destination
+            // Connection ID of the received packet.
+            // rfc9000 6.2
+            // A client MUST discard a Version Negotiation packet that lists the QUIC version selected by the client.
+            if *packet.scid() != origin_dcid || packet.versions().contains(&selected_version) {
+                tracing::warn!(%pathway, "discard an invalid Version Negotiation packet");
+                continue;
+            }
+
+            tracing::debug!(
+                %pathway,
+                versions = format!("{:#x?}", packet.versions()),
+                "received Version Negotiation packet"
+            );
+            // rfc9368 2.1
+            // Upon receiving the Version Negotiation packet, the client will search for a version it supports in
+            // the list provided by the server. If it doesn't find one, it aborts the connection attempt. Otherwise,
+            // it selects a mutually supported version and sends a new first flight with that version.
+            let Some(&version) = available_versions.iter().find(|version| {
+                SUPPORTED_VERSIONS.contains(version) && packet.versions().contains(version)
+            }) else {
+                event_broker.emit(Event::VersionMismatch(packet.versions().clone()));
+                break;
+            };
+            tracing::debug!(
+                version = format!("{version:#x}"),
+                "start a new connection attempt"
+            );
+            components.parameters.choose_version(version);
+            space.on_version_negotiation(&origin_dcid, version);
+            // The Initial packets sent before are no longer in flight, they will never be acknowledged.
+            for path in components.paths.iter() {
+                path.cc().discard_epoch(Epoch::Initial);
+            }
+            components
+                .tls_session
+                .restart_client(version, &components.parameters);
+            // The 0-RTT keys are derived again in the new version, from the same session ticket.
+            let zero_rtt_keys = components.spaces.data().zero_rtt_keys();
+            match components.tls_session.zero_rtt_keys() {
+                Some(keys) => zero_rtt_keys.replace_keys(keys),
+                None => _ = zero_rtt_keys.invalid(),
+            }
+            break;
+        }
+        packets.close();
+        Result::<(), Error>::Ok(())
+    };
+
+    tokio::spawn(
+        async move {
+            tokio::select! {
+                _ = deliver_and_parse => {},
+                _ = conn_state.terminated() => {}
+            };
+        }
+        .instrument_in_current()
+        .in_current_span(),
+    );
+}
+
 impl Feedback for InitialSpace {
     fn may_loss(&self, trigger: PacketLostTrigger, pns: &mut dyn Iterator<Item = u64>) {
         let sent_jornal = self.journal.of_sent_packets();
@@ -350,6 +601,7 @@ impl Feedback for InitialSpace {
 
 #[derive(Clone)]
 pub struct ClosingInitialSpace {
+    version: u32,
     rcvd_journal: ArcRcvdJournal,
     ccf_packet_pn: (u64, PacketNumber),
     keys: Arc<Keys>,
@@ -363,6 +615,7 @@ impl InitialSpace {
         let ccf_packet_pn = new_packet_guard.pn();
         let rcvd_journal = self.journal.of_rcvd_packets();
         Some(ClosingInitialSpace {
+            version: self.version.get(),
             rcvd_journal,
             ccf_packet_pn,
             keys,
@@ -400,7 +653,9 @@ impl ClosingInitialSpace {
         ccf: &ConnectionCloseFrame,
         buf: &mut [u8],
     ) -> Option<FinalPacketLayout> {
-        let header = LongHeaderBuilder::with_cid(dcid, scid).initial(vec![]);
+        let header = LongHeaderBuilder::with_cid(dcid, scid)
+            .version(self.version)
+            .initial(vec![]);
         let pn = self.ccf_packet_pn;
         let mut packet_writer = PacketWriter::new_long(&header, buf, pn, self.keys.clone()).ok()?;
 
@@ -413,6 +668,10 @@ impl ClosingInitialSpace {
         };
 
         packet_writer.dump_frame(ccf);
+        // rfc9000 14.1: the Initial packets carried in the datagrams smaller than 1200 bytes are
+        // discarded by the receiver, the refused client would never know the connection is closed
+        let padding_len = 1200usize.saturating_sub(packet_writer.packet_len());
+        packet_writer.pad(padding_len.min(packet_writer.remaining_mut()));
 
         Some(packet_writer.encrypt_and_protect())
     }
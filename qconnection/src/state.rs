@@ -141,6 +141,11 @@ impl ConnState {
         }
     }
 
+    /// Wait for the handshake to be confirmed, returns false if the connection is closed before.
+    ///
+    /// rfc9001 4.1.2
+    /// The server considers the handshake confirmed once it is complete, the client only after
+    /// receiving the HANDSHAKE_DONE frame, not when its own handshake is complete.
     pub fn handshaked(&self) -> impl Future<Output = bool> + Send {
         let handshaked = self.handshaked.clone();
         async move { handshaked.acquire().await.is_ok() }
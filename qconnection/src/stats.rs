@@ -0,0 +1,49 @@
+use qbase::{
+    Epoch,
+    flow::FlowWindow,
+    net::route::{Link, Pathway},
+    sid::StreamsCount,
+};
+use qcongestion::CongestionStats;
+
+/// A snapshot of a path of the connection, see [`Connection::stats`].
+///
+/// [`Connection::stats`]: crate::Connection::stats
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PathStats {
+    /// The path identifier of the multipath extension, always 0 if multipath is not enabled.
+    pub path_id: u32,
+    pub pathway: Pathway,
+    pub link: Link,
+    /// Whether the path carries the new data of the connection.
+    pub is_active: bool,
+    pub is_validated: bool,
+    /// The path MTU discovered so far.
+    pub pmtu: usize,
+    pub rcvd_packets: u64,
+    /// The bytes of the packets received, in each epoch.
+    pub rcvd_bytes: [u64; Epoch::count()],
+    pub congestion: CongestionStats,
+}
+
+/// A snapshot of the connection, taken by [`Connection::stats`].
+///
+/// [`Connection::stats`]: crate::Connection::stats
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionStats {
+    pub paths: Vec<PathStats>,
+    /// The connection level flow control of the data sent.
+    pub send_flow: FlowWindow,
+    /// The connection level flow control of the data received.
+    pub recv_flow: FlowWindow,
+    /// The bidirectional streams opened by this endpoint, and the limit set by the peer.
+    pub local_bi_streams: StreamsCount,
+    /// The unidirectional streams opened by this endpoint, and the limit set by the peer.
+    pub local_uni_streams: StreamsCount,
+    /// The bidirectional streams opened by the peer, and the limit advertised to it.
+    pub remote_bi_streams: StreamsCount,
+    /// The unidirectional streams opened by the peer, and the limit advertised to it.
+    pub remote_uni_streams: StreamsCount,
+    /// The number of the 1-RTT key updates, initiated by either endpoint.
+    pub key_updates: u64,
+}
@@ -72,7 +72,7 @@ impl Terminator {
             match write(&mut datagram, self.scid, self.dcid, &self.ccf) {
                 Some(written) if written > 0 => {
                     _ = path
-                        .send_packets(&[io::IoSlice::new(&datagram[..written])])
+                        .send_packets(&[io::IoSlice::new(&datagram[..written])], None)
                         .await;
                 }
                 _ => {}
@@ -97,7 +97,7 @@ impl Terminator {
         match write(&mut datagram, self.scid, self.dcid, &self.ccf) {
             Some(written) if written > 0 => {
                 _ = path
-                    .send_packets(&[io::IoSlice::new(&datagram[..written])])
+                    .send_packets(&[io::IoSlice::new(&datagram[..written])], None)
                     .await;
             }
             _ => {}
@@ -14,23 +14,36 @@ pub use peer_name::{ArcClientName, ArcEndpointName, ArcServerName};
 use qbase::{
     Epoch,
     error::{Error, ErrorKind, QuicError},
-    param::{ArcParameters, ParameterId, StoreParameter, StoreParameterExt},
+    packet::r#type::long::rustls_version,
+    param::{
+        ArcParameters, ClientParameters, ParameterId, RememberedParameters, StoreParameter,
+        StoreParameterExt, WriteParameters, be_remembered_parameters,
+    },
     varint::VarInt,
 };
 use qevent::telemetry::Instrument;
 use qrecovery::crypto::CryptoStream;
-use rustls::quic::KeyChange;
+use rustls::quic::{KeyChange, Keys};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tracing::Instrument as _;
 
 use crate::{Components, SpecificComponents, events::Event, prelude::EmitEvent};
 
 type TlsConnection = rustls::quic::Connection;
+type ClientConfig = (
+    rustls::pki_types::ServerName<'static>,
+    Arc<rustls::ClientConfig>,
+);
 
 #[derive(Debug)]
 struct TlsSession {
     tls_conn: TlsConnection,
     read_waker: Option<Waker>,
+    // Kept by the client to start a new TLS session for another connection attempt.
+    client_config: Option<ClientConfig>,
+    // Whether the client resuming a session loaded the server transport parameters remembered
+    // with the session ticket, they are not the ones of this connection.
+    loaded_remembered_params: bool,
 }
 
 impl From<TlsConnection> for TlsSession {
@@ -38,6 +51,8 @@ impl From<TlsConnection> for TlsSession {
         Self {
             tls_conn,
             read_waker: None,
+            client_config: None,
+            loaded_remembered_params: false,
         }
     }
 }
@@ -71,6 +86,22 @@ impl TlsSession {
             TlsConnection::Client(_) => None,
         }
     }
+
+    /// The keys protecting 0-RTT packets, the client uses them to encrypt, and the server uses
+    /// them to decrypt.
+    fn zero_rtt_keys(&self) -> Option<Keys> {
+        Some(Keys {
+            local: self.tls_conn.zero_rtt_keys()?,
+            remote: self.tls_conn.zero_rtt_keys()?,
+        })
+    }
+
+    fn is_early_data_accepted(&self) -> bool {
+        match &self.tls_conn {
+            TlsConnection::Client(client_conn) => client_conn.is_early_data_accepted(),
+            TlsConnection::Server(server_conn) => server_conn.zero_rtt_keys().is_some(),
+        }
+    }
 }
 
 struct ReadAndProcess<'r> {
@@ -126,6 +157,11 @@ impl ReadAndProcess<'_> {
             }
             Ok(())
         };
+        // The client resuming a session gets the server transport parameters remembered with the
+        // session ticket at first, they are replaced by the received ones during the handshake.
+        if tls_session.loaded_remembered_params && tls_session.is_handshaking() {
+            return Ok(());
+        }
         if !self.params.is_remote_params_ready() {
             if let Some(raw) = tls_session.tls_conn.quic_transport_parameters() {
                 return self.params.recv_remote_params(raw, extra_auth);
@@ -218,39 +254,86 @@ impl futures::Future for ReadAndProcess<'_> {
 pub struct ArcTlsSession(Arc<Mutex<Result<TlsSession, Error>>>);
 
 impl ArcTlsSession {
-    /// The QUIC version used by the TLS session.
-    const QUIC_VERSION: rustls::quic::Version = rustls::quic::Version::V1;
+    fn client_connection(
+        (server_name, tls_config): ClientConfig,
+        version: u32,
+        params: Vec<u8>,
+    ) -> TlsConnection {
+        let client_connection = rustls::quic::ClientConnection::new(
+            tls_config,
+            rustls_version(version),
+            server_name,
+            params,
+        );
+        rustls::quic::Connection::Client(client_connection.unwrap())
+    }
 
-    /// Create a new client-side TLS session.
+    /// Create a new client-side TLS session for the QUIC `version`.
+    ///
+    /// If the client resumes a session with early data enabled, the keys of 0-RTT packets are
+    /// available immediately, see [`ArcTlsSession::zero_rtt_keys`].
     pub fn new_client(
         server_name: rustls::pki_types::ServerName<'static>,
         tls_config: Arc<rustls::ClientConfig>,
-        parameters: &ArcParameters,
+        version: u32,
+        client_params: &ClientParameters,
     ) -> Self {
         let mut params = Vec::with_capacity(1024);
-        parameters.load_local_params_into(&mut params);
-
-        let client_connection = rustls::quic::ClientConnection::new(
-            tls_config,
-            Self::QUIC_VERSION,
-            server_name,
-            params,
-        );
-        let connection = rustls::quic::Connection::Client(client_connection.unwrap());
-        Self(Arc::new(Mutex::new(Ok(connection.into()))))
+        params.put_parameters(client_params.as_ref());
+
+        let client_config = (server_name, tls_config);
+        let connection = Self::client_connection(client_config.clone(), version, params);
+        let tls_session = TlsSession {
+            client_config: Some(client_config),
+            loaded_remembered_params: connection.quic_transport_parameters().is_some(),
+            ..connection.into()
+        };
+        Self(Arc::new(Mutex::new(Ok(tls_session))))
     }
 
-    /// Create a new server-side TLS session.
-    pub fn new_server(tls_config: Arc<rustls::ServerConfig>, parameters: &ArcParameters) -> Self {
+    /// Create a new server-side TLS session for the QUIC `version`.
+    ///
+    /// The early data of the client resuming a session is accepted only if `accept_early_data`
+    /// is true, and the `tls_config` allows it.
+    pub fn new_server(
+        tls_config: Arc<rustls::ServerConfig>,
+        version: u32,
+        parameters: &ArcParameters,
+        accept_early_data: bool,
+    ) -> Self {
         let mut params = Vec::with_capacity(1024);
         parameters.load_local_params_into(&mut params);
 
-        let server_connection =
-            rustls::quic::ServerConnection::new(tls_config, Self::QUIC_VERSION, params).unwrap();
+        let mut server_connection =
+            rustls::quic::ServerConnection::new(tls_config, rustls_version(version), params)
+                .unwrap();
+        if !accept_early_data {
+            server_connection.reject_early_data();
+        }
         let connection = rustls::quic::Connection::Server(server_connection);
         Self(Arc::new(Mutex::new(Ok(connection.into()))))
     }
 
+    /// Replace the client-side TLS session with a new one for the QUIC `version`, when the client
+    /// starts a new connection attempt after receiving a Version Negotiation packet.
+    ///
+    /// The task reading the handshake messages will be woken up to send the new ClientHello, so
+    /// the crypto stream must have been reset.
+    pub fn restart_client(&self, version: u32, parameters: &ArcParameters) {
+        let mut guard = self.0.lock().unwrap();
+        if let Ok(tls_session) = guard.deref_mut() {
+            let Some(client_config) = tls_session.client_config.clone() else {
+                return;
+            };
+            let mut params = Vec::with_capacity(1024);
+            parameters.load_local_params_into(&mut params);
+            let connection = Self::client_connection(client_config, version, params);
+            tls_session.loaded_remembered_params = connection.quic_transport_parameters().is_some();
+            tls_session.tls_conn = connection;
+            tls_session.wake_read();
+        }
+    }
+
     /// Abort the TLS session, the handshaking will be stopped if it is not completed.
     pub fn on_conn_error(&self, error: &Error) {
         let mut guard = self.0.lock().unwrap();
@@ -298,6 +381,43 @@ impl ArcTlsSession {
             .map(ToString::to_string)
     }
 
+    /// Returns the keys protecting 0-RTT packets, if available.
+    ///
+    /// For client, they are available when resuming a session with early data enabled, and they
+    /// change after a new TLS session is started by [`ArcTlsSession::restart_client`].
+    ///
+    /// For server, they are available after the ClientHello is processed, if the early data of
+    /// the client is accepted.
+    pub fn zero_rtt_keys(&self) -> Option<Keys> {
+        self.0.lock().unwrap().as_ref().ok()?.zero_rtt_keys()
+    }
+
+    /// For client resuming a session, returns the server transport parameters remembered with
+    /// the session ticket, before the handshake is completed.
+    ///
+    /// For server, returns [`None`].
+    pub fn remembered_parameters(&self) -> Option<RememberedParameters> {
+        let guard = self.0.lock().unwrap();
+        let tls_session = guard.as_ref().ok()?;
+        match &tls_session.tls_conn {
+            TlsConnection::Client(client_conn) if client_conn.is_handshaking() => {
+                be_remembered_parameters(client_conn.quic_transport_parameters()?).ok()
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the early data has been accepted, it is meaningful only after the handshake is
+    /// completed.
+    pub fn is_early_data_accepted(&self) -> Result<bool, Error> {
+        self.0
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(TlsSession::is_early_data_accepted)
+            .map_err(|e| e.clone())
+    }
+
     pub fn handshake_complete(&self) -> Result<bool, Error> {
         self.0
             .lock()
@@ -358,6 +478,7 @@ pub fn keys_upgrade(components: &Components) -> impl futures::Future<Output = ()
 
     let tls_session = components.tls_session.clone();
     let handshake_keys = components.spaces.handshake().keys();
+    let zero_rtt_keys = components.spaces.data().zero_rtt_keys();
     let one_rtt_keys = components.spaces.data().one_rtt_keys();
     let handshake = components.handshake.clone();
     let params = components.parameters.clone();
@@ -372,7 +493,7 @@ pub fn keys_upgrade(components: &Components) -> impl futures::Future<Output = ()
         let mut messages = Vec::with_capacity(1500);
         let mut cur_epoch = Epoch::Initial;
         loop {
-            let (key_upgrade, is_tls_done) = match tls_session
+            let (key_upgrade, is_handshaking) = match tls_session
                 .read_and_process(
                     &mut messages,
                     &params,
@@ -405,18 +526,32 @@ pub fn keys_upgrade(components: &Components) -> impl futures::Future<Output = ()
             if let Some(key_change) = key_upgrade {
                 match key_change {
                     rustls::quic::KeyChange::Handshake { keys } => {
+                        // The server decides whether to accept the early data while processing
+                        // the ClientHello, the 0-RTT packets are dropped if it is rejected.
+                        if let SpecificComponents::Server(..) = specific {
+                            match tls_session.zero_rtt_keys() {
+                                Some(keys) => zero_rtt_keys.set_keys(keys),
+                                None => _ = zero_rtt_keys.invalid(),
+                            }
+                        }
                         handshake_keys.set_keys(keys);
                         handshake.got_handshake_key();
                         cur_epoch = Epoch::Handshake;
                     }
                     rustls::quic::KeyChange::OneRtt { keys, next } => {
+                        // rfc9001 4.9.3
+                        // A client SHOULD discard 0-RTT keys as soon as it installs 1-RTT keys as
+                        // they have no use after that moment.
+                        if let SpecificComponents::Client = specific {
+                            _ = zero_rtt_keys.invalid();
+                        }
                         one_rtt_keys.set_keys(keys, next);
                         cur_epoch = Epoch::Data;
                     }
                 }
             }
 
-            if is_tls_done {
+            if !is_handshaking {
                 // only server is handshake confirmed, client is confirmed after receiving the first HANDSHAKE_DONE frame
                 handshake.discard_spaces_on_server_handshake_done(&paths);
             }
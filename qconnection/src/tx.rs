@@ -6,8 +6,8 @@ use qbase::{
     Epoch,
     cid::{BorrowedCid, ConnectionId},
     frame::{
-        AckFrame, CryptoFrame, DatagramFrame, EncodeFrame, FrameFeture, PathChallengeFrame,
-        PathResponseFrame, PingFrame, ReliableFrame, StreamFrame,
+        AckFrame, CryptoFrame, DatagramFrame, EncodeFrame, FrameFeture, ImmediateAckFrame,
+        PathAckFrame, PathChallengeFrame, PathResponseFrame, PingFrame, ReliableFrame, StreamFrame,
         io::{WriteDataFrame, WriteFrame},
     },
     net::tx::{ArcSendWaker, Signals},
@@ -28,8 +28,9 @@ use qrecovery::journal::{ArcSentJournal, NewPacketGuard};
 use tokio::time::{Duration, Instant};
 
 use crate::{
-    ArcDcidCell, ArcReliableFrameDeque, Credit, GuaranteedFrame,
-    path::{AntiAmplifier, Constraints, SendBuffer},
+    ArcDcidCell, Credit, GuaranteedFrame,
+    multipath::PathCidFrames,
+    path::{AntiAmplifier, Constraints, SendBuffer, scheduler::Carriage},
     space::{Spaces, data::DataSpace},
 };
 
@@ -69,6 +70,8 @@ pub struct PacketBuffer<'b, 's, F> {
     // 不同空间的send guard类型不一样
     clerk: NewPacketGuard<'s, F>,
     logger: PacketLogger,
+    // The stream frames sent, to be duplicated onto another path
+    duplicated: Option<Vec<StreamFrame>>,
 }
 
 impl<'b, 's, F> PacketBuffer<'b, 's, F> {
@@ -102,6 +105,7 @@ impl<'b, 's, F> PacketBuffer<'b, 's, F> {
                 },
                 frames: QuicFramesCollector::new(),
             },
+            duplicated: None,
         })
     }
 
@@ -130,10 +134,20 @@ impl<'b, 's, F> PacketBuffer<'b, 's, F> {
                 },
                 frames: QuicFramesCollector::new(),
             },
+            duplicated: None,
         })
     }
 }
 
+/// rfc9000 10.3
+/// To support stateless reset, endpoints SHOULD ensure that all packets they send are at least
+/// 22 bytes longer than the minimum connection ID length that they request the peer to include
+/// in its packets, adding PADDING frames as necessary.
+///
+/// Connection IDs issued by us are always 8 bytes long, and only short header packets may be
+/// smaller than this.
+const MIN_SHORT_PACKET_SIZE: usize = 22 + 8;
+
 #[derive(Deref)]
 pub struct PaddablePacket {
     #[deref]
@@ -160,8 +174,12 @@ impl PaddablePacket {
 
     pub fn complete(mut self, buffer: &mut [u8]) -> FinalPacketLayout {
         let mut writer = self.layout.writer(buffer);
-        if writer.payload_len() + writer.tag_len() < 20 {
-            let padding_len = 20 - writer.payload_len() - writer.tag_len();
+        let mut padding_len = 20usize.saturating_sub(writer.payload_len() + writer.tag_len());
+        if writer.is_short_header() {
+            padding_len =
+                padding_len.max(MIN_SHORT_PACKET_SIZE.saturating_sub(writer.packet_len()));
+        }
+        if padding_len > 0 {
             writer.pad(padding_len);
             self.logger.record_frame(QuicFrame::Padding {
                 length: Some(padding_len as u32),
@@ -208,6 +226,12 @@ impl<F> PacketBuffer<'_, '_, F> {
         self.clerk.record_trivial();
     }
 
+    pub fn dump_path_ack_frame(&mut self, frame: PathAckFrame) {
+        self.logger.record_frame(&frame);
+        self.writer.dump_frame(frame);
+        self.clerk.record_trivial();
+    }
+
     pub fn dump_ping_frame(&mut self) {
         self.logger.record_frame(QuicFrame::Ping {
             length: Some(1),
@@ -217,6 +241,12 @@ impl<F> PacketBuffer<'_, '_, F> {
         self.clerk.record_trivial();
     }
 
+    pub fn dump_immediate_ack_frame(&mut self) {
+        self.logger.record_frame(QuicFrame::ImmediateAck {});
+        self.writer.dump_frame(ImmediateAckFrame);
+        self.clerk.record_trivial();
+    }
+
     pub fn pad(&mut self, len: usize) {
         if len == 0 {
             return;
@@ -265,6 +295,16 @@ impl<F> PacketBuffer<'_, '_, F> {
     pub fn pn(&self) -> u64 {
         self.pn
     }
+
+    /// Record the stream frames dumped into the packet from now on, see [`Self::take_duplicated`].
+    pub fn duplicate_stream_frames(&mut self) {
+        self.duplicated = Some(Vec::new());
+    }
+
+    /// Take the stream frames recorded, to be sent on another path as well.
+    pub fn take_duplicated(&mut self) -> Vec<StreamFrame> {
+        self.duplicated.take().unwrap_or_default()
+    }
 }
 
 /// 对IH空间有效
@@ -349,6 +389,9 @@ where
             .dump_frame_with_data(frame, data.clone())
             .and_then(|frame| {
                 self.logger.record_frame((&frame, &data));
+                if let Some(duplicated) = self.duplicated.as_mut() {
+                    duplicated.push(frame);
+                }
                 self.clerk.record_frame(GuaranteedFrame::Stream(frame));
                 None
             })
@@ -373,16 +416,21 @@ where
 
 pub struct Transaction<'a> {
     scid: ConnectionId,
-    dcid: BorrowedCid<'a, ArcReliableFrameDeque>,
+    dcid: BorrowedCid<'a, PathCidFrames>,
+    path_id: u32,
+    carriage: Carriage,
     cc: &'a ArcCC,
     flow_limit: Credit<'a>,
     constraints: Constraints,
 }
 
 impl<'a> Transaction<'a> {
+    #[allow(clippy::too_many_arguments)]
     pub fn prepare(
         scid: ConnectionId,
         dcid: &'a ArcDcidCell,
+        path_id: u32,
+        carriage: Carriage,
         cc: &'a ArcCC,
         anti_amplifier: &'a AntiAmplifier,
         flow_ctrl: &'a crate::FlowController,
@@ -392,7 +440,13 @@ impl<'a> Transaction<'a> {
         let Some(credit_limit) = anti_amplifier.balance()? else {
             return Ok(None);
         };
-        let flow_limit = match flow_ctrl.send_limit(send_quota) {
+        // The paths not carrying the new data are not credited by the flow control
+        let flow_quota = if carriage.carries_new_data() {
+            send_quota
+        } else {
+            0
+        };
+        let flow_limit = match flow_ctrl.send_limit(flow_quota) {
             Ok(flow_limit) => flow_limit,
             Err(_error) => return Ok(None),
         };
@@ -404,6 +458,8 @@ impl<'a> Transaction<'a> {
         Ok(Some(Self {
             scid,
             dcid: borriwed_dcid,
+            path_id,
+            carriage,
             cc,
             flow_limit,
             constraints,
@@ -418,6 +474,16 @@ impl<'a> Transaction<'a> {
         *self.dcid
     }
 
+    /// The path identifier of the multipath extension, always 0 if multipath is not enabled.
+    pub fn path_id(&self) -> u32 {
+        self.path_id
+    }
+
+    /// What the packets carry, decided by the path scheduler.
+    pub fn carriage(&self) -> Carriage {
+        self.carriage
+    }
+
     pub fn need_ack(&self, epoch: Epoch) -> Option<(u64, Instant)> {
         self.cc.need_ack(epoch)
     }
@@ -672,6 +738,36 @@ impl Transaction<'_> {
             })
     }
 
+    /// Load a PMTU probe, which contains a PING frame and is padded to fill the whole `buf`.
+    ///
+    /// Return the packet number and the size of the probe.
+    pub fn load_mtu_probe(
+        &mut self,
+        buf: &mut [u8],
+        spin: SpinBit,
+        data_space: &DataSpace,
+    ) -> Result<(u64, usize), Signals> {
+        if self.constraints.available() < buf.len() {
+            return Err(Signals::CONGESTION);
+        }
+        data_space
+            .try_assemble_ping_packet(self, spin, buf)
+            .map(|packet| {
+                let final_layout = packet.fill_and_complete(buf);
+                self.constraints
+                    .commit(final_layout.sent_bytes(), final_layout.in_flight());
+                self.cc.on_pkt_sent(
+                    Epoch::Data,
+                    final_layout.pn(),
+                    final_layout.is_ack_eliciting(),
+                    final_layout.sent_bytes(),
+                    final_layout.in_flight(),
+                    None,
+                );
+                (final_layout.pn(), final_layout.sent_bytes())
+            })
+    }
+
     pub fn load_ping(
         &mut self,
         buf: &mut [u8],
@@ -696,7 +696,7 @@ macro_rules! map {
 mod rollback {
 
     use super::*;
-    use crate::{build, legacy};
+    use crate::legacy;
 
     impl TryFrom<EvnetData> for legacy::EventData {
         type Error = ();
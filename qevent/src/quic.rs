@@ -8,7 +8,7 @@ use derive_more::{From, Into, LowerHex};
 use qbase::{
     frame::{
         AckFrame, AppCloseFrame, ConnectionCloseFrame, CryptoFrame, DatagramFrame, EncodeFrame,
-        Frame, MaxStreamsFrame, NewTokenFrame, PathChallengeFrame, PathResponseFrame,
+        Frame, MaxStreamsFrame, NewTokenFrame, PathAckFrame, PathChallengeFrame, PathResponseFrame,
         ReliableFrame, StreamCtlFrame, StreamFrame, StreamsBlockedFrame,
     },
     net::address::RealAddr,
@@ -190,25 +190,21 @@ pub enum PacketType {
 
 impl From<qbase::packet::Type> for PacketType {
     fn from(r#type: qbase::packet::Type) -> Self {
+        use qbase::packet::r#type::{
+            Type,
+            long::{Type as LongType, Ver1, Ver2},
+        };
         match r#type {
-            qbase::packet::r#type::Type::Long(long) => match long {
-                qbase::packet::r#type::long::Type::VersionNegotiation => {
-                    PacketType::VersionNegotiation
+            Type::Long(long) => match long {
+                LongType::VersionNegotiation => PacketType::VersionNegotiation,
+                LongType::V1(Ver1::INITIAL) | LongType::V2(Ver2::INITIAL) => PacketType::Initial,
+                LongType::V1(Ver1::HANDSHAKE) | LongType::V2(Ver2::HANDSHAKE) => {
+                    PacketType::Handshake
                 }
-                qbase::packet::r#type::long::Type::V1(
-                    qbase::packet::r#type::long::Version::INITIAL,
-                ) => PacketType::Initial,
-                qbase::packet::r#type::long::Type::V1(
-                    qbase::packet::r#type::long::Version::HANDSHAKE,
-                ) => PacketType::Handshake,
-                qbase::packet::r#type::long::Type::V1(
-                    qbase::packet::r#type::long::Version::ZERO_RTT,
-                ) => PacketType::ZeroRTT,
-                qbase::packet::r#type::long::Type::V1(
-                    qbase::packet::r#type::long::Version::RETRY,
-                ) => PacketType::Retry,
+                LongType::V1(Ver1::ZERO_RTT) | LongType::V2(Ver2::ZERO_RTT) => PacketType::ZeroRTT,
+                LongType::V1(Ver1::RETRY) | LongType::V2(Ver2::RETRY) => PacketType::Retry,
             },
-            qbase::packet::r#type::Type::Short(_one_rtt) => PacketType::OneRTT,
+            Type::Short(_one_rtt) => PacketType::OneRTT,
         }
     }
 }
@@ -604,6 +600,61 @@ pub enum QuicFrame {
         trigger_frame_type: Option<ConnectionCloseTriggerFrameType>,
     },
     HandshakeDone {},
+    /// See draft-ietf-quic-ack-frequency.
+    AckFrequency {
+        sequence_number: u64,
+        ack_eliciting_threshold: u64,
+        /// in ms
+        request_max_ack_delay: f32,
+        reordering_threshold: u64,
+    },
+    ImmediateAck {},
+    /// See draft-ietf-quic-multipath.
+    PathAck {
+        path_id: u64,
+        /// in ms
+        ack_delay: Option<f32>,
+        acked_ranges: Vec<[u64; 2]>,
+        ect1: Option<u64>,
+        ect0: Option<u64>,
+        ce: Option<u64>,
+        /// total frame length, including frame header
+        length: Option<u32>,
+    },
+    PathAbandon {
+        path_id: u64,
+        error_code: u64,
+    },
+    PathStatusBackup {
+        path_id: u64,
+        path_status_sequence_number: u64,
+    },
+    PathStatusAvailable {
+        path_id: u64,
+        path_status_sequence_number: u64,
+    },
+    PathNewConnectionId {
+        path_id: u64,
+        sequence_number: u32,
+        retire_prior_to: u32,
+        connection_id_length: Option<u8>,
+        connection_id: ConnectionID,
+        stateless_reset_token: Option<StatelessResetToken>,
+    },
+    PathRetireConnectionId {
+        path_id: u64,
+        sequence_number: u32,
+    },
+    MaxPathId {
+        maximum_path_id: u64,
+    },
+    PathsBlocked {
+        maximum_path_id: u64,
+    },
+    PathCidsBlocked {
+        path_id: u64,
+        next_sequence_number: u64,
+    },
     /// The frame_type_bytes field is the numerical value without variable-
     /// length integer encoding.
     Unknow {
@@ -759,6 +810,31 @@ impl From<&AckFrame> for QuicFrame {
     }
 }
 
+impl From<&PathAckFrame> for QuicFrame {
+    fn from(frame: &PathAckFrame) -> Self {
+        let QuicFrame::Ack {
+            ack_delay,
+            acked_ranges,
+            ect1,
+            ect0,
+            ce,
+            ..
+        } = QuicFrame::from(frame.ack())
+        else {
+            unreachable!("AckFrame is always converted into QuicFrame::Ack")
+        };
+        QuicFrame::PathAck {
+            path_id: frame.path_id(),
+            ack_delay,
+            acked_ranges,
+            ect1,
+            ect0,
+            ce,
+            length: Some(frame.encoding_size() as u32),
+        }
+    }
+}
+
 impl From<&ReliableFrame> for QuicFrame {
     fn from(frame: &ReliableFrame) -> Self {
         match frame {
@@ -782,6 +858,54 @@ impl From<&ReliableFrame> for QuicFrame {
                 }
             }
             ReliableFrame::HandshakeDone(_handshake_done_frame) => QuicFrame::HandshakeDone {},
+            ReliableFrame::AckFrequency(ack_frequency_frame) => QuicFrame::AckFrequency {
+                sequence_number: ack_frequency_frame.sequence(),
+                ack_eliciting_threshold: ack_frequency_frame.ack_eliciting_threshold(),
+                request_max_ack_delay: ack_frequency_frame.request_max_ack_delay().as_secs_f32()
+                    * 1000.0,
+                reordering_threshold: ack_frequency_frame.reordering_threshold(),
+            },
+            ReliableFrame::PathAbandon(path_abandon_frame) => QuicFrame::PathAbandon {
+                path_id: path_abandon_frame.path_id(),
+                error_code: path_abandon_frame.error_code(),
+            },
+            ReliableFrame::PathStatus(path_status_frame) if path_status_frame.is_backup() => {
+                QuicFrame::PathStatusBackup {
+                    path_id: path_status_frame.path_id(),
+                    path_status_sequence_number: path_status_frame.sequence(),
+                }
+            }
+            ReliableFrame::PathStatus(path_status_frame) => QuicFrame::PathStatusAvailable {
+                path_id: path_status_frame.path_id(),
+                path_status_sequence_number: path_status_frame.sequence(),
+            },
+            ReliableFrame::PathNewConnectionId(path_new_connection_id_frame) => {
+                let new_connection_id_frame = path_new_connection_id_frame.new_connection_id();
+                QuicFrame::PathNewConnectionId {
+                    path_id: path_new_connection_id_frame.path_id(),
+                    sequence_number: new_connection_id_frame.sequence() as u32,
+                    retire_prior_to: new_connection_id_frame.retire_prior_to() as u32,
+                    connection_id_length: Some(new_connection_id_frame.connection_id().len() as u8),
+                    connection_id: (*new_connection_id_frame.connection_id()).into(),
+                    stateless_reset_token: Some((**new_connection_id_frame.reset_token()).into()),
+                }
+            }
+            ReliableFrame::PathRetireConnectionId(path_retire_connection_id_frame) => {
+                QuicFrame::PathRetireConnectionId {
+                    path_id: path_retire_connection_id_frame.path_id(),
+                    sequence_number: path_retire_connection_id_frame.sequence() as u32,
+                }
+            }
+            ReliableFrame::MaxPathId(max_path_id_frame) => QuicFrame::MaxPathId {
+                maximum_path_id: max_path_id_frame.max_path_id(),
+            },
+            ReliableFrame::PathsBlocked(paths_blocked_frame) => QuicFrame::PathsBlocked {
+                maximum_path_id: paths_blocked_frame.max_path_id(),
+            },
+            ReliableFrame::PathCidsBlocked(path_cids_blocked_frame) => QuicFrame::PathCidsBlocked {
+                path_id: path_cids_blocked_frame.path_id(),
+                next_sequence_number: path_cids_blocked_frame.next_sequence(),
+            },
             ReliableFrame::Stream(stream_ctl_frame) => QuicFrame::from(stream_ctl_frame),
         }
     }
@@ -900,6 +1024,16 @@ impl From<&Frame> for QuicFrame {
             Frame::NewConnectionId(frame) => (&ReliableFrame::from(*frame)).into(),
             Frame::RetireConnectionId(frame) => (&ReliableFrame::from(*frame)).into(),
             Frame::HandshakeDone(frame) => (&ReliableFrame::from(*frame)).into(),
+            Frame::AckFrequency(frame) => (&ReliableFrame::from(*frame)).into(),
+            Frame::ImmediateAck(..) => QuicFrame::ImmediateAck {},
+            Frame::PathAck(frame) => frame.into(),
+            Frame::PathAbandon(frame) => (&ReliableFrame::from(*frame)).into(),
+            Frame::PathStatus(frame) => (&ReliableFrame::from(*frame)).into(),
+            Frame::PathNewConnectionId(frame) => (&ReliableFrame::from(*frame)).into(),
+            Frame::PathRetireConnectionId(frame) => (&ReliableFrame::from(*frame)).into(),
+            Frame::MaxPathId(frame) => (&ReliableFrame::from(*frame)).into(),
+            Frame::PathsBlocked(frame) => (&ReliableFrame::from(*frame)).into(),
+            Frame::PathCidsBlocked(frame) => (&ReliableFrame::from(*frame)).into(),
             Frame::Challenge(frame) => frame.into(),
             Frame::Response(frame) => frame.into(),
             Frame::StreamCtl(frame) => frame.into(),
@@ -1293,9 +1427,7 @@ mod rollback {
                     legacy::ConnectionCloseErrorCode::TransportError(error.into()),
                 ),
                 ConnectionCloseErrorCode::CryptoError(_error) => Err(()),
-                ConnectionCloseErrorCode::ApplicationError(error) => Ok(
-                    legacy::ConnectionCloseErrorCode::ApplicationError(error.into()),
-                ),
+                ConnectionCloseErrorCode::ApplicationError(error) => match error {},
                 ConnectionCloseErrorCode::Value(value) => {
                     Ok(legacy::ConnectionCloseErrorCode::Value(value))
                 }
@@ -1459,6 +1591,43 @@ mod rollback {
                     trigger_frame_type: trigger_frame_type.map(Into::into),
                 },
                 QuicFrame::HandshakeDone {} => legacy::QuicFrame::HandshakeDone {},
+                // not in the legacy draft
+                QuicFrame::AckFrequency { .. } => legacy::QuicFrame::Unknown {
+                    raw_frame_type: 0xaf,
+                    raw_length: None,
+                    raw: None,
+                },
+                QuicFrame::ImmediateAck {} => legacy::QuicFrame::Unknown {
+                    raw_frame_type: 0x1f,
+                    raw_length: None,
+                    raw: None,
+                },
+                QuicFrame::PathAck { length, .. } => legacy::QuicFrame::Unknown {
+                    raw_frame_type: 0x15228c00,
+                    raw_length: length,
+                    raw: None,
+                },
+                frame @ (QuicFrame::PathAbandon { .. }
+                | QuicFrame::PathStatusBackup { .. }
+                | QuicFrame::PathStatusAvailable { .. }
+                | QuicFrame::PathNewConnectionId { .. }
+                | QuicFrame::PathRetireConnectionId { .. }
+                | QuicFrame::MaxPathId { .. }
+                | QuicFrame::PathsBlocked { .. }
+                | QuicFrame::PathCidsBlocked { .. }) => legacy::QuicFrame::Unknown {
+                    raw_frame_type: match frame {
+                        QuicFrame::PathAbandon { .. } => 0x15228c05,
+                        QuicFrame::PathStatusBackup { .. } => 0x15228c07,
+                        QuicFrame::PathStatusAvailable { .. } => 0x15228c08,
+                        QuicFrame::PathNewConnectionId { .. } => 0x15228c09,
+                        QuicFrame::PathRetireConnectionId { .. } => 0x15228c0a,
+                        QuicFrame::MaxPathId { .. } => 0x15228c0c,
+                        QuicFrame::PathsBlocked { .. } => 0x15228c0d,
+                        _ => 0x15228c0e,
+                    },
+                    raw_length: None,
+                    raw: None,
+                },
                 QuicFrame::Unknow {
                     frame_type_bytes,
                     raw,
@@ -1485,9 +1654,7 @@ mod rollback {
         #[inline]
         fn from(value: ApplicationCode) -> Self {
             match value {
-                ApplicationCode::ApplicationError(error) => {
-                    legacy::ApplicationCode::ApplicationError(error.into())
-                }
+                ApplicationCode::ApplicationError(error) => match error {},
                 ApplicationCode::Value(value) => legacy::ApplicationCode::Value(value),
             }
         }
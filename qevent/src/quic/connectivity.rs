@@ -207,6 +207,8 @@ impl From<&QuicCloseFrame> for ConnectionCode {
             ErrorKind::KeyUpdate => TransportError::KeyUpdateError.into(),
             ErrorKind::AeadLimitReached => TransportError::AeadLimitReached.into(),
             ErrorKind::NoViablePath => TransportError::NoViablePath.into(),
+            // not defined in qlog yet
+            ErrorKind::VersionNegotiation => ConnectionCode::Value(0x11),
             ErrorKind::Crypto(code) => CryptoError(code).into(),
         }
     }
@@ -9,9 +9,8 @@ mod qudp {
     use bytes::BytesMut;
     use qbase::net::{
         address::{BindAddr, RealAddr, SocketBindAddr},
-        route::{Link, Pathway, ToEndpointAddr},
+        route::{Ecn, Link, Pathway, ToEndpointAddr},
     };
-    use qudp::BATCH_SIZE;
 
     use crate::{PacketHeader, QuicInterface};
 
@@ -53,11 +52,12 @@ mod qudp {
         }
 
         fn max_segments(&self) -> usize {
-            BATCH_SIZE
+            // probed on binding, and falls back to 1 once the kernel refuses GSO
+            self.inner.gso_segments()
         }
 
         fn max_segment_size(&self) -> usize {
-            1500
+            self.inner.max_segment_size()
         }
 
         fn poll_send(
@@ -66,13 +66,12 @@ mod qudp {
             pkts: &[io::IoSlice],
             hdr: PacketHeader,
         ) -> Poll<io::Result<usize>> {
-            debug_assert_eq!(hdr.ecn(), None);
             debug_assert_eq!(hdr.link().src(), self.read_addr()?);
             let hdr = qudp::DatagramHeader::new(
                 hdr.link().src().try_into().expect("Must be SocketAddr"),
                 hdr.link().dst().try_into().expect("Must be SocketAddr"),
                 hdr.ttl(),
-                hdr.ecn(),
+                hdr.ecn().map(|ecn| ecn as u8),
                 hdr.seg_size(),
             );
             self.inner.poll_send(cx, pkts, &hdr)
@@ -102,7 +101,7 @@ mod qudp {
                     way.flip(),
                     link.flip(),
                     qudp_hdr.ttl,
-                    qudp_hdr.ecn,
+                    qudp_hdr.ecn.map(Ecn::from_bits),
                     qudp_hdr.seg_size,
                 );
             }
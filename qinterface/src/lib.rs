@@ -4,6 +4,9 @@ pub mod handy;
 pub mod packet;
 pub mod queue;
 pub mod router;
+// 模拟网络（simulated）是可选的，用于测试
+#[cfg(feature = "simulated")]
+pub mod simulated;
 pub mod util;
 
 use std::{
@@ -59,6 +62,9 @@ pub trait QuicInterface: Send + Sync {
     ///
     /// Attempts to send multiple packets in a single operation.
     /// Return the number of packets sent,
+    ///
+    /// The packets are of `hdr.seg_size()` bytes except the last one, which may be shorter,
+    /// so that they can be sent in a single buffer with UDP GSO.
     fn poll_send(
         &self,
         cx: &mut Context,
@@ -1,7 +1,8 @@
 use bytes::{Bytes, BytesMut};
 use derive_more::Deref;
 use qbase::{
-    error::QuicError,
+    error::{ErrorKind, QuicError},
+    net::route::Ecn,
     packet::{
         decrypt::{
             decrypt_packet, remove_protection_of_long_packet, remove_protection_of_short_packet,
@@ -10,6 +11,7 @@ use qbase::{
         keys::ArcOneRttPacketKeys,
         number::{InvalidPacketNumber, PacketNumber},
     },
+    token::{RESET_TOKEN_SIZE, ResetToken},
 };
 use qevent::quic::{
     PacketHeader, PacketHeaderBuilder, QuicFrame,
@@ -23,17 +25,24 @@ pub struct CipherPacket<H> {
     header: H,
     payload: BytesMut,
     payload_offset: usize,
+    ecn: Option<Ecn>,
 }
 
 impl<H> CipherPacket<H>
 where
     PacketHeaderBuilder: for<'a> From<&'a H>,
 {
-    pub(crate) fn new(header: H, payload: BytesMut, payload_offset: usize) -> Self {
+    pub(crate) fn new(
+        header: H,
+        payload: BytesMut,
+        payload_offset: usize,
+        ecn: Option<Ecn>,
+    ) -> Self {
         Self {
             header,
             payload,
             payload_offset,
+            ecn,
         }
     }
 
@@ -104,6 +113,14 @@ where
         self.payload.len()
     }
 
+    /// Return the trailing 16 bytes of the packet, which may be a stateless reset token.
+    ///
+    /// Only meaningful for the short header packet, which is always the last packet in a datagram.
+    pub fn trailing_reset_token(&self) -> Option<ResetToken> {
+        let tail = self.payload.len().checked_sub(RESET_TOKEN_SIZE)?;
+        Some(ResetToken::new(&self.payload[tail..]))
+    }
+
     pub fn decrypt_long_packet(
         mut self,
         hpk: &dyn HeaderProtectionKey,
@@ -144,16 +161,20 @@ where
             header: self.header,
             plain: self.payload.freeze(),
             payload_offset: self.payload_offset,
+            ecn: self.ecn,
             undecoded_pn,
             decoded_pn,
             body_len: body_length,
         }))
     }
 
+    /// Remove the protection of the 1-RTT packet received on the path `path_id`,
+    /// which is 0 unless multipath is enabled.
     pub fn decrypt_short_packet(
         mut self,
         hpk: &dyn HeaderProtectionKey,
         pk: &ArcOneRttPacketKeys,
+        path_id: u32,
         pn_decoder: impl FnOnce(PacketNumber) -> Result<u64, InvalidPacketNumber>,
     ) -> Option<Result<PlainPacket<H>, QuicError>> {
         let pkt_buf = self.payload.as_mut();
@@ -176,12 +197,32 @@ where
                 return None;
             }
         };
-        let pk = pk.lock_guard().get_remote(key_phase, decoded_pn);
+        // The keys must not be updated elsewhere until the packet is decrypted,
+        // they may be updated by this packet if it is in the next key phase.
+        let mut pk = pk.lock_guard();
+        let remote_pk = pk.get_remote(key_phase, path_id, decoded_pn);
         let body_offset = self.payload_offset + undecoded_pn.size();
-        let body_length = match decrypt_packet(pk.as_ref(), decoded_pn, pkt_buf, body_offset) {
-            Ok(body_length) => body_length,
+        let body_length = match decrypt_packet(remote_pk.as_ref(), decoded_pn, pkt_buf, body_offset)
+        {
+            Ok(body_length) => {
+                pk.on_packet_decrypted(key_phase, path_id, decoded_pn);
+                body_length
+            }
             Err(error) => {
+                let is_limit_reached = pk.on_decryption_failure();
+                drop(pk);
                 self.drop_on_decryption_failure(error, decoded_pn);
+                // rfc9001 6.6
+                // If the total number of received packets that fail authentication within the
+                // connection, across all keys, exceeds the integrity limit for the selected
+                // AEAD, the endpoint MUST immediately close the connection with a connection
+                // error of type AEAD_LIMIT_REACHED
+                if is_limit_reached {
+                    return Some(Err(QuicError::with_default_fty(
+                        ErrorKind::AeadLimitReached,
+                        "too many packets failed to be decrypted",
+                    )));
+                }
                 return None;
             }
         };
@@ -190,6 +231,7 @@ where
             header: self.header,
             plain: self.payload.freeze(),
             payload_offset: self.payload_offset,
+            ecn: self.ecn,
             undecoded_pn,
             decoded_pn,
             body_len: body_length,
@@ -218,6 +260,7 @@ pub struct PlainPacket<H> {
     undecoded_pn: PacketNumber,
     plain: Bytes,
     payload_offset: usize,
+    ecn: Option<Ecn>,
     body_len: usize,
 }
 
@@ -230,6 +273,11 @@ impl<H> PlainPacket<H> {
         self.decoded_pn
     }
 
+    /// The ECN codepoint of the datagram carrying this packet, `None` if not reported.
+    pub fn ecn(&self) -> Option<Ecn> {
+        self.ecn
+    }
+
     pub fn payload_len(&self) -> usize {
         self.undecoded_pn.size() + self.body_len
     }
@@ -273,6 +321,28 @@ where
         })
     }
 
+    pub fn drop_on_migration_refused(self) {
+        qevent::event!(PacketDropped {
+            header: self.qlog_header(),
+            raw: self.raw_info(),
+            details: Map {
+                reason: "active migration disabled"
+            },
+            trigger: PacketDroppedTrigger::Genera
+        })
+    }
+
+    pub fn drop_on_unknown_server(self) {
+        qevent::event!(PacketDropped {
+            header: self.qlog_header(),
+            raw: self.raw_info(),
+            details: Map {
+                reason: "unknown server address"
+            },
+            trigger: PacketDroppedTrigger::Genera
+        })
+    }
+
     pub fn log_received(&self, frames: impl Into<Vec<QuicFrame>>) {
         qevent::event!(PacketReceived {
             header: self.qlog_header(),
@@ -1,12 +1,15 @@
+use std::sync::Mutex;
+
 use qbase::{
     net::{
         address::BindAddr,
         route::{Link, Pathway},
     },
     packet::{
-        DataHeader, Packet,
+        DataHeader, Packet, RetryPacket, VersionNegotiationHeader,
         header::{long, short},
     },
+    token::ResetToken,
     util::BoundQueue,
 };
 
@@ -20,7 +23,10 @@ pub struct RcvdPacketQueue {
     handshake: PacketQueue<long::HandshakeHeader>,
     zero_rtt: PacketQueue<long::ZeroRttHeader>,
     one_rtt: PacketQueue<short::OneRttHeader>,
-    // pub retry:
+    retry: BoundQueue<(BindAddr, RetryPacket, Pathway, Link)>,
+    vn: BoundQueue<(BindAddr, VersionNegotiationHeader, Pathway, Link)>,
+    // the reset tokens routed to this queue, to remove them from the router without a full scan
+    pub(crate) reset_tokens: Mutex<Vec<ResetToken>>,
 }
 
 impl Default for RcvdPacketQueue {
@@ -36,6 +42,9 @@ impl RcvdPacketQueue {
             handshake: BoundQueue::new(16),
             zero_rtt: BoundQueue::new(16),
             one_rtt: BoundQueue::new(16),
+            retry: BoundQueue::new(4),
+            vn: BoundQueue::new(4),
+            reset_tokens: Mutex::new(Vec::new()),
         }
     }
 
@@ -55,11 +64,21 @@ impl RcvdPacketQueue {
         &self.one_rtt
     }
 
+    pub fn retry(&self) -> &BoundQueue<(BindAddr, RetryPacket, Pathway, Link)> {
+        &self.retry
+    }
+
+    pub fn vn(&self) -> &BoundQueue<(BindAddr, VersionNegotiationHeader, Pathway, Link)> {
+        &self.vn
+    }
+
     pub fn close_all(&self) {
         self.initial.close();
         self.handshake.close();
         self.zero_rtt.close();
         self.one_rtt.close();
+        self.retry.close();
+        self.vn.close();
     }
 
     pub async fn deliver(
@@ -72,36 +91,40 @@ impl RcvdPacketQueue {
         match packet {
             Packet::Data(packet) => match packet.header {
                 DataHeader::Long(long::DataHeader::Initial(header)) => {
-                    let packet = CipherPacket::new(header, packet.bytes, packet.offset);
+                    let packet = CipherPacket::new(header, packet.bytes, packet.offset, packet.ecn);
                     _ = self
                         .initial
                         .send((bind_addr, packet, pathway, socket))
                         .await;
                 }
                 DataHeader::Long(long::DataHeader::Handshake(header)) => {
-                    let packet = CipherPacket::new(header, packet.bytes, packet.offset);
+                    let packet = CipherPacket::new(header, packet.bytes, packet.offset, packet.ecn);
                     _ = self
                         .handshake
                         .send((bind_addr, packet, pathway, socket))
                         .await;
                 }
                 DataHeader::Long(long::DataHeader::ZeroRtt(header)) => {
-                    let packet = CipherPacket::new(header, packet.bytes, packet.offset);
+                    let packet = CipherPacket::new(header, packet.bytes, packet.offset, packet.ecn);
                     _ = self
                         .zero_rtt
                         .send((bind_addr, packet, pathway, socket))
                         .await;
                 }
                 DataHeader::Short(header) => {
-                    let packet = CipherPacket::new(header, packet.bytes, packet.offset);
+                    let packet = CipherPacket::new(header, packet.bytes, packet.offset, packet.ecn);
                     _ = self
                         .one_rtt
                         .send((bind_addr, packet, pathway, socket))
                         .await;
                 }
             },
-            Packet::VN(_vn) => {}
-            Packet::Retry(_retry) => {}
+            Packet::VN(vn) => {
+                _ = self.vn.send((bind_addr, vn, pathway, socket)).await;
+            }
+            Packet::Retry(packet) => {
+                _ = self.retry.send((bind_addr, packet, pathway, socket)).await;
+            }
         }
     }
 }
@@ -1,7 +1,10 @@
 use std::{
-    fmt, io,
+    collections::hash_map::RandomState,
+    fmt,
+    hash::BuildHasher,
+    io,
     net::SocketAddr,
-    sync::{Arc, Weak},
+    sync::{Arc, RwLock, Weak},
 };
 
 use bytes::{Bytes, BytesMut};
@@ -14,8 +17,18 @@ use qbase::{
         address::{BindAddr, RealAddr},
         route::{Link, PacketHeader, Pathway},
     },
-    packet::{self, Packet, PacketReader, header::GetDcid},
+    packet::{
+        self, DataHeader, Packet, PacketReader,
+        header::{
+            GetDcid,
+            io::WriteHeader,
+            long::io::{LongHeaderBuilder, be_invariant_long_header},
+        },
+        r#type::long::SUPPORTED_VERSIONS,
+    },
+    token::{RESET_TOKEN_SIZE, ResetToken, StatelessResetKey},
 };
+use rand::Rng;
 use tokio::task::{AbortHandle, JoinHandle};
 
 use crate::{QuicInterface, queue::RcvdPacketQueue, util::Channel};
@@ -55,10 +68,22 @@ impl Drop for InterfaceContext {
     }
 }
 
+/// The smallest stateless reset: 5 unpredictable bytes followed by the reset token.
+const MIN_STATELESS_RESET_SIZE: usize = 5 + RESET_TOKEN_SIZE;
+/// A stateless reset needs not to be larger than this to be indistinguishable from other packets.
+const MAX_STATELESS_RESET_SIZE: usize = 43;
+
 #[doc(alias = "RouterInterface")]
 pub struct QuicProto {
     interfaces: DashMap<BindAddr, InterfaceContext>,
     router_table: DashMap<Signpost, Arc<RcvdPacketQueue>>,
+    // the reset tokens issued by the peers, to route the stateless resets to the connections.
+    // The table is indexed by the keyed hash of the tokens, so that looking up the trailing bytes
+    // of a packet leaks nothing about the tokens through timing, see RFC 9000 section 10.3.1
+    reset_table: DashMap<u64, (ResetToken, Arc<RcvdPacketQueue>)>,
+    reset_hasher: RandomState,
+    // the versions listed in the Version Negotiation packets
+    supported_versions: RwLock<Option<Vec<u32>>>,
     //
     unrouted_packets: Channel<(BindAddr, Packet, Pathway, Link)>,
     broken_interfaces: Channel<(BindAddr, Weak<dyn QuicInterface>, io::Error)>,
@@ -69,6 +94,9 @@ impl Default for QuicProto {
         Self {
             interfaces: DashMap::new(),
             router_table: DashMap::new(),
+            reset_table: DashMap::new(),
+            reset_hasher: RandomState::new(),
+            supported_versions: RwLock::new(None),
             unrouted_packets: Channel::new(64),
             broken_interfaces: Channel::new(64),
         }
@@ -81,6 +109,8 @@ impl fmt::Debug for QuicProto {
             .field("interfaces", &"...")
             .field("address_mappings", &"...")
             .field("router_table", &"...")
+            .field("reset_table", &"...")
+            .field("supported_versions", &"...")
             .field("unrouted_packets", &"...")
             .field("broken_interfaces", &"...")
             .finish()
@@ -153,6 +183,22 @@ impl QuicProto {
                     .map(|(mut seg, hdr)| (seg.split_to(seg.len().min(hdr.seg_size() as _)), hdr))
                 {
                     let datagram_size = datagram.len();
+                    // The packets of unsupported versions can not be parsed, only the version-independent
+                    // fields of the first packet are read to respond with a Version Negotiation packet.
+                    if let Ok((_, (version, dcid, scid))) = be_invariant_long_header(&datagram) {
+                        if version != 0 && !SUPPORTED_VERSIONS.contains(&version) {
+                            let (pathway, link) = (header.pathway(), header.link());
+                            this.on_unsupported_version(
+                                bind_addr.clone(),
+                                datagram_size,
+                                (dcid, scid),
+                                pathway,
+                                link,
+                            )
+                            .await;
+                            continue;
+                        }
+                    }
                     // todo: parse packets with any length of dcid, but this doesn't seem to matter because the DCID of the perr is chosen by ourselves
                     rcvd_pkts.extend(PacketReader::new(datagram, 8).flatten());
 
@@ -163,10 +209,13 @@ impl QuicProto {
                     // Section 10.2.3.
                     let is_initial_packet = |pkt: &Packet| matches!(pkt, Packet::Data(packet) if matches!(packet.header, packet::DataHeader::Long(packet::long::DataHeader::Initial(..))));
 
-                    for packet in rcvd_pkts
+                    for mut packet in rcvd_pkts
                         .drain(..)
                         .filter(|pkt| !(is_initial_packet(pkt) && datagram_size < 1200))
                     {
+                        if let Packet::Data(packet) = &mut packet {
+                            packet.ecn = header.ecn();
+                        }
                         this.deliver(bind_addr.clone(), packet, header.pathway(), header.link())
                             .await;
                     }
@@ -239,7 +288,11 @@ impl QuicProto {
         self.del_interface_if(bind_addr, |iface, _| Arc::strong_count(iface) == 1)
     }
 
-    async fn try_deliver(
+    /// Deliver the packet to the connection it is routed to.
+    ///
+    /// Returns the packet back if no connection is routed, unlike [`Self::deliver`], the packet
+    /// is not put into the unrouted packets queue.
+    pub async fn try_deliver(
         &self,
         bind_addr: BindAddr,
         packet: Packet,
@@ -273,9 +326,154 @@ impl QuicProto {
     }
 
     pub async fn deliver(&self, bind_addr: BindAddr, packet: Packet, pathway: Pathway, link: Link) {
-        if let Err(received) = self.try_deliver(bind_addr, packet, pathway, link).await {
-            _ = self.unrouted_packets.send(received).await;
+        match self.try_deliver(bind_addr, packet, pathway, link).await {
+            Ok(()) => {}
+            Err((bind_addr, Packet::Data(packet), pathway, link))
+                if matches!(packet.header, DataHeader::Short(..)) =>
+            {
+                if let Err(received) = self
+                    .try_deliver_stateless_reset(bind_addr, packet, pathway, link)
+                    .await
+                {
+                    _ = self.unrouted_packets.send(received).await
+                }
+            }
+            Err(received) => _ = self.unrouted_packets.send(received).await,
+        }
+    }
+
+    /// Deliver the short header packet that does not belong to any connection, if it may be a
+    /// stateless reset sent by the peer, whose trailing 16 bytes are the reset token issued by
+    /// the peer, it will be delivered to the connection to check.
+    ///
+    /// Otherwise, the state of the connection may have been lost, the packet is returned back.
+    async fn try_deliver_stateless_reset(
+        &self,
+        bind_addr: BindAddr,
+        packet: packet::DataPacket,
+        pathway: Pathway,
+        link: Link,
+    ) -> Result<(), (BindAddr, Packet, Pathway, Link)> {
+        let size = packet.bytes.len();
+        if size >= MIN_STATELESS_RESET_SIZE {
+            let tail = &packet.bytes[size - RESET_TOKEN_SIZE..];
+            let routed = self
+                .reset_table
+                .get(&self.reset_hasher.hash_one(tail))
+                .filter(|entry| entry.0.matches(tail))
+                .map(|entry| entry.1.clone());
+            if let Some(rcvd_pkt_q) = routed {
+                _ = rcvd_pkt_q
+                    .deliver(bind_addr, Packet::Data(packet), pathway, link)
+                    .await;
+                return Ok(());
+            }
         }
+        Err((bind_addr, Packet::Data(packet), pathway, link))
+    }
+
+    /// Respond the short header packet that does not belong to any connection with a stateless
+    /// reset, whose token is derived from the connection ID of the packet with the `reset_key`.
+    ///
+    /// The state of the connection may have been lost, the peer closes the connection on
+    /// receiving the stateless reset, if the connection ID was issued with the same key.
+    pub async fn send_stateless_reset(
+        &self,
+        bind_addr: BindAddr,
+        packet: &packet::DataPacket,
+        pathway: Pathway,
+        link: Link,
+        reset_key: &StatelessResetKey,
+    ) {
+        let size = packet.bytes.len();
+        // rfc9000 10.3
+        // An endpoint MUST ensure that every Stateless Reset that it sends is smaller than the
+        // packet that triggered it, unless it maintains other means to prevent looping. An endpoint
+        // that sends a Stateless Reset in response to a packet that is 43 bytes or shorter SHOULD
+        // send a Stateless Reset that is one byte shorter than the packet it responds to.
+        let reset_size = size.saturating_sub(1).min(MAX_STATELESS_RESET_SIZE);
+        if reset_size < MIN_STATELESS_RESET_SIZE {
+            return;
+        }
+        let reset_token = reset_key.reset_token(packet.header.dcid());
+        let Some(iface) = self.get_interface(bind_addr) else {
+            return;
+        };
+
+        let mut stateless_reset = vec![0u8; reset_size];
+        let (unpredictable, token) = stateless_reset.split_at_mut(reset_size - RESET_TOKEN_SIZE);
+        rand::rng().fill(&mut unpredictable[..]);
+        // Fixed Bit (1) = 1, and looks like a short header packet
+        unpredictable[0] = 0x40 | (unpredictable[0] & 0x3f);
+        token.copy_from_slice(reset_token.as_slice());
+
+        tracing::debug!(%pathway, reset_size, "send stateless reset for unrouted packet");
+        let hdr = PacketHeader::new(pathway, link, 64, None, reset_size as _);
+        _ = core::future::poll_fn(|cx| {
+            iface.poll_send(cx, &[io::IoSlice::new(&stateless_reset)], hdr)
+        })
+        .await;
+    }
+
+    /// Handle a long header packet of an unsupported version, which can not be parsed.
+    ///
+    /// A Version Negotiation packet listing the supported versions will be sent in response, if
+    /// the supported versions are set, see [`Self::set_supported_versions`].
+    async fn on_unsupported_version(
+        &self,
+        bind_addr: BindAddr,
+        datagram_size: usize,
+        (dcid, scid): (ConnectionId, ConnectionId),
+        pathway: Pathway,
+        link: Link,
+    ) {
+        // rfc9000 5.2.2
+        // If a server receives a packet that indicates an unsupported version and if the packet is large enough
+        // to initiate a new connection for any supported version, the server SHOULD send a Version Negotiation
+        // packet as described in Section 6.1. Servers MUST drop smaller packets that specify unsupported versions.
+        if datagram_size < 1200 {
+            return;
+        }
+        self.send_version_negotiation(bind_addr, (dcid, scid), pathway, link)
+            .await;
+    }
+
+    /// Respond the long header packet with the `dcid` and `scid` with a Version Negotiation
+    /// packet, listing the supported versions set by [`Self::set_supported_versions`].
+    ///
+    /// Nothing will be sent if the supported versions are not set.
+    pub async fn send_version_negotiation(
+        &self,
+        bind_addr: BindAddr,
+        (dcid, scid): (ConnectionId, ConnectionId),
+        pathway: Pathway,
+        link: Link,
+    ) {
+        let Some(mut versions) = self.supported_versions.read().unwrap().clone() else {
+            return;
+        };
+        let Some(iface) = self.get_interface(bind_addr) else {
+            return;
+        };
+
+        // rfc9000 6.3
+        // Endpoints MAY add reserved versions to any field where unknown or unsupported versions are ignored to
+        // test that a peer correctly ignores the value.
+        versions.push(rand::rng().random::<u32>() & 0xf0f0_f0f0 | 0x0a0a_0a0a);
+        // rfc9000 17.2.1
+        // The server MUST include the value from the Source Connection ID field of the packet it receives in the
+        // Destination Connection ID field. The value for Source Connection ID MUST be copied from the Destination
+        // Connection ID of the received packet, which is initially randomly selected by a client.
+        let header = LongHeaderBuilder::with_cid(scid, dcid).vn(versions);
+        let mut version_negotiation = Vec::with_capacity(64);
+        version_negotiation.put_header(&header);
+
+        tracing::debug!(%pathway, "send version negotiation for unsupported version");
+        let hdr = PacketHeader::new(pathway, link, 64, None, version_negotiation.len() as _);
+        _ = core::future::poll_fn(|cx| {
+            iface.poll_send(cx, &[io::IoSlice::new(&version_negotiation)], hdr)
+        })
+        .await;
     }
 
     /// Dismiss all unrouted packets.
@@ -318,6 +516,35 @@ impl QuicProto {
         self.router_table.remove(signpost);
     }
 
+    /// Set the versions to list in the Version Negotiation packets.
+    ///
+    /// Once the versions are set, a Version Negotiation packet will be sent in response to each long
+    /// header packet of unsupported versions, if it is carried in a datagram of at least 1200 bytes.
+    pub fn set_supported_versions(&self, versions: Vec<u32>) {
+        *self.supported_versions.write().unwrap() = Some(versions);
+    }
+
+    /// Route the stateless resets with the `token` issued by the peer to the connection.
+    pub fn add_reset_entry(&self, token: ResetToken, queue: Arc<RcvdPacketQueue>) {
+        let digest = self.reset_hasher.hash_one(token.as_slice());
+        queue.reset_tokens.lock().unwrap().push(token);
+        self.reset_table.insert(digest, (token, queue));
+    }
+
+    /// Remove all stateless reset routes to the connection.
+    pub fn del_reset_entries(&self, queue: &Arc<RcvdPacketQueue>) {
+        let tokens = core::mem::take(&mut *queue.reset_tokens.lock().unwrap());
+        for token in tokens {
+            let digest = self.reset_hasher.hash_one(token.as_slice());
+            self.reset_table
+                .remove_if(&digest, |_, (_, rcvd_pkt_q)| Arc::ptr_eq(rcvd_pkt_q, queue));
+        }
+    }
+
+    /// Create the registry to route the connection IDs issued by a connection to its queue.
+    ///
+    /// The stateless reset tokens of the connection IDs are generated randomly, unless the reset
+    /// key of the endpoint is given, see [`RouterRegistry::with_reset_key`].
     pub fn registry<T>(
         self: &Arc<Self>,
         rcvd_pkts_buf: Arc<RcvdPacketQueue>,
@@ -327,6 +554,7 @@ impl QuicProto {
             router_iface: self.clone(),
             rcvd_pkts_buf,
             issued_cids,
+            reset_key: None,
         }
     }
 }
@@ -336,6 +564,31 @@ pub struct RouterRegistry<TX> {
     router_iface: Arc<QuicProto>,
     rcvd_pkts_buf: Arc<RcvdPacketQueue>,
     issued_cids: TX,
+    reset_key: Option<StatelessResetKey>,
+}
+
+impl<TX> RouterRegistry<TX> {
+    /// Derive the stateless reset tokens of the connection IDs from the `reset_key` of the
+    /// endpoint, the tokens are generated randomly if [`None`] is given.
+    pub fn with_reset_key(self, reset_key: Option<StatelessResetKey>) -> Self {
+        Self { reset_key, ..self }
+    }
+
+    /// Create the registry for another set of connection IDs issued by the same connection,
+    /// such as the ones of another path with multipath, sharing the queue and the reset key.
+    pub fn with_issued_cids<U>(&self, issued_cids: U) -> RouterRegistry<U> {
+        RouterRegistry {
+            router_iface: self.router_iface.clone(),
+            rcvd_pkts_buf: self.rcvd_pkts_buf.clone(),
+            issued_cids,
+            reset_key: self.reset_key.clone(),
+        }
+    }
+
+    /// The [`QuicProto`] that the connection IDs are routed by.
+    pub fn proto(&self) -> &Arc<QuicProto> {
+        &self.router_iface
+    }
 }
 
 impl<T> GenUniqueCid for RouterRegistry<T>
@@ -357,6 +610,13 @@ where
             })
             .unwrap()
     }
+
+    fn gen_reset_token(&self, cid: &ConnectionId) -> ResetToken {
+        match &self.reset_key {
+            Some(key) => key.reset_token(cid),
+            None => ResetToken::random_gen(),
+        }
+    }
 }
 
 impl<TX> RetireCid for RouterRegistry<TX>
@@ -387,3 +647,55 @@ where
         self.issued_cids.recv_frame(frame)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reset_entries() {
+        let proto = QuicProto::new();
+        let (queue, other) = (
+            Arc::new(RcvdPacketQueue::new()),
+            Arc::new(RcvdPacketQueue::new()),
+        );
+        let tokens = [ResetToken::random_gen(), ResetToken::random_gen()];
+        for token in tokens {
+            proto.add_reset_entry(token, queue.clone());
+        }
+        let other_token = ResetToken::random_gen();
+        proto.add_reset_entry(other_token, other.clone());
+        assert_eq!(proto.reset_table.len(), 3);
+
+        proto.del_reset_entries(&queue);
+        assert_eq!(proto.reset_table.len(), 1);
+        assert!(queue.reset_tokens.lock().unwrap().is_empty());
+        let digest = proto.reset_hasher.hash_one(other_token.as_slice());
+        let entry = proto.reset_table.get(&digest).unwrap();
+        assert!(entry.0.matches(other_token.as_slice()));
+        assert!(Arc::ptr_eq(&entry.1, &other));
+    }
+
+    #[test]
+    fn registry_reset_tokens() {
+        let hkdf = rustls::crypto::ring::default_provider()
+            .cipher_suites
+            .iter()
+            .find_map(|suite| suite.tls13())
+            .unwrap()
+            .hkdf_provider;
+        let key = StatelessResetKey::new(hkdf, b"secret");
+        let proto = Arc::new(QuicProto::new());
+        let queue = Arc::new(RcvdPacketQueue::new());
+        let cid = ConnectionId::from_slice(&[1, 2, 3, 4, 5, 6, 7, 8]);
+
+        // the tokens are reproducible by the endpoint holding the key, on any path
+        let registry = (proto.registry(queue.clone(), ())).with_reset_key(Some(key.clone()));
+        assert_eq!(registry.gen_reset_token(&cid), key.reset_token(&cid));
+        let path_registry = registry.with_issued_cids(0u64);
+        assert_eq!(path_registry.gen_reset_token(&cid), key.reset_token(&cid));
+
+        let registry = proto.registry(queue, ());
+        assert_ne!(registry.gen_reset_token(&cid), key.reset_token(&cid));
+    }
+}
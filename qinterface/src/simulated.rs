@@ -0,0 +1,591 @@
+//! An in-process virtual network, to reproduce loss, reordering and migration in tests.
+//!
+//! [`SimulatedNetwork`] binds [`SimulatedInterface`]s which deliver datagrams to each other
+//! through links impaired as their [`LinkConfig`]. The delivery is driven by the tokio timer,
+//! and the impairments are decided by a seeded random number generator, so the behavior is
+//! deterministic on a runtime with the paused clock:
+//!
+//! ```rust,ignore
+//! let network = SimulatedNetwork::with_seed(7);
+//! network.set_default_link(LinkConfig {
+//!     latency: Duration::from_millis(20),
+//!     loss: 0.05,
+//!     ..Default::default()
+//! });
+//! let listeners = QuicListeners::builder()?
+//!     .with_iface_factory(network.clone())
+//!     .listen(128);
+//! let client = QuicClient::builder()
+//!     .with_iface_factory(network.clone())
+//!     .build();
+//! ```
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    future::Future,
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    pin::Pin,
+    sync::{Arc, Mutex, Weak},
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+
+use bytes::{Bytes, BytesMut};
+use qbase::net::{
+    address::{BindAddr, Port, RealAddr, SocketBindAddr},
+    route::{Ecn, Link, PacketHeader, Pathway, ToEndpointAddr},
+};
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use tokio::time::{Instant, Sleep};
+
+use crate::{QuicInterface, factory::ProductQuicInterface};
+
+/// The maximum size of the UDP payload the simulated interfaces send and receive.
+pub const MAX_SEGMENT_SIZE: usize = 1472;
+/// The maximum number of datagrams the simulated interfaces send or receive at once.
+pub const MAX_SEGMENTS: usize = 64;
+// The first port allocated for the interfaces bound to the `alloc` or `any` port.
+const EPHEMERAL_PORT: u16 = 49152;
+
+/// The impairments of datagrams traveling in one direction between two hosts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkConfig {
+    /// The one-way delay of each datagram.
+    pub latency: Duration,
+    /// Each datagram is delayed for extra random time up to `jitter`, which may reorder them.
+    pub jitter: Duration,
+    /// The bandwidth in bytes per second, datagrams are queued behind each other if limited.
+    pub bandwidth: Option<u64>,
+    /// The probability for a datagram to be lost.
+    pub loss: f64,
+    /// The probability for a datagram to be delivered twice.
+    pub duplication: f64,
+    /// The probability for a datagram to be delayed for another `latency`, so that the
+    /// datagrams sent after it arrive first.
+    pub reordering: f64,
+    /// The maximum size of the IP packet, the datagrams that do not fit are dropped.
+    pub mtu: usize,
+}
+
+impl Default for LinkConfig {
+    fn default() -> Self {
+        Self {
+            latency: Duration::from_millis(10),
+            jitter: Duration::ZERO,
+            bandwidth: None,
+            loss: 0.0,
+            duplication: 0.0,
+            reordering: 0.0,
+            mtu: 1500,
+        }
+    }
+}
+
+impl LinkConfig {
+    fn max_payload_size(&self, dst: &SocketAddr) -> usize {
+        // IP header and UDP header
+        let overhead = if dst.is_ipv4() { 20 + 8 } else { 40 + 8 };
+        self.mtu.saturating_sub(overhead)
+    }
+}
+
+struct Datagram {
+    deliver_at: Instant,
+    // The order of sending, keeps the datagrams delivered at the same time in order.
+    seq: u64,
+    src: SocketAddr,
+    ecn: Option<Ecn>,
+    payload: Bytes,
+}
+
+impl PartialEq for Datagram {
+    fn eq(&self, other: &Self) -> bool {
+        (self.deliver_at, self.seq) == (other.deliver_at, other.seq)
+    }
+}
+
+impl Eq for Datagram {}
+
+impl PartialOrd for Datagram {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Datagram {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.deliver_at, self.seq).cmp(&(other.deliver_at, other.seq))
+    }
+}
+
+#[derive(Default)]
+struct InboxState {
+    datagrams: BinaryHeap<Reverse<Datagram>>,
+    timer: Option<Pin<Box<Sleep>>>,
+    waker: Option<Waker>,
+}
+
+/// The datagrams on the way to an interface, ordered by the time to deliver.
+#[derive(Default)]
+struct Inbox(Mutex<InboxState>);
+
+impl Inbox {
+    fn push(&self, datagram: Datagram) {
+        let mut inbox = self.0.lock().unwrap();
+        inbox.datagrams.push(Reverse(datagram));
+        if let Some(waker) = inbox.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+struct NetworkState {
+    rng: StdRng,
+    seq: u64,
+    next_port: u16,
+    default_link: LinkConfig,
+    links: HashMap<(IpAddr, IpAddr), LinkConfig>,
+    // The time the link finishes sending the queued datagrams, if the bandwidth is limited.
+    busy_until: HashMap<(IpAddr, IpAddr), Instant>,
+    // Local address of the interface -> the address its datagrams appear from, rewritten by NAT.
+    public_addrs: HashMap<SocketAddr, SocketAddr>,
+    routes: HashMap<SocketAddr, Weak<Inbox>>,
+}
+
+impl NetworkState {
+    fn alloc_port(&mut self, ip: IpAddr) -> io::Result<u16> {
+        for _ in EPHEMERAL_PORT..=u16::MAX {
+            let port = self.next_port;
+            self.next_port = self.next_port.checked_add(1).unwrap_or(EPHEMERAL_PORT);
+            let addr = SocketAddr::new(ip, port);
+            if !self.public_addrs.contains_key(&addr) && !self.routes.contains_key(&addr) {
+                return Ok(port);
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::AddrInUse,
+            "no port available in the simulated network",
+        ))
+    }
+
+    fn link(&self, src: &SocketAddr, dst: &SocketAddr) -> &LinkConfig {
+        self.links
+            .get(&(src.ip(), dst.ip()))
+            .unwrap_or(&self.default_link)
+    }
+
+    /// Decide the fate of a datagram, returns the times to deliver its copies.
+    fn transmit(&mut self, src: SocketAddr, dst: SocketAddr, size: usize) -> Vec<Instant> {
+        let link = self.link(&src, &dst).clone();
+        if size > link.max_payload_size(&dst) || self.rng.random_bool(link.loss.clamp(0.0, 1.0)) {
+            return vec![];
+        }
+
+        let now = Instant::now();
+        let sent_at = match link.bandwidth {
+            Some(bandwidth) => {
+                let busy_until = self.busy_until.entry((src.ip(), dst.ip())).or_insert(now);
+                let transmission = Duration::from_secs_f64(size as f64 / bandwidth.max(1) as f64);
+                *busy_until = (*busy_until).max(now) + transmission;
+                *busy_until
+            }
+            None => now,
+        };
+
+        let copies = 1 + self.rng.random_bool(link.duplication.clamp(0.0, 1.0)) as usize;
+        (0..copies)
+            .map(|_| {
+                let mut delay = link.latency;
+                if !link.jitter.is_zero() {
+                    delay += link.jitter.mul_f64(self.rng.random::<f64>());
+                }
+                if self.rng.random_bool(link.reordering.clamp(0.0, 1.0)) {
+                    delay += link.latency;
+                }
+                sent_at + delay
+            })
+            .collect()
+    }
+}
+
+/// An in-process virtual network, to bind [`SimulatedInterface`]s on.
+///
+/// The addresses of the network are independent of the host, the interfaces bound to the
+/// unspecified address appear from the loopback address. Cloning the network gives a handle to
+/// the same network.
+#[derive(Clone)]
+pub struct SimulatedNetwork(Arc<Mutex<NetworkState>>);
+
+impl Default for SimulatedNetwork {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SimulatedNetwork {
+    /// Create a network with the default links, and the random number generator seeded with 0.
+    pub fn new() -> Self {
+        Self::with_seed(0)
+    }
+
+    /// Create a network with the default links, the impairments are decided by a random number
+    /// generator seeded with `seed`.
+    pub fn with_seed(seed: u64) -> Self {
+        Self(Arc::new(Mutex::new(NetworkState {
+            rng: StdRng::seed_from_u64(seed),
+            seq: 0,
+            next_port: EPHEMERAL_PORT,
+            default_link: LinkConfig::default(),
+            links: HashMap::new(),
+            busy_until: HashMap::new(),
+            public_addrs: HashMap::new(),
+            routes: HashMap::new(),
+        })))
+    }
+
+    /// Set the config of the links between the hosts without their own config.
+    pub fn set_default_link(&self, config: LinkConfig) {
+        self.0.lock().unwrap().default_link = config;
+    }
+
+    /// Set the config of the link from the host `src` to the host `dst`.
+    ///
+    /// The hosts are the IP addresses the datagrams appear from and are sent to.
+    pub fn set_link(&self, src: IpAddr, dst: IpAddr, config: LinkConfig) {
+        self.0.lock().unwrap().links.insert((src, dst), config);
+    }
+
+    /// Bind an interface on the network.
+    ///
+    /// Only the [`BindAddr`]s of inet are supported, a port is allocated for the `alloc` and
+    /// `any` port.
+    pub fn bind(&self, bind_addr: BindAddr) -> io::Result<SimulatedInterface> {
+        let BindAddr::Socket(SocketBindAddr::Inet(inet_bind_addr)) = &bind_addr else {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("simulated network only binds inet addresses, got: {bind_addr}"),
+            ));
+        };
+
+        let mut network = self.0.lock().unwrap();
+        let ip = inet_bind_addr.ip();
+        let port = match inet_bind_addr.port() {
+            Port::Special(port) => port.get(),
+            Port::Any | Port::Alloc(_) => network.alloc_port(ip)?,
+        };
+        let local_addr = SocketAddr::new(ip, port);
+        let public_addr = match ip {
+            ip if !ip.is_unspecified() => local_addr,
+            IpAddr::V4(_) => SocketAddr::new(Ipv4Addr::LOCALHOST.into(), port),
+            IpAddr::V6(_) => SocketAddr::new(Ipv6Addr::LOCALHOST.into(), port),
+        };
+        if network.public_addrs.contains_key(&local_addr)
+            || network.routes.contains_key(&public_addr)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                format!("{local_addr} is in use in the simulated network"),
+            ));
+        }
+
+        let inbox = Arc::new(Inbox::default());
+        network.public_addrs.insert(local_addr, public_addr);
+        network.routes.insert(public_addr, Arc::downgrade(&inbox));
+        Ok(SimulatedInterface {
+            bind_addr,
+            local_addr,
+            network: self.clone(),
+            inbox,
+        })
+    }
+
+    /// Rebind the interface bound to `local_addr` to a new port like a NAT does, returns the new
+    /// address its datagrams appear from.
+    ///
+    /// The datagrams sent to the old address are dropped since then.
+    pub fn rebind(&self, local_addr: SocketAddr) -> io::Result<SocketAddr> {
+        let mut network = self.0.lock().unwrap();
+        let Some(&old_addr) = network.public_addrs.get(&local_addr) else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no interface is bound to {local_addr} in the simulated network"),
+            ));
+        };
+        let new_addr = SocketAddr::new(old_addr.ip(), network.alloc_port(old_addr.ip())?);
+        let route = network.routes.remove(&old_addr).unwrap_or_default();
+        network.routes.insert(new_addr, route);
+        network.public_addrs.insert(local_addr, new_addr);
+        Ok(new_addr)
+    }
+
+    fn send(&self, src: SocketAddr, dst: SocketAddr, ecn: Option<Ecn>, payload: &[u8]) {
+        let mut network = self.0.lock().unwrap();
+        let Some(&src) = network.public_addrs.get(&src) else {
+            return;
+        };
+        let Some(inbox) = network.routes.get(&dst).and_then(Weak::upgrade) else {
+            return;
+        };
+        for deliver_at in network.transmit(src, dst, payload.len()) {
+            network.seq += 1;
+            inbox.push(Datagram {
+                deliver_at,
+                seq: network.seq,
+                src,
+                ecn,
+                payload: Bytes::copy_from_slice(payload),
+            });
+        }
+    }
+
+    fn unbind(&self, local_addr: &SocketAddr) {
+        let mut network = self.0.lock().unwrap();
+        if let Some(public_addr) = network.public_addrs.remove(local_addr) {
+            network.routes.remove(&public_addr);
+        }
+    }
+}
+
+impl ProductQuicInterface for SimulatedNetwork {
+    fn bind(&self, addr: BindAddr) -> io::Result<Arc<dyn QuicInterface>> {
+        Ok(Arc::new(SimulatedNetwork::bind(self, addr)?))
+    }
+}
+
+/// A [`QuicInterface`] bound on a [`SimulatedNetwork`].
+pub struct SimulatedInterface {
+    bind_addr: BindAddr,
+    local_addr: SocketAddr,
+    network: SimulatedNetwork,
+    inbox: Arc<Inbox>,
+}
+
+impl SimulatedInterface {
+    /// The address this interface is bound to.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
+impl QuicInterface for SimulatedInterface {
+    fn bind_addr(&self) -> BindAddr {
+        self.bind_addr.clone()
+    }
+
+    fn read_addr(&self) -> io::Result<RealAddr> {
+        Ok(RealAddr::Inet(self.local_addr))
+    }
+
+    fn max_segment_size(&self) -> usize {
+        MAX_SEGMENT_SIZE
+    }
+
+    fn max_segments(&self) -> usize {
+        MAX_SEGMENTS
+    }
+
+    fn poll_send(
+        &self,
+        _cx: &mut Context,
+        pkts: &[io::IoSlice],
+        hdr: PacketHeader,
+    ) -> Poll<io::Result<usize>> {
+        let dst = hdr.link().dst().try_into().map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::Unsupported,
+                "simulated network only sends to inet addresses",
+            )
+        })?;
+        for pkt in pkts {
+            self.network.send(self.local_addr, dst, hdr.ecn(), pkt);
+        }
+        Poll::Ready(Ok(pkts.len()))
+    }
+
+    fn poll_recv(
+        &self,
+        cx: &mut Context,
+        pkts: &mut Vec<BytesMut>,
+        hdrs: &mut [PacketHeader],
+    ) -> Poll<io::Result<usize>> {
+        let capacity = pkts.len().min(hdrs.len());
+        let mut inbox = self.inbox.0.lock().unwrap();
+        loop {
+            let now = Instant::now();
+            let mut rcvd = 0;
+            while rcvd < capacity {
+                match inbox.datagrams.peek() {
+                    Some(Reverse(datagram)) if datagram.deliver_at <= now => {}
+                    _ => break,
+                }
+                let Reverse(datagram) = inbox.datagrams.pop().unwrap();
+                let size = datagram.payload.len();
+                if size > pkts[rcvd].len() {
+                    continue;
+                }
+                pkts[rcvd][..size].copy_from_slice(&datagram.payload);
+                let pathway = Pathway::new(
+                    datagram.src.to_endpoint_addr(),
+                    self.local_addr.to_endpoint_addr(),
+                );
+                let link = Link::new(datagram.src, self.local_addr);
+                hdrs[rcvd] =
+                    PacketHeader::new(pathway.flip(), link.flip(), 64, datagram.ecn, size as u16);
+                rcvd += 1;
+            }
+            if rcvd > 0 {
+                return Poll::Ready(Ok(rcvd));
+            }
+
+            inbox.waker = Some(cx.waker().clone());
+            let Some(deliver_at) = inbox.datagrams.peek().map(|Reverse(d)| d.deliver_at) else {
+                return Poll::Pending;
+            };
+            let timer = inbox
+                .timer
+                .get_or_insert_with(|| Box::pin(tokio::time::sleep_until(deliver_at)));
+            if timer.deadline() != deliver_at {
+                timer.as_mut().reset(deliver_at);
+            }
+            if timer.as_mut().poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+        }
+    }
+}
+
+impl Drop for SimulatedInterface {
+    fn drop(&mut self) {
+        self.network.unbind(&self.local_addr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::poll_fn;
+
+    use super::*;
+
+    fn send(iface: &SimulatedInterface, dst: SocketAddr, payloads: &[&[u8]]) {
+        let src = iface.local_addr;
+        let pathway = Pathway::new(src.to_endpoint_addr(), dst.to_endpoint_addr());
+        let hdr = PacketHeader::new(pathway, Link::new(src, dst), 64, None, 0);
+        let pkts = payloads
+            .iter()
+            .map(|p| io::IoSlice::new(p))
+            .collect::<Vec<_>>();
+        let cx = &mut Context::from_waker(futures::task::noop_waker_ref());
+        assert!(matches!(iface.poll_send(cx, &pkts, hdr), Poll::Ready(Ok(n)) if n == pkts.len()));
+    }
+
+    async fn recv(iface: &SimulatedInterface) -> Vec<(Vec<u8>, SocketAddr)> {
+        let mut pkts = vec![BytesMut::zeroed(MAX_SEGMENT_SIZE); MAX_SEGMENTS];
+        let mut hdrs = vec![PacketHeader::empty(); MAX_SEGMENTS];
+        let rcvd = poll_fn(|cx| iface.poll_recv(cx, &mut pkts, &mut hdrs))
+            .await
+            .unwrap();
+        (0..rcvd)
+            .map(|i| {
+                let size = hdrs[i].seg_size() as usize;
+                let src = hdrs[i].link().dst().try_into().unwrap();
+                (pkts[i][..size].to_vec(), src)
+            })
+            .collect()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn latency_and_bandwidth() {
+        let network = SimulatedNetwork::new();
+        network.set_default_link(LinkConfig {
+            latency: Duration::from_millis(50),
+            bandwidth: Some(100_000),
+            ..Default::default()
+        });
+        let a = network.bind("inet://127.0.0.1/alloc".into()).unwrap();
+        let b = network.bind("inet://127.0.0.1/alloc".into()).unwrap();
+
+        let start = Instant::now();
+        // 1000 bytes take 10ms to be sent
+        send(&a, b.local_addr(), &[&[1; 1000], &[2; 1000]]);
+        assert_eq!(recv(&b).await, [(vec![1; 1000], a.local_addr())]);
+        assert_eq!(start.elapsed(), Duration::from_millis(60));
+        assert_eq!(recv(&b).await, [(vec![2; 1000], a.local_addr())]);
+        assert_eq!(start.elapsed(), Duration::from_millis(70));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn loss_and_mtu() {
+        let network = SimulatedNetwork::with_seed(1);
+        let a = network.bind("inet://127.0.0.1/alloc".into()).unwrap();
+        let b = network.bind("inet://127.0.0.1/alloc".into()).unwrap();
+
+        network.set_link(
+            Ipv4Addr::LOCALHOST.into(),
+            Ipv4Addr::LOCALHOST.into(),
+            LinkConfig {
+                mtu: 1028,
+                ..Default::default()
+            },
+        );
+        send(&a, b.local_addr(), &[&[1; 1001], &[2; 1000]]);
+        assert_eq!(recv(&b).await, [(vec![2; 1000], a.local_addr())]);
+
+        network.set_default_link(LinkConfig {
+            loss: 1.0,
+            ..Default::default()
+        });
+        network.set_link(
+            Ipv4Addr::LOCALHOST.into(),
+            Ipv4Addr::LOCALHOST.into(),
+            LinkConfig::default(),
+        );
+        send(&a, b.local_addr(), &[&[3; 10]]);
+        assert_eq!(recv(&b).await, [(vec![3; 10], a.local_addr())]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn duplication_and_reordering() {
+        let network = SimulatedNetwork::new();
+        let a = network.bind("inet://127.0.0.1/alloc".into()).unwrap();
+        let b = network.bind("inet://127.0.0.1/alloc".into()).unwrap();
+
+        network.set_default_link(LinkConfig {
+            duplication: 1.0,
+            ..Default::default()
+        });
+        send(&a, b.local_addr(), &[&[1]]);
+        assert_eq!(recv(&b).await.len(), 2);
+
+        network.set_default_link(LinkConfig {
+            reordering: 1.0,
+            ..Default::default()
+        });
+        send(&a, b.local_addr(), &[&[1]]);
+        network.set_default_link(LinkConfig::default());
+        send(&a, b.local_addr(), &[&[2]]);
+        assert_eq!(recv(&b).await, [(vec![2], a.local_addr())]);
+        assert_eq!(recv(&b).await, [(vec![1], a.local_addr())]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn nat_rebinding() {
+        let network = SimulatedNetwork::new();
+        let a = network.bind("inet://0.0.0.0/alloc".into()).unwrap();
+        let b = network.bind("inet://127.0.0.1/alloc".into()).unwrap();
+        let a_addr = SocketAddr::new(Ipv4Addr::LOCALHOST.into(), a.local_addr().port());
+
+        send(&a, b.local_addr(), &[&[1]]);
+        assert_eq!(recv(&b).await, [(vec![1], a_addr)]);
+
+        let new_addr = network.rebind(a.local_addr()).unwrap();
+        assert_ne!(new_addr, a_addr);
+        send(&a, b.local_addr(), &[&[2]]);
+        assert_eq!(recv(&b).await, [(vec![2], new_addr)]);
+
+        // the old mapping is gone
+        send(&b, a_addr, &[&[3]]);
+        send(&b, new_addr, &[&[4]]);
+        assert_eq!(recv(&a).await, [(vec![4], b.local_addr())]);
+    }
+}
@@ -21,6 +21,7 @@ mod send {
 
     #[derive(Debug)]
     pub(super) struct Sender {
+        capacity: usize,
         sndbuf: SendBuf,
         writable_waker: Option<Waker>,
         flush_waker: Option<Waker>,
@@ -61,6 +62,13 @@ mod send {
             self.tx_wakers.wake_all_by(Signals::TRANSPORT);
             self.sndbuf.may_loss_data(&crypto_frame.range())
         }
+
+        fn reset(&mut self) {
+            self.sndbuf = SendBuf::with_capacity(self.capacity);
+            if let Some(waker) = self.writable_waker.take() {
+                waker.wake();
+            }
+        }
     }
 
     impl Sender {
@@ -162,10 +170,19 @@ mod send {
         pub fn may_loss_data(&self, crypto_frame: &CryptoFrame) {
             self.0.lock().unwrap().may_loss_data(crypto_frame)
         }
+
+        /// Discard all the crypto data written, the subsequent data will be sent from offset 0.
+        ///
+        /// It is used when the client starts a new connection attempt, the crypto frames sent
+        /// before must have been forgotten, they will never be acknowledged or lost.
+        pub fn reset(&self) {
+            self.0.lock().unwrap().reset()
+        }
     }
 
     pub(super) fn create(capacity: usize, tx_wakers: ArcSendWakers) -> ArcSender {
         Arc::new(Mutex::new(Sender {
+            capacity,
             sndbuf: SendBuf::with_capacity(capacity),
             writable_waker: None,
             flush_waker: None,
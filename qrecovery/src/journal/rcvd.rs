@@ -4,8 +4,8 @@ use std::{
 };
 
 use qbase::{
-    frame::AckFrame,
-    net::tx::Signals,
+    frame::{AckFrame, EcnCounts},
+    net::{route::Ecn, tx::Signals},
     packet::{InvalidPacketNumber, PacketNumber},
     util::IndexDeque,
     varint::{VARINT_MAX, VarInt},
@@ -69,6 +69,8 @@ struct RcvdJournal {
     max_ack_delay: Option<Duration>,
     packet_include_ack: HashSet<u64>,
     earliest_not_ack_time: Option<(u64, Instant)>,
+    // the counts of the ECN codepoints of received packets, reported in ACK frames
+    ecn_counts: EcnCounts,
 }
 
 impl RcvdJournal {
@@ -78,6 +80,7 @@ impl RcvdJournal {
             max_ack_delay,
             packet_include_ack: HashSet::new(),
             earliest_not_ack_time: None,
+            ecn_counts: EcnCounts::default(),
         }
     }
 
@@ -101,7 +104,7 @@ impl RcvdJournal {
         }
     }
 
-    fn on_rcvd_pn(&mut self, pn: u64, is_ack_eliciting: bool, pto: Duration) {
+    fn on_rcvd_pn(&mut self, pn: u64, is_ack_eliciting: bool, ecn: Option<Ecn>, pto: Duration) {
         let now = tokio::time::Instant::now();
         let ack_time = if is_ack_eliciting {
             Some(now + self.max_ack_delay.unwrap_or_default())
@@ -109,16 +112,31 @@ impl RcvdJournal {
             None
         };
         let expire_time = now + pto * 3;
-        if let Some(record) = self.queue.get_mut(pn) {
-            assert!(matches!(record, State::Empty));
-            *record = State::PacketReceived(now, ack_time, expire_time);
+        // A duplicated packet may be decoded again before the first copy is registered, and a
+        // late packet may be rotated out while it is being decrypted.
+        if pn < self.queue.offset() {
+            tracing::debug!("   Cause by: packet {pn} has been rotated out before registered");
         } else {
-            self.queue
-                .insert(pn, State::PacketReceived(now, ack_time, expire_time))
-                .expect("packet number never exceed limit");
+            match self.queue.get_mut(pn) {
+                Some(record @ State::Empty) => {
+                    *record = State::PacketReceived(now, ack_time, expire_time)
+                }
+                Some(_) => {
+                    tracing::debug!("   Cause by: packet {pn} has already been registered");
+                    return;
+                }
+                None => {
+                    self.queue
+                        .insert(pn, State::PacketReceived(now, ack_time, expire_time))
+                        .expect("packet number never exceed limit");
+                }
+            }
+            if self.earliest_not_ack_time.is_none() {
+                self.earliest_not_ack_time = Some((pn, now));
+            }
         }
-        if self.earliest_not_ack_time.is_none() {
-            self.earliest_not_ack_time = Some((pn, now));
+        if let Some(ecn) = ecn {
+            self.ecn_counts.increase(ecn);
         }
     }
 
@@ -180,9 +198,16 @@ impl RcvdJournal {
         first_range = first_range.saturating_sub(1);
 
         let first_range = VarInt::from(first_range);
-        // Frame type + Largest Acknowledged + First Ack Range + Ack Range Count
-        let min_len =
-            1 + largest.encoding_size() + delay.encoding_size() + first_range.encoding_size() + 1;
+        // rfc9000 13.4.1
+        // The ECN counts are reported once any packet with ECN codepoint is received
+        let ecn = (!self.ecn_counts.is_zero()).then_some(self.ecn_counts);
+        // Frame type + Largest Acknowledged + First Ack Range + Ack Range Count + ECN Counts
+        let min_len = 1
+            + largest.encoding_size()
+            + delay.encoding_size()
+            + first_range.encoding_size()
+            + 1
+            + ecn.map_or(0, |ecn| ecn.encoding_size());
         if capacity < min_len {
             return Err(Signals::CONGESTION);
         }
@@ -257,7 +282,7 @@ impl RcvdJournal {
                 self.earliest_not_ack_time = None;
             }
         }
-        Ok(AckFrame::new(largest, delay, first_range, ranges, None))
+        Ok(AckFrame::new(largest, delay, first_range, ranges, ecn))
     }
 
     fn trigger_ack_frame(&self) -> Option<(u64, Instant)> {
@@ -325,13 +350,13 @@ impl ArcRcvdJournal {
     /// Register the packet has been recieved.
     ///
     /// The registered packet must be valid, successfully decrypted, and the frames in it must be
-    /// valid.
+    /// valid. The `ecn` codepoint of the packet is counted and reported in the ack frames.
     // 当包号合法，且包被完全解密，且包中的帧都正确之后，记录该包已经收到。
-    pub fn register_pn(&self, pn: u64, is_ack_eliciting: bool, pto: Duration) {
+    pub fn register_pn(&self, pn: u64, is_ack_eliciting: bool, ecn: Option<Ecn>, pto: Duration) {
         self.inner
             .write()
             .unwrap()
-            .on_rcvd_pn(pn, is_ack_eliciting, pto);
+            .on_rcvd_pn(pn, is_ack_eliciting, ecn, pto);
     }
 
     /// Generate an ack frame which ack the received frames until `largest`.
@@ -372,7 +397,7 @@ mod tests {
         assert_eq!(records.inner.read().unwrap().queue.len(), 0);
 
         let pto = Duration::from_millis(100);
-        records.register_pn(1, true, pto);
+        records.register_pn(1, true, None, pto);
 
         assert_eq!(records.inner.read().unwrap().queue.len(), 2);
         assert_eq!(
@@ -438,6 +463,7 @@ mod tests {
             max_ack_delay: None,
             packet_include_ack: Default::default(),
             earliest_not_ack_time: None,
+            ecn_counts: Default::default(),
         };
 
         let ack = rcvd_jornal
@@ -452,4 +478,55 @@ mod tests {
         );
         assert_eq!(ack.first_range(), 2)
     }
+
+    #[test]
+    fn report_ecn_counts() {
+        let records = ArcRcvdJournal::with_capacity(16, None);
+        let pto = Duration::from_millis(100);
+        records.register_pn(0, true, None, pto);
+        let ack_frame = records.gen_ack_frame_util(0, 0, Instant::now(), 1200);
+        assert_eq!(ack_frame.unwrap().ecn(), None);
+
+        records.register_pn(1, true, Some(Ecn::Ect0), pto);
+        records.register_pn(2, true, Some(Ecn::Ect0), pto);
+        records.register_pn(3, true, Some(Ecn::Ce), pto);
+        records.register_pn(4, true, Some(Ecn::NotEct), pto);
+        let ack_frame = records.gen_ack_frame_util(1, 4, Instant::now(), 1200);
+        assert_eq!(
+            ack_frame.unwrap().ecn(),
+            Some(EcnCounts::new(
+                VarInt::from_u32(2),
+                VarInt::from_u32(0),
+                VarInt::from_u32(1)
+            ))
+        );
+    }
+
+    #[test]
+    fn register_duplicated_and_rotated_pn() {
+        let records = ArcRcvdJournal::with_capacity(16, None);
+        let pto = Duration::from_millis(100);
+        // Both copies of packet 1 are decoded before any of them is registered
+        assert_eq!(records.decode_pn(PacketNumber::encode(1, 0)), Ok(1));
+        assert_eq!(records.decode_pn(PacketNumber::encode(1, 0)), Ok(1));
+        records.register_pn(1, true, Some(Ecn::Ect0), pto);
+        records.register_pn(1, true, Some(Ecn::Ect0), pto);
+
+        // Packet 0 is rotated out while it is being decrypted
+        assert_eq!(records.decode_pn(PacketNumber::encode(0, 0)), Ok(0));
+        let ack_frame = AckFrame::new(0_u32.into(), 100_u32.into(), 0_u32.into(), vec![], None);
+        records.on_rcvd_ack(&ack_frame);
+        assert_eq!(records.inner.read().unwrap().queue.offset(), 1);
+        records.register_pn(0, true, Some(Ecn::Ect0), pto);
+
+        let ack_frame = records.gen_ack_frame_util(2, 1, Instant::now(), 1200);
+        assert_eq!(
+            ack_frame.unwrap().ecn(),
+            Some(EcnCounts::new(
+                VarInt::from_u32(2),
+                VarInt::from_u32(0),
+                VarInt::from_u32(0)
+            ))
+        );
+    }
 }
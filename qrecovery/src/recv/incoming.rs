@@ -64,6 +64,21 @@ where
         Ok((is_into_rcvd, fresh_data))
     }
 
+    /// Tell the peer to stop sending data with the given error code, as [`Reader::stop`] does.
+    ///
+    /// It's useful when the protocol above stops the streams handed over to the application, for
+    /// example the streams of a closed WebTransport session.
+    ///
+    /// [`Reader::stop`]: crate::recv::Reader::stop
+    pub fn stop(&self, error_code: u64) {
+        let mut recver = self.0.recver();
+        match recver.deref_mut() {
+            Ok(Recver::Recv(r)) => r.stop(error_code),
+            Ok(Recver::SizeKnown(r)) => r.stop(error_code),
+            _ => {}
+        }
+    }
+
     /// Receive a stream reset frame from peer.
     ///
     /// If all data sent by the peer has not been received, receiving a stream reset frame will cause
@@ -170,18 +170,25 @@ impl<TX> Drop for Reader<TX> {
         let inner = recver.deref_mut();
         if let Ok(receiving_state) = inner {
             match receiving_state {
-                Recver::Recv(r) if !r.is_stopped() => {
-                    tracing::warn!(
-                        "The receiving {} is not stopped with error before dropped!",
-                        r.stream_id(),
-                    );
+                Recver::Recv(r) => {
+                    if !r.is_stopped() {
+                        tracing::warn!(
+                            "The receiving {} is not stopped with error before dropped!",
+                            r.stream_id(),
+                        );
+                    }
+                    r.discard();
                 }
-                Recver::SizeKnown(r) if !r.is_stopped() => {
-                    tracing::warn!(
-                        "The receiving {} is not stopped with error before dropped!",
-                        r.stream_id()
-                    );
+                Recver::SizeKnown(r) => {
+                    if !r.is_stopped() {
+                        tracing::warn!(
+                            "The receiving {} is not stopped with error before dropped!",
+                            r.stream_id()
+                        );
+                    }
+                    r.discard();
                 }
+                Recver::DataRcvd(r) => r.discard(),
                 _ => (),
             }
         }
@@ -2,17 +2,19 @@ use std::{
     io,
     sync::{Arc, Mutex, MutexGuard},
     task::{Context, Poll, Waker},
+    time::Instant,
 };
 
 use bytes::{BufMut, Bytes};
 use qbase::{
     error::{Error, ErrorKind, QuicError},
+    flow::{RecvWindow, StreamsRecvFlow},
     frame::{
         GetFrameType, MaxStreamDataFrame, ResetStreamError, ResetStreamFrame, SendFrame,
         StopSendingFrame, StreamFrame,
     },
     sid::StreamId,
-    varint::{VARINT_MAX, VarInt},
+    varint::VarInt,
 };
 use qevent::quic::transport::{
     GranularStreamStates, StreamDataLocation, StreamDataMoved, StreamSide, StreamStateUpdated,
@@ -20,6 +22,60 @@ use qevent::quic::transport::{
 
 use super::rcvbuf;
 
+/// The receive flow control of a stream.
+///
+/// It tunes the receive window of the stream, and gives the credit of the connection back once the
+/// data is consumed: read by the application, or discarded because the stream is stopped or reset,
+/// or the [`Reader`] is dropped.
+///
+/// [`Reader`]: super::Reader
+#[derive(Debug, Clone)]
+struct RecvFlow {
+    window: RecvWindow,
+    streams_flow: StreamsRecvFlow,
+    released: u64,
+    discarding: bool,
+}
+
+impl RecvFlow {
+    fn new(streams_flow: StreamsRecvFlow, initial_window: u64) -> Self {
+        Self {
+            window: streams_flow.window(initial_window),
+            streams_flow,
+            released: 0,
+            discarding: false,
+        }
+    }
+
+    fn release(&mut self, consumed: u64) {
+        if consumed > self.released {
+            self.streams_flow.on_data_consumed(consumed - self.released);
+            self.released = consumed;
+        }
+    }
+
+    /// Called when the data is read by the application, `nread` is the offset read.
+    fn on_read(&mut self, nread: u64) {
+        if !self.discarding {
+            self.release(nread);
+        }
+    }
+
+    /// Called when the data is received, `largest` is the largest offset received.
+    fn on_rcvd(&mut self, largest: u64) {
+        if self.discarding {
+            self.release(largest);
+        }
+    }
+
+    /// The data received will never be read, all the data received, and to be received, are
+    /// released immediately.
+    fn discard(&mut self, largest: u64) {
+        self.discarding = true;
+        self.release(largest);
+    }
+}
+
 #[derive(Debug)]
 pub(super) struct Recv<TX> {
     stream_id: StreamId,
@@ -29,6 +85,7 @@ pub(super) struct Recv<TX> {
     broker: TX,
     largest: u64,
     max_stream_data: u64,
+    flow: RecvFlow,
 }
 
 impl<TX> Recv<TX>
@@ -51,9 +108,13 @@ where
                 to: StreamDataLocation::Application,
             });
 
-            let threshold = 1_000_000;
-            if self.rcvbuf.nread() + threshold > self.max_stream_data {
-                let max_stream_data = (self.rcvbuf.nread() + threshold * 2).min(VARINT_MAX);
+            let nread = self.rcvbuf.nread();
+            self.flow.on_read(nread);
+            if let Some(max_stream_data) =
+                self.flow
+                    .window
+                    .update(nread, self.max_stream_data, Instant::now())
+            {
                 if max_stream_data > self.max_stream_data {
                     self.max_stream_data = max_stream_data;
                     self.broker.send_frame([MaxStreamDataFrame::new(
@@ -78,6 +139,7 @@ where
     pub(super) fn stop(&mut self, err_code: u64) {
         if self.stop_state.is_none() {
             self.stop_state = Some(err_code);
+            self.flow.discard(self.rcvbuf.largest_offset());
             self.broker.send_frame([StopSendingFrame::new(
                 self.stream_id,
                 VarInt::from_u64(err_code).expect("app error code must not exceed 2^62!"),
@@ -128,12 +190,18 @@ impl<TX: Clone> Recv<TX> {
             stop_state: self.stop_state.take(),
             broker: self.broker.clone(),
             read_waker: self.read_waker.take(),
+            flow: self.flow.clone(),
         })
     }
 }
 
 impl<TX> Recv<TX> {
-    pub(super) fn new(stream_id: StreamId, buf_size: u64, broker: TX) -> Self {
+    pub(super) fn new(
+        stream_id: StreamId,
+        buf_size: u64,
+        streams_flow: StreamsRecvFlow,
+        broker: TX,
+    ) -> Self {
         Self {
             stream_id,
             rcvbuf: rcvbuf::RecvBuf::default(),
@@ -142,6 +210,7 @@ impl<TX> Recv<TX> {
             broker,
             largest: 0,
             max_stream_data: buf_size,
+            flow: RecvFlow::new(streams_flow, buf_size),
         }
     }
 
@@ -187,6 +256,7 @@ impl<TX> Recv<TX> {
         if self.largest < data_end {
             self.largest = data_end;
         }
+        self.flow.on_rcvd(self.rcvbuf.largest_offset());
         if self.rcvbuf.is_readable() {
             if let Some(waker) = self.read_waker.take() {
                 waker.wake()
@@ -215,6 +285,7 @@ impl<TX> Recv<TX> {
                 ),
             ));
         }
+        self.flow.discard(final_size);
         self.wake_reader();
         log_reset_event(self.stream_id, GranularStreamStates::Receive);
         Ok((final_size - self.largest) as _)
@@ -224,6 +295,13 @@ impl<TX> Recv<TX> {
         self.stop_state.is_some()
     }
 
+    /// Called when the [`Reader`] is dropped, the data will never be read.
+    ///
+    /// [`Reader`]: super::Reader
+    pub(super) fn discard(&mut self) {
+        self.flow.discard(self.rcvbuf.largest_offset());
+    }
+
     pub(super) fn wake_reader(&mut self) {
         if let Some(waker) = self.read_waker.take() {
             waker.wake()
@@ -242,6 +320,7 @@ pub struct SizeKnown<TX> {
     stop_state: Option<u64>,
     broker: TX,
     final_size: u64,
+    flow: RecvFlow,
 }
 
 impl<TX> SizeKnown<TX> {
@@ -298,6 +377,7 @@ impl<TX> SizeKnown<TX> {
             },
             fresh_data
         );
+        self.flow.on_rcvd(self.rcvbuf.largest_offset());
         if self.rcvbuf.is_readable() {
             if let Some(waker) = self.read_waker.take() {
                 waker.wake()
@@ -336,6 +416,7 @@ impl<TX> SizeKnown<TX> {
                 from: StreamDataLocation::Transport,
                 to: StreamDataLocation::Application,
             });
+            self.flow.on_read(self.rcvbuf.nread());
             Poll::Ready(Ok(()))
         } else {
             self.read_waker = Some(cx.waker().clone());
@@ -360,6 +441,7 @@ impl<TX> SizeKnown<TX> {
                 ),
             ));
         }
+        self.flow.discard(final_size);
         self.wake_reader();
         log_reset_event(self.stream_id, GranularStreamStates::SizeKnown);
         Ok(())
@@ -369,6 +451,13 @@ impl<TX> SizeKnown<TX> {
         self.stop_state.is_some()
     }
 
+    /// Called when the [`Reader`] is dropped, the data will never be read.
+    ///
+    /// [`Reader`]: super::Reader
+    pub(super) fn discard(&mut self) {
+        self.flow.discard(self.rcvbuf.largest_offset());
+    }
+
     pub(super) fn wake_reader(&mut self) {
         if let Some(waker) = self.read_waker.take() {
             waker.wake()
@@ -392,6 +481,7 @@ where
         DataRcvd {
             stream_id: self.stream_id,
             rcvbuf: std::mem::take(&mut self.rcvbuf),
+            flow: self.flow.clone(),
         }
     }
 }
@@ -405,6 +495,7 @@ where
     pub(super) fn stop(&mut self, err_code: u64) {
         if self.stop_state.is_none() {
             self.stop_state = Some(err_code);
+            self.flow.discard(self.rcvbuf.largest_offset());
             self.broker.send_frame([StopSendingFrame::new(
                 self.stream_id,
                 VarInt::from_u64(err_code).expect("app error code must not exceed 2^62!"),
@@ -422,6 +513,7 @@ where
 pub struct DataRcvd {
     stream_id: StreamId,
     rcvbuf: rcvbuf::RecvBuf,
+    flow: RecvFlow,
 }
 
 impl DataRcvd {
@@ -447,6 +539,14 @@ impl DataRcvd {
             from: StreamDataLocation::Transport,
             to: StreamDataLocation::Application,
         });
+        self.flow.on_read(self.rcvbuf.nread());
+    }
+
+    /// Called when the [`Reader`] is dropped, the data will never be read.
+    ///
+    /// [`Reader`]: super::Reader
+    pub(super) fn discard(&mut self) {
+        self.flow.discard(self.rcvbuf.largest_offset());
     }
 
     pub(super) fn is_all_read(&self) -> bool {
@@ -491,8 +591,13 @@ pub(super) enum Recver<TX> {
 }
 
 impl<TX> Recver<TX> {
-    pub(super) fn new(stream_id: StreamId, buf_size: u64, frames_tx: TX) -> Self {
-        Self::Recv(Recv::new(stream_id, buf_size, frames_tx))
+    pub(super) fn new(
+        stream_id: StreamId,
+        buf_size: u64,
+        streams_flow: StreamsRecvFlow,
+        frames_tx: TX,
+    ) -> Self {
+        Self::Recv(Recv::new(stream_id, buf_size, streams_flow, frames_tx))
     }
 }
 
@@ -515,15 +620,104 @@ where
     TX: SendFrame<StopSendingFrame> + SendFrame<MaxStreamDataFrame> + Clone + Send + 'static,
 {
     #[doc(hidden)]
-    pub(crate) fn new(stream_id: StreamId, buf_size: u64, frames_tx: TX) -> Self {
+    pub(crate) fn new(
+        stream_id: StreamId,
+        buf_size: u64,
+        streams_flow: StreamsRecvFlow,
+        frames_tx: TX,
+    ) -> Self {
         ArcRecver(Arc::new(Mutex::new(Ok(Recver::new(
-            stream_id, buf_size, frames_tx,
+            stream_id,
+            buf_size,
+            streams_flow,
+            frames_tx,
         )))))
     }
 }
 
 impl<TX> ArcRecver<TX> {
-    pub(super) fn recver(&self) -> MutexGuard<Result<Recver<TX>, Error>> {
+    pub(super) fn recver(&self) -> MutexGuard<'_, Result<Recver<TX>, Error>> {
         self.0.lock().unwrap()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use qbase::{
+        flow::{ArcRecvController, RecvWindowLimits},
+        frame::MaxDataFrame,
+        sid::{Dir, Role},
+    };
+    use tokio::io::AsyncReadExt;
+
+    use super::*;
+    use crate::recv::{Incoming, Reader};
+
+    /// Records the limits of the MAX_DATA frames sent.
+    #[derive(Debug, Clone, Default)]
+    struct MockFrames(Arc<Mutex<Vec<u64>>>);
+
+    impl SendFrame<MaxDataFrame> for MockFrames {
+        fn send_frame<I: IntoIterator<Item = MaxDataFrame>>(&self, iter: I) {
+            let mut max_data = self.0.lock().unwrap();
+            max_data.extend(iter.into_iter().map(|frame| frame.max_data()));
+        }
+    }
+
+    impl SendFrame<MaxStreamDataFrame> for MockFrames {
+        fn send_frame<I: IntoIterator<Item = MaxStreamDataFrame>>(&self, _iter: I) {}
+    }
+
+    impl SendFrame<StopSendingFrame> for MockFrames {
+        fn send_frame<I: IntoIterator<Item = StopSendingFrame>>(&self, _iter: I) {}
+    }
+
+    #[tokio::test]
+    async fn test_release_credit() {
+        let frames = MockFrames::default();
+        // the connection window never grows, to make the limits predictable
+        let limits = RecvWindowLimits {
+            connection: 100,
+            stream: 100,
+        };
+        let controller = ArcRecvController::new(100, limits, frames.clone());
+        let new_stream = |id| {
+            let sid = StreamId::new(Role::Server, Dir::Uni, id);
+            let recver = ArcRecver::new(sid, 100, controller.streams_flow(), frames.clone());
+            (sid, Incoming::new(recver.clone()), Reader::new(recver))
+        };
+        let recv_data = |incoming: &Incoming<MockFrames>, sid, offset, len: usize| {
+            let frame = StreamFrame::new(sid, offset, len);
+            let (_, fresh_data) = incoming
+                .recv_data(&frame, Bytes::from(vec![0; len]))
+                .unwrap();
+            controller
+                .on_new_rcvd(frame.frame_type(), fresh_data)
+                .unwrap();
+        };
+
+        // the credit is given back once the data is read
+        let (s0, incoming0, mut reader0) = new_stream(0);
+        recv_data(&incoming0, s0, 0, 60);
+        assert!(frames.0.lock().unwrap().is_empty());
+        let mut buf = [0; 60];
+        reader0.read_exact(&mut buf).await.unwrap();
+        assert_eq!(*frames.0.lock().unwrap(), [160]);
+
+        // and the data never read is given back once the stream is reset
+        let (s1, incoming1, _reader1) = new_stream(1);
+        recv_data(&incoming1, s1, 0, 30);
+        let reset = ResetStreamFrame::new(s1, VarInt::from_u32(0), VarInt::from_u32(50));
+        let sync_fresh_data = incoming1.recv_reset(&reset).unwrap();
+        controller
+            .on_new_rcvd(reset.frame_type(), sync_fresh_data)
+            .unwrap();
+        assert_eq!(*frames.0.lock().unwrap(), [160, 210]);
+
+        // or the reader is dropped
+        let (s2, incoming2, reader2) = new_stream(2);
+        recv_data(&incoming2, s2, 0, 60);
+        drop(reader2);
+        assert_eq!(*frames.0.lock().unwrap(), [160, 210, 270]);
+    }
+}
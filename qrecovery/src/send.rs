@@ -1,10 +1,12 @@
 //! Types for sending data on a Stream.
 mod outgoing;
+mod priority;
 mod sender;
 mod sndbuf;
 mod writer;
 
 pub use outgoing::Outgoing;
+pub use priority::Priority;
 pub use sender::ArcSender;
 pub use sndbuf::SendBuf;
 pub use writer::Writer;
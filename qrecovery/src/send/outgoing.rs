@@ -3,7 +3,7 @@ use std::ops::DerefMut;
 use bytes::BufMut;
 use qbase::{
     error::Error as QuicError,
-    frame::{ResetStreamError, StreamFrame},
+    frame::{ResetStreamError, ResetStreamFrame, SendFrame, StreamFrame},
     net::tx::Signals,
     packet::MarshalDataFrame,
     sid::StreamId,
@@ -12,7 +12,10 @@ use qbase::{
 };
 use qevent::quic::transport::{GranularStreamStates, StreamSide, StreamStateUpdated};
 
-use super::sender::{ArcSender, Sender, SendingSender};
+use super::{
+    Priority,
+    sender::{ArcSender, Sender, SendingSender},
+};
 
 /// An struct for protocol layer to manage the sending part of a stream.
 #[derive(Debug, Clone)]
@@ -90,12 +93,47 @@ impl<TX: Clone> Outgoing<TX> {
     }
 }
 
+impl<TX> Outgoing<TX>
+where
+    TX: SendFrame<ResetStreamFrame>,
+{
+    /// Cancel the stream with the given error code, as [`Writer::cancel`] does.
+    ///
+    /// It's useful when the protocol above resets the streams handed over to the application, for
+    /// example the streams of a closed WebTransport session.
+    ///
+    /// [`Writer::cancel`]: super::Writer::cancel
+    pub fn cancel(&self, err_code: u64) {
+        let mut sender = self.0.sender();
+        if let Ok(sending_state) = sender.deref_mut() {
+            match sending_state {
+                Sender::Ready(s) => *sending_state = Sender::ResetSent(s.cancel(err_code)),
+                Sender::Sending(s) => *sending_state = Sender::ResetSent(s.cancel(err_code)),
+                Sender::DataSent(s) => *sending_state = Sender::ResetSent(s.cancel(err_code)),
+                _ => (),
+            }
+        }
+    }
+}
+
 impl<TX> Outgoing<TX> {
     /// Create a new instance of [`Outgoing`]
     pub fn new(sender: ArcSender<TX>) -> Self {
         Self(sender)
     }
 
+    /// The priority of the stream, see [`Priority`] for how it schedules the streams.
+    pub fn priority(&self) -> Priority {
+        self.0.priority().load()
+    }
+
+    /// Change the priority of the stream, as [`Writer::set_priority`] does.
+    ///
+    /// [`Writer::set_priority`]: super::Writer::set_priority
+    pub fn set_priority(&self, priority: Priority) {
+        self.0.priority().store(priority);
+    }
+
     /// Update the sending window to `max_data_size`
     ///
     /// Callded when the  [`MAX_STREAM_DATA frame`] belonging to the stream is received.
@@ -0,0 +1,126 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicU8, Ordering},
+};
+
+/// The priority of the sending part of a stream, in the style of the
+/// [Extensible Prioritization Scheme](https://www.rfc-editor.org/rfc/rfc9218.html).
+///
+/// The data of the streams with lower urgency is always sent first, including the lost data to be
+/// retransmitted. Among the streams with the same urgency, the non-incremental streams are sent one
+/// after another in the order of their stream IDs, then the incremental streams share the rest of
+/// the bandwidth in turn.
+///
+/// Unlike the default priority of HTTP, streams are incremental by default, so that all streams
+/// share the bandwidth fairly if the application never sets the priorities.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Priority {
+    urgency: u8,
+    incremental: bool,
+}
+
+impl Priority {
+    /// The urgency of the least urgent streams, the most urgent ones have urgency 0.
+    pub const MAX_URGENCY: u8 = 7;
+    /// The urgency of the streams whose priority is not set.
+    pub const DEFAULT_URGENCY: u8 = 3;
+
+    const INCREMENTAL: u8 = 0x08;
+
+    /// Create a new priority.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `urgency` is greater than [`Priority::MAX_URGENCY`].
+    pub fn new(urgency: u8, incremental: bool) -> Self {
+        assert!(
+            urgency <= Self::MAX_URGENCY,
+            "urgency {urgency} out of range 0..={}",
+            Self::MAX_URGENCY
+        );
+        Self {
+            urgency,
+            incremental,
+        }
+    }
+
+    /// The urgency of the stream, lower is more urgent.
+    pub fn urgency(&self) -> u8 {
+        self.urgency
+    }
+
+    /// Whether the stream shares the bandwidth with the other streams of the same urgency.
+    pub fn is_incremental(&self) -> bool {
+        self.incremental
+    }
+
+    fn to_bits(self) -> u8 {
+        if self.incremental {
+            self.urgency | Self::INCREMENTAL
+        } else {
+            self.urgency
+        }
+    }
+
+    fn from_bits(bits: u8) -> Self {
+        Self {
+            urgency: bits & !Self::INCREMENTAL,
+            incremental: bits & Self::INCREMENTAL != 0,
+        }
+    }
+}
+
+impl Default for Priority {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_URGENCY, true)
+    }
+}
+
+/// The priority shared by [`Writer`] and [`Outgoing`], so that the scheduler reads it without
+/// locking the sender.
+///
+/// [`Writer`]: super::Writer
+/// [`Outgoing`]: super::Outgoing
+#[derive(Debug, Clone)]
+pub(super) struct ArcPriority(Arc<AtomicU8>);
+
+impl Default for ArcPriority {
+    fn default() -> Self {
+        Self(Arc::new(AtomicU8::new(Priority::default().to_bits())))
+    }
+}
+
+impl ArcPriority {
+    pub(super) fn load(&self) -> Priority {
+        Priority::from_bits(self.0.load(Ordering::Relaxed))
+    }
+
+    pub(super) fn store(&self, priority: Priority) {
+        self.0.store(priority.to_bits(), Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_priority() {
+        let priority = ArcPriority::default();
+        assert_eq!(priority.load(), Priority::new(3, true));
+
+        for urgency in 0..=Priority::MAX_URGENCY {
+            for incremental in [false, true] {
+                priority.store(Priority::new(urgency, incremental));
+                assert_eq!(priority.load().urgency(), urgency);
+                assert_eq!(priority.load().is_incremental(), incremental);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_urgency_out_of_range() {
+        Priority::new(8, false);
+    }
+}
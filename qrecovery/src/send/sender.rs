@@ -20,7 +20,7 @@ use qevent::{
     },
 };
 
-use super::sndbuf::SendBuf;
+use super::{priority::ArcPriority, sndbuf::SendBuf};
 
 fn log_reset_event(sid: StreamId, from_state: GranularStreamStates) {
     qevent::event!(StreamStateUpdated {
@@ -195,6 +195,8 @@ where
         self.broker
             .send_frame([reset_stream_err.combine(self.stream_id)]);
         log_reset_event(self.stream_id, GranularStreamStates::Ready);
+        // the stream may be cancelled by the protocol layer, while the writer is blocked
+        self.wake_all();
         reset_stream_err
     }
 }
@@ -262,7 +264,7 @@ impl<TX> SendingSender<TX> {
         &mut self,
         predicate: P,
         flow_limit: usize,
-    ) -> Result<StreamData, Signals>
+    ) -> Result<StreamData<'_>, Signals>
     where
         P: Fn(u64) -> Option<usize>,
     {
@@ -391,6 +393,8 @@ where
         self.broker
             .send_frame([reset_stream_err.combine(self.stream_id)]);
         log_reset_event(self.stream_id, GranularStreamStates::Send);
+        // the stream may be cancelled by the protocol layer, while the writer is blocked
+        self.wake_all();
         reset_stream_err
     }
 }
@@ -419,7 +423,7 @@ impl<TX> DataSentSender<TX> {
         &mut self,
         predicate: P,
         flow_limit: usize,
-    ) -> Result<StreamData, Signals>
+    ) -> Result<StreamData<'_>, Signals>
     where
         P: Fn(u64) -> Option<usize>,
     {
@@ -525,6 +529,8 @@ where
         self.broker
             .send_frame([reset_stream_err.combine(self.stream_id)]);
         log_reset_event(self.stream_id, GranularStreamStates::DataSent);
+        // the stream may be cancelled by the protocol layer, while the writer is blocked
+        self.wake_all();
         reset_stream_err
     }
 }
@@ -556,7 +562,7 @@ impl<TX> Sender<TX> {
 /// [`Outgoing`]: super::Outgoing
 /// [`Writer`]: super::Writer
 #[derive(Debug, Clone)]
-pub struct ArcSender<TX>(Arc<Mutex<Result<Sender<TX>, Error>>>);
+pub struct ArcSender<TX>(Arc<Mutex<Result<Sender<TX>, Error>>>, ArcPriority);
 
 impl<TX> ArcSender<TX> {
     #[doc(hidden)]
@@ -566,9 +572,12 @@ impl<TX> ArcSender<TX> {
         broker: TX,
         tx_wakers: ArcSendWakers,
     ) -> Self {
-        ArcSender(Arc::new(Mutex::new(Ok(Sender::new(
-            stream_id, buf_size, broker, tx_wakers,
-        )))))
+        ArcSender(
+            Arc::new(Mutex::new(Ok(Sender::new(
+                stream_id, buf_size, broker, tx_wakers,
+            )))),
+            ArcPriority::default(),
+        )
     }
 }
 
@@ -582,9 +591,13 @@ impl<TX> ArcSender<TX> {
         }
     }
 
-    pub(super) fn sender(&self) -> MutexGuard<Result<Sender<TX>, Error>> {
+    pub(super) fn sender(&self) -> MutexGuard<'_, Result<Sender<TX>, Error>> {
         self.0.lock().unwrap()
     }
+
+    pub(super) fn priority(&self) -> &ArcPriority {
+        &self.1
+    }
 }
 
 #[cfg(test)]
@@ -8,7 +8,10 @@ use std::{
 use qbase::frame::{ResetStreamFrame, SendFrame};
 use tokio::io::AsyncWrite;
 
-use super::sender::{ArcSender, Sender};
+use super::{
+    Priority,
+    sender::{ArcSender, Sender},
+};
 
 /// The writer part of a QUIC stream.
 ///
@@ -78,6 +81,26 @@ impl<TX> Writer<TX> {
             tracing_span: tracing::Span::current(),
         }
     }
+
+    /// Set the priority of the stream, the streams with lower `urgency` (0 to 7) are sent first.
+    ///
+    /// Among the streams with the same urgency, the `incremental` streams share the bandwidth in
+    /// turn, and the others are sent one after another in the order of their stream IDs. See
+    /// [`Priority`] for more details.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `urgency` is greater than [`Priority::MAX_URGENCY`].
+    pub fn set_priority(&self, urgency: u8, incremental: bool) {
+        self.inner
+            .priority()
+            .store(Priority::new(urgency, incremental));
+    }
+
+    /// The priority of the stream.
+    pub fn priority(&self) -> Priority {
+        self.inner.priority().load()
+    }
 }
 
 impl<TX> Writer<TX>
@@ -30,6 +30,7 @@ use derive_more::Deref;
 pub use listener::{AcceptBiStream, AcceptUniStream};
 use qbase::{
     error::Error,
+    flow::StreamsRecvFlow,
     frame::{ReceiveFrame, SendFrame, StreamCtlFrame, StreamFrame},
     net::tx::ArcSendWakers,
     param::StoreParameter,
@@ -81,10 +82,14 @@ where
     /// Creates a new instance of [`DataStreams`].
     ///
     /// The `ctrl_frames` is the frame sender, read [`raw::DataStreams`] for more details.
+    ///
+    /// The `recv_flow` tunes the receive windows of the streams, and gives the credit back to the
+    /// connection once the data is read, see [`StreamsRecvFlow`].
     pub fn new(
         role: Role,
         local_params: &impl StoreParameter,
         ctrl: Box<dyn ControlStreamsConcurrency>,
+        recv_flow: StreamsRecvFlow,
         ctrl_frames: TX,
         tx_wakers: ArcSendWakers,
     ) -> Self {
@@ -92,6 +97,7 @@ where
             role,
             local_params,
             ctrl,
+            recv_flow,
             ctrl_frames,
             tx_wakers,
         )))
@@ -99,7 +105,7 @@ where
 
     /// Create a bidirectional stream, see the method of the same name on `QuicConnection` for more.
     #[inline]
-    pub fn open_bi(&self, snd_wnd_size: u64) -> OpenBiStream<TX> {
+    pub fn open_bi(&self, snd_wnd_size: u64) -> OpenBiStream<'_, TX> {
         OpenBiStream {
             inner: self,
             snd_wnd_size,
@@ -108,7 +114,7 @@ where
 
     /// Create a unidirectional stream, see the method of the same name on `QuicConnection` for more.
     #[inline]
-    pub fn open_uni(&self, snd_wnd_size: u64) -> OpenUniStream<TX> {
+    pub fn open_uni(&self, snd_wnd_size: u64) -> OpenUniStream<'_, TX> {
         OpenUniStream {
             inner: self,
             snd_wnd_size,
@@ -117,13 +123,13 @@ where
 
     /// accept a bidirectional stream, see the method of the same name on `QuicConnection` for more.
     #[inline]
-    pub fn accept_bi(&self, snd_wnd_size: u64) -> AcceptBiStream<Ext<TX>> {
+    pub fn accept_bi(&self, snd_wnd_size: u64) -> AcceptBiStream<'_, Ext<TX>> {
         self.0.accept_bi(snd_wnd_size)
     }
 
     /// accept a unidirectional stream, see the method of the same name on `QuicConnection` for more.
     #[inline]
-    pub fn accept_uni(&self) -> AcceptUniStream<Ext<TX>> {
+    pub fn accept_uni(&self) -> AcceptUniStream<'_, Ext<TX>> {
         self.0.accept_uni()
     }
 }
@@ -3,6 +3,7 @@ use std::task::{Context, Poll, ready};
 use bytes::BufMut;
 use qbase::{
     error::{Error, ErrorKind, QuicError},
+    flow::StreamsRecvFlow,
     frame::{
         FrameType, GetFrameType, ReceiveFrame, ResetStreamFrame, STREAM_FRAME_MAX_ENCODING_SIZE,
         SendFrame, StreamCtlFrame, StreamFrame,
@@ -11,7 +12,7 @@ use qbase::{
     packet::MarshalDataFrame,
     param::{ParameterId, StoreParameter, StoreParameterExt},
     sid::{
-        ControlStreamsConcurrency, Dir, Role, StreamId, StreamIds,
+        ControlStreamsConcurrency, Dir, Role, StreamId, StreamIds, StreamsCount,
         remote_sid::{AcceptSid, ExceedLimitError},
     },
     varint::VarInt,
@@ -24,7 +25,7 @@ use super::{
 };
 use crate::{
     recv::{ArcRecver, Incoming, Reader},
-    send::{ArcSender, Outgoing, Writer},
+    send::{ArcSender, Outgoing, Priority, Writer},
 };
 
 /// Manage all streams in the connection, send and receive frames, handle frame loss, and acknowledge.
@@ -117,6 +118,8 @@ where
     local_bi_stream_rcvbuf_size: u64,
     // the receive buffer size for the accepted bidirectional stream created by peer
     remote_bi_stream_rcvbuf_size: u64,
+    // 所有流共享的接收流量控制，读取数据后归还连接级的额度
+    recv_flow: StreamsRecvFlow,
     // 所有流的待写端，要发送数据，就得向这些流索取
     output: ArcOutput<Ext<TX>>,
     // 所有流的待读端，收到了数据，交付给这些流
@@ -183,54 +186,89 @@ where
         // 该tokens是令牌桶算法的token，为了多条Stream的公平性，给每个流定期地发放tokens，不累积
         // 各流轮流按令牌桶算法发放的tokens来整理数据去发送
         const DEFAULT_TOKENS: usize = 4096;
-        let (sid, remain_tokens, fresh_bytes) = match &output.cursor {
-            // rev([..=sid]) + rev([sid+1..])
-            Some((sid, tokens)) if *tokens == 0 => try_load_data_into_once(
-                (output.outgoings.range(..=sid).rev())
-                    .chain(output.outgoings.range((Excluded(sid), Unbounded)).rev())
-                    .map(|(sid, outgoing)| (*sid, outgoing, DEFAULT_TOKENS)),
+        // 按urgency由高到低严格排序，只遍历存在流的urgency
+        let urgencies = (output.outgoings.values()).fold(0u8, |urgencies, (o, _)| {
+            urgencies | 1 << o.priority().urgency()
+        });
+        let mut signals = Signals::TRANSPORT;
+        for urgency in (0..=Priority::MAX_URGENCY).filter(|u| urgencies & 1 << u != 0) {
+            let at_level = |incremental: bool| {
+                move |(_, (outgoing, _)): &(&StreamId, &(Outgoing<Ext<TX>>, IOState))| {
+                    let priority = outgoing.priority();
+                    priority.urgency() == urgency && priority.is_incremental() == incremental
+                }
+            };
+
+            // 非incremental的流按sid顺序依次发送，前一个流没数据可发了，才轮到下一个流
+            match try_load_data_into_once(
+                (output.outgoings.iter().filter(at_level(false)))
+                    .map(|(sid, outgoing)| (*sid, outgoing, usize::MAX)),
                 packet,
                 flow_limit,
-            ),
-            // [sid] + rev([..sid]) + rev([sid+1..])
-            Some((sid, tokens)) => try_load_data_into_once(
-                Option::into_iter(
-                    output
-                        .outgoings
-                        .get(sid)
-                        .map(|outgoing| (*sid, outgoing, *tokens)),
-                )
-                .chain(
-                    (output.outgoings.range(..sid).rev())
-                        .chain(output.outgoings.range((Excluded(sid), Unbounded)).rev())
+            ) {
+                Ok((_sid, _remain_tokens, fresh_bytes)) => return Ok(fresh_bytes),
+                Err(s) => signals |= s,
+            }
+
+            // incremental的流轮流发送，cursor记录上次发送的流及其剩余的tokens
+            let outgoings = &output.outgoings;
+            let round_robin = match &output.cursor {
+                // rev([..sid]) + rev([sid..])
+                Some((sid, tokens)) if *tokens == 0 => try_load_data_into_once(
+                    (outgoings.range(..sid).rev())
+                        .chain(outgoings.range(sid..).rev())
+                        .filter(at_level(true))
                         .map(|(sid, outgoing)| (*sid, outgoing, DEFAULT_TOKENS)),
+                    packet,
+                    flow_limit,
                 ),
-                packet,
-                flow_limit,
-            ),
-            // rev([..])
-            None => try_load_data_into_once(
-                (output.outgoings.range(..).rev())
-                    .map(|(sid, outgoing)| (*sid, outgoing, DEFAULT_TOKENS)),
-                packet,
-                flow_limit,
-            ),
-        }?;
-
-        output.cursor = Some((sid, remain_tokens));
-        Ok(fresh_bytes)
+                // [sid] + rev([..sid]) + rev([sid+1..])
+                Some((sid, tokens)) => try_load_data_into_once(
+                    Option::into_iter(
+                        (outgoings.get_key_value(sid).filter(at_level(true)))
+                            .map(|(sid, outgoing)| (*sid, outgoing, *tokens)),
+                    )
+                    .chain(
+                        (outgoings.range(..sid).rev())
+                            .chain(outgoings.range((Excluded(sid), Unbounded)).rev())
+                            .filter(at_level(true))
+                            .map(|(sid, outgoing)| (*sid, outgoing, DEFAULT_TOKENS)),
+                    ),
+                    packet,
+                    flow_limit,
+                ),
+                // rev([..])
+                None => try_load_data_into_once(
+                    (outgoings.range(..).rev())
+                        .filter(at_level(true))
+                        .map(|(sid, outgoing)| (*sid, outgoing, DEFAULT_TOKENS)),
+                    packet,
+                    flow_limit,
+                ),
+            };
+            match round_robin {
+                Ok((sid, remain_tokens, fresh_bytes)) => {
+                    output.cursor = Some((sid, remain_tokens));
+                    return Ok(fresh_bytes);
+                }
+                Err(s) => signals |= s,
+            }
+        }
+        Err(signals)
     }
 
     /// Try to load data from streams into the packet.
     ///
-    /// # Fairness
+    /// # Priority
     ///
-    /// It's fair between streams.
+    /// The streams are scheduled by their [`Priority`]. The data of the streams with lower urgency,
+    /// either fresh or to be retransmitted, is always loaded first.
     ///
-    /// We have implemented a token bucket algorithm, and this method will read the data of each stream
-    /// sequentially.  Starting from the first stream, when a stream exhausts its tokens (default is 4096,
-    /// depending on the priority of the stream), or there is no data to send, the method will move to
-    /// the next stream, and so on.
+    /// Among the streams with the same urgency, the non-incremental streams are loaded one after
+    /// another in the order of their stream IDs. Then we have implemented a token bucket algorithm for
+    /// the incremental streams, this method will read the data of each stream in turn. When a stream
+    /// exhausts its tokens (4096 bytes), or there is no data to send, the method will move to the next
+    /// stream, and so on.
     ///
     /// # Flow control
     ///
@@ -512,6 +550,58 @@ where
         Ok(sync_fresh_data)
     }
 
+    /// Returns the number of the streams opened by `role` in the `dir` direction, and the maximum
+    /// number of them allowed.
+    pub fn streams_count(&self, role: Role, dir: Dir) -> StreamsCount {
+        if role == self.role {
+            self.stream_ids.local.streams_count(dir)
+        } else {
+            self.stream_ids.remote.streams_count(dir)
+        }
+    }
+
+    /// Change the priority of the stream `sid`, as [`Writer::set_priority`] does.
+    ///
+    /// It's useful when the priority is updated by the peer, for example the PRIORITY_UPDATE frame
+    /// of HTTP/3. The streams not opened yet or has sent all data are ignored.
+    pub fn set_priority(&self, sid: StreamId, priority: Priority) {
+        if let Some((outgoing, _s)) = self
+            .output
+            .streams()
+            .as_ref()
+            .ok()
+            .and_then(|set| set.get(&sid))
+        {
+            outgoing.set_priority(priority);
+        }
+    }
+
+    /// Reset the stream `sid` with the `error_code`, as [`Writer::cancel`] and [`Reader::stop`] do.
+    ///
+    /// It's useful when the protocol above resets the streams handed over to the application, for
+    /// example the streams of a closed WebTransport session. The streams not opened yet or closed
+    /// already are ignored.
+    pub fn cancel_stream(&self, sid: StreamId, error_code: u64) {
+        if let Some((outgoing, _s)) = self
+            .output
+            .streams()
+            .as_ref()
+            .ok()
+            .and_then(|set| set.get(&sid))
+        {
+            outgoing.cancel(error_code);
+        }
+        if let Some((incoming, _s)) = self
+            .input
+            .streams()
+            .as_ref()
+            .ok()
+            .and_then(|set| set.get(&sid))
+        {
+            incoming.stop(error_code);
+        }
+    }
+
     /// Called when a connection error occured.
     ///
     /// After the method called, read on [`Reader`] or write on [`Writer`] will return an error,
@@ -544,6 +634,7 @@ where
         role: Role,
         local_params: &impl StoreParameter,
         ctrl: Box<dyn ControlStreamsConcurrency>,
+        recv_flow: StreamsRecvFlow,
         ctrl_frames: TX,
         tx_wakers: ArcSendWakers,
     ) -> Self {
@@ -568,6 +659,7 @@ where
             uni_stream_rcvbuf_size,
             local_bi_stream_rcvbuf_size,
             remote_bi_stream_rcvbuf_size,
+            recv_flow,
             output: ArcOutput::new(),
             input: ArcInput::default(),
             listener: ArcListener::new(),
@@ -625,11 +717,11 @@ where
         }
     }
 
-    pub(super) fn accept_bi(&self, snd_buf_size: u64) -> AcceptBiStream<Ext<TX>> {
+    pub(super) fn accept_bi(&self, snd_buf_size: u64) -> AcceptBiStream<'_, Ext<TX>> {
         self.listener.accept_bi_stream(snd_buf_size)
     }
 
-    pub(super) fn accept_uni(&self) -> AcceptUniStream<Ext<TX>> {
+    pub(super) fn accept_uni(&self) -> AcceptUniStream<'_, Ext<TX>> {
         self.listener.accept_uni_stream()
     }
 
@@ -709,6 +801,196 @@ where
     }
 
     fn create_recver(&self, sid: StreamId, buf_size: u64) -> ArcRecver<Ext<TX>> {
-        ArcRecver::new(sid, buf_size, Ext(self.ctrl_frames.clone()))
+        ArcRecver::new(
+            sid,
+            buf_size,
+            self.recv_flow.clone(),
+            Ext(self.ctrl_frames.clone()),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::buf::UninitSlice;
+    use qbase::{
+        flow::{ArcRecvController, RecvWindowLimits},
+        frame::{MaxDataFrame, MaxStreamsFrame, io::WriteDataFrame},
+        param::ClientParameters,
+        sid::handy::ConsistentConcurrency,
+    };
+    use tokio::io::AsyncWriteExt;
+
+    use super::*;
+
+    #[derive(Debug, Default, Clone)]
+    struct MockCtrlFrames;
+
+    impl SendFrame<StreamCtlFrame> for MockCtrlFrames {
+        fn send_frame<I: IntoIterator<Item = StreamCtlFrame>>(&self, _iter: I) {}
+    }
+
+    impl SendFrame<MaxDataFrame> for MockCtrlFrames {
+        fn send_frame<I: IntoIterator<Item = MaxDataFrame>>(&self, _iter: I) {}
+    }
+
+    /// A packet records the stream frames loaded into it.
+    struct MockPacket {
+        buf: Vec<u8>,
+        capacity: usize,
+        frames: Vec<StreamFrame>,
+    }
+
+    impl MockPacket {
+        fn new(capacity: usize) -> Self {
+            Self {
+                buf: Vec::with_capacity(capacity),
+                capacity,
+                frames: vec![],
+            }
+        }
+    }
+
+    unsafe impl BufMut for MockPacket {
+        fn remaining_mut(&self) -> usize {
+            self.capacity - self.buf.len()
+        }
+
+        unsafe fn advance_mut(&mut self, cnt: usize) {
+            unsafe { self.buf.advance_mut(cnt) }
+        }
+
+        fn chunk_mut(&mut self) -> &mut UninitSlice {
+            self.buf.chunk_mut()
+        }
+    }
+
+    impl<'b> MarshalDataFrame<StreamFrame, (&'b [u8], &'b [u8])> for MockPacket {
+        fn dump_frame_with_data(
+            &mut self,
+            frame: StreamFrame,
+            data: (&'b [u8], &'b [u8]),
+        ) -> Option<StreamFrame> {
+            self.buf.put_data_frame(&frame, &data);
+            self.frames.push(frame);
+            Some(frame)
+        }
+    }
+
+    async fn open_streams(
+        streams: &super::super::DataStreams<MockCtrlFrames>,
+        count: u64,
+    ) -> Vec<(StreamId, Writer<Ext<MockCtrlFrames>>)> {
+        let max_streams = MaxStreamsFrame::with(Dir::Uni, VarInt::from_u64(count).unwrap());
+        streams
+            .recv_stream_control(&StreamCtlFrame::MaxStreams(max_streams))
+            .unwrap();
+        let mut writers = vec![];
+        for _ in 0..count {
+            let (sid, mut writer) = streams.open_uni(1 << 20).await.unwrap().unwrap();
+            writer.write_all(&[0; 8192]).await.unwrap();
+            writers.push((sid, writer));
+        }
+        writers
+    }
+
+    /// Load the data into `count` packets, returns the streams and the length of the data loaded.
+    fn load_data(streams: &DataStreams<MockCtrlFrames>, count: usize) -> Vec<(StreamId, usize)> {
+        (0..count)
+            .flat_map(|_| {
+                let mut packet = MockPacket::new(1200);
+                streams.try_load_data_into(&mut packet, usize::MAX).unwrap();
+                packet.frames
+            })
+            .map(|frame| (frame.stream_id(), frame.len()))
+            .collect()
+    }
+
+    /// Merge the data loaded consecutively from the same stream.
+    fn turns(loaded: Vec<(StreamId, usize)>) -> Vec<(StreamId, usize)> {
+        let mut turns: Vec<(StreamId, usize)> = vec![];
+        for (sid, len) in loaded {
+            match turns.last_mut() {
+                Some((last, total)) if *last == sid => *total += len,
+                _ => turns.push((sid, len)),
+            }
+        }
+        turns
+    }
+
+    fn new_streams() -> super::super::DataStreams<MockCtrlFrames> {
+        super::super::DataStreams::new(
+            Role::Client,
+            &ClientParameters::default(),
+            Box::new(ConsistentConcurrency::new(0, 0)),
+            ArcRecvController::new(0, RecvWindowLimits::default(), MockCtrlFrames).streams_flow(),
+            MockCtrlFrames,
+            ArcSendWakers::default(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_urgency() {
+        let streams = new_streams();
+        let writers = open_streams(&streams, 3).await;
+        let [(s0, w0), (s1, w1), (s2, _w2)] = &writers[..] else {
+            unreachable!()
+        };
+        w0.set_priority(7, true);
+        w1.set_priority(0, true);
+        // the least urgent stream is sent at last
+        let turns = turns(load_data(&streams, 21));
+        assert_eq!(turns, vec![(*s1, 8192), (*s2, 8192), (*s0, 8192)]);
+    }
+
+    #[tokio::test]
+    async fn test_incremental() {
+        let streams = new_streams();
+        let writers = open_streams(&streams, 3).await;
+        let [(s0, _w0), (s1, _w1), (s2, _w2)] = &writers[..] else {
+            unreachable!()
+        };
+        // the incremental streams share the bandwidth in turn, 4096 bytes each time
+        let turns = turns(load_data(&streams, 21));
+        assert_eq!(
+            turns,
+            vec![
+                (*s2, 4096),
+                (*s1, 4096),
+                (*s0, 4096),
+                (*s2, 4096),
+                (*s1, 4096),
+                (*s0, 4096)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sequential() {
+        let streams = new_streams();
+        let writers = open_streams(&streams, 3).await;
+        let [(s0, w0), (s1, w1), (s2, w2)] = &writers[..] else {
+            unreachable!()
+        };
+        for writer in [w0, w1, w2] {
+            writer.set_priority(Priority::DEFAULT_URGENCY, false);
+        }
+        streams.set_priority(*s0, Priority::default());
+        // the non-incremental streams are sent one by one in the order of their stream IDs, ahead
+        // of the incremental ones of the same urgency
+        let turns = turns(load_data(&streams, 21));
+        assert_eq!(turns, vec![(*s1, 8192), (*s2, 8192), (*s0, 8192)]);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_stream() {
+        let streams = new_streams();
+        let mut writers = open_streams(&streams, 2).await;
+        let (s0, s1) = (writers[0].0, writers[1].0);
+        streams.cancel_stream(s0, 0x170d7b68);
+        // the data of the cancelled stream is no longer sent, and the writer is broken
+        let turns = turns(load_data(&streams, 7));
+        assert_eq!(turns, vec![(s1, 8192)]);
+        assert!(writers[0].1.write_all(&[0; 8]).await.is_err());
     }
 }
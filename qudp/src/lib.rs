@@ -3,8 +3,12 @@ use std::{
     future::Future,
     io::{self, IoSlice, IoSliceMut},
     net::SocketAddr,
+    ops::Range,
     pin::Pin,
-    sync::{Arc, Mutex},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
     task::{Context, Poll, Wake, Waker, ready},
 };
 
@@ -12,6 +16,11 @@ use socket2::{Domain, Socket, Type};
 use tokio::io::Interest;
 const DEFAULT_TTL: libc::c_int = 64;
 pub const BATCH_SIZE: usize = 64;
+// The size of each buffer receiving datagrams coalesced by GRO
+const GRO_BUFFER_SIZE: usize = u16::MAX as usize;
+// The number of GRO buffers received at once, each of them holds dozens of datagrams
+const GRO_BATCH_SIZE: usize = 4;
+const ETHERNET_MTU: usize = 1500;
 cfg_if::cfg_if! {
     if #[cfg(unix)]{
         #[path = "unix.rs"]
@@ -63,6 +72,11 @@ pub struct UdpSocketController {
     io: tokio::net::UdpSocket,
     read: Arc<Wakers>,
     write: Arc<Wakers>,
+    max_segment_size: usize,
+    // 1 if GSO is not supported, or is disabled after a failed send
+    gso_segments: AtomicUsize,
+    // None if GRO is not supported
+    gro: Option<Mutex<GroBuffer>>,
 }
 
 impl UdpSocketController {
@@ -76,11 +90,20 @@ impl UdpSocketController {
         let socket = Socket::new(domain, Type::DGRAM, None)?;
         socket.set_nonblocking(true)?;
         Self::config(&socket, addr)?;
+        let gso_segments = Self::max_gso_segments(&socket);
+        let gro = Self::enable_gro(&socket).then(|| Mutex::new(GroBuffer::new()));
         let io = tokio::net::UdpSocket::from_std(socket.into())?;
+        let max_segment_size = match addr {
+            SocketAddr::V4(_) => ETHERNET_MTU - 20 - 8,
+            SocketAddr::V6(_) => ETHERNET_MTU - 40 - 8,
+        };
         let usc = Self {
             io,
             read: Default::default(),
             write: Default::default(),
+            max_segment_size,
+            gso_segments: AtomicUsize::new(gso_segments),
+            gro,
         };
         Ok(usc)
     }
@@ -89,6 +112,21 @@ impl UdpSocketController {
         self.io.local_addr()
     }
 
+    /// The largest UDP payload that fits in an Ethernet frame without IP fragmentation.
+    pub fn max_segment_size(&self) -> usize {
+        self.max_segment_size
+    }
+
+    /// The maximum number of datagrams sent in a single GSO buffer, 1 if GSO is unavailable.
+    pub fn gso_segments(&self) -> usize {
+        self.gso_segments.load(Ordering::Relaxed)
+    }
+
+    /// Whether datagrams from the same peer are received coalesced with GRO.
+    pub fn gro_enabled(&self) -> bool {
+        self.gro.is_some()
+    }
+
     pub fn poll_send_ready(&self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
         self.write.register(cx.waker());
         self.io
@@ -101,6 +139,10 @@ impl UdpSocketController {
             .poll_recv_ready(&mut Context::from_waker(&self.read.clone().into()))
     }
 
+    /// Sends the datagrams in `bufs` to `hdr.dst`, returns the number of datagrams sent.
+    ///
+    /// With GSO, consecutive datagrams of `hdr.seg_size` bytes are sent as a single buffer,
+    /// `hdr.seg_size` must not be less than the length of any datagram in `bufs`.
     pub fn poll_send(
         &self,
         cx: &mut Context<'_>,
@@ -120,6 +162,10 @@ impl UdpSocketController {
         }
     }
 
+    /// Receives datagrams into `bufs`, returns the number of datagrams received.
+    ///
+    /// Datagrams coalesced by GRO are split apart, each of `bufs` holds a single datagram,
+    /// whose length is the `seg_size` of the corresponding header.
     pub fn poll_recv(
         &self,
         cx: &mut Context,
@@ -127,6 +173,14 @@ impl UdpSocketController {
         hdrs: &mut [DatagramHeader],
     ) -> Poll<io::Result<usize>> {
         loop {
+            // The datagrams split from the last GRO buffers must be taken before waiting for
+            // the socket to be readable again
+            if let Some(gro) = &self.gro {
+                let count = gro.lock().unwrap().split_into(bufs, hdrs);
+                if count > 0 {
+                    return Poll::Ready(Ok(count));
+                }
+            }
             ready!(self.poll_recv_ready(cx)?);
             let f = || self.recvmsg(bufs, hdrs);
             let ret = self.io.try_io(Interest::READABLE, f);
@@ -139,6 +193,67 @@ impl UdpSocketController {
     }
 }
 
+/// The buffers receiving datagrams with GRO, and the datagrams split from them that have not
+/// been returned yet.
+///
+/// The buffers are allocated on the first receive, sockets that only send never pay for them.
+#[derive(Debug)]
+struct GroBuffer {
+    buf: Vec<u8>,
+    pending: VecDeque<(Range<usize>, DatagramHeader)>,
+}
+
+impl GroBuffer {
+    fn new() -> Self {
+        Self {
+            buf: Vec::new(),
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// The buffers to receive the coalesced datagrams into.
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    fn bufs(&mut self) -> Vec<IoSliceMut<'_>> {
+        if self.buf.is_empty() {
+            self.buf = vec![0; GRO_BATCH_SIZE * GRO_BUFFER_SIZE];
+        }
+        self.buf
+            .chunks_mut(GRO_BUFFER_SIZE)
+            .map(IoSliceMut::new)
+            .collect()
+    }
+
+    /// Records `len` bytes received at `offset`, which are datagrams of `hdr.seg_size` bytes
+    /// except the last one.
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    fn push(&mut self, offset: usize, len: usize, hdr: DatagramHeader) {
+        let seg_size = (hdr.seg_size as usize).max(1);
+        for start in (offset..offset + len).step_by(seg_size) {
+            let end = (start + seg_size).min(offset + len);
+            let seg_size = (end - start) as u16;
+            self.pending
+                .push_back((start..end, DatagramHeader { seg_size, ..hdr }));
+        }
+    }
+
+    fn split_into(&mut self, bufs: &mut [IoSliceMut<'_>], hdrs: &mut [DatagramHeader]) -> usize {
+        let mut count = 0;
+        for (buf, hdr) in bufs.iter_mut().zip(hdrs.iter_mut()) {
+            let Some((range, datagram_hdr)) = self.pending.pop_front() else {
+                break;
+            };
+            let len = range.len().min(buf.len());
+            buf[..len].copy_from_slice(&self.buf[range.start..range.start + len]);
+            *hdr = DatagramHeader {
+                seg_size: len as u16,
+                ..datagram_hdr
+            };
+            count += 1;
+        }
+        count
+    }
+}
+
 #[derive(Default, Debug)]
 struct Wakers(Mutex<VecDeque<Waker>>);
 
@@ -163,6 +278,12 @@ impl Wake for Wakers {
 pub trait Io {
     fn config(io: &socket2::Socket, addr: SocketAddr) -> io::Result<()>;
 
+    /// Probes the maximum number of segments in a GSO buffer, 1 if GSO is not supported.
+    fn max_gso_segments(io: &socket2::Socket) -> usize;
+
+    /// Enables GRO on the socket, returns false if GRO is not supported.
+    fn enable_gro(io: &socket2::Socket) -> bool;
+
     fn sendmsg(&self, bufs: &[IoSlice<'_>], hdr: &DatagramHeader) -> io::Result<usize>;
 
     fn recvmsg(&self, bufs: &mut [IoSliceMut<'_>], hdr: &mut [DatagramHeader])
@@ -178,7 +299,7 @@ impl UdpSocketController {
         }
     }
 
-    pub fn receiver(&self) -> Receiver {
+    pub fn receiver(&self) -> Receiver<'_> {
         Receiver {
             usc: self,
             iovecs: (0..BATCH_SIZE)
@@ -229,3 +350,81 @@ impl Receiver<'_> {
         core::future::poll_fn(|cx| self.poll_recv(cx)).await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn send_and_recv_segments() {
+        let sender = UdpSocketController::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let receiver = UdpSocketController::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let dst = receiver.local_addr().unwrap();
+
+        // 20 full datagrams and a shorter one, coalesced by GSO and GRO if supported
+        let datagrams = (0..21u8)
+            .map(|i| vec![i; if i < 20 { 1200 } else { 500 }])
+            .collect::<Vec<_>>();
+        let bufs = datagrams
+            .iter()
+            .map(|datagram| IoSlice::new(datagram))
+            .collect::<Vec<_>>();
+        let hdr = DatagramHeader::new(sender.local_addr().unwrap(), dst, 64, None, 1200);
+        let sent = sender.send(&bufs, hdr).await.unwrap();
+        assert_eq!(sent, datagrams.len());
+
+        let mut receiver = receiver.receiver();
+        let mut received = Vec::new();
+        while received.len() < datagrams.len() {
+            let count = receiver.recv().await.unwrap();
+            for (buf, hdr) in receiver.iovecs.iter().zip(&receiver.headers).take(count) {
+                received.push(buf[..hdr.seg_size as usize].to_vec());
+            }
+        }
+        assert_eq!(received, datagrams);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn send_oversized_datagram() {
+        let sender = UdpSocketController::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let dst = "127.0.0.1:9".parse().unwrap();
+
+        // Larger than any UDP payload, the error is not hidden as a successful send
+        let datagram = vec![0; u16::MAX as usize];
+        let hdr = DatagramHeader::new(sender.local_addr().unwrap(), dst, 64, None, u16::MAX);
+        assert!(sender.send(&[IoSlice::new(&datagram)], hdr).await.is_err());
+    }
+
+    #[cfg(any(
+        target_os = "android",
+        target_os = "linux",
+        target_os = "freebsd",
+        windows
+    ))]
+    #[tokio::test]
+    async fn send_and_recv_ecn() {
+        for addr in ["127.0.0.1:0", "[::1]:0"] {
+            let sender = UdpSocketController::bind(addr.parse().unwrap()).unwrap();
+            let receiver = UdpSocketController::bind(addr.parse().unwrap()).unwrap();
+            let dst = receiver.local_addr().unwrap();
+
+            // ECT(0) and CE
+            for ecn in [0b10, 0b11] {
+                let datagram = [ecn; 100];
+                let hdr =
+                    DatagramHeader::new(sender.local_addr().unwrap(), dst, 64, Some(ecn), 100);
+                let sent = sender.send(&[IoSlice::new(&datagram)], hdr).await.unwrap();
+                assert_eq!(sent, 1);
+            }
+
+            let mut receiver = receiver.receiver();
+            let mut received = Vec::new();
+            while received.len() < 2 {
+                let count = receiver.recv().await.unwrap();
+                received.extend(receiver.headers.iter().take(count).map(|hdr| hdr.ecn));
+            }
+            assert_eq!(received, [Some(0b10), Some(0b11)]);
+        }
+    }
+}
@@ -2,6 +2,7 @@ use std::{
     io::{self, IoSlice},
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6},
     os::fd::{AsFd, AsRawFd},
+    sync::atomic::Ordering,
 };
 
 use nix::{
@@ -17,6 +18,14 @@ use crate::{DEFAULT_TTL, DatagramHeader, Io, UdpSocketController};
 
 const OPTION_ON: bool = true;
 const OPTION_OFF: bool = false;
+// The maximum size of a GSO buffer, fits the UDP payload of both IPv4 and IPv6
+#[cfg(any(
+    target_os = "android",
+    target_os = "linux",
+    target_os = "freebsd",
+    target_os = "netbsd"
+))]
+const MAX_GSO_SIZE: usize = u16::MAX as usize - 40 - 8;
 
 impl Io for UdpSocketController {
     fn config(socket: &Socket, addr: SocketAddr) -> io::Result<()> {
@@ -37,12 +46,26 @@ impl Io for UdpSocketController {
                 ))]
                 nix::sys::socket::setsockopt(&io, sockopt::Ipv4Ttl, &DEFAULT_TTL)?;
                 nix::sys::socket::setsockopt(&io, sockopt::Ipv4PacketInfo, &OPTION_ON)?;
+                // The ECN codepoint is optional, the datagrams are received without it if failed
+                #[cfg(any(target_os = "android", target_os = "linux", target_os = "freebsd"))]
+                {
+                    _ = nix::sys::socket::setsockopt(&io, sockopt::IpRecvTos, &OPTION_ON);
+                }
             }
             SocketAddr::V6(_) => {
                 nix::sys::socket::setsockopt(&io, sockopt::Ipv6V6Only, &OPTION_OFF)?;
                 nix::sys::socket::setsockopt(&io, sockopt::Ipv6RecvPacketInfo, &OPTION_ON)?;
                 nix::sys::socket::setsockopt(&io, sockopt::Ipv6DontFrag, &OPTION_ON)?;
                 nix::sys::socket::setsockopt(&io, sockopt::Ipv6Ttl, &DEFAULT_TTL)?;
+                #[cfg(any(target_os = "android", target_os = "linux", target_os = "freebsd"))]
+                {
+                    _ = nix::sys::socket::setsockopt(&io, sockopt::Ipv6RecvTClass, &OPTION_ON);
+                }
+                // For the IPv4-mapped addresses of the dual-stack socket
+                #[cfg(any(target_os = "android", target_os = "linux"))]
+                {
+                    _ = nix::sys::socket::setsockopt(&io, sockopt::IpRecvTos, &OPTION_ON);
+                }
             }
         }
         if let Err(e) = socket.bind(&addr.into()) {
@@ -52,6 +75,33 @@ impl Io for UdpSocketController {
         Ok(())
     }
 
+    fn max_gso_segments(_socket: &Socket) -> usize {
+        // Setting a segment size succeeds only if the kernel supports UDP GSO (linux 4.18+),
+        // it is reset to 0 since the segment size is given by each send instead
+        #[cfg(all(target_os = "linux", feature = "gso"))]
+        {
+            let io = _socket.as_fd();
+            let seg_size = crate::ETHERNET_MTU as libc::c_int;
+            if nix::sys::socket::setsockopt(&io, sockopt::UdpGsoSegment, &seg_size).is_ok()
+                && nix::sys::socket::setsockopt(&io, sockopt::UdpGsoSegment, &0).is_ok()
+            {
+                // UDP_MAX_SEGMENTS of the kernels supporting GSO, raised to 128 since linux 6.9
+                return 64;
+            }
+        }
+        1
+    }
+
+    fn enable_gro(_socket: &Socket) -> bool {
+        #[cfg(all(target_os = "linux", feature = "gro"))]
+        if nix::sys::socket::setsockopt(&_socket.as_fd(), sockopt::UdpGroSegment, &OPTION_ON)
+            .is_ok()
+        {
+            return true;
+        }
+        false
+    }
+
     #[cfg(any(
         target_os = "android",
         target_os = "linux",
@@ -65,51 +115,94 @@ impl Io for UdpSocketController {
         };
 
         use super::BATCH_SIZE;
-        let slices: Vec<_> = buffers
-            .iter()
-            .take(BATCH_SIZE)
-            .map(std::slice::from_ref)
-            .collect();
+        // Each message is a GSO buffer of datagrams, or a single datagram without GSO
+        let gso_segments = self.gso_segments();
+        let mut messages = Vec::with_capacity(BATCH_SIZE);
+        let mut rest = buffers;
+        while !rest.is_empty() && messages.len() < BATCH_SIZE {
+            let len = Self::gso_batch_len(rest, hdr.seg_size as usize, gso_segments);
+            let (message, remain) = rest.split_at(len);
+            messages.push(message);
+            rest = remain;
+        }
 
-        let batch_size = slices.len();
+        let batch_size = messages.len();
         if batch_size == 0 {
             return Ok(0);
         }
-        #[cfg(feature = "gso")]
-        let (cmsgs, space) = (
-            vec![nix::sys::socket::ControlMessage::UdpGsoSegments(
+        // A single datagram is sent without segmentation, so that a PMTU probe larger than
+        // the MTU of the network device fails with EMSGSIZE rather than EINVAL
+        let gso = messages.iter().any(|message| message.len() > 1);
+        #[allow(unused_mut)]
+        let mut cmsgs = Vec::with_capacity(2);
+        #[cfg(all(target_os = "linux", feature = "gso"))]
+        if gso {
+            cmsgs.push(nix::sys::socket::ControlMessage::UdpGsoSegments(
                 &hdr.seg_size,
-            )],
-            Some(cmsg_space!(libc::c_int)),
-        );
-        #[cfg(not(feature = "gso"))]
-        let (cmsgs, space) = (Vec::new(), None);
+            ));
+        }
+        // The ECN codepoint is carried in the ToS byte for IPv4, including the IPv4-mapped
+        // addresses of the dual-stack socket, and in the Traffic Class byte for IPv6
+        #[cfg(any(target_os = "android", target_os = "linux", target_os = "freebsd"))]
+        let (tos, tclass) = (hdr.ecn.unwrap_or(0), hdr.ecn.unwrap_or(0) as libc::c_int);
+        #[cfg(any(target_os = "android", target_os = "linux", target_os = "freebsd"))]
+        if hdr.ecn.is_some() {
+            let is_ipv4 = match hdr.dst {
+                SocketAddr::V4(_) => true,
+                SocketAddr::V6(v6) => v6.ip().to_ipv4_mapped().is_some(),
+            };
+            cmsgs.push(match is_ipv4 {
+                true => nix::sys::socket::ControlMessage::Ipv4Tos(&tos),
+                false => nix::sys::socket::ControlMessage::Ipv6TClass(&tclass),
+            });
+        }
+        // The control messages must fill the buffer exactly, or the kernel rejects them
+        let space = match cmsgs.len() {
+            0 => None,
+            1 => Some(cmsg_space!(libc::c_int)),
+            _ => Some(cmsg_space!(libc::c_int, libc::c_int)),
+        };
 
         macro_rules! send_batch {
             ($ty:ty, $addr:expr) => {{
                 let sock_addr = <$ty>::from($addr);
-                let addrs = vec![Some(sock_addr); BATCH_SIZE];
-                let mut data = MultiHeaders::<$ty>::preallocate(BATCH_SIZE, space);
-                match sendmmsg(
+                let addrs = vec![Some(sock_addr); batch_size];
+                let mut data = MultiHeaders::<$ty>::preallocate(batch_size, space);
+                sendmmsg(
                     self.io.as_raw_fd(),
                     &mut data,
-                    &slices,
+                    &messages,
                     &addrs,
                     &cmsgs,
                     MsgFlags::empty(),
-                ) {
-                    Ok(ret) => Ok(ret.count()),
-                    Err(e @ (Errno::EINTR | Errno::EAGAIN | Errno::ENOBUFS)) => {
-                        Err(io::Error::new(io::ErrorKind::WouldBlock, e))
-                    }
-                    Err(e) => Err(e.into()),
-                }
+                )
+                .map(|ret| ret.count())
             }};
         }
 
-        match hdr.dst {
+        let ret = match hdr.dst {
             SocketAddr::V4(v4) => send_batch!(SockaddrIn, v4),
             SocketAddr::V6(v6) => send_batch!(SockaddrIn6, v6),
+        };
+        match ret {
+            Ok(sent) => Ok(messages[..sent].iter().map(|message| message.len()).sum()),
+            Err(e @ (Errno::EINTR | Errno::EAGAIN | Errno::ENOBUFS)) => {
+                Err(io::Error::new(io::ErrorKind::WouldBlock, e))
+            }
+            // The network device cannot checksum the GSO buffer, or the segments exceed its MTU,
+            // send the datagrams one by one
+            Err(e @ (Errno::EIO | Errno::EMSGSIZE)) if gso => {
+                if self.gso_segments.swap(1, Ordering::Relaxed) > 1 {
+                    tracing::warn!("   Cause by: UDP GSO is not usable, {e}");
+                }
+                self.sendmsg(buffers, hdr)
+            }
+            // The kernel may reject the ECN codepoint, send the datagrams without it
+            Err(Errno::EINVAL) if hdr.ecn.is_some() => {
+                tracing::debug!("   Cause by: failing to set the ECN codepoint");
+                self.sendmsg(buffers, &DatagramHeader { ecn: None, ..*hdr })
+            }
+            Err(e) => Err(e.into()),
         }
     }
 
@@ -169,56 +262,20 @@ impl Io for UdpSocketController {
         bufs: &mut [std::io::IoSliceMut<'_>],
         recv_hdrs: &mut [DatagramHeader],
     ) -> io::Result<usize> {
-        use nix::sys::socket::{MsgFlags, recvmmsg};
-
-        use super::BATCH_SIZE;
-        let mut msgs: Vec<_> = bufs
-            .iter_mut()
-            .map(|buf| [std::io::IoSliceMut::new(&mut buf[..])])
-            .collect();
-
-        let cmsg_buffer = cmsg_space!(libc::in_pktinfo, libc::in6_pktinfo, libc::c_int);
-        let mut data = nix::sys::socket::MultiHeaders::<SockaddrStorage>::preallocate(
-            BATCH_SIZE,
-            Some(cmsg_buffer),
-        );
-
-        let res = match recvmmsg(
-            self.io.as_raw_fd(),
-            &mut data,
-            &mut msgs,
-            MsgFlags::MSG_DONTWAIT,
-            None,
-        ) {
-            Ok(results) => results.collect::<Vec<_>>(),
-            Err(e) => {
-                if matches!(e, nix::errno::Errno::EAGAIN | nix::errno::Errno::EINTR) {
-                    return Err(io::Error::new(io::ErrorKind::WouldBlock, e));
-                }
-                return Err(e.into());
+        if let Some(gro) = &self.gro {
+            let mut gro = gro.lock().unwrap();
+            let datagrams = self.recv_batch(&mut gro.bufs(), recv_hdrs)?;
+            for (index, (hdr, len)) in datagrams.into_iter().enumerate() {
+                gro.push(index * crate::GRO_BUFFER_SIZE, len, hdr);
             }
-        };
-
-        let local_port = self.local_addr()?.port();
-        let mut count = 0;
-
-        for recv_msg in res {
-            let src_addr = recv_msg.address.unwrap().to_socketaddr();
-            let mut recv_hdr = DatagramHeader {
-                src: src_addr,
-                dst: recv_hdrs[count].dst,
-                ttl: 0,
-                ecn: None,
-                seg_size: recv_msg.bytes as u16,
-            };
-            for cmsg in recv_msg.cmsgs().unwrap() {
-                parse_cmsg(cmsg, &mut recv_hdr);
-            }
-            recv_hdr.dst.set_port(local_port);
-            recv_hdrs[count] = recv_hdr;
-            count += 1;
+            return Ok(gro.split_into(bufs, recv_hdrs));
         }
 
+        let datagrams = self.recv_batch(bufs, recv_hdrs)?;
+        let count = datagrams.len();
+        for (recv_hdr, (hdr, _len)) in recv_hdrs.iter_mut().zip(datagrams) {
+            *recv_hdr = hdr;
+        }
         Ok(count)
     }
 
@@ -265,6 +322,100 @@ impl Io for UdpSocketController {
     }
 }
 
+#[cfg(any(
+    target_os = "android",
+    target_os = "linux",
+    target_os = "freebsd",
+    target_os = "netbsd"
+))]
+impl UdpSocketController {
+    /// Returns the number of datagrams at the front of `bufs` that can be sent in a single GSO
+    /// buffer: all of them are `seg_size` bytes except the last one, which may be shorter.
+    fn gso_batch_len(bufs: &[IoSlice<'_>], seg_size: usize, max_segments: usize) -> usize {
+        let mut count = 0;
+        let mut size = 0;
+        for buf in bufs.iter().take(max_segments) {
+            if count > 0
+                && (bufs[count - 1].len() != seg_size
+                    || buf.len() > seg_size
+                    || size + buf.len() > MAX_GSO_SIZE)
+            {
+                break;
+            }
+            count += 1;
+            size += buf.len();
+        }
+        count
+    }
+
+    /// Receives a batch of messages, returns the header and the received length of each one.
+    ///
+    /// With GRO, a message may contain multiple datagrams, and the `seg_size` of its header is
+    /// the size of each datagram but the last one.
+    fn recv_batch(
+        &self,
+        bufs: &mut [std::io::IoSliceMut<'_>],
+        recv_hdrs: &[DatagramHeader],
+    ) -> io::Result<Vec<(DatagramHeader, usize)>> {
+        use nix::sys::socket::{MsgFlags, recvmmsg};
+
+        use super::BATCH_SIZE;
+        let mut msgs: Vec<_> = bufs
+            .iter_mut()
+            .take(recv_hdrs.len())
+            .map(|buf| [std::io::IoSliceMut::new(&mut buf[..])])
+            .collect();
+
+        let cmsg_buffer = cmsg_space!(
+            libc::in_pktinfo,
+            libc::in6_pktinfo,
+            libc::c_int,
+            libc::c_int
+        );
+        let mut data = nix::sys::socket::MultiHeaders::<SockaddrStorage>::preallocate(
+            BATCH_SIZE,
+            Some(cmsg_buffer),
+        );
+
+        let res = match recvmmsg(
+            self.io.as_raw_fd(),
+            &mut data,
+            &mut msgs,
+            MsgFlags::MSG_DONTWAIT,
+            None,
+        ) {
+            Ok(results) => results.collect::<Vec<_>>(),
+            Err(e) => {
+                if matches!(e, nix::errno::Errno::EAGAIN | nix::errno::Errno::EINTR) {
+                    return Err(io::Error::new(io::ErrorKind::WouldBlock, e));
+                }
+                return Err(e.into());
+            }
+        };
+
+        let local_port = self.local_addr()?.port();
+        let mut datagrams = Vec::with_capacity(res.len());
+
+        for recv_msg in res {
+            let src_addr = recv_msg.address.unwrap().to_socketaddr();
+            let mut recv_hdr = DatagramHeader {
+                src: src_addr,
+                dst: recv_hdrs[datagrams.len()].dst,
+                ttl: 0,
+                ecn: None,
+                seg_size: recv_msg.bytes as u16,
+            };
+            for cmsg in recv_msg.cmsgs().unwrap() {
+                parse_cmsg(cmsg, &mut recv_hdr);
+            }
+            recv_hdr.dst.set_port(local_port);
+            datagrams.push((recv_hdr, recv_msg.bytes));
+        }
+
+        Ok(datagrams)
+    }
+}
+
 fn parse_cmsg(cmsg: ControlMessageOwned, hdr: &mut DatagramHeader) {
     match cmsg {
         ControlMessageOwned::Ipv4PacketInfo(pktinfo) => {
@@ -275,6 +426,12 @@ fn parse_cmsg(cmsg: ControlMessageOwned, hdr: &mut DatagramHeader) {
             let ip = IpAddr::V6(Ipv6Addr::from(pktinfo6.ipi6_addr.s6_addr));
             hdr.dst.set_ip(ip);
         }
+        #[cfg(target_os = "linux")]
+        ControlMessageOwned::UdpGroSegments(seg_size) => hdr.seg_size = seg_size as u16,
+        #[cfg(any(target_os = "android", target_os = "linux", target_os = "freebsd"))]
+        ControlMessageOwned::Ipv4Tos(tos) => hdr.ecn = Some(tos & 0b11),
+        #[cfg(any(target_os = "android", target_os = "linux", target_os = "freebsd"))]
+        ControlMessageOwned::Ipv6TClass(tclass) => hdr.ecn = Some(tclass as u8 & 0b11),
         _ => {}
     }
 }
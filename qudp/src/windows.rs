@@ -51,6 +51,14 @@ impl Io for UdpSocketController {
         Ok(())
     }
 
+    fn max_gso_segments(_socket: &Socket) -> usize {
+        1
+    }
+
+    fn enable_gro(_socket: &Socket) -> bool {
+        false
+    }
+
     fn sendmsg(
         &self,
         bufs: &[std::io::IoSlice<'_>],